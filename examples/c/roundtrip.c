@@ -0,0 +1,61 @@
+/*
+ * Writes and reads a file using the C interface.
+ *
+ *     cargo rustc --features ffi --crate-type staticlib
+ *     cc examples/c/roundtrip.c -Iinclude -Ltarget/debug -lbinpool -lpthread -ldl -lm -o roundtrip
+ *     ./roundtrip
+ */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "binpool.h"
+
+/* Type format of `[f32; 3]`. */
+#define F32_VEC3 binpool_format_for(BINPOOL_TYPE_F32, 1, 3)
+#define POSITION 1
+
+static int on_block(
+    void *user_data,
+    uint16_t type_format,
+    uint16_t property_id,
+    uint64_t offset,
+    const void *data,
+    uint64_t len_bytes
+) {
+    float *out = user_data;
+    if (type_format != F32_VEC3 || property_id != POSITION) return 1;
+    memcpy(out + offset * 3, data, len_bytes);
+    return BINPOOL_OK;
+}
+
+int main(void) {
+    const char *path = "roundtrip.pool";
+    float pos[6] = {1, 2, 3, 4, 5, 6};
+    float out[6] = {0};
+    int i;
+
+    BinpoolWriter *w = binpool_writer_open_path(path);
+    if (!w) {
+        fprintf(stderr, "%s\n", binpool_last_error_message());
+        return 1;
+    }
+    if (binpool_write_array(w, F32_VEC3, POSITION, 0, pos, sizeof(pos)) != BINPOOL_OK ||
+        binpool_finish(w) != BINPOOL_OK) {
+        fprintf(stderr, "%s\n", binpool_last_error_message());
+        return 1;
+    }
+
+    if (binpool_read_path(path, on_block, out) != BINPOOL_OK) {
+        fprintf(stderr, "%s\n", binpool_last_error_message());
+        return 1;
+    }
+    for (i = 0; i < 6; i++) {
+        if (out[i] != pos[i]) {
+            fprintf(stderr, "mismatch at %d\n", i);
+            return 1;
+        }
+    }
+    printf("ok\n");
+    return 0;
+}
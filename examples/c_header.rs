@@ -0,0 +1,18 @@
+extern crate binpool;
+
+use binpool::generate_c_header;
+use std::fs;
+
+const PATH: &str = "include/binpool_format.h";
+
+fn main() {
+    let header = generate_c_header();
+    if std::env::args().any(|arg| arg == "--check") {
+        let current = fs::read_to_string(PATH).unwrap();
+        assert!(current == header, "{} is out of date", PATH);
+        println!("{} is up to date", PATH);
+    } else {
+        fs::write(PATH, &header).unwrap();
+        println!("wrote {}", PATH);
+    }
+}
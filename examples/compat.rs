@@ -0,0 +1,15 @@
+extern crate binpool;
+
+use binpool::compat;
+
+const DIR: &str = "assets/compat";
+
+fn main() {
+    if std::env::args().any(|arg| arg == "--regenerate") {
+        for name in compat::regenerate(DIR).unwrap() {
+            println!("added {}", name);
+        }
+    }
+    let n = compat::verify_all(DIR).unwrap();
+    println!("{} fixtures are compatible", n);
+}
@@ -18,7 +18,7 @@ fn scalar() {
     let mut file = File::create(filename).unwrap();
     let data: Vec<f32> = vec![1.0, 2.0, 3.0];
     Scalar::write_array(ARRAY_PROPERTY, &data, &mut file).unwrap();
-    (10 as u8).write_property(SINGLE_PROPERTY, &mut file).unwrap();
+    10_u8.write_property(SINGLE_PROPERTY, &mut file).unwrap();
     drop(file);
 
     let mut file = File::open(filename).unwrap();
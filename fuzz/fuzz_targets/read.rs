@@ -0,0 +1,76 @@
+//! Reads arbitrary bytes through every read path.
+//!
+//! Run with `cargo fuzz run read`.
+
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate binpool;
+
+use binpool::*;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    // Limits keep hostile offsets from allocating without bound.
+    let limits = Limits {
+        max_block_bytes: Some(1 << 20),
+        max_instances_per_property: Some(1 << 16),
+        ..Limits::default()
+    };
+
+    let mut r = PoolReader::new(Cursor::new(data)).limits(limits).unknown(Unknown::Skip);
+    for id in 0..8 {r.register(id)}
+    while let Ok(Some((state, ty, prop))) = r.next_property() {
+        let res = match prop % 4 {
+            0 => {
+                let mut v: Vec<f32> = vec![];
+                Scalar::read_array(state, ty, &mut v, &mut r)
+            }
+            1 => {
+                let mut v: Vec<[u8; 3]> = vec![];
+                Vector::read_array(state, ty, &mut v, &mut r)
+            }
+            2 => {
+                let mut v: Vec<[[i16; 2]; 3]> = vec![];
+                Matrix::read_array(state, ty, &mut v, &mut r)
+            }
+            _ => {
+                let mut x = 0_u64;
+                x.read_property(state, ty, &mut r)
+            }
+        };
+        if res.is_err() {break}
+    }
+
+    let mut frames = FrameReader::with_time(Cursor::new(data), 1).limits(limits);
+    while let Ok(Some(frame)) = frames.next_frame() {
+        let mut v: Vec<u32> = vec![];
+        let _ = frame.read_array(2, &mut v);
+    }
+
+    let _ = validate(&mut Cursor::new(data), &ValidateOptions {
+        frame_marker: Some(1),
+        limits,
+        ..ValidateOptions::default()
+    });
+    if let Ok(index) = Index::build_with_limits(&mut Cursor::new(data), 1, Some(1), limits) {
+        let _ = index.seek_time(0.5, &mut Cursor::new(data));
+    }
+
+    let mut r = data;
+    if let Ok(Some((ty, _))) = codec::read_property_header(&mut r) {
+        let mut v: Vec<u16> = vec![];
+        let _ = codec::read_array_max(ty, 2, 1 << 16, &mut v, &mut r);
+    }
+
+    let mut out = vec![];
+    let mut r = PoolReader::new(Cursor::new(data)).limits(limits).unknown(Unknown::Skip);
+    let _ = canonicalize(&mut r, &mut out, Some(1));
+    let _ = downsample(&mut Cursor::new(data), &mut out, &DownsampleOptions {
+        frame_marker: Some(1),
+        frame_step: 2,
+        instance_step: 3,
+        limits,
+    });
+});
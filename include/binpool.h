@@ -0,0 +1,82 @@
+/*
+ * C interface for binpool.
+ *
+ * Build the library with the `ffi` feature, e.g.
+ *
+ *     cargo rustc --release --features ffi --crate-type staticlib
+ *
+ * Functions return BINPOOL_OK (0) on success, or an error code.
+ * Data is stored in little-endian format.
+ */
+
+#ifndef BINPOOL_H
+#define BINPOOL_H
+
+#include <stdint.h>
+
+#include "binpool_format.h"
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* No error. */
+#define BINPOOL_OK 0
+/* A null pointer was passed as argument. */
+#define BINPOOL_ERROR_NULL 1
+/* An IO error occured. */
+#define BINPOOL_ERROR_IO 2
+/* The data is not valid. */
+#define BINPOOL_ERROR_INVALID_DATA 3
+/* The callback stopped reading. */
+#define BINPOOL_ERROR_CALLBACK 4
+
+/* Writes blocks to a file. */
+typedef struct BinpoolWriter BinpoolWriter;
+
+/* Called for every block when reading. Returns BINPOOL_OK to continue reading. */
+typedef int (*BinpoolBlockCallback)(
+    void *user_data,
+    uint16_t type_format,
+    uint16_t property_id,
+    uint64_t offset,
+    const void *data,
+    uint64_t len_bytes
+);
+
+/* Returns the message of the last error on the calling thread. */
+const char *binpool_last_error_message(void);
+
+/* Creates a file and opens a writer. Returns NULL on failure. */
+BinpoolWriter *binpool_writer_open_path(const char *path);
+
+/* Opens a writer on a file descriptor, taking ownership of it (Unix only). */
+BinpoolWriter *binpool_writer_open_fd(int fd);
+
+/* Writes a property with a single block. */
+int binpool_write_array(
+    BinpoolWriter *writer,
+    uint16_t type_format,
+    uint16_t property_id,
+    uint64_t offset,
+    const void *data,
+    uint64_t len_bytes
+);
+
+/* Writes end of stream and closes the writer. */
+int binpool_finish(BinpoolWriter *writer);
+
+/* Closes the writer without writing end of stream. */
+void binpool_writer_close(BinpoolWriter *writer);
+
+/* Reads a file, calling the callback for every block. */
+int binpool_read_path(const char *path, BinpoolBlockCallback callback, void *user_data);
+
+/* Reads from a file descriptor, calling the callback for every block (Unix only). */
+int binpool_read_fd(int fd, BinpoolBlockCallback callback, void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
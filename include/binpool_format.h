@@ -0,0 +1,84 @@
+/*
+ * Constants of the binpool format.
+ *
+ * Generated by `cargo run --example c_header`, do not edit.
+ */
+
+#ifndef BINPOOL_FORMAT_H
+#define BINPOOL_FORMAT_H
+
+#include <stdint.h>
+
+/* The maximum number of rows or columns of a built-in type format. */
+#define BINPOOL_SIZE 80
+/* The number of scalar types. */
+#define BINPOOL_TYPES 10
+/* The first custom format. */
+#define BINPOOL_OFFSET_CUSTOM_FORMAT 64001
+/* The first custom format reserved for the crate. */
+#define BINPOOL_RESERVED_FORMAT_START 0xff00
+/* The first property id reserved for the crate. */
+#define BINPOOL_RESERVED_PROPERTY_START 0xff00
+
+/* Scalar types, by type id. */
+enum BinpoolType {
+    BINPOOL_TYPE_U8 = 0,
+    BINPOOL_TYPE_U16 = 1,
+    BINPOOL_TYPE_U32 = 2,
+    BINPOOL_TYPE_U64 = 3,
+    BINPOOL_TYPE_I8 = 4,
+    BINPOOL_TYPE_I16 = 5,
+    BINPOOL_TYPE_I32 = 6,
+    BINPOOL_TYPE_I64 = 7,
+    BINPOOL_TYPE_F32 = 8,
+    BINPOOL_TYPE_F64 = 9,
+};
+
+/* Returns the size in bytes of a scalar type, or 0 for an unknown type. */
+static inline uint64_t binpool_type_size(enum BinpoolType type) {
+    switch (type) {
+    case BINPOOL_TYPE_U8: return 1;
+    case BINPOOL_TYPE_U16: return 2;
+    case BINPOOL_TYPE_U32: return 4;
+    case BINPOOL_TYPE_U64: return 8;
+    case BINPOOL_TYPE_I8: return 1;
+    case BINPOOL_TYPE_I16: return 2;
+    case BINPOOL_TYPE_I32: return 4;
+    case BINPOOL_TYPE_I64: return 8;
+    case BINPOOL_TYPE_F32: return 4;
+    case BINPOOL_TYPE_F64: return 8;
+    default: return 0;
+    }
+}
+
+/*
+ * Returns the type format of a matrix, scalars having 1x1 and vectors 1xN,
+ * or 0 if the dimensions are not supported.
+ */
+static inline uint16_t binpool_format_for(enum BinpoolType type, uint8_t rows, uint8_t cols) {
+    if ((unsigned)type >= BINPOOL_TYPES || rows == 0 || cols == 0 ||
+        rows > BINPOOL_SIZE || cols > BINPOOL_SIZE) {
+        return 0;
+    }
+    return (uint16_t)(1 + (unsigned)type * BINPOOL_SIZE * BINPOOL_SIZE +
+        (rows - 1) * BINPOOL_SIZE + (cols - 1));
+}
+
+/*
+ * Decodes a built-in type format into type, rows and columns.
+ * Returns 1 for built-in type formats, or 0 for end of stream and custom formats.
+ */
+static inline int binpool_info(
+    uint16_t format, enum BinpoolType *type, uint8_t *rows, uint8_t *cols
+) {
+    if (format == 0 || format >= BINPOOL_OFFSET_CUSTOM_FORMAT) {
+        return 0;
+    }
+    format -= 1;
+    *type = (enum BinpoolType)(format / (BINPOOL_SIZE * BINPOOL_SIZE));
+    *rows = (uint8_t)((format % (BINPOOL_SIZE * BINPOOL_SIZE)) / BINPOOL_SIZE + 1);
+    *cols = (uint8_t)(format % BINPOOL_SIZE + 1);
+    return 1;
+}
+
+#endif
@@ -0,0 +1,137 @@
+//! Aliases of property ids.
+//!
+//! When an application changes the id of a property,
+//! older streams can be read by declaring the old ids as aliases of the new one.
+
+use std::io;
+
+use endian::EndianReader;
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY};
+use State;
+
+/// Determines what happens when a property and its alias appear in the same frame.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum AliasConflict {
+    /// Return an error.
+    #[default]
+    Error,
+    /// Keep the blocks of the new property id.
+    PreferNew,
+    /// Keep the blocks of the old property ids.
+    PreferOld,
+}
+
+/// Maps old property ids onto new ones.
+///
+/// ```ignore
+/// let mut aliases = Aliases::new().on_conflict(AliasConflict::PreferNew);
+/// aliases.alias(VELOCITY, &[OLD_VELOCITY])?;
+/// let mut reader = FrameReader::new(file, TIME).aliases(aliases);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Aliases {
+    aliases: Vec<(u16, u16)>,
+    conflict: AliasConflict,
+}
+
+impl Aliases {
+    /// Creates an empty alias table.
+    pub fn new() -> Aliases {
+        Aliases::default()
+    }
+
+    /// Sets what happens when a property and its alias appear in the same frame.
+    ///
+    /// The default is `AliasConflict::Error`.
+    pub fn on_conflict(mut self, policy: AliasConflict) -> Aliases {
+        self.conflict = policy;
+        self
+    }
+
+    /// Returns what happens when a property and its alias appear in the same frame.
+    pub fn conflict(&self) -> AliasConflict {
+        self.conflict
+    }
+
+    /// Declares old property ids as aliases of a new property id.
+    ///
+    /// Returns an error if an old id is already an alias,
+    /// or if an id is used both as old and new id.
+    pub fn alias(&mut self, property_id: u16, old: &[u16]) -> io::Result<()> {
+        for &id in old {
+            if id == property_id || self.is_alias(id) || self.is_alias(property_id) ||
+               self.aliases.iter().any(|&(_, new)| new == id) {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+            self.aliases.push((id, property_id));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the property id is an alias of another property id.
+    pub fn is_alias(&self, property_id: u16) -> bool {
+        self.aliases.iter().any(|&(old, _)| old == property_id)
+    }
+
+    /// Returns the new property id of a property id.
+    ///
+    /// Property ids that are not aliases are returned unchanged.
+    pub fn resolve(&self, property_id: u16) -> u16 {
+        self.aliases.iter()
+            .find(|&&(old, _)| old == property_id)
+            .map(|&(_, new)| new)
+            .unwrap_or(property_id)
+    }
+
+    /// Returns the old property ids of a property id.
+    pub fn aliases_of(&self, property_id: u16) -> impl Iterator<Item = u16> + '_ {
+        self.aliases.iter().filter(move |&&(_, new)| new == property_id).map(|&(old, _)| old)
+    }
+
+    /// Renames the blocks of a frame to new property ids.
+    ///
+    /// Conflicts are resolved when blocks of both a new property id
+    /// and one of its aliases are in the frame.
+    pub fn resolve_frame(&self, blocks: &mut Vec<RawBlock>) -> io::Result<()> {
+        for &(_, new) in &self.aliases {
+            let has_new = blocks.iter().any(|b| b.property_id == new);
+            let has_old = blocks.iter().any(|b| self.resolve(b.property_id) == new &&
+                                                b.property_id != new);
+            if !(has_new && has_old) {continue}
+            match self.conflict {
+                AliasConflict::Error => {
+                    return Err(Error::new(ErrorKind::InvalidData).with_property(new).into());
+                }
+                AliasConflict::PreferNew => {
+                    blocks.retain(|b| b.property_id == new || self.resolve(b.property_id) != new);
+                }
+                AliasConflict::PreferOld => blocks.retain(|b| b.property_id != new),
+            }
+        }
+        for block in blocks.iter_mut() {
+            block.property_id = self.resolve(block.property_id);
+        }
+        Ok(())
+    }
+
+    /// Copies a stream, renaming old property ids to new ones.
+    ///
+    /// This is used to normalize old streams.
+    /// Since there are no frames, conflicts are not resolved.
+    /// The file header of the source stream is skipped,
+    /// and properties are written little endian.
+    pub fn remap<R: io::Read, W: io::Write>(&self, r: &mut R, w: &mut W) -> io::Result<()> {
+        let r = &mut EndianReader::new(r);
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                raw::copy_property(state, ty, prop, r, &mut io::sink())?;
+                continue;
+            }
+            raw::copy_property(state, ty, self.resolve(prop), r, w)?;
+        }
+        State::new().end_type_formats(w)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Alive masks of particle sets that grow and shrink.
+//!
+//! When particles are created and destroyed, the data of destroyed particles
+//! stays in dense arrays until the instance id is reused.
+//! An alive mask is a property with a reserved format and property id,
+//! with one bit per instance, set for instances that are alive.
+//! Bits are packed with the lowest instance in the lowest bit.
+//! The offset instance id of a block is a multiple of 8,
+//! such that a block replaces whole bytes of the mask,
+//! and the mask is updated incrementally by writing the bytes that changed.
+//! Instances that were never written are dead.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use reserved::{ALIVE_FORMAT, ALIVE_PROPERTY};
+
+/// Stores which instances are alive.
+///
+/// When writing, the changes since the last write are tracked,
+/// such that `write_changes` writes only the bytes that changed:
+///
+/// ```ignore
+/// let mut alive = AliveMask::new();
+/// for t in 0..100 {
+///     w.begin_frame(t as f64)?;
+///     alive.kill(old);
+///     let id = alive.spawn();
+///     Vector::write_array(POSITION, &pos, &mut w)?;
+///     alive.write_changes(&mut w)?;
+/// }
+/// ```
+///
+/// When reading, `FrameReader::alive` returns the mask of the last frame read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AliveMask {
+    bits: Vec<u8>,
+    written: Option<Vec<u8>>,
+}
+
+impl AliveMask {
+    /// Creates an alive mask where all instances are dead.
+    pub fn new() -> AliveMask {
+        AliveMask::default()
+    }
+
+    /// Returns `true` if an instance is alive.
+    pub fn is_alive(&self, id: u64) -> bool {
+        match self.bits.get((id / 8) as usize) {
+            Some(byte) => byte & (1 << (id % 8)) != 0,
+            None => false,
+        }
+    }
+
+    /// Sets whether an instance is alive.
+    pub fn set_alive(&mut self, id: u64, val: bool) {
+        let i = (id / 8) as usize;
+        if i >= self.bits.len() {
+            if !val {return}
+            self.bits.resize(i + 1, 0);
+        }
+        if val {
+            self.bits[i] |= 1 << (id % 8);
+        } else {
+            self.bits[i] &= !(1 << (id % 8));
+        }
+    }
+
+    /// Marks the lowest dead instance as alive and returns its id.
+    ///
+    /// Instance ids of destroyed particles are reused.
+    pub fn spawn(&mut self) -> u64 {
+        let id = match self.bits.iter().position(|&byte| byte != 0xff) {
+            Some(i) => i as u64 * 8 + (!self.bits[i]).trailing_zeros() as u64,
+            None => self.bits.len() as u64 * 8,
+        };
+        self.set_alive(id, true);
+        id
+    }
+
+    /// Marks an instance as dead.
+    pub fn kill(&mut self, id: u64) {
+        self.set_alive(id, false);
+    }
+
+    /// Marks all instances as dead.
+    pub fn clear(&mut self) {
+        for byte in &mut self.bits {*byte = 0}
+    }
+
+    /// Returns the number of instances that are alive.
+    pub fn count(&self) -> u64 {
+        self.bits.iter().map(|byte| byte.count_ones() as u64).sum()
+    }
+
+    /// Returns an upper bound of the instances that are alive.
+    ///
+    /// This is a multiple of 8.
+    pub fn len(&self) -> u64 {
+        self.bits.len() as u64 * 8
+    }
+
+    /// Returns `true` if no instance is alive.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&byte| byte == 0)
+    }
+
+    /// Returns the ids of the instances that are alive, in increasing order.
+    pub fn ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.bits.iter().enumerate()
+            .filter(|&(_, &byte)| byte != 0)
+            .flat_map(|(i, &byte)| {
+                (0..8).filter(move |j| byte & (1 << j) != 0).map(move |j| i as u64 * 8 + j)
+            })
+    }
+
+    /// Zips an array with the mask, skipping the instances that are dead.
+    ///
+    /// Yields the instance id and item of every alive instance in the array.
+    pub fn zip<'a, T>(&'a self, items: &'a [T]) -> impl Iterator<Item = (u64, &'a T)> + 'a {
+        self.ids()
+            .take_while(move |&id| id < items.len() as u64)
+            .map(move |id| (id, &items[id as usize]))
+    }
+
+    /// Zips an array with the mask, skipping the instances that are dead.
+    ///
+    /// Yields the instance id and a mutable item of every alive instance in the array.
+    pub fn zip_mut<'a, T>(
+        &'a self,
+        items: &'a mut [T]
+    ) -> impl Iterator<Item = (u64, &'a mut T)> + 'a {
+        items.iter_mut().enumerate()
+            .map(|(id, item)| (id as u64, item))
+            .filter(move |&(id, _)| self.is_alive(id))
+    }
+
+    /// Writes the whole mask.
+    pub fn write<W: io::Write>(&mut self, w: &mut W) -> io::Result<()> {
+        RawBlock::write_property(ALIVE_FORMAT, ALIVE_PROPERTY, &[RawBlock {
+            type_format: ALIVE_FORMAT,
+            property_id: ALIVE_PROPERTY,
+            offset: 0,
+            data: self.bits.clone(),
+        }], w)?;
+        self.written = Some(self.bits.clone());
+        Ok(())
+    }
+
+    /// Writes the bytes of the mask that changed since the last write,
+    /// as one block for each range of changed bytes.
+    ///
+    /// The whole mask is written the first time.
+    /// Nothing is written when nothing changed.
+    pub fn write_changes<W: io::Write>(&mut self, w: &mut W) -> io::Result<()> {
+        let blocks = {
+            let written = match self.written {
+                None => return self.write(w),
+                Some(ref x) => x,
+            };
+            let changed = |i: usize| self.bits.get(i).unwrap_or(&0) != written.get(i).unwrap_or(&0);
+            let n = self.bits.len().max(written.len());
+            let mut blocks = vec![];
+            let mut i = 0;
+            while i < n {
+                if !changed(i) {
+                    i += 1;
+                    continue;
+                }
+                let start = i;
+                while i < n && changed(i) {i += 1}
+                let data = (start..i).map(|j| *self.bits.get(j).unwrap_or(&0)).collect();
+                blocks.push(RawBlock {
+                    type_format: ALIVE_FORMAT,
+                    property_id: ALIVE_PROPERTY,
+                    offset: start as u64 * 8,
+                    data,
+                });
+            }
+            blocks
+        };
+        if blocks.is_empty() {return Ok(())}
+        RawBlock::write_property(ALIVE_FORMAT, ALIVE_PROPERTY, &blocks, w)?;
+        self.written = Some(self.bits.clone());
+        Ok(())
+    }
+
+    /// Applies a block of the mask.
+    ///
+    /// Returns an error if the offset is not a multiple of 8.
+    pub fn apply(&mut self, block: &RawBlock) -> io::Result<()> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.type_format != ALIVE_FORMAT || !block.offset.is_multiple_of(8) {return Err(err())}
+        let start = block.offset / 8;
+        let end = match start.checked_add(block.data.len() as u64) {
+            Some(x) if x < usize::MAX as u64 => x as usize,
+            _ => return Err(err()),
+        };
+        let start = start as usize;
+        if end > self.bits.len() {self.bits.resize(end, 0)}
+        self.bits[start..end].copy_from_slice(&block.data);
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Assigning property ids by name.
+//!
+//! Names are stored in the stream as metadata of the properties, with the key `NAME`.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use endian::EndianReader;
+use error::{Error, ErrorKind};
+use metadata::{self, Metadata, NAME};
+use raw::{self, RawBlock};
+use reserved::{METADATA_FORMAT, METADATA_PROPERTY, RESERVED_PROPERTY_START};
+
+/// Assigns unique property ids to names.
+///
+/// Modules of an application ask for ids by name, such that ids never collide:
+///
+/// ```ignore
+/// let mut alloc = PropertyAllocator::read(&mut old_file)?;
+/// let position = alloc.get_or_assign("physics/position")?;
+/// let note = alloc.get_or_assign("annotations/note")?;
+/// alloc.write(&mut file)?;
+/// ```
+///
+/// Reserved property ids are never assigned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PropertyAllocator {
+    ids: BTreeMap<String, u16>,
+    names: BTreeMap<u16, String>,
+    /// All ids below are assigned.
+    first_free: u16,
+}
+
+impl PropertyAllocator {
+    /// Creates an empty allocator.
+    pub fn new() -> PropertyAllocator {
+        PropertyAllocator::default()
+    }
+
+    /// Returns the id of a name, assigning the lowest free id to a new name.
+    ///
+    /// Returns an error of kind `ErrorKind::PropertyIdsExhausted`
+    /// when all property ids below the reserved ids are assigned.
+    pub fn get_or_assign(&mut self, name: &str) -> io::Result<u16> {
+        if let Some(&id) = self.ids.get(name) {return Ok(id)}
+        let id = self.first_free;
+        if id >= RESERVED_PROPERTY_START {
+            return Err(Error::new(ErrorKind::PropertyIdsExhausted).into());
+        }
+        self.insert(name, id);
+        Ok(id)
+    }
+
+    /// Assigns a given id to a name.
+    ///
+    /// Returns an error of kind `ErrorKind::PropertyConflict`
+    /// if the id is assigned to another name, the name to another id,
+    /// or if the id is reserved.
+    pub fn assign(&mut self, name: &str, property_id: u16) -> io::Result<()> {
+        let same_name = self.ids.get(name).map(|&id| id == property_id);
+        let same_id = self.names.get(&property_id).map(|n| n == name);
+        match (same_name, same_id) {
+            (Some(true), _) => Ok(()),
+            (None, None) if property_id < RESERVED_PROPERTY_START => {
+                self.insert(name, property_id);
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorKind::PropertyConflict).with_property(property_id).into()),
+        }
+    }
+
+    fn insert(&mut self, name: &str, property_id: u16) {
+        self.ids.insert(name.into(), property_id);
+        self.names.insert(property_id, name.into());
+        while self.names.contains_key(&self.first_free) {
+            self.first_free += 1;
+        }
+    }
+
+    /// Returns the id of a name.
+    pub fn get(&self, name: &str) -> Option<u16> {
+        self.ids.get(name).cloned()
+    }
+
+    /// Returns the name of a property id.
+    pub fn name(&self, property_id: u16) -> Option<&str> {
+        self.names.get(&property_id).map(|n| &n[..])
+    }
+
+    /// Returns an iterator over ids and names, sorted by id.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.names.iter().map(|(&id, n)| (id, &n[..]))
+    }
+
+    /// Returns the number of names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns `true` if no names are assigned.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Writes the names as metadata.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let metadata: Vec<(u16, Metadata)> = self.iter()
+            .map(|(id, name)| (id, Metadata::new().with(NAME, name)))
+            .collect();
+        let entries: Vec<(u16, &Metadata)> = metadata.iter().map(|(id, m)| (*id, m)).collect();
+        metadata::write_metadata(&entries, w)
+    }
+
+    /// Creates an allocator from the names in metadata of properties.
+    ///
+    /// Returns an error if a name is used by several properties.
+    pub fn from_metadata(entries: &[(u16, Metadata)]) -> io::Result<PropertyAllocator> {
+        let mut alloc = PropertyAllocator::new();
+        for (id, m) in entries {
+            if let Some(name) = m.name() {
+                alloc.assign(name, *id)?;
+            }
+        }
+        Ok(alloc)
+    }
+
+    /// Reads the names in a stream, skipping other properties.
+    ///
+    /// When a property is renamed, the last name wins.
+    /// A stream starting with a header is read in the byte order set by the header.
+    pub fn read<R: io::Read>(r: &mut R) -> io::Result<PropertyAllocator> {
+        let r = &mut EndianReader::new(r);
+        let mut entries = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            if ty == METADATA_FORMAT && prop == METADATA_PROPERTY {
+                metadata::merge_metadata(&mut entries, metadata::read_metadata(state, ty, prop, r)?);
+            } else {
+                RawBlock::read_property(state, ty, prop, r)?;
+            }
+        }
+        PropertyAllocator::from_metadata(&entries)
+    }
+}
@@ -0,0 +1,226 @@
+//! Decoding a whole frame into one resettable arena.
+//!
+//! `FrameReader::next_frame_in` copies the blocks of a frame into a `FrameArena`
+//! and returns a `FrameView` that borrows the arena.
+//! The arena is reset when the next frame is read into it,
+//! which requires the view to be dropped first, so a view can not outlive its frame.
+//! Reading into two arenas alternately keeps the previous frame while reading the next.
+//!
+//! The arena keeps its memory between frames, so once it has grown to the largest frame,
+//! reading a frame does not allocate.
+
+use std::borrow::Cow;
+use std::io;
+
+use buffer_pool::BufferPool;
+use endian::Endian;
+use error::{Error, ErrorKind};
+use frame::{self, Frame};
+use mapped::{MappedBlock, MappedScalar};
+use raw::RawBlock;
+use read_write::{Array, Element, ReadOptions};
+
+/// A block stored in an arena.
+#[derive(Copy, Clone, Debug)]
+struct ArenaBlock {
+    type_format: u16,
+    property_id: u16,
+    offset: u64,
+    start: usize,
+    len: usize,
+}
+
+/// Storage for the blocks of one frame, reused between frames.
+///
+/// The data of every block starts at an address that is a multiple of 8,
+/// such that scalars can be borrowed with `FrameView::scalars`.
+///
+/// ```ignore
+/// let mut arenas = [FrameArena::new(), FrameArena::new()];
+/// let mut previous = None;
+/// for i in 0.. {
+///     let (a, b) = arenas.split_at_mut(1);
+///     let arena = if i % 2 == 0 {&mut a[0]} else {&mut b[0]};
+///     let view = match frames.next_frame_in(arena)? {
+///         Some(x) => x,
+///         None => break,
+///     };
+///     let pos = view.scalars::<f32>(POSITION)?;
+///     // Interpolate between `previous` and `view`.
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FrameArena {
+    data: Vec<u8>,
+    blocks: Vec<ArenaBlock>,
+    pool: BufferPool,
+}
+
+impl Default for FrameArena {
+    fn default() -> FrameArena {
+        FrameArena::new()
+    }
+}
+
+impl FrameArena {
+    /// Creates an empty arena.
+    pub fn new() -> FrameArena {
+        FrameArena::with_capacity(0)
+    }
+
+    /// Creates an arena with a capacity in bytes.
+    pub fn with_capacity(bytes: usize) -> FrameArena {
+        FrameArena {
+            data: Vec::with_capacity(bytes),
+            blocks: vec![],
+            pool: BufferPool::new(usize::MAX),
+        }
+    }
+
+    /// Returns the capacity in bytes.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Returns the number of bytes used by the frame in the arena.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns `true` if the arena contains no data.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Removes the frame in the arena, keeping the memory.
+    pub fn reset(&mut self) {
+        self.data.clear();
+        self.blocks.clear();
+    }
+
+    /// Returns the pool of block buffers used while reading into the arena.
+    pub(crate) fn pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// Copies the blocks of a frame into the arena and returns a view of them.
+    pub(crate) fn fill(&mut self, frame: &Frame) -> FrameView<'_> {
+        self.reset();
+        // Reserve all memory up front, such that the data does not move while aligning blocks.
+        let bytes = frame.blocks.iter().map(|b| b.data.len() + 7).sum::<usize>() + 7;
+        self.data.reserve(bytes);
+        let base = self.data.as_ptr() as usize;
+        for block in &frame.blocks {
+            let pad = (8 - (base + self.data.len()) % 8) % 8;
+            self.data.resize(self.data.len() + pad, 0);
+            self.blocks.push(ArenaBlock {
+                type_format: block.type_format,
+                property_id: block.property_id,
+                offset: block.offset,
+                start: self.data.len(),
+                len: block.data.len(),
+            });
+            self.data.extend_from_slice(&block.data);
+        }
+        FrameView {
+            number: frame.number,
+            time: frame.time,
+            blocks: &self.blocks,
+            data: &self.data,
+        }
+    }
+}
+
+/// The blocks of a frame, borrowed from a `FrameArena`.
+#[derive(Copy, Clone, Debug)]
+pub struct FrameView<'a> {
+    /// The frame number, starting at 0.
+    pub number: u64,
+    /// The time of the frame, if there is a time property.
+    pub time: Option<f64>,
+    blocks: &'a [ArenaBlock],
+    data: &'a [u8],
+}
+
+impl<'a> FrameView<'a> {
+    fn block(&self, b: &ArenaBlock) -> MappedBlock<'a> {
+        MappedBlock {
+            type_format: b.type_format,
+            property_id: b.property_id,
+            offset: b.offset,
+            data: &self.data[b.start..b.start + b.len],
+            endian: Endian::Little,
+        }
+    }
+
+    /// Returns `true` if the frame contains blocks of a property.
+    pub fn contains(&self, property_id: u16) -> bool {
+        self.blocks.iter().any(|b| b.property_id == property_id)
+    }
+
+    /// Returns an iterator over the blocks of the frame, in the order they were read.
+    pub fn blocks(&self) -> impl Iterator<Item = MappedBlock<'a>> + '_ {
+        self.blocks.iter().map(move |b| self.block(b))
+    }
+
+    /// Returns an iterator over the blocks of a property.
+    pub fn blocks_of(&self, property_id: u16) -> impl Iterator<Item = MappedBlock<'a>> + '_ {
+        self.blocks().filter(move |b| b.property_id == property_id)
+    }
+
+    /// Returns the scalars of a property written as one block, row by row for vectors and matrices.
+    ///
+    /// The data is borrowed from the arena on little endian platforms.
+    /// Returns `None` if the frame contains no blocks of the property,
+    /// and an error of kind `ErrorKind::InvalidData` if it contains more than one.
+    /// See `MappedBlock::scalars`.
+    pub fn scalars<T: MappedScalar>(&self, property_id: u16) -> io::Result<Option<Cow<'a, [T]>>> {
+        let mut blocks = self.blocks_of(property_id);
+        let block = match blocks.next() {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        if blocks.next().is_some() {
+            return Err(Error::new(ErrorKind::InvalidData).with_property(property_id).into());
+        }
+        block.scalars().map(Some)
+    }
+
+    /// Reads all blocks of a property into an array.
+    ///
+    /// See `Frame::read_array`.
+    pub fn read_array<T, A>(&self, property_id: u16, arr: &mut A) -> io::Result<bool>
+        where T: Element, A: Array<Item = T>
+    {
+        self.read_array_with(property_id, arr, &ReadOptions::default())
+    }
+
+    /// Reads all blocks of a property into an array, with options.
+    ///
+    /// See `Frame::read_array_with`.
+    pub fn read_array_with<T, A>(
+        &self,
+        property_id: u16,
+        arr: &mut A,
+        opts: &ReadOptions
+    ) -> io::Result<bool>
+        where T: Element, A: Array<Item = T>
+    {
+        let blocks = self.blocks_of(property_id).map(|b| (b.type_format, b.offset, b.data));
+        frame::read_blocks_into(property_id, blocks, arr, opts)
+    }
+
+    /// Copies the blocks of the frame.
+    pub fn to_frame(&self) -> Frame {
+        Frame {
+            number: self.number,
+            time: self.time,
+            blocks: self.blocks().map(|b| RawBlock {
+                type_format: b.type_format,
+                property_id: b.property_id,
+                offset: b.offset,
+                data: b.data.to_vec(),
+            }).collect(),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+//! Exporting to Apache Arrow, enabled with the `arrow` feature.
+//!
+//! A stream is exported as a table with one row per instance of every frame:
+//!
+//! ```ignore
+//! frame: u64, instance: u64, time: f64, one column per property
+//! ```
+//!
+//! Scalars are stored in columns of the scalar type,
+//! vectors and matrices in fixed size lists of their scalars, row by row.
+//! Columns are named by the names of the properties, as assigned by `PropertyAllocator`,
+//! or `property_<id>` for properties without a name.
+//! Every field has the property id in its metadata, with the key `binpool.property_id`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, SeekFrom};
+use std::sync::Arc;
+
+use arrow_array::{make_array, ArrayRef, RecordBatch};
+use arrow_buffer::{Buffer, NullBuffer};
+use arrow_data::ArrayData;
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+
+use frame::FrameReader;
+use metadata::{self, Metadata};
+use raw::{self, RawBlock};
+use reserved::{METADATA_FORMAT, METADATA_PROPERTY};
+use ring::{self, Keyframe};
+use Type;
+
+/// The key of the property id in the metadata of fields.
+pub const PROPERTY_ID_KEY: &str = "binpool.property_id";
+
+/// Options for exporting to Arrow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ArrowOptions {
+    /// The maximum number of rows per record batch.
+    pub batch_size: usize,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> ArrowOptions {
+        ArrowOptions {batch_size: 8192}
+    }
+}
+
+impl ArrowOptions {
+    /// Sets the maximum number of rows per record batch.
+    pub fn batch_size(mut self, val: usize) -> ArrowOptions {
+        self.batch_size = val;
+        self
+    }
+}
+
+/// Reads a stream as Arrow record batches.
+///
+/// The stream is read once to find the properties and their names,
+/// then frame by frame, such that at most one batch is kept in memory.
+/// Frames start with the time property.
+/// Every frame has the values of every property merged up to the frame, like `Player::tick`,
+/// such that properties written as changes have values in every frame.
+/// Data before the first frame belongs to every frame.
+///
+/// A frame has as many rows as the longest array in it, at least one.
+/// Values after the end of a shorter array are null.
+/// Properties with custom formats are not exported,
+/// and neither are blocks of a property with another format than the first one.
+///
+/// ```ignore
+/// let batches = export_arrow(&mut file, TIME, &ArrowOptions::default())?;
+/// let schema = batches.schema();
+/// for batch in batches {
+///     let batch = batch?;
+///     ...
+/// }
+/// ```
+pub fn export_arrow<'a, R: io::Read + io::Seek>(
+    r: &'a mut R,
+    time_property: u16,
+    opts: &ArrowOptions
+) -> io::Result<ArrowBatches<'a, R>> {
+    if opts.batch_size == 0 {return Err(io::ErrorKind::InvalidInput.into())}
+    let start = r.stream_position()?;
+    let mut formats: BTreeMap<u16, u16> = BTreeMap::new();
+    let mut entries: Vec<(u16, Metadata)> = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+        if ty == METADATA_FORMAT && prop == METADATA_PROPERTY {
+            metadata::merge_metadata(&mut entries, metadata::read_metadata(state, ty, prop, r)?);
+            continue;
+        }
+        RawBlock::read_property(state, ty, prop, r)?;
+        if prop != time_property && Type::info(ty).is_some() {
+            formats.entry(prop).or_insert(ty);
+        }
+    }
+    r.seek(SeekFrom::Start(start))?;
+
+    let name = |prop: u16, default: String| -> String {
+        entries.iter()
+            .find(|(id, _)| *id == prop)
+            .and_then(|(_, m)| m.name())
+            .map(|name| name.into())
+            .unwrap_or(default)
+    };
+    let with_id = |field: Field, prop: u16| {
+        let mut metadata = HashMap::new();
+        metadata.insert(PROPERTY_ID_KEY.to_string(), prop.to_string());
+        field.with_metadata(metadata)
+    };
+    let mut fields = vec![
+        Field::new("frame", DataType::UInt64, false),
+        Field::new("instance", DataType::UInt64, false),
+        with_id(Field::new(name(time_property, "time".into()), DataType::Float64, true), time_property),
+    ];
+    let mut columns = vec![];
+    for (&prop, &ty) in &formats {
+        let (scalar, rows, cols) = match Type::info(ty) {
+            Some(info) => info,
+            None => continue,
+        };
+        let scalar = data_type(scalar);
+        let components = rows as usize * cols as usize;
+        let data_type = if components == 1 {
+            scalar.clone()
+        } else {
+            let item = Arc::new(Field::new("item", scalar.clone(), false));
+            DataType::FixedSizeList(item, components as i32)
+        };
+        fields.push(with_id(Field::new(name(prop, format!("property_{}", prop)), data_type, true), prop));
+        columns.push(Column {
+            property_id: prop,
+            type_format: ty,
+            scalar,
+            components,
+            values: vec![],
+            valid: vec![],
+        });
+    }
+    Ok(ArrowBatches {
+        reader: FrameReader::with_time(r, time_property),
+        schema: Arc::new(Schema::new(fields)),
+        batch_size: opts.batch_size,
+        state: BTreeMap::new(),
+        columns,
+        frames: vec![],
+        instances: vec![],
+        times: vec![],
+        current: None,
+        done: false,
+    })
+}
+
+/// Writes a stream as an Arrow IPC file, batch by batch.
+///
+/// See `export_arrow` for the layout of the table.
+pub fn write_arrow_ipc<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: W,
+    time_property: u16,
+    opts: &ArrowOptions
+) -> io::Result<W> {
+    let batches = export_arrow(r, time_property, opts)?;
+    let mut writer = FileWriter::try_new(w, &batches.schema()).map_err(io::Error::other)?;
+    for batch in batches {
+        writer.write(&batch?).map_err(io::Error::other)?;
+    }
+    writer.finish().map_err(io::Error::other)?;
+    writer.into_inner().map_err(io::Error::other)
+}
+
+/// An iterator over the record batches of a stream, created by `export_arrow`.
+pub struct ArrowBatches<'a, R: 'a> {
+    reader: FrameReader<&'a mut R>,
+    schema: SchemaRef,
+    batch_size: usize,
+    state: BTreeMap<(u16, u16), Keyframe>,
+    columns: Vec<Column>,
+    frames: Vec<u64>,
+    instances: Vec<u64>,
+    times: Vec<Option<f64>>,
+    current: Option<Current>,
+    done: bool,
+}
+
+/// The frame whose rows are being added.
+struct Current {
+    frame: u64,
+    time: Option<f64>,
+    next: u64,
+    rows: u64,
+}
+
+/// The values of a property column in the current batch.
+struct Column {
+    property_id: u16,
+    type_format: u16,
+    scalar: DataType,
+    components: usize,
+    /// Little endian scalars, with zeroes for null rows.
+    values: Vec<u8>,
+    valid: Vec<bool>,
+}
+
+impl<'a, R: io::Read> ArrowBatches<'a, R> {
+    /// Returns the schema of the record batches.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    /// Reads the next frame into the state.
+    fn next_frame(&mut self) -> io::Result<bool> {
+        let first = self.reader.frames() == 0;
+        let frame = match self.reader.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(false),
+        };
+        if first {
+            ring::merge_blocks(&mut self.state, self.reader.preamble().to_vec())?;
+        }
+        ring::merge_blocks(&mut self.state, frame.blocks)?;
+        let mut rows = 1;
+        for column in &self.columns {
+            let key = (column.property_id, column.type_format);
+            if let Some(Keyframe::Items(data)) = self.state.get(&key) {
+                let size = raw::item_size(column.type_format).unwrap_or(1);
+                rows = rows.max(data.len() as u64 / size);
+            }
+        }
+        self.current = Some(Current {frame: frame.number, time: frame.time, next: 0, rows});
+        Ok(true)
+    }
+
+    /// Adds a row of the current frame.
+    fn push_row(&mut self, frame: u64, time: Option<f64>, instance: u64) {
+        self.frames.push(frame);
+        self.instances.push(instance);
+        self.times.push(time);
+        for column in &mut self.columns {
+            let size = raw::item_size(column.type_format).unwrap_or(1) as usize;
+            let item = match self.state.get(&(column.property_id, column.type_format)) {
+                Some(Keyframe::Items(data)) => {
+                    let start = instance as usize * size;
+                    data.get(start..start + size)
+                }
+                _ => None,
+            };
+            match item {
+                Some(item) => column.values.extend_from_slice(item),
+                None => column.values.resize(column.values.len() + size, 0),
+            }
+            column.valid.push(item.is_some());
+        }
+    }
+
+    /// Creates a record batch of the rows added.
+    fn finish_batch(&mut self) -> io::Result<RecordBatch> {
+        let frames: Vec<u64> = std::mem::take(&mut self.frames);
+        let instances: Vec<u64> = std::mem::take(&mut self.instances);
+        let times: Vec<Option<f64>> = std::mem::take(&mut self.times);
+        let mut arrays: Vec<ArrayRef> = vec![
+            Arc::new(arrow_array::UInt64Array::from(frames)),
+            Arc::new(arrow_array::UInt64Array::from(instances)),
+            Arc::new(arrow_array::Float64Array::from(times)),
+        ];
+        let fields = self.schema.fields().iter().skip(3);
+        for (column, field) in self.columns.iter_mut().zip(fields) {
+            arrays.push(column.finish(field.data_type())?);
+        }
+        RecordBatch::try_new(self.schema.clone(), arrays).map_err(io::Error::other)
+    }
+}
+
+impl<'a, R: io::Read> Iterator for ArrowBatches<'a, R> {
+    type Item = io::Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<io::Result<RecordBatch>> {
+        if self.done {return None}
+        loop {
+            if self.frames.len() == self.batch_size {
+                return Some(self.finish_batch());
+            }
+            match self.current {
+                Some(ref mut current) if current.next < current.rows => {
+                    let (frame, time, instance) = (current.frame, current.time, current.next);
+                    current.next += 1;
+                    self.push_row(frame, time, instance);
+                }
+                _ => match self.next_frame() {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.done = true;
+                        if self.frames.is_empty() {return None}
+                        return Some(self.finish_batch());
+                    }
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Column {
+    /// Creates an array of the values and clears them.
+    fn finish(&mut self, data_type: &DataType) -> io::Result<ArrayRef> {
+        let mut values = std::mem::take(&mut self.values);
+        let valid = std::mem::take(&mut self.valid);
+        let scalar_size = self.scalar.primitive_width().unwrap_or(1);
+        if cfg!(target_endian = "big") {
+            for scalar in values.chunks_mut(scalar_size) {scalar.reverse()}
+        }
+        let rows = valid.len();
+        let scalars = ArrayData::builder(self.scalar.clone())
+            .len(rows * self.components)
+            .add_buffer(Buffer::from_vec(values))
+            .align_buffers(true);
+        let nulls = Some(NullBuffer::from(valid));
+        let data = if self.components == 1 {
+            scalars.nulls(nulls).build()
+        } else {
+            let child = scalars.build().map_err(io::Error::other)?;
+            ArrayData::builder(data_type.clone())
+                .len(rows)
+                .add_child_data(child)
+                .nulls(nulls)
+                .build()
+        };
+        Ok(make_array(data.map_err(io::Error::other)?))
+    }
+}
+
+/// Returns the Arrow type of a scalar type.
+fn data_type(ty: Type) -> DataType {
+    match ty {
+        Type::U8 => DataType::UInt8,
+        Type::U16 => DataType::UInt16,
+        Type::U32 => DataType::UInt32,
+        Type::U64 => DataType::UInt64,
+        Type::I8 => DataType::Int8,
+        Type::I16 => DataType::Int16,
+        Type::I32 => DataType::Int32,
+        Type::I64 => DataType::Int64,
+        Type::F32 => DataType::Float32,
+        Type::F64 => DataType::Float64,
+    }
+}
@@ -0,0 +1,171 @@
+//! Writing on a background thread.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use State;
+
+type Job<W> = Box<dyn FnOnce(&mut W) -> io::Result<()> + Send>;
+
+enum Message<W> {
+    Write(Job<W>),
+    Flush(SyncSender<()>),
+}
+
+/// Serializes and writes data on a background thread.
+///
+/// Data is sent through a bounded queue.
+/// When the queue is full, writing blocks until the thread catches up,
+/// while `try_write_with` returns an error of kind `ErrorKind::Busy`.
+///
+/// An error on the thread stops the thread.
+/// The error is returned by the next call, and later calls return `BrokenPipe`:
+///
+/// ```ignore
+/// let mut w = AsyncFileWriter::new(file, 16);
+/// for step in 0..1000 {
+///     let pos = sim.positions().to_vec();
+///     w.write_with(move |w| Vector::write_array(POSITION, &pos, w))?;
+/// }
+/// let file = w.finish()?;
+/// ```
+pub struct AsyncFileWriter<W: io::Write + Send + 'static> {
+    sender: Option<SyncSender<Message<W>>>,
+    thread: Option<JoinHandle<W>>,
+    error: Arc<Mutex<Option<io::Error>>>,
+}
+
+impl<W: io::Write + Send + 'static> AsyncFileWriter<W> {
+    /// Creates a new writer with a queue of `capacity` writes.
+    pub fn new(inner: W, capacity: usize) -> AsyncFileWriter<W> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let error = Arc::new(Mutex::new(None));
+        let thread_error = error.clone();
+        let thread = thread::spawn(move || run(inner, receiver, thread_error));
+        AsyncFileWriter {
+            sender: Some(sender),
+            thread: Some(thread),
+            error,
+        }
+    }
+
+    /// Writes on the thread by calling a function with the underlying writer.
+    ///
+    /// Blocks while the queue is full.
+    pub fn write_with<F>(&mut self, f: F) -> io::Result<()>
+        where F: FnOnce(&mut W) -> io::Result<()> + Send + 'static
+    {
+        self.check()?;
+        let sent = self.sender.as_ref().map(|s| s.send(Message::Write(Box::new(f))).is_ok());
+        if sent != Some(true) {return Err(self.stopped())}
+        Ok(())
+    }
+
+    /// Writes on the thread by calling a function with the underlying writer,
+    /// without blocking.
+    ///
+    /// Returns an error of kind `ErrorKind::Busy` when the queue is full.
+    pub fn try_write_with<F>(&mut self, f: F) -> io::Result<()>
+        where F: FnOnce(&mut W) -> io::Result<()> + Send + 'static
+    {
+        self.check()?;
+        match self.sender.as_ref().map(|s| s.try_send(Message::Write(Box::new(f)))) {
+            Some(Ok(())) => Ok(()),
+            Some(Err(TrySendError::Full(_))) => Err(Error::new(ErrorKind::Busy).into()),
+            _ => Err(self.stopped()),
+        }
+    }
+
+    /// Writes blocks as a single property on the thread.
+    ///
+    /// Blocks while the queue is full.
+    pub fn write_property(
+        &mut self,
+        type_format: u16,
+        property_id: u16,
+        blocks: Vec<RawBlock>
+    ) -> io::Result<()> {
+        self.write_with(move |w| RawBlock::write_property(type_format, property_id, &blocks, w))
+    }
+
+    /// Waits until everything queued is written and the underlying writer is flushed.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.check()?;
+        let (ack, done) = mpsc::sync_channel(1);
+        let sent = self.sender.as_ref().map(|s| s.send(Message::Flush(ack)).is_ok());
+        if sent != Some(true) || done.recv().is_err() {return Err(self.stopped())}
+        self.check()
+    }
+
+    /// Writes end of stream, waits for the thread and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_with(|w| {
+            State::new().end_type_formats(w)?;
+            w.flush()
+        })?;
+        self.sender = None;
+        let inner = match self.thread.take().map(|t| t.join()) {
+            Some(Ok(inner)) => inner,
+            _ => return Err(io::Error::other("writer thread panicked")),
+        };
+        self.check()?;
+        Ok(inner)
+    }
+
+    /// Returns the error of the thread, if any.
+    fn check(&self) -> io::Result<()> {
+        match self.error.lock().ok().and_then(|mut err| err.take()) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the error when the thread no longer accepts writes.
+    fn stopped(&self) -> io::Error {
+        match self.check() {
+            Err(err) => err,
+            Ok(()) => io::ErrorKind::BrokenPipe.into(),
+        }
+    }
+}
+
+impl<W: io::Write + Send + 'static> Drop for AsyncFileWriter<W> {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run<W: io::Write>(
+    mut inner: W,
+    receiver: Receiver<Message<W>>,
+    error: Arc<Mutex<Option<io::Error>>>
+) -> W {
+    for msg in receiver {
+        let res = match msg {
+            Message::Write(job) => job(&mut inner),
+            Message::Flush(ack) => {
+                let res = inner.flush();
+                // The error is stored before the producer is woken up.
+                if let Err(err) = res {
+                    if let Ok(mut e) = error.lock() {*e = Some(err)}
+                    let _ = ack.send(());
+                    break;
+                }
+                let _ = ack.send(());
+                Ok(())
+            }
+        };
+        if let Err(err) = res {
+            if let Ok(mut e) = error.lock() {*e = Some(err)}
+            break;
+        }
+    }
+    inner
+}
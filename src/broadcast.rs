@@ -0,0 +1,260 @@
+//! Fanning out the blocks of a stream to several consumers.
+//!
+//! One thread reads the stream and forwards every block to the subscribers of its property,
+//! through a bounded queue per subscriber.
+//! The data of a block is shared between subscribers without copying,
+//! and blocks of properties without subscribers are skipped without being stored.
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use raw;
+
+/// A block forwarded to subscribers, with data shared between them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BroadcastBlock {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// Offset instance id.
+    pub offset: u64,
+    /// Data.
+    pub data: Arc<[u8]>,
+}
+
+/// Determines what happens when the queue of a subscriber is full.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Backpressure {
+    /// Wait until the subscriber receives a block.
+    ///
+    /// This slows down reading, and every other subscriber, to the slowest subscriber.
+    Block,
+    /// Drop the oldest block in the queue.
+    DropOldest,
+    /// Drop the new block.
+    DropNewest,
+}
+
+struct QueueState {
+    blocks: VecDeque<BroadcastBlock>,
+    closed: bool,
+    subscribed: bool,
+    lagged: u64,
+    dropped: u64,
+}
+
+struct Queue {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: Backpressure,
+}
+
+impl Queue {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn push(&self, block: BroadcastBlock) {
+        let mut state = self.lock();
+        if !state.subscribed {return}
+        if state.blocks.len() >= self.capacity {
+            state.lagged += 1;
+            match self.policy {
+                Backpressure::Block => {
+                    while state.subscribed && state.blocks.len() >= self.capacity {
+                        state = self.not_full.wait(state).unwrap_or_else(|err| err.into_inner());
+                    }
+                    if !state.subscribed {return}
+                }
+                Backpressure::DropOldest => {
+                    state.blocks.pop_front();
+                    state.dropped += 1;
+                }
+                Backpressure::DropNewest => {
+                    state.dropped += 1;
+                    return;
+                }
+            }
+        }
+        state.blocks.push_back(block);
+        self.not_empty.notify_one();
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+    }
+}
+
+/// Receives the blocks of subscribed properties from a `Broadcast`.
+///
+/// Dropping a subscriber unsubscribes it, such that it no longer slows down reading.
+pub struct Subscriber {
+    queue: Arc<Queue>,
+}
+
+impl Subscriber {
+    /// Receives the next block, waiting until one is available.
+    ///
+    /// Returns `None` when the broadcast has ended and all blocks have been received.
+    pub fn recv(&self) -> Option<BroadcastBlock> {
+        let mut state = self.queue.lock();
+        loop {
+            if let Some(block) = state.blocks.pop_front() {
+                self.queue.not_full.notify_one();
+                return Some(block);
+            }
+            if state.closed {return None}
+            state = self.queue.not_empty.wait(state).unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Receives the next block, if one is available.
+    pub fn try_recv(&self) -> Option<BroadcastBlock> {
+        let block = self.queue.lock().blocks.pop_front();
+        if block.is_some() {self.queue.not_full.notify_one()}
+        block
+    }
+
+    /// Returns the number of blocks that arrived when the queue was full.
+    pub fn lagged(&self) -> u64 {
+        self.queue.lock().lagged
+    }
+
+    /// Returns the number of blocks dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.lock().dropped
+    }
+}
+
+impl Iterator for Subscriber {
+    type Item = BroadcastBlock;
+
+    fn next(&mut self) -> Option<BroadcastBlock> {
+        self.recv()
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let mut state = self.queue.lock();
+        state.subscribed = false;
+        state.blocks.clear();
+        self.queue.not_full.notify_all();
+    }
+}
+
+struct Subscription {
+    properties: Vec<u16>,
+    queue: Arc<Queue>,
+}
+
+impl Subscription {
+    fn wants(&self, property_id: u16) -> bool {
+        self.properties.is_empty() || self.properties.contains(&property_id)
+    }
+}
+
+/// Forwards the blocks of a stream to subscribers of properties.
+///
+/// ```ignore
+/// let mut broadcast = Broadcast::new();
+/// let positions = broadcast.subscribe(&[POSITION], 64, Backpressure::Block);
+/// let gui = broadcast.subscribe(&[POSITION, COLOR], 8, Backpressure::DropOldest);
+/// thread::spawn(move || for block in positions { /* ... */ });
+/// thread::spawn(move || for block in gui { /* ... */ });
+/// broadcast.run(&mut file)?;
+/// ```
+///
+/// Subscribers receive `None` after the last block when the broadcast is dropped.
+#[derive(Default)]
+pub struct Broadcast {
+    subscriptions: Vec<Subscription>,
+}
+
+impl Broadcast {
+    /// Creates a broadcast without subscribers.
+    pub fn new() -> Broadcast {
+        Broadcast::default()
+    }
+
+    /// Subscribes to the blocks of properties, with a queue of `capacity` blocks.
+    ///
+    /// When `properties` is empty, the subscriber receives the blocks of all properties.
+    /// A capacity of 0 is treated as 1.
+    pub fn subscribe(&mut self, properties: &[u16], capacity: usize, policy: Backpressure) -> Subscriber {
+        let queue = Arc::new(Queue {
+            state: Mutex::new(QueueState {
+                blocks: VecDeque::new(),
+                closed: false,
+                subscribed: true,
+                lagged: 0,
+                dropped: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+        });
+        self.subscriptions.push(Subscription {properties: properties.into(), queue: queue.clone()});
+        Subscriber {queue}
+    }
+
+    /// Returns the number of blocks that arrived when the queue of a subscriber was full,
+    /// for every subscriber in the order they subscribed.
+    pub fn lagged(&self) -> Vec<u64> {
+        self.subscriptions.iter().map(|s| s.queue.lock().lagged).collect()
+    }
+
+    /// Returns `true` if a subscriber receives the blocks of a property.
+    pub fn is_subscribed(&self, property_id: u16) -> bool {
+        self.subscriptions.iter().any(|s| s.wants(property_id) && s.queue.lock().subscribed)
+    }
+
+    /// Forwards a block to the subscribers of its property.
+    pub fn send(&self, block: BroadcastBlock) {
+        for s in &self.subscriptions {
+            if s.wants(block.property_id) {s.queue.push(block.clone())}
+        }
+    }
+
+    /// Reads blocks until the end of stream, forwarding them to subscribers.
+    ///
+    /// The data of properties without subscribers is skipped.
+    pub fn run<R: io::Read>(&self, r: &mut R) -> io::Result<()> {
+        while let Some((mut state, ty, prop)) = raw::read_property_header(r)? {
+            let subscribed = self.is_subscribed(prop);
+            loop {
+                let (header, data_state) = state.read_block_header(ty, prop, r)?;
+                if header.is_end() {break}
+                if subscribed {
+                    let mut data = vec![];
+                    r.by_ref().take(header.bytes).read_to_end(&mut data)?;
+                    if (data.len() as u64) < header.bytes {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    self.send(BroadcastBlock {
+                        type_format: ty,
+                        property_id: prop,
+                        offset: header.offset,
+                        data: data.into(),
+                    });
+                } else {
+                    raw::copy_payload(header.bytes, r, &mut io::sink())?;
+                }
+                state = data_state.end_data();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Broadcast {
+    fn drop(&mut self) {
+        for s in &self.subscriptions {s.queue.close()}
+    }
+}
@@ -0,0 +1,279 @@
+//! Reusing buffers between frames.
+//!
+//! Reading a frame allocates a buffer for the data of every block.
+//! When replaying at a steady rate, the buffers of one frame have about the sizes of the next,
+//! so a `BufferPool` keeps returned buffers and hands them out again,
+//! instead of freeing and allocating them every frame.
+//!
+//! A pool is shared between threads by cloning it.
+//! The total capacity of the buffers kept is bounded,
+//! and buffers returned beyond the bound are freed.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use frame::Frame;
+use raw::RawBlock;
+use read_write::Element;
+
+/// Statistics of a buffer pool, for tuning its capacity.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// The number of buffers taken that reused a pooled buffer.
+    pub hits: u64,
+    /// The number of buffers taken that were new.
+    pub misses: u64,
+    /// The number of buffers returned and kept.
+    pub returned: u64,
+    /// The number of buffers returned and freed, because the pool was full.
+    pub discarded: u64,
+    /// The capacity in bytes of the buffers kept now.
+    pub pooled_bytes: u64,
+    /// The largest capacity in bytes of the buffers kept at once.
+    pub peak_bytes: u64,
+}
+
+impl BufferStats {
+    /// Returns the fraction of buffers taken that reused a pooled buffer.
+    ///
+    /// Returns `0.0` when no buffer has been taken.
+    pub fn hit_rate(&self) -> f64 {
+        let taken = self.hits + self.misses;
+        if taken == 0 {0.0} else {self.hits as f64 / taken as f64}
+    }
+}
+
+/// A pooled buffer with its capacity in bytes.
+struct Entry {
+    bytes: usize,
+    buffer: Box<dyn Any + Send>,
+}
+
+struct Inner {
+    max_bytes: usize,
+    buffers: HashMap<TypeId, Vec<Entry>>,
+    stats: BufferStats,
+}
+
+impl Inner {
+    /// Removes the smallest buffer with a capacity of at least `len` items,
+    /// or else the largest buffer.
+    fn take<T: 'static + Send>(&mut self, len: usize) -> Option<Vec<T>> {
+        let size = mem::size_of::<T>().max(1);
+        let entries = self.buffers.get_mut(&TypeId::of::<Vec<T>>())?;
+        let fits = entries.iter().enumerate()
+            .filter(|&(_, e)| e.bytes / size >= len)
+            .min_by_key(|&(_, e)| e.bytes)
+            .map(|(i, _)| i);
+        let ind = fits.or_else(|| {
+            entries.iter().enumerate().max_by_key(|&(_, e)| e.bytes).map(|(i, _)| i)
+        })?;
+        let entry = entries.swap_remove(ind);
+        self.stats.pooled_bytes -= entry.bytes as u64;
+        entry.buffer.downcast::<Vec<T>>().ok().map(|buffer| *buffer)
+    }
+
+    fn give<T: 'static + Send>(&mut self, mut buffer: Vec<T>) {
+        let bytes = buffer.capacity() * mem::size_of::<T>();
+        if bytes == 0 {return}
+        if self.stats.pooled_bytes + bytes as u64 > self.max_bytes as u64 {
+            self.stats.discarded += 1;
+            return;
+        }
+        buffer.clear();
+        self.buffers.entry(TypeId::of::<Vec<T>>()).or_default().push(Entry {
+            bytes,
+            buffer: Box::new(buffer),
+        });
+        self.stats.returned += 1;
+        self.stats.pooled_bytes += bytes as u64;
+        self.stats.peak_bytes = self.stats.peak_bytes.max(self.stats.pooled_bytes);
+    }
+}
+
+/// A thread-safe pool of buffers with a bounded total capacity.
+///
+/// Readers take the buffers of block data from the pool:
+///
+/// ```ignore
+/// let pool = BufferPool::new(64 << 20);
+/// let mut frames = FrameReader::with_time(file, TIME).buffer_pool(pool.clone());
+/// while let Some(frame) = frames.next_pooled_frame()? {
+///     frame.read_array(POSITION, &mut pos)?;
+///     // The buffers of the frame are returned here.
+/// }
+/// println!("hit rate {}", pool.stats().hit_rate());
+/// ```
+///
+/// Clones share the same buffers.
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl fmt::Debug for BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("max_bytes", &self.max_bytes())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl BufferPool {
+    /// Creates a pool that keeps buffers up to a total capacity in bytes.
+    pub fn new(max_bytes: usize) -> BufferPool {
+        BufferPool {
+            inner: Arc::new(Mutex::new(Inner {
+                max_bytes,
+                buffers: HashMap::new(),
+                stats: BufferStats::default(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // The pool is consistent between calls, so a panic elsewhere does not corrupt it.
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the maximum total capacity in bytes of the buffers kept.
+    pub fn max_bytes(&self) -> usize {
+        self.lock().max_bytes
+    }
+
+    /// Returns the statistics of the pool.
+    pub fn stats(&self) -> BufferStats {
+        self.lock().stats
+    }
+
+    /// Frees the buffers kept and resets the statistics.
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.buffers.clear();
+        inner.stats = BufferStats::default();
+    }
+
+    /// Takes an empty byte buffer, for about `len` bytes.
+    ///
+    /// Reuses the smallest pooled buffer with a capacity of at least `len`,
+    /// or else the largest pooled buffer.
+    /// When no buffer is pooled, a new buffer is returned without allocating,
+    /// such that untrusted lengths are not allocated up front.
+    pub fn take(&self, len: usize) -> Vec<u8> {
+        self.take_vec(len)
+    }
+
+    /// Returns a byte buffer to the pool.
+    ///
+    /// The buffer is freed when the pool is full.
+    pub fn give(&self, buffer: Vec<u8>) {
+        self.give_vec(buffer)
+    }
+
+    /// Takes an empty vector, for about `len` items.
+    ///
+    /// See `take`.
+    pub fn take_vec<T: 'static + Send>(&self, len: usize) -> Vec<T> {
+        let mut inner = self.lock();
+        match inner.take(len) {
+            Some(buffer) => {
+                inner.stats.hits += 1;
+                buffer
+            }
+            None => {
+                inner.stats.misses += 1;
+                vec![]
+            }
+        }
+    }
+
+    /// Returns a vector to the pool.
+    ///
+    /// The items are dropped, and the vector is freed when the pool is full.
+    pub fn give_vec<T: 'static + Send>(&self, buffer: Vec<T>) {
+        self.lock().give(buffer)
+    }
+
+    /// Returns the data of blocks and the vector of blocks to the pool.
+    pub fn give_blocks(&self, mut blocks: Vec<RawBlock>) {
+        let mut inner = self.lock();
+        for block in blocks.drain(..) {inner.give(block.data)}
+        inner.give(blocks);
+    }
+
+    /// Checks out a byte buffer that is returned to the pool when dropped.
+    pub fn checkout(&self, len: usize) -> Pooled<Vec<u8>> {
+        Pooled::new(self.take(len), Some(self.clone()), |pool, buffer| pool.give(buffer))
+    }
+
+    /// Checks out a vector that is returned to the pool when dropped.
+    pub fn checkout_vec<T: 'static + Send>(&self, len: usize) -> Pooled<Vec<T>> {
+        Pooled::new(self.take_vec(len), Some(self.clone()), |pool, buffer| pool.give_vec(buffer))
+    }
+
+    /// Decodes the items of a block into a vector that is returned to the pool when dropped.
+    ///
+    /// Returns an error if the type format does not match the element type.
+    pub fn decode<T: Element + 'static + Send>(&self, block: &RawBlock) -> io::Result<Pooled<Vec<T>>> {
+        let len = block.items().unwrap_or(0).min(usize::MAX as u64) as usize;
+        let mut res = self.checkout_vec::<T>(len);
+        block.decode_into(&mut res)?;
+        Ok(res)
+    }
+
+    pub(crate) fn frame(&self, frame: Frame) -> Pooled<Frame> {
+        Pooled::new(frame, Some(self.clone()), |pool, frame| pool.give_blocks(frame.blocks))
+    }
+}
+
+/// A value with storage that is returned to a buffer pool when dropped.
+pub struct Pooled<T> {
+    value: Option<T>,
+    pool: Option<BufferPool>,
+    give: fn(&BufferPool, T),
+}
+
+impl<T> Pooled<T> {
+    pub(crate) fn new(value: T, pool: Option<BufferPool>, give: fn(&BufferPool, T)) -> Pooled<T> {
+        Pooled {value: Some(value), pool, give}
+    }
+
+    /// Takes the value, such that its storage is not returned to the pool.
+    pub fn into_inner(mut self) -> T {
+        self.value.take().unwrap()
+    }
+}
+
+impl<T> Deref for Pooled<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().unwrap()
+    }
+}
+
+impl<T> DerefMut for Pooled<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().unwrap()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T> Drop for Pooled<T> {
+    fn drop(&mut self) {
+        if let (Some(value), Some(pool)) = (self.value.take(), self.pool.as_ref()) {
+            (self.give)(pool, value);
+        }
+    }
+}
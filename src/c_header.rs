@@ -0,0 +1,104 @@
+//! Generating a C header with the constants of the format.
+
+use std::fmt::Write;
+
+use reserved::{RESERVED_FORMAT_START, RESERVED_PROPERTY_START};
+use Type;
+use {SIZE, TYPES};
+
+const ALL_TYPES: [(Type, &str); 10] = [
+    (Type::U8, "U8"), (Type::U16, "U16"), (Type::U32, "U32"), (Type::U64, "U64"),
+    (Type::I8, "I8"), (Type::I16, "I16"), (Type::I32, "I32"), (Type::I64, "I64"),
+    (Type::F32, "F32"), (Type::F64, "F64"),
+];
+
+/// Returns a C header with the constants of the format
+/// and helpers for converting between type formats and types.
+///
+/// The header of this crate is `include/binpool_format.h`,
+/// written by `cargo run --example c_header` and checked by `tests/c_header.rs`.
+/// The helpers mirror `Type::format_for` and `Type::info`:
+///
+/// ```ignore
+/// uint16_t format = binpool_format_for(BINPOOL_TYPE_F32, 1, 3);
+/// enum BinpoolType type; uint8_t rows, cols;
+/// if (binpool_info(format, &type, &rows, &cols)) { ... }
+/// ```
+pub fn generate_c_header() -> String {
+    let mut s = String::new();
+    let _ = write_header(&mut s);
+    s
+}
+
+fn write_header(s: &mut String) -> ::std::fmt::Result {
+    writeln!(s, "/*")?;
+    writeln!(s, " * Constants of the binpool format.")?;
+    writeln!(s, " *")?;
+    writeln!(s, " * Generated by `cargo run --example c_header`, do not edit.")?;
+    writeln!(s, " */")?;
+    writeln!(s)?;
+    writeln!(s, "#ifndef BINPOOL_FORMAT_H")?;
+    writeln!(s, "#define BINPOOL_FORMAT_H")?;
+    writeln!(s)?;
+    writeln!(s, "#include <stdint.h>")?;
+    writeln!(s)?;
+    writeln!(s, "/* The maximum number of rows or columns of a built-in type format. */")?;
+    writeln!(s, "#define BINPOOL_SIZE {}", SIZE)?;
+    writeln!(s, "/* The number of scalar types. */")?;
+    writeln!(s, "#define BINPOOL_TYPES {}", TYPES)?;
+    writeln!(s, "/* The first custom format. */")?;
+    writeln!(s, "#define BINPOOL_OFFSET_CUSTOM_FORMAT {}", Type::offset_custom_format())?;
+    writeln!(s, "/* The first custom format reserved for the crate. */")?;
+    writeln!(s, "#define BINPOOL_RESERVED_FORMAT_START 0x{:04x}", RESERVED_FORMAT_START)?;
+    writeln!(s, "/* The first property id reserved for the crate. */")?;
+    writeln!(s, "#define BINPOOL_RESERVED_PROPERTY_START 0x{:04x}", RESERVED_PROPERTY_START)?;
+    writeln!(s)?;
+    writeln!(s, "/* Scalar types, by type id. */")?;
+    writeln!(s, "enum BinpoolType {{")?;
+    for &(ty, name) in &ALL_TYPES {
+        writeln!(s, "    BINPOOL_TYPE_{} = {},", name, ty.type_id())?;
+    }
+    writeln!(s, "}};")?;
+    writeln!(s)?;
+    writeln!(s, "/* Returns the size in bytes of a scalar type, or 0 for an unknown type. */")?;
+    writeln!(s, "static inline uint64_t binpool_type_size(enum BinpoolType type) {{")?;
+    writeln!(s, "    switch (type) {{")?;
+    for &(ty, name) in &ALL_TYPES {
+        writeln!(s, "    case BINPOOL_TYPE_{}: return {};", name, ty.type_size())?;
+    }
+    writeln!(s, "    default: return 0;")?;
+    writeln!(s, "    }}")?;
+    writeln!(s, "}}")?;
+    writeln!(s)?;
+    writeln!(s, "/*")?;
+    writeln!(s, " * Returns the type format of a matrix, scalars having 1x1 and vectors 1xN,")?;
+    writeln!(s, " * or 0 if the dimensions are not supported.")?;
+    writeln!(s, " */")?;
+    writeln!(s, "static inline uint16_t binpool_format_for(enum BinpoolType type, uint8_t rows, uint8_t cols) {{")?;
+    writeln!(s, "    if ((unsigned)type >= BINPOOL_TYPES || rows == 0 || cols == 0 ||")?;
+    writeln!(s, "        rows > BINPOOL_SIZE || cols > BINPOOL_SIZE) {{")?;
+    writeln!(s, "        return 0;")?;
+    writeln!(s, "    }}")?;
+    writeln!(s, "    return (uint16_t)(1 + (unsigned)type * BINPOOL_SIZE * BINPOOL_SIZE +")?;
+    writeln!(s, "        (rows - 1) * BINPOOL_SIZE + (cols - 1));")?;
+    writeln!(s, "}}")?;
+    writeln!(s)?;
+    writeln!(s, "/*")?;
+    writeln!(s, " * Decodes a built-in type format into type, rows and columns.")?;
+    writeln!(s, " * Returns 1 for built-in type formats, or 0 for end of stream and custom formats.")?;
+    writeln!(s, " */")?;
+    writeln!(s, "static inline int binpool_info(")?;
+    writeln!(s, "    uint16_t format, enum BinpoolType *type, uint8_t *rows, uint8_t *cols")?;
+    writeln!(s, ") {{")?;
+    writeln!(s, "    if (format == 0 || format >= BINPOOL_OFFSET_CUSTOM_FORMAT) {{")?;
+    writeln!(s, "        return 0;")?;
+    writeln!(s, "    }}")?;
+    writeln!(s, "    format -= 1;")?;
+    writeln!(s, "    *type = (enum BinpoolType)(format / (BINPOOL_SIZE * BINPOOL_SIZE));")?;
+    writeln!(s, "    *rows = (uint8_t)((format % (BINPOOL_SIZE * BINPOOL_SIZE)) / BINPOOL_SIZE + 1);")?;
+    writeln!(s, "    *cols = (uint8_t)(format % BINPOOL_SIZE + 1);")?;
+    writeln!(s, "    return 1;")?;
+    writeln!(s, "}}")?;
+    writeln!(s)?;
+    writeln!(s, "#endif")
+}
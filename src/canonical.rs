@@ -0,0 +1,127 @@
+//! Rewriting streams in canonical form.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use endian::EndianWriter;
+use raw::{self, RawBlock};
+use reader::PoolReader;
+use reserved;
+use State;
+
+/// Copies a stream in canonical form.
+///
+/// Within each frame, properties are sorted by property id and type format.
+/// Properties with the same id and type format are written under one header,
+/// with blocks merged into maximal contiguous ranges sorted by offset.
+/// Where blocks overlap, the data that was written last is kept,
+/// which is the same result as reading the blocks in order.
+///
+/// Frames start at each property with the id of the frame marker.
+/// The frame marker keeps its leading position in the frame.
+/// Properties before the first frame marker are sorted as a frame of their own.
+/// Without a frame marker, the whole stream is sorted as one frame.
+///
+/// Properties with reserved formats, such as the file header, units and metadata,
+/// are copied as they are and keep their position:
+/// properties are only sorted between them.
+/// Properties using custom formats are not merged,
+/// and their blocks keep their order.
+///
+/// Big endian streams are copied in their byte order.
+/// The output is terminated with an end of stream.
+pub fn canonicalize<R: io::Read, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    frame_marker: Option<u16>
+) -> io::Result<()> {
+    let mut r = PoolReader::new(r);
+    let mut w = EndianWriter::new(w);
+    let mut run: BTreeMap<(u16, u16), Vec<RawBlock>> = BTreeMap::new();
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r)? {
+        if reserved::is_reserved_format(ty) {
+            write_run(&mut run, &mut w)?;
+            raw::copy_property(state, ty, prop, &mut r, &mut w)?;
+            continue;
+        }
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r)?;
+        if Some(prop) == frame_marker {
+            write_run(&mut run, &mut w)?;
+            RawBlock::write_property(ty, prop, &merge(blocks), &mut w)?;
+        } else {
+            run.entry((prop, ty)).or_default().extend(blocks);
+        }
+    }
+    write_run(&mut run, &mut w)?;
+    State::new().end_type_formats(&mut w)
+}
+
+/// Writes properties in canonical order and clears them.
+fn write_run<W: io::Write>(
+    run: &mut BTreeMap<(u16, u16), Vec<RawBlock>>,
+    w: &mut W
+) -> io::Result<()> {
+    for ((prop, ty), blocks) in std::mem::take(run) {
+        RawBlock::write_property(ty, prop, &merge(blocks), w)?;
+    }
+    Ok(())
+}
+
+/// Merges blocks into maximal contiguous ranges sorted by offset.
+fn merge(mut blocks: Vec<RawBlock>) -> Vec<RawBlock> {
+    blocks.retain(|b| !b.data.is_empty());
+    let size = match blocks.first().and_then(|b| b.item_size()) {
+        Some(size) if size > 0 => size,
+        _ => return blocks,
+    };
+    let mut ranges = vec![];
+    for (i, b) in blocks.iter().enumerate() {
+        if !(b.data.len() as u64).is_multiple_of(size) {return blocks}
+        match b.offset.checked_add(b.data.len() as u64 / size) {
+            None => return blocks,
+            Some(end) => ranges.push((b.offset, end, i)),
+        }
+    }
+    ranges.sort_by_key(|r| r.0);
+
+    let mut res: Vec<RawBlock> = vec![];
+    if ranges.windows(2).all(|w| w[0].1 <= w[1].0) {
+        // Blocks do not overlap, so they only need to be sorted.
+        let mut blocks: Vec<Option<RawBlock>> = blocks.into_iter().map(Some).collect();
+        let mut end = None;
+        for (start, block_end, i) in ranges {
+            let block = blocks[i].take().unwrap();
+            if end == Some(start) {
+                res.last_mut().unwrap().data.extend_from_slice(&block.data);
+            } else {
+                res.push(block);
+            }
+            end = Some(block_end);
+        }
+        return res;
+    }
+
+    // Later blocks overwrite earlier ones.
+    let mut items: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+    for (i, b) in blocks.iter().enumerate() {
+        for j in 0..b.data.len() / size as usize {
+            items.insert(b.offset + j as u64, (i, j));
+        }
+    }
+    let mut next: Option<u64> = None;
+    for (ind, (i, j)) in items {
+        let item = &blocks[i].data[j * size as usize..(j + 1) * size as usize];
+        if next == Some(ind) {
+            res.last_mut().unwrap().data.extend_from_slice(item);
+        } else {
+            res.push(RawBlock {
+                type_format: blocks[i].type_format,
+                property_id: blocks[i].property_id,
+                offset: ind,
+                data: item.to_vec(),
+            });
+        }
+        next = Some(ind + 1);
+    }
+    res
+}
@@ -0,0 +1,350 @@
+//! Several files read as one stream.
+//!
+//! Every file is a complete stream.
+//! The end of stream of every file but the last is skipped,
+//! and so is the file header of every file but the first.
+//! The files must have the same byte order.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use endian::{Converter, Endian, Header};
+use error::{Error, ErrorKind};
+use index::{FrameCursor, Index, IndexEntry};
+use raw::{self, RawBlock};
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY};
+use trace;
+use Bytes;
+use State;
+
+/// Reads a sequence of files as one stream.
+///
+/// Frames continue across files, such that a `FrameReader` numbers them as in one stream.
+/// Data before the first frame of a file, other than the file header,
+/// belongs to the last frame of the file before.
+///
+/// Errors from reading carry the index of the file and the position in the file.
+/// Errors found when decoding the data read through the reader can be annotated with `annotate`:
+///
+/// ```ignore
+/// let chain = ChainedReader::open(&["run.0001.pool", "run.0002.pool"])?;
+/// let mut frames = FrameReader::new(chain, TIME);
+/// while let Some(frame) = frames.next_frame()? {
+///     ...
+/// }
+/// ```
+pub struct ChainedReader<R> {
+    sources: Vec<R>,
+    current: usize,
+    position: u64,
+    converter: Converter,
+    header: Option<Option<Header>>,
+    checked: bool,
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl ChainedReader<BufReader<File>> {
+    /// Opens files in order.
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> io::Result<ChainedReader<BufReader<File>>> {
+        let mut sources = vec![];
+        for (i, path) in paths.iter().enumerate() {
+            let file = File::open(path).map_err(|err| annotate(err, i, None))?;
+            sources.push(BufReader::new(file));
+        }
+        Ok(ChainedReader::new(sources))
+    }
+}
+
+impl<R: io::Read> ChainedReader<R> {
+    /// Creates a new reader from streams in order.
+    pub fn new(sources: Vec<R>) -> ChainedReader<R> {
+        ChainedReader {
+            sources,
+            current: 0,
+            position: 0,
+            converter: Converter::default(),
+            header: None,
+            checked: true,
+            pending: vec![],
+            done: false,
+        }
+    }
+
+    /// Returns the number of files.
+    pub fn file_count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns the index of the file that is read.
+    pub fn current_file(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the number of bytes read from the current file.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Adds the index of the current file and the position in the file to an error.
+    ///
+    /// Information that is already stored in the error is kept.
+    pub fn annotate(&self, err: io::Error) -> io::Error {
+        annotate(err, self.current, Some(self.position))
+    }
+
+    /// Gets references to the underlying readers.
+    pub fn get_ref(&self) -> &[R] {
+        &self.sources
+    }
+
+    /// Unwraps the underlying readers.
+    pub fn into_inner(self) -> Vec<R> {
+        self.sources
+    }
+
+    fn fill(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if !self.pending.is_empty() {
+                let n = self.pending.len().min(buf.len());
+                buf[..n].copy_from_slice(&self.pending[..n]);
+                self.pending.drain(..n);
+                self.follow(&buf[..n])?;
+                return Ok(n);
+            }
+            if self.done || self.current >= self.sources.len() {return Ok(0)}
+            if !self.converter.at_property_boundary() {
+                // Header fields are read one byte at a time to find where properties end.
+                let max = (self.converter.remaining() as usize).min(buf.len()).max(1);
+                let n = self.read_source(&mut buf[..max])?;
+                if n == 0 {return Err(io::ErrorKind::UnexpectedEof.into())}
+                self.follow(&buf[..n])?;
+                return Ok(n);
+            }
+
+            let mut ty = [0; 2];
+            let n = self.read_full(&mut ty)?;
+            if n == 1 {return Err(io::ErrorKind::UnexpectedEof.into())}
+            if !self.checked {
+                self.checked = true;
+                if n == 2 && u16::from_le_bytes(ty) == HEADER_FORMAT {
+                    let mut prop = [0; 2];
+                    if self.read_full(&mut prop)? < 2 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    if u16::from_le_bytes(prop) == HEADER_PROPERTY {
+                        let header = self.read_header()?;
+                        self.check_endian(header.endian)?;
+                        self.converter = Converter::resume(Some(header));
+                        continue;
+                    }
+                    self.check_endian(Endian::Little)?;
+                    self.pending.extend_from_slice(&ty);
+                    self.pending.extend_from_slice(&prop);
+                    continue;
+                }
+                self.check_endian(Endian::Little)?;
+            }
+            let end = n == 0 || ty == [0, 0];
+            if end && self.current + 1 < self.sources.len() {
+                if self.header.is_none() {self.header = Some(self.converter.header())}
+                self.current += 1;
+                self.position = 0;
+                self.checked = false;
+                self.converter = Converter::resume(None);
+                continue;
+            }
+            if end {
+                self.done = true;
+                if n == 0 {return Ok(0)}
+            }
+            self.pending.extend_from_slice(&ty);
+        }
+    }
+
+    /// Follows the bytes that are passed on, to find where properties end.
+    fn follow(&mut self, data: &[u8]) -> io::Result<()> {
+        self.converter.convert(data, false, |_| Ok(()), |_| Ok(()))
+    }
+
+    fn read_source(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.sources[self.current].read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+
+    /// Reads until the buffer is full or the end of the current file.
+    fn read_full(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < buf.len() {
+            match self.read_source(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => trace::retry(),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(n)
+    }
+
+    /// Reads the rest of the header property of the current file.
+    fn read_header(&mut self) -> io::Result<Header> {
+        let state: State<Bytes> = State(PhantomData, Some(HEADER_PROPERTY));
+        let blocks = RawBlock::read_property(state, HEADER_FORMAT, HEADER_PROPERTY, &mut Source(self))?;
+        blocks.first().and_then(|block| Header::decode(&block.data)).ok_or_else(|| {
+            Error::new(ErrorKind::InvalidData).with_property(HEADER_PROPERTY).into()
+        })
+    }
+
+    /// Checks that a file has the byte order of the first file.
+    fn check_endian(&self, endian: Endian) -> io::Result<()> {
+        let first = self.header.flatten().map(|h| h.endian).unwrap_or_default();
+        if endian != first {
+            return Err(Error::new(ErrorKind::InvalidData).with_property(HEADER_PROPERTY).into());
+        }
+        Ok(())
+    }
+}
+
+impl<R: io::Read + io::Seek> ChainedReader<R> {
+    /// Positions the reader at a property in a file.
+    ///
+    /// The reader continues with the next files from their start after the end of the file.
+    pub fn seek_to(&mut self, file: usize, position: u64) -> io::Result<()> {
+        if file >= self.sources.len() {return Err(io::ErrorKind::InvalidInput.into())}
+        let start = file == 0 && position == 0;
+        if self.header.is_none() && !start {
+            self.header = Some(self.first_header().map_err(|err| annotate(err, 0, None))?);
+        }
+        self.sources[file].seek(io::SeekFrom::Start(position))
+            .map_err(|err| annotate(err, file, None))?;
+        // The next files are read from the start.
+        for (i, r) in self.sources.iter_mut().enumerate().skip(file + 1) {
+            r.seek(io::SeekFrom::Start(0)).map_err(|err| annotate(err, i, None))?;
+        }
+        self.current = file;
+        self.position = position;
+        self.converter = if start {
+            Converter::default()
+        } else {
+            Converter::resume(self.header.flatten())
+        };
+        self.checked = !(file > 0 && position == 0);
+        self.pending.clear();
+        self.done = false;
+        Ok(())
+    }
+
+    /// Reads the file header of the first file, if any.
+    fn first_header(&mut self) -> io::Result<Option<Header>> {
+        let r = &mut self.sources[0];
+        r.seek(io::SeekFrom::Start(0))?;
+        match raw::read_property_header(r)? {
+            Some((state, ty, prop)) if ty == HEADER_FORMAT && prop == HEADER_PROPERTY => {
+                let blocks = RawBlock::read_property(state, ty, prop, r)?;
+                Ok(blocks.first().and_then(|block| Header::decode(&block.data)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<R: io::Read> io::Read for ChainedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {return Ok(0)}
+        self.fill(buf).map_err(|err| self.annotate(err))
+    }
+}
+
+/// Reads from the current file, counting the position.
+struct Source<'a, R: 'a>(&'a mut ChainedReader<R>);
+
+impl<'a, R: io::Read> io::Read for Source<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_source(buf)
+    }
+}
+
+fn annotate(err: io::Error, file: usize, position: Option<u64>) -> io::Error {
+    let kind = err.kind();
+    let mut res = Error::from_io(&err);
+    if res.file().is_some() {return err}
+    res = res.with_file(file);
+    if let (None, Some(position)) = (res.at_byte(), position) {
+        res = res.with_position(position);
+    }
+    res.into_io(kind)
+}
+
+/// An index of frames in several files read as one stream.
+///
+/// Frames are numbered across files.
+#[derive(Clone, Debug)]
+pub struct ChainedIndex {
+    indices: Vec<Index>,
+    starts: Vec<u64>,
+}
+
+impl ChainedIndex {
+    /// Builds an index of every file, then positions the reader at the start.
+    ///
+    /// The files are little endian, like for `Index`.
+    pub fn build<R: io::Read + io::Seek>(
+        chain: &mut ChainedReader<R>,
+        marker: u16,
+        time_property: Option<u16>
+    ) -> io::Result<ChainedIndex> {
+        let mut indices = vec![];
+        let mut starts = vec![];
+        let mut frames = 0;
+        for (i, r) in chain.sources.iter_mut().enumerate() {
+            r.seek(io::SeekFrom::Start(0)).map_err(|err| annotate(err, i, None))?;
+            let index = Index::build(r, marker, time_property)
+                .map_err(|err| annotate(err, i, None))?;
+            starts.push(frames);
+            frames += index.frame_count();
+            indices.push(index);
+        }
+        chain.seek_to(0, 0)?;
+        Ok(ChainedIndex {indices, starts})
+    }
+
+    /// Returns the index of every file.
+    pub fn indices(&self) -> &[Index] {
+        &self.indices
+    }
+
+    /// Returns the number of frames in all files.
+    pub fn frame_count(&self) -> u64 {
+        self.indices.iter().map(|index| index.frame_count()).sum()
+    }
+
+    /// Returns the file of frame `n` and the frame in the file.
+    ///
+    /// Returns an error of kind `ErrorKind::FrameOutOfRange`
+    /// with the valid range when `n` is out of range.
+    pub fn locate(&self, n: u64) -> io::Result<(usize, IndexEntry)> {
+        for (file, index) in self.indices.iter().enumerate().rev() {
+            let start = self.starts[file];
+            if n >= start && n - start < index.frame_count() {
+                return Ok((file, index.frames()[(n - start) as usize]));
+            }
+        }
+        Err(Error::new(ErrorKind::FrameOutOfRange).with_range(0, self.frame_count()).into())
+    }
+
+    /// Positions the reader at the start of frame `n`.
+    ///
+    /// The position of the cursor is in the file of the frame.
+    pub fn seek_frame<R: io::Read + io::Seek>(
+        &self,
+        n: u64,
+        chain: &mut ChainedReader<R>
+    ) -> io::Result<FrameCursor> {
+        let (file, entry) = self.locate(n)?;
+        chain.seek_to(file, entry.position)?;
+        Ok(FrameCursor {frame: n, position: entry.position, time: entry.time})
+    }
+}
@@ -0,0 +1,157 @@
+//! Sending blocks between threads over channels.
+//!
+//! Blocks travel as a header and a payload buffer, without the wire format,
+//! through a bounded channel.
+//! With `SinkWriter` and `SourceReader`, the same code that writes and reads byte streams
+//! works between threads:
+//!
+//! ```ignore
+//! let (sink, source) = binpool::channel(64);
+//! thread::spawn(move || {
+//!     let mut w = SinkWriter::new(sink);
+//!     Vector::write_array(POSITION, &pos, &mut w)?;
+//!     w.finish()?.finish()
+//! });
+//! let mut r = PoolReader::new(SourceReader::new(source));
+//! ```
+//!
+//! The end of stream is received when the sink sends it, or when the sink is dropped
+//! between properties.
+//! Dropping the sink in the middle of a property is an unexpected end of stream.
+
+use std::borrow::Cow;
+use std::io;
+use std::sync::mpsc;
+
+#[cfg(feature = "crossbeam")]
+use crossbeam_channel as crossbeam;
+
+use transport::{BlockSink, BlockSource, Payload};
+use BlockHeader;
+
+/// A message sent over a channel transport.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum BlockMessage {
+    /// A block, or the end of bytes of a property with an empty payload.
+    Block(BlockHeader, Vec<u8>),
+    /// The end of stream.
+    End,
+}
+
+enum Sender {
+    Std(mpsc::SyncSender<BlockMessage>),
+    #[cfg(feature = "crossbeam")]
+    Crossbeam(crossbeam::Sender<BlockMessage>),
+}
+
+enum Receiver {
+    Std(mpsc::Receiver<BlockMessage>),
+    #[cfg(feature = "crossbeam")]
+    Crossbeam(crossbeam::Receiver<BlockMessage>),
+}
+
+/// Creates a channel transport that holds up to `capacity` blocks.
+///
+/// Sending blocks while the channel is full waits until the source receives a block.
+/// A capacity of 0 sends every block as a rendezvous.
+pub fn channel(capacity: usize) -> (ChannelSink, ChannelSource) {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    (ChannelSink::new(Sender::Std(sender)), ChannelSource::new(Receiver::Std(receiver)))
+}
+
+/// Creates a channel transport over a bounded crossbeam channel.
+///
+/// See `channel`.
+#[cfg(feature = "crossbeam")]
+pub fn crossbeam_transport(capacity: usize) -> (ChannelSink, ChannelSource) {
+    let (sender, receiver) = crossbeam::bounded(capacity);
+    (ChannelSink::new(Sender::Crossbeam(sender)), ChannelSource::new(Receiver::Crossbeam(receiver)))
+}
+
+/// Sends blocks to a `ChannelSource` on another thread.
+pub struct ChannelSink {
+    sender: Option<Sender>,
+    property: Option<(u16, u16)>,
+}
+
+impl ChannelSink {
+    fn new(sender: Sender) -> ChannelSink {
+        ChannelSink {sender: Some(sender), property: None}
+    }
+
+    fn send_message(&mut self, message: BlockMessage) -> io::Result<()> {
+        let sent = match self.sender {
+            Some(Sender::Std(ref s)) => s.send(message).is_ok(),
+            #[cfg(feature = "crossbeam")]
+            Some(Sender::Crossbeam(ref s)) => s.send(message).is_ok(),
+            None => false,
+        };
+        if sent {Ok(())} else {Err(io::ErrorKind::BrokenPipe.into())}
+    }
+
+    /// Sends the end of stream, unless it has been sent, and closes the channel.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.sender.is_none() {return Ok(())}
+        self.end()
+    }
+}
+
+impl BlockSink for ChannelSink {
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` for type format 0,
+    /// a payload that does not have the number of bytes of the header,
+    /// or a block of another property before the end of bytes,
+    /// and of kind `io::ErrorKind::BrokenPipe` when the source has been dropped
+    /// or the end of stream has been sent.
+    fn send(&mut self, header: &BlockHeader, payload: &[u8]) -> io::Result<()> {
+        let key = (header.type_format, header.property_id);
+        if header.type_format == 0 || payload.len() as u64 != header.bytes ||
+           self.property.is_some_and(|p| p != key) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        self.send_message(BlockMessage::Block(*header, payload.to_vec()))?;
+        self.property = if header.is_end() {None} else {Some(key)};
+        Ok(())
+    }
+
+    /// Sends the end of stream and closes the channel.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` before the end of bytes.
+    fn end(&mut self) -> io::Result<()> {
+        if self.property.is_some() {return Err(io::ErrorKind::InvalidInput.into())}
+        self.send_message(BlockMessage::End)?;
+        self.sender = None;
+        Ok(())
+    }
+}
+
+/// Receives blocks from a `ChannelSink` on another thread.
+///
+/// The payload of a block is moved out of the message without copying.
+pub struct ChannelSource {
+    receiver: Receiver,
+    done: bool,
+}
+
+impl ChannelSource {
+    fn new(receiver: Receiver) -> ChannelSource {
+        ChannelSource {receiver, done: false}
+    }
+}
+
+impl BlockSource for ChannelSource {
+    fn recv(&mut self) -> io::Result<Option<(BlockHeader, Payload<'_>)>> {
+        if self.done {return Ok(None)}
+        let message = match self.receiver {
+            Receiver::Std(ref r) => r.recv().ok(),
+            #[cfg(feature = "crossbeam")]
+            Receiver::Crossbeam(ref r) => r.recv().ok(),
+        };
+        match message {
+            Some(BlockMessage::Block(header, payload)) => Ok(Some((header, Cow::Owned(payload)))),
+            Some(BlockMessage::End) | None => {
+                self.done = true;
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Stopping and resuming reading.
+//!
+//! A checkpoint is written as a stream of its own:
+//!
+//! ```ignore
+//! u64 scalars: position (0), properties (1), payload (2), non-finite count (3), hash (4)
+//! file header, units, metadata and custom formats, when there are any
+//! ```
+
+use std::io;
+
+use endian::Header;
+use error::{Error, ErrorKind};
+use formats::CustomFormats;
+use metadata::{self, Metadata};
+use raw::{self, RawBlock};
+use read_write::Scalar;
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY, METADATA_FORMAT, METADATA_PROPERTY};
+use reserved::{FORMATS_FORMAT, FORMATS_PROPERTY, UNITS_FORMAT, UNITS_PROPERTY};
+use units::Units;
+use State;
+
+const POSITION: u16 = 0;
+const PROPERTIES: u16 = 1;
+const PAYLOAD: u16 = 2;
+const NON_FINITE: u16 = 3;
+const HASH: u16 = 4;
+
+/// The maximum number of bytes before the position that are hashed.
+pub(crate) const TAIL: usize = 64;
+
+/// The state of a `PoolReader` between two properties.
+///
+/// Created by `PoolReader::checkpoint` and used by `PoolReader::resume`.
+/// Settings of the reader, such as registered properties and limits, are not included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Byte offset of the next property in the underlying stream.
+    pub position: u64,
+    /// The file header, if the stream starts with one.
+    pub header: Option<Header>,
+    /// Number of property headers read, which counts against `Limits::max_properties`.
+    pub properties: u64,
+    /// Number of bytes of data read, which counts against `Limits::max_total_payload`.
+    pub payload: u64,
+    /// Number of non-finite values counted.
+    pub non_finite_count: u64,
+    /// Units read before the position.
+    pub units: Units,
+    /// Metadata read before the position.
+    pub metadata: Vec<(u16, Metadata)>,
+    /// Custom formats in the registry of the stream before the position.
+    pub formats: CustomFormats,
+    /// Hash of the bytes right before the position,
+    /// used to check that the stream is the same when resuming.
+    pub hash: u64,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint, followed by end of stream.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.position.write_property(POSITION, w)?;
+        self.properties.write_property(PROPERTIES, w)?;
+        self.payload.write_property(PAYLOAD, w)?;
+        self.non_finite_count.write_property(NON_FINITE, w)?;
+        self.hash.write_property(HASH, w)?;
+        if let Some(ref header) = self.header {
+            header.write(w)?;
+        }
+        if !self.units.is_empty() {
+            self.units.write_units(w)?;
+        }
+        if !self.metadata.is_empty() {
+            let entries: Vec<(u16, &Metadata)> = self.metadata.iter()
+                .map(|(prop, m)| (*prop, m))
+                .collect();
+            metadata::write_metadata(&entries, w)?;
+        }
+        if !self.formats.is_empty() {
+            self.formats.write(w)?;
+        }
+        State::new().end_type_formats(w)
+    }
+
+    /// Reads a checkpoint written by `Checkpoint::write`.
+    ///
+    /// Properties that are not known are skipped.
+    pub fn read<R: io::Read>(r: &mut R) -> io::Result<Checkpoint> {
+        let mut cp = Checkpoint {
+            position: 0,
+            header: None,
+            properties: 0,
+            payload: 0,
+            non_finite_count: 0,
+            units: Units::new(),
+            metadata: vec![],
+            formats: CustomFormats::new(),
+            hash: 0,
+        };
+        let mut has_position = false;
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            let val = match prop {
+                POSITION => {
+                    has_position = true;
+                    &mut cp.position
+                }
+                PROPERTIES => &mut cp.properties,
+                PAYLOAD => &mut cp.payload,
+                NON_FINITE => &mut cp.non_finite_count,
+                HASH => &mut cp.hash,
+                _ => {
+                    if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                        let blocks = RawBlock::read_property(state, ty, prop, r)?;
+                        cp.header = blocks.first().and_then(|b| Header::decode(&b.data));
+                        if cp.header.is_none() {
+                            return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+                        }
+                    } else if ty == UNITS_FORMAT && prop == UNITS_PROPERTY {
+                        cp.units.merge(&Units::read(state, ty, prop, r)?);
+                    } else if ty == METADATA_FORMAT && prop == METADATA_PROPERTY {
+                        metadata::merge_metadata(&mut cp.metadata,
+                            metadata::read_metadata(state, ty, prop, r)?);
+                    } else if ty == FORMATS_FORMAT && prop == FORMATS_PROPERTY {
+                        cp.formats.merge(&CustomFormats::read(state, ty, prop, r)?);
+                    } else {
+                        RawBlock::read_property(state, ty, prop, r)?;
+                    }
+                    continue;
+                }
+            };
+            val.read_property(state, ty, r)?;
+        }
+        if !has_position {
+            return Err(Error::new(ErrorKind::InvalidData).with_property(POSITION).into());
+        }
+        Ok(cp)
+    }
+}
+
+/// Hashes the bytes before a position, with the 64 bit FNV-1a hash.
+///
+/// The hash does not depend on the platform or version of Rust.
+pub(crate) fn hash_tail(position: u64, tail: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in position.to_le_bytes().iter().chain(tail) {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
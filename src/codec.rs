@@ -0,0 +1,266 @@
+//! Encoding and decoding of the format without `std::io`.
+//!
+//! This module is available without the `std` feature,
+//! e.g. to write data from a microcontroller.
+//! Bytes are written to a `ByteWrite` and read from a `ByteRead`.
+//!
+//! With the `std` feature, these traits are implemented for all
+//! `std::io::Write` and `std::io::Read` types.
+//! Without it, they are implemented for `Vec<u8>` and `&[u8]`.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use error::{Error, ErrorKind};
+use Type;
+
+/// Implemented by sinks of bytes.
+pub trait ByteWrite {
+    /// The error type.
+    type Error: From<Error>;
+
+    /// Writes all bytes.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Implemented by sources of bytes.
+pub trait ByteRead {
+    /// The error type.
+    type Error: From<Error>;
+
+    /// Reads exactly enough bytes to fill the buffer.
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "std")]
+impl<W: ::std::io::Write> ByteWrite for W {
+    type Error = ::std::io::Error;
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.write_all(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: ::std::io::Read> ByteRead for R {
+    type Error = ::std::io::Error;
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.read_exact(buf)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl ByteWrite for Vec<u8> {
+    type Error = Error;
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl ByteRead for &[u8] {
+    type Error = Error;
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        if self.len() < buf.len() {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        let (a, b) = self.split_at(buf.len());
+        buf.copy_from_slice(a);
+        *self = b;
+        Ok(())
+    }
+}
+
+/// Implemented by the primitive number types.
+pub trait Value: Sized + Copy + Default {
+    /// Returns the type.
+    fn ty() -> Type;
+    /// Encodes value in little-endian format.
+    fn encode<W: ByteWrite>(&self, w: &mut W) -> Result<(), W::Error>;
+    /// Decodes value from little-endian format.
+    fn decode<R: ByteRead>(r: &mut R) -> Result<Self, R::Error>;
+}
+
+macro_rules! value {
+    ($t:ty, $ty:ident, $n:expr) => {
+        impl Value for $t {
+            #[inline]
+            fn ty() -> Type {Type::$ty}
+            fn encode<W: ByteWrite>(&self, w: &mut W) -> Result<(), W::Error> {
+                w.write_bytes(&self.to_le_bytes())
+            }
+            fn decode<R: ByteRead>(r: &mut R) -> Result<Self, R::Error> {
+                let mut buf = [0; $n];
+                r.read_bytes(&mut buf)?;
+                Ok(<$t>::from_le_bytes(buf))
+            }
+        }
+    }
+}
+
+value!(u8, U8, 1);
+value!(u16, U16, 2);
+value!(u32, U32, 4);
+value!(u64, U64, 8);
+value!(i8, I8, 1);
+value!(i16, I16, 2);
+value!(i32, I32, 4);
+value!(i64, I64, 8);
+value!(f32, F32, 4);
+value!(f64, F64, 8);
+
+/// Writes type format and property id.
+pub fn write_property_header<W: ByteWrite>(
+    type_format: u16,
+    property_id: u16,
+    w: &mut W
+) -> Result<(), W::Error> {
+    type_format.encode(w)?;
+    property_id.encode(w)
+}
+
+/// Writes number of bytes and offset instance id of a block.
+///
+/// The data of the block must follow.
+pub fn write_block_header<W: ByteWrite>(
+    bytes: u64,
+    offset: u64,
+    w: &mut W
+) -> Result<(), W::Error> {
+    bytes.encode(w)?;
+    offset.encode(w)
+}
+
+/// Writes end of bytes of a property.
+pub fn write_end_bytes<W: ByteWrite>(w: &mut W) -> Result<(), W::Error> {
+    0_u64.encode(w)
+}
+
+/// Writes end of stream.
+pub fn write_end<W: ByteWrite>(w: &mut W) -> Result<(), W::Error> {
+    0_u16.encode(w)
+}
+
+/// Writes an array of vectors, or an array of scalars when `dim` is 1.
+///
+/// The components are stored one vector after another,
+/// such that the number of components is a multiple of `dim`.
+pub fn write_array<W: ByteWrite, T: Value>(
+    property_id: u16,
+    dim: u8,
+    offset: u64,
+    components: &[T],
+    w: &mut W
+) -> Result<(), W::Error> {
+    let (ty, s) = match T::ty().vector(dim) {
+        Some(x) => x,
+        None => return Err(Error::new(ErrorKind::InvalidData).into()),
+    };
+    let n = components.len() as u64;
+    if !n.is_multiple_of(dim as u64) {
+        return Err(Error::new(ErrorKind::InvalidData).into());
+    }
+    write_property_header(ty, property_id, w)?;
+    if n > 0 {
+        write_block_header(s * n / dim as u64, offset, w)?;
+        for c in components {
+            c.encode(w)?;
+        }
+    }
+    write_end_bytes(w)
+}
+
+/// Reads type format and property id.
+///
+/// Returns `None` at the end of stream.
+pub fn read_property_header<R: ByteRead>(r: &mut R) -> Result<Option<(u16, u16)>, R::Error> {
+    let type_format = u16::decode(r)?;
+    if type_format == 0 {return Ok(None)}
+    let property_id = u16::decode(r)?;
+    Ok(Some((type_format, property_id)))
+}
+
+/// Reads number of bytes and offset instance id of a block.
+///
+/// Returns `None` at the end of bytes.
+pub fn read_block_header<R: ByteRead>(r: &mut R) -> Result<Option<(u64, u64)>, R::Error> {
+    let bytes = u64::decode(r)?;
+    if bytes == 0 {return Ok(None)}
+    let offset = u64::decode(r)?;
+    Ok(Some((bytes, offset)))
+}
+
+/// Reads an array of vectors, or an array of scalars when `dim` is 1.
+///
+/// Reads all blocks of the property and stores the components
+/// at their offsets, growing the vector when needed.
+///
+/// The vector grows to the largest offset in the data.
+/// Use `read_array_max` to bound it when the data is not trusted.
+pub fn read_array<R: ByteRead, T: Value>(
+    type_format: u16,
+    dim: u8,
+    components: &mut Vec<T>,
+    r: &mut R
+) -> Result<(), R::Error> {
+    read_array_max(type_format, dim, usize::MAX, components, r)
+}
+
+/// Reads an array like `read_array`, growing the vector to at most `max_len` components.
+///
+/// Returns an error of kind `ErrorKind::LimitExceeded`
+/// if a block ends after `max_len` components.
+pub fn read_array_max<R: ByteRead, T: Value>(
+    type_format: u16,
+    dim: u8,
+    max_len: usize,
+    components: &mut Vec<T>,
+    r: &mut R
+) -> Result<(), R::Error> {
+    let (ty, s) = match T::ty().vector(dim) {
+        Some(x) => x,
+        None => return Err(Error::new(ErrorKind::InvalidData).into()),
+    };
+    if ty != type_format {
+        return Err(Error::new(ErrorKind::InvalidData).into());
+    }
+    while let Some((bytes, offset)) = read_block_header(r)? {
+        if bytes % s != 0 {
+            return Err(Error::new(ErrorKind::InvalidData).into());
+        }
+        let n = bytes / T::ty().type_size();
+        let start = match offset.checked_mul(dim as u64) {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::InvalidData).into()),
+        };
+        let end = match start.checked_add(n) {
+            Some(x) if x <= usize::MAX as u64 => x as usize,
+            _ => return Err(Error::new(ErrorKind::InvalidData).into()),
+        };
+        if end > max_len && end > components.len() {
+            return Err(Error::new(ErrorKind::LimitExceeded).into());
+        }
+        let start = start as usize;
+        if components.len() < start {
+            if components.try_reserve(start - components.len()).is_err() {
+                return Err(Error::new(ErrorKind::InvalidData).into());
+            }
+            components.resize(start, T::default());
+        }
+        // Items past the end are pushed as they are decoded,
+        // such that a block header alone does not allocate its size.
+        for i in start..end {
+            let c = T::decode(r)?;
+            if i < components.len() {
+                components[i] = c;
+            } else {
+                components.push(c);
+            }
+        }
+    }
+    Ok(())
+}
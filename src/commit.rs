@@ -0,0 +1,279 @@
+//! Writing frames such that a crash never leaves a torn frame behind.
+//!
+//! Every frame is serialized into memory and written with one `write_all`.
+//! The committed length only advances after the write succeeded,
+//! and after syncing, depending on the `SyncPolicy`.
+//!
+//! With a journal, every frame is first written to `<path>.journal` and synced,
+//! then appended to the file.
+//! After a crash, `replay_journal` completes the frames in the journal,
+//! and `recover_file` writes the end of stream:
+//!
+//! ```ignore
+//! replay_journal("run.pool")?;
+//! recover_file("run.pool", &RecoverOptions::default())?;
+//! ```
+
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use checkpoint::hash_tail;
+use time::Time;
+
+/// When to sync the file to storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncPolicy {
+    /// Never sync, except when finishing.
+    ///
+    /// Frames are still written whole, but the operating system decides when they are stored.
+    Never,
+    /// Sync after every frame.
+    #[default]
+    EveryFrame,
+    /// Sync after every `n` frames.
+    EveryFrames(u64),
+}
+
+/// Options for committing frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommitOptions {
+    /// When to sync the file to storage.
+    pub sync: SyncPolicy,
+    /// Whether to write every frame to a journal first.
+    pub journal: bool,
+}
+
+impl CommitOptions {
+    /// Sets when to sync the file to storage.
+    pub fn sync(mut self, val: SyncPolicy) -> CommitOptions {
+        self.sync = val;
+        self
+    }
+
+    /// Sets whether to write every frame to a journal first.
+    pub fn journal(mut self, val: bool) -> CommitOptions {
+        self.journal = val;
+        self
+    }
+}
+
+/// Returns the path of the journal of a file, which is the path with `.journal` appended.
+pub fn journal_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut s: OsString = path.as_ref().as_os_str().to_owned();
+    s.push(".journal");
+    PathBuf::from(s)
+}
+
+/// Writes frames to a file, committing each frame as a whole.
+///
+/// Bytes written with `io::Write` are kept in memory until the frame is committed,
+/// which happens when the next frame begins, when calling `commit`, or when finishing.
+/// Bytes that are not committed are lost when the writer is dropped.
+///
+/// A journal record is the offset in the file (`u64`), the number of bytes (`u64`),
+/// the bytes, and a 64 bit FNV-1a hash of the offset and the bytes (`u64`),
+/// all in little endian byte order.
+/// With a journal, the `SyncPolicy` sets how often the file is synced,
+/// while the journal is synced for every frame.
+/// The journal is emptied each time the file is synced.
+///
+/// ```ignore
+/// let opts = CommitOptions::default().sync(SyncPolicy::EveryFrames(10)).journal(true);
+/// let mut w = CommitWriter::create("run.pool", TIME, opts)?;
+/// for step in 0..steps {
+///     w.begin_frame(step as f64 * dt)?;
+///     Vector::write_array(POSITION, &pos, &mut w)?;
+/// }
+/// w.finish()?;
+/// ```
+pub struct CommitWriter {
+    file: File,
+    journal: Option<(PathBuf, File)>,
+    options: CommitOptions,
+    time_property: u16,
+    buf: Vec<u8>,
+    committed_len: u64,
+    frames: u64,
+    committed_frames: u64,
+    unsynced: u64,
+}
+
+impl CommitWriter {
+    /// Creates a file, replacing an existing one, and an empty journal if enabled.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        time_property: u16,
+        options: CommitOptions
+    ) -> io::Result<CommitWriter> {
+        let file = File::create(&path)?;
+        let journal = if options.journal {
+            let journal_path = journal_path(&path);
+            let journal = File::create(&journal_path)?;
+            Some((journal_path, journal))
+        } else {None};
+        Ok(CommitWriter {
+            file,
+            journal,
+            options,
+            time_property,
+            buf: vec![],
+            committed_len: 0,
+            frames: 0,
+            committed_frames: 0,
+            unsynced: 0,
+        })
+    }
+
+    /// Commits the previous frame and starts a new frame at time `t`.
+    ///
+    /// Bytes written before the first frame, e.g. the file header,
+    /// are committed with the first frame.
+    pub fn begin_frame(&mut self, t: f64) -> io::Result<()> {
+        if self.frames > 0 {self.commit()?}
+        Time::write(self.time_property, t, &mut self.buf)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Commits the bytes written since the last commit.
+    ///
+    /// Syncs the file when the `SyncPolicy` says so.
+    /// On error, nothing after the committed length is part of the stream,
+    /// and the bytes are kept for the next commit.
+    pub fn commit(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {return Ok(())}
+        if let Some((_, ref mut journal)) = self.journal {
+            let mut record = Vec::with_capacity(self.buf.len() + 24);
+            record.extend_from_slice(&self.committed_len.to_le_bytes());
+            record.extend_from_slice(&(self.buf.len() as u64).to_le_bytes());
+            record.extend_from_slice(&self.buf);
+            record.extend_from_slice(&hash_tail(self.committed_len, &self.buf).to_le_bytes());
+            journal.write_all(&record)?;
+            journal.sync_data()?;
+        }
+        self.file.seek(SeekFrom::Start(self.committed_len))?;
+        self.file.write_all(&self.buf)?;
+        self.unsynced += 1;
+        let sync = match self.options.sync {
+            SyncPolicy::Never => false,
+            SyncPolicy::EveryFrame => true,
+            SyncPolicy::EveryFrames(n) => self.unsynced >= n,
+        };
+        if sync {self.sync()?}
+        self.committed_len += self.buf.len() as u64;
+        self.committed_frames = self.frames;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Syncs the file and empties the journal.
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        if let Some((_, ref mut journal)) = self.journal {
+            journal.set_len(0)?;
+            journal.seek(SeekFrom::Start(0))?;
+            journal.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the time property id.
+    pub fn time_property(&self) -> u16 {
+        self.time_property
+    }
+
+    /// Returns the number of bytes committed to the file.
+    pub fn committed_len(&self) -> u64 {
+        self.committed_len
+    }
+
+    /// Returns the number of frames begun.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of frames committed to the file.
+    pub fn committed_frames(&self) -> u64 {
+        self.committed_frames
+    }
+
+    /// Gets a reference to the file.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Commits the last frame, writes end of stream, syncs the file and removes the journal.
+    pub fn finish(mut self) -> io::Result<File> {
+        self.buf.extend_from_slice(&[0, 0]);
+        self.commit()?;
+        self.sync()?;
+        self.file.set_len(self.committed_len)?;
+        if let Some((path, journal)) = self.journal.take() {
+            drop(journal);
+            std::fs::remove_file(path)?;
+        }
+        Ok(self.file)
+    }
+}
+
+impl io::Write for CommitWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Does nothing, since bytes are only written by committing.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Completes the frames in the journal of a file after a crash.
+///
+/// Every complete record in `<path>.journal` is written to the file,
+/// which is then truncated after the last record, synced, and the journal is removed.
+/// A torn record at the end of the journal was never appended to the file, so it is ignored.
+/// Replaying twice gives the same result.
+///
+/// Returns the number of records replayed, or 0 when there is no journal.
+/// The file usually has no end of stream afterwards; use `recover_file` to write it.
+pub fn replay_journal<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    let journal_path = journal_path(&path);
+    let mut data = vec![];
+    match File::open(&journal_path) {
+        Ok(mut journal) => {journal.read_to_end(&mut data)?;}
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => return Err(err),
+    }
+
+    let mut file = OpenOptions::new().write(true).open(&path)?;
+    let mut records = 0;
+    let mut end = None;
+    let mut rest = &data[..];
+    while rest.len() >= 16 {
+        let offset = u64_at(rest, 0);
+        let len = u64_at(rest, 8);
+        if len > (rest.len() - 16) as u64 || rest.len() - 16 - (len as usize) < 8 {break}
+        let len_usize = len as usize;
+        let bytes = &rest[16..16 + len_usize];
+        if u64_at(rest, 16 + len_usize) != hash_tail(offset, bytes) {break}
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(bytes)?;
+        records += 1;
+        end = Some(offset + len);
+        rest = &rest[24 + len_usize..];
+    }
+    if let Some(end) = end {file.set_len(end)?}
+    file.sync_data()?;
+    std::fs::remove_file(&journal_path)?;
+    Ok(records)
+}
+
+fn u64_at(data: &[u8], i: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[i..i + 8]);
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,308 @@
+//! Compaction of streams into their final state.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, SeekFrom};
+
+use endian::{self, Endian, EndianReader, EndianWriter};
+use error::{Error, ErrorKind};
+use lazy::BlockLocation;
+use raw::{self, RawBlock};
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY, RLE_FORMAT};
+use State;
+
+/// Options for compacting a stream.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CompactOptions {
+    /// Property id that marks the start of a new frame.
+    ///
+    /// The frame marker is left out of the output, unless `keep_marker` is set.
+    /// Properties with the id of the frame marker and a custom format,
+    /// such as the time unit, are kept.
+    pub frame_marker: Option<u16>,
+    /// Write the frame marker of the last frame first,
+    /// such that the output is a single frame.
+    pub keep_marker: bool,
+}
+
+/// Where the data of a range of instances comes from.
+#[derive(Clone, Debug)]
+enum Source {
+    /// The byte position of the first instance in the stream.
+    Data(u64),
+    /// A repeated item of a run-length encoded block.
+    Run(Vec<u8>),
+}
+
+impl Source {
+    /// Returns the source of the instances starting `n` items later.
+    fn skip(&self, n: u64, size: u64) -> Source {
+        match *self {
+            Source::Data(pos) => Source::Data(pos + n * size),
+            Source::Run(ref item) => Source::Run(item.clone()),
+        }
+    }
+}
+
+/// The final state of a property with a built-in format.
+struct Property {
+    type_format: u16,
+    size: u64,
+    /// Maps the start of each range to its end and source.
+    ranges: BTreeMap<u64, (u64, Source)>,
+}
+
+impl Property {
+    /// Overwrites the instances `start..end`.
+    fn paint(&mut self, start: u64, end: u64, source: Source) {
+        if start >= end {return}
+        let mut covered = vec![];
+        for (&s, &(e, _)) in self.ranges.range(..end).rev() {
+            if e <= start {break}
+            covered.push(s);
+        }
+        for s in covered {
+            let (e, old) = self.ranges.remove(&s).unwrap();
+            if s < start {
+                self.ranges.insert(s, (start, old.clone()));
+            }
+            if e > end {
+                self.ranges.insert(end, (e, old.skip(end - s, self.size)));
+            }
+        }
+        self.ranges.insert(start, (end, source));
+    }
+
+    /// Reads the data of the ranges, merging contiguous ranges into blocks.
+    fn blocks<R: io::Read + io::Seek>(&self, prop: u16, r: &mut R) -> io::Result<Vec<RawBlock>> {
+        let mut blocks: Vec<RawBlock> = vec![];
+        let mut last_end = None;
+        for (&start, &(end, ref source)) in &self.ranges {
+            if last_end != Some(start) {
+                blocks.push(RawBlock {
+                    type_format: self.type_format,
+                    property_id: prop,
+                    offset: start,
+                    data: vec![],
+                });
+            }
+            last_end = Some(end);
+            let data = &mut blocks.last_mut().unwrap().data;
+            match *source {
+                Source::Data(pos) => {
+                    r.seek(SeekFrom::Start(pos))?;
+                    let n = (end - start) * self.size;
+                    if r.by_ref().take(n).read_to_end(data)? as u64 != n {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+                Source::Run(ref item) => {
+                    for _ in start..end {
+                        data.extend_from_slice(item);
+                    }
+                }
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+/// Copies the final state of a stream, leaving out the history.
+///
+/// Reading the output gives the same arrays as reading the whole input
+/// with `ReadMode::Merge`: for every instance of every property,
+/// only the data that was written last is kept.
+/// Blocks are merged into contiguous ranges sorted by offset.
+/// Run-length encoded properties are written as plain blocks.
+/// When the type format of a property changes, the blocks with the old format are left out.
+///
+/// Properties using other custom formats can not be merged,
+/// so only the last property with each custom format and property id is kept.
+/// The file header is written first.
+/// Other properties are sorted by property id and type format.
+///
+/// The stream is read twice: first the headers, to find which data is kept,
+/// then only the data that is kept.
+/// A big endian stream is converted in memory first, and the output is big endian too.
+/// The output is terminated with an end of stream.
+pub fn compact<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    opts: &CompactOptions
+) -> io::Result<()> {
+    let mut w = EndianWriter::new(w);
+    compact_until(r, &mut w, None, opts)?;
+    State::new().end_type_formats(&mut w)
+}
+
+/// Copies the final state of a stream up to a byte position, without end of stream.
+///
+/// Reading stops at the first property starting at or after `end`.
+/// The output is little endian, with the file header of the stream,
+/// so it should be written through an `EndianWriter`.
+pub(crate) fn compact_until<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    end: Option<u64>,
+    opts: &CompactOptions
+) -> io::Result<()> {
+    let start = r.stream_position()?;
+    let big = match endian::read_header(r, start)? {
+        Some((header, _)) => header.endian == Endian::Big,
+        None => false,
+    };
+    r.seek(SeekFrom::Start(start))?;
+    if !big {return compact_little(r, w, end, opts)}
+    let mut data = vec![];
+    let n = end.map(|end| end.saturating_sub(start)).unwrap_or(u64::MAX);
+    EndianReader::new(r).take(n).read_to_end(&mut data)?;
+    compact_little(&mut io::Cursor::new(data), w, end.map(|end| end.saturating_sub(start)), opts)
+}
+
+/// Copies the final state of a little endian stream up to a byte position.
+fn compact_little<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    end: Option<u64>,
+    opts: &CompactOptions
+) -> io::Result<()> {
+    let mut properties: BTreeMap<u16, Property> = BTreeMap::new();
+    let mut custom: BTreeMap<(u16, u16), Vec<BlockLocation>> = BTreeMap::new();
+    loop {
+        if let Some(end) = end {
+            if r.stream_position()? >= end {break}
+        }
+        let (state, ty, prop) = match raw::read_property_header(r)? {
+            Some(x) => x,
+            None => break,
+        };
+        let mut state = state;
+        let mut locations = vec![];
+        loop {
+            let (header, data_state) = state.read_block_header(ty, prop, r)?;
+            if header.is_end() {break}
+            let pos = r.stream_position()?;
+            if ty == RLE_FORMAT {
+                let mut data = vec![];
+                if r.by_ref().take(header.bytes).read_to_end(&mut data)? as u64 != header.bytes {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                paint_rle(&mut properties, prop, header.offset, &data)?;
+            } else {
+                if header.bytes > i64::MAX as u64 {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                r.seek(SeekFrom::Current(header.bytes as i64))?;
+                if let Some(size) = raw::item_size(ty).filter(|&size| size > 0) {
+                    let end = header.offset.checked_add(header.bytes / size)
+                        .ok_or_else(|| -> io::Error {
+                            Error::new(ErrorKind::InvalidData).with_property(prop).into()
+                        })?;
+                    property(&mut properties, ty, prop, size)
+                        .paint(header.offset, end, Source::Data(pos));
+                }
+            }
+            locations.push(BlockLocation {
+                type_format: ty,
+                position: pos,
+                bytes: header.bytes,
+                offset: header.offset,
+            });
+            state = data_state.end_data();
+        }
+        if raw::item_size(ty).is_none() && ty != RLE_FORMAT {
+            custom.insert((prop, ty), locations);
+        }
+    }
+
+    if let Some(locations) = custom.remove(&(HEADER_PROPERTY, HEADER_FORMAT)) {
+        write_custom(HEADER_FORMAT, HEADER_PROPERTY, &locations, r, w)?;
+    }
+    let marker = opts.frame_marker;
+    if let Some(marker) = marker.filter(|_| opts.keep_marker) {
+        if let Some(p) = properties.get(&marker) {
+            RawBlock::write_property(p.type_format, marker, &p.blocks(marker, r)?, w)?;
+        }
+        for (&(prop, ty), locations) in custom.iter().filter(|&(&(prop, _), _)| prop == marker) {
+            write_custom(ty, prop, locations, r, w)?;
+        }
+    }
+    // Plain properties are written before custom properties with the same id,
+    // such as the presence bitmap of an optional array.
+    let mut ids: Vec<u16> = properties.keys().cloned()
+        .chain(custom.keys().map(|&(prop, _)| prop))
+        .collect();
+    ids.sort();
+    ids.dedup();
+    for prop in ids {
+        if Some(prop) == marker && opts.keep_marker {continue}
+        if let Some(p) = properties.get(&prop).filter(|_| Some(prop) != marker) {
+            RawBlock::write_property(p.type_format, prop, &p.blocks(prop, r)?, w)?;
+        }
+        for (&(_, ty), locations) in custom.range((prop, 0)..=(prop, u16::MAX)) {
+            write_custom(ty, prop, locations, r, w)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the state of a property, clearing it when the type format changes.
+fn property(
+    properties: &mut BTreeMap<u16, Property>,
+    ty: u16,
+    prop: u16,
+    size: u64
+) -> &mut Property {
+    let p = properties.entry(prop).or_insert_with(|| Property {
+        type_format: ty,
+        size,
+        ranges: BTreeMap::new(),
+    });
+    if p.type_format != ty {
+        *p = Property {type_format: ty, size, ranges: BTreeMap::new()};
+    }
+    p
+}
+
+/// Overwrites the instances of a property with the runs of a run-length encoded block.
+fn paint_rle(
+    properties: &mut BTreeMap<u16, Property>,
+    prop: u16,
+    offset: u64,
+    data: &[u8]
+) -> io::Result<()> {
+    let err = || -> io::Error {Error::new(ErrorKind::InvalidData).with_property(prop).into()};
+    if data.len() < 2 {return Err(err())}
+    let ty = u16::from_le_bytes([data[0], data[1]]);
+    let size = raw::item_size(ty).filter(|&size| size > 0).ok_or_else(err)?;
+    if !(data.len() as u64 - 2).is_multiple_of(8 + size) {return Err(err())}
+    let p = property(properties, ty, prop, size);
+    let mut start = offset;
+    for run in data[2..].chunks(8 + size as usize) {
+        let mut count = [0; 8];
+        count.copy_from_slice(&run[..8]);
+        let end = start.checked_add(u64::from_le_bytes(count)).ok_or_else(err)?;
+        p.paint(start, end, Source::Run(run[8..].to_vec()));
+        start = end;
+    }
+    Ok(())
+}
+
+/// Copies the blocks of a property with a custom format.
+fn write_custom<R: io::Read + io::Seek, W: io::Write>(
+    ty: u16,
+    prop: u16,
+    locations: &[BlockLocation],
+    r: &mut R,
+    w: &mut W
+) -> io::Result<()> {
+    let mut blocks = vec![];
+    for loc in locations {
+        r.seek(SeekFrom::Start(loc.position))?;
+        let mut data = vec![];
+        if r.by_ref().take(loc.bytes).read_to_end(&mut data)? as u64 != loc.bytes {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        blocks.push(RawBlock {type_format: ty, property_id: prop, offset: loc.offset, data});
+    }
+    RawBlock::write_property(ty, prop, &blocks, w)
+}
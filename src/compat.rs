@@ -0,0 +1,282 @@
+//! Golden files that check compatibility with streams written by older versions.
+//!
+//! Enabled with the `test-util` feature.
+//! A directory of fixtures contains small `.pool` files and a `MANIFEST`
+//! with the expected contents of decoding each file.
+//! `verify_all` decodes every fixture with the current reader and compares with the manifest,
+//! and checks that the current writers still produce the same bytes.
+//! The fixtures of this crate are in `assets/compat` and are verified by `tests/compat.rs`,
+//! or with `cargo run --example compat --features test-util`.
+//!
+//! When a feature that affects the encoding lands, add a fixture for it to `fixtures`,
+//! then run `regenerate`, or the example with `--regenerate`,
+//! and check in the new file with the updated manifest.
+//! Existing fixtures are never rewritten, since they record what older versions wrote.
+//!
+//! The manifest has one section per fixture:
+//!
+//! ```ignore
+//! fixture <name>.pool
+//! header <version> <little|big> <alignment>
+//! property <type format> <property id> <item as hex>...
+//! custom <type format> <property id> <offset>:<data as hex>...
+//! ```
+//!
+//! Properties with built-in formats list their items after applying all blocks in order,
+//! and properties with custom formats list their blocks, both in the order they first appear.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use endian::Endian;
+use formats::CustomFormats;
+use frame::FrameWriter;
+use metadata::{write_metadata, Metadata, DESCRIPTION, NAME};
+use raw::{write_block, RawBlock};
+use reader::{PoolReader, Unknown};
+use read_write::{Matrix, Scalar, Vector};
+use rle::write_rle;
+use time::TimeUnit;
+use units::Units;
+use varint::write_varint_delta;
+use writer::PoolWriter;
+use State;
+
+/// The name of the manifest file.
+pub const MANIFEST: &str = "MANIFEST";
+
+/// Returns the name and bytes of every fixture, as written by the current version.
+pub fn fixtures() -> io::Result<Vec<(&'static str, Vec<u8>)>> {
+    let mut res = vec![];
+
+    let mut w = vec![];
+    1_u8.write_property(1, &mut w)?;
+    (-2_i16).write_property(2, &mut w)?;
+    3_u32.write_property(3, &mut w)?;
+    (-4_i64).write_property(4, &mut w)?;
+    0.5_f32.write_property(5, &mut w)?;
+    (-0.25_f64).write_property(6, &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("scalars.pool", w));
+
+    let mut w = vec![];
+    Scalar::write_array(1, &vec![1_u16, 2, 3], &mut w)?;
+    Vector::write_array(2, &vec![[1.0_f32, 2.0, 3.0], [4.0, 5.0, 6.0]], &mut w)?;
+    Matrix::write_array(3, &vec![[[1.0_f64, 2.0], [3.0, 4.0]]], &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("arrays.pool", w));
+
+    let mut w = vec![];
+    let (ty, _) = ::Type::I32.scalar();
+    let items = |v: &[i32]| -> Vec<u8> {v.iter().flat_map(|x| x.to_le_bytes()).collect()};
+    write_block(ty, 1, 0, &items(&[1, 2, 3, 4]), &mut w)?;
+    write_block(ty, 1, 2, &items(&[30, 40, 50]), &mut w)?;
+    RawBlock::write_property(ty, 2, &[
+        RawBlock {type_format: ty, property_id: 2, offset: 5, data: items(&[7])},
+        RawBlock {type_format: ty, property_id: 2, offset: 0, data: items(&[8, 9])},
+    ], &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("blocks.pool", w));
+
+    let mut w = FrameWriter::new(vec![], 0);
+    w.write_time_unit(&TimeUnit::Seconds)?;
+    for k in 0..3 {
+        w.begin_frame(k as f64 * 0.5)?;
+        Vector::write_array(1, &vec![[k as f32, 0.0]; k + 1], &mut w)?;
+    }
+    res.push(("frames.pool", w.finish()?));
+
+    let mut w = vec![];
+    let metadata = Metadata::new().with(NAME, "position").with(DESCRIPTION, "center of mass");
+    write_metadata(&[(1, &metadata)], &mut w)?;
+    Units::write(&[(1, "m", 1.0), (2, "ns", 1e-9)], &mut w)?;
+    Vector::write_array(1, &vec![[0.0_f64, 1.0, 2.0]], &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("metadata.pool", w));
+
+    let mut w = vec![];
+    let mut runs = vec![7_u8; 64];
+    runs.extend_from_slice(&[1, 2]);
+    write_rle(1, &runs, &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("rle.pool", w));
+
+    let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big);
+    Scalar::write_array(1, &vec![1_u32, 2, 0xdeadbeef], &mut w)?;
+    Vector::write_array(2, &vec![[1.5_f64, -2.5]], &mut w)?;
+    res.push(("big_endian.pool", w.finish()?));
+
+    let mut w = vec![];
+    let mut formats = CustomFormats::new();
+    let custom = ::Type::offset_custom_format();
+    formats.register(custom, 2, Some("channel: u32, adc: u16"))?;
+    formats.register(custom + 1, 1, None)?;
+    formats.write(&mut w)?;
+    RawBlock::write_property(custom, 1, &[
+        RawBlock {type_format: custom, property_id: 1, offset: 0, data: vec![1, 0, 0, 0, 2, 0]},
+    ], &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("formats.pool", w));
+
+    let mut w = vec![];
+    write_varint_delta(1, &[1000_u64, 1001, 1003, 1002, u64::MAX, 0], &mut w)?;
+    write_varint_delta(2, &[-5_i16, 7, i16::MIN], &mut w)?;
+    State::new().end_type_formats(&mut w)?;
+    res.push(("varint.pool", w));
+
+    let mut w = FrameWriter::new(vec![], 0).keyframe_interval(2);
+    for (i, x) in [[1.0_f32, 2.0], [1.0, 2.5], [1.5, 2.5]].iter().enumerate() {
+        w.begin_frame(i as f64)?;
+        w.write_xor_delta(1, x)?;
+    }
+    res.push(("xor.pool", w.finish()?));
+
+    Ok(res)
+}
+
+/// Decodes a stream into the lines of its manifest section, without the `fixture` line.
+pub fn describe(bytes: &[u8]) -> io::Result<Vec<String>> {
+    let mut r = PoolReader::new(bytes).unknown(Unknown::Collect).reserved_as_data(true);
+    while r.next_property()?.is_some() {}
+    let mut lines = vec![];
+    if let Some(header) = r.header() {
+        let endian = match header.endian {
+            Endian::Little => "little",
+            Endian::Big => "big",
+        };
+        lines.push(format!("header {} {} {}", header.version, endian, header.alignment));
+    }
+    let blocks = r.take_unknown_blocks();
+    let mut keys: Vec<(u16, u16)> = vec![];
+    for block in &blocks {
+        let key = (block.type_format, block.property_id);
+        if !keys.contains(&key) {keys.push(key)}
+    }
+    for (ty, prop) in keys {
+        let blocks = blocks.iter().filter(|b| b.type_format == ty && b.property_id == prop);
+        let mut line = match ::raw::item_size(ty) {
+            Some(size) => {
+                let mut items: Vec<&[u8]> = vec![];
+                let zero = vec![0; size as usize];
+                for block in blocks {
+                    for (i, item) in block.data.chunks(size as usize).enumerate() {
+                        let ind = block.offset as usize + i;
+                        if items.len() <= ind {items.resize(ind + 1, &zero)}
+                        items[ind] = item;
+                    }
+                }
+                let mut line = format!("property {} {}", ty, prop);
+                for item in items {
+                    line.push(' ');
+                    line.push_str(&hex(item));
+                }
+                line
+            }
+            None => {
+                let mut line = format!("custom {} {}", ty, prop);
+                for block in blocks {
+                    line.push_str(&format!(" {}:{}", block.offset, hex(&block.data)));
+                }
+                line
+            }
+        };
+        line.truncate(line.trim_end().len());
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// Writes the fixtures that are not in a directory yet and adds them to the manifest.
+///
+/// Existing fixtures and their manifest sections are kept.
+/// Returns the names of the fixtures that were added.
+pub fn regenerate<P: AsRef<Path>>(dir: P) -> io::Result<Vec<&'static str>> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let mut manifest = match fs::read_to_string(dir.join(MANIFEST)) {
+        Ok(text) => text,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            "# Expected contents of the fixtures, see `binpool::compat`.\n".into()
+        }
+        Err(err) => return Err(err),
+    };
+    let listed: Vec<String> = read_manifest(&manifest).into_iter().map(|(name, _)| name).collect();
+    let mut added = vec![];
+    for (name, bytes) in fixtures()? {
+        if listed.iter().any(|n| n == name) || dir.join(name).exists() {continue}
+        fs::write(dir.join(name), &bytes)?;
+        manifest.push_str(&format!("\nfixture {}\n", name));
+        for line in describe(&bytes)? {
+            manifest.push_str(&line);
+            manifest.push('\n');
+        }
+        added.push(name);
+    }
+    fs::write(dir.join(MANIFEST), manifest)?;
+    Ok(added)
+}
+
+/// Checks every fixture in a directory against the manifest and the current writers.
+///
+/// Returns the number of fixtures.
+/// Returns an error of kind `io::ErrorKind::InvalidData`, with a message naming the fixture,
+/// if a fixture decodes to other contents than in the manifest,
+/// if a fixture is not in the manifest or missing,
+/// or if the current version writes other bytes than the fixture.
+pub fn verify_all<P: AsRef<Path>>(dir: P) -> io::Result<usize> {
+    let dir = dir.as_ref();
+    let err = |name: &str, msg: String| -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("compat fixture `{}`: {}", name, msg))
+    };
+    let sections = read_manifest(&fs::read_to_string(dir.join(MANIFEST))?);
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.ends_with(".pool") && !sections.iter().any(|(n, _)| *n == name) {
+            return Err(err(&name, "not in the manifest".into()));
+        }
+    }
+    let current = fixtures()?;
+    for &(name, _) in &current {
+        if !sections.iter().any(|(n, _)| n == name) {
+            return Err(err(name, "not generated, run `compat::regenerate`".into()));
+        }
+    }
+    for (name, expected) in &sections {
+        let bytes = fs::read(dir.join(name)).map_err(|e| err(name, e.to_string()))?;
+        let lines = describe(&bytes).map_err(|e| err(name, format!("decoding failed: {}", e)))?;
+        if let Some(i) = (0..lines.len().max(expected.len()))
+            .find(|&i| lines.get(i) != expected.get(i)) {
+            return Err(err(name, format!(
+                "decoded `{}`, expected `{}`",
+                lines.get(i).map(|s| &s[..]).unwrap_or("<end>"),
+                expected.get(i).map(|s| &s[..]).unwrap_or("<end>"),
+            )));
+        }
+        if let Some((_, written)) = current.iter().find(|(n, _)| n == name) {
+            if *written != bytes {
+                return Err(err(name, "the current version writes other bytes".into()));
+            }
+        }
+    }
+    Ok(sections.len())
+}
+
+/// Reads the sections of a manifest.
+fn read_manifest(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut sections: Vec<(String, Vec<String>)> = vec![];
+    for line in text.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') {continue}
+        match (line.strip_prefix("fixture "), sections.last_mut()) {
+            (Some(name), _) => sections.push((name.trim().into(), vec![])),
+            (None, Some((_, lines))) => lines.push(line.into()),
+            (None, None) => {}
+        }
+    }
+    sections
+}
+
+/// Encodes bytes as lower case hex.
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
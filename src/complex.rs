@@ -0,0 +1,54 @@
+//! Complex numbers, enabled with the `num-complex` feature.
+//!
+//! A complex number is stored as a vector of dimension 2,
+//! with the real part first and the imaginary part second.
+//! This is the same layout as `[T; 2]`, so streams written either way
+//! can be read either way.
+
+use std::io;
+
+use num_complex::Complex;
+
+use read_write::{Element, Scalar, Vector};
+
+impl<T: Scalar> Vector for Complex<T> {
+    type Scalar = T;
+
+    #[inline]
+    fn dim() -> usize {2}
+    #[inline]
+    fn get(&self, ind: usize) -> &T {
+        if ind == 0 {&self.re} else {&self.im}
+    }
+    #[inline]
+    fn set(&mut self, ind: usize, val: T) {
+        if ind == 0 {self.re = val} else {self.im = val}
+    }
+}
+
+impl<T: Scalar> Element for Complex<T> {
+    type Scalar = T;
+
+    #[inline]
+    fn dim() -> [usize; 2] {[1, 2]}
+    fn write_element<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.re.write(w)?;
+        self.im.write(w)?;
+        Ok(())
+    }
+    fn read_element<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+        self.re.read(r)?;
+        self.im.read(r)?;
+        Ok(())
+    }
+}
+
+/// Converts pairs of real and imaginary parts into complex numbers.
+pub fn from_interleaved<T: Copy>(arr: &[[T; 2]]) -> Vec<Complex<T>> {
+    arr.iter().map(|x| Complex::new(x[0], x[1])).collect()
+}
+
+/// Converts complex numbers into pairs of real and imaginary parts.
+pub fn to_interleaved<T: Copy>(arr: &[Complex<T>]) -> Vec<[T; 2]> {
+    arr.iter().map(|x| [x.re, x.im]).collect()
+}
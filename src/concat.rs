@@ -0,0 +1,187 @@
+//! Concatenation of streams.
+//!
+//! Concatenating files with `cat` gives a stream that ends at the end of stream of the first file,
+//! such that readers silently ignore the rest.
+//! `concat` leaves out the end of stream of every input but the last,
+//! and the file header of every input but the first:
+//!
+//! ```ignore
+//! let inputs = vec![File::open("a.pool")?, File::open("b.pool")?];
+//! concat(inputs, &mut File::create("c.pool")?, &ConcatOptions::default())?;
+//! ```
+
+use std::io::{self, Write};
+
+use endian::{Endian, EndianReader, EndianWriter, Header};
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reserved::{
+    FOOTER_FORMAT, FOOTER_PROPERTY, FORMATS_FORMAT, HEADER_FORMAT, HEADER_PROPERTY,
+    METADATA_FORMAT, NAMESPACE_FORMAT, TIME_UNIT_FORMAT, UNITS_FORMAT,
+};
+
+/// What to do with the sticky properties of an input,
+/// such as units, metadata and namespace manifests.
+///
+/// The first sticky property of each kind in an input is compared with
+/// the last one of the same type format and property id in the inputs before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StickyPolicy {
+    /// Keep sticky properties without comparing them.
+    #[default]
+    Keep,
+    /// Return an error of kind `ErrorKind::PropertyConflict` when they differ.
+    Verify,
+    /// Leave out sticky properties that are equal.
+    Dedupe,
+}
+
+/// Options for concatenating streams.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConcatOptions {
+    /// What to do with the sticky properties of an input.
+    pub sticky: StickyPolicy,
+}
+
+impl ConcatOptions {
+    /// Sets what to do with the sticky properties of an input.
+    pub fn sticky(mut self, val: StickyPolicy) -> ConcatOptions {
+        self.sticky = val;
+        self
+    }
+}
+
+/// Counts the bytes read, to report positions in an input.
+struct Counted<R> {
+    inner: R,
+    position: u64,
+}
+
+impl<R: io::Read> io::Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+fn is_sticky(type_format: u16) -> bool {
+    matches!(type_format,
+        TIME_UNIT_FORMAT | NAMESPACE_FORMAT | UNITS_FORMAT | METADATA_FORMAT | FORMATS_FORMAT)
+}
+
+/// Concatenates streams into one stream, returning the number of bytes written.
+///
+/// Every property is copied verbatim, except:
+///
+/// - the end of stream of every input, which is written once at the end
+/// - the file header of every input but the first
+/// - footer indices, since their positions no longer hold
+/// - sticky properties left out by `StickyPolicy::Dedupe`
+///
+/// Bytes after the end of stream of an input, such as the trailer of a footer index,
+/// are left out. An input without an end of stream must end between two properties.
+/// Properties are copied with `raw::copy_property`, so when reading an input fails,
+/// the output may end inside a property.
+///
+/// The inputs must have the same byte order.
+/// Otherwise, an error of kind `ErrorKind::InvalidData` is returned
+/// for the header property, with the index of the input.
+pub fn concat<I, R, W>(inputs: I, w: &mut W, opts: &ConcatOptions) -> io::Result<u64>
+    where I: IntoIterator<Item = R>, R: io::Read, W: io::Write
+{
+    // Inputs are read in little endian, and written in the byte order of the first input.
+    let mut w = EndianWriter::new(w);
+    let mut endian = None;
+    let mut last_sticky: Vec<((u16, u16), Vec<u8>)> = vec![];
+    let mut written = 0;
+    for (file, r) in inputs.into_iter().enumerate() {
+        let mut r = Counted {inner: EndianReader::new(r), position: 0};
+        let mut seen: Vec<(u16, u16)> = vec![];
+        // Whether no property has been read yet, which may be the file header.
+        let mut start = true;
+        loop {
+            let position = r.position;
+            let (state, ty, prop) = match raw::read_property_header(&mut r) {
+                Ok(Some(x)) => x,
+                Ok(None) => {
+                    if start {check_endian(None, &mut endian, file)?}
+                    break;
+                }
+                Err(err) => return Err(torn(err, file, r.position)),
+            };
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY && start {
+                start = false;
+                let blocks = RawBlock::read_property(state, ty, prop, &mut r)
+                    .map_err(|err| torn(err, file, r.position))?;
+                check_endian(blocks.first().and_then(|b| Header::decode(&b.data)),
+                    &mut endian, file)?;
+                if file == 0 {
+                    let mut header = vec![];
+                    RawBlock::write_property(ty, prop, &blocks, &mut header)?;
+                    w.write_all(&header)?;
+                    written += header.len() as u64;
+                }
+                continue;
+            }
+            if start {
+                start = false;
+                check_endian(None, &mut endian, file)?;
+            }
+
+            let res = if ty == FOOTER_FORMAT && prop == FOOTER_PROPERTY {
+                raw::copy_property(state, ty, prop, &mut r, &mut io::sink()).map(|_| 0)
+            } else if opts.sticky != StickyPolicy::Keep && is_sticky(ty) {
+                let key = (ty, prop);
+                let mut data = vec![];
+                raw::copy_property(state, ty, prop, &mut r, &mut data)
+                    .map_err(|err| torn(err, file, r.position))?;
+                let first = !seen.contains(&key);
+                if first {seen.push(key)}
+                let same = last_sticky.iter().find(|s| s.0 == key).map(|s| s.1 == data);
+                if first && same == Some(false) && opts.sticky == StickyPolicy::Verify {
+                    return Err(Error::new(ErrorKind::PropertyConflict)
+                        .with_property(prop)
+                        .with_file(file)
+                        .with_position(position)
+                        .into());
+                }
+                let keep = !(first && same == Some(true) && opts.sticky == StickyPolicy::Dedupe);
+                if keep {w.write_all(&data)?}
+                let n = if keep {data.len() as u64} else {0};
+                match last_sticky.iter_mut().find(|s| s.0 == key) {
+                    Some(s) => s.1 = data,
+                    None => last_sticky.push((key, data)),
+                }
+                Ok(n)
+            } else {
+                raw::copy_property(state, ty, prop, &mut r, &mut w)
+            };
+            written += res.map_err(|err| torn(err, file, r.position))?;
+        }
+    }
+    w.write_all(&[0, 0])?;
+    Ok(written + 2)
+}
+
+/// Adds the input and position to an error of kind `io::ErrorKind::UnexpectedEof`.
+fn torn(err: io::Error, file: usize, position: u64) -> io::Error {
+    if err.kind() != io::ErrorKind::UnexpectedEof {return err}
+    Error::new(ErrorKind::UnexpectedEof).with_file(file).with_position(position).into()
+}
+
+/// Checks that an input has the byte order of the first input.
+fn check_endian(header: Option<Header>, endian: &mut Option<Endian>, file: usize) -> io::Result<()> {
+    let e = header.map(|h| h.endian).unwrap_or(Endian::Little);
+    match *endian {
+        None => *endian = Some(e),
+        Some(first) if first != e => {
+            return Err(Error::new(ErrorKind::InvalidData)
+                .with_property(HEADER_PROPERTY)
+                .with_file(file)
+                .into());
+        }
+        Some(_) => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Finding instances of a property that are never written.
+//!
+//! Each block of a property covers the instances `offset..offset + n`.
+//! Blocks with custom formats are not counted, except run-length encoded blocks.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::ops::Range;
+
+use endian::EndianReader;
+use raw;
+use reserved::RLE_FORMAT;
+use rle;
+
+/// The union of instance ranges written to a property.
+///
+/// Ranges are coalesced when inserted, such that many small adjacent ranges
+/// take the space of one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Coverage {
+    ranges: BTreeMap<u64, u64>,
+    instances: u64,
+    written: u64,
+    blocks: u64,
+    overlapping_blocks: u64,
+}
+
+impl Coverage {
+    /// Creates an empty coverage.
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Adds the instances `start..end`.
+    pub fn insert(&mut self, start: u64, end: u64) {
+        self.blocks += 1;
+        if start >= end {return}
+        self.written += end - start;
+        let mut new_start = start;
+        let mut new_end = end;
+        let mut overlap = 0;
+        let mut remove = vec![];
+        // Ranges are disjoint and sorted, so their ends are sorted too.
+        for (&s, &e) in self.ranges.range(..=end).rev() {
+            if e < start {break}
+            overlap += e.min(end).saturating_sub(s.max(start));
+            new_start = new_start.min(s);
+            new_end = new_end.max(e);
+            remove.push(s);
+        }
+        for s in remove {
+            self.ranges.remove(&s);
+        }
+        self.ranges.insert(new_start, new_end);
+        self.instances += end - start - overlap;
+        if overlap > 0 {self.overlapping_blocks += 1}
+    }
+
+    /// Returns the covered ranges, in order.
+    pub fn ranges(&self) -> Vec<Range<u64>> {
+        self.ranges.iter().map(|(&s, &e)| s..e).collect()
+    }
+
+    /// Returns the ranges from instance 0 to the end of the coverage that are not covered.
+    pub fn gaps(&self) -> Vec<Range<u64>> {
+        let mut gaps = vec![];
+        let mut pos = 0;
+        for (&s, &e) in &self.ranges {
+            if s > pos {gaps.push(pos..s)}
+            pos = e;
+        }
+        gaps
+    }
+
+    /// Returns the end of the last covered range.
+    pub fn end(&self) -> u64 {
+        self.ranges.values().next_back().cloned().unwrap_or(0)
+    }
+
+    /// Returns `true` if all instances from 0 to the end are covered.
+    pub fn is_complete(&self) -> bool {
+        self.ranges.len() <= 1 && self.ranges.keys().all(|&s| s == 0)
+    }
+
+    /// Returns the number of instances touched.
+    pub fn instances(&self) -> u64 {
+        self.instances
+    }
+
+    /// Returns the number of instances written, counting overlaps more than once.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Returns the number of instances written again after the first time.
+    pub fn overlap(&self) -> u64 {
+        self.written - self.instances
+    }
+
+    /// Returns the number of blocks.
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// Returns the number of blocks that overlap instances written before.
+    pub fn overlapping_blocks(&self) -> u64 {
+        self.overlapping_blocks
+    }
+}
+
+/// The coverage of a property in a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// The property id.
+    pub property_id: u16,
+    /// The coverage of the whole stream.
+    pub total: Coverage,
+    /// The coverage of each frame, when a frame marker is given.
+    ///
+    /// Blocks before the first frame marker are only counted in the total.
+    pub frames: Vec<Coverage>,
+}
+
+impl CoverageReport {
+    /// Creates an empty report for a property.
+    pub fn new(property_id: u16) -> CoverageReport {
+        CoverageReport {property_id, ..CoverageReport::default()}
+    }
+
+    pub(crate) fn start_frame(&mut self) {
+        self.frames.push(Coverage::new());
+    }
+
+    /// Adds a block of the property.
+    pub(crate) fn block(&mut self, type_format: u16, bytes: u64, offset: u64, data: &[u8]) {
+        let n = if type_format == RLE_FORMAT {
+            rle::rle_instances(data)
+        } else {
+            raw::item_size(type_format).filter(|&size| size > 0).map(|size| bytes / size)
+        };
+        if let Some(n) = n {
+            let end = offset.saturating_add(n);
+            self.total.insert(offset, end);
+            if let Some(frame) = self.frames.last_mut() {
+                frame.insert(offset, end);
+            }
+        }
+    }
+}
+
+/// Reports the instances of a property written in a stream.
+pub fn coverage<R: io::Read>(r: &mut R, property_id: u16) -> io::Result<CoverageReport> {
+    coverage_per_frame(r, property_id, None)
+}
+
+/// Reports the instances of a property written in a stream,
+/// also per frame when a frame marker is given.
+///
+/// A stream starting with a header is read in the byte order set by the header.
+pub fn coverage_per_frame<R: io::Read>(
+    r: &mut R,
+    property_id: u16,
+    frame_marker: Option<u16>
+) -> io::Result<CoverageReport> {
+    let r = &mut EndianReader::new(r);
+    let mut report = CoverageReport::new(property_id);
+    while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+        if Some(prop) == frame_marker {report.start_frame()}
+        let mut state = state;
+        loop {
+            let (header, data_state) = state.read_block_header(ty, prop, r)?;
+            if header.is_end() {break}
+            let mut data = vec![];
+            if prop == property_id && ty == RLE_FORMAT {
+                if (r.by_ref().take(header.bytes).read_to_end(&mut data)? as u64) < header.bytes {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            } else {
+                raw::copy_payload(header.bytes, r, &mut io::sink())?;
+            }
+            if prop == property_id {
+                report.block(ty, header.bytes, header.offset, &data);
+            }
+            state = data_state.end_data();
+        }
+    }
+    Ok(report)
+}
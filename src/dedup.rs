@@ -0,0 +1,171 @@
+//! Skipping properties that are written again with the same data.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io;
+
+use parse::{Event, Parser};
+use State;
+
+struct Last {
+    hash: u64,
+    bytes: Option<Vec<u8>>,
+}
+
+/// Skips properties that are identical to the last property written with the same id.
+///
+/// Since readers keep values until they are overwritten,
+/// a property that repeats the previous blocks of the same id does not change the result,
+/// when reading with `ReadMode::Merge`.
+/// Frames without the skipped property do not contain its blocks,
+/// so keyframes can be forced every N frames, such that seeking to a keyframe
+/// gives all properties:
+///
+/// ```ignore
+/// let mut w = DedupWriter::new(file).keyframes(TIME, 100);
+/// for step in 0..1000 {
+///     Time::write(TIME, step as f64, &mut w)?;
+///     Scalar::write_array(MASS, &masses, &mut w)?;
+/// }
+/// w.finish()?;
+/// ```
+///
+/// Each property is buffered until its end of bytes.
+/// The frame marker is never skipped.
+pub struct DedupWriter<W: io::Write> {
+    inner: W,
+    parser: Parser,
+    pending: Vec<u8>,
+    property: Option<u16>,
+    last: HashMap<u16, Last>,
+    compare_bytes: bool,
+    marker: Option<u16>,
+    keyframe_interval: u64,
+    frames: u64,
+    skipped: u64,
+}
+
+impl<W: io::Write> DedupWriter<W> {
+    /// Creates a new deduplicating writer, comparing hashes of properties.
+    pub fn new(inner: W) -> DedupWriter<W> {
+        DedupWriter {
+            inner,
+            parser: Parser::new(),
+            pending: vec![],
+            property: None,
+            last: HashMap::new(),
+            compare_bytes: false,
+            marker: None,
+            keyframe_interval: 0,
+            frames: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Sets whether to compare all bytes when hashes are equal.
+    ///
+    /// This guards against hash collisions, but keeps a copy of the last property per id.
+    pub fn compare_bytes(mut self, val: bool) -> DedupWriter<W> {
+        self.compare_bytes = val;
+        self
+    }
+
+    /// Writes every property in every `interval` frames, starting with the first frame.
+    ///
+    /// Frames start with the `marker` property.
+    pub fn keyframes(mut self, marker: u16, interval: u64) -> DedupWriter<W> {
+        self.marker = Some(marker);
+        self.keyframe_interval = interval;
+        self
+    }
+
+    /// Returns the number of properties skipped.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing directly to the underlying writer bypasses deduplication.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    ///
+    /// Returns an error if a property is not complete.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.parser.at_property_boundary() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        State::new().end_type_formats(&mut self)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn is_keyframe(&self) -> bool {
+        self.keyframe_interval > 0 && (self.frames - 1).is_multiple_of(self.keyframe_interval)
+    }
+
+    fn end_property(&mut self) -> io::Result<()> {
+        let prop = match self.property.take() {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&self.pending);
+        let hash = hasher.finish();
+        let force = Some(prop) == self.marker || (self.frames > 0 && self.is_keyframe());
+        let same = match self.last.get(&prop) {
+            Some(last) => last.hash == hash &&
+                last.bytes.as_ref().map(|bytes| *bytes == self.pending).unwrap_or(true),
+            None => false,
+        };
+        if same && !force {
+            self.skipped += 1;
+        } else {
+            self.inner.write_all(&self.pending)?;
+            let bytes = if self.compare_bytes {Some(self.pending.clone())} else {None};
+            self.last.insert(prop, Last {hash, bytes});
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl<W: io::Write> io::Write for DedupWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = buf;
+        while !data.is_empty() {
+            // Header fields are fed one byte at a time to find where properties end.
+            let n = (self.parser.remaining() as usize).min(data.len()).max(1);
+            let mut event = None;
+            self.parser.consume(&data[..n], |e| event = Some(e));
+            self.pending.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            match event {
+                Some(Event::Property {property_id, ..}) => {
+                    self.property = Some(property_id);
+                    if Some(property_id) == self.marker {self.frames += 1}
+                }
+                Some(Event::EndBytes) => self.end_property()?,
+                Some(Event::End) => {
+                    self.inner.write_all(&self.pending)?;
+                    self.pending.clear();
+                }
+                Some(Event::Block {..}) | None => {}
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
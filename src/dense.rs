@@ -0,0 +1,269 @@
+//! Compaction of sparse instance ids into dense indices.
+//!
+//! Instance ids from a global counter grow large and sparse,
+//! which makes reading into dense arrays impractical.
+//! An `IdMap` maps the sparse ids to the dense indices `0..n`,
+//! and `compact_copy` rewrites the offset instance ids of a stream with the map.
+//!
+//! The map is written to the output as a property with a reserved format and property id,
+//! with the sparse ids (u64) in the order of the dense indices,
+//! such that the original ids can be recovered with `IdMap::decode`.
+
+use std::collections::HashMap;
+use std::io;
+
+use endian::{self, EndianReader, EndianWriter};
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reserved::{
+    self, HEADER_FORMAT, HEADER_PROPERTY, ID_MAP_FORMAT, ID_MAP_PROPERTY, PADDING_FORMAT,
+    PADDING_PROPERTY, PRESENCE_FORMAT, RLE_FORMAT, VARINT_FORMAT, XOR_FORMAT,
+};
+use rle::expand_rle;
+use varint::expand_varint_delta;
+use State;
+
+/// A bijective map from sparse instance ids to dense indices.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdMap {
+    ids: Vec<u64>,
+    dense: HashMap<u64, u64>,
+    properties: Vec<u16>,
+}
+
+impl IdMap {
+    /// Creates a map where the dense index of an id is its position in `ids`.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` if an id occurs twice.
+    pub fn from_ids(ids: &[u64]) -> io::Result<IdMap> {
+        let mut dense = HashMap::with_capacity(ids.len());
+        for (i, &id) in ids.iter().enumerate() {
+            if dense.insert(id, i as u64).is_some() {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+        }
+        Ok(IdMap {ids: ids.into(), dense, properties: vec![]})
+    }
+
+    /// Scans a stream for the instance ids covered by blocks of properties,
+    /// and maps them to dense indices in increasing order.
+    ///
+    /// When `properties` is empty, every property with a built-in format is scanned,
+    /// including properties that are not indexed by particles, such as the time.
+    /// The map remaps the scanned properties only.
+    /// Run-length and delta encoded blocks are expanded.
+    pub fn scan<R: io::Read>(r: &mut R, properties: &[u16]) -> io::Result<IdMap> {
+        let r = &mut EndianReader::new(r);
+        let mut ranges: Vec<(u64, u64)> = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            let blocks = RawBlock::read_property(state, ty, prop, r)?;
+            if !remapped(properties, prop) {continue}
+            for block in blocks {
+                let block = if block.item_size().is_some() {block} else {
+                    match expand(&block)? {
+                        Some(x) => x,
+                        None => continue,
+                    }
+                };
+                let (start, end) = range(&block)?;
+                if start < end {ranges.push((start, end))}
+            }
+        }
+        ranges.sort_unstable();
+        let mut ids = vec![];
+        let mut next = 0;
+        for (start, end) in ranges {
+            for id in start.max(next)..end {ids.push(id)}
+            next = next.max(end);
+        }
+        Ok(IdMap::from_ids(&ids)?.properties(properties))
+    }
+
+    /// Sets the properties to remap.
+    ///
+    /// When empty, which is the default, every property with a built-in format is remapped.
+    pub fn properties(mut self, properties: &[u16]) -> IdMap {
+        self.properties = properties.into();
+        self
+    }
+
+    /// Returns the number of ids.
+    pub fn len(&self) -> u64 {
+        self.ids.len() as u64
+    }
+
+    /// Returns `true` if there are no ids.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the dense index of a sparse id.
+    pub fn dense(&self, id: u64) -> Option<u64> {
+        self.dense.get(&id).cloned()
+    }
+
+    /// Returns the sparse id of a dense index.
+    pub fn sparse(&self, index: u64) -> Option<u64> {
+        self.ids.get(index as usize).cloned()
+    }
+
+    /// Returns the sparse ids in the order of the dense indices.
+    pub fn ids(&self) -> &[u64] {
+        &self.ids
+    }
+
+    /// Writes the map.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut data = Vec::with_capacity(8 * self.ids.len());
+        for id in &self.ids {data.extend_from_slice(&id.to_le_bytes())}
+        RawBlock::write_property(ID_MAP_FORMAT, ID_MAP_PROPERTY, &[RawBlock {
+            type_format: ID_MAP_FORMAT,
+            property_id: ID_MAP_PROPERTY,
+            offset: 0,
+            data,
+        }], w)
+    }
+
+    /// Decodes a map from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<IdMap> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.type_format != ID_MAP_FORMAT || !block.data.len().is_multiple_of(8) {
+            return Err(err());
+        }
+        let ids: Vec<u64> = block.data.chunks(8).map(|chunk| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        }).collect();
+        IdMap::from_ids(&ids).map_err(|_| err())
+    }
+}
+
+/// Returns `true` if a property is remapped.
+fn remapped(properties: &[u16], property_id: u16) -> bool {
+    if properties.is_empty() {
+        !reserved::is_reserved_property(property_id)
+    } else {
+        properties.contains(&property_id)
+    }
+}
+
+/// Expands a run-length or delta encoded block into a block with a built-in format.
+///
+/// Returns `None` for custom formats that are copied unchanged,
+/// and an error of kind `ErrorKind::TypeMismatch` for formats that depend on instance ids.
+fn expand(block: &RawBlock) -> io::Result<Option<RawBlock>> {
+    match block.type_format {
+        RLE_FORMAT => expand_rle(block, None).map(Some),
+        VARINT_FORMAT => expand_varint_delta(block, None).map(Some),
+        PRESENCE_FORMAT | XOR_FORMAT => {
+            Err(Error::new(ErrorKind::TypeMismatch).with_property(block.property_id).into())
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the instance ids covered by a block with a built-in format.
+fn range(block: &RawBlock) -> io::Result<(u64, u64)> {
+    let size = block.item_size().unwrap_or(1).max(1);
+    let len = block.data.len() as u64;
+    if !len.is_multiple_of(size) {
+        return Err(Error::new(ErrorKind::BytesNotMultiple)
+            .with_property(block.property_id)
+            .with_item_size(len, size)
+            .into());
+    }
+    match block.offset.checked_add(len / size) {
+        Some(end) => Ok((block.offset, end)),
+        None => Err(Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()),
+    }
+}
+
+/// Splits a block into blocks of items with contiguous dense indices.
+fn remap(block: &RawBlock, map: &IdMap, out: &mut Vec<RawBlock>) -> io::Result<()> {
+    let (start, end) = range(block)?;
+    let size = block.item_size().unwrap_or(1).max(1) as usize;
+    let mut next = None;
+    for (i, id) in (start..end).enumerate() {
+        let ind = match map.dense(id) {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::InvalidData)
+                .with_property(block.property_id)
+                .with_instance(id)
+                .into()),
+        };
+        let item = &block.data[i * size..(i + 1) * size];
+        if next == Some(ind) {
+            out.last_mut().unwrap().data.extend_from_slice(item);
+        } else {
+            out.push(RawBlock {
+                type_format: block.type_format,
+                property_id: block.property_id,
+                offset: ind,
+                data: item.to_vec(),
+            });
+        }
+        next = Some(ind + 1);
+    }
+    Ok(())
+}
+
+/// Copies a stream, replacing the offset instance ids of remapped properties
+/// by their dense indices.
+///
+/// Blocks are split where consecutive instances map to indices that are not consecutive.
+/// Run-length and delta encoded blocks of remapped properties are expanded.
+/// The map is written after the header, or first when there is no header.
+/// Big endian streams are copied in their byte order.
+/// Since blocks change size, padding is left out and the header is written without alignment.
+/// Ids that are stored in data, such as events and alive masks, are not changed.
+///
+/// Returns an error of kind `ErrorKind::InvalidData`, with the property and instance,
+/// for an instance id that is not in the map,
+/// and of kind `ErrorKind::TypeMismatch` for presence bitmaps and XOR deltas
+/// of remapped properties.
+/// The output is terminated with an end of stream.
+pub fn compact_copy<R: io::Read, W: io::Write>(r: &mut R, w: &mut W, map: &IdMap) -> io::Result<()> {
+    let r = &mut EndianReader::new(r);
+    let w = &mut EndianWriter::new(w);
+    let mut first = true;
+    while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+        if first {
+            first = false;
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                endian::copy_unaligned_header(state, r, w)?;
+                map.write(w)?;
+                continue;
+            }
+            map.write(w)?;
+        }
+        if ty == PADDING_FORMAT && prop == PADDING_PROPERTY {
+            raw::copy_property(state, ty, prop, r, &mut io::sink())?;
+            continue;
+        }
+        if !remapped(&map.properties, prop) {
+            raw::copy_property(state, ty, prop, r, w)?;
+            continue;
+        }
+        let mut out = vec![];
+        let mut format = ty;
+        for block in RawBlock::read_property(state, ty, prop, r)? {
+            if block.item_size().is_some() {
+                remap(&block, map, &mut out)?;
+                continue;
+            }
+            match expand(&block)? {
+                Some(block) => {
+                    format = block.type_format;
+                    remap(&block, map, &mut out)?;
+                }
+                None => out.push(block),
+            }
+        }
+        RawBlock::write_property(format, prop, &out, w)?;
+    }
+    if first {map.write(w)?}
+    State::new().end_type_formats(w)
+}
@@ -0,0 +1,128 @@
+//! Calling handlers per property id.
+
+use std::collections::HashMap;
+use std::io;
+
+use alias::Aliases;
+use error::Error;
+use raw::{self, RawBlock};
+use read_write::Element;
+
+type Handler<'a> = Box<dyn FnMut(&RawBlock) -> io::Result<()> + 'a>;
+
+/// Calls registered handlers for properties as they arrive in a stream.
+///
+/// Handlers are closures that can borrow state mutably,
+/// such that several modules can register handlers for their own properties:
+///
+/// ```ignore
+/// let mut d = Dispatcher::new();
+/// d.on_scalar(TIME, |t: f32| {time = t; Ok(())});
+/// d.on_array(POSITION, |offset, items: &[[f32; 3]]| {
+///     physics.set_positions(offset, items);
+///     Ok(())
+/// });
+/// d.run(&mut file)?;
+/// ```
+///
+/// Handlers are called once per block.
+/// An error from a handler stops reading,
+/// and the error is annotated with the property id.
+#[derive(Default)]
+pub struct Dispatcher<'a> {
+    handlers: HashMap<u16, Handler<'a>>,
+    default: Option<Handler<'a>>,
+    aliases: Aliases,
+}
+
+impl<'a> Dispatcher<'a> {
+    /// Creates a new dispatcher without handlers.
+    pub fn new() -> Dispatcher<'a> {
+        Dispatcher {
+            handlers: HashMap::new(),
+            default: None,
+            aliases: Aliases::new(),
+        }
+    }
+
+    /// Registers a handler for blocks of an array property.
+    ///
+    /// The handler is called with the offset instance id and the decoded items.
+    pub fn on_array<T, F>(&mut self, property_id: u16, mut f: F)
+        where T: Element, F: 'a + FnMut(u64, &[T]) -> io::Result<()>
+    {
+        self.on_raw(property_id, move |block| {
+            let items = block.decode::<T>()?;
+            f(block.offset, &items)
+        });
+    }
+
+    /// Registers a handler for a single value property.
+    ///
+    /// Returns an error if a block does not contain exactly one item at offset 0.
+    pub fn on_scalar<T, F>(&mut self, property_id: u16, mut f: F)
+        where T: Element, F: 'a + FnMut(T) -> io::Result<()>
+    {
+        self.on_raw(property_id, move |block| {
+            let mut items = block.decode::<T>()?;
+            if block.offset != 0 || items.len() != 1 {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+            f(items.pop().unwrap_or_default())
+        });
+    }
+
+    /// Registers a handler for blocks without decoding.
+    pub fn on_raw<F>(&mut self, property_id: u16, f: F)
+        where F: 'a + FnMut(&RawBlock) -> io::Result<()>
+    {
+        self.handlers.insert(property_id, Box::new(f));
+    }
+
+    /// Registers a handler for blocks of properties without a handler.
+    ///
+    /// Without a default handler, these properties are skipped.
+    pub fn on_default<F>(&mut self, f: F)
+        where F: 'a + FnMut(&RawBlock) -> io::Result<()>
+    {
+        self.default = Some(Box::new(f));
+    }
+
+    /// Sets aliases of property ids.
+    ///
+    /// Blocks of old property ids are passed to the handler of the new property id,
+    /// renamed to the new property id.
+    pub fn aliases(&mut self, aliases: Aliases) {
+        self.aliases = aliases;
+    }
+
+    /// Reads until the end of stream, calling handlers.
+    pub fn run<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            let mut blocks = RawBlock::read_property(state, ty, prop, r)
+                .map_err(|err| with_property(err, prop))?;
+            let prop = self.aliases.resolve(prop);
+            for block in &mut blocks {
+                block.property_id = prop;
+            }
+            let handler = match self.handlers.get_mut(&prop) {
+                Some(x) => x,
+                None => match self.default {
+                    Some(ref mut x) => x,
+                    None => continue,
+                }
+            };
+            for block in &blocks {
+                handler(block).map_err(|err| with_property(err, prop))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn with_property(err: io::Error, property_id: u16) -> io::Error {
+    let kind = err.kind();
+    let res = Error::from_io(&err);
+    if res.property().is_some() {return err}
+    res.with_property(property_id).into_io(kind)
+}
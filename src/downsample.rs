@@ -0,0 +1,157 @@
+//! Downsampling of streams.
+
+use std::io;
+
+use endian::{EndianReader, EndianWriter};
+use error::{Error, ErrorKind};
+use limits::{Limits, Usage};
+use raw::{self, RawBlock};
+use reserved::{PRESENCE_FORMAT, RLE_FORMAT, VARINT_FORMAT, XOR_FORMAT};
+use rle::expand_rle;
+use varint::expand_varint_delta;
+use State;
+
+/// Options for downsampling a stream.
+#[derive(Clone, Copy, Debug)]
+pub struct DownsampleOptions {
+    /// Property id that marks the start of a new frame.
+    ///
+    /// This is required when keeping every k-th frame.
+    pub frame_marker: Option<u16>,
+    /// Keep every k-th frame, starting with the first one.
+    pub frame_step: u64,
+    /// Keep every k-th instance, starting with instance 0.
+    pub instance_step: u64,
+    /// Limits on the resources used when reading.
+    ///
+    /// Limits are checked for properties that are downsampled,
+    /// including the size of expanded encoded blocks.
+    pub limits: Limits,
+}
+
+impl Default for DownsampleOptions {
+    fn default() -> DownsampleOptions {
+        DownsampleOptions {
+            frame_marker: None,
+            frame_step: 1,
+            instance_step: 1,
+            limits: Limits::default(),
+        }
+    }
+}
+
+/// Copies a stream while keeping every k-th frame and/or every k-th instance.
+///
+/// Frames start at each property with the id of the frame marker.
+/// Properties before the first frame marker are always kept.
+///
+/// When keeping every k-th instance, instance `i` in the output
+/// corresponds to instance `i * k` in the input.
+/// Offsets and byte counts are rewritten accordingly.
+/// Since instance 0 is always kept, properties that are per-frame scalars
+/// pass through unchanged.
+/// Run-length and delta encoded blocks are expanded before they are downsampled,
+/// and presence bitmaps are downsampled like the array they belong to.
+/// Other properties using custom formats are copied without changes.
+///
+/// XOR deltas depend on the data of the previous frame, which is changed by downsampling.
+/// Returns an error of kind `ErrorKind::TypeMismatch` for XOR deltas,
+/// unless every frame and every instance is kept.
+///
+/// Big endian streams are copied in their byte order.
+/// The output is terminated with an end of stream.
+///
+/// To report progress or to cancel, wrap the reader in a `ProgressReader`.
+/// Properties that are kept unchanged are copied in chunks with `copy_property`,
+/// such that an error while reading can leave part of a property written.
+/// The writer is not flushed on error.
+pub fn downsample<R: io::Read, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    opts: &DownsampleOptions
+) -> io::Result<()> {
+    if opts.frame_step == 0 || opts.instance_step == 0 ||
+       opts.frame_step > 1 && opts.frame_marker.is_none() {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+
+    let r = &mut EndianReader::new(r);
+    let w = &mut EndianWriter::new(w);
+    let mut usage = Usage::new(opts.limits);
+    let mut frame: Option<u64> = None;
+    while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+        if Some(prop) == opts.frame_marker {
+            frame = Some(frame.map(|n| n + 1).unwrap_or(0));
+        }
+        if ty == XOR_FORMAT && (opts.frame_step > 1 || opts.instance_step > 1) {
+            return Err(Error::new(ErrorKind::TypeMismatch).with_property(prop).into());
+        }
+        if let Some(n) = frame {
+            if n % opts.frame_step != 0 {
+                raw::copy_property(state, ty, prop, r, &mut io::sink())?;
+                continue;
+            }
+        }
+        if opts.instance_step == 1 {
+            raw::copy_property(state, ty, prop, r, w)?;
+        } else {
+            usage.property(ty, prop)?;
+            let max_bytes = usage.max_block_bytes();
+            let mut format = ty;
+            let mut blocks = vec![];
+            for block in raw::read_blocks(state, ty, prop, &mut usage, r)? {
+                let block = match ty {
+                    RLE_FORMAT => expand_rle(&block, max_bytes)?,
+                    VARINT_FORMAT => expand_varint_delta(&block, max_bytes)?,
+                    _ => block,
+                };
+                format = block.type_format;
+                blocks.push(downsample_block(&block, opts.instance_step));
+            }
+            RawBlock::write_property(format, prop, &blocks, w)?;
+        }
+    }
+    State::new().end_type_formats(w)
+}
+
+/// Keeps every k-th instance of a block.
+fn downsample_block(block: &RawBlock, k: u64) -> RawBlock {
+    // The first instance in the block that is a multiple of `k`.
+    let skip = match block.offset % k {
+        0 => 0,
+        m => k - m,
+    };
+    let mut data = vec![];
+    let mut i = skip;
+    match block.item_size() {
+        Some(size) => {
+            let size = size as usize;
+            let n = (block.data.len() / size) as u64;
+            while i < n {
+                let start = i as usize * size;
+                data.extend_from_slice(&block.data[start..start + size]);
+                i += k;
+            }
+        }
+        None if block.type_format == PRESENCE_FORMAT => {
+            // One bit per instance.
+            let n = block.data.len() as u64 * 8;
+            let mut j = 0_u64;
+            while i < n {
+                if j.is_multiple_of(8) {data.push(0)}
+                if block.data[(i / 8) as usize] & (1 << (i % 8)) != 0 {
+                    *data.last_mut().unwrap() |= 1 << (j % 8);
+                }
+                i += k;
+                j += 1;
+            }
+        }
+        None => return block.clone(),
+    }
+    RawBlock {
+        type_format: block.type_format,
+        property_id: block.property_id,
+        offset: block.offset / k + if skip == 0 {0} else {1},
+        data,
+    }
+}
@@ -0,0 +1,119 @@
+//! Values whose type is only known when reading.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw;
+use read_write::{Element, Scalar};
+use Type;
+
+/// A value of a built-in type format, such as one item of an array.
+///
+/// The scalars are stored in little endian, row by row.
+///
+/// ```ignore
+/// for (t, value) in extract_series(&mut file, TIME, ENERGY, 0, &SeriesOptions::default())? {
+///     println!("{} {}", t, value.get(0).unwrap());
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DynValue {
+    type_format: u16,
+    data: Vec<u8>,
+}
+
+impl DynValue {
+    /// Creates a value from the little endian scalars of one item.
+    ///
+    /// Returns `None` for custom formats or if the data is not one item.
+    pub fn new(type_format: u16, data: Vec<u8>) -> Option<DynValue> {
+        match raw::item_size(type_format) {
+            Some(size) if size == data.len() as u64 => Some(DynValue {type_format, data}),
+            _ => None,
+        }
+    }
+
+    /// Returns the type format.
+    pub fn type_format(&self) -> u16 {
+        self.type_format
+    }
+
+    /// Returns the scalar type.
+    pub fn ty(&self) -> Type {
+        self.info().0
+    }
+
+    /// Returns the number of rows and columns.
+    ///
+    /// Scalars have dimensions 1x1 and vectors 1xN.
+    pub fn dim(&self) -> (u8, u8) {
+        let (_, rows, cols) = self.info();
+        (rows, cols)
+    }
+
+    /// Returns the number of scalars.
+    pub fn components(&self) -> usize {
+        let (rows, cols) = self.dim();
+        rows as usize * cols as usize
+    }
+
+    /// Returns the little endian scalars.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns scalar `i`, row by row, converted to `f64`.
+    ///
+    /// 64 bit integers beyond 2^53 lose precision.
+    pub fn get(&self, i: usize) -> Option<f64> {
+        let ty = self.ty();
+        let size = ty.type_size() as usize;
+        let bytes = self.data.get(i * size..(i + 1) * size)?;
+        let mut b = [0; 8];
+        b[..size].copy_from_slice(bytes);
+        Some(match ty {
+            Type::U8 => b[0] as f64,
+            Type::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::U64 => u64::from_le_bytes(b) as f64,
+            Type::I8 => b[0] as i8 as f64,
+            Type::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Type::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::I64 => i64::from_le_bytes(b) as f64,
+            Type::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Type::F64 => f64::from_le_bytes(b),
+        })
+    }
+
+    /// Returns the scalars converted to `f64`.
+    pub fn to_f64(&self) -> Vec<f64> {
+        (0..self.components()).filter_map(|i| self.get(i)).collect()
+    }
+
+    /// Decodes the value as an element type.
+    ///
+    /// Returns an error of kind `ErrorKind::TypeMismatch` if the type format differs.
+    pub fn decode<T: Element>(&self) -> io::Result<T> {
+        check_format::<T>(self.type_format)?;
+        let mut val: T = Default::default();
+        val.read_element(&mut &self.data[..])?;
+        Ok(val)
+    }
+
+    fn info(&self) -> (Type, u8, u8) {
+        Type::info(self.type_format).expect("checked by DynValue::new")
+    }
+}
+
+/// Checks that a type format is the format of an element type.
+pub(crate) fn check_format<T: Element>(type_format: u16) -> io::Result<()> {
+    match T::format() {
+        Some((format, _)) if format == type_format => Ok(()),
+        _ => {
+            let dim = T::dim();
+            Err(Error::new(ErrorKind::TypeMismatch)
+                .with_types((T::Scalar::ty(), dim[0] as u8, dim[1] as u8), type_format)
+                .into())
+        }
+    }
+}
@@ -0,0 +1,429 @@
+//! File header and byte order.
+//!
+//! The header is an optional property at the start of a stream,
+//! using a reserved custom format, such that legacy readers skip it.
+//! The header itself is always little endian.
+//! When the header sets big endian byte order, every field after the header,
+//! and every scalar in the data of built-in type formats, is big endian.
+//! Data of custom formats is never swapped.
+//!
+//! Header data:
+//!
+//! ```ignore
+//! magic: b"binpool\0", version: u16, flags: u16
+//! alignment: u16, when flag 2 is set
+//! ```
+
+use std::io;
+
+use error::Field;
+use parse::{Event, Parser};
+use raw::RawBlock;
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY};
+use Bytes;
+use State;
+use Type;
+
+const MAGIC: &[u8; 8] = b"binpool\0";
+
+/// Byte order of a stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Endian {
+    /// Little endian, the default.
+    #[default]
+    Little,
+    /// Big endian.
+    Big,
+}
+
+/// The file header.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Header {
+    /// Format version.
+    pub version: u16,
+    /// Byte order of the stream after the header.
+    pub endian: Endian,
+    /// Alignment in bytes of the data of the first block of every property,
+    /// or 0 when data is not aligned.
+    pub alignment: u16,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {version: Header::VERSION, endian: Endian::Little, alignment: 0}
+    }
+}
+
+impl Header {
+    /// The current format version.
+    pub const VERSION: u16 = 1;
+
+    /// Encodes the data of the header block.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&self.version.to_le_bytes());
+        let mut flags: u16 = match self.endian {
+            Endian::Little => 0,
+            Endian::Big => 1,
+        };
+        if self.alignment > 1 {flags |= 2}
+        data.extend_from_slice(&flags.to_le_bytes());
+        if self.alignment > 1 {
+            data.extend_from_slice(&self.alignment.to_le_bytes());
+        }
+        data
+    }
+
+    /// Decodes the data of the header block.
+    ///
+    /// Returns `None` if the data is not a header.
+    /// Bytes after the known fields are ignored.
+    pub fn decode(data: &[u8]) -> Option<Header> {
+        if data.len() < 12 || &data[..8] != MAGIC {return None}
+        let version = u16::from_le_bytes([data[8], data[9]]);
+        let flags = u16::from_le_bytes([data[10], data[11]]);
+        let endian = if flags & 1 == 0 {Endian::Little} else {Endian::Big};
+        let alignment = match data.get(12..14) {
+            Some(a) if flags & 2 != 0 => u16::from_le_bytes([a[0], a[1]]),
+            _ => 0,
+        };
+        Some(Header {version, endian, alignment})
+    }
+
+    /// Writes the header property.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        RawBlock::write_property(HEADER_FORMAT, HEADER_PROPERTY, &[RawBlock {
+            type_format: HEADER_FORMAT,
+            property_id: HEADER_PROPERTY,
+            offset: 0,
+            data: self.encode(),
+        }], w)
+    }
+}
+
+/// Reads the header at position `start` of a stream, returning it with the position after it.
+///
+/// Returns `None` when the stream does not start with a header, or the header is torn.
+pub(crate) fn read_header<R: io::Read + io::Seek>(
+    r: &mut R,
+    start: u64
+) -> io::Result<Option<(Header, u64)>> {
+    r.seek(io::SeekFrom::Start(start))?;
+    let (state, ty, prop) = match State::read(r) {
+        Ok((Some(state), ty, prop)) => (state, ty, prop),
+        Ok((None, _, _)) => return Ok(None),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if ty != HEADER_FORMAT || prop != HEADER_PROPERTY {return Ok(None)}
+    let blocks = match RawBlock::read_property(state, ty, prop, r) {
+        Ok(blocks) => blocks,
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match blocks.first().and_then(|b| Header::decode(&b.data)) {
+        Some(header) => Ok(Some((header, r.stream_position()?))),
+        None => Ok(None),
+    }
+}
+
+/// Copies the header property without alignment, for rewrites that move data,
+/// such that the padding of the stream no longer aligns it.
+///
+/// A header that does not decode is copied as is.
+pub(crate) fn copy_unaligned_header<R: io::Read, W: io::Write>(
+    state: State<Bytes>,
+    r: &mut R,
+    w: &mut W
+) -> io::Result<()> {
+    let blocks = RawBlock::read_property(state, HEADER_FORMAT, HEADER_PROPERTY, r)?;
+    match blocks.first().and_then(|b| Header::decode(&b.data)) {
+        Some(header) if blocks.len() == 1 => Header {alignment: 0, ..header}.write(w),
+        _ => RawBlock::write_property(HEADER_FORMAT, HEADER_PROPERTY, &blocks, w),
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Mode {
+    /// Reading the first property, which might be the header.
+    Detect,
+    Little,
+    Big,
+}
+
+/// Converts between the byte order of a stream and little endian.
+///
+/// The parser follows the little endian view of the stream.
+pub(crate) struct Converter {
+    parser: Parser,
+    mode: Mode,
+    header: Option<Header>,
+    header_data: Vec<u8>,
+    in_header: bool,
+    unit: [u8; 8],
+    filled: usize,
+}
+
+impl Default for Converter {
+    fn default() -> Converter {
+        Converter {
+            parser: Parser::new(),
+            mode: Mode::Detect,
+            header: None,
+            header_data: vec![],
+            in_header: false,
+            unit: [0; 8],
+            filled: 0,
+        }
+    }
+}
+
+impl Converter {
+    /// Creates a converter for the rest of a stream,
+    /// after the first property has been read.
+    pub fn resume(header: Option<Header>) -> Converter {
+        let mode = match header {
+            Some(Header {endian: Endian::Big, ..}) => Mode::Big,
+            _ => Mode::Little,
+        };
+        Converter {mode, header, ..Converter::default()}
+    }
+
+    /// Returns `true` when the converter is between two properties.
+    pub fn at_property_boundary(&self) -> bool {
+        self.filled == 0 && self.parser.at_property_boundary()
+    }
+
+    /// Returns the number of bytes left of the data of the current block.
+    pub fn remaining(&self) -> u64 {
+        self.parser.remaining().saturating_sub(self.filled as u64)
+    }
+
+    /// Returns the header, if it has been read.
+    pub fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Returns `true` while reading the header property.
+    pub fn in_header(&self) -> bool {
+        self.in_header
+    }
+
+    /// Returns `true` when bytes pass through without conversion.
+    pub fn is_passthrough(&self) -> bool {
+        self.mode == Mode::Little
+    }
+
+    /// Returns the number of bytes held until the unit they belong to is complete.
+    pub fn pending(&self) -> usize {
+        self.filled
+    }
+
+    /// Returns the size of the next unit that is swapped as a whole.
+    fn unit_size(&self) -> usize {
+        match self.parser.field() {
+            Field::TypeFormat | Field::PropertyId => 2,
+            Field::Bytes | Field::OffsetInstanceId => 8,
+            Field::Data => {
+                let size = self.parser.type_format()
+                    .and_then(Type::info)
+                    .map(|(ty, _, _)| ty.type_size())
+                    .unwrap_or(1);
+                size.min(self.parser.remaining()).max(1) as usize
+            }
+        }
+    }
+
+    /// Converts bytes, passing the converted bytes to `out`
+    /// and the events of the little endian view to `on_event`.
+    ///
+    /// Events are passed before the bytes that complete them,
+    /// such that an error from `on_event` stops the bytes from being passed to `out`.
+    ///
+    /// When `little` is `true`, the input is little endian and the output
+    /// uses the byte order of the stream. Otherwise, the other way around.
+    pub fn convert<O, E>(
+        &mut self,
+        mut data: &[u8],
+        little: bool,
+        mut out: O,
+        mut on_event: E
+    ) -> io::Result<()>
+        where O: FnMut(&[u8]) -> io::Result<()>,
+              E: FnMut(Event) -> io::Result<()>
+    {
+        while !data.is_empty() {
+            if self.mode == Mode::Little {
+                let mut res = Ok(());
+                self.parser.consume(data, |e| if res.is_ok() {res = on_event(e)});
+                res?;
+                return out(data);
+            }
+
+            let size = self.unit_size();
+            if size == 1 && self.filled == 0 && self.parser.field() == Field::Data &&
+               !self.in_header {
+                // Bytes are not swapped.
+                let n = (self.parser.remaining() as usize).min(data.len());
+                let mut res = Ok(());
+                self.parser.consume(&data[..n], |e| if res.is_ok() {res = on_event(e)});
+                res?;
+                out(&data[..n])?;
+                data = &data[n..];
+                continue;
+            }
+
+            let n = (size - self.filled).min(data.len());
+            self.unit[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            data = &data[n..];
+            if self.filled < size {continue}
+            self.filled = 0;
+
+            let mut unit = [0; 8];
+            unit[..size].copy_from_slice(&self.unit[..size]);
+            let swap = self.mode == Mode::Big;
+            if swap && !little {unit[..size].reverse()}
+            if self.in_header && self.parser.field() == Field::Data &&
+               self.header_data.len() < 256 {
+                self.header_data.extend_from_slice(&unit[..size]);
+            }
+            let mut event = None;
+            self.parser.consume(&unit[..size], |e| event = Some(e));
+            if swap && little {unit[..size].reverse()}
+            if let Some(e) = event {
+                self.detect(e);
+                on_event(e)?;
+            }
+            out(&unit[..size])?;
+        }
+        Ok(())
+    }
+
+    fn detect(&mut self, event: Event) {
+        // Bytes after the end of stream, such as the trailer of a footer, are not converted.
+        if event == Event::End {self.mode = Mode::Little}
+        if self.mode != Mode::Detect {return}
+        match event {
+            Event::Property {type_format, property_id} => {
+                if type_format == HEADER_FORMAT && property_id == HEADER_PROPERTY {
+                    self.in_header = true;
+                } else {
+                    self.mode = Mode::Little;
+                }
+            }
+            Event::EndBytes => {
+                self.in_header = false;
+                self.header = Header::decode(&self.header_data);
+                self.header_data = vec![];
+                self.mode = match self.header {
+                    Some(Header {endian: Endian::Big, ..}) => Mode::Big,
+                    _ => Mode::Little,
+                };
+            }
+            Event::End | Event::Block {..} => {}
+        }
+    }
+}
+
+/// Writes the little endian view of a stream in the byte order set by its header.
+///
+/// This is the inverse of reading a stream through `PoolReader`,
+/// for functions that rewrite a stream without changing its byte order.
+pub(crate) struct EndianWriter<W> {
+    inner: W,
+    converter: Converter,
+}
+
+impl<W: io::Write> EndianWriter<W> {
+    /// Creates a new writer at the start of a stream.
+    pub fn new(inner: W) -> EndianWriter<W> {
+        EndianWriter {inner, converter: Converter::default()}
+    }
+}
+
+impl<W: io::Write> io::Write for EndianWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.converter.convert(buf, true, |data| inner.write_all(data), |_| Ok(()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the little endian view of a stream in the byte order set by its header.
+///
+/// This is the conversion done by `PoolReader`, without its other checks,
+/// for functions that follow the fields of a stream themselves.
+/// Seeking is passed to the underlying reader once the stream is known to be little endian.
+/// Before, only seeking forward from the current position is supported,
+/// which reads the bytes in between.
+pub(crate) struct EndianReader<R> {
+    inner: R,
+    converter: Converter,
+    converted: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: io::Read> EndianReader<R> {
+    /// Creates a new reader at the start of a stream.
+    pub fn new(inner: R) -> EndianReader<R> {
+        EndianReader::with_converter(inner, Converter::default())
+    }
+
+    /// Creates a reader for the rest of a stream, starting at a property after the header.
+    pub fn resume(inner: R, header: Option<Header>) -> EndianReader<R> {
+        EndianReader::with_converter(inner, Converter::resume(header))
+    }
+
+    fn with_converter(inner: R, converter: Converter) -> EndianReader<R> {
+        EndianReader {inner, converter, converted: vec![], pos: 0}
+    }
+}
+
+impl<R: io::Read> io::Read for EndianReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos >= self.converted.len() {
+            if self.converter.is_passthrough() {return self.inner.read(buf)}
+            self.converted.clear();
+            self.pos = 0;
+            let n = self.inner.read(buf)?;
+            // A field that was cut off is not passed on, so the end is reported as an error.
+            if n == 0 && self.converter.pending() > 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            if n == 0 {return Ok(0)}
+            let converted = &mut self.converted;
+            self.converter.convert(&buf[..n], false, |data| {
+                converted.extend_from_slice(data);
+                Ok(())
+            }, |_| Ok(()))?;
+        }
+        let n = buf.len().min(self.converted.len() - self.pos);
+        buf[..n].copy_from_slice(&self.converted[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: io::Read + io::Seek> io::Seek for EndianReader<R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let buffered = |r: &EndianReader<R>| {
+            (r.converted.len() - r.pos + r.converter.pending()) as u64
+        };
+        if buffered(self) == 0 && self.converter.is_passthrough() {
+            return self.inner.seek(pos);
+        }
+        match pos {
+            io::SeekFrom::Current(n) if n >= 0 => {
+                io::copy(&mut io::Read::take(&mut *self, n as u64), &mut io::sink())?;
+                let position = self.inner.stream_position()?;
+                Ok(position - buffered(self))
+            }
+            _ => Err(io::ErrorKind::Unsupported.into()),
+        }
+    }
+}
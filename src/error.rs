@@ -0,0 +1,628 @@
+//! Errors that do not depend on `std::io`.
+
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use std::fmt;
+
+use Type;
+
+/// The kind of error.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// The data ended before a value was complete.
+    UnexpectedEof,
+    /// The data is not valid.
+    InvalidData,
+    /// The sink could not accept more bytes.
+    WriteZero,
+    /// An error from the underlying reader or writer.
+    Io,
+    /// A frame number outside the valid range.
+    FrameOutOfRange,
+    /// The operation was cancelled.
+    Cancelled,
+    /// A limit on resources was exceeded.
+    LimitExceeded,
+    /// A float value was NaN or infinite.
+    NonFinite,
+    /// A property has no unit or the scale of a unit is unknown.
+    UnknownUnit,
+    /// A property id is reserved for the crate.
+    ReservedProperty,
+    /// Dimensions of a vector or matrix type are not supported by the built-in formats.
+    DimensionOutOfRange,
+    /// A queue is full, such that the operation would block.
+    Busy,
+    /// A time is not after the previous time, or is NaN.
+    NonMonotonicTime,
+    /// A property id or name is already assigned to something else.
+    PropertyConflict,
+    /// There are no property ids left to assign.
+    PropertyIdsExhausted,
+    /// The type format of a property is not the type that was read.
+    TypeMismatch,
+    /// The bytes of a block are not a multiple of the item size.
+    BytesNotMultiple,
+    /// An iterator yielded a different number of items than its length.
+    LengthMismatch,
+    /// A stream does not match the checkpoint it is resumed from.
+    CheckpointMismatch,
+    /// A custom format in a stream has another version than the registered one.
+    CustomFormatVersionMismatch,
+    /// A stream has a format version that is newer than the crate supports.
+    UnsupportedVersion,
+    /// A frame does not satisfy an invariant.
+    InvariantViolation,
+    /// The bit split of packed instance ids in a stream is not the configured one.
+    PackedIdMismatch,
+    /// A reader lagged behind, and data it had not read was overwritten.
+    Overrun,
+    /// The writer of a shared stream restarted the stream.
+    ProducerRestarted,
+}
+
+impl ErrorKind {
+    fn description(&self) -> &'static str {
+        match *self {
+            ErrorKind::UnexpectedEof => "unexpected end of data",
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::WriteZero => "failed to write whole buffer",
+            ErrorKind::Io => "io error",
+            ErrorKind::FrameOutOfRange => "frame out of range",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::LimitExceeded => "limit exceeded",
+            ErrorKind::NonFinite => "non-finite value",
+            ErrorKind::UnknownUnit => "unknown unit",
+            ErrorKind::ReservedProperty => "reserved property id",
+            ErrorKind::DimensionOutOfRange => "dimension out of range",
+            ErrorKind::Busy => "busy",
+            ErrorKind::NonMonotonicTime => "time is not monotonic",
+            ErrorKind::PropertyConflict => "property id conflict",
+            ErrorKind::PropertyIdsExhausted => "no property ids left",
+            ErrorKind::TypeMismatch => "type mismatch",
+            ErrorKind::BytesNotMultiple => "bytes are not a multiple of the item size",
+            ErrorKind::LengthMismatch => "iterator length does not match its items",
+            ErrorKind::CheckpointMismatch => "stream does not match checkpoint",
+            ErrorKind::CustomFormatVersionMismatch => "custom format version mismatch",
+            ErrorKind::UnsupportedVersion => "unsupported format version",
+            ErrorKind::InvariantViolation => "invariant violated",
+            ErrorKind::PackedIdMismatch => "packed id split mismatch",
+            ErrorKind::Overrun => "overrun by the writer",
+            ErrorKind::ProducerRestarted => "the writer restarted",
+        }
+    }
+}
+
+/// A field in the format.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Field {
+    /// Type format of a property.
+    TypeFormat,
+    /// Property id.
+    PropertyId,
+    /// Number of bytes of a block.
+    Bytes,
+    /// Offset instance id of a block.
+    OffsetInstanceId,
+    /// Data of a block.
+    Data,
+}
+
+impl Field {
+    fn name(&self) -> &'static str {
+        match *self {
+            Field::TypeFormat => "type format",
+            Field::PropertyId => "property id",
+            Field::Bytes => "bytes",
+            Field::OffsetInstanceId => "offset instance id",
+            Field::Data => "data",
+        }
+    }
+}
+
+/// A limit on resources.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Limit {
+    /// Maximum number of bytes in a block.
+    BlockBytes,
+    /// Maximum number of bytes of all blocks.
+    TotalPayload,
+    /// Maximum number of blocks under one property header.
+    Blocks,
+    /// Maximum number of instances of a property.
+    InstancesPerProperty,
+    /// Maximum number of property headers.
+    Properties,
+}
+
+impl Limit {
+    pub(crate) fn name(&self) -> &'static str {
+        match *self {
+            Limit::BlockBytes => "max_block_bytes",
+            Limit::TotalPayload => "max_total_payload",
+            Limit::Blocks => "max_blocks",
+            Limit::InstancesPerProperty => "max_instances_per_property",
+            Limit::Properties => "max_properties",
+        }
+    }
+}
+
+/// A type format that was found in the stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// A built-in format, with type, rows and columns.
+    Builtin(Type, u8, u8),
+    /// A custom format.
+    Custom(u16),
+}
+
+impl Format {
+    /// Decodes a type format.
+    pub fn from_type_format(format: u16) -> Format {
+        match Type::info(format) {
+            Some((ty, rows, cols)) => Format::Builtin(ty, rows, cols),
+            None => Format::Custom(format),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Format::Builtin(ty, rows, cols) => write_type(f, ty, rows, cols),
+            Format::Custom(format) => write!(f, "custom format {}", format),
+        }
+    }
+}
+
+fn write_type(f: &mut fmt::Formatter, ty: Type, rows: u8, cols: u8) -> fmt::Result {
+    let name = match ty {
+        Type::U8 => "u8",
+        Type::U16 => "u16",
+        Type::U32 => "u32",
+        Type::U64 => "u64",
+        Type::I8 => "i8",
+        Type::I16 => "i16",
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+    };
+    match (rows, cols) {
+        (1, 1) => f.write_str(name),
+        (1, _) => write!(f, "{} vector of {}", name, cols),
+        _ => write!(f, "{} matrix {}x{}", name, rows, cols),
+    }
+}
+
+/// Details that only some kinds of errors have.
+///
+/// Where an error happened is kept next to the detail, in a `Location`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Detail {
+    /// Rows and columns.
+    Dimensions(u64, u64),
+    /// The bits of the previous time and the time,
+    /// such that errors can be compared.
+    Times(u64, u64),
+    /// The type that was expected and the format that was found.
+    Types((Type, u8, u8), Format),
+    /// Bytes of a block and the item size.
+    ItemSize(u64, u64),
+    /// A custom format, its version in the stream and the registered version.
+    Versions(u16, u32, u32),
+    /// The bits of particles of packed ids in the stream and configured.
+    Splits(u8, u8),
+}
+
+/// Where an error happened.
+///
+/// This is boxed, since few errors have it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+struct Location {
+    line: Option<u64>,
+    frame: Option<u64>,
+    event: Option<u64>,
+    file: Option<u32>,
+}
+
+/// An error when reading or writing.
+///
+/// When the `std` feature is enabled, this converts into `std::io::Error`,
+/// such that it can be recovered with `std::io::Error::get_ref`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    at_byte: Option<u64>,
+    property: Option<u16>,
+    field: Option<Field>,
+    range: Option<(u64, u64)>,
+    limit: Option<Limit>,
+    instance: Option<u64>,
+    detail: Option<Detail>,
+    location: Option<Box<Location>>,
+    #[cfg(feature = "std")]
+    message: Option<String>,
+}
+
+impl Error {
+    /// Creates a new error.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            at_byte: None,
+            property: None,
+            field: None,
+            range: None,
+            limit: None,
+            instance: None,
+            detail: None,
+            location: None,
+            #[cfg(feature = "std")]
+            message: None,
+        }
+    }
+
+    fn location(&mut self) -> &mut Location {
+        self.location.get_or_insert_with(Default::default)
+    }
+
+    /// Sets the byte position in the stream where the error happened.
+    pub fn with_position(mut self, at_byte: u64) -> Error {
+        self.at_byte = Some(at_byte);
+        self
+    }
+
+    /// Sets the property id that was read when the error happened.
+    pub fn with_property(mut self, property: u16) -> Error {
+        self.property = Some(property);
+        self
+    }
+
+    /// Sets the field that was read when the error happened.
+    pub fn with_field(mut self, field: Field) -> Error {
+        self.field = Some(field);
+        self
+    }
+
+    /// Sets the valid range, as `start..end`, of a value that was out of range.
+    pub fn with_range(mut self, start: u64, end: u64) -> Error {
+        self.range = Some((start, end));
+        self
+    }
+
+    /// Sets the limit that was exceeded.
+    pub fn with_limit(mut self, limit: Limit) -> Error {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets the instance of the property where the error happened.
+    pub fn with_instance(mut self, instance: u64) -> Error {
+        self.instance = Some(instance);
+        self
+    }
+
+    /// Sets the rows and columns of a type with unsupported dimensions.
+    pub fn with_dimensions(mut self, rows: u64, cols: u64) -> Error {
+        self.detail = Some(Detail::Dimensions(rows, cols));
+        self
+    }
+
+    /// Sets the previous time and the time that was not after it.
+    pub fn with_times(mut self, previous: f64, time: f64) -> Error {
+        self.detail = Some(Detail::Times(previous.to_bits(), time.to_bits()));
+        self
+    }
+
+    /// Sets the type that was expected, as type, rows and columns,
+    /// and the type format that was found.
+    pub fn with_types(mut self, expected: (Type, u8, u8), found_format: u16) -> Error {
+        self.detail = Some(Detail::Types(expected, Format::from_type_format(found_format)));
+        self
+    }
+
+    /// Sets the bytes of a block and the item size that they are not a multiple of.
+    pub fn with_item_size(mut self, bytes: u64, item_size: u64) -> Error {
+        self.detail = Some(Detail::ItemSize(bytes, item_size));
+        self
+    }
+
+    /// Sets the index of the file, among several files read as one stream,
+    /// where the error happened.
+    ///
+    /// The byte position is then relative to the start of the file.
+    pub fn with_file(mut self, file: usize) -> Error {
+        self.location().file = Some(file as u32);
+        self
+    }
+
+    /// Sets a custom format, its version in the stream and the registered version.
+    pub fn with_versions(mut self, format: u16, file_version: u32, local_version: u32) -> Error {
+        self.detail = Some(Detail::Versions(format, file_version, local_version));
+        self
+    }
+
+    /// Sets the event number where the error happened.
+    pub fn with_event(mut self, event: u64) -> Error {
+        self.location().event = Some(event);
+        self
+    }
+
+    /// Sets the bits of particles of packed ids in the stream and the configured bits.
+    pub fn with_splits(mut self, file_particle_bits: u8, local_particle_bits: u8) -> Error {
+        self.detail = Some(Detail::Splits(file_particle_bits, local_particle_bits));
+        self
+    }
+
+    /// Sets the line, starting at 1, of a text input where the error happened.
+    pub fn with_line(mut self, line: u64) -> Error {
+        self.location().line = Some(line);
+        self
+    }
+
+    /// Sets the frame number where the error happened.
+    pub fn with_frame(mut self, frame: u64) -> Error {
+        self.location().frame = Some(frame);
+        self
+    }
+
+    /// Returns the kind of error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the byte position in the stream, if known.
+    pub fn at_byte(&self) -> Option<u64> {
+        self.at_byte
+    }
+
+    /// Returns the property id, if known.
+    pub fn property(&self) -> Option<u16> {
+        self.property
+    }
+
+    /// Returns the field, if known.
+    pub fn field(&self) -> Option<Field> {
+        self.field
+    }
+
+    /// Returns the valid range, as `start..end`, if known.
+    pub fn range(&self) -> Option<(u64, u64)> {
+        self.range
+    }
+
+    /// Returns the limit that was exceeded, if any.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit
+    }
+
+    /// Returns the instance of the property, if known.
+    pub fn instance(&self) -> Option<u64> {
+        self.instance
+    }
+
+    /// Returns the index of the file, if known.
+    pub fn file(&self) -> Option<usize> {
+        self.location.as_ref().and_then(|l| l.file).map(|file| file as usize)
+    }
+
+    /// Returns a custom format, its version in the stream and the registered version, if any.
+    pub fn versions(&self) -> Option<(u16, u32, u32)> {
+        match self.detail {
+            Some(Detail::Versions(format, file_version, local_version)) => {
+                Some((format, file_version, local_version))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the bits of particles of packed ids in the stream and the configured bits, if any.
+    pub fn splits(&self) -> Option<(u8, u8)> {
+        match self.detail {
+            Some(Detail::Splits(file_particle_bits, local_particle_bits)) => {
+                Some((file_particle_bits, local_particle_bits))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the line of a text input, if known.
+    pub fn line(&self) -> Option<u64> {
+        self.location.as_ref().and_then(|l| l.line)
+    }
+
+    /// Returns the frame number, if known.
+    pub fn frame(&self) -> Option<u64> {
+        self.location.as_ref().and_then(|l| l.frame)
+    }
+
+    /// Returns the event number, if known.
+    pub fn event(&self) -> Option<u64> {
+        self.location.as_ref().and_then(|l| l.event)
+    }
+
+    /// Returns the rows and columns of a type with unsupported dimensions, if any.
+    pub fn dimensions(&self) -> Option<(u64, u64)> {
+        match self.detail {
+            Some(Detail::Dimensions(rows, cols)) => Some((rows, cols)),
+            _ => None,
+        }
+    }
+
+    /// Returns the previous time and the time that was not after it, if any.
+    pub fn times(&self) -> Option<(f64, f64)> {
+        match self.detail {
+            Some(Detail::Times(previous, time)) => {
+                Some((f64::from_bits(previous), f64::from_bits(time)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the type that was expected, as type, rows and columns, if any.
+    pub fn expected_type(&self) -> Option<(Type, u8, u8)> {
+        match self.detail {
+            Some(Detail::Types(expected, _)) => Some(expected),
+            _ => None,
+        }
+    }
+
+    /// Returns the type format that was found, if any.
+    pub fn found_format(&self) -> Option<Format> {
+        match self.detail {
+            Some(Detail::Types(_, found)) => Some(found),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes of a block and the item size, if any.
+    pub fn item_size(&self) -> Option<(u64, u64)> {
+        match self.detail {
+            Some(Detail::ItemSize(bytes, item_size)) => Some((bytes, item_size)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error {
+    /// Returns the error stored in an IO error, or converts it.
+    ///
+    /// Errors from the underlying reader or writer get the kind `ErrorKind::Io`
+    /// and keep their message.
+    pub fn from_io(err: &::std::io::Error) -> Error {
+        use std::io;
+
+        if let Some(err) = err.get_ref().and_then(|e| e.downcast_ref::<Error>()) {
+            return err.clone();
+        }
+        let kind = match err.kind() {
+            io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof,
+            io::ErrorKind::InvalidData => ErrorKind::InvalidData,
+            io::ErrorKind::WriteZero => ErrorKind::WriteZero,
+            _ => ErrorKind::Io,
+        };
+        let mut res = Error::new(kind);
+        if kind == ErrorKind::Io || err.get_ref().is_some() {
+            res.message = Some(err.to_string());
+        }
+        res
+    }
+
+    /// Converts into an IO error with the given kind.
+    pub fn into_io(self, kind: ::std::io::ErrorKind) -> ::std::io::Error {
+        ::std::io::Error::new(kind, self)
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error::new(kind)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "std")]
+        {
+            match self.message {
+                Some(ref msg) => f.write_str(msg)?,
+                None => f.write_str(self.kind.description())?,
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        f.write_str(self.kind.description())?;
+        if let Some(limit) = self.limit {
+            write!(f, " ({})", limit.name())?;
+        }
+        if let Some(field) = self.field {
+            write!(f, " when reading {}", field.name())?;
+        }
+        if let Some(property) = self.property {
+            write!(f, " of property {}", property)?;
+        }
+        if let Some(instance) = self.instance {
+            write!(f, " at instance {}", instance)?;
+        }
+        if let Some((rows, cols)) = self.dimensions() {
+            write!(f, " with dimensions {}x{}", rows, cols)?;
+        }
+        if let Some((previous, time)) = self.times() {
+            write!(f, " (time {} is not after {})", time, previous)?;
+        }
+        if let Some(Detail::Types((ty, rows, cols), found)) = self.detail {
+            f.write_str(": expected ")?;
+            write_type(f, ty, rows, cols)?;
+            write!(f, ", found {}", found)?;
+        }
+        if let Some((format, file_version, local_version)) = self.versions() {
+            write!(f, " of format {} (version {} in stream, {} registered)",
+                format, file_version, local_version)?;
+        }
+        if let Some((file, local)) = self.splits() {
+            write!(f, " (split {}/{} in stream, {}/{} configured)", 64 - file, file, 64 - local, local)?;
+        }
+        if let Some((bytes, item_size)) = self.item_size() {
+            write!(f, " ({} bytes, item size {})", bytes, item_size)?;
+        }
+        if let Some(at_byte) = self.at_byte {
+            write!(f, " at byte {}", at_byte)?;
+        }
+        if let Some(line) = self.line() {
+            write!(f, " at line {}", line)?;
+        }
+        if let Some(frame) = self.frame() {
+            write!(f, " in frame {}", frame)?;
+        }
+        if let Some(event) = self.event() {
+            write!(f, " in event {}", event)?;
+        }
+        if let Some(file) = self.file() {
+            write!(f, " in file {}", file)?;
+        }
+        if let Some((start, end)) = self.range {
+            write!(f, " (valid range {}..{})", start, end)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for ::std::io::Error {
+    fn from(err: Error) -> ::std::io::Error {
+        use std::io;
+
+        let kind = match err.kind {
+            ErrorKind::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            ErrorKind::InvalidData => io::ErrorKind::InvalidData,
+            ErrorKind::WriteZero => io::ErrorKind::WriteZero,
+            ErrorKind::Io => io::ErrorKind::Other,
+            ErrorKind::FrameOutOfRange => io::ErrorKind::InvalidInput,
+            ErrorKind::Cancelled => io::ErrorKind::Other,
+            ErrorKind::LimitExceeded => io::ErrorKind::InvalidData,
+            ErrorKind::NonFinite => io::ErrorKind::InvalidData,
+            ErrorKind::UnknownUnit => io::ErrorKind::InvalidInput,
+            ErrorKind::ReservedProperty => io::ErrorKind::InvalidInput,
+            ErrorKind::DimensionOutOfRange => io::ErrorKind::InvalidInput,
+            ErrorKind::Busy => io::ErrorKind::WouldBlock,
+            ErrorKind::NonMonotonicTime => io::ErrorKind::InvalidInput,
+            ErrorKind::PropertyConflict => io::ErrorKind::InvalidInput,
+            ErrorKind::PropertyIdsExhausted => io::ErrorKind::Other,
+            ErrorKind::TypeMismatch => io::ErrorKind::InvalidData,
+            ErrorKind::BytesNotMultiple => io::ErrorKind::InvalidData,
+            ErrorKind::LengthMismatch => io::ErrorKind::InvalidInput,
+            ErrorKind::CheckpointMismatch => io::ErrorKind::InvalidData,
+            ErrorKind::CustomFormatVersionMismatch => io::ErrorKind::InvalidData,
+            ErrorKind::UnsupportedVersion => io::ErrorKind::InvalidData,
+            ErrorKind::InvariantViolation => io::ErrorKind::InvalidData,
+            ErrorKind::PackedIdMismatch => io::ErrorKind::InvalidData,
+            ErrorKind::Overrun => io::ErrorKind::Other,
+            ErrorKind::ProducerRestarted => io::ErrorKind::ConnectionReset,
+        };
+        io::Error::new(kind, err)
+    }
+}
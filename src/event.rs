@@ -0,0 +1,412 @@
+//! Reading streams grouped into events.
+//!
+//! An event starts with an event marker property, an integer event number,
+//! and lasts until the next event marker, like a frame.
+//! Event numbers are not required to increase.
+//!
+//! An `EventIndex` is written as a stream of its own:
+//!
+//! ```ignore
+//! u16 scalar: event marker (0)
+//! u64 scalars: start (1) and end (2) of the indexed stream
+//! u64 arrays: event numbers (3) and byte positions of the event markers (4)
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+
+use alias::Aliases;
+use error::{Error, ErrorKind};
+use frame::{DuplicatePolicy, Frame, FrameReader};
+use index::{self, FrameCursor};
+use limits::{Limits, Usage};
+use raw::{self, RawBlock};
+use read_write::{Element, Scalar};
+use State;
+use Type;
+
+const MARKER: u16 = 0;
+const START: u16 = 1;
+const END: u16 = 2;
+const NUMBERS: u16 = 3;
+const POSITIONS: u16 = 4;
+
+/// The blocks of an event.
+#[derive(Clone, Debug)]
+pub struct Event {
+    /// The event number of the event marker.
+    pub number: u64,
+    /// The blocks of the event, where the frame number is the index of the event.
+    pub frame: Frame,
+}
+
+impl Event {
+    /// Returns the index of the event in the stream, starting at 0.
+    pub fn index(&self) -> u64 {
+        self.frame.number
+    }
+
+    /// Returns the blocks of the event, starting with the event marker.
+    pub fn blocks(&self) -> &[RawBlock] {
+        &self.frame.blocks
+    }
+
+    /// Returns `true` if the event contains blocks of a property.
+    pub fn contains(&self, property_id: u16) -> bool {
+        self.frame.contains(property_id)
+    }
+
+    /// Returns an iterator over the blocks of a property.
+    pub fn blocks_of(&self, property_id: u16) -> impl Iterator<Item = &RawBlock> {
+        self.frame.blocks_of(property_id)
+    }
+
+    /// Reads the blocks of a property into a new array.
+    ///
+    /// Blocks are applied in order at their offsets.
+    /// Items that are not covered by the blocks have the default value.
+    /// The array is empty if the event contains no blocks of the property.
+    pub fn array<T: Element>(&self, property_id: u16) -> io::Result<Vec<T>> {
+        let mut res = vec![];
+        self.frame.read_array(property_id, &mut res)?;
+        Ok(res)
+    }
+
+    /// Reads a property with a single value.
+    ///
+    /// Returns `None` if the event contains no blocks of the property.
+    pub fn property<T: Element>(&self, property_id: u16) -> io::Result<Option<T>> {
+        self.frame.read_property(property_id)
+    }
+}
+
+/// Reads events separated by an event marker property.
+///
+/// Blocks before the first event marker are stored as preamble.
+/// Blocks after the last event marker belong to the last event.
+pub struct EventReader<R> {
+    frames: FrameReader<R>,
+    marker: u16,
+}
+
+impl<R: io::Read> EventReader<R> {
+    /// Creates a new event reader.
+    pub fn new(r: R, marker: u16) -> EventReader<R> {
+        EventReader {
+            frames: FrameReader::new(r, marker),
+            marker,
+        }
+    }
+
+    /// Sets what happens when a property appears more than once in an event.
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> EventReader<R> {
+        self.frames = self.frames.on_duplicate(policy);
+        self
+    }
+
+    /// Sets limits on the resources used.
+    pub fn limits(mut self, limits: Limits) -> EventReader<R> {
+        self.frames = self.frames.limits(limits);
+        self
+    }
+
+    /// Sets aliases of property ids.
+    pub fn aliases(mut self, aliases: Aliases) -> EventReader<R> {
+        self.frames = self.frames.aliases(aliases);
+        self
+    }
+
+    /// Returns the event marker property id.
+    pub fn marker(&self) -> u16 {
+        self.marker
+    }
+
+    /// Returns the blocks before the first event.
+    ///
+    /// This is empty until the first event is read.
+    pub fn preamble(&self) -> &[RawBlock] {
+        self.frames.preamble()
+    }
+
+    /// Returns the number of events read.
+    pub fn events(&self) -> u64 {
+        self.frames.frames()
+    }
+
+    /// Reads the next event.
+    ///
+    /// Returns `None` when there are no more events.
+    /// Returns an error of kind `io::ErrorKind::InvalidData`, with the marker property,
+    /// if the event number is not one non-negative integer.
+    pub fn next_event(&mut self) -> io::Result<Option<Event>> {
+        let frame = match self.frames.next_frame()? {
+            None => return Ok(None),
+            Some(x) => x,
+        };
+        let number = match frame.blocks_of(self.marker).next() {
+            Some(block) => event_number(block),
+            None => None,
+        };
+        match number {
+            Some(number) => Ok(Some(Event {number, frame})),
+            None => Err(Error::new(ErrorKind::InvalidData).with_property(self.marker).into()),
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.frames.get_ref()
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.frames.get_mut()
+    }
+}
+
+/// Decodes an event number from a block with one integer.
+pub(crate) fn event_number(block: &RawBlock) -> Option<u64> {
+    let (ty, size) = match Type::info(block.type_format) {
+        Some((ty, 1, 1)) if ty != Type::F32 && ty != Type::F64 => (ty, ty.type_size() as usize),
+        _ => return None,
+    };
+    if block.data.len() != size {return None}
+    let signed = ty.type_id() >= Type::I8.type_id();
+    if signed && block.data[size - 1] & 0x80 != 0 {return None}
+    let mut bytes = [0; 8];
+    bytes[..size].copy_from_slice(&block.data);
+    Some(u64::from_le_bytes(bytes))
+}
+
+/// Determines what happens when an event number appears more than once when building an index.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DuplicateEvents {
+    /// Return an error of kind `io::ErrorKind::InvalidData`,
+    /// with the marker property and the byte position of the second event.
+    Error,
+    /// Keep all events, which are read in order with `EventIndex::read_events`.
+    KeepAll,
+}
+
+/// An event recorded by an event index.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EventEntry {
+    /// The event number.
+    pub number: u64,
+    /// The byte position of the event marker.
+    pub position: u64,
+}
+
+/// Maps event numbers to the byte ranges of events, for random access.
+///
+/// ```ignore
+/// let index = EventIndex::build(&mut file, EVENT, DuplicateEvents::Error)?;
+/// if let Some(event) = index.read_event(1_234_567, &mut file)? {
+///     let p4 = event.array::<[f64; 4]>(P4)?;
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventIndex {
+    marker: u16,
+    start: u64,
+    end: u64,
+    events: Vec<EventEntry>,
+    lookup: HashMap<u64, Vec<usize>>,
+}
+
+impl EventIndex {
+    /// Builds an index by scanning a stream from the current position.
+    ///
+    /// Only the event markers are read, the data of other properties is skipped.
+    pub fn build<R: io::Read + io::Seek>(
+        r: &mut R,
+        marker: u16,
+        duplicates: DuplicateEvents
+    ) -> io::Result<EventIndex> {
+        EventIndex::build_with_limits(r, marker, duplicates, Limits::default())
+    }
+
+    /// Builds an index, checking limits on the resources used.
+    pub fn build_with_limits<R: io::Read + io::Seek>(
+        r: &mut R,
+        marker: u16,
+        duplicates: DuplicateEvents,
+        limits: Limits
+    ) -> io::Result<EventIndex> {
+        let mut usage = Usage::new(limits);
+        let start = r.stream_position()?;
+        let mut end = start;
+        let mut events = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            usage.property(ty, prop)?;
+            if prop == marker {
+                let blocks = raw::read_blocks(state, ty, prop, &mut usage, r)?;
+                let number = match blocks.first().and_then(event_number) {
+                    Some(x) => x,
+                    None => return Err(Error::new(ErrorKind::InvalidData)
+                        .with_property(marker)
+                        .with_position(end)
+                        .into()),
+                };
+                events.push(EventEntry {number, position: end});
+            } else {
+                index::skip_property(state, ty, prop, &mut usage, r)?;
+            }
+            end = r.stream_position()?;
+        }
+        EventIndex::new(marker, start, end, events, duplicates)
+    }
+
+    fn new(
+        marker: u16,
+        start: u64,
+        end: u64,
+        events: Vec<EventEntry>,
+        duplicates: DuplicateEvents
+    ) -> io::Result<EventIndex> {
+        let mut lookup: HashMap<u64, Vec<usize>> = HashMap::with_capacity(events.len());
+        for (i, event) in events.iter().enumerate() {
+            let entry = lookup.entry(event.number).or_default();
+            if !entry.is_empty() && duplicates == DuplicateEvents::Error {
+                return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(marker)
+                    .with_position(event.position)
+                    .into());
+            }
+            entry.push(i);
+        }
+        Ok(EventIndex {marker, start, end, events, lookup})
+    }
+
+    /// Returns the event marker property id.
+    pub fn marker(&self) -> u16 {
+        self.marker
+    }
+
+    /// Returns the recorded events, in the order of the stream.
+    pub fn events(&self) -> &[EventEntry] {
+        &self.events
+    }
+
+    /// Returns the number of events.
+    pub fn event_count(&self) -> u64 {
+        self.events.len() as u64
+    }
+
+    /// Returns `true` if an event number is in the index.
+    pub fn contains(&self, number: u64) -> bool {
+        self.lookup.contains_key(&number)
+    }
+
+    /// Returns the indices of the events with an event number, in the order of the stream.
+    pub fn find(&self, number: u64) -> &[usize] {
+        self.lookup.get(&number).map(|x| &x[..]).unwrap_or(&[])
+    }
+
+    /// Returns the byte range of the stream before the first event.
+    pub fn preamble_range(&self) -> Range<u64> {
+        self.start..self.events.first().map(|e| e.position).unwrap_or(self.end)
+    }
+
+    /// Returns the byte range occupied by the event at an index.
+    ///
+    /// The range of the last event ends before the end of stream.
+    /// Returns an error of kind `ErrorKind::FrameOutOfRange`
+    /// with the valid range when the index is out of range.
+    pub fn event_range(&self, index: usize) -> io::Result<Range<u64>> {
+        let entry = match self.events.get(index) {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::FrameOutOfRange)
+                .with_range(0, self.event_count())
+                .into()),
+        };
+        let end = self.events.get(index + 1).map(|e| e.position).unwrap_or(self.end);
+        Ok(entry.position..end)
+    }
+
+    /// Reads the first event with an event number.
+    ///
+    /// Returns `None` if the event number is not in the index.
+    pub fn read_event<R: io::Read + io::Seek>(&self, number: u64, r: &mut R) -> io::Result<Option<Event>> {
+        match self.find(number).first() {
+            Some(&index) => self.read_event_at(index, r).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads all events with an event number, in the order of the stream.
+    pub fn read_events<R: io::Read + io::Seek>(&self, number: u64, r: &mut R) -> io::Result<Vec<Event>> {
+        self.find(number).iter().map(|&index| self.read_event_at(index, r)).collect()
+    }
+
+    /// Reads the event at an index.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidData`
+    /// if the stream does not match the index.
+    pub fn read_event_at<R: io::Read + io::Seek>(&self, index: usize, r: &mut R) -> io::Result<Event> {
+        let range = self.event_range(index)?;
+        r.seek(io::SeekFrom::Start(range.start))?;
+        let mut frames = FrameReader::new(io::Read::take(&mut *r, range.end - range.start), self.marker);
+        frames.resume(&FrameCursor {frame: index as u64, position: range.start, time: None})?;
+        let frame = match frames.next_frame()? {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidData.into()),
+        };
+        let number = frame.blocks_of(self.marker).next().and_then(event_number);
+        match number {
+            Some(number) if number == self.events[index].number => Ok(Event {number, frame}),
+            _ => Err(Error::new(ErrorKind::InvalidData).with_property(self.marker).into()),
+        }
+    }
+
+    /// Writes the index, followed by end of stream.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.marker.write_property(MARKER, w)?;
+        self.start.write_property(START, w)?;
+        self.end.write_property(END, w)?;
+        let numbers: Vec<u64> = self.events.iter().map(|e| e.number).collect();
+        let positions: Vec<u64> = self.events.iter().map(|e| e.position).collect();
+        Scalar::write_array(NUMBERS, &numbers, w)?;
+        Scalar::write_array(POSITIONS, &positions, w)?;
+        State::new().end_type_formats(w)
+    }
+
+    /// Reads an index written by `EventIndex::write`.
+    ///
+    /// Properties that are not known are skipped.
+    pub fn read<R: io::Read>(r: &mut R, duplicates: DuplicateEvents) -> io::Result<EventIndex> {
+        let mut marker: Option<u16> = None;
+        let (mut start, mut end) = (0_u64, 0_u64);
+        let mut numbers: Vec<u64> = vec![];
+        let mut positions: Vec<u64> = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            match prop {
+                MARKER => {
+                    let mut val = 0_u16;
+                    val.read_property(state, ty, r)?;
+                    marker = Some(val);
+                }
+                START => start.read_property(state, ty, r)?,
+                END => end.read_property(state, ty, r)?,
+                NUMBERS => Scalar::read_array(state, ty, &mut numbers, r)?,
+                POSITIONS => Scalar::read_array(state, ty, &mut positions, r)?,
+                _ => {RawBlock::read_property(state, ty, prop, r)?;}
+            }
+        }
+        let marker = match marker {
+            Some(x) => x,
+            None => return Err(Error::new(ErrorKind::InvalidData).with_property(MARKER).into()),
+        };
+        if numbers.len() != positions.len() ||
+           positions.windows(2).any(|w| w[0] >= w[1]) ||
+           positions.first().map(|&p| p < start).unwrap_or(false) ||
+           positions.last().map(|&p| p >= end).unwrap_or(false) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let events = numbers.into_iter().zip(positions)
+            .map(|(number, position)| EventEntry {number, position})
+            .collect();
+        EventIndex::new(marker, start, end, events, duplicates)
+    }
+}
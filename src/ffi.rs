@@ -0,0 +1,274 @@
+//! C interface for writing and reading blocks.
+//!
+//! The C header is `include/binpool.h`.
+//! To link with a C program, build the library with e.g.
+//! `cargo rustc --release --features ffi --crate-type staticlib`.
+//!
+//! Functions return `BINPOOL_OK` (0) on success, or an error code.
+//! A message describing the last error on the calling thread
+//! is returned by `binpool_last_error_message`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+
+use raw::{self, RawBlock};
+use State;
+
+/// No error.
+pub const BINPOOL_OK: c_int = 0;
+/// A null pointer was passed as argument.
+pub const BINPOOL_ERROR_NULL: c_int = 1;
+/// An IO error occured.
+pub const BINPOOL_ERROR_IO: c_int = 2;
+/// The data is not valid.
+pub const BINPOOL_ERROR_INVALID_DATA: c_int = 3;
+/// The callback stopped reading.
+pub const BINPOOL_ERROR_CALLBACK: c_int = 4;
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_error(code: c_int, msg: &str) -> c_int {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+    code
+}
+
+fn io_error(err: io::Error) -> c_int {
+    let code = match err.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => BINPOOL_ERROR_INVALID_DATA,
+        _ => BINPOOL_ERROR_IO,
+    };
+    set_error(code, &err.to_string())
+}
+
+/// Writes blocks to a file.
+pub struct BinpoolWriter {
+    w: BufWriter<File>,
+}
+
+/// Called for every block when reading.
+///
+/// Returns `BINPOOL_OK` to continue reading.
+pub type BinpoolBlockCallback = extern "C" fn(
+    user_data: *mut c_void,
+    type_format: u16,
+    property_id: u16,
+    offset: u64,
+    data: *const c_void,
+    len_bytes: u64
+) -> c_int;
+
+/// Returns the message of the last error on the calling thread.
+///
+/// The pointer is valid until the next call into the library from the same thread.
+#[no_mangle]
+pub extern "C" fn binpool_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ptr())
+}
+
+/// Creates a file and opens a writer.
+///
+/// Returns null on failure.
+///
+/// # Safety
+///
+/// `path` must be a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn binpool_writer_open_path(path: *const c_char) -> *mut BinpoolWriter {
+    if path.is_null() {
+        set_error(BINPOOL_ERROR_NULL, "path is null");
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(x) => x,
+        Err(_) => {
+            set_error(BINPOOL_ERROR_INVALID_DATA, "path is not valid UTF-8");
+            return ptr::null_mut();
+        }
+    };
+    match File::create(path) {
+        Ok(file) => Box::into_raw(Box::new(BinpoolWriter {w: BufWriter::new(file)})),
+        Err(err) => {
+            io_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Opens a writer on a file descriptor.
+///
+/// The writer takes ownership of the file descriptor.
+///
+/// # Safety
+///
+/// `fd` must be an open file descriptor that is not used elsewhere.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn binpool_writer_open_fd(fd: c_int) -> *mut BinpoolWriter {
+    use std::os::unix::io::FromRawFd;
+
+    if fd < 0 {
+        set_error(BINPOOL_ERROR_IO, "invalid file descriptor");
+        return ptr::null_mut();
+    }
+    let file = File::from_raw_fd(fd);
+    Box::into_raw(Box::new(BinpoolWriter {w: BufWriter::new(file)}))
+}
+
+/// Writes a property with a single block.
+///
+/// The data must be stored in little-endian format.
+/// For built-in type formats, `len_bytes` must be a multiple of the item size.
+///
+/// # Safety
+///
+/// `writer` must be returned from an open function and not finished or closed.
+/// `data` must point to `len_bytes` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn binpool_write_array(
+    writer: *mut BinpoolWriter,
+    type_format: u16,
+    property_id: u16,
+    offset: u64,
+    data: *const c_void,
+    len_bytes: u64
+) -> c_int {
+    if writer.is_null() || data.is_null() && len_bytes > 0 {
+        return set_error(BINPOOL_ERROR_NULL, "writer or data is null");
+    }
+    if type_format == 0 {
+        return set_error(BINPOOL_ERROR_INVALID_DATA, "type format 0 is end of stream");
+    }
+    if let Some(size) = raw::item_size(type_format) {
+        if !len_bytes.is_multiple_of(size) {
+            return set_error(BINPOOL_ERROR_INVALID_DATA,
+                "number of bytes is not a multiple of the item size");
+        }
+    }
+    let data = if len_bytes == 0 {&[][..]}
+        else {slice::from_raw_parts(data as *const u8, len_bytes as usize)};
+    let writer = &mut *writer;
+    let res = raw::write_block(type_format, property_id, offset, data, &mut writer.w);
+    match res {
+        Ok(_) => BINPOOL_OK,
+        Err(err) => io_error(err),
+    }
+}
+
+/// Writes end of stream and closes the writer.
+///
+/// The writer is freed even when an error is returned.
+///
+/// # Safety
+///
+/// `writer` must be returned from an open function and not finished or closed.
+#[no_mangle]
+pub unsafe extern "C" fn binpool_finish(writer: *mut BinpoolWriter) -> c_int {
+    if writer.is_null() {
+        return set_error(BINPOOL_ERROR_NULL, "writer is null");
+    }
+    let mut writer = Box::from_raw(writer);
+    let res = State::new().end_type_formats(&mut writer.w)
+        .and_then(|_| writer.w.flush());
+    match res {
+        Ok(()) => BINPOOL_OK,
+        Err(err) => io_error(err),
+    }
+}
+
+/// Closes the writer without writing end of stream.
+///
+/// # Safety
+///
+/// `writer` must be null, or returned from an open function and not finished or closed.
+#[no_mangle]
+pub unsafe extern "C" fn binpool_writer_close(writer: *mut BinpoolWriter) {
+    if !writer.is_null() {
+        drop(Box::from_raw(writer));
+    }
+}
+
+/// Reads a file, calling the callback for every block.
+///
+/// # Safety
+///
+/// `path` must be a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn binpool_read_path(
+    path: *const c_char,
+    callback: Option<BinpoolBlockCallback>,
+    user_data: *mut c_void
+) -> c_int {
+    if path.is_null() {
+        return set_error(BINPOOL_ERROR_NULL, "path is null");
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(x) => x,
+        Err(_) => return set_error(BINPOOL_ERROR_INVALID_DATA, "path is not valid UTF-8"),
+    };
+    match File::open(path) {
+        Ok(file) => read(file, callback, user_data),
+        Err(err) => io_error(err),
+    }
+}
+
+/// Reads from a file descriptor, calling the callback for every block.
+///
+/// The file descriptor is closed afterwards.
+///
+/// # Safety
+///
+/// `fd` must be an open file descriptor that is not used elsewhere.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn binpool_read_fd(
+    fd: c_int,
+    callback: Option<BinpoolBlockCallback>,
+    user_data: *mut c_void
+) -> c_int {
+    use std::os::unix::io::FromRawFd;
+
+    if fd < 0 {
+        return set_error(BINPOOL_ERROR_IO, "invalid file descriptor");
+    }
+    read(File::from_raw_fd(fd), callback, user_data)
+}
+
+fn read(file: File, callback: Option<BinpoolBlockCallback>, user_data: *mut c_void) -> c_int {
+    let callback = match callback {
+        Some(x) => x,
+        None => return set_error(BINPOOL_ERROR_NULL, "callback is null"),
+    };
+    let mut r = BufReader::new(file);
+    loop {
+        let (state, ty, prop) = match raw::read_property_header(&mut r) {
+            Ok(Some(x)) => x,
+            Ok(None) => return BINPOOL_OK,
+            Err(err) => return io_error(err),
+        };
+        let blocks = match RawBlock::read_property(state, ty, prop, &mut r) {
+            Ok(x) => x,
+            Err(err) => return io_error(err),
+        };
+        for block in &blocks {
+            let code = callback(
+                user_data,
+                block.type_format,
+                block.property_id,
+                block.offset,
+                block.data.as_ptr() as *const c_void,
+                block.data.len() as u64
+            );
+            if code != BINPOOL_OK {
+                return set_error(BINPOOL_ERROR_CALLBACK, "callback stopped reading");
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Reading instances that pass a predicate.
+
+use std::io;
+
+use dyn_value;
+use error::{Error, ErrorKind, Field};
+use read_write::Element;
+use Bytes;
+use State;
+
+/// Reads all blocks of a property, keeping the items that pass a predicate.
+///
+/// The predicate is called with the instance id and the item,
+/// and passing items are pushed with their instance ids, in the order they were read.
+/// Items are decoded one at a time, such that the whole array is never stored.
+///
+/// Returns an error of kind `ErrorKind::TypeMismatch` if the type format
+/// is not the format of the element type.
+///
+/// ```ignore
+/// let mut near: Vec<(u64, [f32; 3])> = vec![];
+/// read_array_filtered(state, ty, &mut r, |_, pos: &[f32; 3]| pos[2].abs() < 10.0, &mut near)?;
+/// ```
+pub fn read_array_filtered<T, R, F>(
+    state: State<Bytes>,
+    ty: u16,
+    r: &mut R,
+    mut pred: F,
+    out: &mut Vec<(u64, T)>
+) -> io::Result<()>
+    where T: Element, R: io::Read, F: FnMut(u64, &T) -> bool
+{
+    dyn_value::check_format::<T>(ty)?;
+    let size = T::format().map(|(_, size)| size).unwrap_or(1);
+    let mut state = state;
+    loop {
+        let mut bytes = 0;
+        let offset_state = state.read_bytes(&mut bytes, r)?;
+        if bytes == 0 {return Ok(())}
+        if !bytes.is_multiple_of(size) {
+            return Err(Error::new(ErrorKind::BytesNotMultiple).with_item_size(bytes, size).into());
+        }
+        let mut offset = 0;
+        let data_state = offset_state.read_offset_instance_id(&mut offset, r)?;
+        if offset.checked_add(bytes / size).is_none() {
+            return Err(Error::new(ErrorKind::InvalidData).with_field(Field::OffsetInstanceId).into());
+        }
+        for i in offset..offset + bytes / size {
+            let mut val: T = Default::default();
+            val.read_element(r)?;
+            if pred(i, &val) {out.push((i, val))}
+        }
+        state = data_state.end_data();
+    }
+}
@@ -0,0 +1,121 @@
+//! Checking float values for NaN and infinity.
+
+use error::{Error, ErrorKind, Field};
+use parse::{Event, Parser};
+use Type;
+
+/// Determines what happens to NaN and infinite values when reading.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum NonFinitePolicy {
+    /// Accept the values without checking.
+    #[default]
+    Allow,
+    /// Return an error with the property id and instance of the first value.
+    Error,
+    /// Count the values.
+    Count,
+}
+
+/// Finds NaN and infinite values of `f32` and `f64` properties
+/// while bytes pass through, in little endian.
+///
+/// Data is scanned directly in the buffers passed through,
+/// such that each value is checked once.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FiniteCheck {
+    parser: Parser,
+    unit: [u8; 8],
+    filled: usize,
+    scalars: u64,
+    offset: u64,
+    count: u64,
+}
+
+impl FiniteCheck {
+    /// Returns the number of non-finite values seen.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sets the number of non-finite values seen.
+    pub fn set_count(&mut self, count: u64) {
+        self.count = count;
+    }
+
+    /// Adds the non-finite values counted by another check.
+    pub fn add_count(&mut self, other: &FiniteCheck) {
+        self.count += other.count;
+    }
+
+    /// Checks bytes, returning an error at the first non-finite value.
+    pub fn check(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.scan(data, true)
+    }
+
+    /// Checks bytes, counting the non-finite values.
+    pub fn count_bytes(&mut self, data: &[u8]) {
+        let _ = self.scan(data, false);
+    }
+
+    fn scan(&mut self, mut data: &[u8], stop: bool) -> Result<(), Error> {
+        while !data.is_empty() {
+            if self.parser.field() != Field::Data {
+                // Headers are consumed one byte at a time to see where data begins.
+                let FiniteCheck {ref mut parser, ref mut offset, ref mut scalars, ..} = *self;
+                parser.consume(&data[..1], |e| if let Event::Block {offset: o, ..} = e {
+                    *offset = o;
+                    *scalars = 0;
+                });
+                data = &data[1..];
+                continue;
+            }
+
+            let n = (self.parser.remaining() as usize).min(data.len());
+            let info = self.parser.type_format().and_then(Type::info);
+            if let Some((ty @ Type::F32, rows, cols)) | Some((ty @ Type::F64, rows, cols)) = info {
+                let size = ty.type_size() as usize;
+                let per_item = rows as u64 * cols as u64;
+                let mut chunk = &data[..n];
+                if self.filled > 0 {
+                    let m = (size - self.filled).min(chunk.len());
+                    self.unit[self.filled..self.filled + m].copy_from_slice(&chunk[..m]);
+                    self.filled += m;
+                    chunk = &chunk[m..];
+                    if self.filled == size {
+                        self.filled = 0;
+                        let unit = self.unit;
+                        self.value(&unit[..size], per_item, stop)?;
+                    }
+                }
+                let mut values = chunk.chunks_exact(size);
+                for x in &mut values {
+                    self.value(x, per_item, stop)?;
+                }
+                let rest = values.remainder();
+                self.unit[..rest.len()].copy_from_slice(rest);
+                self.filled += rest.len();
+            }
+            self.parser.consume(&data[..n], |_| {});
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, x: &[u8], per_item: u64, stop: bool) -> Result<(), Error> {
+        let finite = if x.len() == 4 {
+            f32::from_le_bytes([x[0], x[1], x[2], x[3]]).is_finite()
+        } else {
+            f64::from_le_bytes([x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7]]).is_finite()
+        };
+        let instance = self.offset.saturating_add(self.scalars / per_item);
+        self.scalars += 1;
+        if finite {return Ok(())}
+        self.count += 1;
+        if !stop {return Ok(())}
+        let mut err = Error::new(ErrorKind::NonFinite).with_instance(instance);
+        if let Some(prop) = self.parser.property() {
+            err = err.with_property(prop);
+        }
+        Err(err)
+    }
+}
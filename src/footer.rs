@@ -0,0 +1,260 @@
+//! An index of properties appended when a stream is finished.
+//!
+//! The footer is a property using a reserved custom format, written before the end of stream,
+//! followed by a trailer after the end of stream, which readers of the stream never see:
+//!
+//! ```ignore
+//! properties
+//! footer: one entry per property, in the order of the stream
+//! end of stream
+//! trailer: magic: b"bpfooter", footer position: u64
+//! ```
+//!
+//! An entry is the position of the property (`u64`), its type format (`u16`),
+//! its property id (`u16`), a flag (`u32`) that is 1 when the property has a time,
+//! and the time (`f64`), all little endian.
+//! The time is set for `f32` and `f64` properties whose first block is one scalar,
+//! such that an `Index` can be built for any frame marker and time property.
+//! Positions are counted from the start of the stream.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+use endian::Converter;
+use error::Field;
+use parse::{Event, Parser};
+use raw::RawBlock;
+use reserved::{FOOTER_FORMAT, FOOTER_PROPERTY};
+use time::Time;
+use Type;
+
+const MAGIC: &[u8; 8] = b"bpfooter";
+
+/// The size of the trailer after the end of stream.
+pub(crate) const TRAILER: u64 = 16;
+
+/// The size of an entry.
+const ENTRY: u64 = 24;
+
+/// A property recorded in the footer.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) struct Entry {
+    pub position: u64,
+    pub type_format: u16,
+    pub property_id: u16,
+    pub time: Option<f64>,
+}
+
+/// Records the properties of a stream.
+pub(crate) struct Recorder {
+    parser: Parser,
+    base: u64,
+    entries: Vec<Entry>,
+    blocks: u64,
+    time: Option<RawBlock>,
+    end: Option<u64>,
+}
+
+impl Recorder {
+    /// Creates a recorder at position `base` of a stream, between two properties,
+    /// continuing from the entries before.
+    pub fn new(base: u64, entries: Vec<Entry>) -> Recorder {
+        Recorder {parser: Parser::new(), base, entries, blocks: 0, time: None, end: None}
+    }
+
+    /// Returns the position of the end of stream, once it has been consumed.
+    pub fn end(&self) -> Option<u64> {
+        self.end
+    }
+
+    /// Returns the position after the bytes consumed.
+    pub fn position(&self) -> u64 {
+        self.base + self.parser.position()
+    }
+
+    /// Returns the recorded entries.
+    pub fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+
+    /// Follows the little endian view of the stream, up to the end of stream.
+    pub fn consume(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && self.end.is_none() {
+            if self.parser.field() == Field::Data {
+                let n = self.parser.remaining().min(data.len() as u64) as usize;
+                if let Some(ref mut t) = self.time {t.data.extend_from_slice(&data[..n])}
+                self.parser.consume(&data[..n], |_| {});
+                data = &data[n..];
+                continue;
+            }
+
+            // Header fields are consumed a byte at a time, to know where a property starts.
+            let mut event = None;
+            self.parser.consume(&data[..1], |e| event = Some(e));
+            data = &data[1..];
+            match event {
+                Some(Event::Property {type_format, property_id}) => {
+                    self.entries.push(Entry {
+                        position: self.position() - 4,
+                        type_format,
+                        property_id,
+                        time: None,
+                    });
+                    self.blocks = 0;
+                }
+                Some(Event::Block {bytes, offset}) => {
+                    self.blocks += 1;
+                    self.decode_time();
+                    // Only the first block is decoded, like `Index::build` does.
+                    if let Some(e) = self.entries.last() {
+                        let scalar = e.type_format == Type::F32.scalar().0 ||
+                            e.type_format == Type::F64.scalar().0;
+                        if self.blocks == 1 && scalar && bytes <= 8 {
+                            self.time = Some(RawBlock {
+                                type_format: e.type_format,
+                                property_id: e.property_id,
+                                offset,
+                                data: vec![],
+                            });
+                        }
+                    }
+                }
+                Some(Event::EndBytes) => self.decode_time(),
+                Some(Event::End) => self.end = Some(self.position() - 2),
+                None => {}
+            }
+        }
+    }
+
+    fn decode_time(&mut self) {
+        if let Some(t) = self.time.take() {
+            if let (Ok(t), Some(e)) = (Time::decode(&t), self.entries.last_mut()) {
+                e.time = Some(t);
+            }
+        }
+    }
+}
+
+/// Encodes the footer property, in little endian byte order.
+pub(crate) fn encode(entries: &[Entry]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(entries.len() * ENTRY as usize);
+    for e in entries {
+        data.extend_from_slice(&e.position.to_le_bytes());
+        data.extend_from_slice(&e.type_format.to_le_bytes());
+        data.extend_from_slice(&e.property_id.to_le_bytes());
+        data.extend_from_slice(&(e.time.is_some() as u32).to_le_bytes());
+        data.extend_from_slice(&e.time.unwrap_or(0.0).to_bits().to_le_bytes());
+    }
+    let mut buf = Vec::with_capacity(data.len() + 28);
+    buf.extend_from_slice(&FOOTER_FORMAT.to_le_bytes());
+    buf.extend_from_slice(&FOOTER_PROPERTY.to_le_bytes());
+    if !data.is_empty() {
+        buf.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        buf.extend_from_slice(&0_u64.to_le_bytes());
+        buf.extend_from_slice(&data);
+    }
+    buf.extend_from_slice(&0_u64.to_le_bytes());
+    buf
+}
+
+/// Encodes the trailer, given the position of the footer from the start of the stream.
+pub(crate) fn trailer(position: u64) -> [u8; TRAILER as usize] {
+    let mut buf = [0; TRAILER as usize];
+    buf[..8].copy_from_slice(MAGIC);
+    buf[8..].copy_from_slice(&position.to_le_bytes());
+    buf
+}
+
+/// Returns `true` if the bytes are a trailer.
+pub(crate) fn is_trailer(data: &[u8]) -> bool {
+    data.len() as u64 == TRAILER && data[..8] == MAGIC[..]
+}
+
+/// A footer read from a stream, with positions from the start of the stream.
+pub(crate) struct Footer {
+    /// The position of the footer property.
+    pub position: u64,
+    /// The position of the end of stream.
+    pub end: u64,
+    /// The entries.
+    pub entries: Vec<Entry>,
+}
+
+fn u64_at(data: &[u8], i: usize, big: bool) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[i..i + 8]);
+    if big {u64::from_be_bytes(bytes)} else {u64::from_le_bytes(bytes)}
+}
+
+/// Reads the footer of a stream starting at position `start`.
+///
+/// Returns `None` when the stream does not end with a footer, end of stream and trailer.
+/// The position of the reader is unspecified afterwards.
+pub(crate) fn read<R: Read + Seek>(r: &mut R, start: u64) -> io::Result<Option<Footer>> {
+    let len = r.seek(SeekFrom::End(0))?;
+    // A footer without blocks takes 12 bytes, followed by the end of stream.
+    if len < start + 14 + TRAILER {return Ok(None)}
+    let end = len - TRAILER - 2;
+    let mut buf = [0; TRAILER as usize + 2];
+    r.seek(SeekFrom::Start(end))?;
+    r.read_exact(&mut buf)?;
+    if buf[..2] != [0, 0] || &buf[2..10] != MAGIC {return Ok(None)}
+    let position = match start.checked_add(u64_at(&buf, 10, false)) {
+        Some(x) if x <= end - 12 => x,
+        _ => return Ok(None),
+    };
+
+    let mut head = [0; 12];
+    r.seek(SeekFrom::Start(position))?;
+    r.read_exact(&mut head)?;
+    // The fields before the data follow the byte order of the stream.
+    let (ty, prop) = ([head[0], head[1]], [head[2], head[3]]);
+    let big = match (u16::from_le_bytes(ty), u16::from_be_bytes(ty), u16::from_le_bytes(prop)) {
+        (FOOTER_FORMAT, _, FOOTER_PROPERTY) => false,
+        (_, FOOTER_FORMAT, FOOTER_PROPERTY) => true,
+        _ => return Ok(None),
+    };
+    let bytes = u64_at(&head, 4, big);
+    let size = if bytes == 0 {12} else {bytes.saturating_add(28)};
+    if !bytes.is_multiple_of(ENTRY) || size != end - position {return Ok(None)}
+
+    let mut data = vec![0; (size - 12) as usize];
+    r.read_exact(&mut data)?;
+    if bytes > 0 && (u64_at(&data, 0, big) != 0 || u64_at(&data, data.len() - 8, big) != 0) {
+        return Ok(None);
+    }
+    let entries = if bytes == 0 {vec![]} else {
+        data[8..data.len() - 8].chunks(ENTRY as usize).map(|e| Entry {
+            position: u64_at(e, 0, false),
+            type_format: u16::from_le_bytes([e[8], e[9]]),
+            property_id: u16::from_le_bytes([e[10], e[11]]),
+            time: if e[12..16] == [0; 4] {None} else {Some(f64::from_bits(u64_at(e, 16, false)))},
+        }).collect()
+    };
+    Ok(Some(Footer {position: position - start, end: end - start, entries}))
+}
+
+/// Scans a stream from its start, recording its properties up to the end of stream.
+///
+/// Returns an error of kind `io::ErrorKind::UnexpectedEof`
+/// if the data ends inside a property.
+pub(crate) fn scan<R: Read>(r: &mut R) -> io::Result<Recorder> {
+    let mut converter = Converter::default();
+    let mut recorder = Recorder::new(0, vec![]);
+    let mut buf = vec![0; 64 << 10];
+    while recorder.end.is_none() {
+        let n = match r.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        converter.convert(&buf[..n], false, |out| {
+            recorder.consume(out);
+            Ok(())
+        }, |_| Ok(()))?;
+    }
+    if recorder.end.is_none() && !recorder.parser.at_property_boundary() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(recorder)
+}
@@ -0,0 +1,180 @@
+//! Versions of custom formats.
+//!
+//! Applications that define custom formats register a version for each,
+//! and optionally a description of the layout.
+//! The registry is written as a block with a reserved custom format,
+//! such that readers know which version of a format wrote a stream.
+//!
+//! Data of the block is a list of entries:
+//!
+//! ```ignore
+//! format: u16, version: u32, layout length: u32 (u32::MAX without layout), layout (UTF-8)
+//! ```
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use reserved::{self, FORMATS_FORMAT, FORMATS_PROPERTY};
+use Bytes;
+use State;
+use Type;
+
+/// The version and layout of a custom format.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CustomFormat {
+    /// The version of the layout.
+    pub version: u32,
+    /// A description of the layout, e.g. a list of fields.
+    pub layout: Option<String>,
+}
+
+/// Registered custom formats.
+///
+/// ```ignore
+/// let mut formats = CustomFormats::new();
+/// formats.register(HIT_FORMAT, 2, Some("channel: u32, adc: u16, tdc: u16"))?;
+/// formats.write(&mut file)?;
+///
+/// let mut reader = PoolReader::new(file).custom_formats(formats);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct CustomFormats {
+    formats: Vec<(u16, CustomFormat)>,
+}
+
+impl CustomFormats {
+    /// Creates an empty registry.
+    pub fn new() -> CustomFormats {
+        CustomFormats::default()
+    }
+
+    /// Registers a custom format, replacing an earlier registration of the format.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if the format is built-in or reserved for the crate.
+    pub fn register(&mut self, format: u16, version: u32, layout: Option<&str>) -> io::Result<()> {
+        if format < Type::offset_custom_format() || reserved::is_reserved_format(format) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        self.set(format, CustomFormat {version, layout: layout.map(|s| s.into())});
+        Ok(())
+    }
+
+    /// Returns the version and layout of a custom format.
+    pub fn get(&self, format: u16) -> Option<&CustomFormat> {
+        self.formats.iter().find(|&&(f, _)| f == format).map(|(_, custom)| custom)
+    }
+
+    /// Returns `true` if no format is registered.
+    pub fn is_empty(&self) -> bool {
+        self.formats.is_empty()
+    }
+
+    /// Returns an iterator over formats with versions and layouts.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &CustomFormat)> {
+        self.formats.iter().map(|&(format, ref custom)| (format, custom))
+    }
+
+    /// Adds formats from another registry, replacing formats that are registered in both.
+    pub fn merge(&mut self, other: &CustomFormats) {
+        for (format, custom) in other.iter() {
+            self.set(format, custom.clone());
+        }
+    }
+
+    /// Checks the formats of a stream against this registry.
+    ///
+    /// Formats that are only in one of the registries are accepted.
+    /// Returns an error of kind `ErrorKind::CustomFormatVersionMismatch`
+    /// for the first format with another version in the stream.
+    pub fn reconcile(&self, file: &CustomFormats) -> io::Result<()> {
+        for (format, custom) in file.iter() {
+            if let Some(local) = self.get(format) {
+                if local.version != custom.version {
+                    return Err(Error::new(ErrorKind::CustomFormatVersionMismatch)
+                        .with_versions(format, custom.version, local.version)
+                        .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the registry.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut data = vec![];
+        for (format, custom) in self.iter() {
+            data.extend_from_slice(&format.to_le_bytes());
+            data.extend_from_slice(&custom.version.to_le_bytes());
+            match custom.layout {
+                Some(ref layout) => {
+                    if layout.len() >= u32::MAX as usize {
+                        return Err(io::ErrorKind::InvalidInput.into());
+                    }
+                    data.extend_from_slice(&(layout.len() as u32).to_le_bytes());
+                    data.extend_from_slice(layout.as_bytes());
+                }
+                None => data.extend_from_slice(&u32::MAX.to_le_bytes()),
+            }
+        }
+        RawBlock::write_property(FORMATS_FORMAT, FORMATS_PROPERTY, &[RawBlock {
+            type_format: FORMATS_FORMAT,
+            property_id: FORMATS_PROPERTY,
+            offset: 0,
+            data,
+        }], w)
+    }
+
+    /// Reads a registry.
+    pub fn read<R: io::Read>(
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<CustomFormats> {
+        let mut formats = CustomFormats::new();
+        for block in RawBlock::read_property(state, ty, property_id, r)? {
+            formats.merge(&CustomFormats::decode(&block)?);
+        }
+        Ok(formats)
+    }
+
+    /// Decodes a registry from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<CustomFormats> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.type_format != FORMATS_FORMAT {return Err(err())}
+        let mut formats = CustomFormats::new();
+        let mut data = &block.data[..];
+        while !data.is_empty() {
+            if data.len() < 10 {return Err(err())}
+            let format = u16::from_le_bytes([data[0], data[1]]);
+            let version = u32::from_le_bytes([data[2], data[3], data[4], data[5]]);
+            let len = u32::from_le_bytes([data[6], data[7], data[8], data[9]]);
+            data = &data[10..];
+            let layout = if len == u32::MAX {
+                None
+            } else {
+                let len = len as usize;
+                if data.len() < len {return Err(err())}
+                let layout = match ::std::str::from_utf8(&data[..len]) {
+                    Ok(layout) => layout,
+                    Err(_) => return Err(err()),
+                };
+                data = &data[len..];
+                Some(layout.into())
+            };
+            formats.set(format, CustomFormat {version, layout});
+        }
+        Ok(formats)
+    }
+
+    fn set(&mut self, format: u16, custom: CustomFormat) {
+        match self.formats.iter_mut().find(|&&mut (f, _)| f == format) {
+            Some(&mut (_, ref mut c)) => *c = custom,
+            None => self.formats.push((format, custom)),
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! Reading and writing frames.
+//!
+//! A frame starts with a frame marker property and lasts
+//! until the next frame marker.
+//! When the frame marker is a time property,
+//! the time is written at the start of each frame.
+
+use std::io;
+use std::mem;
+
+use alias::Aliases;
+use alive::AliveMask;
+use arena::{FrameArena, FrameView};
+use buffer_pool::{BufferPool, Pooled};
+use error::{Error, ErrorKind};
+use index::FrameCursor;
+use limits::{Limits, Usage};
+use lineage::{self, Creation};
+use raw::{self, RawBlock};
+use read_write::{self, Array, Element, ReadMode, ReadOptions};
+use reduce::{Reductions, Violation};
+use reserved::{
+    self, ALIVE_FORMAT, ALIVE_PROPERTY, FOOTER_FORMAT, FOOTER_PROPERTY, PADDING_FORMAT, PADDING_PROPERTY,
+    PRESENCE_FORMAT, TIME_UNIT_FORMAT,
+};
+use time::{Time, TimeUnit};
+use trace::{self, Span};
+use xor::{XorState, XorWriter};
+use State;
+
+/// Determines what happens when a property appears more than once in a frame.
+///
+/// Blocks that share one property header are not duplicates,
+/// since range updates are written this way.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DuplicatePolicy {
+    /// Return an error.
+    Error,
+    /// Keep all blocks and call the warning callback.
+    Warn,
+    /// Keep the blocks of the first property header.
+    KeepFirst,
+    /// Keep the blocks of the last property header.
+    KeepLast,
+}
+
+/// A property that appeared more than once in a frame.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DuplicateProperty {
+    /// The frame number.
+    pub frame: u64,
+    /// The property id.
+    pub property_id: u16,
+}
+
+/// A frame of blocks that are not decoded.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The frame number, starting at 0.
+    pub number: u64,
+    /// The time of the frame, if there is a time property.
+    pub time: Option<f64>,
+    /// The blocks of the frame, in the order they were read.
+    pub blocks: Vec<RawBlock>,
+}
+
+impl Frame {
+    /// Returns `true` if the frame contains blocks of a property.
+    pub fn contains(&self, property_id: u16) -> bool {
+        self.blocks.iter().any(|b| b.property_id == property_id)
+    }
+
+    /// Returns an iterator over the blocks of a property.
+    pub fn blocks_of(&self, property_id: u16) -> impl Iterator<Item = &RawBlock> {
+        self.blocks.iter().filter(move |b| b.property_id == property_id)
+    }
+
+    /// Reads all blocks of a property into an array.
+    ///
+    /// Blocks are applied in order at their offsets, growing the array when needed.
+    /// Items that are not covered by the blocks keep their values.
+    /// Presence bitmaps of optional arrays are ignored.
+    ///
+    /// Returns `false` if the frame contains no blocks of the property.
+    pub fn read_array<T, A>(&self, property_id: u16, arr: &mut A) -> io::Result<bool>
+        where T: Element, A: Array<Item = T>
+    {
+        self.read_array_with(property_id, arr, &ReadOptions::default())
+    }
+
+    /// Reads all blocks of a property into an array, with options.
+    ///
+    /// With `ReadMode::Replace`, the array is cleared when the frame
+    /// contains blocks of the property.
+    pub fn read_array_with<T, A>(
+        &self,
+        property_id: u16,
+        arr: &mut A,
+        opts: &ReadOptions
+    ) -> io::Result<bool>
+        where T: Element, A: Array<Item = T>
+    {
+        let blocks = self.blocks_of(property_id).map(|b| (b.type_format, b.offset, &b.data[..]));
+        read_blocks_into(property_id, blocks, arr, opts)
+    }
+
+    /// Reads a property with a single value.
+    ///
+    /// Returns `None` if the frame contains no blocks of the property.
+    pub fn read_property<T: Element>(&self, property_id: u16) -> io::Result<Option<T>> {
+        let mut res: Vec<T> = vec![];
+        if !self.read_array(property_id, &mut res)? {return Ok(None)}
+        if res.len() != 1 {
+            return Err(Error::new(ErrorKind::InvalidData).with_property(property_id).into());
+        }
+        Ok(res.pop())
+    }
+
+    /// Reads the items of a property whose instance in another property passes a predicate,
+    /// e.g. positions of particles above an energy.
+    ///
+    /// The predicate is called with the instance id and the item of the key property.
+    /// Passing items are pushed with their instance ids, in order of instance id.
+    /// Instances that are not written in both properties are skipped.
+    ///
+    /// Returns `false` if the frame contains no blocks of the property.
+    pub fn read_array_filtered_by<T, K, F>(
+        &self,
+        property_id: u16,
+        key_property: u16,
+        mut pred: F,
+        out: &mut Vec<(u64, T)>
+    ) -> io::Result<bool>
+        where T: Element, K: Element, F: FnMut(u64, &K) -> bool
+    {
+        let keys: Vec<(u64, K)> = self.read_written(key_property)?;
+        if !self.contains(property_id) {return Ok(false)}
+        let items: Vec<(u64, T)> = self.read_written(property_id)?;
+        let mut keys = keys.iter().peekable();
+        for (i, item) in items {
+            while keys.peek().is_some_and(|k| k.0 < i) {keys.next();}
+            if let Some(&&(j, ref key)) = keys.peek() {
+                if j == i && pred(i, key) {out.push((i, item))}
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reads the written items of a property with their instance ids, in order of instance id.
+    ///
+    /// Items are not stored by instance id, such that a block with a large offset
+    /// does not allocate memory for the instances before it.
+    fn read_written<T: Element>(&self, property_id: u16) -> io::Result<Vec<(u64, T)>> {
+        let mut res = vec![];
+        for block in self.blocks_of(property_id) {
+            if block.type_format == PRESENCE_FORMAT {continue}
+            for (i, item) in block.decode::<T>()?.into_iter().enumerate() {
+                match block.offset.checked_add(i as u64) {
+                    Some(ind) => res.push((ind, item)),
+                    None => return Err(Error::new(ErrorKind::InvalidData)
+                            .with_property(property_id).into()),
+                }
+            }
+        }
+        // The sort is stable, so the last written item of an instance comes first.
+        res.reverse();
+        res.sort_by_key(|x| x.0);
+        res.dedup_by_key(|x| x.0);
+        Ok(res)
+    }
+}
+
+/// Writes frames starting with a time property.
+///
+/// Data is written through the frame writer, e.g.
+/// `Vector::write_array(POSITION, &pos, &mut frame_writer)`.
+pub struct FrameWriter<W: io::Write> {
+    w: W,
+    time_property: u16,
+    frames: u64,
+    monotonic: bool,
+    strict: bool,
+    last_time: Option<f64>,
+    xor: XorWriter,
+}
+
+impl<W: io::Write> FrameWriter<W> {
+    /// Creates a new frame writer.
+    pub fn new(w: W, time_property: u16) -> FrameWriter<W> {
+        FrameWriter {
+            w,
+            time_property,
+            frames: 0,
+            monotonic: false,
+            strict: false,
+            last_time: None,
+            xor: XorWriter::new(32),
+        }
+    }
+
+    /// Sets whether frame times must not decrease.
+    ///
+    /// When set, `begin_frame` returns an error of kind `ErrorKind::NonMonotonicTime`
+    /// with both times for a time before the previous one or a NaN time,
+    /// and nothing is written.
+    pub fn require_monotonic_time(mut self, val: bool) -> FrameWriter<W> {
+        self.monotonic = val;
+        self
+    }
+
+    /// Sets whether frame times must increase, when monotonic time is required.
+    ///
+    /// By default, a time equal to the previous one is accepted.
+    pub fn strictly_increasing_time(mut self, val: bool) -> FrameWriter<W> {
+        self.strict = val;
+        self
+    }
+
+    /// Sets the number of frames between keyframes of `write_xor_delta`.
+    ///
+    /// The default is 32. Frames with a multiple of the interval as number are keyframes.
+    pub fn keyframe_interval(mut self, val: u64) -> FrameWriter<W> {
+        self.xor.set_keyframe_interval(val);
+        self
+    }
+
+    /// Writes an array in the current frame as the XOR with the previous frame.
+    ///
+    /// Values that vary slowly between frames differ in few bits,
+    /// which compresses well. `FrameReader` restores the array transparently.
+    /// The array is written as a plain block in keyframes,
+    /// before the first frame, the first time the property is written,
+    /// and when the type format or the number of items changed.
+    /// Seek to keyframes only, since reading a delta requires the previous frames.
+    ///
+    /// The property should only be written with this method and once per frame.
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if the dimensions of the element type are not supported.
+    pub fn write_xor_delta<T: Element>(&mut self, property_id: u16, items: &[T]) -> io::Result<()> {
+        let (format, size) = match T::format() {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        let mut data = Vec::with_capacity(items.len() * size as usize);
+        for item in items {item.write_element(&mut data)?}
+        let frame = self.frames.checked_sub(1);
+        self.xor.write(frame, property_id, format, data, &mut self.w)
+    }
+
+    /// Writes the unit of the time property.
+    ///
+    /// This should be written before the first frame.
+    pub fn write_time_unit(&mut self, unit: &TimeUnit) -> io::Result<()> {
+        unit.write(self.time_property, &mut self.w)
+    }
+
+    /// Starts a new frame at time `t`.
+    pub fn begin_frame(&mut self, t: f64) -> io::Result<()> {
+        if self.monotonic {
+            let previous = self.last_time.unwrap_or(f64::NEG_INFINITY);
+            if !is_after(previous, t, self.strict) {
+                return Err(Error::new(ErrorKind::NonMonotonicTime)
+                    .with_property(self.time_property)
+                    .with_times(previous, t)
+                    .into());
+            }
+        }
+        Time::write(self.time_property, t, &mut self.w)?;
+        self.last_time = Some(t);
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Returns the time property id.
+    pub fn time_property(&self) -> u16 {
+        self.time_property
+    }
+
+    /// Returns the number of frames written.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.w
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        State::new().end_type_formats(&mut self.w)?;
+        self.w.flush()?;
+        Ok(self.w)
+    }
+}
+
+impl<W: io::Write> io::Write for FrameWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.w.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Applies the blocks of the alive mask.
+fn apply_alive(alive: &mut AliveMask, blocks: &[RawBlock]) -> io::Result<()> {
+    for block in blocks {
+        if block.type_format == ALIVE_FORMAT && block.property_id == ALIVE_PROPERTY {
+            alive.apply(block)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads blocks of a property, given as type format, offset and data, into an array.
+///
+/// See `Frame::read_array_with`.
+pub(crate) fn read_blocks_into<'b, T, A, I>(
+    property_id: u16,
+    blocks: I,
+    arr: &mut A,
+    opts: &ReadOptions
+) -> io::Result<bool>
+    where T: Element, A: Array<Item = T>, I: IntoIterator<Item = (u16, u64, &'b [u8])>
+{
+    let mut found = false;
+    for (type_format, offset, data) in blocks {
+        if type_format == PRESENCE_FORMAT {continue}
+        let format = read_write::transposed_format::<T>(type_format, opts);
+        let n = raw::item_count::<T>(format, property_id, data.len())?;
+        // The array is cleared once the first block is known to fit the element type.
+        if !found && opts.mode == ReadMode::Replace {arr.truncate(0)}
+        found = true;
+        let mut r = data;
+        for i in 0..n {
+            let ind = match offset.checked_add(opts.instance_offset)
+                .and_then(|offset| offset.checked_add(i as u64)) {
+                Some(x) if x < usize::MAX as u64 => x as usize,
+                _ => return Err(Error::new(ErrorKind::InvalidData)
+                        .with_property(property_id).into()),
+            };
+            read_write::make_room(arr, ind, ind.saturating_add(n - i))?;
+            let mut item: T = Default::default();
+            item.read_element(&mut r)?;
+            arr.set(ind, item);
+        }
+    }
+    Ok(found)
+}
+
+/// Moves blocks to the end of a vector, returning the emptied vector to a pool.
+fn append(blocks: &mut Vec<RawBlock>, mut b: Vec<RawBlock>, pool: Option<&BufferPool>) {
+    blocks.append(&mut b);
+    if let Some(pool) = pool {pool.give_vec(b)}
+}
+
+/// Returns `true` if a time may follow the previous time.
+///
+/// NaN times never do.
+pub(crate) fn is_after(previous: f64, t: f64, strict: bool) -> bool {
+    if strict {t > previous} else {t >= previous}
+}
+
+/// Reads frames separated by a frame marker property.
+///
+/// Blocks before the first frame marker are stored as preamble.
+///
+/// `Frame::read_array` merges the blocks of a frame into the target array,
+/// like `ReadMode::Merge`, such that a frame can contain changes only.
+/// When reusing an array for frames with all data,
+/// use `Frame::read_array_with` and `ReadMode::Replace`.
+pub struct FrameReader<R> {
+    r: R,
+    marker: u16,
+    on_duplicate: DuplicatePolicy,
+    duplicate_warning: Option<Box<dyn FnMut(DuplicateProperty)>>,
+    time_property: Option<u16>,
+    time_unit: Option<TimeUnit>,
+    preamble: Vec<RawBlock>,
+    next: Option<Vec<RawBlock>>,
+    started: bool,
+    frames: u64,
+    usage: Usage,
+    aliases: Aliases,
+    reductions: Reductions,
+    xor: XorState,
+    alive: AliveMask,
+    created: Vec<Creation>,
+    destroyed: Vec<u64>,
+    buffers: Option<BufferPool>,
+    span: Span,
+}
+
+impl<R: io::Read> FrameReader<R> {
+    /// Creates a new frame reader.
+    pub fn new(r: R, marker: u16) -> FrameReader<R> {
+        FrameReader {
+            r,
+            marker,
+            on_duplicate: DuplicatePolicy::Warn,
+            duplicate_warning: None,
+            time_property: None,
+            time_unit: None,
+            preamble: vec![],
+            next: None,
+            started: false,
+            frames: 0,
+            usage: Usage::default(),
+            aliases: Aliases::new(),
+            reductions: Reductions::default(),
+            xor: XorState::default(),
+            alive: AliveMask::new(),
+            created: vec![],
+            destroyed: vec![],
+            buffers: None,
+            span: Span::none(),
+        }
+    }
+
+    /// Creates a new frame reader where frames start with a time property.
+    pub fn with_time(r: R, time_property: u16) -> FrameReader<R> {
+        FrameReader::new(r, time_property).time_property(time_property)
+    }
+
+    /// Sets the time property.
+    pub fn time_property(mut self, time_property: u16) -> FrameReader<R> {
+        self.time_property = Some(time_property);
+        self
+    }
+
+    /// Sets what happens when a property appears more than once in a frame.
+    ///
+    /// The default is `DuplicatePolicy::Warn`.
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> FrameReader<R> {
+        self.on_duplicate = policy;
+        self
+    }
+
+    /// Sets the callback for duplicate properties with `DuplicatePolicy::Warn`.
+    pub fn duplicate_warning<F>(mut self, f: F) -> FrameReader<R>
+        where F: 'static + FnMut(DuplicateProperty)
+    {
+        self.duplicate_warning = Some(Box::new(f));
+        self
+    }
+
+    /// Sets limits on the resources used when reading.
+    pub fn limits(mut self, limits: Limits) -> FrameReader<R> {
+        self.usage = Usage::new(limits);
+        self
+    }
+
+    /// Sets aliases of property ids.
+    ///
+    /// Blocks of old property ids are renamed to the new property ids,
+    /// after duplicates have been handled.
+    /// The frame marker and time property use the new property ids.
+    pub fn aliases(mut self, aliases: Aliases) -> FrameReader<R> {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Sets a pool to take the buffers of block data from.
+    ///
+    /// Buffers are returned to the pool when frames from `next_pooled_frame` are dropped,
+    /// or with `BufferPool::give_blocks`.
+    pub fn buffer_pool(mut self, pool: BufferPool) -> FrameReader<R> {
+        self.buffers = Some(pool);
+        self
+    }
+
+    /// Adds a reduction of a property over every frame, e.g. total momentum.
+    ///
+    /// Each frame starts with `init` and `f` is called with every item of the property,
+    /// decoded from the blocks of the frame without copying them into an array.
+    /// Results are available with `reductions` after reading a frame.
+    /// Reading a frame returns an error of kind `ErrorKind::TypeMismatch`
+    /// if the property has another type format than the element type.
+    ///
+    /// ```ignore
+    /// let mut frames = FrameReader::with_time(file, TIME)
+    ///     .reduce(CHARGE, vec![0.0], |acc, q: &f64| acc[0] += q)
+    ///     .invariant("charge", |res| {
+    ///         if res[0][0] == 0.0 {Ok(())} else {Err(format!("total charge {}", res[0][0]))}
+    ///     });
+    /// ```
+    pub fn reduce<T, F>(mut self, property_id: u16, init: Vec<f64>, f: F) -> FrameReader<R>
+        where T: Element, F: 'static + FnMut(&mut Vec<f64>, &T)
+    {
+        self.reductions.add(property_id, init, f);
+        self
+    }
+
+    /// Adds an invariant checked after the reductions of every frame.
+    ///
+    /// The check is called with the results of all reductions, in the order they were added,
+    /// and returns a description of the problem when the frame violates the invariant.
+    /// Violations are collected, see `violations`.
+    pub fn invariant<F>(mut self, name: &str, f: F) -> FrameReader<R>
+        where F: 'static + FnMut(&[Vec<f64>]) -> Result<(), String>
+    {
+        self.reductions.add_invariant(name, f);
+        self
+    }
+
+    /// Sets whether reading a frame that violates an invariant returns an error
+    /// of kind `ErrorKind::InvariantViolation`, instead of only collecting the violation.
+    pub fn abort_on_violation(mut self, val: bool) -> FrameReader<R> {
+        self.reductions.set_abort(val);
+        self
+    }
+
+    /// Returns the results of the reductions in the last frame read,
+    /// in the order they were added.
+    pub fn reductions(&self) -> &[Vec<f64>] {
+        self.reductions.results()
+    }
+
+    /// Returns the invariant violations of the frames read so far.
+    pub fn violations(&self) -> &[Violation] {
+        self.reductions.violations()
+    }
+
+    /// Returns the frame marker property id.
+    pub fn marker(&self) -> u16 {
+        self.marker
+    }
+
+    /// Returns the unit of the time property, if it has been read.
+    pub fn time_unit(&self) -> Option<&TimeUnit> {
+        self.time_unit.as_ref()
+    }
+
+    /// Returns the blocks before the first frame marker.
+    ///
+    /// This is empty until the first frame is read.
+    pub fn preamble(&self) -> &[RawBlock] {
+        &self.preamble
+    }
+
+    /// Returns the number of frames read.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the alive mask after the last frame read.
+    ///
+    /// Blocks of the mask are applied in the order they were read,
+    /// starting with the preamble, and kept in the blocks of the frame.
+    pub fn alive(&self) -> &AliveMask {
+        &self.alive
+    }
+
+    /// Returns the instances created in the last frame read.
+    pub fn created(&self) -> &[Creation] {
+        &self.created
+    }
+
+    /// Returns the instances destroyed in the last frame read.
+    pub fn destroyed(&self) -> &[u64] {
+        &self.destroyed
+    }
+
+    /// Reads the next property.
+    ///
+    /// Returns `None` at the end of stream.
+    /// Time units are stored in the reader and padding is skipped.
+    /// A frame marker starts the span of frame `frame`, which `next_frame` continues.
+    fn read_property(&mut self, frame: u64) -> io::Result<Option<(u16, Vec<RawBlock>)>> {
+        loop {
+            let (state, ty, prop) = match raw::read_property_header(&mut self.r)? {
+                None => return Ok(None),
+                Some(x) => x,
+            };
+            let time_unit = ty == TIME_UNIT_FORMAT &&
+                Some(self.aliases.resolve(prop)) == self.time_property;
+            // The footer index is skipped like padding.
+            let padding = (ty == PADDING_FORMAT && prop == PADDING_PROPERTY) ||
+                (ty == FOOTER_FORMAT && prop == FOOTER_PROPERTY);
+            let marker = !time_unit && !padding && self.aliases.resolve(prop) == self.marker;
+            let span = if marker {trace::frame(frame)} else {Span::none()};
+            let _span = span.clone().entered();
+            self.usage.property(ty, prop)?;
+            let blocks = raw::read_blocks_pooled(
+                state, ty, prop, &mut self.usage, self.buffers.as_ref(), &mut self.r)?;
+            if time_unit {
+                if let Some(block) = blocks.last() {
+                    self.time_unit = Some(TimeUnit::decode(block)?);
+                }
+                continue;
+            }
+            if padding {continue}
+            if marker {self.span = span}
+            return Ok(Some((prop, blocks)));
+        }
+    }
+
+    /// Reads the next frame.
+    ///
+    /// Returns `None` when there are no more frames.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        if !self.started {
+            self.started = true;
+            while let Some((prop, blocks)) = self.read_property(self.frames)? {
+                if self.aliases.resolve(prop) == self.marker {
+                    self.next = Some(blocks);
+                    break;
+                }
+                append(&mut self.preamble, blocks, self.buffers.as_ref());
+            }
+            self.aliases.resolve_frame(&mut self.preamble)?;
+            apply_alive(&mut self.alive, &self.preamble)?;
+        }
+        let mut blocks = match self.next.take() {
+            None => return Ok(None),
+            Some(x) => x,
+        };
+        let span = mem::replace(&mut self.span, Span::none());
+        let _span = span.clone().entered();
+        let mut seen = vec![self.marker];
+        let pool = self.buffers.clone();
+        let pool = pool.as_ref();
+        while let Some((prop, b)) = self.read_property(self.frames + 1)? {
+            if self.aliases.resolve(prop) == self.marker {
+                self.next = Some(b);
+                break;
+            }
+            // The presence bitmap of an optional array follows the array.
+            let presence = !b.is_empty() && b.iter().all(|block| block.type_format == PRESENCE_FORMAT);
+            // Reserved properties, such as alive masks and events, share one property id.
+            let reserved = reserved::is_reserved_property(prop) &&
+                b.iter().all(|block| reserved::is_reserved_format(block.type_format));
+            if presence || reserved {
+                append(&mut blocks, b, pool);
+                continue;
+            }
+            if !seen.contains(&prop) {
+                seen.push(prop);
+                append(&mut blocks, b, pool);
+                continue;
+            }
+            match self.on_duplicate {
+                DuplicatePolicy::Error => {
+                    return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+                }
+                DuplicatePolicy::Warn => {
+                    if let Some(ref mut f) = self.duplicate_warning {
+                        f(DuplicateProperty {frame: self.frames, property_id: prop});
+                    }
+                    append(&mut blocks, b, pool);
+                }
+                DuplicatePolicy::KeepFirst => {}
+                DuplicatePolicy::KeepLast => {
+                    blocks.retain(|block| block.property_id != prop);
+                    append(&mut blocks, b, pool);
+                }
+            }
+        }
+        self.aliases.resolve_frame(&mut blocks)?;
+        self.xor.apply(&mut blocks)?;
+        apply_alive(&mut self.alive, &blocks)?;
+        self.created.clear();
+        self.destroyed.clear();
+        lineage::read_events(&blocks, &mut self.created, &mut self.destroyed)?;
+        let mut time = None;
+        if let Some(time_property) = self.time_property {
+            if let Some(block) = blocks.iter().find(|b| b.property_id == time_property) {
+                let t = Time::decode(block)?;
+                trace::record_time(&span, t);
+                time = Some(t);
+            }
+        }
+        let number = self.frames;
+        self.frames += 1;
+        self.reductions.apply(number, &blocks)?;
+        Ok(Some(Frame {number, time, blocks}))
+    }
+
+    /// Reads the next frame, returning the buffers of its blocks
+    /// to the pool set with `buffer_pool` when dropped.
+    ///
+    /// Without a pool, this is the same as `next_frame`.
+    pub fn next_pooled_frame(&mut self) -> io::Result<Option<Pooled<Frame>>> {
+        let frame = self.next_frame()?;
+        Ok(frame.map(|frame| match self.buffers {
+            Some(ref pool) => pool.frame(frame),
+            None => Pooled::new(frame, None, |_, _| {}),
+        }))
+    }
+
+    /// Reads the next frame into an arena, resetting the arena.
+    ///
+    /// The view borrows the arena, so it must be dropped before the arena is read into again.
+    /// Without a pool set with `buffer_pool`, the buffers of blocks are reused through the arena.
+    pub fn next_frame_in<'a>(&mut self, arena: &'a mut FrameArena) -> io::Result<Option<FrameView<'a>>> {
+        let own = self.buffers.is_none();
+        if own {self.buffers = Some(arena.pool().clone())}
+        let frame = self.next_frame();
+        let pool = if own {self.buffers.take()} else {self.buffers.clone()};
+        let frame = match frame? {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let view = arena.fill(&frame);
+        if let Some(pool) = pool {pool.give_blocks(frame.blocks)}
+        Ok(Some(view))
+    }
+
+    /// Continues reading from a frame after the underlying reader was positioned.
+    ///
+    /// The next frame read is the frame of the cursor.
+    /// Arrays written with `FrameWriter::write_xor_delta` can be read from a keyframe.
+    /// The alive mask is cleared, so write the whole mask with `AliveMask::write`
+    /// in frames that are seeked to.
+    /// Returns an error if the reader is not at a frame marker.
+    pub fn resume(&mut self, cursor: &FrameCursor) -> io::Result<()> {
+        self.started = true;
+        self.frames = cursor.frame;
+        self.xor.clear();
+        self.alive.clear();
+        match self.read_property(self.frames)? {
+            Some((prop, blocks)) if self.aliases.resolve(prop) == self.marker => {
+                self.next = Some(blocks);
+                Ok(())
+            }
+            _ => {
+                self.next = None;
+                Err(io::ErrorKind::InvalidData.into())
+            }
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.r
+    }
+}
@@ -0,0 +1,187 @@
+//! Grids larger than the built-in matrix formats.
+//!
+//! A grid is split into tiles of at most 80x80 scalars,
+//! numbered row by row, with smaller tiles at the right and bottom edges.
+//! Each tile is a block with the tile number as offset instance id,
+//! such that a grid can be updated one tile at a time.
+//!
+//! The data of a block starts with the scalar type format (u16)
+//! and the shape of the whole grid as rows (u32) and columns (u32),
+//! followed by the scalars of the tile, row by row.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use read_write::Scalar;
+use reserved::GRID_FORMAT;
+use Bytes;
+use State;
+use Type;
+
+const HEADER: usize = 10;
+
+/// A grid of scalars, stored row by row.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Grid<T> {
+    /// The number of rows.
+    pub rows: u32,
+    /// The number of columns.
+    pub cols: u32,
+    /// The scalars, row by row.
+    pub data: Vec<T>,
+}
+
+fn tile_size() -> u32 {
+    Type::max_matrix_dim() as u32
+}
+
+fn tiles(rows: u32, cols: u32) -> (u32, u32) {
+    let size = tile_size();
+    (rows.div_ceil(size), cols.div_ceil(size))
+}
+
+fn tile_range(rows: u32, cols: u32, tile: u64) -> Option<([u32; 2], [u32; 2])> {
+    let (tile_rows, tile_cols) = tiles(rows, cols);
+    if tile >= tile_rows as u64 * tile_cols as u64 {return None}
+    let size = tile_size();
+    let row = (tile / tile_cols as u64) as u32 * size;
+    let col = (tile % tile_cols as u64) as u32 * size;
+    Some(([row, row.saturating_add(size).min(rows)], [col, col.saturating_add(size).min(cols)]))
+}
+
+fn write_tiles<T, W, I>(
+    property_id: u16,
+    rows: u32,
+    cols: u32,
+    data: &[T],
+    tiles: I,
+    w: &mut W
+) -> io::Result<()>
+    where T: Scalar, W: io::Write, I: IntoIterator<Item = u64>
+{
+    if data.len() as u64 != rows as u64 * cols as u64 {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    let mut blocks = vec![];
+    for tile in tiles {
+        let (tile_rows, tile_cols) = match tile_range(rows, cols, tile) {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        let mut buf = Vec::with_capacity(HEADER);
+        buf.extend_from_slice(&T::ty().scalar().0.to_le_bytes());
+        buf.extend_from_slice(&rows.to_le_bytes());
+        buf.extend_from_slice(&cols.to_le_bytes());
+        for i in tile_rows[0]..tile_rows[1] {
+            for j in tile_cols[0]..tile_cols[1] {
+                data[i as usize * cols as usize + j as usize].write(&mut buf)?;
+            }
+        }
+        blocks.push(RawBlock {type_format: GRID_FORMAT, property_id, offset: tile, data: buf});
+    }
+    RawBlock::write_property(GRID_FORMAT, property_id, &blocks, w)
+}
+
+impl<T: Scalar + Clone> Grid<T> {
+    /// Creates a grid filled with default values.
+    pub fn new(rows: u32, cols: u32) -> Grid<T> {
+        Grid {rows, cols, data: vec![T::default(); rows as usize * cols as usize]}
+    }
+
+    /// Returns the number of tiles.
+    pub fn tile_count(&self) -> u64 {
+        let (tile_rows, tile_cols) = tiles(self.rows, self.cols);
+        tile_rows as u64 * tile_cols as u64
+    }
+
+    /// Returns the range of rows and columns covered by a tile.
+    pub fn tile_range(&self, tile: u64) -> Option<([u32; 2], [u32; 2])> {
+        tile_range(self.rows, self.cols, tile)
+    }
+
+    /// Writes all tiles of the grid.
+    pub fn write<W: io::Write>(&self, property_id: u16, w: &mut W) -> io::Result<()> {
+        self.write_tiles(property_id, 0..self.tile_count(), w)
+    }
+
+    /// Writes some tiles of the grid, to update a grid written before.
+    pub fn write_tiles<W, I>(&self, property_id: u16, tiles: I, w: &mut W) -> io::Result<()>
+        where W: io::Write, I: IntoIterator<Item = u64>
+    {
+        write_tiles(property_id, self.rows, self.cols, &self.data, tiles, w)
+    }
+
+    /// Reads tiles into the grid.
+    ///
+    /// When the shape of the grid changes, the grid is cleared to default values first.
+    pub fn read<R: io::Read>(
+        &mut self,
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<()> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(property_id).into()
+        };
+        if ty != GRID_FORMAT {return Err(err())}
+        let size = T::ty().type_size() as usize;
+        for block in RawBlock::read_property(state, ty, property_id, r)? {
+            let data = &block.data;
+            if data.len() < HEADER ||
+               u16::from_le_bytes([data[0], data[1]]) != T::ty().scalar().0 {
+                return Err(err());
+            }
+            let rows = u32::from_le_bytes([data[2], data[3], data[4], data[5]]);
+            let cols = u32::from_le_bytes([data[6], data[7], data[8], data[9]]);
+            let (tile_rows, tile_cols) = match tile_range(rows, cols, block.offset) {
+                Some(x) => x,
+                None => return Err(err()),
+            };
+            let n = (tile_rows[1] - tile_rows[0]) as usize * (tile_cols[1] - tile_cols[0]) as usize;
+            if data.len() - HEADER != n * size {return Err(err())}
+            if rows != self.rows || cols != self.cols {
+                let len = match (rows as usize).checked_mul(cols as usize) {
+                    Some(len) => len,
+                    None => return Err(err()),
+                };
+                self.rows = rows;
+                self.cols = cols;
+                self.data.clear();
+                self.data.resize(len, T::default());
+            }
+            let mut r = &data[HEADER..];
+            for i in tile_rows[0]..tile_rows[1] {
+                for j in tile_cols[0]..tile_cols[1] {
+                    self.data[i as usize * cols as usize + j as usize].read(&mut r)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a grid of scalars, stored row by row.
+pub fn write_grid<T: Scalar, W: io::Write>(
+    property_id: u16,
+    rows: u32,
+    cols: u32,
+    data: &[T],
+    w: &mut W
+) -> io::Result<()> {
+    let (tile_rows, tile_cols) = tiles(rows, cols);
+    write_tiles(property_id, rows, cols, data, 0..tile_rows as u64 * tile_cols as u64, w)
+}
+
+/// Reads a grid of scalars.
+pub fn read_grid<T: Scalar + Clone, R: io::Read>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    r: &mut R
+) -> io::Result<Grid<T>> {
+    let mut grid = Grid {rows: 0, cols: 0, data: vec![]};
+    grid.read(state, ty, property_id, r)?;
+    Ok(grid)
+}
@@ -0,0 +1,120 @@
+//! Writing blocks of a property without forgetting the end of bytes.
+
+use std::io::{self, IoSlice};
+use std::marker::PhantomData;
+
+use raw;
+use Bytes;
+use State;
+
+/// Writes the header of a property and returns a guard for writing its blocks.
+///
+/// ```ignore
+/// let mut block = begin_block(format, POSITION, &mut file)?;
+/// block.write(0, &first)?;
+/// block.write(16, &second)?;
+/// block.finish()?;
+/// ```
+pub fn begin_block<W: io::Write>(
+    type_format: u16,
+    property_id: u16,
+    w: &mut W
+) -> io::Result<BlockGuard<'_, W>> {
+    // Type format 0 is the end of stream.
+    if type_format == 0 {return Err(io::ErrorKind::InvalidInput.into())}
+    State::new()
+        .write_type_format(type_format, w)?
+        .write_property_id(property_id, w)?;
+    Ok(BlockGuard {
+        w,
+        type_format,
+        property_id,
+        state: GuardState::Open,
+    })
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum GuardState {
+    /// Between blocks, such that the end of bytes can be written.
+    Open,
+    /// A write failed in the middle of a block.
+    Broken,
+    /// The end of bytes is written.
+    Finished,
+}
+
+/// Writes blocks of a property, ending the property when finished or dropped.
+///
+/// When the guard is dropped without calling `finish`,
+/// for example when returning early on an error,
+/// the end of bytes is written such that the stream stays valid.
+/// Errors are ignored in that case, so call `finish` to check them.
+///
+/// After a write fails in the middle of a block, nothing more is written,
+/// since the stream can not be made valid.
+#[must_use = "call `finish` to end the property and check for errors"]
+pub struct BlockGuard<'a, W: 'a + io::Write> {
+    w: &'a mut W,
+    type_format: u16,
+    property_id: u16,
+    state: GuardState,
+}
+
+impl<'a, W: io::Write> BlockGuard<'a, W> {
+    /// Returns the type format of the property.
+    pub fn type_format(&self) -> u16 {
+        self.type_format
+    }
+
+    /// Returns the property id.
+    pub fn property_id(&self) -> u16 {
+        self.property_id
+    }
+
+    /// Writes a block with the given offset instance id.
+    ///
+    /// The block is written with one call to `write_vectored`.
+    /// Empty data is skipped, since an empty block would be read as the end of bytes.
+    pub fn write(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        if self.state != GuardState::Open {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        if data.is_empty() {return Ok(())}
+        let mut head = [0; 16];
+        head[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        head[8..].copy_from_slice(&offset.to_le_bytes());
+        let res = raw::write_all_vectored(self.w, &mut [IoSlice::new(&head), IoSlice::new(data)]);
+        if res.is_err() {self.state = GuardState::Broken}
+        res
+    }
+
+    /// Writes the end of bytes.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end()
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        if self.state != GuardState::Open {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+        let state: State<Bytes> = State(PhantomData, Some(self.property_id));
+        match state.end_bytes(self.w) {
+            Ok(_) => {
+                self.state = GuardState::Finished;
+                Ok(())
+            }
+            Err(err) => {
+                self.state = GuardState::Broken;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl<'a, W: io::Write> Drop for BlockGuard<'a, W> {
+    fn drop(&mut self) {
+        if self.state == GuardState::Open {
+            let _ = self.end();
+        }
+    }
+}
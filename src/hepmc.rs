@@ -0,0 +1,290 @@
+//! Importing HepMC3 ASCII event records.
+//!
+//! Every event is written as one frame:
+//!
+//! ```ignore
+//! event: event number, written as time
+//! weight: f64, the first weight of the event
+//! momentum: [f64; 4], px, py, pz and e of every particle
+//! pdg_id: i32, for every particle
+//! status: i32, for every particle
+//! mass: f64, the generated mass of every particle
+//! ```
+//!
+//! The instance id of a particle is its HepMC id minus 1.
+//! Names of the properties are written as metadata before the first frame,
+//! followed by the momentum unit of the first event, `GEV` or `MEV`.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use metadata::{self, Metadata, NAME};
+use read_write::{Scalar, Vector};
+use time::Time;
+use units::Units;
+use State;
+
+/// Property ids of the data imported from HepMC3 event records.
+///
+/// Data with no property id is not written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HepMcMapping {
+    /// The event number, written as time at the start of every frame.
+    pub event: u16,
+    /// The first weight of every event.
+    pub weight: Option<u16>,
+    /// The four-momentum of every particle.
+    pub momentum: Option<u16>,
+    /// The PDG id of every particle.
+    pub pdg_id: Option<u16>,
+    /// The status of every particle.
+    pub status: Option<u16>,
+    /// The generated mass of every particle.
+    pub mass: Option<u16>,
+}
+
+impl Default for HepMcMapping {
+    fn default() -> HepMcMapping {
+        HepMcMapping {
+            event: 0,
+            weight: Some(1),
+            momentum: Some(2),
+            pdg_id: Some(3),
+            status: Some(4),
+            mass: Some(5),
+        }
+    }
+}
+
+impl HepMcMapping {
+    /// Sets the property id of the event number.
+    pub fn event(mut self, val: u16) -> HepMcMapping {
+        self.event = val;
+        self
+    }
+
+    /// Sets the property id of the weight.
+    pub fn weight(mut self, val: Option<u16>) -> HepMcMapping {
+        self.weight = val;
+        self
+    }
+
+    /// Sets the property id of the four-momentum.
+    pub fn momentum(mut self, val: Option<u16>) -> HepMcMapping {
+        self.momentum = val;
+        self
+    }
+
+    /// Sets the property id of the PDG id.
+    pub fn pdg_id(mut self, val: Option<u16>) -> HepMcMapping {
+        self.pdg_id = val;
+        self
+    }
+
+    /// Sets the property id of the status.
+    pub fn status(mut self, val: Option<u16>) -> HepMcMapping {
+        self.status = val;
+        self
+    }
+
+    /// Sets the property id of the generated mass.
+    pub fn mass(mut self, val: Option<u16>) -> HepMcMapping {
+        self.mass = val;
+        self
+    }
+
+    /// Returns the property ids that are written, with their names.
+    fn names(&self) -> Vec<(u16, &'static str)> {
+        let mut res = vec![(self.event, "event")];
+        let optional = [
+            (self.weight, "weight"),
+            (self.momentum, "momentum"),
+            (self.pdg_id, "pdg_id"),
+            (self.status, "status"),
+            (self.mass, "mass"),
+        ];
+        res.extend(optional.iter().filter_map(|&(id, name)| id.map(|id| (id, name))));
+        res
+    }
+}
+
+/// An event that is being read.
+#[derive(Default)]
+struct Event {
+    line: u64,
+    number: i64,
+    particles: u64,
+    weight: f64,
+    units: Option<(String, String)>,
+    momentum: Vec<[f64; 4]>,
+    pdg_id: Vec<i32>,
+    status: Vec<i32>,
+    mass: Vec<f64>,
+}
+
+/// Converts HepMC3 ASCII event records to a stream, one frame per event.
+///
+/// Vertices, attributes, cross sections and other records are skipped.
+/// Returns the number of events.
+///
+/// The output is terminated with an end of stream.
+/// Returns an error of kind `ErrorKind::InvalidData` with the line number for malformed lines,
+/// particle ids that are not in order, or events with another number of particles than declared.
+/// Returns an error of kind `ErrorKind::PropertyConflict` if the mapping uses a property id twice.
+pub fn import_hepmc3<R: io::BufRead, W: io::Write>(
+    r: R,
+    w: &mut W,
+    mapping: &HepMcMapping
+) -> io::Result<u64> {
+    let names = mapping.names();
+    for (i, &(id, _)) in names.iter().enumerate() {
+        if names[..i].iter().any(|&(other, _)| other == id) {
+            return Err(Error::new(ErrorKind::PropertyConflict).with_property(id).into());
+        }
+    }
+
+    let mut event: Option<Event> = None;
+    let mut events = 0;
+    let mut units: Option<(String, String)> = None;
+    let mut started = false;
+    let mut ended = false;
+    for (n, line) in r.lines().enumerate() {
+        let line = line?;
+        let n = n as u64 + 1;
+        let invalid = || -> io::Error {Error::new(ErrorKind::InvalidData).with_line(n).into()};
+        let line = line.trim();
+        if line.is_empty() {continue}
+        if line.starts_with("HepMC::") {
+            match line {
+                "HepMC::Asciiv3-START_EVENT_LISTING" if !started => started = true,
+                "HepMC::Asciiv3-END_EVENT_LISTING" if started && !ended => ended = true,
+                _ if line.starts_with("HepMC::Version") => {}
+                _ => return Err(invalid()),
+            }
+            continue;
+        }
+        if !started || ended {return Err(invalid())}
+        let mut fields = line.split_whitespace();
+        let tag = fields.next().unwrap_or("");
+        match tag {
+            "E" => {
+                if let Some(ev) = event.take() {
+                    write_event(&ev, &mut units, mapping, &names, events == 0, w)?;
+                    events += 1;
+                }
+                let number = parse(fields.next(), n)?;
+                let _vertices: i64 = parse(fields.next(), n)?;
+                let particles = parse(fields.next(), n)?;
+                event = Some(Event {line: n, number, particles, weight: 1.0, ..Event::default()});
+            }
+            "U" => {
+                let ev = event.as_mut().ok_or_else(invalid)?;
+                match (fields.next(), fields.next()) {
+                    (Some(momentum), Some(length)) => {
+                        ev.units = Some((momentum.into(), length.into()));
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            "W" => {
+                let ev = event.as_mut().ok_or_else(invalid)?;
+                ev.weight = parse(fields.next(), n)?;
+                for field in fields {
+                    let _: f64 = parse(Some(field), n)?;
+                }
+            }
+            "P" => {
+                let ev = event.as_mut().ok_or_else(invalid)?;
+                let id: u64 = parse(fields.next(), n)?;
+                if id != ev.momentum.len() as u64 + 1 {return Err(invalid())}
+                let _mother: i64 = parse(fields.next(), n)?;
+                ev.pdg_id.push(parse(fields.next(), n)?);
+                let mut p = [0.0; 4];
+                for x in &mut p {*x = parse(fields.next(), n)?}
+                ev.momentum.push(p);
+                ev.mass.push(parse(fields.next(), n)?);
+                ev.status.push(parse(fields.next(), n)?);
+                if fields.next().is_some() {return Err(invalid())}
+            }
+            "V" => {
+                event.as_ref().ok_or_else(invalid)?;
+                let _id: i64 = parse(fields.next(), n)?;
+            }
+            "A" | "C" | "F" | "H" => {
+                event.as_ref().ok_or_else(invalid)?;
+            }
+            "N" | "T" if event.is_none() => {}
+            _ => return Err(invalid()),
+        }
+    }
+    if let Some(ev) = event.take() {
+        write_event(&ev, &mut units, mapping, &names, events == 0, w)?;
+        events += 1;
+    }
+    State::new().end_type_formats(w)?;
+    Ok(events)
+}
+
+/// Parses a field of a line.
+pub(crate) fn parse<T: ::std::str::FromStr>(field: Option<&str>, line: u64) -> io::Result<T> {
+    field.and_then(|s| s.parse().ok())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData).with_line(line).into())
+}
+
+/// Writes an event as a frame, after the names and units when it is the first event.
+fn write_event<W: io::Write>(
+    ev: &Event,
+    units: &mut Option<(String, String)>,
+    mapping: &HepMcMapping,
+    names: &[(u16, &str)],
+    first: bool,
+    w: &mut W
+) -> io::Result<()> {
+    let invalid = || -> io::Error {Error::new(ErrorKind::InvalidData).with_line(ev.line).into()};
+    if ev.momentum.len() as u64 != ev.particles {return Err(invalid())}
+    if first {
+        let entries: Vec<(u16, Metadata)> = names.iter()
+            .map(|&(id, name)| (id, Metadata::new().with(NAME, name)))
+            .collect();
+        let entries: Vec<(u16, &Metadata)> = entries.iter().map(|(id, m)| (*id, m)).collect();
+        metadata::write_metadata(&entries, w)?;
+        *units = ev.units.clone();
+        if let Some((ref momentum, _)) = *units {
+            let entries: Vec<(u16, &str, f64)> = [mapping.momentum, mapping.mass].iter()
+                .filter_map(|&id| id.map(|id| (id, &momentum[..], energy_scale(momentum))))
+                .collect();
+            if !entries.is_empty() {Units::write(&entries, w)?}
+        }
+    } else if ev.units.is_some() && ev.units != *units {
+        return Err(invalid());
+    }
+
+    Time::write(mapping.event, ev.number as f64, w)?;
+    if let Some(id) = mapping.weight {
+        ev.weight.write_property(id, w)?;
+    }
+    // Empty arrays are not written.
+    if ev.momentum.is_empty() {return Ok(())}
+    if let Some(id) = mapping.momentum {
+        Vector::write_array(id, &ev.momentum, w)?;
+    }
+    if let Some(id) = mapping.pdg_id {
+        Scalar::write_array(id, &ev.pdg_id, w)?;
+    }
+    if let Some(id) = mapping.status {
+        Scalar::write_array(id, &ev.status, w)?;
+    }
+    if let Some(id) = mapping.mass {
+        Scalar::write_array(id, &ev.mass, w)?;
+    }
+    Ok(())
+}
+
+/// Returns the scale of a HepMC3 momentum unit in joules, NaN when unknown.
+fn energy_scale(unit: &str) -> f64 {
+    match unit {
+        "GEV" => 1.602176634e-10,
+        "MEV" => 1.602176634e-13,
+        _ => f64::NAN,
+    }
+}
@@ -0,0 +1,437 @@
+//! Filling histograms while streaming.
+
+use std::io;
+
+use dyn_value::DynValue;
+use error::{Error, ErrorKind};
+use frame::FrameReader;
+use raw::{self, RawBlock};
+use reserved::PRESENCE_FORMAT;
+
+/// Uniform bins between a lower and an upper edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binning {
+    /// The number of bins, without underflow and overflow.
+    pub bins: usize,
+    /// The lower edge of the first bin.
+    pub min: f64,
+    /// The upper edge of the last bin.
+    pub max: f64,
+}
+
+impl Binning {
+    /// Creates a new binning.
+    pub fn new(bins: usize, min: f64, max: f64) -> Binning {
+        Binning {bins, min, max}
+    }
+
+    /// Returns the index of a value, with 0 for underflow and `bins + 1` for overflow.
+    ///
+    /// Returns `None` for NaN.
+    fn index(&self, x: f64) -> Option<usize> {
+        if x.is_nan() {return None}
+        if x < self.min {return Some(0)}
+        if x >= self.max {return Some(self.bins + 1)}
+        let i = ((x - self.min) / (self.max - self.min) * self.bins as f64) as usize;
+        Some(i.min(self.bins - 1) + 1)
+    }
+
+    /// Returns the `bins + 1` bin edges.
+    fn edges(&self) -> Vec<f64> {
+        let width = (self.max - self.min) / self.bins as f64;
+        (0..=self.bins).map(|i| self.min + width * i as f64).collect()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.bins == 0 || self.min >= self.max || !self.min.is_finite() || !self.max.is_finite() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        Ok(())
+    }
+}
+
+/// Weighted sums of values in range.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Moments {
+    sum_w: f64,
+    sum_wx: f64,
+    sum_wx2: f64,
+}
+
+impl Moments {
+    fn add(&mut self, x: f64, w: f64) {
+        self.sum_w += w;
+        self.sum_wx += w * x;
+        self.sum_wx2 += w * x * x;
+    }
+
+    fn merge(&mut self, other: &Moments) {
+        self.sum_w += other.sum_w;
+        self.sum_wx += other.sum_wx;
+        self.sum_wx2 += other.sum_wx2;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum_wx / self.sum_w
+    }
+
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.sum_wx2 / self.sum_w - mean * mean
+    }
+}
+
+/// A histogram of one variable with uniform bins.
+///
+/// Values below the first bin go to the underflow bin,
+/// and values at or above the upper edge go to the overflow bin.
+/// NaN values are not filled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram1D {
+    binning: Binning,
+    counts: Vec<f64>,
+    entries: u64,
+    moments: Moments,
+}
+
+impl Histogram1D {
+    /// Creates an empty histogram.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if there are no bins or the edges are not finite and increasing.
+    pub fn new(binning: Binning) -> io::Result<Histogram1D> {
+        binning.check()?;
+        Ok(Histogram1D {
+            binning,
+            counts: vec![0.0; binning.bins + 2],
+            entries: 0,
+            moments: Moments::default(),
+        })
+    }
+
+    /// Adds a value with a weight.
+    pub fn fill(&mut self, x: f64, weight: f64) {
+        let i = match self.binning.index(x) {
+            Some(i) => i,
+            None => return,
+        };
+        self.counts[i] += weight;
+        self.entries += 1;
+        if i > 0 && i <= self.binning.bins {self.moments.add(x, weight)}
+    }
+
+    /// Returns the binning.
+    pub fn binning(&self) -> &Binning {
+        &self.binning
+    }
+
+    /// Returns the sum of weights in each bin, without underflow and overflow.
+    pub fn counts(&self) -> &[f64] {
+        &self.counts[1..self.binning.bins + 1]
+    }
+
+    /// Returns the sum of weights below the first bin.
+    pub fn underflow(&self) -> f64 {
+        self.counts[0]
+    }
+
+    /// Returns the sum of weights at or above the upper edge.
+    pub fn overflow(&self) -> f64 {
+        self.counts[self.binning.bins + 1]
+    }
+
+    /// Returns the bin edges, one more than the number of bins.
+    pub fn edges(&self) -> Vec<f64> {
+        self.binning.edges()
+    }
+
+    /// Returns the number of filled values, including underflow and overflow.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Returns the weighted mean of the values in range.
+    ///
+    /// Returns NaN when no weight is in range.
+    pub fn mean(&self) -> f64 {
+        self.moments.mean()
+    }
+
+    /// Returns the weighted variance of the values in range.
+    ///
+    /// Returns NaN when no weight is in range.
+    pub fn variance(&self) -> f64 {
+        self.moments.variance()
+    }
+
+    /// Adds the contents of another histogram, e.g. filled from another file.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` if the binning differs.
+    pub fn merge(&mut self, other: &Histogram1D) -> io::Result<()> {
+        if self.binning != other.binning {return Err(io::ErrorKind::InvalidInput.into())}
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {*a += b}
+        self.entries += other.entries;
+        self.moments.merge(&other.moments);
+        Ok(())
+    }
+}
+
+/// A histogram of two variables with uniform bins.
+///
+/// Each axis has underflow and overflow bins, like `Histogram1D`.
+/// Pairs with a NaN value are not filled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram2D {
+    x: Binning,
+    y: Binning,
+    counts: Vec<f64>,
+    entries: u64,
+    moments: [Moments; 2],
+}
+
+impl Histogram2D {
+    /// Creates an empty histogram.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if an axis has no bins or its edges are not finite and increasing.
+    pub fn new(x: Binning, y: Binning) -> io::Result<Histogram2D> {
+        x.check()?;
+        y.check()?;
+        Ok(Histogram2D {
+            x,
+            y,
+            counts: vec![0.0; (x.bins + 2) * (y.bins + 2)],
+            entries: 0,
+            moments: [Moments::default(); 2],
+        })
+    }
+
+    /// Adds a pair of values with a weight.
+    pub fn fill(&mut self, x: f64, y: f64, weight: f64) {
+        let (i, j) = match (self.x.index(x), self.y.index(y)) {
+            (Some(i), Some(j)) => (i, j),
+            _ => return,
+        };
+        self.counts[j * (self.x.bins + 2) + i] += weight;
+        self.entries += 1;
+        if i > 0 && i <= self.x.bins && j > 0 && j <= self.y.bins {
+            self.moments[0].add(x, weight);
+            self.moments[1].add(y, weight);
+        }
+    }
+
+    /// Returns the binning of the x and y axes.
+    pub fn binning(&self) -> (&Binning, &Binning) {
+        (&self.x, &self.y)
+    }
+
+    /// Returns the sum of weights in a bin.
+    ///
+    /// Bin 0 is underflow and bin `bins + 1` is overflow on each axis.
+    /// Returns `None` if the bin does not exist.
+    pub fn get(&self, i: usize, j: usize) -> Option<f64> {
+        if i > self.x.bins + 1 || j > self.y.bins + 1 {return None}
+        Some(self.counts[j * (self.x.bins + 2) + i])
+    }
+
+    /// Returns the bin edges of the x and y axes.
+    pub fn edges(&self) -> (Vec<f64>, Vec<f64>) {
+        (self.x.edges(), self.y.edges())
+    }
+
+    /// Returns the number of filled pairs, including underflow and overflow.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Returns the weighted means of x and y of the pairs in range.
+    pub fn mean(&self) -> (f64, f64) {
+        (self.moments[0].mean(), self.moments[1].mean())
+    }
+
+    /// Returns the weighted variances of x and y of the pairs in range.
+    pub fn variance(&self) -> (f64, f64) {
+        (self.moments[0].variance(), self.moments[1].variance())
+    }
+
+    /// Adds the contents of another histogram, e.g. filled from another file.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` if the binning differs.
+    pub fn merge(&mut self, other: &Histogram2D) -> io::Result<()> {
+        if self.x != other.x || self.y != other.y {return Err(io::ErrorKind::InvalidInput.into())}
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {*a += b}
+        self.entries += other.entries;
+        self.moments[0].merge(&other.moments[0]);
+        self.moments[1].merge(&other.moments[1]);
+        Ok(())
+    }
+}
+
+/// A histogram filled by `fill_histograms`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Histogram {
+    /// A histogram of one variable.
+    OneD(Histogram1D),
+    /// A histogram of two variables.
+    TwoD(Histogram2D),
+}
+
+impl Histogram {
+    /// Adds the contents of another histogram, e.g. filled from another file.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if the dimensions or the binning differ.
+    pub fn merge(&mut self, other: &Histogram) -> io::Result<()> {
+        match (self, other) {
+            (Histogram::OneD(a), Histogram::OneD(b)) => a.merge(b),
+            (Histogram::TwoD(a), Histogram::TwoD(b)) => a.merge(b),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+}
+
+/// A component of a property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Component {
+    /// The property id.
+    pub property_id: u16,
+    /// The index of the scalar in each item, row by row.
+    pub index: usize,
+}
+
+/// Describes a histogram to fill.
+///
+/// ```ignore
+/// let energy = HistogramSpec::new(ENERGY, 0, Binning::new(100, 0.0, 50.0)).weight(WEIGHT);
+/// let xy = HistogramSpec::new(POSITION, 0, Binning::new(20, -1.0, 1.0))
+///     .y(POSITION, 1, Binning::new(20, -1.0, 1.0));
+/// let hists = fill_histograms(&mut file, TIME, &[energy, xy])?;
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistogramSpec {
+    /// The component on the x axis.
+    pub x: Component,
+    /// The binning of the x axis.
+    pub x_binning: Binning,
+    /// The component and binning of the y axis, for histograms of two variables.
+    pub y: Option<(Component, Binning)>,
+    /// A property with a weight per frame, the first scalar of its first instance.
+    ///
+    /// Values are filled with weight 1 in frames without this property.
+    pub weight: Option<u16>,
+}
+
+impl HistogramSpec {
+    /// Creates a spec for a histogram of one component of a property.
+    pub fn new(property_id: u16, index: usize, binning: Binning) -> HistogramSpec {
+        HistogramSpec {
+            x: Component {property_id, index},
+            x_binning: binning,
+            y: None,
+            weight: None,
+        }
+    }
+
+    /// Adds a y axis, pairing instances of the two properties with the same instance id.
+    pub fn y(mut self, property_id: u16, index: usize, binning: Binning) -> HistogramSpec {
+        self.y = Some((Component {property_id, index}, binning));
+        self
+    }
+
+    /// Sets the property with a weight per frame.
+    pub fn weight(mut self, property_id: u16) -> HistogramSpec {
+        self.weight = Some(property_id);
+        self
+    }
+}
+
+/// Reads a stream once and fills a histogram for every spec.
+///
+/// Frames start with the frame marker, and data before the first frame counts as a frame.
+/// Values are decoded with the dynamic decoder, such that any built-in type format is accepted.
+/// Instances that are not written in a frame are not filled,
+/// and histograms of two variables are filled with instances written in both properties.
+///
+/// Returns an error of kind `ErrorKind::DimensionOutOfRange` if a component
+/// is outside the items of the property,
+/// and of kind `io::ErrorKind::InvalidInput` if a binning is not valid.
+pub fn fill_histograms<R: io::Read>(
+    r: &mut R,
+    frame_marker: u16,
+    specs: &[HistogramSpec]
+) -> io::Result<Vec<Histogram>> {
+    let mut hists = specs.iter().map(|spec| Ok(match spec.y {
+        None => Histogram::OneD(Histogram1D::new(spec.x_binning)?),
+        Some((_, y_binning)) => Histogram::TwoD(Histogram2D::new(spec.x_binning, y_binning)?),
+    })).collect::<io::Result<Vec<_>>>()?;
+    let mut frames = FrameReader::new(r, frame_marker);
+    while let Some(frame) = frames.next_frame()? {
+        fill_frame(&frame.blocks, specs, &mut hists)?;
+    }
+    fill_frame(frames.preamble(), specs, &mut hists)?;
+    Ok(hists)
+}
+
+fn fill_frame(blocks: &[RawBlock], specs: &[HistogramSpec], hists: &mut [Histogram]) -> io::Result<()> {
+    for (spec, hist) in specs.iter().zip(hists) {
+        let weight = match spec.weight {
+            Some(prop) => values(blocks, Component {property_id: prop, index: 0})?
+                .first().map(|&(_, w)| w).unwrap_or(1.0),
+            None => 1.0,
+        };
+        let xs = values(blocks, spec.x)?;
+        match *hist {
+            Histogram::OneD(ref mut h) => {
+                for (_, x) in xs {h.fill(x, weight)}
+            }
+            Histogram::TwoD(ref mut h) => {
+                let ys = match spec.y {
+                    Some((y, _)) => values(blocks, y)?,
+                    None => vec![],
+                };
+                let mut ys = ys.into_iter().peekable();
+                for (i, x) in xs {
+                    while ys.peek().is_some_and(|y| y.0 < i) {ys.next();}
+                    if let Some(&(j, y)) = ys.peek() {
+                        if j == i {h.fill(x, y, weight)}
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns a component of every written instance of a property with its instance id,
+/// in order of instance id.
+fn values(blocks: &[RawBlock], c: Component) -> io::Result<Vec<(u64, f64)>> {
+    let mut res = vec![];
+    for block in blocks.iter().filter(|b| b.property_id == c.property_id) {
+        if block.type_format == PRESENCE_FORMAT {continue}
+        let size = match raw::item_size(block.type_format) {
+            Some(size) => size as usize,
+            None => continue,
+        };
+        for (i, item) in block.data.chunks_exact(size).enumerate() {
+            let val = DynValue::new(block.type_format, item.to_vec())
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+            let x = match val.get(c.index) {
+                Some(x) => x,
+                None => return Err(Error::new(ErrorKind::DimensionOutOfRange)
+                    .with_property(c.property_id)
+                    .with_range(0, val.components() as u64)
+                    .into()),
+            };
+            match block.offset.checked_add(i as u64) {
+                Some(ind) => res.push((ind, x)),
+                None => return Err(Error::new(ErrorKind::InvalidData)
+                        .with_property(c.property_id).into()),
+            }
+        }
+    }
+    // The sort is stable, so the last written value of an instance comes first.
+    res.reverse();
+    res.sort_by_key(|x| x.0);
+    res.dedup_by_key(|x| x.0);
+    Ok(res)
+}
@@ -0,0 +1,493 @@
+//! Random access to frames.
+
+use std::io::{self, Read, Seek};
+use std::ops::Range;
+
+use endian::{self, Endian, EndianReader, Header};
+use error::{Error, ErrorKind};
+use footer::{self, Footer};
+use frame::{Frame, FrameReader};
+use limits::{Limits, Usage};
+use raw;
+use reserved::{PADDING_FORMAT, PADDING_PROPERTY, TIME_UNIT_FORMAT};
+use time::Time;
+use trace;
+use Bytes;
+use State;
+
+/// Determines which frame is picked when seeking to a time.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SeekMode {
+    /// The last frame with time less than or equal to the target.
+    LastBefore,
+    /// The frame with time nearest to the target.
+    Nearest,
+}
+
+/// A frame recorded by an index.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct IndexEntry {
+    /// The byte position of the frame marker.
+    pub position: u64,
+    /// The time of the frame, if there is a time property.
+    pub time: Option<f64>,
+}
+
+/// The position of a frame, returned when seeking.
+///
+/// Pass it to `FrameReader::resume` to continue reading from the frame.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FrameCursor {
+    /// The frame number.
+    pub frame: u64,
+    /// The byte position of the frame marker.
+    pub position: u64,
+    /// The time of the frame, if there is a time property.
+    pub time: Option<f64>,
+}
+
+/// Records where frames start in a stream.
+///
+/// Frames start with a frame marker property, like in `FrameReader`.
+///
+/// When time values are not monotonic, seeking to a time
+/// picks the first frame with time nearest to the target,
+/// regardless of the seek mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Index {
+    marker: u16,
+    time_property: Option<u16>,
+    start: u64,
+    end: u64,
+    frames: Vec<IndexEntry>,
+    monotonic: bool,
+    seek_mode: SeekMode,
+}
+
+impl Index {
+    /// Builds an index by scanning from the current position to the end of stream.
+    ///
+    /// The data of properties other than the time property is skipped.
+    /// A stream starting with a header is read in the byte order set by the header.
+    /// The reader is left at the end of the stream.
+    pub fn build<R: io::Read + io::Seek>(
+        r: &mut R,
+        marker: u16,
+        time_property: Option<u16>
+    ) -> io::Result<Index> {
+        Index::build_with_limits(r, marker, time_property, Limits::default())
+    }
+
+    /// Builds an index, checking limits on the resources used.
+    pub fn build_with_limits<R: io::Read + io::Seek>(
+        r: &mut R,
+        marker: u16,
+        time_property: Option<u16>,
+        limits: Limits
+    ) -> io::Result<Index> {
+        let r = &mut EndianReader::new(r);
+        let mut usage = Usage::new(limits);
+        let start = r.stream_position()?;
+        let mut end = start;
+        let mut frames: Vec<IndexEntry> = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            usage.property(ty, prop)?;
+            let time_unit = ty == TIME_UNIT_FORMAT && Some(prop) == time_property;
+            if prop == marker && !time_unit {
+                frames.push(IndexEntry {position: end, time: None});
+            }
+            if Some(prop) == time_property && raw::item_size(ty).is_some() {
+                let blocks = raw::read_blocks(state, ty, prop, &mut usage, r)?;
+                if let (Some(frame), Some(block)) = (frames.last_mut(), blocks.first()) {
+                    if frame.time.is_none() {
+                        frame.time = Some(Time::decode(block)?);
+                    }
+                }
+            } else {
+                skip_property(state, ty, prop, &mut usage, r)?;
+            }
+            end = r.stream_position()?;
+        }
+        Ok(Index::from_frames(marker, time_property, start, end, frames))
+    }
+
+    /// Loads the index from the footer of a stream, or builds it by scanning.
+    ///
+    /// The footer is written by `PoolWriter::with_footer_index`.
+    /// The result is the same as with `build`, without reading more than the footer,
+    /// unless a time can not be taken from the footer.
+    /// Limits are only checked when scanning.
+    /// The reader is left at the end of the stream.
+    pub fn load_or_build<R: io::Read + io::Seek>(
+        r: &mut R,
+        marker: u16,
+        time_property: Option<u16>
+    ) -> io::Result<Index> {
+        let start = r.stream_position()?;
+        if let Some(footer) = footer::read(r, start)? {
+            if let Some(index) = Index::from_footer(&footer, start, marker, time_property) {
+                r.seek(io::SeekFrom::Start(start + footer.end + 2))?;
+                return Ok(index);
+            }
+        }
+        r.seek(io::SeekFrom::Start(start))?;
+        Index::build(r, marker, time_property)
+    }
+
+    /// Builds an index from the entries of a footer, like `build` does from the stream.
+    ///
+    /// Returns `None` when the time of a time property is not in the footer,
+    /// in which case `build` either finds no blocks or returns an error.
+    fn from_footer(
+        footer: &Footer,
+        start: u64,
+        marker: u16,
+        time_property: Option<u16>
+    ) -> Option<Index> {
+        let mut frames: Vec<IndexEntry> = vec![];
+        for e in &footer.entries {
+            let time_unit = e.type_format == TIME_UNIT_FORMAT &&
+                Some(e.property_id) == time_property;
+            if e.property_id == marker && !time_unit {
+                frames.push(IndexEntry {position: start + e.position, time: None});
+            }
+            if Some(e.property_id) == time_property && raw::item_size(e.type_format).is_some() {
+                if let Some(frame) = frames.last_mut() {
+                    if frame.time.is_none() {frame.time = Some(e.time?)}
+                }
+            }
+        }
+        // The footer is a property, which `build` passes over.
+        Some(Index::from_frames(marker, time_property, start, start + footer.end, frames))
+    }
+
+    fn from_frames(
+        marker: u16,
+        time_property: Option<u16>,
+        start: u64,
+        end: u64,
+        frames: Vec<IndexEntry>
+    ) -> Index {
+        let mut monotonic = true;
+        let mut last: Option<f64> = None;
+        for t in frames.iter().filter_map(|f| f.time) {
+            if t.is_nan() || last.map(|last| t < last).unwrap_or(false) {
+                monotonic = false;
+                break;
+            }
+            last = Some(t);
+        }
+        Index {
+            marker,
+            time_property,
+            start,
+            end,
+            frames,
+            monotonic,
+            seek_mode: SeekMode::LastBefore,
+        }
+    }
+
+    /// Sets which frame is picked when seeking to a time.
+    pub fn seek_mode(mut self, seek_mode: SeekMode) -> Index {
+        self.seek_mode = seek_mode;
+        self
+    }
+
+    /// Returns the frame marker property id.
+    pub fn marker(&self) -> u16 {
+        self.marker
+    }
+
+    /// Returns the time property id.
+    pub fn time_property(&self) -> Option<u16> {
+        self.time_property
+    }
+
+    /// Returns the recorded frames.
+    pub fn frames(&self) -> &[IndexEntry] {
+        &self.frames
+    }
+
+    /// Returns the number of frames.
+    pub fn frame_count(&self) -> u64 {
+        self.frames.len() as u64
+    }
+
+    /// Returns `true` if frame times never decrease.
+    ///
+    /// Frames without time are ignored.
+    pub fn is_monotonic(&self) -> bool {
+        self.monotonic
+    }
+
+    /// Returns the byte range of the stream before the first frame.
+    pub fn preamble_range(&self) -> Range<u64> {
+        self.start..self.frames.first().map(|f| f.position).unwrap_or(self.end)
+    }
+
+    /// Returns the byte range occupied by a frame.
+    ///
+    /// The range of the last frame ends before the end of stream.
+    pub fn frame_range(&self, n: u64) -> io::Result<Range<u64>> {
+        let entry = self.entry(n)?;
+        let end = if n + 1 < self.frame_count() {
+            self.frames[n as usize + 1].position
+        } else {
+            self.end
+        };
+        Ok(entry.position..end)
+    }
+
+    /// Positions the reader at the start of frame `n`.
+    ///
+    /// Returns an error of kind `ErrorKind::FrameOutOfRange`
+    /// with the valid range when `n` is out of range.
+    pub fn seek_frame<R: io::Seek>(&self, n: u64, r: &mut R) -> io::Result<FrameCursor> {
+        let entry = self.entry(n)?;
+        r.seek(io::SeekFrom::Start(entry.position))?;
+        Ok(FrameCursor {frame: n, position: entry.position, time: entry.time})
+    }
+
+    /// Positions the reader at the start of the frame picked for time `t`.
+    ///
+    /// With `SeekMode::LastBefore`, a time before the first frame
+    /// picks the first frame with time.
+    /// A time after the last frame picks the last frame with time.
+    ///
+    /// Frames without time are never picked.
+    /// Returns an error if no frame has time.
+    pub fn seek_time<R: io::Seek>(&self, t: f64, r: &mut R) -> io::Result<FrameCursor> {
+        let n = match self.find_time(t) {
+            Some(n) => n,
+            None => return Err(Error::new(ErrorKind::InvalidData).into()),
+        };
+        self.seek_frame(n as u64, r)
+    }
+
+    fn find_time(&self, t: f64) -> Option<usize> {
+        let timed = self.frames.iter().enumerate()
+            .filter_map(|(i, f)| f.time.map(|time| (i, time)));
+        if !self.monotonic || self.seek_mode == SeekMode::Nearest {
+            let mut best: Option<(usize, f64)> = None;
+            for (i, time) in timed {
+                let dist = (time - t).abs();
+                if best.map(|(_, d)| dist < d).unwrap_or(!dist.is_nan()) {
+                    best = Some((i, dist));
+                }
+            }
+            return best.map(|(i, _)| i);
+        }
+        let mut res = None;
+        for (i, time) in timed {
+            if time > t && res.is_some() {break}
+            res = Some(i);
+        }
+        res
+    }
+
+    fn entry(&self, n: u64) -> io::Result<IndexEntry> {
+        if n < self.frame_count() {
+            Ok(self.frames[n as usize])
+        } else {
+            Err(Error::new(ErrorKind::FrameOutOfRange).with_range(0, self.frame_count()).into())
+        }
+    }
+}
+
+/// The size of the tail searched first by `last_frame`, in bytes.
+const TAIL_WINDOW: u64 = 64 << 10;
+
+/// How a walk over properties stopped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Stop {
+    /// At an end of stream or at the end of the data, after a complete property.
+    End,
+    /// In the middle of a property, at the end of the data.
+    Torn,
+    /// In the middle of a frame marker, at the end of the data.
+    TornMarker,
+    /// At something that can not be a property.
+    Invalid,
+}
+
+/// Walks over properties from `pos`, returning the positions of frame markers and how it stopped.
+///
+/// Unless the walk is exact, properties without blocks are treated as invalid,
+/// since they are rare in streams but common in data that is mostly zeros.
+/// Fields are big endian when `big` is set.
+fn walk(
+    data: &[u8],
+    mut pos: usize,
+    marker: u16,
+    exact: bool,
+    big: bool
+) -> (Vec<usize>, Stop, usize) {
+    let u64_at = |i: usize| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&data[i..i + 8]);
+        if big {u64::from_be_bytes(bytes)} else {u64::from_le_bytes(bytes)}
+    };
+    let u16_at = |i: usize| {
+        let bytes = [data[i], data[i + 1]];
+        if big {u16::from_be_bytes(bytes)} else {u16::from_le_bytes(bytes)}
+    };
+    let mut markers = vec![];
+    loop {
+        if pos == data.len() {return (markers, Stop::End, pos)}
+        if pos + 2 > data.len() {return (markers, Stop::Torn, pos)}
+        let ty = u16_at(pos);
+        if ty == 0 {return (markers, Stop::End, pos)}
+        if pos + 4 > data.len() {return (markers, Stop::Torn, pos)}
+        let prop = u16_at(pos + 2);
+        let is_marker = prop == marker && starts_frame(ty, prop);
+        let torn = if is_marker {Stop::TornMarker} else {Stop::Torn};
+        let start = pos;
+        pos += 4;
+        loop {
+            if pos + 8 > data.len() {return (markers, torn, start)}
+            let bytes = u64_at(pos);
+            pos += 8;
+            if bytes == 0 && !exact && pos == start + 12 {return (markers, Stop::Invalid, start)}
+            if bytes == 0 {break}
+            if raw::item_size(ty).is_some_and(|size| size == 0 || bytes % size != 0) {
+                return (markers, Stop::Invalid, start);
+            }
+            if pos + 8 > data.len() || bytes > (data.len() - pos - 8) as u64 {
+                return (markers, torn, start);
+            }
+            pos += 8 + bytes as usize;
+        }
+        if is_marker {markers.push(start)}
+    }
+}
+
+/// Returns the last complete frame of a walk, as a range of positions.
+///
+/// When the walk did not start at the start of the stream, a torn walk
+/// must contain two frame markers to be trusted.
+fn last_complete(markers: &[usize], stop: Stop, end: usize, exact: bool) -> Option<Range<usize>> {
+    let min = if exact {1} else {2};
+    match (stop, markers.len()) {
+        (Stop::End, n) if n > 0 => Some(markers[n - 1]..end),
+        (Stop::TornMarker, n) if n >= min => Some(markers[n - 1]..end),
+        (Stop::Torn, n) if n > 1 => Some(markers[n - 2]..markers[n - 1]),
+        _ => None,
+    }
+}
+
+/// Reads the last complete frame of a stream, without reading the whole stream.
+///
+/// Frames start with a frame marker property, like in `FrameReader`.
+/// When the stream has a footer index, written by `PoolWriter::with_footer_index`,
+/// the frame is found with the footer, which is exact.
+///
+/// Otherwise, since blocks are not linked backwards,
+/// the frame is found by searching the tail of the stream,
+/// reading a larger part of the tail while no frame is found.
+/// A candidate frame marker is accepted when the properties following it
+/// parse up to the end of stream or the end of the data.
+/// When the tail is torn, e.g. after a crash, the frame before the torn one is returned,
+/// as long as the properties up to the torn one parse.
+/// This is heuristic when the stream does not start in the part searched,
+/// since data may happen to parse as properties.
+/// It is exact once the whole stream is searched, which is done when no frame is found before.
+///
+/// The frame number is 0, since frames before are not counted.
+/// The time is set when the frame marker is a time stored as `f32` or `f64`.
+/// Returns an error of kind `ErrorKind::FrameOutOfRange` if the stream has no complete frame.
+/// The stream must start at the start of the reader.
+/// A big endian stream is read in the byte order set by its header.
+pub fn last_frame<R: io::Read + io::Seek>(r: &mut R, frame_marker: u16) -> io::Result<Frame> {
+    let no_frame = || io::Error::from(Error::new(ErrorKind::FrameOutOfRange));
+    let (header, first) = match endian::read_header(r, 0)? {
+        Some((header, end)) => (Some(header), end as usize),
+        None => (None, 0),
+    };
+    let big = header.map(|h| h.endian == Endian::Big).unwrap_or(false);
+    if let Some(footer) = footer::read(r, 0)? {
+        let e = footer.entries.iter().rev()
+            .find(|e| e.property_id == frame_marker && starts_frame(e.type_format, e.property_id))
+            .ok_or_else(no_frame)?;
+        r.seek(io::SeekFrom::Start(e.position))?;
+        let mut data = vec![];
+        r.by_ref().take(footer.position - e.position).read_to_end(&mut data)?;
+        return read_frame(&data, frame_marker, header);
+    }
+
+    let len = r.seek(io::SeekFrom::End(0))?;
+    let mut window = TAIL_WINDOW;
+    loop {
+        let start = len.saturating_sub(window);
+        r.seek(io::SeekFrom::Start(start))?;
+        let mut data = vec![];
+        r.by_ref().take(len - start).read_to_end(&mut data)?;
+
+        let found = if start == 0 {
+            // The header is little endian, and is passed over when the rest is not.
+            let first = if big {first.min(data.len())} else {0};
+            let (markers, stop, end) = walk(&data, first, frame_marker, true, big);
+            Some(last_complete(&markers, stop, end, true).ok_or_else(no_frame)?)
+        } else {
+            let marker = if big {frame_marker.to_be_bytes()} else {frame_marker.to_le_bytes()};
+            (0..data.len().saturating_sub(3))
+                .filter(|&p| data[p + 2..p + 4] == marker && data[p..p + 2] != [0, 0])
+                .find_map(|p| {
+                    let (markers, stop, end) = walk(&data, p, frame_marker, false, big);
+                    // An end of stream is only written at the end of the data,
+                    // or before the trailer of a footer.
+                    let after = (data.len() - end) as u64;
+                    if stop == Stop::End && after > 2 && after != 2 + footer::TRAILER {return None}
+                    last_complete(&markers, stop, end, false)
+                })
+        };
+        if let Some(range) = found {return read_frame(&data[range], frame_marker, header)}
+        window = window.saturating_mul(2);
+    }
+}
+
+/// Returns `true` if a property with the id of the frame marker starts a frame.
+///
+/// Time units and padding do not start frames.
+fn starts_frame(type_format: u16, property_id: u16) -> bool {
+    type_format != TIME_UNIT_FORMAT &&
+        !(type_format == PADDING_FORMAT && property_id == PADDING_PROPERTY)
+}
+
+/// Reads a frame from data starting with its frame marker,
+/// in the byte order of the header of the stream.
+fn read_frame(data: &[u8], frame_marker: u16, header: Option<Header>) -> io::Result<Frame> {
+    let mut frames = FrameReader::new(EndianReader::resume(data, header), frame_marker);
+    let mut frame = frames.next_frame()?
+        .ok_or_else(|| io::Error::from(Error::new(ErrorKind::FrameOutOfRange)))?;
+    frame.time = frame.blocks.first()
+        .filter(|b| b.property_id == frame_marker)
+        .and_then(|b| Time::decode(b).ok());
+    Ok(frame)
+}
+
+/// Skips the blocks of a property without reading the data.
+pub(crate) fn skip_property<R: io::Read + io::Seek>(
+    state: State<Bytes>,
+    ty: u16,
+    prop: u16,
+    usage: &mut Usage,
+    r: &mut R
+) -> io::Result<()> {
+    let mut state = state;
+    let mut bytes = 0;
+    loop {
+        let (header, data_state) = state.read_block_header(ty, prop, r)?;
+        if header.is_end() {
+            trace::skip(prop, ty, bytes);
+            return Ok(());
+        }
+        usage.block(header.bytes, header.offset)?;
+        if header.bytes > i64::MAX as u64 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        r.seek(io::SeekFrom::Current(header.bytes as i64))?;
+        bytes += header.bytes;
+        state = data_state.end_data();
+    }
+}
@@ -0,0 +1,84 @@
+//! Portable arrays of indices.
+//!
+//! `usize` has a different size on different platforms,
+//! so it does not implement `Scalar`.
+//! Indices are written as `u64` and read from `u32` or `u64`.
+
+use std::io;
+
+use error::{Error, ErrorKind, Field};
+use read_write::Scalar;
+use Bytes;
+use State;
+use Type;
+
+/// Writes an array of indices as `u64`.
+pub fn write_index_array<W: io::Write>(
+    property_id: u16,
+    arr: &[usize],
+    w: &mut W
+) -> io::Result<()> {
+    let (ty, s) = Type::U64.scalar();
+    let state = State::new()
+        .write_type_format(ty, w)?
+        .write_property_id(property_id, w)?
+        .write_bytes(s * arr.len() as u64, w)?
+        .write_offset_instance_id(0, w)?;
+    for &x in arr {
+        (x as u64).write(w)?;
+    }
+    state.end_data().end_bytes(w)?;
+    Ok(())
+}
+
+/// Reads an array of indices stored as `u32` or `u64`.
+///
+/// Returns an error if an index does not fit in `usize` on this platform.
+pub fn read_index_array<R: io::Read>(
+    state: State<Bytes>,
+    ty: u16,
+    arr: &mut Vec<usize>,
+    r: &mut R
+) -> io::Result<()> {
+    if ty == Type::U32.scalar().0 {
+        read::<u32, R>(state, arr, r)
+    } else if ty == Type::U64.scalar().0 {
+        read::<u64, R>(state, arr, r)
+    } else {
+        Err(Error::new(ErrorKind::TypeMismatch).with_types((Type::U64, 1, 1), ty).into())
+    }
+}
+
+fn read<T, R>(state: State<Bytes>, arr: &mut Vec<usize>, r: &mut R) -> io::Result<()>
+    where T: Scalar + Into<u64>, R: io::Read
+{
+    let mut bytes = 0;
+    let state = state.read_bytes(&mut bytes, r)?;
+    let (_, scalar_bytes) = T::ty().scalar();
+    if !bytes.is_multiple_of(scalar_bytes) {
+        return Err(Error::new(ErrorKind::BytesNotMultiple)
+            .with_item_size(bytes, scalar_bytes)
+            .into());
+    }
+    let n = bytes / scalar_bytes;
+    let mut offset = 0;
+    let state = state.read_offset_instance_id(&mut offset, r)?;
+    let end = match offset.checked_add(n) {
+        Some(end) if end <= usize::MAX as u64 => end,
+        _ => return Err(io::ErrorKind::InvalidData.into()),
+    };
+    for i in offset..end {
+        let mut x: T = Default::default();
+        x.read(r)?;
+        let x: u64 = x.into();
+        if x > usize::MAX as u64 {
+            return Err(Error::new(ErrorKind::InvalidData).with_field(Field::Data).into());
+        }
+        while i as usize >= arr.len() {
+            arr.push(0);
+        }
+        arr[i as usize] = x as usize;
+    }
+    state.end_data().has_end_bytes(r)?;
+    Ok(())
+}
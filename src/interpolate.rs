@@ -0,0 +1,153 @@
+//! Interpolating between frames for smooth playback.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use frame::Frame;
+use raw::{self, RawBlock};
+use Type;
+
+/// Blends float properties of two frames.
+///
+/// Scalars, vectors and matrices of `f32` and `f64` are interpolated linearly,
+/// component by component.
+/// Other properties keep the values of the earlier frame,
+/// or of the nearer frame with `Interpolator::nearest`.
+///
+/// The frames should contain every property, e.g. as returned by `Player::tick`,
+/// since properties that are missing in the earlier frame are missing in the result.
+///
+/// ```ignore
+/// let interpolator = Interpolator::new(&[POSITION, VELOCITY]);
+/// let view = interpolator.blend(&frame_a, &frame_b, alpha)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Interpolator {
+    properties: Vec<u16>,
+    nearest: bool,
+}
+
+impl Interpolator {
+    /// Creates a new interpolator for properties.
+    pub fn new(properties: &[u16]) -> Interpolator {
+        Interpolator {properties: properties.to_vec(), nearest: false}
+    }
+
+    /// Sets whether properties that are not interpolated are taken from the nearer frame,
+    /// e.g. integers, instead of the earlier frame.
+    ///
+    /// The later frame is nearer at `alpha >= 0.5`.
+    pub fn nearest(mut self, val: bool) -> Interpolator {
+        self.nearest = val;
+        self
+    }
+
+    /// Returns the properties that are interpolated.
+    pub fn properties(&self) -> &[u16] {
+        &self.properties
+    }
+
+    /// Returns the frame at `alpha` between frame `a` at 0 and frame `b` at 1.
+    ///
+    /// The result has the number of frame `a`, with time interpolated when both frames have time.
+    /// An interpolated property is written as one block at offset 0,
+    /// in the place of its first block in the frame the other properties are taken from.
+    /// When the arrays have different lengths, items that are only in one frame
+    /// are taken from the nearer frame, such that the result has the length of the nearer frame.
+    /// A property keeps its values when it is missing in the other frame
+    /// or has another type format.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` if `alpha` is not in `0..=1`,
+    /// or `ErrorKind::InvalidData` if a block offset is out of range.
+    pub fn blend(&self, a: &Frame, b: &Frame, alpha: f64) -> io::Result<Frame> {
+        if !(0.0..=1.0).contains(&alpha) {return Err(io::ErrorKind::InvalidInput.into())}
+        let time = match (a.time, b.time) {
+            (Some(ta), Some(tb)) => Some(lerp(ta, tb, alpha)),
+            (ta, _) => ta,
+        };
+        let later = self.nearest && alpha >= 0.5;
+        let (base, other) = if later {(b, a)} else {(a, b)};
+        let mut blocks = vec![];
+        let mut done = vec![];
+        for block in &base.blocks {
+            let prop = block.property_id;
+            let ty = block.type_format;
+            if !self.properties.contains(&prop) || !is_float(ty) {
+                blocks.push(block.clone());
+                continue;
+            }
+            if done.contains(&prop) {continue}
+            done.push(prop);
+            let items_base = items(base, ty, prop)?;
+            let data = if other.blocks.iter().any(|x| x.property_id == prop && x.type_format == ty) {
+                let items_other = items(other, ty, prop)?;
+                if later {
+                    blend_items(ty, &items_other, &items_base, alpha)
+                } else {
+                    blend_items(ty, &items_base, &items_other, alpha)
+                }
+            } else {
+                items_base
+            };
+            blocks.push(RawBlock {type_format: ty, property_id: prop, offset: 0, data});
+        }
+        Ok(Frame {number: a.number, time, blocks})
+    }
+}
+
+/// Returns `true` if a format is a built-in format of floats.
+fn is_float(type_format: u16) -> bool {
+    matches!(Type::info(type_format), Some((Type::F32, _, _)) | Some((Type::F64, _, _)))
+}
+
+/// Merges the blocks of a property with a type format into items starting at offset 0.
+fn items(frame: &Frame, ty: u16, prop: u16) -> io::Result<Vec<u8>> {
+    let size = raw::item_size(ty).unwrap_or(1);
+    let mut items = vec![];
+    for block in frame.blocks_of(prop).filter(|b| b.type_format == ty) {
+        let end = match block.offset.checked_mul(size)
+            .and_then(|start| start.checked_add(block.data.len() as u64)) {
+            Some(end) if end <= usize::MAX as u64 => end as usize,
+            _ => return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into()),
+        };
+        let start = end - block.data.len();
+        if items.len() < end {items.resize(end, 0)}
+        items[start..end].copy_from_slice(&block.data);
+    }
+    Ok(items)
+}
+
+/// Interpolates the little endian scalars of items.
+fn blend_items(ty: u16, a: &[u8], b: &[u8], alpha: f64) -> Vec<u8> {
+    let size = raw::item_size(ty).unwrap_or(1) as usize;
+    let nearer = if alpha < 0.5 {a} else {b};
+    let len = nearer.len() - nearer.len() % size;
+    let common = a.len().min(b.len()) / size * size;
+    let mut data = Vec::with_capacity(len);
+    match Type::info(ty) {
+        Some((Type::F32, _, _)) => {
+            for (x, y) in a[..common].chunks(4).zip(b[..common].chunks(4)) {
+                let x = f32::from_le_bytes([x[0], x[1], x[2], x[3]]) as f64;
+                let y = f32::from_le_bytes([y[0], y[1], y[2], y[3]]) as f64;
+                data.extend_from_slice(&(lerp(x, y, alpha) as f32).to_le_bytes());
+            }
+        }
+        _ => {
+            for (x, y) in a[..common].chunks(8).zip(b[..common].chunks(8)) {
+                let mut bx = [0; 8];
+                let mut by = [0; 8];
+                bx.copy_from_slice(x);
+                by.copy_from_slice(y);
+                let val = lerp(f64::from_le_bytes(bx), f64::from_le_bytes(by), alpha);
+                data.extend_from_slice(&val.to_le_bytes());
+            }
+        }
+    }
+    if len > common {data.extend_from_slice(&nearer[common..len])}
+    data
+}
+
+/// Interpolates linearly, giving `a` at 0 and `b` at 1 exactly.
+fn lerp(a: f64, b: f64, alpha: f64) -> f64 {
+    if alpha < 0.5 {a + (b - a) * alpha} else {b - (b - a) * (1.0 - alpha)}
+}
@@ -0,0 +1,97 @@
+//! Writing arrays from iterators.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use read_write::Element;
+use State;
+
+/// Writes the items of an iterator as one block, without collecting them.
+///
+/// The length of the iterator is used to write the number of bytes before the items.
+/// Items are written one at a time, so use a buffered writer.
+///
+/// Returns an error of kind `ErrorKind::LengthMismatch` if the iterator yields
+/// a different number of items than its length,
+/// with the instance where the items differ.
+/// The stream is not valid after this error.
+pub fn write_from_iter<T, I, W>(property_id: u16, iter: I, w: &mut W) -> io::Result<()>
+    where T: Element, I: IntoIterator<Item = T>, I::IntoIter: ExactSizeIterator, W: io::Write
+{
+    let (ty, size) = match T::format() {
+        Some(x) => x,
+        None => return Err(io::ErrorKind::InvalidInput.into()),
+    };
+    let mut iter = iter.into_iter();
+    let n = iter.len() as u64;
+    let mismatch = |instance: u64| -> io::Error {
+        Error::new(ErrorKind::LengthMismatch)
+            .with_property(property_id)
+            .with_instance(instance)
+            .into()
+    };
+    let state = State::new()
+        .write_type_format(ty, w)?
+        .write_property_id(property_id, w)?;
+    if n == 0 {
+        if iter.next().is_some() {return Err(mismatch(0))}
+        state.end_bytes(w)?;
+        return Ok(());
+    }
+    let state = state
+        .write_bytes(size * n, w)?
+        .write_offset_instance_id(0, w)?;
+    for i in 0..n {
+        match iter.next() {
+            Some(x) => x.write_element(w)?,
+            None => return Err(mismatch(i)),
+        }
+    }
+    if iter.next().is_some() {return Err(mismatch(n))}
+    state.end_data().end_bytes(w)?;
+    Ok(())
+}
+
+/// Writes the items of an iterator of unknown length,
+/// as blocks of at most `chunk_len` items.
+///
+/// Each block is buffered before it is written,
+/// and has the offset instance id of its first item.
+pub fn write_from_iter_chunked<T, I, W>(
+    property_id: u16,
+    iter: I,
+    chunk_len: usize,
+    w: &mut W
+) -> io::Result<()>
+    where T: Element, I: IntoIterator<Item = T>, W: io::Write
+{
+    let (ty, size) = match T::format() {
+        Some(x) => x,
+        None => return Err(io::ErrorKind::InvalidInput.into()),
+    };
+    if chunk_len == 0 {return Err(io::ErrorKind::InvalidInput.into())}
+    let mut iter = iter.into_iter();
+    let mut state = State::new()
+        .write_type_format(ty, w)?
+        .write_property_id(property_id, w)?;
+    let mut buf = Vec::with_capacity(size as usize * chunk_len);
+    let mut offset = 0;
+    loop {
+        buf.clear();
+        let mut n = 0;
+        for x in iter.by_ref().take(chunk_len) {
+            x.write_element(&mut buf)?;
+            n += 1;
+        }
+        if n == 0 {break}
+        state = state
+            .write_bytes(buf.len() as u64, w)?
+            .write_offset_instance_id(offset, w)?
+            .write_data(&buf, w)?
+            .end_data();
+        offset += n;
+        if n < chunk_len as u64 {break}
+    }
+    state.end_bytes(w)?;
+    Ok(())
+}
@@ -0,0 +1,198 @@
+//! Joining the values of properties by frame.
+
+use std::io::{self, SeekFrom};
+
+use dyn_value::DynValue;
+use index::Index;
+use raw;
+use read_write::Element;
+use series;
+
+/// The time and values of two properties in a frame.
+type Joined<A, B> = io::Result<(f64, Option<A>, Option<B>)>;
+
+/// Determines what happens in frames where a property was not written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Missing {
+    /// Skip the frame.
+    #[default]
+    Skip,
+    /// Yield `None` for the property.
+    Yield,
+    /// Repeat the last value of the property.
+    ///
+    /// Frames before the first value of the property are skipped.
+    CarryForward,
+}
+
+/// Options for joining properties by frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JoinOptions {
+    /// What happens in frames where a property was not written.
+    pub missing: Missing,
+    /// The instance of every property to join.
+    pub instance: u64,
+}
+
+impl JoinOptions {
+    /// Sets what happens in frames where a property was not written.
+    pub fn missing(mut self, val: Missing) -> JoinOptions {
+        self.missing = val;
+        self
+    }
+
+    /// Sets the instance of every property to join.
+    pub fn instance(mut self, val: u64) -> JoinOptions {
+        self.instance = val;
+        self
+    }
+}
+
+/// An iterator over the time and values of properties in every frame.
+///
+/// Created by `join_frames_n`.
+/// Values are `None` only for properties missing with `Missing::Yield`.
+/// The iterator stops after the first error.
+pub struct JoinFrames<'a, R: 'a> {
+    r: &'a mut R,
+    index: Index,
+    properties: Vec<u16>,
+    opts: JoinOptions,
+    last: Vec<Option<DynValue>>,
+    next: u64,
+    done: bool,
+}
+
+impl<'a, R: io::Read + io::Seek> JoinFrames<'a, R> {
+    /// Returns the values of the next frame, or `None` if it is skipped.
+    fn frame(&mut self, n: u64) -> io::Result<Option<(f64, Vec<Option<DynValue>>)>> {
+        let frame = self.index.lazy_frame(n, self.r)?;
+        let t = match frame.time() {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let mut values = Vec::with_capacity(self.properties.len());
+        let mut skip = false;
+        for (i, &prop) in self.properties.iter().enumerate() {
+            let val = match series::locate(frame.blocks_of(prop), self.opts.instance) {
+                Some((ty, position)) => {
+                    self.r.seek(SeekFrom::Start(position))?;
+                    let mut data = vec![0; raw::item_size(ty).unwrap_or(0) as usize];
+                    self.r.read_exact(&mut data)?;
+                    let val = DynValue::new(ty, data)
+                        .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+                    self.last[i] = Some(val.clone());
+                    Some(val)
+                }
+                None => match self.opts.missing {
+                    Missing::Skip => {
+                        skip = true;
+                        None
+                    }
+                    Missing::Yield => None,
+                    Missing::CarryForward => {
+                        if self.last[i].is_none() {skip = true}
+                        self.last[i].clone()
+                    }
+                },
+            };
+            values.push(val);
+        }
+        Ok(if skip {None} else {Some((t, values))})
+    }
+}
+
+impl<'a, R: io::Read + io::Seek> Iterator for JoinFrames<'a, R> {
+    type Item = io::Result<(f64, Vec<Option<DynValue>>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done && self.next < self.index.frame_count() {
+            let n = self.next;
+            self.next += 1;
+            match self.frame(n) {
+                Ok(Some(x)) => return Some(Ok(x)),
+                Ok(None) => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Returns an iterator over the time and values of any number of properties in every frame.
+///
+/// Frames start with the time property.
+/// Only the block headers of every frame are read,
+/// and the data of one item per property and frame, such that whole arrays are not decoded.
+/// When several blocks in a frame contain the instance, the last one wins.
+/// Blocks with custom formats and data before the first frame are skipped.
+pub fn join_frames_n<'a, R: io::Read + io::Seek>(
+    r: &'a mut R,
+    time_property: u16,
+    properties: &[u16],
+    opts: &JoinOptions
+) -> io::Result<JoinFrames<'a, R>> {
+    let index = Index::build(r, time_property, Some(time_property))?;
+    Ok(JoinFrames {
+        r,
+        index,
+        properties: properties.to_vec(),
+        opts: *opts,
+        last: vec![None; properties.len()],
+        next: 0,
+        done: false,
+    })
+}
+
+/// Returns an iterator over the time and values of two properties in every frame.
+///
+/// See `join_frames_n`.
+///
+/// ```ignore
+/// let opts = JoinOptions::default().missing(Missing::CarryForward);
+/// for x in join_frames(&mut file, TIME, (ENERGY, COUNT), &opts)? {
+///     let (t, energy, count) = x?;
+/// }
+/// ```
+pub fn join_frames<'a, R: io::Read + io::Seek>(
+    r: &'a mut R,
+    time_property: u16,
+    (a, b): (u16, u16),
+    opts: &JoinOptions
+) -> io::Result<impl Iterator<Item = Joined<DynValue, DynValue>> + 'a> {
+    Ok(join_frames_n(r, time_property, &[a, b], opts)?.map(|x| x.map(|(t, mut values)| {
+        let b = values.pop().unwrap_or(None);
+        let a = values.pop().unwrap_or(None);
+        (t, a, b)
+    })))
+}
+
+/// Returns an iterator over the time and values of two properties in every frame,
+/// as element types.
+///
+/// See `join_frames_n`.
+/// Yields an error of kind `ErrorKind::TypeMismatch` if a value has another type format.
+pub fn join_frames_as<'a, A, B, R>(
+    r: &'a mut R,
+    time_property: u16,
+    properties: (u16, u16),
+    opts: &JoinOptions
+) -> io::Result<impl Iterator<Item = Joined<A, B>> + 'a>
+    where A: 'a + Element, B: 'a + Element, R: io::Read + io::Seek
+{
+    Ok(join_frames(r, time_property, properties, opts)?.map(|x| {
+        let (t, a, b) = x?;
+        let a = match a {
+            Some(a) => Some(a.decode::<A>()?),
+            None => None,
+        };
+        let b = match b {
+            Some(b) => Some(b.decode::<B>()?),
+            None => None,
+        };
+        Ok((t, a, b))
+    }))
+}
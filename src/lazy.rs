@@ -0,0 +1,199 @@
+//! Frames with properties that are read when accessed.
+
+use std::io;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use error::{Error, ErrorKind};
+use index::Index;
+use raw;
+use read_write::{self, Array, Element};
+use reserved::TIME_UNIT_FORMAT;
+
+/// The location of the data of a block in a stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BlockLocation {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// The byte position of the data.
+    pub position: u64,
+    /// Number of bytes of data.
+    pub bytes: u64,
+    /// Offset instance id.
+    pub offset: u64,
+}
+
+/// A frame where only the location of blocks is known.
+///
+/// Created by `Index::lazy_frame`, which reads the headers of the frame
+/// but skips the data:
+///
+/// ```ignore
+/// let frame = index.lazy_frame(500, &mut file)?;
+/// if let Some(pos) = frame.property::<[f32; 3]>(POSITION) {
+///     let pos = pos.load(&mut file)?;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LazyFrame {
+    number: u64,
+    time: Option<f64>,
+    properties: Vec<(u16, Vec<BlockLocation>)>,
+}
+
+impl LazyFrame {
+    /// Returns the frame number.
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    /// Returns the time of the frame, if there is a time property.
+    pub fn time(&self) -> Option<f64> {
+        self.time
+    }
+
+    /// Returns the property ids in the frame, in the order they first appear.
+    pub fn property_ids(&self) -> Vec<u16> {
+        self.properties.iter().map(|&(prop, _)| prop).collect()
+    }
+
+    /// Returns the blocks of a property, in the order they appear.
+    pub fn blocks_of(&self, property_id: u16) -> &[BlockLocation] {
+        self.properties.iter()
+            .find(|&&(prop, _)| prop == property_id)
+            .map(|(_, blocks)| &blocks[..])
+            .unwrap_or(&[])
+    }
+
+    /// Returns a handle to a property, or `None` if it is not in the frame.
+    ///
+    /// The type format is checked when loading.
+    pub fn property<T: Element>(&self, property_id: u16) -> Option<LazyProperty<T>> {
+        let blocks = self.blocks_of(property_id);
+        if blocks.is_empty() {return None}
+        Some(LazyProperty {
+            property_id,
+            blocks: blocks.into(),
+            element: PhantomData,
+        })
+    }
+}
+
+/// A handle to a property that is read when loaded.
+///
+/// Handles are cheap to clone and do not depend on each other
+/// or on the frame that created them.
+#[derive(Debug)]
+pub struct LazyProperty<T> {
+    property_id: u16,
+    blocks: Arc<[BlockLocation]>,
+    element: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for LazyProperty<T> {
+    fn clone(&self) -> LazyProperty<T> {
+        LazyProperty {
+            property_id: self.property_id,
+            blocks: self.blocks.clone(),
+            element: PhantomData,
+        }
+    }
+}
+
+impl<T: Element> LazyProperty<T> {
+    /// Returns the property id.
+    pub fn property_id(&self) -> u16 {
+        self.property_id
+    }
+
+    /// Returns the location of the blocks.
+    pub fn blocks(&self) -> &[BlockLocation] {
+        &self.blocks
+    }
+
+    /// Seeks to the blocks and decodes them into an array.
+    pub fn load<R: io::Read + io::Seek>(&self, r: &mut R) -> io::Result<Vec<T>> {
+        let mut arr = vec![];
+        self.load_into(&mut arr, r)?;
+        Ok(arr)
+    }
+
+    /// Seeks to the blocks and decodes them into an existing array,
+    /// overwriting items by offset instance id.
+    ///
+    /// Returns an error if the type format does not match the element type.
+    pub fn load_into<R, A>(&self, arr: &mut A, r: &mut R) -> io::Result<()>
+        where R: io::Read + io::Seek, A: Array<Item = T>
+    {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(self.property_id).into()
+        };
+        let (format, size) = match T::format() {
+            Some(x) => x,
+            None => return Err(err()),
+        };
+        for block in self.blocks.iter() {
+            if block.type_format != format || !block.bytes.is_multiple_of(size) {
+                return Err(err());
+            }
+        }
+        for block in self.blocks.iter() {
+            let end = match block.offset.checked_add(block.bytes / size) {
+                Some(end) if end <= usize::MAX as u64 => end,
+                _ => return Err(err()),
+            };
+            r.seek(io::SeekFrom::Start(block.position))?;
+            for i in block.offset..end {
+                let mut val: T = Default::default();
+                val.read_element(r)?;
+                read_write::make_room(arr, i as usize, end as usize)?;
+                arr.set(i as usize, val);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Index {
+    /// Reads the block headers of frame `n`, skipping the data.
+    ///
+    /// The reader is left at the end of the frame.
+    pub fn lazy_frame<R: io::Read + io::Seek>(&self, n: u64, r: &mut R) -> io::Result<LazyFrame> {
+        let range = self.frame_range(n)?;
+        let cursor = self.seek_frame(n, r)?;
+        let mut properties: Vec<(u16, Vec<BlockLocation>)> = vec![];
+        let mut pos = range.start;
+        while pos < range.end {
+            let (state, ty, prop) = match raw::read_property_header(r)? {
+                Some(x) => x,
+                None => break,
+            };
+            let time_unit = ty == TIME_UNIT_FORMAT && Some(prop) == self.time_property();
+            let mut state = state;
+            loop {
+                let (header, data_state) = state.read_block_header(ty, prop, r)?;
+                if header.is_end() {break}
+                let position = r.stream_position()?;
+                if header.bytes > i64::MAX as u64 {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+                r.seek(io::SeekFrom::Current(header.bytes as i64))?;
+                if !time_unit {
+                    let block = BlockLocation {
+                        type_format: ty,
+                        position,
+                        bytes: header.bytes,
+                        offset: header.offset,
+                    };
+                    match properties.iter_mut().find(|&&mut (p, _)| p == prop) {
+                        Some(&mut (_, ref mut blocks)) => blocks.push(block),
+                        None => properties.push((prop, vec![block])),
+                    }
+                }
+                state = data_state.end_data();
+            }
+            pos = r.stream_position()?;
+        }
+        Ok(LazyFrame {number: n, time: cursor.time, properties})
+    }
+}
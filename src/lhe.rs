@@ -0,0 +1,418 @@
+//! Importing Les Houches Event files.
+//!
+//! Every event is written as one frame:
+//!
+//! ```ignore
+//! event: event number, counting from 0, written as time
+//! process_id: i32, IDPRUP
+//! weight: f64, XWGTUP
+//! scale: f64, SCALUP
+//! aqed: f64, AQEDUP
+//! aqcd: f64, AQCDUP
+//! pdg_id: i32, IDUP of every particle
+//! status: i32, ISTUP of every particle
+//! mothers: [i32; 2], MOTHUP of every particle
+//! colors: [i32; 2], ICOLUP of every particle
+//! momentum: [f64; 4], px, py, pz and e of every particle
+//! mass: f64, the mass of every particle
+//! lifetime: f64, VTIMUP of every particle
+//! spin: f64, SPINUP of every particle
+//! ```
+//!
+//! The instance id of a particle is its position in the event minus 1,
+//! such that mothers refer to instance ids plus 1.
+//! Names of the properties are written as metadata before the first frame,
+//! with the text of the `<init>` block under the key `lhe.init` of the event property.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use hepmc::parse;
+use metadata::{self, Metadata, NAME};
+use read_write::{Scalar, Vector};
+use time::Time;
+use State;
+
+/// The key of the `<init>` block in the metadata of the event property.
+pub const LHE_INIT: &str = "lhe.init";
+
+/// Property ids of the data imported from Les Houches Event files.
+///
+/// Data with no property id is not written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LheOptions {
+    /// The event number, written as time at the start of every frame.
+    pub event: u16,
+    /// The process id of every event.
+    pub process_id: Option<u16>,
+    /// The weight of every event.
+    pub weight: Option<u16>,
+    /// The scale of every event.
+    pub scale: Option<u16>,
+    /// The QED coupling of every event.
+    pub aqed: Option<u16>,
+    /// The QCD coupling of every event.
+    pub aqcd: Option<u16>,
+    /// The PDG id of every particle.
+    pub pdg_id: Option<u16>,
+    /// The status of every particle.
+    pub status: Option<u16>,
+    /// The first and last mother of every particle.
+    pub mothers: Option<u16>,
+    /// The color and anticolor of every particle.
+    pub colors: Option<u16>,
+    /// The four-momentum of every particle.
+    pub momentum: Option<u16>,
+    /// The mass of every particle.
+    pub mass: Option<u16>,
+    /// The lifetime of every particle.
+    pub lifetime: Option<u16>,
+    /// The spin of every particle.
+    pub spin: Option<u16>,
+}
+
+impl Default for LheOptions {
+    fn default() -> LheOptions {
+        LheOptions {
+            event: 0,
+            process_id: Some(1),
+            weight: Some(2),
+            scale: Some(3),
+            aqed: Some(4),
+            aqcd: Some(5),
+            pdg_id: Some(6),
+            status: Some(7),
+            mothers: Some(8),
+            colors: Some(9),
+            momentum: Some(10),
+            mass: Some(11),
+            lifetime: Some(12),
+            spin: Some(13),
+        }
+    }
+}
+
+impl LheOptions {
+    /// Sets the property id of the event number.
+    pub fn event(mut self, val: u16) -> LheOptions {
+        self.event = val;
+        self
+    }
+
+    /// Sets the property id of the process id.
+    pub fn process_id(mut self, val: Option<u16>) -> LheOptions {
+        self.process_id = val;
+        self
+    }
+
+    /// Sets the property id of the weight.
+    pub fn weight(mut self, val: Option<u16>) -> LheOptions {
+        self.weight = val;
+        self
+    }
+
+    /// Sets the property id of the scale.
+    pub fn scale(mut self, val: Option<u16>) -> LheOptions {
+        self.scale = val;
+        self
+    }
+
+    /// Sets the property id of the QED coupling.
+    pub fn aqed(mut self, val: Option<u16>) -> LheOptions {
+        self.aqed = val;
+        self
+    }
+
+    /// Sets the property id of the QCD coupling.
+    pub fn aqcd(mut self, val: Option<u16>) -> LheOptions {
+        self.aqcd = val;
+        self
+    }
+
+    /// Sets the property id of the PDG id.
+    pub fn pdg_id(mut self, val: Option<u16>) -> LheOptions {
+        self.pdg_id = val;
+        self
+    }
+
+    /// Sets the property id of the status.
+    pub fn status(mut self, val: Option<u16>) -> LheOptions {
+        self.status = val;
+        self
+    }
+
+    /// Sets the property id of the mothers.
+    pub fn mothers(mut self, val: Option<u16>) -> LheOptions {
+        self.mothers = val;
+        self
+    }
+
+    /// Sets the property id of the colors.
+    pub fn colors(mut self, val: Option<u16>) -> LheOptions {
+        self.colors = val;
+        self
+    }
+
+    /// Sets the property id of the four-momentum.
+    pub fn momentum(mut self, val: Option<u16>) -> LheOptions {
+        self.momentum = val;
+        self
+    }
+
+    /// Sets the property id of the mass.
+    pub fn mass(mut self, val: Option<u16>) -> LheOptions {
+        self.mass = val;
+        self
+    }
+
+    /// Sets the property id of the lifetime.
+    pub fn lifetime(mut self, val: Option<u16>) -> LheOptions {
+        self.lifetime = val;
+        self
+    }
+
+    /// Sets the property id of the spin.
+    pub fn spin(mut self, val: Option<u16>) -> LheOptions {
+        self.spin = val;
+        self
+    }
+
+    /// Returns the property ids that are written, with their names.
+    fn names(&self) -> Vec<(u16, &'static str)> {
+        let mut res = vec![(self.event, "event")];
+        let optional = [
+            (self.process_id, "process_id"),
+            (self.weight, "weight"),
+            (self.scale, "scale"),
+            (self.aqed, "aqed"),
+            (self.aqcd, "aqcd"),
+            (self.pdg_id, "pdg_id"),
+            (self.status, "status"),
+            (self.mothers, "mothers"),
+            (self.colors, "colors"),
+            (self.momentum, "momentum"),
+            (self.mass, "mass"),
+            (self.lifetime, "lifetime"),
+            (self.spin, "spin"),
+        ];
+        res.extend(optional.iter().filter_map(|&(id, name)| id.map(|id| (id, name))));
+        res
+    }
+}
+
+/// An event that is being read.
+#[derive(Default)]
+struct Event {
+    line: u64,
+    particles: Option<usize>,
+    process_id: i32,
+    weight: f64,
+    scale: f64,
+    aqed: f64,
+    aqcd: f64,
+    pdg_id: Vec<i32>,
+    status: Vec<i32>,
+    mothers: Vec<[i32; 2]>,
+    colors: Vec<[i32; 2]>,
+    momentum: Vec<[f64; 4]>,
+    mass: Vec<f64>,
+    lifetime: Vec<f64>,
+    spin: Vec<f64>,
+}
+
+/// Where the parser is in the file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Outside,
+    Init,
+    Event,
+}
+
+/// Converts the events of a Les Houches Event file to a stream, one frame per event.
+///
+/// The parser is line based and tolerant of the formatting found in generator output:
+/// whitespace is ignored, `#` comments and the `<header>` block are skipped,
+/// tags may have attributes, and lines after the particles of an event,
+/// e.g. `<rwgt>`, `<weights>` or `<scales>`, are skipped up to `</event>`.
+/// Floats may use a Fortran `D` exponent.
+/// Returns the number of events.
+///
+/// The output is terminated with an end of stream.
+/// Returns an error of kind `ErrorKind::InvalidData` with the line number for malformed lines,
+/// unclosed blocks, or events with fewer particles than declared.
+/// Returns an error of kind `ErrorKind::PropertyConflict` if the options use a property id twice.
+pub fn import_lhe<R: io::BufRead, W: io::Write>(
+    r: R,
+    w: &mut W,
+    opts: &LheOptions
+) -> io::Result<u64> {
+    let names = opts.names();
+    for (i, &(id, _)) in names.iter().enumerate() {
+        if names[..i].iter().any(|&(other, _)| other == id) {
+            return Err(Error::new(ErrorKind::PropertyConflict).with_property(id).into());
+        }
+    }
+
+    let mut section = Section::Outside;
+    let mut init: Option<String> = None;
+    let mut event = Event::default();
+    let mut events = 0;
+    let mut header = false;
+    let mut start = 0;
+    let mut last = 0;
+    for (n, line) in r.lines().enumerate() {
+        let line = line?;
+        let n = n as u64 + 1;
+        last = n;
+        let invalid = || -> io::Error {Error::new(ErrorKind::InvalidData).with_line(n).into()};
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {continue}
+        match section {
+            Section::Outside if header => {
+                if is_tag(line, "/header") {header = false}
+            }
+            Section::Outside => {
+                if is_tag(line, "header") {
+                    header = !line.ends_with("/>") && !line.contains("</header>");
+                } else if is_tag(line, "init") {
+                    if init.is_some() || events > 0 {return Err(invalid())}
+                    init = Some(String::new());
+                    section = Section::Init;
+                    start = n;
+                } else if is_tag(line, "event") {
+                    if events == 0 {write_names(&names, &init, w)?}
+                    event = Event {line: n, ..Event::default()};
+                    section = Section::Event;
+                    start = n;
+                } else if !line.starts_with('<') {
+                    return Err(invalid());
+                }
+            }
+            Section::Init => {
+                if is_tag(line, "/init") {
+                    section = Section::Outside;
+                } else if let Some(ref mut text) = init {
+                    if !text.is_empty() {text.push('\n')}
+                    text.push_str(line);
+                }
+            }
+            Section::Event => {
+                if is_tag(line, "/event") {
+                    write_event(&event, events, opts, w)?;
+                    events += 1;
+                    section = Section::Outside;
+                    continue;
+                }
+                let mut fields = line.split_whitespace();
+                match event.particles {
+                    None => {
+                        event.particles = Some(parse(fields.next(), n)?);
+                        event.process_id = parse(fields.next(), n)?;
+                        event.weight = parse_float(fields.next(), n)?;
+                        event.scale = parse_float(fields.next(), n)?;
+                        event.aqed = parse_float(fields.next(), n)?;
+                        event.aqcd = parse_float(fields.next(), n)?;
+                        if fields.next().is_some() {return Err(invalid())}
+                    }
+                    Some(particles) if event.pdg_id.len() < particles => {
+                        event.pdg_id.push(parse(fields.next(), n)?);
+                        event.status.push(parse(fields.next(), n)?);
+                        event.mothers.push([parse(fields.next(), n)?, parse(fields.next(), n)?]);
+                        event.colors.push([parse(fields.next(), n)?, parse(fields.next(), n)?]);
+                        let mut p = [0.0; 4];
+                        for x in &mut p {*x = parse_float(fields.next(), n)?}
+                        event.momentum.push(p);
+                        event.mass.push(parse_float(fields.next(), n)?);
+                        event.lifetime.push(parse_float(fields.next(), n)?);
+                        event.spin.push(parse_float(fields.next(), n)?);
+                        if fields.next().is_some() {return Err(invalid())}
+                    }
+                    // Optional information after the particles.
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+    if section != Section::Outside || header {
+        return Err(Error::new(ErrorKind::InvalidData).with_line(if header {last} else {start}).into());
+    }
+    if events == 0 {write_names(&names, &init, w)?}
+    State::new().end_type_formats(w)?;
+    Ok(events)
+}
+
+/// Returns `true` if a line starts with a tag, with or without attributes.
+fn is_tag(line: &str, name: &str) -> bool {
+    line.strip_prefix('<')
+        .and_then(|rest| rest.strip_prefix(name))
+        .is_some_and(|rest| rest.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()))
+}
+
+/// Parses a float field, with a `D` or `E` exponent.
+fn parse_float(field: Option<&str>, line: u64) -> io::Result<f64> {
+    match field {
+        Some(s) if s.contains(['d', 'D']) => parse(Some(&s.replace(['d', 'D'], "e")), line),
+        _ => parse(field, line),
+    }
+}
+
+/// Writes the names of the properties and the `<init>` block.
+fn write_names<W: io::Write>(
+    names: &[(u16, &str)],
+    init: &Option<String>,
+    w: &mut W
+) -> io::Result<()> {
+    let entries: Vec<(u16, Metadata)> = names.iter()
+        .enumerate()
+        .map(|(i, &(id, name))| {
+            let metadata = Metadata::new().with(NAME, name);
+            match *init {
+                Some(ref text) if i == 0 => (id, metadata.with(LHE_INIT, text)),
+                _ => (id, metadata),
+            }
+        })
+        .collect();
+    let entries: Vec<(u16, &Metadata)> = entries.iter().map(|(id, m)| (*id, m)).collect();
+    metadata::write_metadata(&entries, w)
+}
+
+/// Writes an event as a frame.
+fn write_event<W: io::Write>(ev: &Event, number: u64, opts: &LheOptions, w: &mut W) -> io::Result<()> {
+    if ev.particles != Some(ev.pdg_id.len()) {
+        return Err(Error::new(ErrorKind::InvalidData).with_line(ev.line).into());
+    }
+    Time::write(opts.event, number as f64, w)?;
+    let scalars = [
+        (opts.weight, ev.weight),
+        (opts.scale, ev.scale),
+        (opts.aqed, ev.aqed),
+        (opts.aqcd, ev.aqcd),
+    ];
+    if let Some(id) = opts.process_id {
+        ev.process_id.write_property(id, w)?;
+    }
+    for &(id, val) in &scalars {
+        if let Some(id) = id {val.write_property(id, w)?}
+    }
+    // Empty arrays are not written.
+    if ev.pdg_id.is_empty() {return Ok(())}
+    if let Some(id) = opts.pdg_id {
+        Scalar::write_array(id, &ev.pdg_id, w)?;
+    }
+    if let Some(id) = opts.status {
+        Scalar::write_array(id, &ev.status, w)?;
+    }
+    if let Some(id) = opts.mothers {
+        Vector::write_array(id, &ev.mothers, w)?;
+    }
+    if let Some(id) = opts.colors {
+        Vector::write_array(id, &ev.colors, w)?;
+    }
+    if let Some(id) = opts.momentum {
+        Vector::write_array(id, &ev.momentum, w)?;
+    }
+    for &(id, vals) in &[(opts.mass, &ev.mass), (opts.lifetime, &ev.lifetime), (opts.spin, &ev.spin)] {
+        if let Some(id) = id {Scalar::write_array(id, vals, w)?}
+    }
+    Ok(())
+}
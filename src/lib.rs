@@ -33,6 +33,8 @@
 //! ```
 //!
 //! Integers are stored in little-endian format.
+//! A stream written by `PoolWriter` starts with a header,
+//! which can set big-endian format for the rest of the stream.
 //!
 //! The number of items in the data are inferred from the number of bytes
 //! and knowledge about the type format.
@@ -55,6 +57,15 @@
 //!
 //! To describe time, just create a new property id, e.g. f32 scalar,
 //! and write out this value before other data in the same time step.
+//! `Time` writes time as f64 and reads both f32 and f64.
+//! `FrameWriter` and `FrameReader` use this convention to stamp frames,
+//! and `TimeUnit` records whether time is measured in seconds, steps or a custom unit.
+//! Units of other properties can be recorded with `Units`,
+//! which are collected by `PoolReader` and can be used to convert values.
+//! Descriptions and other key/value pairs can be attached with `write_metadata`.
+//! Versions of custom formats are recorded with `CustomFormats`,
+//! which `PoolReader` checks against the versions known to the application.
+//! Streams written by older versions are upgraded with `migrate`.
 //!
 //! ### Usage
 //!
@@ -95,18 +106,460 @@
 //!
 //! Data is often stored in a struct and overwritten for each frame.
 //! The example above uses a local variable just for showing how to read data.
+//!
+//! To write blocks of a custom format, use `begin_block`.
+//! The guard it returns writes the end of bytes when finished or dropped,
+//! such that an early return does not leave the stream without it.
+//! `State` can be used directly for full control.
+//!
+//! ```ignore
+//! let mut block = binpool::begin_block(format, prop_id, &mut file)?;
+//! block.write(0, &data)?;
+//! block.finish()?;
+//! ```
+//!
+//! ### Untrusted input
+//!
+//! Reading never panics on invalid data, but an offset instance id can be arbitrary large,
+//! which makes arrays grow accordingly.
+//! When reading files from untrusted sources, read through a `PoolReader` or `FrameReader`
+//! with `Limits` set, such that headers are checked before any data is read.
+//! The `fuzz` directory contains a target for `cargo fuzz` that exercises the read paths.
+//!
+//! ### Without std
+//!
+//! The `std` feature is enabled by default.
+//! Without it, the crate only requires `alloc` and the `codec` module
+//! can be used to encode and decode data, e.g. on a microcontroller.
+//! `cargo test --no-default-features` builds the crate without `std`
+//! and runs the round trip tests in `tests/no_std.rs` against in-memory buffers.
+//!
+//! ### Complex numbers
+//!
+//! The `num-complex` feature implements `Vector` for `Complex<T>`,
+//! stored as the real part followed by the imaginary part.
+//! The `complex` module has helpers for converting from and to `[T; 2]`.
+//!
+//! ### Apache Arrow
+//!
+//! The `arrow` feature enables the `arrow` module,
+//! which exports a stream to Arrow record batches or an Arrow IPC file,
+//! with one row per instance of every frame.
+//! The `parquet` feature enables the `parquet` module,
+//! which writes the same table to a Parquet file.
+//!
+//! ### Tracing
+//!
+//! The `tracing` feature instruments reading with spans and events of the `tracing` crate,
+//! all with the target `binpool`.
+//! Without the feature, the instrumentation compiles to nothing.
+//!
+//! - Span `frame` (debug), for every frame read by `FrameReader`,
+//!   with fields `frame`, the frame number, and `time`, the time of the frame if any.
+//!   The span starts with the frame marker and has no parent.
+//! - Span `block` (trace), for every block read as a whole,
+//!   with fields `property_id`, `type_format`, `bytes` and `offset`.
+//!   Blocks of a frame are inside the span of the frame.
+//! - Event `skip` (debug), for a property skipped without decoding,
+//!   with fields `property_id`, `type_format` and `bytes`, the number of data bytes.
+//! - Event `retry` (debug), for a read or write retried after it was interrupted.
+//! - Event `type_mismatch` (warn), for a type format that does not match the type read,
+//!   with fields `property_id` if known, `type_format` and `expected`,
+//!   the expected type format if there is one.
+//! - Event `limit_exceeded` (warn), for a limit of `Limits` that was exceeded,
+//!   with fields `property_id`, `limit`, the name of the field in `Limits`,
+//!   `value` and `max`.
+//!
+//! The names and fields are kept stable between versions.
+//!
+//! ### Testing
+//!
+//! The `test-util` feature enables the `test_util` module,
+//! with proptest strategies that generate random but valid streams
+//! together with the expected result of decoding them,
+//! and the `compat` module, which checks golden files written by older versions
+//! against the current reader and writers.
 
 #![deny(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+extern crate alloc;
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_buffer;
+#[cfg(feature = "arrow")]
+extern crate arrow_data;
+#[cfg(feature = "arrow")]
+extern crate arrow_ipc;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "crossbeam")]
+extern crate crossbeam_channel;
+#[cfg(feature = "shm")]
+extern crate memmap2;
+#[cfg(feature = "num-complex")]
+extern crate num_complex;
+#[cfg(feature = "parquet")]
+extern crate parquet as apache_parquet;
+#[cfg(feature = "test-util")]
+extern crate proptest;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 use std::marker::PhantomData;
+#[cfg(feature = "std")]
 use std::io;
 
-pub use read_write::{Array, Matrix, Vector, Scalar};
+#[cfg(feature = "std")]
+pub use alias::{AliasConflict, Aliases};
+#[cfg(feature = "std")]
+pub use alive::AliveMask;
+#[cfg(feature = "std")]
+pub use allocator::PropertyAllocator;
+#[cfg(feature = "std")]
+pub use arena::{FrameArena, FrameView};
+#[cfg(feature = "std")]
+pub use async_writer::AsyncFileWriter;
+#[cfg(feature = "std")]
+pub use broadcast::{Backpressure, Broadcast, BroadcastBlock, Subscriber};
+#[cfg(feature = "std")]
+pub use buffer_pool::{BufferPool, BufferStats, Pooled};
+#[cfg(feature = "std")]
+pub use c_header::generate_c_header;
+#[cfg(feature = "std")]
+pub use canonical::canonicalize;
+#[cfg(feature = "std")]
+pub use chain::{ChainedIndex, ChainedReader};
+#[cfg(feature = "std")]
+pub use channel::{channel, BlockMessage, ChannelSink, ChannelSource};
+#[cfg(feature = "crossbeam")]
+pub use channel::crossbeam_transport;
+#[cfg(feature = "std")]
+pub use checkpoint::Checkpoint;
+#[cfg(feature = "std")]
+pub use commit::{journal_path, replay_journal, CommitOptions, CommitWriter, SyncPolicy};
+#[cfg(feature = "std")]
+pub use compact::{compact, CompactOptions};
+#[cfg(feature = "std")]
+pub use concat::{concat, ConcatOptions, StickyPolicy};
+#[cfg(feature = "std")]
+pub use coverage::{coverage, coverage_per_frame, Coverage, CoverageReport};
+#[cfg(feature = "std")]
+pub use dedup::DedupWriter;
+#[cfg(feature = "std")]
+pub use dense::{compact_copy, IdMap};
+#[cfg(feature = "std")]
+pub use dispatch::Dispatcher;
+#[cfg(feature = "std")]
+pub use downsample::{downsample, DownsampleOptions};
+#[cfg(feature = "std")]
+pub use dyn_value::DynValue;
+#[cfg(feature = "std")]
+pub use endian::{Endian, Header};
+pub use error::{Error, ErrorKind, Field, Format, Limit};
+#[cfg(feature = "std")]
+pub use event::{DuplicateEvents, Event, EventEntry, EventIndex, EventReader};
+#[cfg(feature = "std")]
+pub use filter::read_array_filtered;
+#[cfg(feature = "std")]
+pub use finite::NonFinitePolicy;
+#[cfg(feature = "std")]
+pub use formats::{CustomFormat, CustomFormats};
+#[cfg(feature = "std")]
+pub use frame::{DuplicatePolicy, DuplicateProperty, Frame, FrameReader, FrameWriter};
+#[cfg(feature = "std")]
+pub use grid::{read_grid, write_grid, Grid};
+#[cfg(feature = "std")]
+pub use guard::{begin_block, BlockGuard};
+#[cfg(feature = "std")]
+pub use hepmc::{import_hepmc3, HepMcMapping};
+#[cfg(feature = "std")]
+pub use histogram::{fill_histograms, Binning, Component, Histogram, Histogram1D, Histogram2D, HistogramSpec};
+#[cfg(feature = "std")]
+pub use index::{last_frame, FrameCursor, Index, IndexEntry, SeekMode};
+#[cfg(feature = "std")]
+pub use indices::{read_index_array, write_index_array};
+#[cfg(feature = "std")]
+pub use interpolate::Interpolator;
+#[cfg(feature = "std")]
+pub use iter::{write_from_iter, write_from_iter_chunked};
+#[cfg(feature = "std")]
+pub use join::{join_frames, join_frames_as, join_frames_n, JoinFrames, JoinOptions, Missing};
+#[cfg(feature = "std")]
+pub use lazy::{BlockLocation, LazyFrame, LazyProperty};
+#[cfg(feature = "std")]
+pub use lhe::{import_lhe, LheOptions, LHE_INIT};
+#[cfg(feature = "std")]
+pub use limits::Limits;
+#[cfg(feature = "std")]
+pub use lineage::{write_events, Creation, Lineage, Particle, NO_PARENT};
+#[cfg(feature = "std")]
+pub use mapped::{MappedBlock, MappedProperty, MappedReader, MappedScalar};
+#[cfg(feature = "std")]
+pub use metadata::{decode_metadata, merge_metadata, read_metadata, write_metadata, Metadata, DESCRIPTION, NAME};
+#[cfg(feature = "std")]
+pub use metrics::{Metered, Metrics, MetricsSnapshot, PropertyMetrics};
+#[cfg(feature = "std")]
+pub use migrate::{migrate, MigrateOptions, Migration, MigrationReport};
+#[cfg(feature = "std")]
+pub use namespace::{namespace_copy, Namespace, Namespaces};
+#[cfg(feature = "std")]
+pub use optional::{read_optional_array, write_optional_array, write_optional_array_at};
+#[cfg(feature = "std")]
+pub use packed_id::{PackedId, PACKED_ID};
+#[cfg(feature = "rayon")]
+pub use par::{par_events, par_events_with};
+#[cfg(feature = "std")]
+pub use playback::PlaybackReader;
+#[cfg(feature = "std")]
+pub use player::{Clock, Player, SystemClock};
+#[cfg(feature = "std")]
+pub use progress::{Progress, ProgressReader};
+#[cfg(feature = "std")]
+pub use raw::{copy_payload, copy_property, write_block, RawBlock};
+#[cfg(feature = "std")]
+pub use record::{Recordable, Recorder, Replayer};
+#[cfg(feature = "std")]
+pub use recover::{recover, recover_file, RecoverOptions, RecoveryReport};
+#[cfg(feature = "std")]
+pub use reader::{PoolReader, Unknown};
+#[cfg(feature = "std")]
+pub use read_write::{Array, Element, Matrix, ReadMode, ReadOptions, Vector, Scalar};
+#[cfg(feature = "std")]
+pub use reduce::Violation;
+pub use reserved::{
+    is_reserved_format, is_reserved_property, RESERVED_FORMAT_START, RESERVED_PROPERTY_START,
+    ALIVE_FORMAT, ALIVE_PROPERTY, CREATED_FORMAT, CREATED_PROPERTY, DESTROYED_FORMAT,
+    DESTROYED_PROPERTY, FOOTER_FORMAT, FOOTER_PROPERTY, FORMATS_FORMAT, FORMATS_PROPERTY,
+    GRID_FORMAT, HEADER_FORMAT, HEADER_PROPERTY,
+    ID_MAP_FORMAT, ID_MAP_PROPERTY, METADATA_FORMAT, METADATA_PROPERTY,
+    NAMESPACE_FORMAT, NAMESPACE_PROPERTY, PADDING_FORMAT, PADDING_PROPERTY, PRESENCE_FORMAT,
+    RLE_FORMAT, SPARSE_FORMAT, TIME_UNIT_FORMAT, TRACK_FORMAT, TRACK_PROPERTY, UNITS_FORMAT,
+    UNITS_PROPERTY, VARINT_FORMAT, XOR_FORMAT,
+};
+#[cfg(feature = "std")]
+pub use resample::{resample, Extrapolate, ResampleOptions};
+#[cfg(feature = "std")]
+pub use ring::{RingOptions, RingRecorder};
+#[cfg(feature = "std")]
+pub use rle::{expand_rle, read_rle, write_rle, write_rle_with};
+#[cfg(feature = "std")]
+pub use rotate::{RotateOptions, RotatingWriter};
+#[cfg(feature = "std")]
+pub use series::{extract_series, extract_series_as, SeriesOptions};
+#[cfg(feature = "std")]
+pub use shared::{LocalWriter, SharedWriter};
+#[cfg(feature = "bytes")]
+pub use shared_block::SharedBlock;
+#[cfg(feature = "shm")]
+pub use shm::{Overflow, ShmReader, ShmWriter};
+#[cfg(feature = "std")]
+pub use sparse::{read_sparse, write_sparse, Sparse, SparseDuplicates, SparseValue};
+#[cfg(feature = "std")]
+pub use time::{Time, TimeUnit};
+#[cfg(feature = "std")]
+pub use transport::{
+    BlockSink, BlockSource, Payload, SinkWriter, SourceReader, WireSink, WireSource,
+};
+#[cfg(feature = "std")]
+pub use track::{Track, TrackReader, TrackWriter};
+#[cfg(feature = "std")]
+pub use tracking::TrackingReader;
+#[cfg(feature = "std")]
+pub use units::{Unit, Units};
+#[cfg(feature = "std")]
+pub use writer::{PoolWriter, WriteOptions};
+#[cfg(feature = "std")]
+pub use validate::{validate, Report, TimeRegression, ValidateOptions};
+#[cfg(feature = "std")]
+pub use varint::{expand_varint_delta, read_varint_delta, write_varint_delta, Integer};
+#[cfg(feature = "std")]
+pub use window::{extract_window, WindowOptions};
 
 const TYPES: u16 = 10;
 const SIZE: u16 = 80;
 
+pub mod codec;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "test-util")]
+pub mod compat;
+#[cfg(feature = "num-complex")]
+pub mod complex;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod parse;
+#[cfg(feature = "std")]
+pub mod raw;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+#[cfg(feature = "std")]
+mod alias;
+#[cfg(feature = "std")]
+mod alive;
+#[cfg(feature = "std")]
+mod allocator;
+#[cfg(feature = "std")]
+mod arena;
+#[cfg(feature = "std")]
+mod async_writer;
+#[cfg(feature = "std")]
+mod broadcast;
+#[cfg(feature = "std")]
+mod buffer_pool;
+#[cfg(feature = "std")]
+mod c_header;
+#[cfg(feature = "std")]
+mod canonical;
+#[cfg(feature = "std")]
+mod chain;
+#[cfg(feature = "std")]
+mod channel;
+#[cfg(feature = "std")]
+mod checkpoint;
+#[cfg(feature = "std")]
+mod commit;
+#[cfg(feature = "std")]
+mod compact;
+#[cfg(feature = "std")]
+mod concat;
+#[cfg(feature = "std")]
+mod coverage;
+#[cfg(feature = "std")]
+mod dedup;
+#[cfg(feature = "std")]
+mod dense;
+#[cfg(feature = "std")]
+mod dispatch;
+#[cfg(feature = "std")]
+mod downsample;
+#[cfg(feature = "std")]
+mod dyn_value;
+#[cfg(feature = "std")]
+mod endian;
+mod error;
+#[cfg(feature = "std")]
+mod event;
+#[cfg(feature = "std")]
+mod filter;
+#[cfg(feature = "std")]
+mod finite;
+#[cfg(feature = "std")]
+mod footer;
+#[cfg(feature = "std")]
+mod formats;
+#[cfg(feature = "std")]
+mod frame;
+#[cfg(feature = "std")]
+mod grid;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "std")]
+mod hepmc;
+#[cfg(feature = "std")]
+mod histogram;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod indices;
+#[cfg(feature = "std")]
+mod interpolate;
+#[cfg(feature = "std")]
+mod iter;
+#[cfg(feature = "std")]
+mod join;
+#[cfg(feature = "std")]
+mod lazy;
+#[cfg(feature = "std")]
+mod lhe;
+#[cfg(feature = "std")]
+mod limits;
+#[cfg(feature = "std")]
+mod lineage;
+#[cfg(feature = "std")]
+mod mapped;
+#[cfg(feature = "std")]
+mod metadata;
+#[cfg(feature = "std")]
+mod metrics;
+#[cfg(feature = "std")]
+mod migrate;
+#[cfg(feature = "std")]
+mod namespace;
+#[cfg(feature = "std")]
+mod optional;
+#[cfg(feature = "std")]
+mod packed_id;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "std")]
+mod playback;
+#[cfg(feature = "std")]
+mod player;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(feature = "std")]
+mod record;
+#[cfg(feature = "std")]
+mod recover;
+#[cfg(feature = "std")]
 mod read_write;
+#[cfg(feature = "std")]
+mod reduce;
+mod reserved;
+#[cfg(feature = "std")]
+mod resample;
+#[cfg(feature = "std")]
+mod ring;
+#[cfg(feature = "std")]
+mod rle;
+#[cfg(feature = "std")]
+mod rotate;
+#[cfg(feature = "std")]
+mod series;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "bytes")]
+mod shared_block;
+#[cfg(feature = "shm")]
+mod shm;
+#[cfg(feature = "std")]
+mod sparse;
+#[cfg(feature = "std")]
+mod time;
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "std")]
+mod track;
+#[cfg(feature = "std")]
+mod transport;
+#[cfg(feature = "std")]
+mod tracking;
+#[cfg(feature = "std")]
+mod units;
+#[cfg(feature = "std")]
+mod validate;
+#[cfg(feature = "std")]
+mod varint;
+#[cfg(feature = "std")]
+mod window;
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+mod xor;
 
 /// Type format for a property.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -204,14 +657,45 @@ impl Type {
         }
     }
 
+    /// Returns the type format for a matrix, or `None` if dimensions are not supported.
+    ///
+    /// This is the inverse of `Type::info`.
+    /// Scalars have dimensions 1x1 and vectors 1xN.
+    pub fn format_for(ty: Type, rows: u8, cols: u8) -> Option<u16> {
+        ty.matrix(rows, cols).map(|(format, _)| format)
+    }
+
+    /// Returns the maximum dimension of a vector.
+    pub fn max_vector_dim() -> u8 {
+        SIZE as u8
+    }
+
+    /// Returns the maximum number of rows or columns of a matrix.
+    pub fn max_matrix_dim() -> u8 {
+        SIZE as u8
+    }
+
+    /// Returns the number of built-in type formats.
+    pub fn builtin_format_count() -> u16 {
+        TYPES * SIZE * SIZE
+    }
+
+    /// Returns an iterator over all built-in type formats,
+    /// as `(format, type, rows, cols)`, in increasing order of format.
+    pub fn all_formats() -> impl Iterator<Item = (u16, Type, u8, u8)> {
+        (1..Type::offset_custom_format()).filter_map(|format| {
+            Type::info(format).map(|(ty, rows, cols)| (format, ty, rows, cols))
+        })
+    }
+
     /// Returns the offset for specifying a custom format.
     pub fn offset_custom_format() -> u16 {
-        1 + TYPES * SIZE * SIZE
+        1 + Type::builtin_format_count()
     }
 
     /// Returns the number of available custom formats.
     pub fn custom_formats() -> u16 {
-        (((1 as u32) << 16) - Type::offset_custom_format() as u32) as u16
+        ((1_u32 << 16) - Type::offset_custom_format() as u32) as u16
     }
 
     /// Returns the type and matrix dimensions from type format.
@@ -253,12 +737,50 @@ pub struct OffsetInstanceId;
 pub struct Data;
 
 /// Stores the state for writing and reading.
-pub struct State<T = TypeFormat>(PhantomData<T>);
+///
+/// The property id is kept once it is known, such that errors can report it.
+pub struct State<T = TypeFormat>(PhantomData<T>, Option<u16>);
+
+impl<T> State<T> {
+    /// Returns the property id, once it is written or read.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn property_id(&self) -> Option<u16> {
+        self.1
+    }
+}
+
+/// The header fields of a block.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BlockHeader {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// Number of bytes of data.
+    pub bytes: u64,
+    /// Offset instance id.
+    pub offset: u64,
+}
+
+impl BlockHeader {
+    /// Returns `true` if this is the end of bytes of a property, and not a block.
+    pub fn is_end(&self) -> bool {
+        self.bytes == 0
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
 
+#[cfg(feature = "std")]
 impl State {
     /// Creates a new state.
     pub fn new() -> State {
-        State(PhantomData)
+        State(PhantomData, None)
     }
 
     /// Reads type format and property.
@@ -277,6 +799,21 @@ impl State {
         }
     }
 
+    /// Reads the header of the first block of the next property.
+    ///
+    /// Returns `None` if there is no more data.
+    /// When the property has no blocks, `bytes` is 0 and the offset is not read,
+    /// such that the next call reads a new property.
+    /// Otherwise, the reader is positioned at the data of the block.
+    pub fn read_full_header<R: io::Read>(
+        r: &mut R
+    ) -> io::Result<Option<(BlockHeader, State<Data>)>> {
+        match raw::read_property_header(r)? {
+            None => Ok(None),
+            Some((state, ty, prop)) => state.read_block_header(ty, prop, r).map(Some),
+        }
+    }
+
     /// Writes type format.
     pub fn write_type_format<W: io::Write>(
         self,
@@ -286,7 +823,7 @@ impl State {
         use read_write::Scalar;
 
         type_format.write(w)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, None))
     }
 
     /// Reads type format.
@@ -298,18 +835,19 @@ impl State {
         use read_write::Scalar;
 
         type_format.read(r)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, None))
     }
 
     /// Ends writing state.
     pub fn end_type_formats<W: io::Write>(self, w: &mut W) -> io::Result<()> {
         use read_write::Scalar;
 
-        (0 as u16).write(w)?;
+        0_u16.write(w)?;
         Ok(())
     }
 }
 
+#[cfg(feature = "std")]
 impl State<PropertyId> {
     /// Writes property id.
     pub fn write_property_id<W: io::Write>(
@@ -320,7 +858,7 @@ impl State<PropertyId> {
         use read_write::Scalar;
 
         property_id.write(w)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, Some(property_id)))
     }
 
     /// Reads property id.
@@ -332,10 +870,11 @@ impl State<PropertyId> {
         use read_write::Scalar;
 
         property_id.read(r)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, Some(*property_id)))
     }
 }
 
+#[cfg(feature = "std")]
 impl State<Bytes> {
     /// Writes number of bytes in data.
     pub fn write_bytes<W: io::Write>(
@@ -346,7 +885,7 @@ impl State<Bytes> {
         use read_write::Scalar;
 
         bytes.write(w)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, self.1))
     }
 
     /// Reads bytes.
@@ -358,7 +897,24 @@ impl State<Bytes> {
         use read_write::Scalar;
 
         bytes.read(r)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, self.1))
+    }
+
+    /// Reads number of bytes and offset instance id of the next block of a property.
+    ///
+    /// When `bytes` is 0, this is the end of bytes of the property
+    /// and the offset is not read.
+    pub fn read_block_header<R: io::Read>(
+        self,
+        type_format: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<(BlockHeader, State<Data>)> {
+        let mut header = BlockHeader {type_format, property_id, bytes: 0, offset: 0};
+        let state = self.read_bytes(&mut header.bytes, r)?;
+        if header.bytes == 0 {return Ok((header, State(PhantomData, state.1)))}
+        let state = state.read_offset_instance_id(&mut header.offset, r)?;
+        Ok((header, state))
     }
 
     /// Ends byte block.
@@ -368,8 +924,8 @@ impl State<Bytes> {
     ) -> io::Result<State<TypeFormat>> {
         use read_write::Scalar;
 
-        (0 as u64).write(w)?;
-        Ok(State(PhantomData))
+        0_u64.write(w)?;
+        Ok(State(PhantomData, None))
     }
 
     /// Checks if this is the end of bytes.
@@ -380,13 +936,14 @@ impl State<Bytes> {
         let mut val: u64 = 0;
         val.read(r)?;
         if val == 0 {
-            Ok(State(PhantomData))
+            Ok(State(PhantomData, None))
         } else {
             Err(io::ErrorKind::InvalidData.into())
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl State<OffsetInstanceId> {
     /// Writes offset instance id.
     pub fn write_offset_instance_id<W: io::Write>(
@@ -397,7 +954,7 @@ impl State<OffsetInstanceId> {
         use read_write::Scalar;
 
         offset_instance_id.write(w)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, self.1))
     }
 
     /// Reads offset instance id.
@@ -409,10 +966,11 @@ impl State<OffsetInstanceId> {
         use read_write::Scalar;
 
         offset_instance_id.read(r)?;
-        Ok(State(PhantomData))
+        Ok(State(PhantomData, self.1))
     }
 }
 
+#[cfg(feature = "std")]
 impl State<Data> {
     /// Writes data.
     pub fn write_data<W: io::Write>(
@@ -420,12 +978,12 @@ impl State<Data> {
         data: &[u8],
         w: &mut W
     ) -> io::Result<State<Data>> {
-        w.write(data)?;
-        Ok(State(PhantomData))
+        w.write_all(data)?;
+        Ok(State(PhantomData, self.1))
     }
 
     /// End of data.
     pub fn end_data(self) -> State<Bytes> {
-        State(PhantomData)
+        State(PhantomData, self.1)
     }
 }
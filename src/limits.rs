@@ -0,0 +1,143 @@
+//! Limits on resources used when reading untrusted streams.
+
+use error::{Error, ErrorKind, Limit};
+use parse::Event;
+use raw;
+use reserved::ALIVE_FORMAT;
+use trace;
+
+/// Limits on the resources used when reading a stream.
+///
+/// Every limit is `None` by default, which means unlimited.
+/// Limits are checked when a header is read,
+/// before the data of a block is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of bytes in a block.
+    pub max_block_bytes: Option<u64>,
+    /// The maximum number of bytes of all blocks in the stream.
+    pub max_total_payload: Option<u64>,
+    /// The maximum number of blocks under one property header.
+    pub max_blocks: Option<u64>,
+    /// The maximum number of instances of a property,
+    /// counting from instance 0 to the end of a block.
+    pub max_instances_per_property: Option<u64>,
+    /// The maximum number of property headers in the stream.
+    pub max_properties: Option<u64>,
+}
+
+impl Limits {
+    /// Creates limits where everything is unlimited.
+    pub fn unlimited() -> Limits {
+        Limits::default()
+    }
+}
+
+/// Counts the resources used so far, checking limits.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Usage {
+    limits: Limits,
+    type_format: u16,
+    property: u16,
+    properties: u64,
+    blocks: u64,
+    payload: u64,
+}
+
+impl Usage {
+    fn check(&self, value: u64, max: Option<u64>, limit: Limit) -> Result<(), Error> {
+        match max {
+            Some(max) if value > max => {
+                trace::limit_exceeded(self.property, limit.name(), value, max);
+                Err(Error::new(ErrorKind::LimitExceeded).with_limit(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn new(limits: Limits) -> Usage {
+        Usage {limits, ..Usage::default()}
+    }
+
+    /// Sets the limits, keeping the counts.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Returns the number of property headers and bytes of data counted.
+    pub fn counts(&self) -> (u64, u64) {
+        (self.properties, self.payload)
+    }
+
+    /// Sets the number of property headers and bytes of data counted.
+    pub fn set_counts(&mut self, properties: u64, payload: u64) {
+        self.properties = properties;
+        self.payload = payload;
+    }
+
+    /// Counts a property header.
+    pub fn property(&mut self, type_format: u16, property_id: u16) -> Result<(), Error> {
+        self.type_format = type_format;
+        self.property = property_id;
+        self.blocks = 0;
+        self.properties += 1;
+        self.check(self.properties, self.limits.max_properties, Limit::Properties)
+            .map_err(|err| err.with_property(property_id))
+    }
+
+    /// Counts a block header.
+    pub fn block(&mut self, bytes: u64, offset: u64) -> Result<(), Error> {
+        self.blocks += 1;
+        self.payload = self.payload.saturating_add(bytes);
+        self.check(self.blocks, self.limits.max_blocks, Limit::Blocks)
+            .and_then(|_| self.check(bytes, self.limits.max_block_bytes, Limit::BlockBytes))
+            .and_then(|_| self.check(self.payload, self.limits.max_total_payload, Limit::TotalPayload))
+            .and_then(|_| match raw::item_size(self.type_format) {
+                Some(size) if size > 0 => self.check(
+                    offset.saturating_add(bytes / size),
+                    self.limits.max_instances_per_property,
+                    Limit::InstancesPerProperty
+                ),
+                // Alive masks have one bit per instance.
+                None if self.type_format == ALIVE_FORMAT => self.check(
+                    offset.saturating_add(bytes.saturating_mul(8)),
+                    self.limits.max_instances_per_property,
+                    Limit::InstancesPerProperty
+                ),
+                _ => Ok(()),
+            })
+            .map_err(|err| err.with_property(self.property))
+    }
+
+    /// Counts a block that was expanded from run-length encoded data.
+    ///
+    /// The block header was already counted, so only its size and instances are checked.
+    pub fn expanded(&mut self, type_format: u16, bytes: u64, offset: u64) -> Result<(), Error> {
+        self.payload = self.payload.saturating_add(bytes);
+        self.check(bytes, self.limits.max_block_bytes, Limit::BlockBytes)
+            .and_then(|_| self.check(self.payload, self.limits.max_total_payload, Limit::TotalPayload))
+            .and_then(|_| match raw::item_size(type_format) {
+                Some(size) if size > 0 => self.check(
+                    offset.saturating_add(bytes / size),
+                    self.limits.max_instances_per_property,
+                    Limit::InstancesPerProperty
+                ),
+                _ => Ok(()),
+            })
+            .map_err(|err| err.with_property(self.property))
+    }
+
+    /// Returns the maximum number of bytes in a block.
+    pub fn max_block_bytes(&self) -> Option<u64> {
+        self.limits.max_block_bytes
+    }
+
+    /// Counts the headers of an event from the parser.
+    pub fn event(&mut self, event: Event) -> Result<(), Error> {
+        match event {
+            Event::Property {type_format, property_id} => self.property(type_format, property_id),
+            Event::Block {bytes, offset} => self.block(bytes, offset),
+            Event::EndBytes | Event::End => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Events of instances that are created and destroyed.
+//!
+//! Creations and destructions of a frame are properties with reserved formats and property ids.
+//! A creation is the instance id and the parent instance id, as two u64,
+//! where `NO_PARENT` is written for instances without a parent.
+//! A destruction is the instance id as u64.
+//! Empty lists of events are not written.
+//!
+//! `Lineage` folds the events of frames into a tree of parents and children.
+
+use std::collections::HashMap;
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use reserved::{CREATED_FORMAT, CREATED_PROPERTY, DESTROYED_FORMAT, DESTROYED_PROPERTY};
+
+/// The parent instance id written for instances without a parent.
+pub const NO_PARENT: u64 = u64::MAX;
+
+/// The creation of an instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Creation {
+    /// The instance id.
+    pub id: u64,
+    /// The instance id of the parent.
+    pub parent: Option<u64>,
+}
+
+impl Creation {
+    /// Creates a creation of an instance without a parent.
+    pub fn new(id: u64) -> Creation {
+        Creation {id, parent: None}
+    }
+
+    /// Creates a creation of an instance with a parent.
+    pub fn with_parent(id: u64, parent: u64) -> Creation {
+        Creation {id, parent: Some(parent)}
+    }
+}
+
+/// Writes the creations and destructions of a frame.
+///
+/// Nothing is written for an empty list.
+/// Returns an error of kind `io::ErrorKind::InvalidInput` if a parent is `NO_PARENT`.
+pub fn write_events<W: io::Write>(
+    created: &[Creation],
+    destroyed: &[u64],
+    w: &mut W
+) -> io::Result<()> {
+    if !created.is_empty() {
+        let mut data = Vec::with_capacity(16 * created.len());
+        for c in created {
+            if c.parent == Some(NO_PARENT) {return Err(io::ErrorKind::InvalidInput.into())}
+            data.extend_from_slice(&c.id.to_le_bytes());
+            data.extend_from_slice(&c.parent.unwrap_or(NO_PARENT).to_le_bytes());
+        }
+        RawBlock::write_property(CREATED_FORMAT, CREATED_PROPERTY, &[RawBlock {
+            type_format: CREATED_FORMAT,
+            property_id: CREATED_PROPERTY,
+            offset: 0,
+            data,
+        }], w)?;
+    }
+    if !destroyed.is_empty() {
+        let mut data = Vec::with_capacity(8 * destroyed.len());
+        for id in destroyed {data.extend_from_slice(&id.to_le_bytes())}
+        RawBlock::write_property(DESTROYED_FORMAT, DESTROYED_PROPERTY, &[RawBlock {
+            type_format: DESTROYED_FORMAT,
+            property_id: DESTROYED_PROPERTY,
+            offset: 0,
+            data,
+        }], w)?;
+    }
+    Ok(())
+}
+
+fn read_u64s(block: &RawBlock) -> io::Result<Vec<u64>> {
+    if !block.data.len().is_multiple_of(8) {
+        return Err(Error::new(ErrorKind::InvalidData).with_property(block.property_id).into());
+    }
+    Ok(block.data.chunks(8).map(|chunk| {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        u64::from_le_bytes(bytes)
+    }).collect())
+}
+
+/// Decodes the creations and destructions in blocks, in the order they were read.
+pub(crate) fn read_events(
+    blocks: &[RawBlock],
+    created: &mut Vec<Creation>,
+    destroyed: &mut Vec<u64>
+) -> io::Result<()> {
+    for block in blocks {
+        if block.type_format == CREATED_FORMAT && block.property_id == CREATED_PROPERTY {
+            let items = read_u64s(block)?;
+            if !items.len().is_multiple_of(2) {
+                return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(block.property_id).into());
+            }
+            created.extend(items.chunks(2).map(|pair| Creation {
+                id: pair[0],
+                parent: if pair[1] == NO_PARENT {None} else {Some(pair[1])},
+            }));
+        } else if block.type_format == DESTROYED_FORMAT && block.property_id == DESTROYED_PROPERTY {
+            destroyed.extend(read_u64s(block)?);
+        }
+    }
+    Ok(())
+}
+
+/// An instance in a lineage.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Particle {
+    /// The instance id.
+    pub id: u64,
+    /// The instance id of the parent.
+    pub parent: Option<u64>,
+    /// The frame where the instance was created.
+    pub created: u64,
+    /// The frame where the instance was destroyed.
+    pub destroyed: Option<u64>,
+}
+
+struct Node {
+    particle: Particle,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// A tree of parents and children, folded from the events of frames.
+///
+/// Instance ids can be reused after an instance is destroyed,
+/// so every creation is a separate particle.
+/// Queries by instance id refer to the last particle created with the id.
+///
+/// ```ignore
+/// let mut lineage = Lineage::new();
+/// while let Some(frame) = frames.next_frame()? {
+///     lineage.record(frame.number, frames.created(), frames.destroyed());
+/// }
+/// ```
+#[derive(Default)]
+pub struct Lineage {
+    nodes: Vec<Node>,
+    last: HashMap<u64, usize>,
+}
+
+impl Lineage {
+    /// Creates an empty lineage.
+    pub fn new() -> Lineage {
+        Lineage::default()
+    }
+
+    /// Folds the events of a frame.
+    ///
+    /// Destructions are recorded before creations,
+    /// such that an instance id can be reused in the frame it was freed.
+    /// A parent destroyed in the frame is still the parent of its children,
+    /// such that an instance can decay in one frame.
+    /// Creating an instance id that is alive replaces the particle,
+    /// which is destroyed in the frame.
+    /// Destructions of unknown instances and parents that are not known are ignored.
+    pub fn record(&mut self, frame: u64, created: &[Creation], destroyed: &[u64]) {
+        for id in destroyed {
+            if let Some(&ind) = self.last.get(id) {
+                let particle = &mut self.nodes[ind].particle;
+                if particle.destroyed.is_none() {particle.destroyed = Some(frame)}
+            }
+        }
+        for c in created {
+            let parent = c.parent.and_then(|p| self.last.get(&p).cloned());
+            if let Some(&old) = self.last.get(&c.id) {
+                let old = &mut self.nodes[old].particle;
+                if old.destroyed.is_none() {old.destroyed = Some(frame)}
+            }
+            let ind = self.nodes.len();
+            self.nodes.push(Node {
+                particle: Particle {id: c.id, parent: c.parent, created: frame, destroyed: None},
+                parent,
+                children: vec![],
+            });
+            if let Some(parent) = parent {self.nodes[parent].children.push(ind)}
+            self.last.insert(c.id, ind);
+        }
+    }
+
+    /// Returns the last particle created with an instance id.
+    pub fn particle(&self, id: u64) -> Option<&Particle> {
+        self.last.get(&id).map(|&ind| &self.nodes[ind].particle)
+    }
+
+    /// Returns the parent of the last particle created with an instance id.
+    pub fn parent(&self, id: u64) -> Option<&Particle> {
+        let ind = *self.last.get(&id)?;
+        self.nodes[ind].parent.map(|p| &self.nodes[p].particle)
+    }
+
+    /// Returns the children of the last particle created with an instance id,
+    /// in the order they were created.
+    pub fn children(&self, id: u64) -> Vec<&Particle> {
+        match self.last.get(&id) {
+            Some(&ind) => self.nodes[ind].children.iter().map(|&c| &self.nodes[c].particle).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Returns the descendants of the last particle created with an instance id,
+    /// depth first in the order they were created.
+    pub fn descendants(&self, id: u64) -> Vec<&Particle> {
+        let mut res = vec![];
+        let mut stack: Vec<usize> = match self.last.get(&id) {
+            Some(&ind) => self.nodes[ind].children.iter().rev().cloned().collect(),
+            None => vec![],
+        };
+        while let Some(ind) = stack.pop() {
+            res.push(&self.nodes[ind].particle);
+            stack.extend(self.nodes[ind].children.iter().rev());
+        }
+        res
+    }
+
+    /// Returns the ancestors of the last particle created with an instance id,
+    /// starting with the parent.
+    pub fn ancestors(&self, id: u64) -> Vec<&Particle> {
+        let mut res = vec![];
+        let mut ind = self.last.get(&id).and_then(|&ind| self.nodes[ind].parent);
+        while let Some(i) = ind {
+            res.push(&self.nodes[i].particle);
+            ind = self.nodes[i].parent;
+        }
+        res
+    }
+
+    /// Returns the particles without a known parent, in the order they were created.
+    pub fn roots(&self) -> Vec<&Particle> {
+        self.nodes.iter().filter(|n| n.parent.is_none()).map(|n| &n.particle).collect()
+    }
+
+    /// Returns all particles, in the order they were created.
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.nodes.iter().map(|n| &n.particle)
+    }
+}
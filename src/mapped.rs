@@ -0,0 +1,234 @@
+//! Reading a stream from memory without copying, e.g. a memory-mapped file.
+//!
+//! Data that is aligned, as written with `WriteOptions::alignment`,
+//! can be borrowed as slices of scalars when the byte order matches.
+//! Other data is copied.
+
+use std::borrow::Cow;
+use std::io;
+use std::mem;
+
+use endian::{Endian, Header};
+use error::{Error, ErrorKind, Field};
+use raw::{self, RawBlock};
+use read_write::Scalar;
+use reserved::{FOOTER_FORMAT, FOOTER_PROPERTY, HEADER_FORMAT, HEADER_PROPERTY};
+use reserved::{PADDING_FORMAT, PADDING_PROPERTY};
+use Type;
+
+/// Scalars that can be borrowed from memory.
+///
+/// Implemented for the primitive number types,
+/// which have no padding and every bit pattern is valid.
+pub trait MappedScalar: Scalar + Copy + private::Sealed {}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! mapped_scalar {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+        impl MappedScalar for $t {}
+    )*}
+}
+
+mapped_scalar!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+/// Reads properties from bytes in memory, borrowing the data of blocks.
+///
+/// The file header is read when the reader is created.
+/// Padding is skipped.
+///
+/// ```ignore
+/// let map = unsafe { Mmap::map(&file)? };
+/// let mut r = MappedReader::new(&map)?;
+/// while let Some(prop) = r.next_property()? {
+///     if prop.property_id == POSITION {
+///         let pos: Cow<[f32]> = prop.blocks[0].scalars()?;
+///     }
+/// }
+/// ```
+pub struct MappedReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    header: Option<Header>,
+}
+
+/// A property read by `MappedReader`.
+#[derive(Clone, Debug)]
+pub struct MappedProperty<'a> {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// The blocks of the property.
+    pub blocks: Vec<MappedBlock<'a>>,
+}
+
+/// A block borrowed from memory.
+#[derive(Clone, Copy, Debug)]
+pub struct MappedBlock<'a> {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// Offset instance id.
+    pub offset: u64,
+    /// Data, in the byte order of the stream.
+    pub data: &'a [u8],
+    /// Byte order of the stream.
+    pub endian: Endian,
+}
+
+impl<'a> MappedReader<'a> {
+    /// Creates a new reader, reading the file header if there is one.
+    pub fn new(data: &'a [u8]) -> io::Result<MappedReader<'a>> {
+        let mut r = data;
+        let mut header = None;
+        if let Some((state, ty, prop)) = raw::read_property_header(&mut r)? {
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                let blocks = RawBlock::read_property(state, ty, prop, &mut r)?;
+                header = blocks.first().and_then(|b| Header::decode(&b.data));
+                if header.is_none() {
+                    return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+                }
+            }
+        }
+        let position = if header.is_some() {data.len() - r.len()} else {0};
+        Ok(MappedReader {data, position, header})
+    }
+
+    /// Returns the file header, if the stream starts with one.
+    pub fn header(&self) -> Option<Header> {
+        self.header
+    }
+
+    /// Returns the byte order of the stream.
+    pub fn endianness(&self) -> Endian {
+        self.header.map(|h| h.endian).unwrap_or_default()
+    }
+
+    /// Returns the position of the next property.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Reads the next property.
+    ///
+    /// Returns `None` at the end of stream or the end of the data.
+    pub fn next_property(&mut self) -> io::Result<Option<MappedProperty<'a>>> {
+        loop {
+            if self.position == self.data.len() {return Ok(None)}
+            let type_format = self.read_u16(Field::TypeFormat)?;
+            if type_format == 0 {return Ok(None)}
+            let property_id = self.read_u16(Field::PropertyId)?;
+            let mut blocks = vec![];
+            loop {
+                let bytes = self.read_u64(Field::Bytes)?;
+                if bytes == 0 {break}
+                let offset = self.read_u64(Field::OffsetInstanceId)?;
+                let data = match self.take(bytes) {
+                    Some(data) => data,
+                    None => return Err(self.eof(Field::Data)),
+                };
+                blocks.push(MappedBlock {
+                    type_format,
+                    property_id,
+                    offset,
+                    data,
+                    endian: self.endianness(),
+                });
+            }
+            if type_format == PADDING_FORMAT && property_id == PADDING_PROPERTY {continue}
+            if type_format == FOOTER_FORMAT && property_id == FOOTER_PROPERTY {continue}
+            return Ok(Some(MappedProperty {type_format, property_id, blocks}));
+        }
+    }
+
+    fn take(&mut self, n: u64) -> Option<&'a [u8]> {
+        let rest = &self.data[self.position..];
+        if n > rest.len() as u64 {return None}
+        self.position += n as usize;
+        Some(&rest[..n as usize])
+    }
+
+    fn eof(&self, field: Field) -> io::Error {
+        Error::new(ErrorKind::UnexpectedEof)
+            .with_position(self.position as u64)
+            .with_field(field)
+            .into_io(io::ErrorKind::UnexpectedEof)
+    }
+
+    fn read_u16(&mut self, field: Field) -> io::Result<u16> {
+        let b = match self.take(2) {
+            Some(b) => [b[0], b[1]],
+            None => return Err(self.eof(field)),
+        };
+        Ok(match self.endianness() {
+            Endian::Little => u16::from_le_bytes(b),
+            Endian::Big => u16::from_be_bytes(b),
+        })
+    }
+
+    fn read_u64(&mut self, field: Field) -> io::Result<u64> {
+        let mut b = [0; 8];
+        match self.take(8) {
+            Some(data) => b.copy_from_slice(data),
+            None => return Err(self.eof(field)),
+        }
+        Ok(match self.endianness() {
+            Endian::Little => u64::from_le_bytes(b),
+            Endian::Big => u64::from_be_bytes(b),
+        })
+    }
+}
+
+impl<'a> MappedBlock<'a> {
+    /// Returns `true` if the data can be borrowed as a slice of `T`.
+    pub fn is_borrowable<T: MappedScalar>(&self) -> bool {
+        cfg!(target_endian = "little") && self.endian == Endian::Little &&
+        (self.data.as_ptr() as usize).is_multiple_of(mem::align_of::<T>())
+    }
+
+    /// Returns the scalars of the data, row by row for vectors and matrices.
+    ///
+    /// The data is borrowed when it is aligned and has the byte order of the platform,
+    /// otherwise it is copied.
+    /// Returns an error of kind `ErrorKind::TypeMismatch` if the scalar type differs,
+    /// or `ErrorKind::BytesNotMultiple` if the data is not a whole number of scalars.
+    pub fn scalars<T: MappedScalar>(&self) -> io::Result<Cow<'a, [T]>> {
+        let ty = <T as Scalar>::ty();
+        if Type::info(self.type_format).map(|(t, _, _)| t) != Some(ty) {
+            return Err(Error::new(ErrorKind::TypeMismatch)
+                .with_property(self.property_id)
+                .with_types((ty, 1, 1), self.type_format)
+                .into());
+        }
+        let size = mem::size_of::<T>();
+        if !self.data.len().is_multiple_of(size) {
+            return Err(Error::new(ErrorKind::BytesNotMultiple)
+                .with_property(self.property_id)
+                .with_item_size(self.data.len() as u64, size as u64)
+                .into());
+        }
+        if self.is_borrowable::<T>() {
+            // The type is a primitive number, where every bit pattern is valid,
+            // and the data is aligned with a length that is a multiple of the size.
+            let (head, items, tail) = unsafe {self.data.align_to::<T>()};
+            if head.is_empty() && tail.is_empty() {
+                return Ok(Cow::Borrowed(items));
+            }
+        }
+        let mut res = Vec::with_capacity(self.data.len() / size);
+        let mut buf = [0; 8];
+        for chunk in self.data.chunks(size) {
+            buf[..size].copy_from_slice(chunk);
+            if self.endian == Endian::Big {buf[..size].reverse()}
+            let mut val = T::default();
+            val.read(&mut &buf[..size])?;
+            res.push(val);
+        }
+        Ok(Cow::Owned(res))
+    }
+}
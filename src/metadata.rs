@@ -0,0 +1,168 @@
+//! Descriptions and other key/value metadata of properties.
+//!
+//! Metadata is stored in a block with a reserved custom format.
+//! The data is a list of entries, each with the property id (u16)
+//! and the number of pairs (u16), followed by the pairs.
+//! A pair is the length of the key (u16), the key,
+//! the length of the value (u32) and the value, all in UTF-8.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use reserved::{METADATA_FORMAT, METADATA_PROPERTY};
+use Bytes;
+use State;
+
+/// The key of descriptions.
+pub const DESCRIPTION: &str = "description";
+/// The key of names assigned by `PropertyAllocator`.
+pub const NAME: &str = "name";
+
+/// Key/value metadata of a property.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Metadata {
+    pairs: Vec<(String, String)>,
+}
+
+impl Metadata {
+    /// Creates empty metadata.
+    pub fn new() -> Metadata {
+        Metadata::default()
+    }
+
+    /// Sets the value of a key.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.pairs.iter_mut().find(|&&mut (ref k, _)| k == key) {
+            Some(&mut (_, ref mut v)) => *v = value.into(),
+            None => self.pairs.push((key.into(), value.into())),
+        }
+    }
+
+    /// Sets the value of a key.
+    pub fn with(mut self, key: &str, value: &str) -> Metadata {
+        self.set(key, value);
+        self
+    }
+
+    /// Returns the value of a key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs.iter().find(|(k, _)| k == key).map(|(_, v)| &v[..])
+    }
+
+    /// Returns the description.
+    pub fn description(&self) -> Option<&str> {
+        self.get(DESCRIPTION)
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> Option<&str> {
+        self.get(NAME)
+    }
+
+    /// Returns an iterator over keys and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(k, v)| (&k[..], &v[..]))
+    }
+
+    /// Returns `true` if there are no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Adds pairs from other metadata, replacing values of the same keys.
+    pub fn merge(&mut self, other: &Metadata) {
+        for (k, v) in &other.pairs {
+            self.set(k, v);
+        }
+    }
+}
+
+/// Writes metadata of properties.
+pub fn write_metadata<W: io::Write>(entries: &[(u16, &Metadata)], w: &mut W) -> io::Result<()> {
+    let mut data = vec![];
+    for &(prop, metadata) in entries {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(prop).into()
+        };
+        if metadata.pairs.len() > u16::MAX as usize {return Err(err())}
+        data.extend_from_slice(&prop.to_le_bytes());
+        data.extend_from_slice(&(metadata.pairs.len() as u16).to_le_bytes());
+        for (k, v) in &metadata.pairs {
+            if k.len() > u16::MAX as usize || v.len() > u32::MAX as usize {return Err(err())}
+            data.extend_from_slice(&(k.len() as u16).to_le_bytes());
+            data.extend_from_slice(k.as_bytes());
+            data.extend_from_slice(&(v.len() as u32).to_le_bytes());
+            data.extend_from_slice(v.as_bytes());
+        }
+    }
+    RawBlock::write_property(METADATA_FORMAT, METADATA_PROPERTY, &[RawBlock {
+        type_format: METADATA_FORMAT,
+        property_id: METADATA_PROPERTY,
+        offset: 0,
+        data,
+    }], w)
+}
+
+/// Reads metadata of properties.
+///
+/// Metadata of the same property is merged, where the last value of a key wins.
+pub fn read_metadata<R: io::Read>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    r: &mut R
+) -> io::Result<Vec<(u16, Metadata)>> {
+    let mut entries = vec![];
+    for block in RawBlock::read_property(state, ty, property_id, r)? {
+        merge_metadata(&mut entries, decode_metadata(&block)?);
+    }
+    Ok(entries)
+}
+
+/// Decodes metadata of properties from a block.
+pub fn decode_metadata(block: &RawBlock) -> io::Result<Vec<(u16, Metadata)>> {
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+    };
+    fn take<'a>(data: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        if data.len() < n {return None}
+        let (a, b) = data.split_at(n);
+        *data = b;
+        Some(a)
+    }
+    fn string(data: &mut &[u8], n: usize) -> Option<String> {
+        take(data, n).and_then(|s| ::std::str::from_utf8(s).ok()).map(|s| s.into())
+    }
+
+    if block.type_format != METADATA_FORMAT {return Err(err())}
+    let mut entries = vec![];
+    let mut data = &block.data[..];
+    while !data.is_empty() {
+        let head = take(&mut data, 4).ok_or_else(err)?;
+        let prop = u16::from_le_bytes([head[0], head[1]]);
+        let n = u16::from_le_bytes([head[2], head[3]]);
+        let mut metadata = Metadata::new();
+        for _ in 0..n {
+            let len = take(&mut data, 2).ok_or_else(err)?;
+            let key = string(&mut data, u16::from_le_bytes([len[0], len[1]]) as usize)
+                .ok_or_else(err)?;
+            let len = take(&mut data, 4).ok_or_else(err)?;
+            let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]);
+            let value = string(&mut data, len as usize).ok_or_else(err)?;
+            metadata.set(&key, &value);
+        }
+        merge_metadata(&mut entries, vec![(prop, metadata)]);
+    }
+    Ok(entries)
+}
+
+/// Merges metadata of properties, where the last value of a key wins.
+pub fn merge_metadata(entries: &mut Vec<(u16, Metadata)>, other: Vec<(u16, Metadata)>) {
+    for (prop, metadata) in other {
+        match entries.iter_mut().find(|&&mut (p, _)| p == prop) {
+            Some(&mut (_, ref mut m)) => m.merge(&metadata),
+            None => entries.push((prop, metadata)),
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Counting the bytes and blocks of a stream for monitoring.
+//!
+//! `Metered` wraps a reader or writer and counts what passes through
+//! into shared counters, which another thread reads with `Metrics::snapshot`.
+//! Counting costs a few relaxed atomic additions per read or write and per block,
+//! and a lock once per property for the counts of each property.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use error::Field;
+use parse::{Event, Parser};
+use raw::RawBlock;
+use time::Time;
+
+/// The counts of one property.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct PropertyMetrics {
+    /// Property id.
+    pub property_id: u16,
+    /// The number of data bytes in blocks, without headers.
+    pub bytes: u64,
+    /// The number of blocks.
+    pub blocks: u64,
+}
+
+/// The counts of a stream at one point in time.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MetricsSnapshot {
+    /// The number of bytes, including headers.
+    pub bytes: u64,
+    /// The number of blocks.
+    pub blocks: u64,
+    /// The number of properties.
+    pub properties: u64,
+    /// The number of frames, if a time property is set.
+    pub frames: u64,
+    /// The time of the latest frame, if a time property is set.
+    pub frame_time: Option<f64>,
+    /// The counts of each property, sorted by property id.
+    ///
+    /// A property is counted when its end of bytes has passed.
+    pub per_property: Vec<PropertyMetrics>,
+}
+
+impl MetricsSnapshot {
+    /// Returns the counts of a property.
+    pub fn property(&self, property_id: u16) -> Option<PropertyMetrics> {
+        self.per_property.binary_search_by_key(&property_id, |p| p.property_id)
+            .ok().map(|i| self.per_property[i])
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    bytes: AtomicU64,
+    blocks: AtomicU64,
+    properties: AtomicU64,
+    frames: AtomicU64,
+    has_frame_time: AtomicBool,
+    frame_time: AtomicU64,
+    per_property: Mutex<BTreeMap<u16, PropertyMetrics>>,
+}
+
+/// Shared counters of a metered stream.
+///
+/// Clones share the same counters, such that the stream is monitored from another thread:
+///
+/// ```ignore
+/// let mut w = Metered::new(file).time_property(TIME);
+/// let recorder = w.metrics();
+/// let mut r = Metered::new(ShmReader::open(path)?);
+/// let player = r.metrics();
+/// thread::spawn(move || loop {
+///     let (w, r) = (recorder.snapshot(), player.snapshot());
+///     println!("{} bytes written, reader {} bytes behind", w.bytes, w.bytes - r.bytes);
+///     thread::sleep(Duration::from_secs(1));
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+impl Metrics {
+    /// Creates counters at zero.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    fn per_property(&self) -> MutexGuard<'_, BTreeMap<u16, PropertyMetrics>> {
+        self.counters.per_property.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Returns the number of bytes, including headers.
+    pub fn bytes(&self) -> u64 {
+        self.counters.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of blocks.
+    pub fn blocks(&self) -> u64 {
+        self.counters.blocks.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current counts.
+    ///
+    /// Counts are read one by one while the stream continues,
+    /// so they may be from slightly different points in the stream.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let c = &self.counters;
+        let frame_time = if c.has_frame_time.load(Ordering::Acquire) {
+            Some(f64::from_bits(c.frame_time.load(Ordering::Relaxed)))
+        } else {None};
+        MetricsSnapshot {
+            bytes: c.bytes.load(Ordering::Relaxed),
+            blocks: c.blocks.load(Ordering::Relaxed),
+            properties: c.properties.load(Ordering::Relaxed),
+            frames: c.frames.load(Ordering::Relaxed),
+            frame_time,
+            per_property: self.per_property().values().cloned().collect(),
+        }
+    }
+
+    /// Sets the counts to zero.
+    pub fn reset(&self) {
+        let c = &self.counters;
+        c.bytes.store(0, Ordering::Relaxed);
+        c.blocks.store(0, Ordering::Relaxed);
+        c.properties.store(0, Ordering::Relaxed);
+        c.frames.store(0, Ordering::Relaxed);
+        c.has_frame_time.store(false, Ordering::Relaxed);
+        self.per_property().clear();
+    }
+}
+
+type Callback<'a> = Box<dyn FnMut(&MetricsSnapshot) + 'a>;
+
+/// Wraps a reader or writer and counts the bytes and blocks passing through.
+///
+/// Counting follows the stream from its start.
+/// The frame time is decoded from a time property stored as `f32` or `f64`.
+pub struct Metered<'a, T> {
+    inner: T,
+    metrics: Metrics,
+    parser: Parser,
+    time_property: Option<u16>,
+    property: Option<PropertyMetrics>,
+    time: Option<RawBlock>,
+    on_blocks: Option<(u64, Callback<'a>)>,
+    next_callback: u64,
+}
+
+impl<'a, T> Metered<'a, T> {
+    /// Creates a metered reader or writer at the start of a stream, with new counters.
+    pub fn new(inner: T) -> Metered<'a, T> {
+        Metered::with_metrics(inner, Metrics::new())
+    }
+
+    /// Creates a metered reader or writer that adds to existing counters.
+    pub fn with_metrics(inner: T, metrics: Metrics) -> Metered<'a, T> {
+        Metered {
+            inner,
+            metrics,
+            parser: Parser::new(),
+            time_property: None,
+            property: None,
+            time: None,
+            on_blocks: None,
+            next_callback: 0,
+        }
+    }
+
+    /// Sets the time property, which counts frames and their time.
+    pub fn time_property(mut self, property_id: u16) -> Metered<'a, T> {
+        self.time_property = Some(property_id);
+        self
+    }
+
+    /// Sets a callback that receives a snapshot every `n` blocks.
+    ///
+    /// The callback is called on the thread reading or writing,
+    /// after the read or write that reaches the number of blocks.
+    pub fn on_blocks<F: 'a + FnMut(&MetricsSnapshot)>(mut self, n: u64, f: F) -> Metered<'a, T> {
+        let n = n.max(1);
+        self.next_callback = (self.metrics.blocks() / n + 1) * n;
+        self.on_blocks = Some((n, Box::new(f)));
+        self
+    }
+
+    /// Returns the shared counters.
+    pub fn metrics(&self) -> Metrics {
+        self.metrics.clone()
+    }
+
+    /// Gets a reference to the underlying reader or writer.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader or writer.
+    ///
+    /// Reading or writing directly is not counted.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader or writer.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn count(&mut self, mut data: &[u8]) {
+        let counters = &self.metrics.counters;
+        counters.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+        while !data.is_empty() {
+            if self.parser.field() == Field::Data {
+                let n = self.parser.remaining().min(data.len() as u64) as usize;
+                if let Some(ref mut t) = self.time {t.data.extend_from_slice(&data[..n])}
+                if let Some(ref mut p) = self.property {p.bytes += n as u64}
+                self.parser.consume(&data[..n], |_| {});
+                data = &data[n..];
+                continue;
+            }
+
+            // Header fields are consumed a byte at a time, to see where the data starts.
+            let mut event = None;
+            self.parser.consume(&data[..1], |e| event = Some(e));
+            data = &data[1..];
+            match event {
+                Some(Event::Property {type_format, property_id}) => {
+                    counters.properties.fetch_add(1, Ordering::Relaxed);
+                    self.property = Some(PropertyMetrics {property_id, bytes: 0, blocks: 0});
+                    if Some(property_id) == self.time_property {
+                        counters.frames.fetch_add(1, Ordering::Relaxed);
+                        self.time = Some(RawBlock {type_format, property_id, offset: 0, data: vec![]});
+                    }
+                }
+                Some(Event::Block {bytes, offset}) => {
+                    counters.blocks.fetch_add(1, Ordering::Relaxed);
+                    if let Some(ref mut p) = self.property {p.blocks += 1}
+                    if let Some(ref mut t) = self.time {
+                        // A time is one scalar, so larger blocks are not kept.
+                        if bytes > 8 || !t.data.is_empty() {self.time = None} else {t.offset = offset}
+                    }
+                }
+                Some(Event::EndBytes) => {
+                    if let Some(p) = self.property.take() {
+                        let mut per_property = self.metrics.per_property();
+                        let entry = per_property.entry(p.property_id).or_insert(PropertyMetrics {
+                            property_id: p.property_id,
+                            bytes: 0,
+                            blocks: 0,
+                        });
+                        entry.bytes += p.bytes;
+                        entry.blocks += p.blocks;
+                    }
+                    if let Some(t) = self.time.take() {
+                        if let Ok(t) = Time::decode(&t) {
+                            counters.frame_time.store(t.to_bits(), Ordering::Relaxed);
+                            counters.has_frame_time.store(true, Ordering::Release);
+                        }
+                    }
+                }
+                Some(Event::End) | None => {}
+            }
+        }
+
+        if let Some((n, ref mut f)) = self.on_blocks {
+            let blocks = self.metrics.blocks();
+            if blocks >= self.next_callback {
+                self.next_callback = (blocks / n + 1) * n;
+                f(&self.metrics.snapshot());
+            }
+        }
+    }
+}
+
+impl<'a, R: io::Read> io::Read for Metered<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<'a, W: io::Write> io::Write for Metered<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
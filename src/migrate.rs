@@ -0,0 +1,175 @@
+//! Upgrading streams written by older versions.
+//!
+//! Streams without a file header are version 0.
+//! Migrated streams have a header with the current version,
+//! are little endian and have no padding for alignment.
+//! Some writers of version 0 used wrong type ids for `i16`, `i32`, `i64`, `f32` and `f64`,
+//! which is corrected by setting the real type of the affected properties.
+
+use std::io;
+
+use endian::{Endian, Header};
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reader::PoolReader;
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY, PADDING_FORMAT, PADDING_PROPERTY};
+use State;
+use Type;
+
+/// Options for migrating a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrateOptions {
+    /// The real scalar type of properties that were written with a wrong type id.
+    pub types: Vec<(u16, Type)>,
+}
+
+impl MigrateOptions {
+    /// Sets the real scalar type of a property.
+    ///
+    /// Built-in type formats of the property are rewritten to the same dimensions
+    /// with this type.
+    pub fn real_type(mut self, property_id: u16, ty: Type) -> MigrateOptions {
+        self.types.retain(|&(id, _)| id != property_id);
+        self.types.push((property_id, ty));
+        self
+    }
+}
+
+/// A transformation applied when migrating a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Migration {
+    /// A header was added to a stream without header.
+    AddedHeader,
+    /// The version in the header was raised from an older version.
+    UpgradedVersion {
+        /// The version of the stream.
+        from: u16,
+    },
+    /// Big endian data was converted to little endian.
+    ConvertedToLittleEndian,
+    /// Padding for aligned data was removed.
+    RemovedAlignment {
+        /// The alignment of the stream in bytes.
+        alignment: u16,
+    },
+    /// The type format of a property was rewritten.
+    Retyped {
+        /// The property id.
+        property_id: u16,
+        /// The type format in the stream.
+        from: u16,
+        /// The new type format.
+        to: u16,
+    },
+}
+
+/// The result of migrating a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// The version of the stream, 0 for streams without header.
+    pub version: u16,
+    /// The transformations that were applied, in the order they first happened.
+    ///
+    /// Every transformation is listed once.
+    /// An empty list means the stream was already in the current encoding.
+    pub migrations: Vec<Migration>,
+}
+
+/// Rewrites a stream written by an older version in the encoding of the current version.
+///
+/// Properties keep their order, and blocks are copied unchanged,
+/// except for the type formats set in the options.
+/// Only built-in type formats are rewritten.
+/// The output is terminated with an end of stream.
+///
+/// Returns an error of kind `ErrorKind::UnsupportedVersion`
+/// if the stream has a newer version than the current version,
+/// and an error of kind `ErrorKind::BytesNotMultiple`
+/// if the data of a rewritten property is not a multiple of the new item size.
+///
+/// ```ignore
+/// let opts = MigrateOptions::default().real_type(ENERGY, Type::F64);
+/// let report = migrate(&mut old, &mut new, &opts)?;
+/// for migration in &report.migrations {
+///     println!("{:?}", migration);
+/// }
+/// ```
+pub fn migrate<R: io::Read, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    opts: &MigrateOptions
+) -> io::Result<MigrationReport> {
+    let mut reader = PoolReader::new(r);
+    let mut report = MigrationReport::default();
+    let mut first = true;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut reader)? {
+        let mut blocks = RawBlock::read_property(state, ty, prop, &mut reader)?;
+        if first {
+            first = false;
+            start(reader.header(), &mut report, w)?;
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY && reader.header().is_some() {
+                continue;
+            }
+        }
+        if ty == PADDING_FORMAT && prop == PADDING_PROPERTY {continue}
+        let new_ty = match retype(ty, prop, opts) {
+            Some(new_ty) => new_ty,
+            None => {
+                RawBlock::write_property(ty, prop, &blocks, w)?;
+                continue;
+            }
+        };
+        let size = raw::item_size(new_ty).unwrap_or(1);
+        for block in &mut blocks {
+            if !(block.data.len() as u64).is_multiple_of(size) {
+                return Err(Error::new(ErrorKind::BytesNotMultiple).with_property(prop).into());
+            }
+            block.type_format = new_ty;
+        }
+        add(&mut report, Migration::Retyped {property_id: prop, from: ty, to: new_ty});
+        RawBlock::write_property(new_ty, prop, &blocks, w)?;
+    }
+    if first {start(None, &mut report, w)?}
+    State::new().end_type_formats(w)?;
+    Ok(report)
+}
+
+/// Writes the header of the current version and reports how the old header differs.
+fn start<W: io::Write>(
+    header: Option<Header>,
+    report: &mut MigrationReport,
+    w: &mut W
+) -> io::Result<()> {
+    match header {
+        None => add(report, Migration::AddedHeader),
+        Some(header) => {
+            if header.version > Header::VERSION {
+                return Err(Error::new(ErrorKind::UnsupportedVersion)
+                    .with_range(0, Header::VERSION as u64 + 1).into());
+            }
+            report.version = header.version;
+            if header.version < Header::VERSION {
+                add(report, Migration::UpgradedVersion {from: header.version});
+            }
+            if header.endian == Endian::Big {
+                add(report, Migration::ConvertedToLittleEndian);
+            }
+            if header.alignment > 1 {
+                add(report, Migration::RemovedAlignment {alignment: header.alignment});
+            }
+        }
+    }
+    Header::default().write(w)
+}
+
+/// Returns the new type format of a property, if it has another real type.
+fn retype(ty: u16, prop: u16, opts: &MigrateOptions) -> Option<u16> {
+    let &(_, real) = opts.types.iter().find(|&&(id, _)| id == prop)?;
+    let (old, rows, cols) = Type::info(ty)?;
+    if old == real {return None}
+    Type::format_for(real, rows, cols)
+}
+
+fn add(report: &mut MigrationReport, migration: Migration) {
+    if !report.migrations.contains(&migration) {report.migrations.push(migration)}
+}
@@ -0,0 +1,199 @@
+//! Combining several streams with the same property ids into one.
+//!
+//! Each source stream is copied into a namespace,
+//! which is a band of property ids `base..base + stride`.
+//! A source property id `id` becomes `base + id`.
+//! Before the properties of a namespace, a manifest is written,
+//! such that readers can recover the original ids.
+
+use std::io;
+
+use endian::EndianReader;
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reserved::{HEADER_FORMAT, HEADER_PROPERTY, NAMESPACE_FORMAT, NAMESPACE_PROPERTY, RESERVED_PROPERTY_START};
+use Bytes;
+use State;
+
+/// A band of property ids holding the properties of one source stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Namespace {
+    /// The first property id of the band.
+    pub base: u16,
+    /// The number of property ids in the band.
+    pub stride: u16,
+}
+
+impl Namespace {
+    /// Returns `true` if the property id is in the band.
+    pub fn contains(&self, property_id: u16) -> bool {
+        property_id >= self.base && ((property_id - self.base) as u32) < self.stride as u32
+    }
+
+    /// Returns the property id in the stream of an original property id.
+    pub fn map(&self, original: u16) -> Option<u16> {
+        if original >= self.stride {return None}
+        self.base.checked_add(original)
+    }
+
+    /// Returns the original property id of a property id in the band.
+    pub fn original(&self, property_id: u16) -> Option<u16> {
+        if self.contains(property_id) {Some(property_id - self.base)} else {None}
+    }
+
+    fn overlaps(&self, other: &Namespace) -> bool {
+        let end = self.base as u32 + self.stride as u32;
+        let other_end = other.base as u32 + other.stride as u32;
+        (self.base as u32) < other_end && (other.base as u32) < end
+    }
+
+    /// Writes the manifest.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.base.to_le_bytes());
+        data.extend_from_slice(&self.stride.to_le_bytes());
+        RawBlock::write_property(NAMESPACE_FORMAT, NAMESPACE_PROPERTY, &[RawBlock {
+            type_format: NAMESPACE_FORMAT,
+            property_id: NAMESPACE_PROPERTY,
+            offset: 0,
+            data,
+        }], w)
+    }
+
+    /// Reads the manifest.
+    pub fn read<R: io::Read>(
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<Namespace> {
+        let blocks = RawBlock::read_property(state, ty, property_id, r)?;
+        match blocks.first() {
+            Some(block) if blocks.len() == 1 => Namespace::decode(block),
+            _ => Err(Error::new(ErrorKind::InvalidData).with_property(property_id).into()),
+        }
+    }
+
+    /// Decodes the manifest from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<Namespace> {
+        let data = &block.data;
+        if block.type_format != NAMESPACE_FORMAT || block.offset != 0 || data.len() < 4 {
+            return Err(Error::new(ErrorKind::InvalidData).with_property(block.property_id).into());
+        }
+        Ok(Namespace {
+            base: u16::from_le_bytes([data[0], data[1]]),
+            stride: u16::from_le_bytes([data[2], data[3]]),
+        })
+    }
+}
+
+/// The namespaces of a stream.
+///
+/// When writing, this keeps track of the namespaces already emitted,
+/// such that property ids of different source streams never collide:
+///
+/// ```ignore
+/// let mut namespaces = Namespaces::new();
+/// for (i, file) in files.iter_mut().enumerate() {
+///     namespaces.copy(file, &mut w, i as u16 * 100, 100)?;
+/// }
+/// State::new().end_type_formats(&mut w)?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Namespaces {
+    namespaces: Vec<Namespace>,
+}
+
+impl Namespaces {
+    /// Creates an empty list of namespaces.
+    pub fn new() -> Namespaces {
+        Namespaces::default()
+    }
+
+    /// Returns the namespaces, in the order they were added.
+    pub fn namespaces(&self) -> &[Namespace] {
+        &self.namespaces
+    }
+
+    /// Returns the namespace and original property id of a property id.
+    pub fn original(&self, property_id: u16) -> Option<(Namespace, u16)> {
+        self.namespaces.iter()
+            .filter_map(|ns| ns.original(property_id).map(|id| (*ns, id)))
+            .next()
+    }
+
+    /// Adds a namespace.
+    ///
+    /// Returns an error if the band overlaps an existing namespace
+    /// or includes reserved property ids.
+    pub fn add(&mut self, ns: Namespace) -> io::Result<()> {
+        if ns.stride == 0 || ns.base as u32 + ns.stride as u32 > RESERVED_PROPERTY_START as u32 ||
+           self.namespaces.iter().any(|other| other.overlaps(&ns)) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        self.namespaces.push(ns);
+        Ok(())
+    }
+
+    /// Reads a manifest and adds its namespace.
+    pub fn read_manifest<R: io::Read>(
+        &mut self,
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<()> {
+        let ns = Namespace::read(state, ty, property_id, r)?;
+        self.add(ns).map_err(|_| Error::new(ErrorKind::InvalidData).with_property(property_id).into())
+    }
+
+    /// Copies a stream into a new namespace.
+    ///
+    /// The manifest is written first, followed by the properties of the stream.
+    /// The end of stream is not written, such that more streams can be copied.
+    ///
+    /// Returns an error if the band collides with property ids already emitted,
+    /// or if a property id of the source stream does not fit in the band.
+    /// The file header of the source stream is skipped,
+    /// and properties are written little endian.
+    pub fn copy<R: io::Read, W: io::Write>(
+        &mut self,
+        r: &mut R,
+        w: &mut W,
+        base: u16,
+        stride: u16
+    ) -> io::Result<Namespace> {
+        let ns = Namespace {base, stride};
+        self.add(ns)?;
+        ns.write(w)?;
+        let r = &mut EndianReader::new(r);
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                raw::copy_property(state, ty, prop, r, &mut io::sink())?;
+                continue;
+            }
+            let id = match ns.map(prop) {
+                Some(id) => id,
+                None => return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(prop)
+                    .with_range(0, stride as u64)
+                    .into()),
+            };
+            raw::copy_property(state, ty, id, r, w)?;
+        }
+        Ok(ns)
+    }
+}
+
+/// Copies a stream into the namespace `base..base + stride`.
+///
+/// This is the same as `Namespaces::copy` for a single stream.
+/// Use `Namespaces` to check collisions between several streams.
+pub fn namespace_copy<R: io::Read, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    base: u16,
+    stride: u16
+) -> io::Result<Namespace> {
+    Namespaces::new().copy(r, w, base, stride)
+}
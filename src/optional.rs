@@ -0,0 +1,105 @@
+//! Arrays of optional values.
+//!
+//! An array of `Option<T>` is written as a dense array of `T`,
+//! where `None` is written as the default value,
+//! followed by a presence bitmap under the same property id.
+//! Readers that do not know about the bitmap still see a valid array.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use read_write::Element;
+use reserved::PRESENCE_FORMAT;
+use Bytes;
+use State;
+
+/// Writes an array of optional values.
+pub fn write_optional_array<T: Element, W: io::Write>(
+    property_id: u16,
+    arr: &[Option<T>],
+    w: &mut W
+) -> io::Result<()> {
+    write_optional_array_at(property_id, 0, arr, w)
+}
+
+/// Writes an array of optional values, starting at an offset instance id.
+pub fn write_optional_array_at<T: Element, W: io::Write>(
+    property_id: u16,
+    offset: u64,
+    arr: &[Option<T>],
+    w: &mut W
+) -> io::Result<()> {
+    let (ty, size) = match T::format() {
+        Some(x) => x,
+        None => return Err(io::ErrorKind::InvalidInput.into()),
+    };
+    let default: T = Default::default();
+    let mut data = Vec::with_capacity(size as usize * arr.len());
+    let mut bits = vec![0; arr.len().div_ceil(8)];
+    for (i, x) in arr.iter().enumerate() {
+        match *x {
+            Some(ref x) => {
+                x.write_element(&mut data)?;
+                bits[i / 8] |= 1 << (i % 8);
+            }
+            None => default.write_element(&mut data)?,
+        }
+    }
+    RawBlock::write_property(ty, property_id, &[RawBlock {
+        type_format: ty,
+        property_id,
+        offset,
+        data,
+    }], w)?;
+    RawBlock::write_property(PRESENCE_FORMAT, property_id, &[RawBlock {
+        type_format: PRESENCE_FORMAT,
+        property_id,
+        offset,
+        data: bits,
+    }], w)
+}
+
+/// Reads an array of optional values.
+///
+/// The presence bitmap is read from the property following the array,
+/// with one block for each block of the array.
+/// Instances that are not covered by the blocks of the array are `None`.
+pub fn read_optional_array<T: Element, R: io::Read>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    arr: &mut Vec<Option<T>>,
+    r: &mut R
+) -> io::Result<()> {
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(property_id).into()
+    };
+    let blocks = RawBlock::read_property(state, ty, property_id, r)?;
+    let presence = match raw::read_property_header(r)? {
+        Some((state, PRESENCE_FORMAT, prop)) if prop == property_id => {
+            RawBlock::read_property(state, PRESENCE_FORMAT, prop, r)?
+        }
+        _ => return Err(err()),
+    };
+    if presence.len() != blocks.len() {return Err(err())}
+    for (block, bits) in blocks.iter().zip(&presence) {
+        let items = block.decode::<T>()?;
+        let bytes = items.len().div_ceil(8);
+        if bits.offset != block.offset || bits.data.len() < bytes {
+            return Err(err());
+        }
+        for (i, item) in items.into_iter().enumerate() {
+            let ind = match block.offset.checked_add(i as u64) {
+                Some(x) if x < usize::MAX as u64 => x as usize,
+                _ => return Err(err()),
+            };
+            while ind >= arr.len() {
+                arr.push(None);
+            }
+            let set = bits.data[i / 8] & (1 << (i % 8)) != 0;
+            arr[ind] = if set {Some(item)} else {None};
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,186 @@
+//! Instance ids packed from an event number and a particle index.
+//!
+//! The high bits of the instance id are the event number
+//! and the low bits are the index of the particle within the event.
+//! The split is recorded in the metadata of the property,
+//! as the number of event bits and particle bits separated by `/`, e.g. `40/24`.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use dyn_value;
+use error::{Error, ErrorKind};
+use metadata::Metadata;
+use raw::RawBlock;
+use read_write::Element;
+use reserved::PRESENCE_FORMAT;
+use Bytes;
+use State;
+
+/// The metadata key of the split of packed instance ids.
+pub const PACKED_ID: &str = "packed_id";
+
+/// Packs an event number and a particle index into an instance id.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PackedId {
+    particle_bits: u8,
+}
+
+impl PackedId {
+    /// Creates a split with the number of event bits and particle bits.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// unless the bits add up to 64 and there are 1 to 32 particle bits.
+    pub fn new(event_bits: u8, particle_bits: u8) -> io::Result<PackedId> {
+        if event_bits as u32 + particle_bits as u32 != 64 || particle_bits == 0 || particle_bits > 32 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        Ok(PackedId {particle_bits})
+    }
+
+    /// Returns the number of event bits.
+    pub fn event_bits(&self) -> u8 {
+        64 - self.particle_bits
+    }
+
+    /// Returns the number of particle bits.
+    pub fn particle_bits(&self) -> u8 {
+        self.particle_bits
+    }
+
+    /// Returns the largest event number.
+    pub fn max_event(&self) -> u64 {
+        u64::MAX >> self.particle_bits
+    }
+
+    /// Returns the largest particle index.
+    pub fn max_particle(&self) -> u32 {
+        (u64::MAX >> (64 - self.particle_bits)) as u32
+    }
+
+    /// Packs an event number and a particle index.
+    ///
+    /// Returns `None` if either is out of range.
+    pub fn pack(&self, event: u64, particle: u32) -> Option<u64> {
+        if event > self.max_event() || particle > self.max_particle() {return None}
+        Some(event << self.particle_bits | particle as u64)
+    }
+
+    /// Unpacks an instance id into the event number and the particle index.
+    pub fn unpack(&self, id: u64) -> (u64, u32) {
+        (id >> self.particle_bits, (id & self.max_particle() as u64) as u32)
+    }
+
+    /// Returns metadata that records the split.
+    pub fn metadata(&self) -> Metadata {
+        Metadata::new().with(PACKED_ID, &format!("{}/{}", self.event_bits(), self.particle_bits))
+    }
+
+    /// Reads the split from metadata.
+    ///
+    /// Returns `None` if no split is recorded,
+    /// and an error of kind `io::ErrorKind::InvalidData` if the split is not valid.
+    pub fn from_metadata(metadata: &Metadata) -> io::Result<Option<PackedId>> {
+        let val = match metadata.get(PACKED_ID) {
+            None => return Ok(None),
+            Some(x) => x,
+        };
+        let mut parts = val.split('/').map(|s| s.trim().parse::<u8>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(event_bits)), Some(Ok(particle_bits)), None) => {
+                PackedId::new(event_bits, particle_bits)
+                    .map(Some)
+                    .map_err(|_| io::ErrorKind::InvalidData.into())
+            }
+            _ => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    /// Checks the split recorded in the metadata of a property against this split.
+    ///
+    /// Metadata without a split is accepted.
+    /// Returns an error of kind `ErrorKind::PackedIdMismatch`,
+    /// with the property and both splits, if the splits differ.
+    pub fn check(&self, property_id: u16, metadata: &Metadata) -> io::Result<()> {
+        let file = PackedId::from_metadata(metadata)
+            .map_err(|err| Error::from_io(&err).with_property(property_id))?;
+        match file {
+            Some(file) if file != *self => Err(Error::new(ErrorKind::PackedIdMismatch)
+                .with_property(property_id)
+                .with_splits(file.particle_bits, self.particle_bits)
+                .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the items of particles in an event as one block,
+    /// starting at the particle index `first`.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if the event or a particle index is out of range.
+    pub fn write_event<T: Element, W: io::Write>(
+        &self,
+        property_id: u16,
+        event: u64,
+        first: u32,
+        items: &[T],
+        w: &mut W
+    ) -> io::Result<()> {
+        let (ty, size) = match T::format() {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        let offset = match self.pack(event, first) {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        if !items.is_empty() && (items.len() - 1) as u64 > (self.max_particle() - first) as u64 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut data = Vec::with_capacity(size as usize * items.len());
+        for item in items {item.write_element(&mut data)?}
+        RawBlock::write_property(ty, property_id, &[RawBlock {
+            type_format: ty,
+            property_id,
+            offset,
+            data,
+        }], w)
+    }
+
+    /// Groups the items of blocks by event.
+    ///
+    /// Yields the particle index and item of every instance, in the order of the blocks.
+    /// Presence bitmaps are skipped.
+    pub fn group_by_event<'a, T, I>(&self, blocks: I) -> io::Result<BTreeMap<u64, Vec<(u32, T)>>>
+        where T: Element, I: IntoIterator<Item = &'a RawBlock>
+    {
+        let mut res: BTreeMap<u64, Vec<(u32, T)>> = BTreeMap::new();
+        for block in blocks {
+            if block.type_format == PRESENCE_FORMAT {continue}
+            dyn_value::check_format::<T>(block.type_format)
+                .map_err(|err| Error::from_io(&err).with_property(block.property_id))?;
+            let mut r = &block.data[..];
+            let mut id = block.offset;
+            while !r.is_empty() {
+                let mut val: T = Default::default();
+                val.read_element(&mut r)?;
+                let (event, particle) = self.unpack(id);
+                res.entry(event).or_default().push((particle, val));
+                id = id.wrapping_add(1);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Reads a property and groups its items by event.
+    pub fn read_by_event<T: Element, R: io::Read>(
+        &self,
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<BTreeMap<u64, Vec<(u32, T)>>> {
+        let blocks = RawBlock::read_property(state, ty, property_id, r)?;
+        self.group_by_event(&blocks)
+    }
+}
@@ -0,0 +1,113 @@
+//! Processing events of an indexed stream in parallel.
+//!
+//! The events of an `EventIndex` are partitioned into contiguous chunks,
+//! and every chunk is decoded with its own reader on a thread of the rayon pool.
+//! Results are returned in the order of the index.
+
+use std::cmp;
+use std::fs::File;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use error::{Error, ErrorKind};
+use event::{self, Event, EventIndex};
+use frame::FrameReader;
+use index::FrameCursor;
+
+/// Maps a function over the events of a file in parallel.
+///
+/// Every chunk of events opens the file independently.
+/// See `par_events_with`.
+pub fn par_events<T, F, P>(index: &EventIndex, path: P, f: F) -> io::Result<Vec<T>>
+    where T: Send, F: Fn(Event) -> T + Sync, P: AsRef<Path>
+{
+    let path = path.as_ref();
+    par_events_with(index, || File::open(path).map(io::BufReader::new), f)
+}
+
+/// Maps a function over the events of a stream in parallel,
+/// returning the results in the order of the index.
+///
+/// The events are partitioned into contiguous chunks,
+/// and `open` is called once per chunk for an independent reader,
+/// e.g. a file handle or a cursor of a mapped buffer.
+///
+/// When events fail to decode, the error of the first failing event in the order of the index
+/// is returned, with its event number and the kind of the IO error.
+/// Chunks after a failing event stop early.
+pub fn par_events_with<T, F, R, O>(index: &EventIndex, open: O, f: F) -> io::Result<Vec<T>>
+    where T: Send,
+          F: Fn(Event) -> T + Sync,
+          R: io::Read + io::Seek,
+          O: Fn() -> io::Result<R> + Sync
+{
+    let n = index.events().len();
+    if n == 0 {return Ok(vec![])}
+    let chunks = cmp::min(n, 4 * rayon::current_num_threads().max(1));
+    let len = n.div_ceil(chunks);
+    let failed = AtomicUsize::new(usize::MAX);
+    let results: Vec<(Vec<T>, Option<io::Error>)> = (0..n.div_ceil(len))
+        .into_par_iter()
+        .map(|chunk| {
+            let start = chunk * len;
+            let end = cmp::min(start + len, n);
+            let mut items = Vec::with_capacity(end - start);
+            let mut current = start;
+            let res = read_chunk(index, start..end, &open, &failed, &mut current, |event| {
+                items.push(f(event))
+            });
+            let err = res.err().map(|err| {
+                failed.fetch_min(current, Ordering::Relaxed);
+                let kind = err.kind();
+                Error::from_io(&err).with_event(index.events()[current].number).into_io(kind)
+            });
+            (items, err)
+        })
+        .collect();
+    let mut res = Vec::with_capacity(n);
+    for (items, err) in results {
+        res.extend(items);
+        if let Some(err) = err {return Err(err)}
+    }
+    Ok(res)
+}
+
+/// Decodes a contiguous range of events sequentially with one reader.
+///
+/// Stops without error when an earlier event has failed.
+/// `current` is the index of the event being decoded.
+fn read_chunk<R, O, G>(
+    index: &EventIndex,
+    range: Range<usize>,
+    open: &O,
+    failed: &AtomicUsize,
+    current: &mut usize,
+    mut g: G
+) -> io::Result<()>
+    where R: io::Read + io::Seek, O: Fn() -> io::Result<R>, G: FnMut(Event)
+{
+    let start = index.event_range(range.start)?.start;
+    let end = index.event_range(range.end - 1)?.end;
+    let mut r = open()?;
+    r.seek(io::SeekFrom::Start(start))?;
+    let mut frames = FrameReader::new(io::Read::take(r, end - start), index.marker());
+    frames.resume(&FrameCursor {frame: range.start as u64, position: start, time: None})?;
+    for i in range {
+        *current = i;
+        if failed.load(Ordering::Relaxed) < i {return Ok(())}
+        let frame = match frames.next_frame()? {
+            Some(x) => x,
+            None => return Err(io::ErrorKind::InvalidData.into()),
+        };
+        let number = frame.blocks_of(index.marker()).next().and_then(event::event_number);
+        match number {
+            Some(number) if number == index.events()[i].number => g(Event {number, frame}),
+            _ => return Err(Error::new(ErrorKind::InvalidData).with_property(index.marker()).into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Exporting to Apache Parquet, enabled with the `parquet` feature.
+//!
+//! The table has the layout of the `arrow` module,
+//! with vectors and matrices stored as list columns.
+//! Every row group contains whole frames.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use apache_parquet::arrow::ArrowWriter;
+use apache_parquet::file::properties::{EnabledStatistics, WriterProperties};
+use arrow_array::{Array, RecordBatch, UInt64Array};
+
+use arrow::{export_arrow, ArrowOptions};
+
+pub use apache_parquet::basic::Compression;
+
+/// Options for exporting to Parquet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParquetOptions {
+    /// The number of frames per row group.
+    pub frames_per_row_group: u64,
+    /// The maximum number of rows converted at once.
+    pub batch_size: usize,
+    /// The compression codec of the columns.
+    pub compression: Compression,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> ParquetOptions {
+        ParquetOptions {
+            frames_per_row_group: 1000,
+            batch_size: ArrowOptions::default().batch_size,
+            compression: Compression::SNAPPY,
+        }
+    }
+}
+
+impl ParquetOptions {
+    /// Sets the number of frames per row group.
+    pub fn frames_per_row_group(mut self, val: u64) -> ParquetOptions {
+        self.frames_per_row_group = val;
+        self
+    }
+
+    /// Sets the maximum number of rows converted at once.
+    pub fn batch_size(mut self, val: usize) -> ParquetOptions {
+        self.batch_size = val;
+        self
+    }
+
+    /// Sets the compression codec.
+    pub fn compression(mut self, val: Compression) -> ParquetOptions {
+        self.compression = val;
+        self
+    }
+}
+
+/// Writes a stream to a Parquet file.
+///
+/// See `write_parquet`.
+pub fn export_parquet<R: io::Read + io::Seek, P: AsRef<Path>>(
+    r: &mut R,
+    path: P,
+    time_property: u16,
+    opts: &ParquetOptions
+) -> io::Result<()> {
+    write_parquet(r, File::create(path)?, time_property, opts)?;
+    Ok(())
+}
+
+/// Writes a stream as Parquet, one row group per `ParquetOptions::frames_per_row_group` frames.
+///
+/// The stream is converted like `export_arrow`, batch by batch,
+/// such that memory is bounded by one row group.
+/// Every column has statistics per row group and per page,
+/// such that readers can skip data by time, frame or instance.
+///
+/// Returns an error of kind `io::ErrorKind::InvalidInput`
+/// if the number of frames per row group or the batch size is 0.
+pub fn write_parquet<R: io::Read + io::Seek, W: io::Write + Send>(
+    r: &mut R,
+    w: W,
+    time_property: u16,
+    opts: &ParquetOptions
+) -> io::Result<W> {
+    if opts.frames_per_row_group == 0 {return Err(io::ErrorKind::InvalidInput.into())}
+    let batches = export_arrow(r, time_property, &ArrowOptions::default().batch_size(opts.batch_size))?;
+    let props = WriterProperties::builder()
+        .set_compression(opts.compression)
+        .set_statistics_enabled(EnabledStatistics::Page)
+        .set_max_row_group_size(usize::MAX)
+        .build();
+    let mut writer = ArrowWriter::try_new(w, batches.schema(), Some(props)).map_err(io::Error::other)?;
+    let mut group = 0;
+    for batch in batches {
+        let batch = batch?;
+        let frames = batch.column(0).as_any().downcast_ref::<UInt64Array>()
+            .ok_or(io::ErrorKind::InvalidData)?;
+        let mut start = 0;
+        for row in 0..batch.num_rows() {
+            let g = frames.value(row) / opts.frames_per_row_group;
+            if g != group {
+                write_slice(&mut writer, &batch, start, row)?;
+                writer.flush().map_err(io::Error::other)?;
+                group = g;
+                start = row;
+            }
+        }
+        write_slice(&mut writer, &batch, start, batch.num_rows())?;
+    }
+    writer.into_inner().map_err(io::Error::other)
+}
+
+/// Writes the rows `start..end` of a batch.
+fn write_slice<W: io::Write + Send>(
+    writer: &mut ArrowWriter<W>,
+    batch: &RecordBatch,
+    start: usize,
+    end: usize
+) -> io::Result<()> {
+    if start == end {return Ok(())}
+    writer.write(&batch.slice(start, end - start)).map_err(io::Error::other)
+}
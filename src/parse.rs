@@ -0,0 +1,164 @@
+//! Tracking the position within the format while bytes pass through.
+
+use error::Field;
+
+/// A complete header field seen by the parser.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// Type format and property id of a new property.
+    Property {
+        /// Type format.
+        type_format: u16,
+        /// Property id.
+        property_id: u16,
+    },
+    /// Number of bytes and offset instance id of a block.
+    Block {
+        /// Number of bytes.
+        bytes: u64,
+        /// Offset instance id.
+        offset: u64,
+    },
+    /// End of bytes of a property.
+    EndBytes,
+    /// End of stream.
+    End,
+}
+
+/// Follows the fields of a stream, one byte slice at a time.
+#[derive(Clone, Debug)]
+pub struct Parser {
+    pos: u64,
+    field: Field,
+    field_start: u64,
+    buf: [u8; 8],
+    filled: usize,
+    type_format: u16,
+    property: Option<u16>,
+    bytes: u64,
+    remaining: u64,
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+impl Parser {
+    /// Creates a new parser at the start of a stream.
+    pub fn new() -> Parser {
+        Parser {
+            pos: 0,
+            field: Field::TypeFormat,
+            field_start: 0,
+            buf: [0; 8],
+            filled: 0,
+            type_format: 0,
+            property: None,
+            bytes: 0,
+            remaining: 0,
+        }
+    }
+
+    /// Returns the number of bytes consumed.
+    pub fn position(&self) -> u64 {self.pos}
+
+    /// Returns the field that is currently read.
+    pub fn field(&self) -> Field {self.field}
+
+    /// Returns the position where the current field starts.
+    pub fn field_start(&self) -> u64 {self.field_start}
+
+    /// Returns the current property id.
+    pub fn property(&self) -> Option<u16> {self.property}
+
+    /// Returns the type format of the current property.
+    pub fn type_format(&self) -> Option<u16> {
+        self.property.map(|_| self.type_format)
+    }
+
+    /// Returns the number of data bytes left of the current block.
+    pub fn remaining(&self) -> u64 {
+        if self.field == Field::Data {self.remaining} else {0}
+    }
+
+    /// Returns `true` when the parser is between two properties.
+    pub fn at_property_boundary(&self) -> bool {
+        self.field == Field::TypeFormat && self.filled == 0
+    }
+
+    fn field_size(&self) -> usize {
+        match self.field {
+            Field::TypeFormat | Field::PropertyId => 2,
+            Field::Bytes | Field::OffsetInstanceId => 8,
+            Field::Data => 0,
+        }
+    }
+
+    fn begin(&mut self, field: Field) {
+        self.field = field;
+        self.field_start = self.pos;
+        self.filled = 0;
+    }
+
+    /// Consumes bytes, calling `f` for every complete header.
+    pub fn consume<F: FnMut(Event)>(&mut self, mut data: &[u8], mut f: F) {
+        while !data.is_empty() {
+            if self.field == Field::Data {
+                let n = if self.remaining < data.len() as u64 {self.remaining as usize}
+                    else {data.len()};
+                self.remaining -= n as u64;
+                self.pos += n as u64;
+                data = &data[n..];
+                if self.remaining == 0 {self.begin(Field::Bytes)}
+                continue;
+            }
+
+            let size = self.field_size();
+            let n = (size - self.filled).min(data.len());
+            self.buf[self.filled..self.filled + n].copy_from_slice(&data[..n]);
+            self.filled += n;
+            self.pos += n as u64;
+            data = &data[n..];
+            if self.filled < size {continue}
+
+            let mut val = [0; 8];
+            val[..size].copy_from_slice(&self.buf[..size]);
+            let val = u64::from_le_bytes(val);
+            match self.field {
+                Field::TypeFormat => {
+                    if val == 0 {
+                        self.property = None;
+                        self.begin(Field::TypeFormat);
+                        f(Event::End);
+                    } else {
+                        self.type_format = val as u16;
+                        self.begin(Field::PropertyId);
+                    }
+                }
+                Field::PropertyId => {
+                    self.property = Some(val as u16);
+                    self.begin(Field::Bytes);
+                    f(Event::Property {type_format: self.type_format, property_id: val as u16});
+                }
+                Field::Bytes => {
+                    if val == 0 {
+                        self.property = None;
+                        self.begin(Field::TypeFormat);
+                        f(Event::EndBytes);
+                    } else {
+                        self.bytes = val;
+                        self.begin(Field::OffsetInstanceId);
+                    }
+                }
+                Field::OffsetInstanceId => {
+                    self.remaining = self.bytes;
+                    self.begin(Field::Data);
+                    f(Event::Block {bytes: self.bytes, offset: val});
+                }
+                Field::Data => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Decoding frames in the background during playback.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use frame::{Frame, FrameReader};
+
+/// Reads frames on a background thread, ahead of playback.
+///
+/// The frame reader is created on the background thread,
+/// which then decodes frames while the current frame is displayed:
+///
+/// ```ignore
+/// let mut playback = PlaybackReader::new(2, move || FrameReader::with_time(file, TIME));
+/// while playback.advance()? {
+///     draw(playback.current_frame().unwrap());
+/// }
+/// ```
+///
+/// The background thread stops when the reader is dropped.
+pub struct PlaybackReader {
+    current: Option<Frame>,
+    frames: Option<Receiver<io::Result<Frame>>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PlaybackReader {
+    /// Starts reading frames on a background thread.
+    ///
+    /// At most `depth` decoded frames wait to be played,
+    /// in addition to the frame that is being decoded.
+    pub fn new<R, F>(depth: usize, f: F) -> PlaybackReader
+        where R: io::Read, F: FnOnce() -> FrameReader<R> + Send + 'static
+    {
+        let (tx, rx) = mpsc::sync_channel(depth);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut reader = f();
+            while !thread_stop.load(Ordering::SeqCst) {
+                let res = match reader.next_frame() {
+                    Ok(Some(frame)) => Ok(frame),
+                    Ok(None) => break,
+                    Err(err) => Err(err),
+                };
+                let last = res.is_err();
+                if tx.send(res).is_err() || last {break}
+            }
+        });
+        PlaybackReader {
+            current: None,
+            frames: Some(rx),
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the current frame.
+    ///
+    /// Returns `None` before the first call to `advance`
+    /// and after the last frame.
+    pub fn current_frame(&self) -> Option<&Frame> {
+        self.current.as_ref()
+    }
+
+    /// Moves to the next frame, waiting until it is decoded.
+    ///
+    /// Returns `false` after the last frame.
+    /// Errors from decoding on the background thread are returned here.
+    pub fn advance(&mut self) -> io::Result<bool> {
+        let next = match self.frames {
+            Some(ref frames) => frames.recv(),
+            None => return Ok(false),
+        };
+        match next {
+            Ok(Ok(frame)) => {
+                self.current = Some(frame);
+                Ok(true)
+            }
+            Ok(Err(err)) => {
+                self.current = None;
+                self.frames = None;
+                Err(err)
+            }
+            Err(_) => {
+                self.current = None;
+                self.frames = None;
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl Drop for PlaybackReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Unblocks the background thread when waiting to send a frame.
+        self.frames = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,433 @@
+//! Playing frames at the recorded rate.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use frame::{Frame, FrameReader};
+use index::Index;
+use ring::{self, Keyframe};
+
+/// A source of time for pacing playback.
+pub trait Clock {
+    /// Returns the time since some fixed point.
+    fn now(&self) -> Duration;
+    /// Waits for a duration.
+    fn sleep(&mut self, dur: Duration);
+}
+
+/// A clock using the system time.
+#[derive(Copy, Clone, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock {start: Instant::now()}
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&mut self, dur: Duration) {
+        thread::sleep(dur)
+    }
+}
+
+/// Delivers frames no earlier than their recorded time.
+///
+/// Frame `n` with time `t` is delivered `(t - t0) / speed` seconds
+/// after playback started, where `t0` is the time of the first frame.
+/// Time spent paused is not counted.
+///
+/// ```ignore
+/// let mut player = Player::new(file, TIME).speed(2.0);
+/// while let Some(frame) = player.next_frame()? {
+///     if player.lag() > Duration::from_millis(100) {
+///         println!("playback is behind");
+///     }
+///     draw(&frame);
+/// }
+/// ```
+///
+/// With an index, the player can seek and loop instead.
+/// `tick` returns the frame to display at the current playback time,
+/// without waiting:
+///
+/// ```ignore
+/// let index = Index::build(&mut file, TIME, Some(TIME))?;
+/// let mut player = Player::with_index(file, index).keyframes(100);
+/// player.set_loop(true);
+/// player.play();
+/// loop {
+///     if let Some(frame) = player.tick()? {
+///         draw(frame);
+///     }
+/// }
+/// ```
+pub struct Player<R, C = SystemClock> {
+    reader: FrameReader<R>,
+    clock: C,
+    speed: f64,
+    fps: Option<f64>,
+    start_time: Option<f64>,
+    started: Option<Duration>,
+    paused: Option<Duration>,
+    paused_total: Duration,
+    lag: Duration,
+    index: Option<Index>,
+    looping: bool,
+    keyframe_interval: u64,
+    state: BTreeMap<(u16, u16), Keyframe>,
+    current: Option<Frame>,
+}
+
+impl<R: io::Read> Player<R> {
+    /// Creates a new player, using a time property as frame marker.
+    pub fn new(r: R, time_property: u16) -> Player<R> {
+        Player::with_reader(FrameReader::with_time(r, time_property))
+    }
+
+    /// Creates a new player from a frame reader.
+    pub fn with_reader(reader: FrameReader<R>) -> Player<R> {
+        Player {
+            reader,
+            clock: SystemClock::default(),
+            speed: 1.0,
+            fps: None,
+            start_time: None,
+            started: None,
+            paused: None,
+            paused_total: Duration::from_secs(0),
+            lag: Duration::from_secs(0),
+            index: None,
+            looping: false,
+            keyframe_interval: 0,
+            state: BTreeMap::new(),
+            current: None,
+        }
+    }
+}
+
+impl<R: io::Read + io::Seek> Player<R> {
+    /// Creates a new player that can seek, using an index of the stream.
+    ///
+    /// The index gives the frame marker and the time property.
+    /// Playback starts paused at the first frame, until `play` is called.
+    pub fn with_index(r: R, index: Index) -> Player<R> {
+        let mut reader = FrameReader::new(r, index.marker());
+        if let Some(time_property) = index.time_property() {
+            reader = reader.time_property(time_property);
+        }
+        let mut player = Player::with_reader(reader);
+        player.start_time = index.frames().iter().find_map(|f| f.time).or(Some(0.0));
+        player.index = Some(index);
+        player
+    }
+}
+
+impl<R: io::Read, C: Clock> Player<R, C> {
+    /// Sets the clock used for pacing.
+    pub fn clock<D: Clock>(self, clock: D) -> Player<R, D> {
+        Player {
+            reader: self.reader,
+            clock,
+            speed: self.speed,
+            fps: self.fps,
+            start_time: self.start_time,
+            started: self.started,
+            paused: self.paused,
+            paused_total: self.paused_total,
+            lag: self.lag,
+            index: self.index,
+            looping: self.looping,
+            keyframe_interval: self.keyframe_interval,
+            state: self.state,
+            current: self.current,
+        }
+    }
+
+    /// Sets the playback speed, where `1.0` is the recorded rate.
+    ///
+    /// Non-positive speeds are ignored.
+    pub fn speed(mut self, speed: f64) -> Player<R, C> {
+        if speed > 0.0 {self.speed = speed}
+        self
+    }
+
+    /// Changes the playback speed, keeping the current playback time.
+    ///
+    /// Non-positive speeds are ignored.
+    pub fn set_speed(&mut self, speed: f64) {
+        if speed <= 0.0 {return}
+        if self.start_time.is_some() {
+            let t = self.position();
+            self.rebase(t);
+        }
+        self.speed = speed;
+    }
+
+    /// Sets the frame rate used for frames without time.
+    ///
+    /// Without a frame rate, frames without time are delivered immediately.
+    pub fn fps(mut self, fps: f64) -> Player<R, C> {
+        self.fps = if fps > 0.0 {Some(fps)} else {None};
+        self
+    }
+
+    /// Returns the frame reader.
+    pub fn reader(&self) -> &FrameReader<R> {
+        &self.reader
+    }
+
+    /// Returns how late the last frame was delivered.
+    pub fn lag(&self) -> Duration {
+        self.lag
+    }
+
+    /// Returns `true` if playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Pauses playback.
+    pub fn pause(&mut self) {
+        if self.paused.is_none() {
+            self.paused = Some(self.clock.now());
+        }
+    }
+
+    /// Resumes playback.
+    pub fn resume(&mut self) {
+        if let Some(paused) = self.paused.take() {
+            self.paused_total += self.clock.now().saturating_sub(paused);
+        }
+    }
+
+    /// Returns the current playback time, in the time of the frames.
+    pub fn position(&self) -> f64 {
+        let start_time = self.start_time.unwrap_or(0.0);
+        match self.started {
+            Some(started) => start_time + self.elapsed(started).as_secs_f64() * self.speed,
+            None => start_time,
+        }
+    }
+
+    /// Continues playback from time `t` at the current clock time.
+    fn rebase(&mut self, t: f64) {
+        self.start_time = Some(t);
+        if self.started.is_some() {
+            let now = self.clock.now();
+            self.started = Some(now);
+            self.paused_total = Duration::from_secs(0);
+            if self.paused.is_some() {self.paused = Some(now)}
+        }
+    }
+
+    /// Returns the playback time since playback started, not counting pauses.
+    fn elapsed(&self, started: Duration) -> Duration {
+        let now = self.paused.unwrap_or_else(|| self.clock.now());
+        now.saturating_sub(started).saturating_sub(self.paused_total)
+    }
+
+    /// Reads the next frame, waiting until it is due.
+    ///
+    /// Returns an error of kind `WouldBlock` while paused.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        if self.is_paused() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        let frame = match self.reader.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        let started = match self.started {
+            Some(started) => started,
+            None => {
+                let now = self.clock.now();
+                self.started = Some(now);
+                now
+            }
+        };
+        let t = match (frame.time, self.fps) {
+            (Some(t), _) => Some(t),
+            (None, Some(fps)) => Some(frame.number as f64 / fps),
+            (None, None) => None,
+        };
+        let due = t.and_then(|t| {
+            let start_time = *self.start_time.get_or_insert(t);
+            let secs = (t - start_time) / self.speed;
+            if secs.is_finite() && secs >= 0.0 {Some(Duration::from_secs_f64(secs))} else {None}
+        });
+        let elapsed = self.elapsed(started);
+        self.lag = Duration::from_secs(0);
+        if let Some(due) = due {
+            if due > elapsed {
+                self.clock.sleep(due - elapsed);
+            } else {
+                self.lag = elapsed - due;
+            }
+        }
+        Ok(Some(frame))
+    }
+}
+
+impl<R: io::Read + io::Seek, C: Clock> Player<R, C> {
+    /// Sets the interval of keyframes, where every property is written.
+    ///
+    /// Frames `0, interval, 2 * interval, ...` are keyframes,
+    /// like the frames forced by `DedupWriter::keyframes`.
+    /// Seeking backwards replays the frames from the nearest keyframe,
+    /// or from the first frame when the interval is 0, which is the default.
+    pub fn keyframes(mut self, interval: u64) -> Player<R, C> {
+        self.keyframe_interval = interval;
+        self
+    }
+
+    /// Starts or resumes playback.
+    pub fn play(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(self.clock.now());
+            self.paused = None;
+            self.paused_total = Duration::from_secs(0);
+        } else {
+            self.resume();
+        }
+    }
+
+    /// Sets whether playback starts over from the first frame after the last frame.
+    ///
+    /// The last frame is displayed for the average duration of a frame.
+    pub fn set_loop(&mut self, val: bool) {
+        self.looping = val;
+    }
+
+    /// Returns `true` if playback loops.
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Moves playback to time `t`, keeping playback paused or playing.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` if `t` is not finite.
+    pub fn seek_time(&mut self, t: f64) -> io::Result<()> {
+        if !t.is_finite() {return Err(io::ErrorKind::InvalidInput.into())}
+        self.rebase(t);
+        Ok(())
+    }
+
+    /// Moves playback to the time of frame `n`.
+    ///
+    /// Returns an error of kind `ErrorKind::FrameOutOfRange` when `n` is out of range,
+    /// or `io::ErrorKind::InvalidInput` without an index or when the frame has no time.
+    pub fn seek_frame(&mut self, n: u64) -> io::Result<()> {
+        let index = self.index.as_ref().ok_or(io::ErrorKind::InvalidInput)?;
+        index.frame_range(n)?;
+        let t = self.frame_time(n).ok_or(io::ErrorKind::InvalidInput)?;
+        self.seek_time(t)
+    }
+
+    /// Returns the last frame decoded by `tick`.
+    pub fn current_frame(&self) -> Option<&Frame> {
+        self.current.as_ref()
+    }
+
+    /// Returns the frame to display at the current playback time.
+    ///
+    /// This is the last frame with time less than or equal to the playback time,
+    /// or the first frame before it.
+    /// Frames without time are picked by their number when a frame rate is set,
+    /// and never picked otherwise.
+    ///
+    /// The blocks of the frame are merged with the frames before it,
+    /// such that every property has its value at the frame, also when written as changes.
+    /// The frame is decoded when it differs from the last frame returned.
+    /// Returns `None` if the stream has no frames,
+    /// or an error of kind `io::ErrorKind::InvalidInput` without an index.
+    pub fn tick(&mut self) -> io::Result<Option<&Frame>> {
+        let frame_count = self.index.as_ref().ok_or(io::ErrorKind::InvalidInput)?.frame_count();
+        let mut first = None;
+        let mut last = None;
+        let mut timed = 0;
+        for n in 0..frame_count {
+            if let Some(t) = self.frame_time(n) {
+                if first.is_none() {first = Some(t)}
+                last = Some(t);
+                timed += 1;
+            }
+        }
+        let first = match first {
+            Some(t) => t,
+            None if frame_count == 0 => return Ok(None),
+            None => {
+                self.decode(0)?;
+                return Ok(self.current.as_ref());
+            }
+        };
+        let mut t = self.position();
+        if self.looping && timed > 1 {
+            let last = last.unwrap_or(first);
+            let period = (last - first) * timed as f64 / (timed - 1) as f64;
+            if period > 0.0 && t >= first + period {
+                t = first + (t - first) % period;
+                self.rebase(t);
+            }
+        }
+        let mut pick = None;
+        for n in 0..frame_count {
+            match self.frame_time(n) {
+                Some(time) if time > t && pick.is_some() => break,
+                Some(_) => pick = Some(n),
+                None => {}
+            }
+        }
+        self.decode(pick.unwrap_or(0))?;
+        Ok(self.current.as_ref())
+    }
+
+    /// Returns the time of frame `n`, using the frame rate for frames without time.
+    fn frame_time(&self, n: u64) -> Option<f64> {
+        let entry = self.index.as_ref()?.frames().get(n as usize)?;
+        entry.time.or_else(|| self.fps.map(|fps| n as f64 / fps))
+    }
+
+    /// Decodes frame `n`, merged with the frames before it.
+    ///
+    /// Reading continues from the last frame decoded when possible,
+    /// otherwise it starts over from the nearest keyframe.
+    fn decode(&mut self, n: u64) -> io::Result<()> {
+        let keyframe = if self.keyframe_interval > 0 {n - n % self.keyframe_interval} else {0};
+        let next = self.reader.frames();
+        let cont = match self.current.take() {
+            Some(frame) if frame.number == n => {
+                self.current = Some(frame);
+                return Ok(());
+            }
+            Some(frame) => keyframe <= frame.number && frame.number < n && next == frame.number + 1,
+            None => false,
+        };
+        if !cont {
+            let index = self.index.as_ref().ok_or(io::ErrorKind::InvalidInput)?;
+            let cursor = index.seek_frame(keyframe, self.reader.get_mut())?;
+            self.reader.resume(&cursor)?;
+            self.state.clear();
+        }
+        loop {
+            let frame = self.reader.next_frame()?.ok_or(io::ErrorKind::UnexpectedEof)?;
+            ring::merge_blocks(&mut self.state, frame.blocks)?;
+            if frame.number == n {
+                self.current = Some(Frame {
+                    number: n,
+                    time: frame.time,
+                    blocks: ring::keyframe_blocks(&self.state),
+                });
+                return Ok(());
+            }
+        }
+    }
+}
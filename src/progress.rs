@@ -0,0 +1,187 @@
+//! Reporting progress of long reads.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use error::{Error, ErrorKind};
+use parse::{Event, Parser};
+
+/// The progress of reading a stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Progress {
+    /// The number of bytes processed.
+    pub bytes: u64,
+    /// The total number of bytes, if known.
+    pub total: Option<u64>,
+    /// The current property id.
+    pub property: Option<u16>,
+    /// The current frame number, if a frame marker is set.
+    pub frame: Option<u64>,
+}
+
+type Callback<'a> = Box<dyn FnMut(Progress) + 'a>;
+
+/// Wraps a reader and reports progress while reading.
+///
+/// Progress is reported at block boundaries
+/// and every time the configured number of bytes has been read.
+///
+/// Reading can be cancelled from another thread with a cancel token.
+/// The token is checked before every read from the underlying reader,
+/// which happens at least once per interval within large blocks.
+/// After cancelling, reads fail with an error of kind `ErrorKind::Cancelled`.
+/// Since this is a reader, it can be passed to any function that reads a stream,
+/// e.g. `downsample`:
+///
+/// ```ignore
+/// let total = file.metadata()?.len();
+/// let mut r = ProgressReader::new(BufReader::new(file))
+///     .total(total)
+///     .frame_marker(TIME)
+///     .on_progress(|p| println!("{} / {:?}", p.bytes, p.total));
+/// downsample(&mut r, &mut out, &opts)?;
+/// ```
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    parser: Parser,
+    on_progress: Option<Callback<'a>>,
+    interval: u64,
+    next_report: u64,
+    total: Option<u64>,
+    frame_marker: Option<u16>,
+    frame: Option<u64>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl<'a, R> ProgressReader<'a, R> {
+    /// Creates a new progress reader at the start of a stream.
+    ///
+    /// By default, progress is reported every MiB.
+    pub fn new(inner: R) -> ProgressReader<'a, R> {
+        ProgressReader {
+            inner,
+            parser: Parser::new(),
+            on_progress: None,
+            interval: 1 << 20,
+            next_report: 1 << 20,
+            total: None,
+            frame_marker: None,
+            frame: None,
+            cancel: None,
+        }
+    }
+
+    /// Sets the callback that receives progress.
+    pub fn on_progress<F: 'a + FnMut(Progress)>(mut self, f: F) -> ProgressReader<'a, R> {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    /// Sets the number of bytes between reports within large blocks.
+    pub fn interval(mut self, bytes: u64) -> ProgressReader<'a, R> {
+        self.interval = bytes.max(1);
+        self.next_report = self.parser.position() + self.interval;
+        self
+    }
+
+    /// Sets the total number of bytes, e.g. the file length.
+    pub fn total(mut self, bytes: u64) -> ProgressReader<'a, R> {
+        self.total = Some(bytes);
+        self
+    }
+
+    /// Sets the property id that marks the start of a new frame.
+    pub fn frame_marker(mut self, property_id: u16) -> ProgressReader<'a, R> {
+        self.frame_marker = Some(property_id);
+        self
+    }
+
+    /// Sets a token that cancels reading when set to `true`.
+    pub fn cancel_token(mut self, token: Arc<AtomicBool>) -> ProgressReader<'a, R> {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Returns `true` if reading was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().map(|t| t.load(Ordering::Relaxed)).unwrap_or(false)
+    }
+
+    /// Returns the current progress.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes: self.parser.position(),
+            total: self.total,
+            property: self.parser.property(),
+            frame: self.frame,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader is not tracked.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<'a, R: io::Read> io::Read for ProgressReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.parser.position();
+        if self.is_cancelled() {
+            return Err(Error::new(ErrorKind::Cancelled).with_position(pos).into());
+        }
+        let mut len = buf.len();
+        if self.on_progress.is_some() || self.cancel.is_some() {
+            // Stop at the next report, such that large blocks are reported in steps.
+            len = (self.next_report.saturating_sub(pos).max(1)).min(len as u64) as usize;
+        }
+        let n = self.inner.read(&mut buf[..len])?;
+        let bytes = pos + n as u64;
+
+        let ProgressReader {
+            ref mut parser,
+            ref mut on_progress,
+            ref mut frame,
+            frame_marker,
+            total,
+            ..
+        } = *self;
+        let mut property = parser.property();
+        let mut reported = false;
+        parser.consume(&buf[..n], |event| {
+            if let Event::Property {property_id, ..} = event {
+                property = Some(property_id);
+                if Some(property_id) == frame_marker {
+                    *frame = Some(frame.map(|n| n + 1).unwrap_or(0));
+                }
+            }
+            if let Some(ref mut f) = *on_progress {
+                reported = true;
+                f(Progress {bytes, total, property, frame: *frame});
+            }
+            if let Event::EndBytes | Event::End = event {
+                property = None;
+            }
+        });
+        if bytes >= self.next_report {
+            if !reported {
+                let progress = self.progress();
+                if let Some(ref mut f) = self.on_progress {f(progress)}
+            }
+            self.next_report = bytes + self.interval;
+        }
+        Ok(n)
+    }
+}
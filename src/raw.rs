@@ -0,0 +1,355 @@
+//! Reading and writing blocks without decoding the data.
+
+use std::io::{self, IoSlice, Read};
+use std::marker::PhantomData;
+
+use buffer_pool::BufferPool;
+use error::{Error, ErrorKind};
+use limits::Usage;
+use read_write::Element;
+use trace;
+use Bytes;
+use PropertyId;
+use State;
+use Type;
+
+/// A block of data that is not decoded.
+///
+/// A property can consist of multiple blocks,
+/// each starting at a different offset instance id.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RawBlock {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// Offset instance id.
+    pub offset: u64,
+    /// Data.
+    pub data: Vec<u8>,
+}
+
+impl RawBlock {
+    /// Returns the size of each item in bytes.
+    ///
+    /// Returns `None` for custom formats.
+    pub fn item_size(&self) -> Option<u64> {
+        item_size(self.type_format)
+    }
+
+    /// Returns the number of items in the data.
+    ///
+    /// Returns `None` for custom formats.
+    pub fn items(&self) -> Option<u64> {
+        self.item_size().map(|s| self.data.len() as u64 / s)
+    }
+
+    /// Decodes the data into elements.
+    ///
+    /// Returns an error if the type format does not match the element type.
+    pub fn decode<T: Element>(&self) -> io::Result<Vec<T>> {
+        let mut res = vec![];
+        self.decode_into(&mut res)?;
+        Ok(res)
+    }
+
+    /// Decodes the data into elements, appending them to a vector.
+    ///
+    /// Returns an error if the type format does not match the element type.
+    pub fn decode_into<T: Element>(&self, res: &mut Vec<T>) -> io::Result<()> {
+        let n = item_count::<T>(self.type_format, self.property_id, self.data.len())?;
+        let mut r = &self.data[..];
+        res.reserve(n);
+        for _ in 0..n {
+            let mut val: T = Default::default();
+            val.read_element(&mut r)?;
+            res.push(val);
+        }
+        Ok(())
+    }
+
+    /// Reads all blocks of a property, until the end of bytes.
+    pub fn read_property<R: io::Read>(
+        state: State<Bytes>,
+        type_format: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<Vec<RawBlock>> {
+        read_blocks(state, type_format, property_id, &mut Usage::default(), r)
+    }
+
+    /// Writes blocks as a single property.
+    ///
+    /// The type format and property id of the blocks are ignored.
+    /// Each block is written with one call to `write_vectored`,
+    /// together with the property header before the first block
+    /// and the end of bytes after the last block.
+    pub fn write_property<W: io::Write>(
+        type_format: u16,
+        property_id: u16,
+        blocks: &[RawBlock],
+        w: &mut W
+    ) -> io::Result<()> {
+        let blocks: Vec<(u64, &[u8])> = blocks.iter()
+            .map(|block| (block.offset, &block.data[..]))
+            .collect();
+        write_blocks(type_format, property_id, &blocks, w)
+    }
+}
+
+/// Writes a property with one block of data that is contiguous in memory.
+///
+/// The 20 bytes before the data, the data and the end of bytes
+/// are written with one call to `write_vectored`,
+/// which saves system calls and packets for files and sockets.
+/// Writers that do not override `write_vectored` get one call to `write` per part.
+/// Empty data is written as a property without blocks.
+pub fn write_block<W: io::Write>(
+    type_format: u16,
+    property_id: u16,
+    offset: u64,
+    data: &[u8],
+    w: &mut W
+) -> io::Result<()> {
+    write_blocks(type_format, property_id, &[(offset, data)], w)
+}
+
+/// The end of bytes.
+const END: [u8; 8] = [0; 8];
+
+/// Writes blocks of offset and data as a property, with one vectored write per block.
+fn write_blocks<W: io::Write>(
+    type_format: u16,
+    property_id: u16,
+    blocks: &[(u64, &[u8])],
+    w: &mut W
+) -> io::Result<()> {
+    let mut head = [0; 20];
+    head[..2].copy_from_slice(&type_format.to_le_bytes());
+    head[2..4].copy_from_slice(&property_id.to_le_bytes());
+    let mut n = 4;
+    // Empty blocks would be read as the end of bytes.
+    let last = match blocks.iter().rposition(|&(_, data)| !data.is_empty()) {
+        Some(last) => last,
+        None => return write_all_vectored(w, &mut [IoSlice::new(&head[..n]), IoSlice::new(&END)]),
+    };
+    for (i, &(offset, data)) in blocks.iter().enumerate() {
+        if data.is_empty() {continue}
+        head[n..n + 8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        head[n + 8..n + 16].copy_from_slice(&offset.to_le_bytes());
+        n += 16;
+        let end: &[u8] = if i == last {&END} else {&[]};
+        write_all_vectored(w, &mut [IoSlice::new(&head[..n]), IoSlice::new(data), IoSlice::new(end)])?;
+        n = 0;
+    }
+    Ok(())
+}
+
+/// Writes all buffers, calling `write_vectored` until everything is written.
+pub(crate) fn write_all_vectored<W: io::Write>(
+    w: &mut W,
+    mut bufs: &mut [IoSlice<'_>]
+) -> io::Result<()> {
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match w.write_vectored(bufs) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => trace::retry(),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Reads all blocks of a property, checking limits before reading the data.
+///
+/// The property header should already be counted.
+pub(crate) fn read_blocks<R: io::Read>(
+    state: State<Bytes>,
+    type_format: u16,
+    property_id: u16,
+    usage: &mut Usage,
+    r: &mut R
+) -> io::Result<Vec<RawBlock>> {
+    read_blocks_pooled(state, type_format, property_id, usage, None, r)
+}
+
+/// Reads all blocks of a property like `read_blocks`,
+/// taking the vector of blocks and the buffers of data from a pool.
+pub(crate) fn read_blocks_pooled<R: io::Read>(
+    state: State<Bytes>,
+    type_format: u16,
+    property_id: u16,
+    usage: &mut Usage,
+    pool: Option<&BufferPool>,
+    r: &mut R
+) -> io::Result<Vec<RawBlock>> {
+    let mut blocks = match pool {
+        Some(pool) => pool.take_vec(1),
+        None => vec![],
+    };
+    let mut state = state;
+    loop {
+        let (header, data_state) = state.read_block_header(type_format, property_id, r)?;
+        if header.is_end() {break}
+        let _span = trace::block(property_id, type_format, header.bytes, header.offset).entered();
+        usage.block(header.bytes, header.offset)?;
+        let mut data = match pool {
+            Some(pool) => pool.take(header.bytes.min(usize::MAX as u64) as usize),
+            None => vec![],
+        };
+        r.by_ref().take(header.bytes).read_to_end(&mut data)?;
+        if (data.len() as u64) < header.bytes {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        blocks.push(RawBlock {type_format, property_id, offset: header.offset, data});
+        state = data_state.end_data();
+    }
+    Ok(blocks)
+}
+
+/// The size of the chunks that data is copied in.
+const CHUNK: u64 = 64 << 10;
+
+/// Reads until the buffer is full, returning the number of bytes read.
+fn read_full<R: io::Read>(r: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(m) => n += m,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => trace::retry(),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(n)
+}
+
+/// Copies `bytes` bytes of data from a reader to a writer, in chunks of bounded size.
+///
+/// Returns the number of bytes copied, which is `bytes`,
+/// or an error of kind `io::ErrorKind::UnexpectedEof` if the reader ends before.
+pub fn copy_payload<R: io::Read, W: io::Write>(bytes: u64, r: &mut R, w: &mut W) -> io::Result<u64> {
+    let mut buf = vec![0; bytes.min(CHUNK) as usize];
+    let mut left = bytes;
+    while left > 0 {
+        let n = left.min(CHUNK) as usize;
+        if read_full(r, &mut buf[..n])? < n {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        w.write_all(&buf[..n])?;
+        left -= n as u64;
+    }
+    Ok(bytes)
+}
+
+/// Copies the blocks of a property verbatim, without decoding the data,
+/// after the property header has been read.
+///
+/// The property is written with the type format and property id,
+/// which can differ from the ones read, e.g. to remap property ids.
+/// Returns the number of bytes written, including the property header and end of bytes.
+///
+/// The data is copied in chunks of bounded size.
+/// A block header is written together with the first chunk of its data,
+/// such that when reading fails before, the block header is not written.
+/// The property header is written with the first block header.
+///
+/// Since blocks are not buffered as a whole, chunks that are written are not taken back:
+/// when reading fails after the first chunk of a block, the writer is left with
+/// a property that ends inside that block.
+/// A stream left like this can be cut after its last complete property with `recover`.
+/// To write all of a property or nothing, copy it to a buffer first.
+pub fn copy_property<R: io::Read, W: io::Write>(
+    state: State<Bytes>,
+    type_format: u16,
+    property_id: u16,
+    r: &mut R,
+    w: &mut W
+) -> io::Result<u64> {
+    let mut head = [0; 20];
+    head[..2].copy_from_slice(&type_format.to_le_bytes());
+    head[2..4].copy_from_slice(&property_id.to_le_bytes());
+    let mut n = 4;
+    let mut written = 0;
+    let mut buf = vec![];
+    let mut state = state;
+    loop {
+        let (header, data_state) = state.read_block_header(type_format, property_id, r)?;
+        if header.is_end() {
+            write_all_vectored(w, &mut [IoSlice::new(&head[..n]), IoSlice::new(&END)])?;
+            return Ok(written + n as u64 + END.len() as u64);
+        }
+        let first = header.bytes.min(CHUNK) as usize;
+        buf.resize(first, 0);
+        if read_full(r, &mut buf)? < first {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head[n..n + 8].copy_from_slice(&header.bytes.to_le_bytes());
+        head[n + 8..n + 16].copy_from_slice(&header.offset.to_le_bytes());
+        n += 16;
+        write_all_vectored(w, &mut [IoSlice::new(&head[..n]), IoSlice::new(&buf)])?;
+        written += n as u64 + copy_payload(header.bytes - first as u64, r, w)? + first as u64;
+        n = 0;
+        state = data_state.end_data();
+    }
+}
+
+/// Returns the number of items of type `T` in data of a type format.
+///
+/// Returns an error if the type format does not match the element type
+/// or the data is not a whole number of items.
+pub(crate) fn item_count<T: Element>(type_format: u16, property_id: u16, len: usize) -> io::Result<usize> {
+    let size = match T::format() {
+        Some((ty, size)) if ty == type_format => size as usize,
+        expected => {
+            trace::type_mismatch(Some(property_id), type_format, expected.map(|(ty, _)| ty));
+            let err = Error::new(ErrorKind::TypeMismatch).with_property(property_id);
+            return Err(match expected.and_then(|(ty, _)| Type::info(ty)) {
+                Some(expected) => err.with_types(expected, type_format),
+                None => err,
+            }.into());
+        }
+    };
+    if !len.is_multiple_of(size) {
+        return Err(Error::new(ErrorKind::BytesNotMultiple)
+            .with_property(property_id)
+            .with_item_size(len as u64, size as u64)
+            .into());
+    }
+    Ok(len / size)
+}
+
+/// Returns the size of each item in bytes for a type format.
+///
+/// Returns `None` for custom formats.
+pub fn item_size(type_format: u16) -> Option<u64> {
+    Type::info(type_format).map(|(ty, rows, cols)| ty.type_size() * rows as u64 * cols as u64)
+}
+
+/// Reads type format and property.
+///
+/// Unlike `State::read`, the end of the underlying reader
+/// is treated as the end of the stream, as long as it happens before a new property.
+pub fn read_property_header<R: io::Read>(
+    r: &mut R
+) -> io::Result<Option<(State<Bytes>, u16, u16)>> {
+    let mut buf = [0; 2];
+    let mut n = 0;
+    while n < 2 {
+        match r.read(&mut buf[n..]) {
+            Ok(0) if n == 0 => return Ok(None),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(m) => n += m,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => trace::retry(),
+            Err(err) => return Err(err),
+        }
+    }
+    let ty = u16::from_le_bytes(buf);
+    if ty == 0 {return Ok(None)}
+    let mut property_id = 0;
+    let state: State<PropertyId> = State(PhantomData, None);
+    let state = state.read_property_id(&mut property_id, r)?;
+    Ok(Some((state, ty, property_id)))
+}
@@ -1,9 +1,167 @@
 use std::io;
 
+use codec::Value;
+use error::{Error, ErrorKind, Field};
+use trace;
 use Bytes;
+use Data;
 use State;
 use Type;
 
+/// Determines what happens to items already in the target array.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Keep items, overwriting those covered by the property.
+    ///
+    /// This is used to apply changes to previous data.
+    #[default]
+    Merge,
+    /// Clear the array once the first item of the property has been read,
+    /// such that it ends with the last instance covered by the property.
+    /// When the property can not be read at all, the array is kept.
+    ///
+    /// Instances before the offset of the property get the default value.
+    /// This is used to reuse an array for data that is not incremental.
+    /// Arrays that do not implement `Array::truncate` are merged into.
+    Replace,
+}
+
+/// Options for reading arrays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadOptions {
+    /// Added to the offset instance id of every block.
+    ///
+    /// This is used to load several streams into the same array.
+    pub instance_offset: u64,
+    /// What happens to items already in the target array.
+    pub mode: ReadMode,
+    /// Read vectors from blocks typed as Nx1 matrices,
+    /// and single row or column matrices from blocks typed the other way.
+    ///
+    /// The data of a 1xN and an Nx1 matrix is the same.
+    pub accept_transposed_vectors: bool,
+}
+
+impl ReadOptions {
+    /// Sets what happens to items already in the target array.
+    pub fn mode(mut self, mode: ReadMode) -> ReadOptions {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the offset added to the offset instance id of every block.
+    pub fn instance_offset(mut self, val: u64) -> ReadOptions {
+        self.instance_offset = val;
+        self
+    }
+
+    /// Sets whether to read vectors from blocks typed as transposed vectors.
+    pub fn accept_transposed_vectors(mut self, val: bool) -> ReadOptions {
+        self.accept_transposed_vectors = val;
+        self
+    }
+}
+
+/// Returns `true` if a block with `rows` and `cols` can be read into dimensions `dim`.
+fn accepts_dim(rows: u8, cols: u8, dim: [usize; 2], opts: &ReadOptions) -> bool {
+    let (rows, cols) = (rows as usize, cols as usize);
+    (rows == dim[0] && cols == dim[1]) ||
+    (opts.accept_transposed_vectors && (dim[0] == 1 || dim[1] == 1) &&
+     rows == dim[1] && cols == dim[0])
+}
+
+/// Returns the type format of elements `T` when a block with `type_format`
+/// can be read into them as transposed vectors, or `type_format` otherwise.
+pub(crate) fn transposed_format<T: Element>(type_format: u16, opts: &ReadOptions) -> u16 {
+    if !opts.accept_transposed_vectors {return type_format}
+    match (Type::info(type_format), T::format()) {
+        (Some((ty, rows, cols)), Some((format, _))) if rows == 1 || cols == 1 => {
+            if Type::format_for(ty, cols, rows) == Some(format) {format} else {type_format}
+        }
+        _ => type_format,
+    }
+}
+
+/// Checks that a type format can be read into values of type `expected` with dimensions `dim`.
+///
+/// The dimensions must be supported by the built-in formats.
+fn check_type(
+    property_id: Option<u16>,
+    format: u16,
+    expected: Type,
+    dim: [usize; 2],
+    opts: &ReadOptions
+) -> io::Result<()> {
+    match Type::info(format) {
+        Some((ty, rows, cols)) if ty == expected && accepts_dim(rows, cols, dim, opts) => Ok(()),
+        _ => {
+            let expected_format = Type::format_for(expected, dim[0] as u8, dim[1] as u8);
+            trace::type_mismatch(property_id, format, expected_format);
+            Err(error(ErrorKind::TypeMismatch, property_id)
+                .with_types((expected, dim[0] as u8, dim[1] as u8), format)
+                .into())
+        }
+    }
+}
+
+/// Returns the number of items in a block.
+fn item_count(property_id: Option<u16>, bytes: u64, item_size: u64) -> io::Result<u64> {
+    if !bytes.is_multiple_of(item_size) {
+        return Err(error(ErrorKind::BytesNotMultiple, property_id)
+            .with_item_size(bytes, item_size)
+            .into());
+    }
+    Ok(bytes / item_size)
+}
+
+/// Creates an error with the property id, when it is known.
+fn error(kind: ErrorKind, property_id: Option<u16>) -> Error {
+    let err = Error::new(kind);
+    match property_id {
+        Some(id) => err.with_property(id),
+        None => err,
+    }
+}
+
+/// Checks that a block holds a single item at offset zero.
+fn check_single<R: io::Read>(
+    state: State<Bytes>,
+    item_size: u64,
+    r: &mut R
+) -> io::Result<State<Data>> {
+    let mut bytes = 0;
+    let state = state.read_bytes(&mut bytes, r)?;
+    let property_id = state.property_id();
+    if item_count(property_id, bytes, item_size)? != 1 {
+        return Err(error(ErrorKind::InvalidData, property_id).with_field(Field::Bytes).into());
+    }
+    let mut offset = 0;
+    let state = state.read_offset_instance_id(&mut offset, r)?;
+    if offset != 0 {
+        return Err(error(ErrorKind::InvalidData, property_id)
+            .with_field(Field::OffsetInstanceId)
+            .into());
+    }
+    Ok(state)
+}
+
+/// Returns the range of instances of a block, after adding the instance offset.
+fn instance_range(offset: u64, n: u64, opts: &ReadOptions) -> io::Result<(u64, u64)> {
+    let offset = offset.checked_add(opts.instance_offset);
+    match offset.and_then(|offset| offset.checked_add(n).map(|end| (offset, end))) {
+        Some((offset, end)) if end <= usize::MAX as u64 => Ok((offset, end)),
+        _ => Err(Error::new(ErrorKind::InvalidData).with_field(Field::OffsetInstanceId).into()),
+    }
+}
+
+/// Clears the array with `ReadMode::Replace`.
+///
+/// This is called once the first item of a property has been read,
+/// such that the array is kept when the property can not be read at all.
+fn replace<A: Array>(arr: &mut A, opts: &ReadOptions) {
+    if opts.mode == ReadMode::Replace {arr.truncate(0)}
+}
+
 /// Implemented by array types.
 pub trait Array {
     /// The type of item.
@@ -17,6 +175,43 @@ pub trait Array {
     fn set(&mut self, ind: usize, val: Self::Item);
     /// Push new item at the end of array.
     fn push(&mut self, val: Self::Item);
+
+    /// Shortens the array to `len` items.
+    ///
+    /// Does nothing by default, such that arrays that can not be shortened
+    /// are read with `ReadMode::Replace` as with `ReadMode::Merge`.
+    fn truncate(&mut self, _len: usize) {}
+
+    /// Returns `true` if the array has no items.
+    fn is_empty(&self) -> bool {self.len() == 0}
+
+    /// Reserves capacity for at least `additional` more items.
+    ///
+    /// Does nothing by default.
+    fn reserve(&mut self, _additional: usize) {}
+
+    /// Tries to reserve capacity for at least `additional` more items.
+    ///
+    /// Returns `false` if the memory can not be allocated.
+    /// Calls `reserve` and returns `true` by default.
+    fn try_reserve(&mut self, additional: usize) -> bool {
+        self.reserve(additional);
+        true
+    }
+
+    /// Resizes the array to `new_len` items, adding default items at the end.
+    ///
+    /// Shorter lengths are passed to `truncate`.
+    fn resize_with_default(&mut self, new_len: usize) where Self::Item: Default {
+        if new_len <= self.len() {
+            self.truncate(new_len);
+            return;
+        }
+        self.reserve(new_len - self.len());
+        while self.len() < new_len {
+            self.push(Default::default());
+        }
+    }
 }
 
 impl<T> Array for Vec<T> {
@@ -28,6 +223,54 @@ impl<T> Array for Vec<T> {
     fn push(&mut self, val: T) {
         Vec::push(self, val)
     }
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+    fn try_reserve(&mut self, additional: usize) -> bool {
+        Vec::try_reserve(self, additional).is_ok()
+    }
+    fn resize_with_default(&mut self, new_len: usize) where T: Default {
+        self.resize_with(new_len, Default::default)
+    }
+}
+
+/// Makes room for item `i` of items up to `end`, adding default items.
+///
+/// The array grows at most 16 MB of items ahead of `i`,
+/// such that a block header claiming more data than there is
+/// does not allocate memory for all of it.
+/// Returns an error for the offset instance id when the memory can not be allocated,
+/// e.g. for an offset near the end of the address space.
+pub(crate) fn make_room<A>(arr: &mut A, i: usize, end: usize) -> io::Result<()>
+    where A: Array, A::Item: Default
+{
+    if i < arr.len() {return Ok(())}
+    let step = (1 << 24) / ::std::mem::size_of::<A::Item>().max(1);
+    let len = end.min(i.saturating_add(step)).max(i + 1);
+    if !arr.try_reserve(len - arr.len()) {
+        return Err(Error::new(ErrorKind::InvalidData).with_field(Field::OffsetInstanceId).into());
+    }
+    arr.resize_with_default(len);
+    Ok(())
+}
+
+/// Returns the type format and size in bytes of a matrix with scalars of type `T`.
+///
+/// Returns an error if the dimensions are not supported by the built-in formats.
+fn dimension_format<T: Scalar>(rows: usize, cols: usize) -> io::Result<(u16, u64)> {
+    let max = Type::max_matrix_dim() as usize;
+    let format = if rows <= max && cols <= max {
+        T::ty().matrix(rows as u8, cols as u8)
+    } else {
+        None
+    };
+    format.ok_or_else(|| Error::new(ErrorKind::DimensionOutOfRange)
+        .with_dimensions(rows as u64, cols as u64)
+        .with_range(1, max as u64 + 1)
+        .into())
 }
 
 /// Implemented by matrix types.
@@ -45,7 +288,7 @@ pub trait Matrix: Sized + Default {
     /// Writes property.
     fn write_property<W: io::Write>(&self, property_id: u16, w: &mut W) -> io::Result<()> {
         let dim = <Self as Matrix>::dim();
-        let (ty, s) = <Self::Scalar as Scalar>::ty().matrix(dim[0] as u8, dim[1] as u8).unwrap();
+        let (ty, s) = dimension_format::<Self::Scalar>(dim[0], dim[1])?;
         let state = State::new()
             .write_type_format(ty, w)?
             .write_property_id(property_id, w)?
@@ -68,7 +311,7 @@ pub trait Matrix: Sized + Default {
     ) -> io::Result<()> {
         let dim = <Self as Matrix>::dim();
         let n = arr.len();
-        let (ty, s) = <Self::Scalar as Scalar>::ty().matrix(dim[0] as u8, dim[1] as u8).unwrap();
+        let (ty, s) = dimension_format::<Self::Scalar>(dim[0], dim[1])?;
         let state = State::new()
             .write_type_format(ty, w)?
             .write_property_id(property_id, w)?
@@ -94,30 +337,18 @@ pub trait Matrix: Sized + Default {
         r: &mut R
     ) -> io::Result<()> {
         let dim = Self::dim();
-        let self_ty = Self::Scalar::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == dim[0] as u8 && cols == dim[1] as u8 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.matrix(dim[0] as u8, dim[1] as u8).unwrap();
-                if bytes == scalar_bytes {
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    if offset == 0 {
-                        for i in 0..dim[0] {
-                            for j in 0..dim[1] {
-                                let mut scalar: Self::Scalar = Default::default();
-                                scalar.read(r)?;
-                                self.set(i, j, scalar);
-                            }
-                        }
-                        state.end_data().has_end_bytes(r)?;
-                        return Ok(())
-                    }
-                }
+        let (_, scalar_bytes) = dimension_format::<Self::Scalar>(dim[0], dim[1])?;
+        check_type(state.property_id(), ty, Self::Scalar::ty(), dim, &ReadOptions::default())?;
+        let state = check_single(state, scalar_bytes, r)?;
+        for i in 0..dim[0] {
+            for j in 0..dim[1] {
+                let mut scalar: Self::Scalar = Default::default();
+                scalar.read(r)?;
+                self.set(i, j, scalar);
             }
         }
-        return Err(io::ErrorKind::InvalidData.into())
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 
     /// Reads array.
@@ -127,42 +358,41 @@ pub trait Matrix: Sized + Default {
         arr: &mut A,
         r: &mut R
     ) -> io::Result<()> {
-        use std::usize;
+        Self::read_array_with(state, ty, arr, &ReadOptions::default(), r)
+    }
 
+    /// Reads array with options.
+    fn read_array_with<R: io::Read, A: Array<Item = Self>>(
+        state: State<Bytes>,
+        ty: u16,
+        arr: &mut A,
+        opts: &ReadOptions,
+        r: &mut R
+    ) -> io::Result<()> {
         let dim = <Self as Matrix>::dim();
-        let self_ty = <Self::Scalar as Scalar>::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == dim[0] as u8 && cols == dim[1] as u8 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.matrix(dim[0] as u8, dim[1] as u8).unwrap();
-                if bytes % scalar_bytes == 0 {
-                    let n = bytes / scalar_bytes;
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    for i in offset..(offset + n) {
-                        if i > usize::MAX as u64 {
-                            return Err(io::ErrorKind::Other.into());
-                        }
-                        while i as usize >= arr.len() {
-                            arr.push(Default::default());
-                        }
-                        let mut vector: Self = Default::default();
-                        for i in 0..dim[0] {
-                            for j in 0..dim[1] {
-                                let mut scalar: Self::Scalar = Default::default();
-                                scalar.read(r)?;
-                                vector.set(i, j, scalar);
-                            }
-                        }
-                        arr.set(i as usize, vector);
-                    }
-                    state.end_data().has_end_bytes(r)?;
-                    return Ok(())
+        let (_, scalar_bytes) = dimension_format::<Self::Scalar>(dim[0], dim[1])?;
+        check_type(state.property_id(), ty, <Self::Scalar as Scalar>::ty(), dim, opts)?;
+        let mut bytes = 0;
+        let state = state.read_bytes(&mut bytes, r)?;
+        let n = item_count(state.property_id(), bytes, scalar_bytes)?;
+        let mut offset = 0;
+        let state = state.read_offset_instance_id(&mut offset, r)?;
+        let (offset, end) = instance_range(offset, n, opts)?;
+        for i in offset..end {
+            let mut vector: Self = Default::default();
+            for i in 0..dim[0] {
+                for j in 0..dim[1] {
+                    let mut scalar: Self::Scalar = Default::default();
+                    scalar.read(r)?;
+                    vector.set(i, j, scalar);
                 }
             }
+            if i == offset {replace(arr, opts)}
+            make_room(arr, i as usize, end as usize)?;
+            arr.set(i as usize, vector);
         }
-        return Err(io::ErrorKind::InvalidData.into())
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 }
 
@@ -281,7 +511,7 @@ pub trait Vector: Sized + Default {
     /// Writes property.
     fn write_property<W: io::Write>(&self, property_id: u16, w: &mut W) -> io::Result<()> {
         let dim = <Self as Vector>::dim();
-        let (ty, s) = <Self::Scalar as Scalar>::ty().vector(dim as u8).unwrap();
+        let (ty, s) = dimension_format::<Self::Scalar>(1, dim)?;
         let state = State::new()
             .write_type_format(ty, w)?
             .write_property_id(property_id, w)?
@@ -302,7 +532,7 @@ pub trait Vector: Sized + Default {
     ) -> io::Result<()> {
         let dim = <Self as Vector>::dim();
         let n = arr.len();
-        let (ty, s) = <Self::Scalar as Scalar>::ty().vector(dim as u8).unwrap();
+        let (ty, s) = dimension_format::<Self::Scalar>(1, dim)?;
         let state = State::new()
             .write_type_format(ty, w)?
             .write_property_id(property_id, w)?
@@ -326,28 +556,16 @@ pub trait Vector: Sized + Default {
         r: &mut R
     ) -> io::Result<()> {
         let dim = Self::dim();
-        let self_ty = Self::Scalar::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == 1 && cols == dim as u8 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.vector(dim as u8).unwrap();
-                if bytes == scalar_bytes {
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    if offset == 0 {
-                        for i in 0..dim {
-                            let mut scalar: Self::Scalar = Default::default();
-                            scalar.read(r)?;
-                            self.set(i, scalar);
-                        }
-                        state.end_data().has_end_bytes(r)?;
-                        return Ok(())
-                    }
-                }
-            }
+        let (_, scalar_bytes) = dimension_format::<Self::Scalar>(1, dim)?;
+        check_type(state.property_id(), ty, Self::Scalar::ty(), [1, dim], &ReadOptions::default())?;
+        let state = check_single(state, scalar_bytes, r)?;
+        for i in 0..dim {
+            let mut scalar: Self::Scalar = Default::default();
+            scalar.read(r)?;
+            self.set(i, scalar);
         }
-        return Err(io::ErrorKind::InvalidData.into())
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 
     /// Reads array.
@@ -357,40 +575,110 @@ pub trait Vector: Sized + Default {
         arr: &mut A,
         r: &mut R
     ) -> io::Result<()> {
-        use std::usize;
+        Self::read_array_with(state, ty, arr, &ReadOptions::default(), r)
+    }
 
+    /// Reads array with options.
+    fn read_array_with<R: io::Read, A: Array<Item = Self>>(
+        state: State<Bytes>,
+        ty: u16,
+        arr: &mut A,
+        opts: &ReadOptions,
+        r: &mut R
+    ) -> io::Result<()> {
         let dim = <Self as Vector>::dim();
-        let self_ty = <Self::Scalar as Scalar>::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == 1 && cols == dim as u8 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.vector(dim as u8).unwrap();
-                if bytes % scalar_bytes == 0 {
-                    let n = bytes / scalar_bytes;
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    for i in offset..(offset + n) {
-                        if i > usize::MAX as u64 {
-                            return Err(io::ErrorKind::Other.into());
-                        }
-                        while i as usize >= arr.len() {
-                            arr.push(Default::default());
-                        }
-                        let mut vector: Self = Default::default();
-                        for i in 0..dim {
-                            let mut scalar: Self::Scalar = Default::default();
-                            scalar.read(r)?;
-                            vector.set(i, scalar);
-                        }
-                        arr.set(i as usize, vector);
-                    }
-                    state.end_data().has_end_bytes(r)?;
-                    return Ok(())
-                }
+        let (_, scalar_bytes) = dimension_format::<Self::Scalar>(1, dim)?;
+        check_type(state.property_id(), ty, <Self::Scalar as Scalar>::ty(), [1, dim], opts)?;
+        let mut bytes = 0;
+        let state = state.read_bytes(&mut bytes, r)?;
+        let n = item_count(state.property_id(), bytes, scalar_bytes)?;
+        let mut offset = 0;
+        let state = state.read_offset_instance_id(&mut offset, r)?;
+        let (offset, end) = instance_range(offset, n, opts)?;
+        for i in offset..end {
+            let mut vector: Self = Default::default();
+            for i in 0..dim {
+                let mut scalar: Self::Scalar = Default::default();
+                scalar.read(r)?;
+                vector.set(i, scalar);
+            }
+            if i == offset {replace(arr, opts)}
+            make_room(arr, i as usize, end as usize)?;
+            arr.set(i as usize, vector);
+        }
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
+    }
+
+    /// Reads array from a block of scalars, grouping consecutive scalars into vectors.
+    ///
+    /// The offset instance id counts scalars and must be a multiple of the dimension.
+    /// This is used to read data written as a flat array of scalars.
+    fn read_array_flat<R: io::Read, A: Array<Item = Self>>(
+        state: State<Bytes>,
+        ty: u16,
+        arr: &mut A,
+        r: &mut R
+    ) -> io::Result<()> {
+        let dim = <Self as Vector>::dim();
+        let (_, vector_bytes) = dimension_format::<Self::Scalar>(1, dim)?;
+        check_type(state.property_id(), ty, <Self::Scalar as Scalar>::ty(), [1, 1], &ReadOptions::default())?;
+        let mut bytes = 0;
+        let state = state.read_bytes(&mut bytes, r)?;
+        let n = item_count(state.property_id(), bytes, vector_bytes)?;
+        let mut offset = 0;
+        let state = state.read_offset_instance_id(&mut offset, r)?;
+        if offset % dim as u64 != 0 {
+            return Err(Error::new(ErrorKind::InvalidData)
+                .with_field(Field::OffsetInstanceId)
+                .into());
+        }
+        let (offset, end) = instance_range(offset / dim as u64, n, &ReadOptions::default())?;
+        for i in offset..end {
+            let mut vector: Self = Default::default();
+            for i in 0..dim {
+                let mut scalar: Self::Scalar = Default::default();
+                scalar.read(r)?;
+                vector.set(i, scalar);
             }
+            make_room(arr, i as usize, end as usize)?;
+            arr.set(i as usize, vector);
         }
-        return Err(io::ErrorKind::InvalidData.into())
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
+    }
+
+    /// Reads a block of vectors into a flat array of scalars.
+    ///
+    /// The offset instance id is multiplied by the dimension.
+    fn read_scalars<R: io::Read, A: Array<Item = Self::Scalar>>(
+        state: State<Bytes>,
+        ty: u16,
+        arr: &mut A,
+        r: &mut R
+    ) -> io::Result<()> {
+        let dim = <Self as Vector>::dim();
+        let (_, vector_bytes) = dimension_format::<Self::Scalar>(1, dim)?;
+        check_type(state.property_id(), ty, <Self::Scalar as Scalar>::ty(), [1, dim], &ReadOptions::default())?;
+        let mut bytes = 0;
+        let state = state.read_bytes(&mut bytes, r)?;
+        let n = item_count(state.property_id(), bytes, vector_bytes)? * dim as u64;
+        let mut offset = 0;
+        let state = state.read_offset_instance_id(&mut offset, r)?;
+        let end = match offset.checked_mul(dim as u64).and_then(|offset| offset.checked_add(n)) {
+            Some(end) if end <= usize::MAX as u64 => end,
+            _ => return Err(Error::new(ErrorKind::InvalidData)
+                .with_field(Field::OffsetInstanceId)
+                .into()),
+        };
+        for i in end - n..end {
+            let mut scalar: Self::Scalar = Default::default();
+            scalar.read(r)?;
+            make_room(arr, i as usize, end as usize)?;
+            arr.set(i as usize, scalar);
+        }
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 }
 
@@ -427,6 +715,97 @@ impl<T: Scalar> Vector for [T; 4] {
     fn set(&mut self, ind: usize, val: T) {self[ind] = val}
 }
 
+/// Implemented by scalar, vector and matrix types stored as items of arrays.
+pub trait Element: Sized + Default {
+    /// Scalar type.
+    type Scalar: Scalar;
+
+    /// Returns the number of rows and columns.
+    ///
+    /// Scalars have dimensions 1x1 and vectors 1xN.
+    fn dim() -> [usize; 2];
+    /// Writes the scalars of the element, row by row.
+    fn write_element<W: io::Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Reads the scalars of the element, row by row.
+    fn read_element<R: io::Read>(&mut self, r: &mut R) -> io::Result<()>;
+
+    /// Returns the type format and size in bytes of the element.
+    ///
+    /// Returns `None` if the dimensions are not supported.
+    fn format() -> Option<(u16, u64)> {
+        let dim = <Self as Element>::dim();
+        if dim[0] > 255 || dim[1] > 255 {return None}
+        <Self::Scalar as Scalar>::ty().matrix(dim[0] as u8, dim[1] as u8)
+    }
+}
+
+macro_rules! scalar_element {
+    ($($t:ty),*) => {$(
+        impl Element for $t {
+            type Scalar = $t;
+
+            #[inline]
+            fn dim() -> [usize; 2] {[1, 1]}
+            fn write_element<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+                self.write(w)?;
+                Ok(())
+            }
+            fn read_element<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+                self.read(r)?;
+                Ok(())
+            }
+        }
+    )*}
+}
+
+scalar_element!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+macro_rules! vector_element {
+    ($($n:expr),*) => {$(
+        impl<T: Scalar> Element for [T; $n] {
+            type Scalar = T;
+
+            #[inline]
+            fn dim() -> [usize; 2] {[1, $n]}
+            fn write_element<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+                for x in self {x.write(w)?;}
+                Ok(())
+            }
+            fn read_element<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+                for x in self {x.read(r)?;}
+                Ok(())
+            }
+        }
+    )*}
+}
+
+vector_element!(2, 3, 4);
+
+macro_rules! matrix_element {
+    ($($rows:expr, $cols:expr);*) => {$(
+        impl<T: Scalar> Element for [[T; $cols]; $rows] {
+            type Scalar = T;
+
+            #[inline]
+            fn dim() -> [usize; 2] {[$rows, $cols]}
+            fn write_element<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+                for row in self {
+                    for x in row {x.write(w)?;}
+                }
+                Ok(())
+            }
+            fn read_element<R: io::Read>(&mut self, r: &mut R) -> io::Result<()> {
+                for row in self {
+                    for x in row {x.read(r)?;}
+                }
+                Ok(())
+            }
+        }
+    )*}
+}
+
+matrix_element!(2, 2; 3, 2; 4, 2; 2, 3; 3, 3; 4, 3; 2, 4; 3, 4; 4, 4);
+
 /// Implemented by scalar values.
 pub trait Scalar: Sized + Default {
     /// Type of scalar.
@@ -473,23 +852,11 @@ pub trait Scalar: Sized + Default {
     /// Reads property.
     fn read_property<R: io::Read>(&mut self, state: State<Bytes>, ty: u16, r: &mut R) -> io::Result<()> {
         let self_ty = <Self as Scalar>::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == 1 && cols == 1 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.scalar();
-                if bytes == scalar_bytes {
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    if offset == 0 {
-                        self.read(r)?;
-                        state.end_data().has_end_bytes(r)?;
-                        return Ok(())
-                    }
-                }
-            }
-        }
-        return Err(io::ErrorKind::InvalidData.into())
+        check_type(state.property_id(), ty, self_ty, [1, 1], &ReadOptions::default())?;
+        let state = check_single(state, self_ty.type_size(), r)?;
+        self.read(r)?;
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 
     /// Reads array.
@@ -499,35 +866,34 @@ pub trait Scalar: Sized + Default {
         arr: &mut A,
         r: &mut R
     ) -> io::Result<()> {
-        use std::usize;
+        Self::read_array_with(state, ty, arr, &ReadOptions::default(), r)
+    }
 
+    /// Reads array with options.
+    fn read_array_with<R: io::Read, A: Array<Item = Self>>(
+        state: State<Bytes>,
+        ty: u16,
+        arr: &mut A,
+        opts: &ReadOptions,
+        r: &mut R
+    ) -> io::Result<()> {
         let self_ty = <Self as Scalar>::ty();
-        if let Some((ty, rows, cols)) = Type::info(ty) {
-            if ty == self_ty && rows == 1 && cols == 1 {
-                let mut bytes = 0;
-                let state = state.read_bytes(&mut bytes, r)?;
-                let (_, scalar_bytes) = self_ty.scalar();
-                if bytes % scalar_bytes == 0 {
-                    let n = bytes / scalar_bytes;
-                    let mut offset = 0;
-                    let state = state.read_offset_instance_id(&mut offset, r)?;
-                    for i in offset..(offset + n) {
-                        if i > usize::MAX as u64 {
-                            return Err(io::ErrorKind::Other.into());
-                        }
-                        while i as usize >= arr.len() {
-                            arr.push(Default::default());
-                        }
-                        let mut scalar: Self = Default::default();
-                        scalar.read(r)?;
-                        arr.set(i as usize, scalar);
-                    }
-                    state.end_data().has_end_bytes(r)?;
-                    return Ok(())
-                }
-            }
+        check_type(state.property_id(), ty, self_ty, [1, 1], &ReadOptions::default())?;
+        let mut bytes = 0;
+        let state = state.read_bytes(&mut bytes, r)?;
+        let n = item_count(state.property_id(), bytes, self_ty.type_size())?;
+        let mut offset = 0;
+        let state = state.read_offset_instance_id(&mut offset, r)?;
+        let (offset, end) = instance_range(offset, n, opts)?;
+        for i in offset..end {
+            let mut scalar: Self = Default::default();
+            scalar.read(r)?;
+            if i == offset {replace(arr, opts)}
+            make_room(arr, i as usize, end as usize)?;
+            arr.set(i as usize, scalar);
         }
-        return Err(io::ErrorKind::InvalidData.into())
+        state.end_data().has_end_bytes(r)?;
+        Ok(())
     }
 }
 
@@ -535,12 +901,11 @@ impl Scalar for u8 {
     #[inline]
     fn ty() -> Type {Type::U8}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        w.write(&[*self])
+        self.encode(w)?;
+        Ok(1)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut buf: [u8; 1] = [0; 1];
-        r.read_exact(&mut buf)?;
-        *self = buf[0];
+        *self = Value::decode(r)?;
         Ok(1)
     }
 }
@@ -549,13 +914,11 @@ impl Scalar for u16 {
     #[inline]
     fn ty() -> Type {Type::U16}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        let le = self.to_le();
-        w.write(&[le as u8, (le >> 8) as u8])
+        self.encode(w)?;
+        Ok(2)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut buf: [u8; 2] = [0; 2];
-        r.read_exact(&mut buf)?;
-        *self = u16::from_le(buf[0] as u16 | (buf[1] as u16) << 8);
+        *self = Value::decode(r)?;
         Ok(2)
     }
 }
@@ -564,16 +927,11 @@ impl Scalar for u32 {
     #[inline]
     fn ty() -> Type {Type::U32}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        let le = self.to_le();
-        w.write(&[le as u8, (le >> 8) as u8, (le >> 16) as u8, (le >> 24) as u8])
+        self.encode(w)?;
+        Ok(4)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut buf: [u8; 4] = [0; 4];
-        r.read_exact(&mut buf)?;
-        *self = u32::from_le(
-            buf[0] as u32 | (buf[1] as u32) << 8 |
-            (buf[2] as u32) << 16 | (buf[3] as u32) << 24
-        );
+        *self = Value::decode(r)?;
         Ok(4)
     }
 }
@@ -582,21 +940,11 @@ impl Scalar for u64 {
     #[inline]
     fn ty() -> Type {Type::U64}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        let le = self.to_le();
-        w.write(&[
-            le as u8, (le >> 8) as u8, (le >> 16) as u8, (le >> 24) as u8,
-            (le >> 32) as u8, (le >> 40) as u8, (le >> 48) as u8, (le >> 56) as u8
-        ])
+        self.encode(w)?;
+        Ok(8)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut buf: [u8; 8] = [0; 8];
-        r.read_exact(&mut buf)?;
-        *self = u64::from_le(
-            buf[0] as u64 | (buf[1] as u64) << 8 |
-            (buf[2] as u64) << 16 | (buf[3] as u64) << 24 |
-            (buf[4] as u64) << 32 | (buf[5] as u64) << 40 |
-            (buf[6] as u64) << 48 | (buf[7] as u64) << 56
-        );
+        *self = Value::decode(r)?;
         Ok(8)
     }
 }
@@ -605,90 +953,76 @@ impl Scalar for i8 {
     #[inline]
     fn ty() -> Type {Type::I8}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        w.write(&[*self as u8])
+        self.encode(w)?;
+        Ok(1)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut val: u8 = 0;
-        let n = val.read(r)?;
-        *self = val as i8;
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(1)
     }
 }
 
 impl Scalar for i16 {
     #[inline]
-    fn ty() -> Type {Type::I8}
+    fn ty() -> Type {Type::I16}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        (*self as u16).write(w)
+        self.encode(w)?;
+        Ok(2)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut val: u16 = 0;
-        let n = val.read(r)?;
-        *self = val as i16;
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(2)
     }
 }
 
 impl Scalar for i32 {
     #[inline]
-    fn ty() -> Type {Type::I8}
+    fn ty() -> Type {Type::I32}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        (*self as u32).write(w)
+        self.encode(w)?;
+        Ok(4)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut val: u32 = 0;
-        let n = val.read(r)?;
-        *self = val as i32;
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(4)
     }
 }
 
 impl Scalar for i64 {
     #[inline]
-    fn ty() -> Type {Type::I8}
+    fn ty() -> Type {Type::I64}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        (*self as u64).write(w)
+        self.encode(w)?;
+        Ok(8)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        let mut val: u64 = 0;
-        let n = val.read(r)?;
-        *self = val as i64;
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(8)
     }
 }
 
 impl Scalar for f32 {
     #[inline]
-    fn ty() -> Type {Type::I8}
+    fn ty() -> Type {Type::F32}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        use std::mem::transmute;
-
-        unsafe {transmute::<&f32, &u32>(self)}.write(w)
+        self.encode(w)?;
+        Ok(4)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        use std::mem::transmute;
-
-        let mut val: u32 = 0;
-        let n = val.read(r)?;
-        *self = unsafe {transmute::<u32, f32>(val)};
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(4)
     }
 }
 
 impl Scalar for f64 {
     #[inline]
-    fn ty() -> Type {Type::I8}
+    fn ty() -> Type {Type::F64}
     fn write<W: io::Write>(&self, w: &mut W) -> io::Result<usize> {
-        use std::mem::transmute;
-
-        unsafe {transmute::<&f64, &u64>(self)}.write(w)
+        self.encode(w)?;
+        Ok(8)
     }
     fn read<R: io::Read>(&mut self, r: &mut R) -> io::Result<usize> {
-        use std::mem::transmute;
-
-        let mut val: u64 = 0;
-        let n = val.read(r)?;
-        *self = unsafe {transmute::<u64, f64>(val)};
-        Ok(n)
+        *self = Value::decode(r)?;
+        Ok(8)
     }
 }
@@ -0,0 +1,507 @@
+//! Reading properties from a stream.
+
+use std::io;
+use std::marker::PhantomData;
+
+use alias::Aliases;
+use buffer_pool::BufferPool;
+use checkpoint::{self, Checkpoint, TAIL};
+use endian::{Converter, Header};
+use error::{Error, ErrorKind};
+use finite::{FiniteCheck, NonFinitePolicy};
+use formats::{CustomFormat, CustomFormats};
+use limits::{Limits, Usage};
+use metadata::{self, Metadata};
+use raw::{self, RawBlock};
+use reserved::{self, HEADER_FORMAT, HEADER_PROPERTY};
+use reserved::{METADATA_FORMAT, METADATA_PROPERTY, RLE_FORMAT, UNITS_FORMAT, UNITS_PROPERTY};
+use reserved::{FOOTER_FORMAT, FOOTER_PROPERTY, FORMATS_FORMAT, FORMATS_PROPERTY};
+use reserved::{PADDING_FORMAT, PADDING_PROPERTY};
+use rle;
+use trace;
+use units::Units;
+use Bytes;
+use State;
+
+type MismatchCallback = Box<dyn FnMut(u16, &CustomFormat, &CustomFormat) -> io::Result<()>>;
+
+/// Determines what happens to properties that are not registered.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Unknown {
+    /// Return an error.
+    Error,
+    /// Skip the blocks of the property.
+    Skip,
+    /// Collect the blocks of the property without decoding.
+    Collect,
+}
+
+/// Reads properties from a stream.
+///
+/// When the stream starts with a file header, the header is skipped
+/// and data is converted from the byte order of the stream.
+/// Streams without a header are little endian.
+/// Properties with reserved ids are metadata, not data of the application.
+/// Units and metadata of properties are collected,
+/// and other reserved properties are collected as reserved blocks,
+/// unless the property is registered.
+/// Since the conversion happens when reading through the reader,
+/// any function reading a stream can read big endian streams this way.
+/// Registered properties that are run-length encoded are expanded,
+/// such that they are returned with the type format of their items.
+///
+/// Properties are registered before reading.
+/// By default, a property that is not registered is an error.
+///
+/// The reader implements `Read`, such that data of the properties
+/// can be read through it:
+///
+/// ```ignore
+/// let mut reader = PoolReader::new(file).unknown(Unknown::Collect);
+/// reader.register(POSITION);
+/// while let Some((state, ty, prop)) = reader.next_property()? {
+///     match prop {
+///         POSITION => Vector::read_array(state, ty, &mut pos, &mut reader)?,
+///         _ => unreachable!(),
+///     }
+/// }
+/// for block in reader.unknown_blocks() {
+///     println!("unknown property {} ({} bytes)", block.property_id, block.data.len());
+/// }
+/// ```
+///
+/// Reading can be stopped between properties with `checkpoint`
+/// and continued later with `resume`, without reading the stream again.
+///
+/// Versions of custom formats in the stream are checked against the formats
+/// set with `custom_formats`, when the stream has a registry.
+pub struct PoolReader<R> {
+    inner: R,
+    known: Vec<u16>,
+    unknown: Unknown,
+    unknown_blocks: Vec<RawBlock>,
+    converter: Converter,
+    converted: Vec<u8>,
+    pos: usize,
+    usage: Usage,
+    non_finite: NonFinitePolicy,
+    finite: FiniteCheck,
+    units: Units,
+    metadata: Vec<(u16, Metadata)>,
+    formats: CustomFormats,
+    file_formats: CustomFormats,
+    format_mismatch: Option<MismatchCallback>,
+    reserved_as_data: bool,
+    reserved_blocks: Vec<RawBlock>,
+    aliases: Aliases,
+    position: u64,
+    tail: Vec<u8>,
+    buffers: Option<BufferPool>,
+}
+
+impl<R: io::Read> PoolReader<R> {
+    /// Creates a new reader.
+    pub fn new(inner: R) -> PoolReader<R> {
+        PoolReader {
+            inner,
+            known: vec![],
+            unknown: Unknown::Error,
+            unknown_blocks: vec![],
+            converter: Converter::default(),
+            converted: vec![],
+            pos: 0,
+            usage: Usage::default(),
+            non_finite: NonFinitePolicy::Allow,
+            finite: FiniteCheck::default(),
+            units: Units::new(),
+            metadata: vec![],
+            formats: CustomFormats::new(),
+            file_formats: CustomFormats::new(),
+            format_mismatch: None,
+            reserved_as_data: false,
+            reserved_blocks: vec![],
+            aliases: Aliases::new(),
+            position: 0,
+            tail: vec![],
+            buffers: None,
+        }
+    }
+
+    /// Sets limits on the resources used when reading.
+    ///
+    /// Limits are checked for all data read through the reader,
+    /// including properties that are not registered.
+    pub fn limits(mut self, limits: Limits) -> PoolReader<R> {
+        self.usage.set_limits(limits);
+        self
+    }
+
+    /// Sets what happens to NaN and infinite values of `f32` and `f64` properties.
+    pub fn non_finite(mut self, policy: NonFinitePolicy) -> PoolReader<R> {
+        self.non_finite = policy;
+        self
+    }
+
+    /// Returns the number of NaN and infinite values read
+    /// with `NonFinitePolicy::Count`.
+    pub fn non_finite_count(&self) -> u64 {
+        self.finite.count()
+    }
+
+    /// Sets what happens to properties that are not registered.
+    pub fn unknown(mut self, unknown: Unknown) -> PoolReader<R> {
+        self.unknown = unknown;
+        self
+    }
+
+    /// Sets whether properties with reserved ids are read as data.
+    ///
+    /// This is used to read legacy streams that use reserved ids for their own properties.
+    pub fn reserved_as_data(mut self, val: bool) -> PoolReader<R> {
+        self.reserved_as_data = val;
+        self
+    }
+
+    /// Sets aliases of property ids.
+    ///
+    /// Properties with old ids are returned with the new ids.
+    /// Since there are no frames, conflicts between old and new ids are not resolved.
+    pub fn aliases(mut self, aliases: Aliases) -> PoolReader<R> {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Sets a pool to take the buffers of skipped and expanded properties from.
+    ///
+    /// The buffers are returned to the pool when the property has been read.
+    pub fn buffer_pool(mut self, pool: BufferPool) -> PoolReader<R> {
+        self.buffers = Some(pool);
+        self
+    }
+
+    /// Sets the registered custom formats, which the registry of the stream is checked against.
+    ///
+    /// By default, a format with another version in the stream is an error
+    /// of kind `ErrorKind::CustomFormatVersionMismatch`.
+    pub fn custom_formats(mut self, formats: CustomFormats) -> PoolReader<R> {
+        self.formats = formats;
+        self
+    }
+
+    /// Sets a callback for custom formats with another version in the stream,
+    /// called with the format, the version in the stream and the registered version,
+    /// instead of returning an error.
+    ///
+    /// The callback can prepare a migration of the data, or return an error to stop reading.
+    pub fn on_format_mismatch<F>(mut self, f: F) -> PoolReader<R>
+        where F: 'static + FnMut(u16, &CustomFormat, &CustomFormat) -> io::Result<()>
+    {
+        self.format_mismatch = Some(Box::new(f));
+        self
+    }
+
+    /// Registers a property.
+    pub fn register(&mut self, property_id: u16) {
+        if !self.known.contains(&property_id) {
+            self.known.push(property_id);
+        }
+    }
+
+    /// Returns `true` if the property is registered.
+    pub fn is_registered(&self, property_id: u16) -> bool {
+        self.known.contains(&property_id)
+    }
+
+    /// Reads type format and property of the next registered property.
+    ///
+    /// Returns `None` if there is no more data.
+    pub fn next_property(&mut self) -> io::Result<Option<(State<Bytes>, u16, u16)>> {
+        while let Some((state, ty, prop)) = raw::read_property_header(self)? {
+            let prop = self.aliases.resolve(prop);
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY && self.converter.in_header() {
+                self.skip_property(state, ty, prop)?;
+                continue;
+            }
+            if reserved::is_reserved_property(prop) && !self.reserved_as_data &&
+               !self.is_registered(prop) {
+                if ty == UNITS_FORMAT && prop == UNITS_PROPERTY {
+                    let units = Units::read(state, ty, prop, self)?;
+                    self.units.merge(&units);
+                } else if ty == METADATA_FORMAT && prop == METADATA_PROPERTY {
+                    let entries = metadata::read_metadata(state, ty, prop, self)?;
+                    metadata::merge_metadata(&mut self.metadata, entries);
+                } else if ty == FORMATS_FORMAT && prop == FORMATS_PROPERTY {
+                    let formats = CustomFormats::read(state, ty, prop, self)?;
+                    self.reconcile(&formats)?;
+                    self.file_formats.merge(&formats);
+                } else if (ty == PADDING_FORMAT && prop == PADDING_PROPERTY) ||
+                    (ty == FOOTER_FORMAT && prop == FOOTER_PROPERTY) {
+                    self.skip_property(state, ty, prop)?;
+                } else {
+                    let blocks = RawBlock::read_property(state, ty, prop, self)?;
+                    self.reserved_blocks.extend(blocks);
+                }
+                continue;
+            }
+            if self.is_registered(prop) {
+                if ty == RLE_FORMAT {
+                    return self.expand_rle(state, prop).map(Some);
+                }
+                return Ok(Some((state, ty, prop)));
+            }
+            match self.unknown {
+                Unknown::Error => {
+                    return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+                }
+                Unknown::Skip => {
+                    self.skip_property(state, ty, prop)?;
+                }
+                Unknown::Collect => {
+                    let blocks = RawBlock::read_property(state, ty, prop, self)?;
+                    self.unknown_blocks.extend(blocks);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the blocks of a property into buffers of the pool.
+    fn read_pooled(&mut self, state: State<Bytes>, ty: u16, prop: u16) -> io::Result<Vec<RawBlock>> {
+        let pool = self.buffers.clone();
+        raw::read_blocks_pooled(state, ty, prop, &mut Usage::default(), pool.as_ref(), self)
+    }
+
+    /// Reads the blocks of a property and returns the buffers to the pool.
+    fn skip_property(&mut self, state: State<Bytes>, ty: u16, prop: u16) -> io::Result<()> {
+        let blocks = self.read_pooled(state, ty, prop)?;
+        trace::skip(prop, ty, blocks.iter().map(|b| b.data.len() as u64).sum());
+        if let Some(ref pool) = self.buffers {pool.give_blocks(blocks)}
+        Ok(())
+    }
+
+    /// Checks the versions of custom formats in the stream against the registered formats.
+    fn reconcile(&mut self, file: &CustomFormats) -> io::Result<()> {
+        let f = match self.format_mismatch {
+            Some(ref mut f) => f,
+            None => return self.formats.reconcile(file),
+        };
+        for (format, custom) in file.iter() {
+            if let Some(local) = self.formats.get(format) {
+                if local.version != custom.version {f(format, custom, local)?}
+            }
+        }
+        Ok(())
+    }
+
+    /// Expands a run-length encoded property into plain blocks,
+    /// which are read next through the reader.
+    fn expand_rle(&mut self, state: State<Bytes>, prop: u16) -> io::Result<(State<Bytes>, u16, u16)> {
+        let blocks = self.read_pooled(state, RLE_FORMAT, prop)?;
+        let mut ty = RLE_FORMAT;
+        let mut data = match self.buffers {
+            Some(ref pool) => pool.take(blocks.iter().map(|b| b.data.len()).sum()),
+            None => vec![],
+        };
+        for block in &blocks {
+            let block = rle::expand_rle(block, self.usage.max_block_bytes())?;
+            if ty != RLE_FORMAT && ty != block.type_format {
+                return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+            }
+            ty = block.type_format;
+            // Empty blocks would be read as the end of bytes.
+            if block.data.is_empty() {continue}
+            self.usage.expanded(ty, block.data.len() as u64, block.offset)?;
+            data.extend_from_slice(&(block.data.len() as u64).to_le_bytes());
+            data.extend_from_slice(&block.offset.to_le_bytes());
+            data.extend_from_slice(&block.data);
+        }
+        data.extend_from_slice(&0u64.to_le_bytes());
+        if self.non_finite != NonFinitePolicy::Allow {
+            // The expanded data does not pass through the stream check.
+            let mut finite = FiniteCheck::default();
+            let mut header = ty.to_le_bytes().to_vec();
+            header.extend_from_slice(&prop.to_le_bytes());
+            check_finite(self.non_finite, &mut finite, &header)?;
+            check_finite(self.non_finite, &mut finite, &data)?;
+            self.finite.add_count(&finite);
+        }
+        let pos = self.pos.min(self.converted.len());
+        self.converted.splice(pos..pos, data.drain(..));
+        self.pos = pos;
+        if let Some(ref pool) = self.buffers {
+            pool.give_blocks(blocks);
+            pool.give(data);
+        }
+        Ok((State(PhantomData, Some(prop)), ty, prop))
+    }
+
+    /// Returns the file header, if the stream starts with one.
+    ///
+    /// This is known after the first property has been read.
+    pub fn header(&self) -> Option<Header> {
+        self.converter.header()
+    }
+
+    /// Returns the units of properties read so far.
+    pub fn units(&self) -> &Units {
+        &self.units
+    }
+
+    /// Returns the metadata of a property read so far.
+    pub fn metadata(&self, property_id: u16) -> Option<&Metadata> {
+        self.metadata.iter().find(|&&(prop, _)| prop == property_id).map(|(_, m)| m)
+    }
+
+    /// Returns the custom formats in the registry of the stream read so far.
+    pub fn file_formats(&self) -> &CustomFormats {
+        &self.file_formats
+    }
+
+    /// Returns the collected blocks of reserved properties that are not units or metadata.
+    pub fn reserved_blocks(&self) -> &[RawBlock] {
+        &self.reserved_blocks
+    }
+
+    /// Returns the collected blocks of properties that are not registered.
+    pub fn unknown_blocks(&self) -> &[RawBlock] {
+        &self.unknown_blocks
+    }
+
+    /// Takes the collected blocks of properties that are not registered.
+    pub fn take_unknown_blocks(&mut self) -> Vec<RawBlock> {
+        ::std::mem::take(&mut self.unknown_blocks)
+    }
+
+    /// Returns the state of the reader, for continuing later with `resume`.
+    ///
+    /// Returns an error if the reader is not between two properties,
+    /// or has read ahead of the data that was read through it.
+    pub fn checkpoint(&self) -> io::Result<Checkpoint> {
+        if self.pos < self.converted.len() || !self.converter.at_property_boundary() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let (properties, payload) = self.usage.counts();
+        let tail = &self.tail[self.tail.len().saturating_sub(TAIL)..];
+        Ok(Checkpoint {
+            position: self.position,
+            header: self.converter.header(),
+            properties,
+            payload,
+            non_finite_count: self.finite.count(),
+            units: self.units.clone(),
+            metadata: self.metadata.clone(),
+            formats: self.file_formats.clone(),
+            hash: checkpoint::hash_tail(self.position, tail),
+        })
+    }
+}
+
+impl<R: io::Read + io::Seek> PoolReader<R> {
+    /// Continues reading from a checkpoint.
+    ///
+    /// The bytes before the position are read again and compared with the hash of the checkpoint.
+    /// Returns an error of kind `ErrorKind::CheckpointMismatch` if the stream has changed.
+    ///
+    /// Settings, such as registered properties and limits, are set again on the returned reader.
+    /// Limits apply to the counts of the checkpoint.
+    pub fn resume(mut inner: R, checkpoint: &Checkpoint) -> io::Result<PoolReader<R>> {
+        let mismatch = || -> io::Error {Error::new(ErrorKind::CheckpointMismatch).into()};
+        let n = checkpoint.position.min(TAIL as u64);
+        inner.seek(io::SeekFrom::Start(checkpoint.position - n))?;
+        let mut tail = vec![0; n as usize];
+        match inner.read_exact(&mut tail) {
+            Ok(()) => {}
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Err(mismatch()),
+            Err(err) => return Err(err),
+        }
+        if checkpoint::hash_tail(checkpoint.position, &tail) != checkpoint.hash {
+            return Err(mismatch());
+        }
+        let mut reader = PoolReader::new(inner);
+        if checkpoint.position > 0 {
+            reader.converter = Converter::resume(checkpoint.header);
+        }
+        reader.usage.set_counts(checkpoint.properties, checkpoint.payload);
+        reader.finite.set_count(checkpoint.non_finite_count);
+        reader.units = checkpoint.units.clone();
+        reader.metadata = checkpoint.metadata.clone();
+        reader.file_formats = checkpoint.formats.clone();
+        reader.position = checkpoint.position;
+        reader.tail = tail;
+        Ok(reader)
+    }
+}
+
+impl<R> PoolReader<R> {
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader is not checked against limits.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: io::Read> io::Read for PoolReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let usage = &mut self.usage;
+        if self.pos >= self.converted.len() && self.converter.is_passthrough() {
+            let n = self.inner.read(buf)?;
+            remember(&mut self.position, &mut self.tail, &buf[..n]);
+            self.converter.convert(&buf[..n], false, |_| Ok(()), |e| Ok(usage.event(e)?))?;
+            check_finite(self.non_finite, &mut self.finite, &buf[..n])?;
+            return Ok(n);
+        }
+        // Convert the byte order, keeping bytes that did not fit into the buffer.
+        while self.pos >= self.converted.len() {
+            self.converted.clear();
+            self.pos = 0;
+            let n = self.inner.read(buf)?;
+            if n == 0 {return Ok(0)}
+            remember(&mut self.position, &mut self.tail, &buf[..n]);
+            let converted = &mut self.converted;
+            self.converter.convert(&buf[..n], false, |data| {
+                converted.extend_from_slice(data);
+                Ok(())
+            }, |e| Ok(usage.event(e)?))?;
+            check_finite(self.non_finite, &mut self.finite, &self.converted)?;
+        }
+        let n = buf.len().min(self.converted.len() - self.pos);
+        buf[..n].copy_from_slice(&self.converted[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Counts bytes read from the stream and keeps the last of them.
+fn remember(position: &mut u64, tail: &mut Vec<u8>, data: &[u8]) {
+    *position += data.len() as u64;
+    if data.len() >= TAIL {
+        tail.clear();
+        tail.extend_from_slice(&data[data.len() - TAIL..]);
+        return;
+    }
+    // Up to twice the bytes needed are kept, such that bytes are moved less often.
+    if tail.len() + data.len() > 2 * TAIL {
+        tail.drain(..tail.len() - TAIL);
+    }
+    tail.extend_from_slice(data);
+}
+
+fn check_finite(policy: NonFinitePolicy, finite: &mut FiniteCheck, data: &[u8]) -> io::Result<()> {
+    match policy {
+        NonFinitePolicy::Allow => {}
+        NonFinitePolicy::Error => finite.check(data)?,
+        NonFinitePolicy::Count => finite.count_bytes(data),
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Recording and replaying the state of a simulation.
+
+use std::io;
+
+use frame::{Frame, FrameReader, FrameWriter};
+use raw::{self, RawBlock};
+
+/// Implemented by simulation state that can be stored as frames.
+pub trait Recordable {
+    /// Writes the properties of the state.
+    fn write_frame<W: io::Write>(&self, w: &mut W) -> io::Result<()>;
+    /// Reads the state from the properties of a frame.
+    fn read_frame(&mut self, frame: &Frame) -> io::Result<()>;
+}
+
+/// Runs simulation steps and records the state after each step.
+///
+/// The time of each frame is the number of steps so far.
+///
+/// ```ignore
+/// let mut recorder = Recorder::new(file, TIME);
+/// for _ in 0..1000 {
+///     recorder.step(&mut state, |s| sim_step(s))?;
+/// }
+/// recorder.finish()?;
+/// ```
+pub struct Recorder<W: io::Write> {
+    w: FrameWriter<W>,
+}
+
+impl<W: io::Write> Recorder<W> {
+    /// Creates a new recorder.
+    pub fn new(w: W, time_property: u16) -> Recorder<W> {
+        Recorder {w: FrameWriter::new(w, time_property)}
+    }
+
+    /// Returns the number of steps recorded.
+    pub fn steps(&self) -> u64 {
+        self.w.frames()
+    }
+
+    /// Runs a step and writes the state as a new frame.
+    pub fn step<S, F>(&mut self, state: &mut S, f: F) -> io::Result<()>
+        where S: Recordable, F: FnOnce(&mut S)
+    {
+        f(state);
+        let t = self.w.frames() as f64;
+        self.w.begin_frame(t)?;
+        state.write_frame(&mut self.w)
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.w.finish()
+    }
+}
+
+/// Replays recorded state instead of running simulation steps.
+///
+/// ```ignore
+/// let mut replayer = Replayer::new(file, TIME);
+/// while replayer.step(&mut state)? {
+///     draw(&state);
+/// }
+/// ```
+pub struct Replayer<R> {
+    r: FrameReader<R>,
+    time_property: u16,
+}
+
+impl<R: io::Read> Replayer<R> {
+    /// Creates a new replayer.
+    pub fn new(r: R, time_property: u16) -> Replayer<R> {
+        Replayer {r: FrameReader::with_time(r, time_property), time_property}
+    }
+
+    /// Returns the frame reader.
+    pub fn reader(&self) -> &FrameReader<R> {
+        &self.r
+    }
+
+    /// Reads the next frame into the state.
+    ///
+    /// Returns `false` at the end of the recording.
+    pub fn step<S: Recordable>(&mut self, state: &mut S) -> io::Result<bool> {
+        match self.r.next_frame()? {
+            Some(frame) => {
+                state.read_frame(&frame)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Runs a step and compares the state with the next frame.
+    ///
+    /// Returns the ids of properties that differ from the recording,
+    /// or `None` at the end of the recording.
+    /// The state keeps the result of the step.
+    pub fn verify<S, F>(&mut self, state: &mut S, f: F) -> io::Result<Option<Vec<u16>>>
+        where S: Recordable, F: FnOnce(&mut S)
+    {
+        let frame = match self.r.next_frame()? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        f(state);
+        let mut data = vec![];
+        state.write_frame(&mut data)?;
+        let mut r = &data[..];
+        let mut blocks = vec![];
+        while let Some((state, ty, prop)) = raw::read_property_header(&mut r)? {
+            blocks.extend(RawBlock::read_property(state, ty, prop, &mut r)?);
+        }
+
+        let mut ids: Vec<u16> = frame.blocks.iter().chain(&blocks)
+            .map(|b| b.property_id)
+            .filter(|&id| id != self.time_property)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.retain(|&id| !frame.blocks_of(id).eq(blocks.iter().filter(|b| b.property_id == id)));
+        Ok(Some(ids))
+    }
+}
@@ -0,0 +1,194 @@
+//! Recovering streams that were cut off while writing.
+//!
+//! When a writer is killed, the file usually contains the complete start of a stream
+//! followed by a torn property, without the end of stream.
+//! `recover` scans the stream from the start, keeps every complete property,
+//! and writes the end of stream after the last one:
+//!
+//! ```ignore
+//! let report = recover_file("run.pool", &RecoverOptions::default().time_property(TIME))?;
+//! println!("dropped {} bytes, last time {:?}", report.dropped_bytes, report.last_time);
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::{self, SeekFrom};
+use std::path::Path;
+
+use endian::Converter;
+use error::Field;
+use parse::{Event, Parser};
+use raw::RawBlock;
+use time::Time;
+
+/// Options for recovering a stream.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoverOptions {
+    /// Only report what would be recovered, without writing.
+    pub dry_run: bool,
+    /// The time property, for reporting the time of the last frame kept.
+    pub time_property: Option<u16>,
+}
+
+impl RecoverOptions {
+    /// Sets whether to only report what would be recovered, without writing.
+    pub fn dry_run(mut self, val: bool) -> RecoverOptions {
+        self.dry_run = val;
+        self
+    }
+
+    /// Sets the time property, for reporting the time of the last frame kept.
+    pub fn time_property(mut self, property_id: u16) -> RecoverOptions {
+        self.time_property = Some(property_id);
+        self
+    }
+}
+
+/// The result of recovering a stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecoveryReport {
+    /// The length of the stream before recovering, in bytes.
+    pub original_len: u64,
+    /// The length of the recovered stream, including the end of stream.
+    pub len: u64,
+    /// The number of bytes after the last complete property that were dropped.
+    pub dropped_bytes: u64,
+    /// The number of blocks of the torn property that were dropped,
+    /// counting blocks with a complete header.
+    pub dropped_blocks: u64,
+    /// The number of complete properties kept.
+    pub properties: u64,
+    /// The time of the last time property kept, if a time property is set.
+    pub last_time: Option<f64>,
+    /// Whether the stream already had an end of stream, in which case nothing is changed.
+    pub complete: bool,
+}
+
+/// Follows the little endian view of a stream,
+/// remembering the end of the last complete property.
+struct Scan {
+    parser: Parser,
+    time_property: Option<u16>,
+    boundary: u64,
+    properties: u64,
+    blocks: u64,
+    time: Option<RawBlock>,
+    last_time: Option<f64>,
+    end: Option<u64>,
+}
+
+impl Scan {
+    fn consume(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && self.end.is_none() {
+            if self.parser.field() == Field::Data {
+                let n = self.parser.remaining().min(data.len() as u64) as usize;
+                if let Some(ref mut t) = self.time {t.data.extend_from_slice(&data[..n])}
+                self.parser.consume(&data[..n], |_| {});
+                data = &data[n..];
+                continue;
+            }
+
+            // Header fields are consumed a byte at a time, to know where a property ends.
+            let mut event = None;
+            self.parser.consume(&data[..1], |e| event = Some(e));
+            data = &data[1..];
+            match event {
+                Some(Event::Property {type_format, property_id}) => {
+                    self.blocks = 0;
+                    self.time = if Some(property_id) == self.time_property {
+                        Some(RawBlock {type_format, property_id, offset: 0, data: vec![]})
+                    } else {None};
+                }
+                Some(Event::Block {bytes, offset}) => {
+                    self.blocks += 1;
+                    if let Some(ref mut t) = self.time {
+                        // A time is one scalar, so larger blocks are not kept.
+                        if bytes > 8 || !t.data.is_empty() {self.time = None} else {t.offset = offset}
+                    }
+                }
+                Some(Event::EndBytes) => {
+                    self.boundary = self.parser.position();
+                    self.properties += 1;
+                    self.blocks = 0;
+                    if let Some(t) = self.time.take() {
+                        if let Ok(t) = Time::decode(&t) {self.last_time = Some(t)}
+                    }
+                }
+                Some(Event::End) => self.end = Some(self.parser.position()),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Recovers a stream that was cut off, keeping all complete properties.
+///
+/// The stream is scanned from the start.
+/// When it has no end of stream, the end of stream is written after the last complete property,
+/// unless `dry_run` is set.
+/// Bytes after it are not removed, since `F` can not be truncated,
+/// but readers stop at the end of stream.
+/// Use `recover_file` to also truncate a file.
+///
+/// Both byte orders are supported.
+pub fn recover<F>(f: &mut F, opts: &RecoverOptions) -> io::Result<RecoveryReport>
+    where F: io::Read + io::Write + io::Seek
+{
+    let original_len = f.seek(SeekFrom::End(0))?;
+    f.seek(SeekFrom::Start(0))?;
+    let mut converter = Converter::default();
+    let mut scan = Scan {
+        parser: Parser::new(),
+        time_property: opts.time_property,
+        boundary: 0,
+        properties: 0,
+        blocks: 0,
+        time: None,
+        last_time: None,
+        end: None,
+    };
+    let mut buf = vec![0; 64 << 10];
+    while scan.end.is_none() {
+        let n = match f.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        converter.convert(&buf[..n], false, |out| {
+            scan.consume(out);
+            Ok(())
+        }, |_| Ok(()))?;
+    }
+
+    let mut report = RecoveryReport {
+        original_len,
+        properties: scan.properties,
+        last_time: scan.last_time,
+        ..RecoveryReport::default()
+    };
+    if let Some(end) = scan.end {
+        report.len = end;
+        report.complete = true;
+        return Ok(report);
+    }
+    report.len = scan.boundary + 2;
+    report.dropped_bytes = original_len - scan.boundary;
+    report.dropped_blocks = scan.blocks;
+    if !opts.dry_run {
+        f.seek(SeekFrom::Start(scan.boundary))?;
+        // The end of stream is a type format of 0 in either byte order.
+        f.write_all(&[0, 0])?;
+        f.flush()?;
+    }
+    Ok(report)
+}
+
+/// Recovers a file that was cut off, truncating it after the end of stream.
+///
+/// See `recover`.
+pub fn recover_file<P: AsRef<Path>>(path: P, opts: &RecoverOptions) -> io::Result<RecoveryReport> {
+    let mut file = OpenOptions::new().read(true).write(!opts.dry_run).open(path)?;
+    let report = recover(&mut file, opts)?;
+    if !opts.dry_run && !report.complete {file.set_len(report.len)?}
+    Ok(report)
+}
@@ -0,0 +1,112 @@
+//! Reductions of frames and invariants checked while reading.
+
+use std::io;
+
+use dyn_value;
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use read_write::Element;
+use reserved::PRESENCE_FORMAT;
+
+type Fold = Box<dyn FnMut(&RawBlock, &mut Vec<f64>) -> io::Result<()>>;
+type Check = Box<dyn FnMut(&[Vec<f64>]) -> Result<(), String>>;
+
+/// A frame that does not satisfy an invariant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// The frame number.
+    pub frame: u64,
+    /// The name of the invariant.
+    pub name: String,
+    /// The description returned by the invariant.
+    pub message: String,
+    /// The results of all reductions in the frame, in the order they were added.
+    pub values: Vec<Vec<f64>>,
+}
+
+struct Reduction {
+    property_id: u16,
+    init: Vec<f64>,
+    fold: Fold,
+}
+
+struct Invariant {
+    name: String,
+    check: Check,
+}
+
+/// Reductions and invariants of a frame reader.
+#[derive(Default)]
+pub(crate) struct Reductions {
+    reductions: Vec<Reduction>,
+    invariants: Vec<Invariant>,
+    results: Vec<Vec<f64>>,
+    violations: Vec<Violation>,
+    abort: bool,
+}
+
+impl Reductions {
+    pub fn add<T, F>(&mut self, property_id: u16, init: Vec<f64>, mut f: F)
+        where T: Element, F: 'static + FnMut(&mut Vec<f64>, &T)
+    {
+        let fold = move |block: &RawBlock, acc: &mut Vec<f64>| -> io::Result<()> {
+            dyn_value::check_format::<T>(block.type_format)
+                .map_err(|err| Error::from_io(&err).with_property(block.property_id))?;
+            let mut r = &block.data[..];
+            let mut val: T = Default::default();
+            while !r.is_empty() {
+                val.read_element(&mut r)?;
+                f(acc, &val);
+            }
+            Ok(())
+        };
+        self.reductions.push(Reduction {property_id, init, fold: Box::new(fold)});
+    }
+
+    pub fn add_invariant<F>(&mut self, name: &str, f: F)
+        where F: 'static + FnMut(&[Vec<f64>]) -> Result<(), String>
+    {
+        self.invariants.push(Invariant {name: name.into(), check: Box::new(f)});
+    }
+
+    pub fn set_abort(&mut self, val: bool) {
+        self.abort = val;
+    }
+
+    pub fn results(&self) -> &[Vec<f64>] {
+        &self.results
+    }
+
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Reduces the blocks of a frame and checks the invariants.
+    pub fn apply(&mut self, frame: u64, blocks: &[RawBlock]) -> io::Result<()> {
+        if self.reductions.is_empty() && self.invariants.is_empty() {return Ok(())}
+        self.results.clear();
+        for reduction in &mut self.reductions {
+            let mut acc = reduction.init.clone();
+            for block in blocks {
+                if block.property_id != reduction.property_id ||
+                   block.type_format == PRESENCE_FORMAT {continue}
+                (reduction.fold)(block, &mut acc)?;
+            }
+            self.results.push(acc);
+        }
+        for invariant in &mut self.invariants {
+            if let Err(message) = (invariant.check)(&self.results) {
+                self.violations.push(Violation {
+                    frame,
+                    name: invariant.name.clone(),
+                    message,
+                    values: self.results.clone(),
+                });
+                if self.abort {
+                    return Err(Error::new(ErrorKind::InvariantViolation).with_frame(frame).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
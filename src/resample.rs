@@ -0,0 +1,211 @@
+//! Resampling a stream onto a fixed time grid.
+
+use std::collections::BTreeMap;
+use std::io::{self, SeekFrom};
+
+use error::{Error, ErrorKind};
+use frame::{Frame, FrameReader};
+use index::Index;
+use interpolate::Interpolator;
+use raw::RawBlock;
+use ring::{self, Keyframe};
+use time::Time;
+use State;
+
+/// Determines what happens at output times outside the recorded range.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Extrapolate {
+    /// Use the first or last frame.
+    #[default]
+    Clamp,
+    /// Write no frame.
+    Skip,
+    /// Return an error of kind `ErrorKind::FrameOutOfRange`.
+    Error,
+}
+
+/// Options for resampling a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResampleOptions {
+    /// The first output time, rounded up to a multiple of the time step.
+    ///
+    /// The default is the time of the first frame.
+    pub start: Option<f64>,
+    /// The last output time, rounded down to a multiple of the time step.
+    ///
+    /// The default is the time of the last frame.
+    pub end: Option<f64>,
+    /// What happens at output times outside the recorded range.
+    pub extrapolate: Extrapolate,
+    /// Take properties that are not interpolated from the nearer frame,
+    /// instead of the earlier frame.
+    pub nearest: bool,
+}
+
+impl ResampleOptions {
+    /// Sets the first output time.
+    pub fn start(mut self, val: f64) -> ResampleOptions {
+        self.start = Some(val);
+        self
+    }
+
+    /// Sets the last output time.
+    pub fn end(mut self, val: f64) -> ResampleOptions {
+        self.end = Some(val);
+        self
+    }
+
+    /// Sets what happens at output times outside the recorded range.
+    pub fn extrapolate(mut self, val: Extrapolate) -> ResampleOptions {
+        self.extrapolate = val;
+        self
+    }
+
+    /// Sets whether properties that are not interpolated are taken from the nearer frame.
+    pub fn nearest(mut self, val: bool) -> ResampleOptions {
+        self.nearest = val;
+        self
+    }
+}
+
+/// Copies a stream with frames at the times `k * dt`.
+///
+/// Frames start with the time property.
+/// Every output frame contains every property, merged up to the frame like `Player::tick`,
+/// with float properties interpolated between the frames before and after the output time,
+/// as done by `Interpolator`.
+/// Frames without time belong to the frame before them.
+/// The data before the first frame and the time unit are kept.
+///
+/// The output is terminated with an end of stream.
+/// Returns an error of kind `io::ErrorKind::InvalidInput` if `dt` is not positive
+/// or the range is not finite,
+/// or `ErrorKind::InvalidData` if time is not monotonic.
+pub fn resample<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    time_property: u16,
+    dt: f64,
+    opts: &ResampleOptions
+) -> io::Result<()> {
+    if !(dt > 0.0 && dt.is_finite()) ||
+       opts.start.is_some_and(|t| !t.is_finite()) || opts.end.is_some_and(|t| !t.is_finite()) {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    let start = r.stream_position()?;
+    let index = Index::build(r, time_property, Some(time_property))?;
+    if !index.is_monotonic() {
+        return Err(Error::new(ErrorKind::InvalidData).with_property(time_property).into());
+    }
+    r.seek(SeekFrom::Start(start))?;
+
+    let mut frames = Frames {
+        reader: FrameReader::with_time(r, time_property),
+        state: BTreeMap::new(),
+        pending: None,
+    };
+    let mut prev = frames.next_point()?;
+    let mut next = frames.next_point()?;
+    write_blocks(frames.reader.preamble(), time_property, w)?;
+    if let Some(unit) = frames.reader.time_unit() {
+        unit.write(time_property, w)?;
+    }
+
+    let first = index.frames().iter().find_map(|f| f.time);
+    let last = index.frames().iter().rev().find_map(|f| f.time);
+    let (t0, t1) = match (opts.start.or(first), opts.end.or(last)) {
+        (Some(t0), Some(t1)) => (t0, t1),
+        _ => return State::new().end_type_formats(w),
+    };
+    let interpolator = |a: &Frame, b: &Frame| {
+        let mut props: Vec<u16> = a.blocks.iter().chain(&b.blocks)
+            .map(|block| block.property_id)
+            .filter(|&prop| prop != time_property)
+            .collect();
+        props.sort_unstable();
+        props.dedup();
+        Interpolator::new(&props).nearest(opts.nearest)
+    };
+    let mut k = (t0 / dt).ceil();
+    while k * dt <= t1 {
+        let t = k * dt;
+        k += 1.0;
+        while next.as_ref().is_some_and(|&(time, _)| time <= t) {
+            prev = next.take();
+            next = frames.next_point()?;
+        }
+        let (prev_time, prev_frame) = match prev {
+            Some((time, ref frame)) => (time, frame),
+            None => break,
+        };
+        let frame = match next {
+            Some((next_time, ref next_frame)) if t >= prev_time => {
+                let alpha = (t - prev_time) / (next_time - prev_time);
+                interpolator(prev_frame, next_frame).blend(prev_frame, next_frame, alpha)?
+            }
+            _ if t == prev_time => prev_frame.clone(),
+            _ => match opts.extrapolate {
+                Extrapolate::Clamp => prev_frame.clone(),
+                Extrapolate::Skip => continue,
+                Extrapolate::Error => {
+                    return Err(Error::new(ErrorKind::FrameOutOfRange)
+                        .with_property(time_property)
+                        .into());
+                }
+            },
+        };
+        Time::write(time_property, t, w)?;
+        write_blocks(&frame.blocks, time_property, w)?;
+    }
+    State::new().end_type_formats(w)
+}
+
+/// Reads frames with every property merged up to the frame.
+struct Frames<R> {
+    reader: FrameReader<R>,
+    state: BTreeMap<(u16, u16), Keyframe>,
+    pending: Option<Frame>,
+}
+
+impl<R: io::Read> Frames<R> {
+    /// Returns the next frame with time, after the frames without time that follow it.
+    fn next_point(&mut self) -> io::Result<Option<(f64, Frame)>> {
+        loop {
+            let frame = match self.reader.next_frame()? {
+                Some(frame) => frame,
+                None => return Ok(self.pending.take().and_then(|f| self.point(f))),
+            };
+            let done = match frame.time {
+                Some(_) => self.pending.replace(Frame {
+                    number: frame.number,
+                    time: frame.time,
+                    blocks: vec![],
+                }),
+                None => None,
+            };
+            let done = done.and_then(|f| self.point(f));
+            ring::merge_blocks(&mut self.state, frame.blocks)?;
+            if done.is_some() {return Ok(done)}
+        }
+    }
+
+    /// Fills a frame with the merged properties.
+    fn point(&self, mut frame: Frame) -> Option<(f64, Frame)> {
+        frame.blocks = ring::keyframe_blocks(&self.state);
+        frame.time.map(|t| (t, frame))
+    }
+}
+
+/// Writes blocks as properties, except the blocks of the time property.
+fn write_blocks<W: io::Write>(blocks: &[RawBlock], time_property: u16, w: &mut W) -> io::Result<()> {
+    let mut blocks = blocks.iter().filter(|b| b.property_id != time_property).peekable();
+    while let Some(block) = blocks.next() {
+        let (ty, prop) = (block.type_format, block.property_id);
+        let mut property = vec![block.clone()];
+        while let Some(block) = blocks.next_if(|b| b.type_format == ty && b.property_id == prop) {
+            property.push(block.clone());
+        }
+        RawBlock::write_property(ty, prop, &property, w)?;
+    }
+    Ok(())
+}
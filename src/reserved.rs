@@ -0,0 +1,97 @@
+//! Property ids and custom formats reserved for the crate.
+//!
+//! The top 256 property ids and the top 256 custom formats are reserved
+//! for blocks written by the crate, such as the file header and metadata.
+//! `PoolWriter` refuses to write properties with reserved ids,
+//! unless they use a reserved format or `WriteOptions::allow_reserved` is set.
+//! `PoolReader` treats properties with reserved ids as metadata,
+//! unless `PoolReader::reserved_as_data` is set for legacy streams.
+
+/// The first reserved property id.
+pub const RESERVED_PROPERTY_START: u16 = 0xff00;
+/// The first reserved custom format.
+pub const RESERVED_FORMAT_START: u16 = 0xff00;
+
+/// Custom format reserved for the unit of a time property.
+///
+/// The block is stored under the property id of the time property.
+pub const TIME_UNIT_FORMAT: u16 = 0xffff;
+/// Custom format reserved for the file header.
+pub const HEADER_FORMAT: u16 = 0xfffe;
+/// Custom format reserved for namespace manifests.
+pub const NAMESPACE_FORMAT: u16 = 0xfffd;
+/// Custom format reserved for presence bitmaps of optional values.
+///
+/// The block is stored under the property id of the array.
+pub const PRESENCE_FORMAT: u16 = 0xfffc;
+/// Custom format reserved for tiles of grids.
+pub const GRID_FORMAT: u16 = 0xfffb;
+/// Custom format reserved for sparse matrices.
+pub const SPARSE_FORMAT: u16 = 0xfffa;
+/// Custom format reserved for units.
+pub const UNITS_FORMAT: u16 = 0xfff9;
+/// Custom format reserved for metadata.
+pub const METADATA_FORMAT: u16 = 0xfff8;
+/// Custom format reserved for run-length encoded arrays.
+///
+/// The block is stored under the property id of the array.
+pub const RLE_FORMAT: u16 = 0xfff7;
+/// Custom format reserved for track switches.
+pub const TRACK_FORMAT: u16 = 0xfff6;
+/// Custom format reserved for padding before aligned data.
+pub const PADDING_FORMAT: u16 = 0xfff5;
+/// Custom format reserved for versions of custom formats.
+pub const FORMATS_FORMAT: u16 = 0xfff4;
+/// Custom format reserved for delta encoded integer arrays.
+///
+/// The block is stored under the property id of the array.
+pub const VARINT_FORMAT: u16 = 0xfff3;
+/// Custom format reserved for the XOR of frame data with the previous frame.
+///
+/// The block is stored under the property id of the array.
+pub const XOR_FORMAT: u16 = 0xfff2;
+/// Custom format reserved for alive masks of instances.
+pub const ALIVE_FORMAT: u16 = 0xfff1;
+/// Custom format reserved for instances created in a frame.
+pub const CREATED_FORMAT: u16 = 0xfff0;
+/// Custom format reserved for instances destroyed in a frame.
+pub const DESTROYED_FORMAT: u16 = 0xffef;
+/// Custom format reserved for maps from sparse instance ids to dense indices.
+pub const ID_MAP_FORMAT: u16 = 0xffee;
+/// Custom format reserved for the index of properties at the end of a stream.
+pub const FOOTER_FORMAT: u16 = 0xffed;
+
+/// Property id reserved for the file header.
+pub const HEADER_PROPERTY: u16 = 0xffff;
+/// Property id of namespace manifests.
+pub const NAMESPACE_PROPERTY: u16 = 0xffff;
+/// Property id of units.
+pub const UNITS_PROPERTY: u16 = 0xffff;
+/// Property id of metadata.
+pub const METADATA_PROPERTY: u16 = 0xffff;
+/// Property id of track switches.
+pub const TRACK_PROPERTY: u16 = 0xffff;
+/// Property id of padding.
+pub const PADDING_PROPERTY: u16 = 0xffff;
+/// Property id of versions of custom formats.
+pub const FORMATS_PROPERTY: u16 = 0xffff;
+/// Property id of alive masks.
+pub const ALIVE_PROPERTY: u16 = 0xffff;
+/// Property id of instances created in a frame.
+pub const CREATED_PROPERTY: u16 = 0xffff;
+/// Property id of instances destroyed in a frame.
+pub const DESTROYED_PROPERTY: u16 = 0xffff;
+/// Property id of maps from sparse instance ids to dense indices.
+pub const ID_MAP_PROPERTY: u16 = 0xffff;
+/// Property id of the index of properties at the end of a stream.
+pub const FOOTER_PROPERTY: u16 = 0xffff;
+
+/// Returns `true` if the property id is reserved for the crate.
+pub fn is_reserved_property(property_id: u16) -> bool {
+    property_id >= RESERVED_PROPERTY_START
+}
+
+/// Returns `true` if the type format is a custom format reserved for the crate.
+pub fn is_reserved_format(type_format: u16) -> bool {
+    type_format >= RESERVED_FORMAT_START
+}
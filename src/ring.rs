@@ -0,0 +1,281 @@
+//! Keeping the most recent frames in memory.
+//!
+//! A snapshot is written as:
+//!
+//! ```ignore
+//! blocks written before the first frame
+//! keyframe: time of the oldest frame, every property merged up to that frame
+//! the other frames, as written
+//! end of stream
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::mem;
+
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use time::Time;
+use State;
+
+/// How many frames a `RingRecorder` keeps.
+///
+/// The frame being written is always kept, even when it alone is above the limits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RingOptions {
+    /// Keep at most this many frames.
+    pub max_frames: Option<usize>,
+    /// Keep at most this many bytes of memory, as reported by `RingRecorder::memory_usage`.
+    pub max_bytes: Option<usize>,
+}
+
+impl RingOptions {
+    /// Sets the maximum number of frames.
+    pub fn max_frames(mut self, val: usize) -> RingOptions {
+        self.max_frames = Some(val);
+        self
+    }
+
+    /// Sets the maximum number of bytes of memory.
+    pub fn max_bytes(mut self, val: usize) -> RingOptions {
+        self.max_bytes = Some(val);
+        self
+    }
+}
+
+/// Records the most recent frames in memory, for dumping the recent history to a file.
+///
+/// Data is written to the recorder with the usual functions, in little endian byte order.
+/// Blocks written before the first frame, such as units and metadata, are always kept.
+/// When a frame is evicted, its blocks are merged into a keyframe,
+/// like `Frame::read_array` merges blocks, such that a snapshot is self-contained
+/// also for properties that are written as changes only.
+/// The first frame of a snapshot then reads like the original frame with `ReadMode::Merge`,
+/// which also keeps items of evicted frames past the end of a shorter array.
+/// For custom formats, the keyframe keeps the blocks of the last property,
+/// since the size of their items is unknown.
+///
+/// ```ignore
+/// let mut ring = RingRecorder::new(TIME, RingOptions::default().max_frames(100));
+/// loop {
+///     ring.begin_frame(t)?;
+///     Vector::write_array(POSITION, &pos, &mut ring)?;
+///     if failed {
+///         ring.snapshot_to(&mut File::create("crash.pool")?)?;
+///     }
+/// }
+/// ```
+pub struct RingRecorder {
+    time_property: u16,
+    options: RingOptions,
+    preamble: Vec<u8>,
+    frames: VecDeque<RingFrame>,
+    keyframe: BTreeMap<(u16, u16), Keyframe>,
+    evicted: u64,
+}
+
+struct RingFrame {
+    time: f64,
+    data: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub(crate) enum Keyframe {
+    /// Items of a built-in format, starting at offset 0.
+    Items(Vec<u8>),
+    /// Blocks of the last property of a custom format.
+    Blocks(Vec<RawBlock>),
+}
+
+impl Keyframe {
+    fn memory_usage(&self) -> usize {
+        mem::size_of::<Keyframe>() + match *self {
+            Keyframe::Items(ref data) => data.capacity(),
+            Keyframe::Blocks(ref blocks) => blocks.iter()
+                .map(|b| mem::size_of::<RawBlock>() + b.data.capacity())
+                .sum(),
+        }
+    }
+
+    /// Returns the blocks of a property.
+    pub(crate) fn blocks(&self, type_format: u16, property_id: u16) -> Vec<RawBlock> {
+        match *self {
+            Keyframe::Items(ref data) => vec![RawBlock {
+                type_format,
+                property_id,
+                offset: 0,
+                data: data.clone(),
+            }],
+            Keyframe::Blocks(ref blocks) => blocks.clone(),
+        }
+    }
+}
+
+impl RingRecorder {
+    /// Creates a new recorder.
+    pub fn new(time_property: u16, options: RingOptions) -> RingRecorder {
+        RingRecorder {
+            time_property,
+            options,
+            preamble: vec![],
+            frames: VecDeque::new(),
+            keyframe: BTreeMap::new(),
+            evicted: 0,
+        }
+    }
+
+    /// Returns the time property id.
+    pub fn time_property(&self) -> u16 {
+        self.time_property
+    }
+
+    /// Starts a new frame at time `t`, evicting the oldest frames when full.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidData` if an evicted frame
+    /// does not contain complete properties.
+    /// With `RingOptions::max_bytes`, frames are also evicted when writing.
+    pub fn begin_frame(&mut self, t: f64) -> io::Result<()> {
+        if let Some(last) = self.frames.back_mut() {
+            last.data.shrink_to_fit();
+        }
+        self.frames.push_back(RingFrame {time: t, data: vec![]});
+        self.evict()
+    }
+
+    /// Returns the number of frames kept.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the number of frames evicted.
+    pub fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// Returns the bytes of memory used, including the bookkeeping of every buffer.
+    ///
+    /// Buffers are counted by their capacity, not their length.
+    pub fn memory_usage(&self) -> usize {
+        mem::size_of::<RingRecorder>() +
+        self.preamble.capacity() +
+        self.frames.capacity() * mem::size_of::<RingFrame>() +
+        self.frames.iter().map(|f| f.data.capacity()).sum::<usize>() +
+        self.keyframe.values()
+            .map(|k| mem::size_of::<(u16, u16)>() + k.memory_usage())
+            .sum::<usize>()
+    }
+
+    /// Writes a stream of the kept frames, followed by end of stream.
+    ///
+    /// The first frame is a keyframe, containing every property merged up to that frame.
+    pub fn snapshot_to<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.preamble)?;
+        let mut frames = self.frames.iter();
+        if let Some(first) = frames.next() {
+            let mut keyframe = self.keyframe.clone();
+            merge(&mut keyframe, &first.data)?;
+            Time::write(self.time_property, first.time, w)?;
+            for (&(prop, ty), k) in &keyframe {
+                RawBlock::write_property(ty, prop, &k.blocks(ty, prop), w)?;
+            }
+        }
+        for frame in frames {
+            Time::write(self.time_property, frame.time, w)?;
+            w.write_all(&frame.data)?;
+        }
+        State::new().end_type_formats(w)
+    }
+
+    /// Evicts the oldest frames until the limits are kept.
+    fn evict(&mut self) -> io::Result<()> {
+        loop {
+            if self.frames.len() <= 1 {return Ok(())}
+            let too_many = self.options.max_frames.is_some_and(|max| self.frames.len() > max);
+            let too_big = self.options.max_bytes.is_some_and(|max| self.memory_usage() > max);
+            if !too_many && !too_big {return Ok(())}
+            if let Some(frame) = self.frames.pop_front() {
+                merge(&mut self.keyframe, &frame.data)?;
+                self.evicted += 1;
+            }
+        }
+    }
+}
+
+impl io::Write for RingRecorder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.frames.back_mut() {
+            Some(frame) => frame.data.extend_from_slice(buf),
+            None => self.preamble.extend_from_slice(buf),
+        }
+        if self.options.max_bytes.is_some() {self.evict()?}
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Merges the properties of a frame into a keyframe.
+fn merge(keyframe: &mut BTreeMap<(u16, u16), Keyframe>, mut data: &[u8]) -> io::Result<()> {
+    let invalid = |_| io::Error::from(Error::new(ErrorKind::InvalidData));
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut data).map_err(invalid)? {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut data).map_err(invalid)?;
+        merge_property(keyframe, ty, prop, blocks)?;
+    }
+    Ok(())
+}
+
+/// Merges the blocks of a frame into a keyframe, property by property.
+pub(crate) fn merge_blocks(
+    keyframe: &mut BTreeMap<(u16, u16), Keyframe>,
+    blocks: Vec<RawBlock>
+) -> io::Result<()> {
+    let mut blocks = blocks.into_iter().peekable();
+    while let Some(block) = blocks.next() {
+        let (ty, prop) = (block.type_format, block.property_id);
+        let mut property = vec![block];
+        while let Some(block) = blocks.next_if(|b| b.type_format == ty && b.property_id == prop) {
+            property.push(block);
+        }
+        merge_property(keyframe, ty, prop, property)?;
+    }
+    Ok(())
+}
+
+/// Returns the blocks of every property in a keyframe.
+pub(crate) fn keyframe_blocks(keyframe: &BTreeMap<(u16, u16), Keyframe>) -> Vec<RawBlock> {
+    keyframe.iter().flat_map(|(&(prop, ty), k)| k.blocks(ty, prop)).collect()
+}
+
+/// Merges the blocks of a property into a keyframe.
+fn merge_property(
+    keyframe: &mut BTreeMap<(u16, u16), Keyframe>,
+    ty: u16,
+    prop: u16,
+    blocks: Vec<RawBlock>
+) -> io::Result<()> {
+    let size = match raw::item_size(ty) {
+        Some(size) => size,
+        None => {
+            keyframe.insert((prop, ty), Keyframe::Blocks(blocks));
+            return Ok(());
+        }
+    };
+    // Items written with another built-in format are replaced.
+    keyframe.retain(|&(p, t), _| p != prop || t == ty || raw::item_size(t).is_none());
+    let entry = keyframe.entry((prop, ty)).or_insert_with(|| Keyframe::Items(vec![]));
+    if let Keyframe::Items(ref mut items) = *entry {
+        for block in &blocks {
+            let end = match block.offset.checked_mul(size)
+                .and_then(|start| start.checked_add(block.data.len() as u64)) {
+                Some(end) if end <= usize::MAX as u64 => end as usize,
+                _ => return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into()),
+            };
+            let start = end - block.data.len();
+            if items.len() < end {items.resize(end, 0)}
+            items[start..end].copy_from_slice(&block.data);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! Run-length encoded arrays.
+//!
+//! The data of a block starts with the type format of the items (u16),
+//! followed by runs of count (u64) and item.
+//! The offset instance id of the block is the instance of the first run.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use read_write::{self, Array, Element};
+use reserved::RLE_FORMAT;
+use Bytes;
+use State;
+
+/// Writes an array, using run-length encoding when it is smaller than a plain block.
+pub fn write_rle<T, W>(property_id: u16, items: &[T], w: &mut W) -> io::Result<()>
+    where T: Element + PartialEq, W: io::Write
+{
+    write_rle_with(property_id, items, 1.0, w)
+}
+
+/// Writes an array, using run-length encoding when the encoded size
+/// is at most `max_ratio` of the size of a plain block.
+///
+/// Otherwise, a plain block of the type format of the items is written.
+pub fn write_rle_with<T, W>(
+    property_id: u16,
+    items: &[T],
+    max_ratio: f64,
+    w: &mut W
+) -> io::Result<()>
+    where T: Element + PartialEq, W: io::Write
+{
+    let (format, size) = match T::format() {
+        Some(x) => x,
+        None => return Err(io::ErrorKind::InvalidInput.into()),
+    };
+    let mut runs: Vec<(u64, &T)> = vec![];
+    for item in items {
+        match runs.last_mut() {
+            Some(&mut (ref mut count, last)) if *last == *item => *count += 1,
+            _ => runs.push((1, item)),
+        }
+    }
+    let plain = size * items.len() as u64;
+    let encoded = 2 + (8 + size) * runs.len() as u64;
+    let (type_format, data) = if encoded as f64 <= plain as f64 * max_ratio {
+        let mut data = Vec::with_capacity(encoded as usize);
+        data.extend_from_slice(&format.to_le_bytes());
+        for (count, item) in runs {
+            data.extend_from_slice(&count.to_le_bytes());
+            item.write_element(&mut data)?;
+        }
+        (RLE_FORMAT, data)
+    } else {
+        let mut data = Vec::with_capacity(plain as usize);
+        for item in items {
+            item.write_element(&mut data)?;
+        }
+        (format, data)
+    };
+    RawBlock::write_property(type_format, property_id, &[RawBlock {
+        type_format,
+        property_id,
+        offset: 0,
+        data,
+    }], w)
+}
+
+/// Expands a run-length encoded block into a plain block.
+///
+/// `max_bytes` limits the size of the expanded data.
+/// Returns an error if the memory for the expanded data can not be allocated.
+pub fn expand_rle(block: &RawBlock, max_bytes: Option<u64>) -> io::Result<RawBlock> {
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+    };
+    let data = &block.data;
+    if block.type_format != RLE_FORMAT || data.len() < 2 {return Err(err())}
+    let format = u16::from_le_bytes([data[0], data[1]]);
+    let size = match raw::item_size(format) {
+        Some(size) => size as usize,
+        None => return Err(err()),
+    };
+    if !(data.len() - 2).is_multiple_of(8 + size) {return Err(err())}
+    let mut total: u64 = 0;
+    for run in data[2..].chunks(8 + size) {
+        let mut count = [0; 8];
+        count.copy_from_slice(&run[..8]);
+        total = match u64::from_le_bytes(count).checked_mul(size as u64)
+            .and_then(|n| n.checked_add(total)) {
+            Some(x) if x <= max_bytes.unwrap_or(usize::MAX as u64) => x,
+            _ => return Err(Error::new(ErrorKind::LimitExceeded)
+                .with_property(block.property_id)
+                .into()),
+        };
+    }
+    let mut res = vec![];
+    if res.try_reserve_exact(total as usize).is_err() {return Err(err())}
+    for run in data[2..].chunks(8 + size) {
+        let mut count = [0; 8];
+        count.copy_from_slice(&run[..8]);
+        for _ in 0..u64::from_le_bytes(count) {
+            res.extend_from_slice(&run[8..]);
+        }
+    }
+    Ok(RawBlock {type_format: format, property_id: block.property_id, offset: block.offset, data: res})
+}
+
+/// Returns the number of instances of run-length encoded data.
+///
+/// Returns `None` if the data is not valid.
+pub(crate) fn rle_instances(data: &[u8]) -> Option<u64> {
+    if data.len() < 2 {return None}
+    let size = raw::item_size(u16::from_le_bytes([data[0], data[1]]))? as usize;
+    if !(data.len() - 2).is_multiple_of(8 + size) {return None}
+    let mut total: u64 = 0;
+    for run in data[2..].chunks(8 + size) {
+        let mut count = [0; 8];
+        count.copy_from_slice(&run[..8]);
+        total = total.checked_add(u64::from_le_bytes(count))?;
+    }
+    Some(total)
+}
+
+/// Reads an array written by `write_rle`, with or without run-length encoding.
+///
+/// Blocks are applied at their offsets, growing the array when needed.
+pub fn read_rle<T, A, R>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    arr: &mut A,
+    r: &mut R
+) -> io::Result<()>
+    where T: Element, A: Array<Item = T>, R: io::Read
+{
+    for block in RawBlock::read_property(state, ty, property_id, r)? {
+        let block = if ty == RLE_FORMAT {expand_rle(&block, None)?} else {block};
+        let items = block.decode::<T>()?;
+        let n = items.len();
+        for (i, item) in items.into_iter().enumerate() {
+            let ind = match block.offset.checked_add(i as u64) {
+                Some(x) if x < usize::MAX as u64 => x as usize,
+                _ => return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(property_id).into()),
+            };
+            read_write::make_room(arr, ind, ind.saturating_add(n - i))?;
+            arr.set(ind, item);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+//! Writing a stream split into several files.
+//!
+//! Every file is a complete stream:
+//!
+//! ```ignore
+//! file header
+//! sticky blocks, e.g. units, metadata and namespace manifests
+//! frames
+//! end of stream
+//! ```
+
+use std::io;
+
+use endian::Endian;
+use raw::{self, RawBlock};
+use time::Time;
+use writer::{PoolWriter, WriteOptions};
+
+/// When to start a new file.
+///
+/// A new file is only started at the start of a frame,
+/// and never before the first frame of a file is written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RotateOptions {
+    /// Start a new file when the current file has at least this many bytes,
+    /// not counting the file header.
+    pub max_bytes: Option<u64>,
+    /// Start a new file when the current file has this many frames.
+    pub max_frames: Option<u64>,
+}
+
+impl RotateOptions {
+    /// Sets the maximum number of bytes per file.
+    pub fn max_bytes(mut self, val: u64) -> RotateOptions {
+        self.max_bytes = Some(val);
+        self
+    }
+
+    /// Sets the maximum number of frames per file.
+    pub fn max_frames(mut self, val: u64) -> RotateOptions {
+        self.max_frames = Some(val);
+        self
+    }
+}
+
+/// Writes frames to a sequence of files, starting a new file when the current one is full.
+///
+/// Files are created by a callback that gets the index of the file.
+/// A finished file gets its end of stream before the next file is created,
+/// such that after a crash at most the last file is not terminated.
+/// The files can be read as one stream with `ChainedReader`,
+/// or each file on its own.
+///
+/// ```ignore
+/// let opts = RotateOptions::default().max_frames(1000);
+/// let mut w = RotatingWriter::new(TIME, opts, |i| {
+///     File::create(format!("run.{:04}.pool", i)).map(BufWriter::new)
+/// })?;
+/// w.write_sticky(|buf| units.write_units(buf))?;
+/// for step in 0..steps {
+///     w.begin_frame(step as f64 * dt)?;
+///     Vector::write_array(POSITION, &pos, &mut w)?;
+/// }
+/// w.finish()?;
+/// ```
+pub struct RotatingWriter<W: io::Write, F> {
+    create: F,
+    current: Option<PoolWriter<W>>,
+    endian: Endian,
+    write_options: WriteOptions,
+    options: RotateOptions,
+    time_property: u16,
+    sticky: Vec<u8>,
+    files: u64,
+    frames: u64,
+    file_frames: u64,
+    file_bytes: u64,
+}
+
+impl<W, F> RotatingWriter<W, F>
+    where W: io::Write, F: FnMut(u64) -> io::Result<W>
+{
+    /// Creates a new writer and the first file.
+    pub fn new(time_property: u16, options: RotateOptions, mut create: F) -> io::Result<Self> {
+        let first = create(0)?;
+        Ok(RotatingWriter {
+            create,
+            current: Some(PoolWriter::new(first)),
+            endian: Endian::default(),
+            write_options: WriteOptions::default(),
+            options,
+            time_property,
+            sticky: vec![],
+            files: 1,
+            frames: 0,
+            file_frames: 0,
+            file_bytes: 0,
+        })
+    }
+
+    /// Sets the byte order of the files.
+    ///
+    /// This should be set before anything is written.
+    pub fn with_endianness(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self.current = self.current.take().map(|w| w.with_endianness(endian));
+        self
+    }
+
+    /// Sets the options for writing.
+    pub fn with_options(mut self, opts: WriteOptions) -> Self {
+        self.write_options = opts;
+        self.current = self.current.take().map(|w| w.with_options(opts));
+        self
+    }
+
+    /// Writes blocks to the current file and repeats them at the start of every new file.
+    ///
+    /// The callback writes complete properties in little endian byte order,
+    /// e.g. `units.write_units(buf)`.
+    /// Returns an error of kind `io::ErrorKind::InvalidInput`
+    /// if the bytes end in the middle of a property or contain an end of stream.
+    pub fn write_sticky<G>(&mut self, write: G) -> io::Result<()>
+        where G: FnOnce(&mut Vec<u8>) -> io::Result<()>
+    {
+        let mut buf = vec![];
+        write(&mut buf)?;
+        check_properties(&buf)?;
+        io::Write::write_all(self, &buf)?;
+        self.sticky.extend_from_slice(&buf);
+        Ok(())
+    }
+
+    /// Starts a new frame at time `t`, first starting a new file if the current one is full.
+    pub fn begin_frame(&mut self, t: f64) -> io::Result<()> {
+        let full = self.options.max_bytes.is_some_and(|max| self.file_bytes >= max) ||
+            self.options.max_frames.is_some_and(|max| self.file_frames >= max);
+        if full && self.file_frames > 0 {
+            self.rotate()?;
+        }
+        Time::write(self.time_property, t, self)?;
+        self.frames += 1;
+        self.file_frames += 1;
+        Ok(())
+    }
+
+    /// Ends the current file and starts the next one.
+    ///
+    /// Should only be called between frames.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        let w = self.current.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        w.finish()?;
+        let next = (self.create)(self.files)?;
+        self.files += 1;
+        self.file_frames = 0;
+        self.file_bytes = 0;
+        let mut w = PoolWriter::new(next)
+            .with_endianness(self.endian)
+            .with_options(self.write_options);
+        w.write_header()?;
+        self.current = Some(w);
+        let sticky = std::mem::take(&mut self.sticky);
+        let res = io::Write::write_all(self, &sticky);
+        self.sticky = sticky;
+        res
+    }
+
+    /// Returns the time property id.
+    pub fn time_property(&self) -> u16 {
+        self.time_property
+    }
+
+    /// Returns the number of files created.
+    pub fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Returns the number of frames written to all files.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns the number of frames written to the current file.
+    pub fn file_frames(&self) -> u64 {
+        self.file_frames
+    }
+
+    /// Gets a reference to the writer of the current file.
+    ///
+    /// Returns `None` if creating the current file failed.
+    pub fn get_ref(&self) -> Option<&W> {
+        self.current.as_ref().map(|w| w.get_ref())
+    }
+
+    /// Writes end of stream to the current file and returns its writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let w = self.current.take().ok_or(io::ErrorKind::BrokenPipe)?;
+        w.finish()
+    }
+}
+
+impl<W, F> io::Write for RotatingWriter<W, F>
+    where W: io::Write, F: FnMut(u64) -> io::Result<W>
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let w = self.current.as_mut().ok_or(io::ErrorKind::BrokenPipe)?;
+        let n = w.write(buf)?;
+        self.file_bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current {
+            Some(ref mut w) => w.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Checks that bytes contain complete properties only.
+fn check_properties(mut data: &[u8]) -> io::Result<()> {
+    let invalid = |_| io::Error::from(io::ErrorKind::InvalidInput);
+    while !data.is_empty() {
+        match raw::read_property_header(&mut data).map_err(invalid)? {
+            Some((state, ty, prop)) => {
+                RawBlock::read_property(state, ty, prop, &mut data).map_err(invalid)?;
+            }
+            None => return Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+    Ok(())
+}
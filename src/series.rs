@@ -0,0 +1,113 @@
+//! Extracting the values of one instance over time.
+
+use std::io::{self, SeekFrom};
+
+use dyn_value::{self, DynValue};
+use index::Index;
+use lazy::BlockLocation;
+use raw;
+use read_write::Element;
+
+/// Options for extracting a time series.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeriesOptions {
+    /// Repeat the last value in frames where the instance was not written,
+    /// instead of skipping those frames.
+    pub carry_forward: bool,
+}
+
+impl SeriesOptions {
+    /// Sets whether the last value is repeated in frames where the instance was not written.
+    pub fn carry_forward(mut self, val: bool) -> SeriesOptions {
+        self.carry_forward = val;
+        self
+    }
+}
+
+/// Returns the time and value of an instance of a property in every frame.
+///
+/// Frames start with the time property.
+/// Only the block headers of every frame are read,
+/// and the data of one item per frame, such that whole arrays are not decoded.
+/// When several blocks in a frame contain the instance, the last one wins.
+/// Blocks with custom formats and data before the first frame are skipped.
+///
+/// ```ignore
+/// let series = extract_series(&mut file, TIME, ENERGY, 3, &SeriesOptions::default())?;
+/// ```
+pub fn extract_series<R: io::Read + io::Seek>(
+    r: &mut R,
+    time_property: u16,
+    target_property: u16,
+    instance: u64,
+    opts: &SeriesOptions
+) -> io::Result<Vec<(f64, DynValue)>> {
+    series(r, time_property, target_property, instance, opts, |ty, r| {
+        let mut data = vec![0; raw::item_size(ty).unwrap_or(0) as usize];
+        r.read_exact(&mut data)?;
+        DynValue::new(ty, data).ok_or_else(|| io::ErrorKind::InvalidData.into())
+    })
+}
+
+/// Returns the time and value of an instance of a property in every frame, as an element type.
+///
+/// See `extract_series`.
+/// Returns an error of kind `ErrorKind::TypeMismatch` if a block containing the instance
+/// has another type format.
+pub fn extract_series_as<T: Element + Clone, R: io::Read + io::Seek>(
+    r: &mut R,
+    time_property: u16,
+    target_property: u16,
+    instance: u64,
+    opts: &SeriesOptions
+) -> io::Result<Vec<(f64, T)>> {
+    series(r, time_property, target_property, instance, opts, |ty, r| {
+        dyn_value::check_format::<T>(ty)?;
+        let mut val: T = Default::default();
+        val.read_element(r)?;
+        Ok(val)
+    })
+}
+
+fn series<R, T, F>(
+    r: &mut R,
+    time_property: u16,
+    target_property: u16,
+    instance: u64,
+    opts: &SeriesOptions,
+    mut read: F
+) -> io::Result<Vec<(f64, T)>>
+    where R: io::Read + io::Seek, T: Clone, F: FnMut(u16, &mut R) -> io::Result<T>
+{
+    let index = Index::build(r, time_property, Some(time_property))?;
+    let mut res = vec![];
+    let mut last: Option<T> = None;
+    for n in 0..index.frame_count() {
+        let frame = index.lazy_frame(n, r)?;
+        let t = match frame.time() {
+            Some(t) => t,
+            None => continue,
+        };
+        match locate(frame.blocks_of(target_property), instance) {
+            Some((ty, position)) => {
+                r.seek(SeekFrom::Start(position))?;
+                let val = read(ty, r)?;
+                res.push((t, val.clone()));
+                last = Some(val);
+            }
+            None => if opts.carry_forward {
+                if let Some(ref val) = last {res.push((t, val.clone()))}
+            },
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the type format and byte position of an instance in the last block containing it.
+pub(crate) fn locate(blocks: &[BlockLocation], instance: u64) -> Option<(u16, u64)> {
+    blocks.iter().rev().find_map(|block| {
+        let size = raw::item_size(block.type_format)?;
+        let i = instance.checked_sub(block.offset)?;
+        if i < block.bytes / size {Some((block.type_format, block.position + i * size))} else {None}
+    })
+}
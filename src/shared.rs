@@ -0,0 +1,113 @@
+//! Writing a stream from several threads.
+
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+
+use parse::{Event, Parser};
+use State;
+
+/// A writer shared between threads.
+///
+/// Each thread writes through its own `LocalWriter`,
+/// which buffers a property until its end of bytes
+/// and then writes it to the underlying writer in one piece.
+/// Properties are never interleaved, but properties of different threads
+/// are written in the order they are completed:
+///
+/// ```ignore
+/// let w = SharedWriter::new(file);
+/// thread::scope(|s| {
+///     s.spawn(|| Vector::write_array(POSITION, &pos, &mut w.local()));
+///     s.spawn(|| Vector::write_array(VELOCITY, &vel, &mut w.local()));
+/// });
+/// w.barrier()?;
+/// let file = w.finish()?;
+/// ```
+pub struct SharedWriter<W: io::Write> {
+    inner: Mutex<W>,
+}
+
+impl<W: io::Write> SharedWriter<W> {
+    /// Creates a new shared writer.
+    pub fn new(inner: W) -> SharedWriter<W> {
+        SharedWriter {inner: Mutex::new(inner)}
+    }
+
+    /// Returns a writer for the current thread.
+    pub fn local(&self) -> LocalWriter<'_, W> {
+        LocalWriter {
+            shared: self,
+            parser: Parser::new(),
+            buf: vec![],
+        }
+    }
+
+    /// Flushes the underlying writer.
+    ///
+    /// Properties completed before the call, in any thread,
+    /// are written before properties completed after the call.
+    /// Call this between frames, after the threads writing to a frame are done.
+    pub fn barrier(&self) -> io::Result<()> {
+        self.lock()?.flush()
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        let mut inner = self.inner.into_inner()
+            .map_err(|_| io::Error::other("writer poisoned"))?;
+        State::new().end_type_formats(&mut inner)?;
+        inner.flush()?;
+        Ok(inner)
+    }
+
+    fn lock(&self) -> io::Result<MutexGuard<'_, W>> {
+        self.inner.lock().map_err(|_| io::Error::other("writer poisoned"))
+    }
+}
+
+/// Writes complete properties to a `SharedWriter`.
+///
+/// A property that is not complete when the writer is dropped is not written.
+/// End of stream is written by `SharedWriter::finish`, and refused here.
+pub struct LocalWriter<'a, W: 'a + io::Write> {
+    shared: &'a SharedWriter<W>,
+    parser: Parser,
+    buf: Vec<u8>,
+}
+
+impl<'a, W: io::Write> LocalWriter<'a, W> {
+    /// Returns `true` if a property is buffered but not complete.
+    pub fn is_pending(&self) -> bool {
+        !self.buf.is_empty()
+    }
+}
+
+impl<'a, W: io::Write> io::Write for LocalWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = buf;
+        while !data.is_empty() {
+            // Header fields are fed one byte at a time to find where properties end.
+            let n = (self.parser.remaining() as usize).min(data.len()).max(1);
+            let mut event = None;
+            let mut parser = self.parser.clone();
+            parser.consume(&data[..n], |e| event = Some(e));
+            if event == Some(Event::End) {
+                return Err(io::ErrorKind::InvalidInput.into());
+            }
+            self.parser = parser;
+            self.buf.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if event == Some(Event::EndBytes) {
+                let res = self.shared.lock().and_then(|mut w| w.write_all(&self.buf));
+                // A property that failed is not retried, such that the writer can continue.
+                self.buf.clear();
+                res?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.shared.lock()?.flush()
+    }
+}
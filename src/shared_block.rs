@@ -0,0 +1,150 @@
+//! Blocks with reference-counted data, for sharing between consumers without copying.
+//!
+//! A `SharedBlock` holds its data as `bytes::Bytes`,
+//! such that cloning a block or slicing the items of one instance does not copy the data,
+//! and slices stay valid after the reader and the block are dropped.
+
+use std::io::{self, Read};
+use std::ops::Range;
+
+use bytes;
+
+use raw::{self, RawBlock};
+use read_write::Element;
+use Bytes;
+use State;
+
+/// A block of data that is not decoded, with reference-counted data.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SharedBlock {
+    /// Type format of the property.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// Offset instance id.
+    pub offset: u64,
+    /// Data.
+    pub data: bytes::Bytes,
+}
+
+impl From<RawBlock> for SharedBlock {
+    /// Converts a block without copying the data.
+    fn from(block: RawBlock) -> SharedBlock {
+        SharedBlock {
+            type_format: block.type_format,
+            property_id: block.property_id,
+            offset: block.offset,
+            data: block.data.into(),
+        }
+    }
+}
+
+impl SharedBlock {
+    /// Returns the size of each item in bytes.
+    ///
+    /// Returns `None` for custom formats.
+    pub fn item_size(&self) -> Option<u64> {
+        raw::item_size(self.type_format)
+    }
+
+    /// Returns the number of items in the data.
+    ///
+    /// Returns `None` for custom formats.
+    pub fn items(&self) -> Option<u64> {
+        self.item_size().map(|s| self.data.len() as u64 / s)
+    }
+
+    /// Returns the data of the items at a range of indices within the block, without copying.
+    ///
+    /// Returns `None` for custom formats and ranges outside the block.
+    pub fn item_range(&self, range: Range<u64>) -> Option<bytes::Bytes> {
+        let size = self.item_size()?;
+        if range.start > range.end || range.end > self.items()? {return None}
+        Some(self.data.slice((range.start * size) as usize..(range.end * size) as usize))
+    }
+
+    /// Returns the data of the item at an index within the block, without copying.
+    ///
+    /// Returns `None` for custom formats and indices outside the block.
+    pub fn item(&self, index: u64) -> Option<bytes::Bytes> {
+        self.item_range(index..index.checked_add(1)?)
+    }
+
+    /// Returns the data of the item of an instance, without copying.
+    ///
+    /// Returns `None` for custom formats and instances outside the block.
+    pub fn instance(&self, id: u64) -> Option<bytes::Bytes> {
+        self.item(id.checked_sub(self.offset)?)
+    }
+
+    /// Returns a block with the items at a range of indices within the block, without copying.
+    ///
+    /// Returns `None` for custom formats and ranges outside the block.
+    pub fn slice(&self, range: Range<u64>) -> Option<SharedBlock> {
+        let offset = self.offset.checked_add(range.start)?;
+        Some(SharedBlock {
+            type_format: self.type_format,
+            property_id: self.property_id,
+            offset,
+            data: self.item_range(range)?,
+        })
+    }
+
+    /// Decodes the data into elements.
+    ///
+    /// Returns an error if the type format does not match the element type.
+    pub fn decode<T: Element>(&self) -> io::Result<Vec<T>> {
+        let n = raw::item_count::<T>(self.type_format, self.property_id, self.data.len())?;
+        let mut r = &self.data[..];
+        let mut res = Vec::with_capacity(n);
+        for _ in 0..n {
+            let mut val: T = Default::default();
+            val.read_element(&mut r)?;
+            res.push(val);
+        }
+        Ok(res)
+    }
+
+    /// Copies the block into a block with owned data.
+    pub fn to_raw(&self) -> RawBlock {
+        RawBlock {
+            type_format: self.type_format,
+            property_id: self.property_id,
+            offset: self.offset,
+            data: self.data.to_vec(),
+        }
+    }
+
+    /// Reads all blocks of a property, until the end of bytes.
+    ///
+    /// The data of all blocks is read into one buffer,
+    /// and every block holds a slice of the buffer.
+    pub fn read_property<R: io::Read>(
+        state: State<Bytes>,
+        type_format: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<Vec<SharedBlock>> {
+        let mut ranges = vec![];
+        let mut data = vec![];
+        let mut state = state;
+        loop {
+            let (header, data_state) = state.read_block_header(type_format, property_id, r)?;
+            if header.is_end() {break}
+            let start = data.len();
+            r.by_ref().take(header.bytes).read_to_end(&mut data)?;
+            if ((data.len() - start) as u64) < header.bytes {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            ranges.push((header.offset, start..data.len()));
+            state = data_state.end_data();
+        }
+        let data = bytes::Bytes::from(data);
+        Ok(ranges.into_iter().map(|(offset, range)| SharedBlock {
+            type_format,
+            property_id,
+            offset,
+            data: data.slice(range),
+        }).collect())
+    }
+}
@@ -0,0 +1,419 @@
+//! Streaming between processes through shared memory.
+//!
+//! A `ShmWriter` writes a stream into a ring buffer in a file mapped into memory,
+//! and a `ShmReader` in another process reads it from the same file.
+//! The ring carries the bytes of the stream unchanged, so the writer and reader
+//! are used like a file or socket, and either side can be replaced by one:
+//!
+//! ```ignore
+//! // Producer
+//! let mut w = PoolWriter::new(ShmWriter::create("/dev/shm/particles", 1 << 20)?);
+//! Vector::write_array(POSITION, &pos, &mut w)?;
+//!
+//! // Consumer
+//! let mut r = PoolReader::new(ShmReader::open("/dev/shm/particles")?);
+//! ```
+//!
+//! The segment starts with a header of counters that only grow:
+//! the position written, the position read and the start of the latest property.
+//! A sequence number in the header is increased every time a writer creates the segment,
+//! such that a reader detects that the writer restarted.
+//!
+//! There must be at most one writer and one reader of a segment at a time.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::Path;
+use std::ptr;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use memmap2::MmapMut;
+
+use error::{Error, ErrorKind, Field};
+use parse::{Event, Parser};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"binpshm1");
+
+// Offsets of the counters in the header.
+const MAGIC_AT: usize = 0;
+const CAPACITY_AT: usize = 8;
+const SEQUENCE_AT: usize = 16;
+// The position the writer is writing up to, which is stored before writing,
+// such that a reader can check whether the data it copied was overwritten.
+const RESERVE_AT: usize = 24;
+const WRITE_AT: usize = 32;
+const READ_AT: usize = 40;
+const BOUNDARY_AT: usize = 48;
+const CLOSED_AT: usize = 56;
+const HEADER: usize = 64;
+
+/// Determines what the writer does when the reader lags a whole ring behind.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Overflow {
+    /// Wait until the reader has read enough data.
+    Block,
+    /// Overwrite data the reader has not read.
+    ///
+    /// The reader gets an error of kind `ErrorKind::Overrun`,
+    /// and continues at the next property with `ShmReader::resync`.
+    Overwrite,
+}
+
+struct Segment {
+    map: MmapMut,
+    capacity: u64,
+}
+
+impl Segment {
+    fn map(file: &File) -> io::Result<MmapMut> {
+        // The file is only changed through the mapping, by one writer and one reader.
+        unsafe {MmapMut::map_mut(file)}
+    }
+
+    fn counter(&self, at: usize) -> &AtomicU64 {
+        // The mapping is page aligned and the counters are 8 bytes apart.
+        unsafe {&*(self.map.as_ptr().add(at) as *const AtomicU64)}
+    }
+
+    fn load(&self, at: usize) -> u64 {
+        self.counter(at).load(Ordering::Acquire)
+    }
+
+    fn store(&self, at: usize, val: u64) {
+        self.counter(at).store(val, Ordering::Release)
+    }
+
+    /// Copies data into the ring at a stream position, wrapping around at the end.
+    fn copy_in(&mut self, pos: u64, data: &[u8]) {
+        let start = (pos % self.capacity) as usize;
+        let first = data.len().min(self.capacity as usize - start);
+        unsafe {
+            let ring = self.map.as_mut_ptr().add(HEADER);
+            ptr::copy_nonoverlapping(data.as_ptr(), ring.add(start), first);
+            ptr::copy_nonoverlapping(data[first..].as_ptr(), ring, data.len() - first);
+        }
+    }
+
+    /// Copies data out of the ring at a stream position, wrapping around at the end.
+    fn copy_out(&self, pos: u64, buf: &mut [u8]) {
+        let start = (pos % self.capacity) as usize;
+        let first = buf.len().min(self.capacity as usize - start);
+        unsafe {
+            let ring = self.map.as_ptr().add(HEADER);
+            ptr::copy_nonoverlapping(ring.add(start), buf.as_mut_ptr(), first);
+            ptr::copy_nonoverlapping(ring, buf[first..].as_mut_ptr(), buf.len() - first);
+        }
+    }
+}
+
+/// Waits with increasing pauses, up to a timeout.
+struct Backoff {
+    start: Instant,
+    timeout: Option<Duration>,
+    spins: u32,
+}
+
+impl Backoff {
+    fn new(timeout: Option<Duration>) -> Backoff {
+        Backoff {start: Instant::now(), timeout, spins: 0}
+    }
+
+    fn wait(&mut self) -> io::Result<()> {
+        if self.timeout.is_some_and(|t| self.start.elapsed() >= t) {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        if self.spins < 64 {
+            self.spins += 1;
+            thread::yield_now();
+        } else {
+            thread::sleep(Duration::from_micros(100));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the magic number and sequence number of an existing segment.
+fn read_header(path: &Path) -> Option<(u64, u64)> {
+    let mut header = [0; HEADER];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+    let field = |at: usize| {
+        let mut val = [0; 8];
+        val.copy_from_slice(&header[at..at + 8]);
+        u64::from_le_bytes(val)
+    };
+    if field(MAGIC_AT) != MAGIC {return None}
+    Some((field(CAPACITY_AT), field(SEQUENCE_AT)))
+}
+
+/// Writes a stream into a shared memory segment.
+///
+/// The segment is closed when the writer is dropped,
+/// such that the reader gets the end of file after reading the rest of the data.
+pub struct ShmWriter {
+    segment: Segment,
+    sequence: u64,
+    write: u64,
+    overflow: Overflow,
+    timeout: Option<Duration>,
+    parser: Parser,
+}
+
+impl ShmWriter {
+    /// Creates a segment with a ring of `capacity` bytes, or restarts an existing one.
+    ///
+    /// A segment is a file, which is in memory when created in a memory file system,
+    /// such as `/dev/shm` on Linux.
+    /// When the file is a segment with the same capacity, its sequence number is increased
+    /// and the ring is emptied, such that a reader detects the restart.
+    /// A segment with another capacity is replaced by a new file,
+    /// after increasing its sequence number, and readers must open the new file.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` for a capacity of 0.
+    pub fn create<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<ShmWriter> {
+        let path = path.as_ref();
+        if capacity == 0 {return Err(io::ErrorKind::InvalidInput.into())}
+        let previous = read_header(path);
+        let sequence = previous.map(|(_, s)| s.wrapping_add(1).max(1)).unwrap_or(1);
+        if let Some((old_capacity, _)) = previous {
+            if old_capacity != capacity as u64 {
+                let file = OpenOptions::new().read(true).write(true).open(path)?;
+                let old = Segment {map: Segment::map(&file)?, capacity: old_capacity};
+                old.store(SEQUENCE_AT, sequence);
+                old.store(CLOSED_AT, 1);
+                fs::remove_file(path)?;
+            }
+        }
+
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        file.set_len((HEADER + capacity) as u64)?;
+        let segment = Segment {map: Segment::map(&file)?, capacity: capacity as u64};
+        // The sequence number changes first, such that a reader of the previous stream
+        // does not mistake the reset counters for its own.
+        segment.store(SEQUENCE_AT, sequence);
+        segment.store(CLOSED_AT, 0);
+        segment.store(RESERVE_AT, 0);
+        segment.store(WRITE_AT, 0);
+        segment.store(READ_AT, 0);
+        segment.store(BOUNDARY_AT, 0);
+        segment.store(CAPACITY_AT, capacity as u64);
+        segment.store(MAGIC_AT, MAGIC);
+        Ok(ShmWriter {
+            segment,
+            sequence,
+            write: 0,
+            overflow: Overflow::Block,
+            timeout: None,
+            parser: Parser::new(),
+        })
+    }
+
+    /// Sets what happens when the reader lags a whole ring behind.
+    ///
+    /// The default is `Overflow::Block`.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets how long to wait for the reader with `Overflow::Block`,
+    /// after which writing returns an error of kind `io::ErrorKind::TimedOut`.
+    ///
+    /// By default, the writer waits forever.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the sequence number of the stream.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the number of bytes written.
+    pub fn position(&self) -> u64 {
+        self.write
+    }
+
+    /// Returns the number of bytes written that the reader has not read.
+    pub fn lag(&self) -> u64 {
+        self.write.saturating_sub(self.segment.load(READ_AT))
+    }
+
+    /// Updates the start of the latest property while data passes through.
+    fn track(&mut self, mut data: &[u8]) {
+        let mut boundary = None;
+        while !data.is_empty() {
+            let n = if self.parser.field() == Field::Data {
+                self.parser.remaining().min(data.len() as u64) as usize
+            } else {1};
+            let mut property = false;
+            self.parser.consume(&data[..n], |event| property = matches!(event, Event::Property {..}));
+            data = &data[n..];
+            // The property header is the type format and property id, 4 bytes.
+            if property {boundary = Some(self.parser.position() - 4)}
+        }
+        if let Some(pos) = boundary {self.segment.store(BOUNDARY_AT, pos)}
+    }
+}
+
+impl io::Write for ShmWriter {
+    /// Returns an error of kind `ErrorKind::ProducerRestarted`
+    /// when another writer has created the segment since.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {return Ok(0)}
+        if self.segment.load(SEQUENCE_AT) != self.sequence {
+            return Err(Error::new(ErrorKind::ProducerRestarted).with_position(self.write).into());
+        }
+        let capacity = self.segment.capacity;
+        let n = match self.overflow {
+            Overflow::Block => {
+                let mut backoff = Backoff::new(self.timeout);
+                loop {
+                    let unread = self.write.saturating_sub(self.segment.load(READ_AT));
+                    let free = capacity.saturating_sub(unread);
+                    if free > 0 {break free.min(buf.len() as u64) as usize}
+                    backoff.wait()?;
+                }
+            }
+            Overflow::Overwrite => capacity.min(buf.len() as u64) as usize,
+        };
+        let end = self.write + n as u64;
+        self.segment.counter(RESERVE_AT).store(end, Ordering::Relaxed);
+        atomic::fence(Ordering::Release);
+        self.segment.copy_in(self.write, &buf[..n]);
+        self.segment.store(WRITE_AT, end);
+        self.write = end;
+        self.track(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ShmWriter {
+    fn drop(&mut self) {
+        if self.segment.load(SEQUENCE_AT) == self.sequence {self.segment.store(CLOSED_AT, 1)}
+    }
+}
+
+/// Reads a stream from a shared memory segment.
+///
+/// Reading waits for the writer until data is available,
+/// and returns the end of file when the writer has closed the segment and all data is read.
+pub struct ShmReader {
+    segment: Segment,
+    sequence: u64,
+    pos: u64,
+    timeout: Option<Duration>,
+}
+
+impl ShmReader {
+    /// Opens a segment created by a `ShmWriter`, reading from the start of the stream.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidData` if the file is not a segment,
+    /// which includes a segment that is being created.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ShmReader> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len();
+        if len < HEADER as u64 {return Err(Error::new(ErrorKind::InvalidData).into())}
+        let segment = Segment {map: Segment::map(&file)?, capacity: len - HEADER as u64};
+        if segment.load(MAGIC_AT) != MAGIC || segment.load(CAPACITY_AT) != segment.capacity ||
+           segment.capacity == 0 {
+            return Err(Error::new(ErrorKind::InvalidData).into());
+        }
+        let sequence = segment.load(SEQUENCE_AT);
+        Ok(ShmReader {segment, sequence, pos: 0, timeout: None})
+    }
+
+    /// Sets how long to wait for data,
+    /// after which reading returns an error of kind `io::ErrorKind::TimedOut`.
+    ///
+    /// By default, the reader waits forever.
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the sequence number of the stream being read.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the number of bytes read.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Returns the number of bytes written that have not been read.
+    pub fn lag(&self) -> u64 {
+        self.segment.load(WRITE_AT).saturating_sub(self.pos)
+    }
+
+    fn check_sequence(&self) -> io::Result<()> {
+        if self.segment.load(SEQUENCE_AT) != self.sequence {
+            return Err(Error::new(ErrorKind::ProducerRestarted).with_position(self.pos).into());
+        }
+        Ok(())
+    }
+
+    /// Moves to a position where a new reader can start, after an overrun or a restart.
+    ///
+    /// After the writer restarted, this is the start of the new stream, when it is in the ring.
+    /// Otherwise, this is the start of the latest property written,
+    /// and the reader continues with the rest of the stream from there.
+    /// Returns `false` when the position has been overwritten,
+    /// for example while the writer writes a property larger than the ring,
+    /// in which case resyncing can be retried later.
+    pub fn resync(&mut self) -> bool {
+        let sequence = self.segment.load(SEQUENCE_AT);
+        let boundary = self.segment.load(BOUNDARY_AT);
+        let write = self.segment.load(WRITE_AT);
+        if self.segment.load(SEQUENCE_AT) != sequence {return false}
+        let start = if sequence != self.sequence && write <= self.segment.capacity {0}
+            else if write - boundary <= self.segment.capacity {boundary}
+            else {return false};
+        self.sequence = sequence;
+        self.pos = start;
+        self.segment.store(READ_AT, start);
+        true
+    }
+}
+
+impl io::Read for ShmReader {
+    /// Returns an error of kind `ErrorKind::Overrun` when the writer overwrote data
+    /// that was not read, and of kind `ErrorKind::ProducerRestarted` when the writer
+    /// restarted the stream.
+    /// Both are recovered from with `resync`.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {return Ok(0)}
+        let capacity = self.segment.capacity;
+        let mut backoff = Backoff::new(self.timeout);
+        loop {
+            let write = self.segment.load(WRITE_AT);
+            self.check_sequence()?;
+            if write > self.pos {
+                let overrun = || Error::new(ErrorKind::Overrun).with_position(self.pos).into();
+                if write - self.pos > capacity {return Err(overrun())}
+                let n = (write - self.pos).min(buf.len() as u64) as usize;
+                self.segment.copy_out(self.pos, &mut buf[..n]);
+                atomic::fence(Ordering::Acquire);
+                let reserve = self.segment.counter(RESERVE_AT).load(Ordering::Relaxed);
+                self.check_sequence()?;
+                if reserve.saturating_sub(self.pos) > capacity {return Err(overrun())}
+                self.pos += n as u64;
+                self.segment.store(READ_AT, self.pos);
+                return Ok(n);
+            }
+            if self.segment.load(CLOSED_AT) != 0 && self.segment.load(WRITE_AT) == self.pos {
+                self.check_sequence()?;
+                return Ok(0);
+            }
+            backoff.wait()?;
+        }
+    }
+}
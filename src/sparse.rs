@@ -0,0 +1,164 @@
+//! Sparse matrices stored as coordinates and values.
+//!
+//! The data of the block starts with the scalar type format (u16)
+//! and the shape of the matrix as rows (u32) and columns (u32),
+//! followed by entries of row (u32), column (u32) and value.
+
+use std::collections::HashMap;
+use std::io;
+
+use error::{Error, ErrorKind};
+use grid::Grid;
+use raw::RawBlock;
+use read_write::Scalar;
+use reserved::SPARSE_FORMAT;
+use Bytes;
+use State;
+
+const HEADER: usize = 10;
+
+/// Determines how entries with the same coordinates are combined when reading.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SparseDuplicates {
+    /// Add the values, wrapping around on integer overflow.
+    Sum,
+    /// Keep the value that was written last.
+    LastWins,
+}
+
+/// Implemented by values of sparse matrices.
+pub trait SparseValue: Scalar + Copy {
+    /// Adds two values, wrapping around on integer overflow.
+    fn sum(self, other: Self) -> Self;
+}
+
+macro_rules! sparse_int {
+    ($($t:ty),*) => {$(
+        impl SparseValue for $t {
+            #[inline]
+            fn sum(self, other: $t) -> $t {self.wrapping_add(other)}
+        }
+    )*}
+}
+
+sparse_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl SparseValue for f32 {
+    #[inline]
+    fn sum(self, other: f32) -> f32 {self + other}
+}
+
+impl SparseValue for f64 {
+    #[inline]
+    fn sum(self, other: f64) -> f64 {self + other}
+}
+
+/// A sparse matrix.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Sparse<T> {
+    /// The number of rows.
+    pub rows: u32,
+    /// The number of columns.
+    pub cols: u32,
+    /// The entries as row, column and value.
+    pub entries: Vec<(u32, u32, T)>,
+}
+
+impl<T: Scalar + Copy> Sparse<T> {
+    /// Converts into a dense grid, where missing entries are default values.
+    ///
+    /// Entries with the same coordinates are expected to be combined already,
+    /// otherwise the last one is kept.
+    pub fn to_grid(&self) -> Grid<T> {
+        let mut grid = Grid::new(self.rows, self.cols);
+        for &(row, col, val) in &self.entries {
+            grid.data[row as usize * self.cols as usize + col as usize] = val;
+        }
+        grid
+    }
+}
+
+/// Writes a sparse matrix with shape `[rows, cols]`.
+///
+/// Returns an error if an entry is outside the shape.
+pub fn write_sparse<T: Scalar, W: io::Write>(
+    property_id: u16,
+    entries: &[(u32, u32, T)],
+    shape: [u32; 2],
+    w: &mut W
+) -> io::Result<()> {
+    let (_, size) = T::ty().scalar();
+    let mut data = Vec::with_capacity(HEADER + entries.len() * (8 + size as usize));
+    data.extend_from_slice(&T::ty().scalar().0.to_le_bytes());
+    data.extend_from_slice(&shape[0].to_le_bytes());
+    data.extend_from_slice(&shape[1].to_le_bytes());
+    for &(row, col, ref val) in entries {
+        if row >= shape[0] || col >= shape[1] {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        data.extend_from_slice(&row.to_le_bytes());
+        data.extend_from_slice(&col.to_le_bytes());
+        val.write(&mut data)?;
+    }
+    RawBlock::write_property(SPARSE_FORMAT, property_id, &[RawBlock {
+        type_format: SPARSE_FORMAT,
+        property_id,
+        offset: 0,
+        data,
+    }], w)
+}
+
+/// Reads a sparse matrix.
+///
+/// Entries with the same coordinates are combined into one,
+/// at the position of the first.
+pub fn read_sparse<T, R>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    duplicates: SparseDuplicates,
+    r: &mut R
+) -> io::Result<Sparse<T>>
+    where T: SparseValue, R: io::Read
+{
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(property_id).into()
+    };
+    if ty != SPARSE_FORMAT {return Err(err())}
+    let blocks = RawBlock::read_property(state, ty, property_id, r)?;
+    let data = match blocks.first() {
+        Some(block) if blocks.len() == 1 && block.offset == 0 => &block.data,
+        _ => return Err(err()),
+    };
+    let (format, size) = T::ty().scalar();
+    let entry_size = 8 + size as usize;
+    if data.len() < HEADER || u16::from_le_bytes([data[0], data[1]]) != format ||
+       !(data.len() - HEADER).is_multiple_of(entry_size) {
+        return Err(err());
+    }
+    let rows = u32::from_le_bytes([data[2], data[3], data[4], data[5]]);
+    let cols = u32::from_le_bytes([data[6], data[7], data[8], data[9]]);
+    let mut entries: Vec<(u32, u32, T)> = Vec::with_capacity((data.len() - HEADER) / entry_size);
+    let mut seen = HashMap::new();
+    for chunk in data[HEADER..].chunks(entry_size) {
+        let row = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let col = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        if row >= rows || col >= cols {return Err(err())}
+        let mut val = T::default();
+        val.read(&mut &chunk[8..])?;
+        match seen.get(&(row, col)) {
+            Some(&i) => {
+                let entry: &mut (u32, u32, T) = &mut entries[i];
+                entry.2 = match duplicates {
+                    SparseDuplicates::Sum => entry.2.sum(val),
+                    SparseDuplicates::LastWins => val,
+                };
+            }
+            None => {
+                seen.insert((row, col), entries.len());
+                entries.push((row, col, val));
+            }
+        }
+    }
+    Ok(Sparse {rows, cols, entries})
+}
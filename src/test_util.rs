@@ -0,0 +1,186 @@
+//! Generators of random but valid streams for property testing.
+//!
+//! Enabled with the `test-util` feature.
+//! Each generated stream comes with the expected result of decoding it:
+//!
+//! ```ignore
+//! use binpool::test_util::{stream, StreamConfig};
+//!
+//! proptest! {
+//!     #[test]
+//!     fn roundtrip(model in stream(StreamConfig::default())) {
+//!         let bytes = model.encode();
+//!         // Decode `bytes` and compare with `model.properties[i].items`.
+//!     }
+//! }
+//! ```
+
+use std::io;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use raw::RawBlock;
+use State;
+use Type;
+
+/// Controls the size of generated streams.
+#[derive(Clone, Copy, Debug)]
+pub struct StreamConfig {
+    /// The maximum number of properties.
+    pub max_properties: usize,
+    /// The maximum number of blocks per property.
+    pub max_blocks: usize,
+    /// The maximum number of items per block.
+    pub max_items: usize,
+    /// The maximum offset instance id of a block.
+    pub max_offset: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> StreamConfig {
+        StreamConfig {
+            max_properties: 8,
+            max_blocks: 8,
+            max_items: 16,
+            max_offset: 64,
+        }
+    }
+}
+
+/// A generated property and the expected result of decoding it.
+#[derive(Clone, Debug)]
+pub struct ModelProperty {
+    /// Type format.
+    pub type_format: u16,
+    /// Property id.
+    pub property_id: u16,
+    /// The blocks as written, in order.
+    pub blocks: Vec<RawBlock>,
+    /// The bytes of each item after applying all blocks in order.
+    ///
+    /// Items that are not covered by any block are zero,
+    /// which is the default value of every built-in type.
+    pub items: Vec<Vec<u8>>,
+}
+
+/// A generated stream and the expected result of decoding it.
+#[derive(Clone, Debug)]
+pub struct Model {
+    /// The properties, in the order they are written.
+    pub properties: Vec<ModelProperty>,
+}
+
+impl Model {
+    /// Writes the stream, including the end of stream.
+    pub fn write<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        for p in &self.properties {
+            RawBlock::write_property(p.type_format, p.property_id, &p.blocks, w)?;
+        }
+        State::new().end_type_formats(w)
+    }
+
+    /// Encodes the stream into bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut res = vec![];
+        self.write(&mut res).expect("writing to a vector never fails");
+        res
+    }
+}
+
+const TYPES: [Type; 10] = [
+    Type::U8, Type::U16, Type::U32, Type::U64,
+    Type::I8, Type::I16, Type::I32, Type::I64,
+    Type::F32, Type::F64,
+];
+
+/// Generates a built-in type.
+pub fn ty() -> impl Strategy<Value = Type> {
+    (0..TYPES.len()).prop_map(|i| TYPES[i])
+}
+
+/// Generates a built-in type format.
+///
+/// Biased toward scalars, small vectors and the maximum dimension 80.
+pub fn type_format() -> impl Strategy<Value = u16> {
+    let dim = prop_oneof![
+        4 => Just((1, 1)),
+        4 => (1_u8..=4).prop_map(|n| (1, n)),
+        2 => (1_u8..=4, 1_u8..=4),
+        1 => Just((1, 80)),
+        1 => Just((80, 80)),
+        1 => (1_u8..=80, 1_u8..=80),
+    ];
+    (ty(), dim).prop_map(|(ty, (rows, cols))| ty.matrix(rows, cols).unwrap().0)
+}
+
+/// Generates an offset instance id, biased toward zero and the maximum.
+pub fn offset(max_offset: u64) -> impl Strategy<Value = u64> {
+    prop_oneof![
+        2 => Just(0),
+        1 => Just(max_offset),
+        4 => 0..=max_offset,
+    ]
+}
+
+/// Generates a property with the given type format and property id.
+pub fn property(
+    type_format: u16,
+    property_id: u16,
+    cfg: StreamConfig
+) -> impl Strategy<Value = ModelProperty> {
+    let (ty, rows, cols) = Type::info(type_format).expect("built-in type format");
+    let size = (ty.type_size() * rows as u64 * cols as u64) as usize;
+    // Keep blocks of large items small.
+    let max_items = if size > 1024 {cfg.max_items.min(2)} else {cfg.max_items};
+    let items = prop_oneof![
+        1 => Just(0),
+        1 => Just(max_items),
+        4 => 0..=max_items,
+    ];
+    let block = (offset(cfg.max_offset), items)
+        .prop_flat_map(move |(offset, n)| (Just(offset), vec(any::<u8>(), n * size)))
+        .prop_map(move |(offset, data)| RawBlock {type_format, property_id, offset, data});
+    vec(block, 0..=cfg.max_blocks).prop_map(move |blocks| {
+        let mut items: Vec<Vec<u8>> = vec![];
+        for b in &blocks {
+            for (i, item) in b.data.chunks(size).enumerate() {
+                let ind = b.offset as usize + i;
+                while ind >= items.len() {
+                    items.push(vec![0; size]);
+                }
+                items[ind] = item.to_vec();
+            }
+        }
+        ModelProperty {type_format, property_id, blocks, items}
+    })
+}
+
+/// Generates a stream of properties with random ids, types, dimensions and blocks.
+///
+/// Each property id appears at most once,
+/// such that the expected items of a property do not depend on other properties.
+/// Property ids are biased toward `0` and `u16::MAX`.
+pub fn stream(cfg: StreamConfig) -> impl Strategy<Value = Model> {
+    let id = prop_oneof![
+        1 => Just(0),
+        1 => Just(u16::MAX),
+        6 => any::<u16>(),
+    ];
+    vec((id, type_format()), 0..=cfg.max_properties)
+        .prop_map(|mut props| {
+            let mut seen = vec![];
+            props.retain(|&(id, _)| {
+                if seen.contains(&id) {return false}
+                seen.push(id);
+                true
+            });
+            props
+        })
+        .prop_flat_map(move |props| {
+            props.into_iter()
+                .map(|(id, ty)| property(ty, id, cfg))
+                .collect::<Vec<_>>()
+        })
+        .prop_map(|properties| Model {properties})
+}
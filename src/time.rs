@@ -0,0 +1,117 @@
+//! Conventions for describing time.
+//!
+//! Time is stored as a scalar property written at the start of each frame.
+//! It is written as `f64`, but both `f32` and `f64` are accepted when reading.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use read_write::Scalar;
+use reserved::TIME_UNIT_FORMAT;
+use Bytes;
+use State;
+use Type;
+
+/// Reads and writes time values.
+pub struct Time;
+
+impl Time {
+    /// Writes a time value.
+    pub fn write<W: io::Write>(property_id: u16, t: f64, w: &mut W) -> io::Result<()> {
+        t.write_property(property_id, w)
+    }
+
+    /// Reads a time value stored as `f32` or `f64`.
+    pub fn read<R: io::Read>(state: State<Bytes>, ty: u16, r: &mut R) -> io::Result<f64> {
+        if ty == Type::F32.scalar().0 {
+            let mut t: f32 = 0.0;
+            t.read_property(state, ty, r)?;
+            Ok(t as f64)
+        } else {
+            let mut t: f64 = 0.0;
+            t.read_property(state, ty, r)?;
+            Ok(t)
+        }
+    }
+
+    /// Decodes a time value stored as `f32` or `f64` from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<f64> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.offset != 0 {return Err(err())}
+        if block.type_format == Type::F32.scalar().0 {
+            let t = block.decode::<f32>()?;
+            if t.len() != 1 {return Err(err())}
+            Ok(t[0] as f64)
+        } else {
+            let t = block.decode::<f64>()?;
+            if t.len() != 1 {return Err(err())}
+            Ok(t[0])
+        }
+    }
+}
+
+/// The unit of a time property.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TimeUnit {
+    /// Seconds.
+    Seconds,
+    /// Simulation steps.
+    Steps,
+    /// A custom unit.
+    Custom(String),
+}
+
+impl TimeUnit {
+    /// Writes the unit of a time property.
+    pub fn write<W: io::Write>(&self, property_id: u16, w: &mut W) -> io::Result<()> {
+        let mut data = vec![];
+        match *self {
+            TimeUnit::Seconds => data.push(0),
+            TimeUnit::Steps => data.push(1),
+            TimeUnit::Custom(ref name) => {
+                data.push(2);
+                data.extend_from_slice(name.as_bytes());
+            }
+        }
+        RawBlock::write_property(TIME_UNIT_FORMAT, property_id, &[RawBlock {
+            type_format: TIME_UNIT_FORMAT,
+            property_id,
+            offset: 0,
+            data,
+        }], w)
+    }
+
+    /// Reads the unit of a time property.
+    pub fn read<R: io::Read>(
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<TimeUnit> {
+        let blocks = RawBlock::read_property(state, ty, property_id, r)?;
+        match blocks.last() {
+            Some(block) => TimeUnit::decode(block),
+            None => Err(Error::new(ErrorKind::InvalidData).with_property(property_id).into()),
+        }
+    }
+
+    /// Decodes the unit of a time property from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<TimeUnit> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.type_format != TIME_UNIT_FORMAT {return Err(err())}
+        match block.data.first() {
+            Some(&0) => Ok(TimeUnit::Seconds),
+            Some(&1) => Ok(TimeUnit::Steps),
+            Some(&2) => match String::from_utf8(block.data[1..].to_vec()) {
+                Ok(name) => Ok(TimeUnit::Custom(name)),
+                Err(_) => Err(err()),
+            },
+            _ => Err(err()),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Spans and events for the `tracing` feature.
+//!
+//! Without the feature, spans are empty and events do nothing,
+//! such that instrumented code compiles to the same code as without instrumentation.
+//! The names and fields are documented in the crate documentation.
+
+pub(crate) use self::imp::*;
+
+#[cfg(feature = "tracing")]
+mod imp {
+    use tracing::field::Empty;
+
+    pub(crate) use tracing::Span;
+
+    /// Creates the span of a frame, without a parent.
+    ///
+    /// The time is recorded with `record_time` when it has been decoded.
+    pub(crate) fn frame(number: u64) -> Span {
+        tracing::debug_span!(target: "binpool", parent: None, "frame", frame = number, time = Empty)
+    }
+
+    /// Records the time of a frame.
+    pub(crate) fn record_time(span: &Span, time: f64) {
+        span.record("time", time);
+    }
+
+    /// Creates the span of reading a block.
+    pub(crate) fn block(property_id: u16, type_format: u16, bytes: u64, offset: u64) -> Span {
+        tracing::trace_span!(target: "binpool", "block", property_id, type_format, bytes, offset)
+    }
+
+    /// A property skipped without decoding it.
+    pub(crate) fn skip(property_id: u16, type_format: u16, bytes: u64) {
+        tracing::debug!(name: "skip", target: "binpool", property_id, type_format, bytes, "skip");
+    }
+
+    /// A read or write retried after it was interrupted.
+    pub(crate) fn retry() {
+        tracing::debug!(name: "retry", target: "binpool", "retry");
+    }
+
+    /// A type format that does not match the expected type.
+    pub(crate) fn type_mismatch(property_id: Option<u16>, type_format: u16, expected: Option<u16>) {
+        tracing::warn!(name: "type_mismatch", target: "binpool",
+            property_id, type_format, expected, "type_mismatch");
+    }
+
+    /// A limit on resources that was exceeded.
+    pub(crate) fn limit_exceeded(property_id: u16, limit: &str, value: u64, max: u64) {
+        tracing::warn!(name: "limit_exceeded", target: "binpool",
+            property_id, limit, value, max, "limit_exceeded");
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod imp {
+    #[derive(Clone, Debug)]
+    pub(crate) struct Span;
+
+    impl Span {
+        #[inline(always)]
+        pub fn none() -> Span {Span}
+
+        #[inline(always)]
+        pub fn entered(self) -> Span {self}
+    }
+
+    #[inline(always)]
+    pub(crate) fn frame(_number: u64) -> Span {Span}
+
+    #[inline(always)]
+    pub(crate) fn record_time(_span: &Span, _time: f64) {}
+
+    #[inline(always)]
+    pub(crate) fn block(_property_id: u16, _type_format: u16, _bytes: u64, _offset: u64) -> Span {Span}
+
+    #[inline(always)]
+    pub(crate) fn skip(_property_id: u16, _type_format: u16, _bytes: u64) {}
+
+    #[inline(always)]
+    pub(crate) fn retry() {}
+
+    #[inline(always)]
+    pub(crate) fn type_mismatch(_property_id: Option<u16>, _type_format: u16, _expected: Option<u16>) {}
+
+    #[inline(always)]
+    pub(crate) fn limit_exceeded(_property_id: u16, _limit: &str, _value: u64, _max: u64) {}
+}
@@ -0,0 +1,209 @@
+//! Several independent streams interleaved in one stream.
+//!
+//! A track switch is a property with a reserved format and property id,
+//! with one block of the track id (u16).
+//! The properties after a switch belong to that track, until the next switch.
+//! Properties before the first switch belong to track 0,
+//! such that streams without tracks read as track 0.
+
+use std::io::{self, Write};
+
+use endian::{self, EndianReader, EndianWriter};
+use error::{Error, ErrorKind};
+use parse::{Event, Parser};
+use raw::{self, RawBlock};
+use reserved::{
+    HEADER_FORMAT, HEADER_PROPERTY, PADDING_FORMAT, PADDING_PROPERTY, TRACK_FORMAT, TRACK_PROPERTY,
+};
+use State;
+
+/// Writes several tracks into one stream.
+///
+/// Each track has its own property ids.
+/// Data is written through the handle returned by `track`:
+///
+/// ```ignore
+/// let mut w = TrackWriter::new(file);
+/// Vector::write_array(POSITION, &pos, &mut w.track(0)?)?;
+/// Scalar::write_array(NOTE, &note, &mut w.track(1)?)?;
+/// w.finish()?;
+/// ```
+///
+/// End of stream is written by `finish`, and refused when written through a track.
+pub struct TrackWriter<W: io::Write> {
+    inner: W,
+    current: u16,
+    parser: Parser,
+}
+
+impl<W: io::Write> TrackWriter<W> {
+    /// Creates a new writer, starting with track 0.
+    pub fn new(inner: W) -> TrackWriter<W> {
+        TrackWriter {
+            inner,
+            current: 0,
+            parser: Parser::new(),
+        }
+    }
+
+    /// Returns a handle that writes to a track.
+    ///
+    /// A track switch is written when the track changes.
+    /// Returns an error if a property is not complete.
+    pub fn track(&mut self, track: u16) -> io::Result<Track<'_, W>> {
+        if !self.parser.at_property_boundary() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        if track != self.current {
+            write_switch(track, &mut self.inner)?;
+            self.current = track;
+        }
+        Ok(Track {writer: self})
+    }
+
+    /// Returns the current track.
+    pub fn current_track(&self) -> u16 {
+        self.current
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing directly to the underlying writer bypasses tracks.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    ///
+    /// Returns an error if a property is not complete.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.parser.at_property_boundary() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        State::new().end_type_formats(&mut self.inner)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// Writes to one track of a `TrackWriter`.
+pub struct Track<'a, W: 'a + io::Write> {
+    writer: &'a mut TrackWriter<W>,
+}
+
+impl<'a, W: io::Write> Track<'a, W> {
+    /// Returns the track id.
+    pub fn id(&self) -> u16 {
+        self.writer.current
+    }
+}
+
+impl<'a, W: io::Write> io::Write for Track<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut parser = self.writer.parser.clone();
+        let mut end = false;
+        parser.consume(buf, |e| if e == Event::End {end = true});
+        if end {return Err(io::ErrorKind::InvalidInput.into())}
+        self.writer.inner.write_all(buf)?;
+        self.writer.parser = parser;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.inner.flush()
+    }
+}
+
+fn write_switch<W: io::Write>(track: u16, w: &mut W) -> io::Result<()> {
+    RawBlock::write_property(TRACK_FORMAT, TRACK_PROPERTY, &[RawBlock {
+        type_format: TRACK_FORMAT,
+        property_id: TRACK_PROPERTY,
+        offset: 0,
+        data: track.to_le_bytes().to_vec(),
+    }], w)
+}
+
+/// Reads the tracks of a stream written by `TrackWriter`.
+///
+/// Tracks are written in the byte order of the stream, with its file header.
+/// Since the data of a track is at other positions than in the stream,
+/// padding is left out and the header of a track has no alignment.
+/// The stream is read as stored, not through `PoolReader`.
+pub struct TrackReader<R> {
+    inner: R,
+}
+
+impl<R: io::Read> TrackReader<R> {
+    /// Creates a new reader.
+    pub fn new(inner: R) -> TrackReader<R> {
+        TrackReader {inner}
+    }
+
+    /// Copies the properties of one track, followed by end of stream.
+    ///
+    /// The data of other tracks is skipped without buffering.
+    pub fn read_track<W: io::Write>(&mut self, track: u16, w: &mut W) -> io::Result<()> {
+        self.demux(&mut [(track, w)])
+    }
+
+    /// Copies the properties of every track to the sink with the same track id,
+    /// followed by end of stream.
+    ///
+    /// Tracks without a sink are skipped.
+    pub fn demux<W: io::Write>(&mut self, sinks: &mut [(u16, W)]) -> io::Result<()> {
+        let r = &mut EndianReader::new(&mut self.inner);
+        let mut sinks: Vec<(u16, EndianWriter<&mut W>)> = sinks.iter_mut()
+            .map(|&mut (id, ref mut w)| (id, EndianWriter::new(w)))
+            .collect();
+        let mut current = 0;
+        while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+            if ty == TRACK_FORMAT && prop == TRACK_PROPERTY {
+                let blocks = RawBlock::read_property(state, ty, prop, r)?;
+                current = match blocks.first() {
+                    Some(block) if blocks.len() == 1 && block.data.len() == 2 => {
+                        u16::from_le_bytes([block.data[0], block.data[1]])
+                    }
+                    _ => return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into()),
+                };
+            } else if ty == HEADER_FORMAT && prop == HEADER_PROPERTY {
+                let mut header = vec![];
+                endian::copy_unaligned_header(state, r, &mut header)?;
+                for &mut (_, ref mut w) in &mut sinks {
+                    w.write_all(&header)?;
+                }
+            } else if ty == PADDING_FORMAT && prop == PADDING_PROPERTY {
+                raw::copy_property(state, ty, prop, r, &mut io::sink())?;
+            } else {
+                match sinks.iter_mut().find(|(id, _)| *id == current) {
+                    Some((_, w)) => raw::copy_property(state, ty, prop, r, w)?,
+                    None => raw::copy_property(state, ty, prop, r, &mut io::sink())?,
+                };
+            }
+        }
+        for &mut (_, ref mut w) in &mut sinks {
+            State::new().end_type_formats(w)?;
+        }
+        Ok(())
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
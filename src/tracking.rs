@@ -0,0 +1,103 @@
+//! Reader that keeps track of the position in the stream.
+
+use std::io;
+
+use error::{Error, Field};
+use parse::Parser;
+
+/// Wraps a reader and counts the consumed bytes.
+///
+/// The reader follows the fields of the format while reading,
+/// such that errors can be annotated with the byte position,
+/// the property id and the field that was read.
+///
+/// ```ignore
+/// use binpool::{State, TrackingReader};
+///
+/// let mut r = TrackingReader::new(file);
+/// while let (Some(state), ty, _) = r.track(|r| State::read(r))? {
+///     r.track(|r| Scalar::read_array(state, ty, &mut data, r))?;
+/// }
+/// ```
+pub struct TrackingReader<R> {
+    inner: R,
+    parser: Parser,
+}
+
+impl<R> TrackingReader<R> {
+    /// Creates a new tracking reader at the start of a stream.
+    pub fn new(inner: R) -> TrackingReader<R> {
+        TrackingReader {inner, parser: Parser::new()}
+    }
+
+    /// Returns the number of bytes consumed.
+    pub fn position(&self) -> u64 {
+        self.parser.position()
+    }
+
+    /// Returns the current property id.
+    pub fn property(&self) -> Option<u16> {
+        self.parser.property()
+    }
+
+    /// Returns the field that is currently read.
+    pub fn field(&self) -> Field {
+        self.parser.field()
+    }
+
+    /// Returns the parser following the fields.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// Reading directly from the underlying reader is not tracked.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Adds position, property id and field to an error.
+    ///
+    /// The position is where the field that was read starts.
+    /// Information that is already stored in the error is kept.
+    pub fn annotate(&self, err: io::Error) -> io::Error {
+        let kind = err.kind();
+        let mut res = Error::from_io(&err);
+        if res.at_byte().is_none() {
+            res = res.with_position(self.parser.field_start());
+        }
+        if res.field().is_none() {
+            res = res.with_field(self.parser.field());
+        }
+        if let (None, Some(prop)) = (res.property(), self.parser.property()) {
+            res = res.with_property(prop);
+        }
+        res.into_io(kind)
+    }
+
+    /// Calls a closure and annotates the error, if any.
+    pub fn track<T, F>(&mut self, f: F) -> io::Result<T>
+        where F: FnOnce(&mut Self) -> io::Result<T>
+    {
+        f(self).map_err(|err| self.annotate(err))
+    }
+}
+
+impl<R: io::Read> io::Read for TrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.parser.consume(&buf[..n], |_| {});
+        Ok(n)
+    }
+}
@@ -0,0 +1,352 @@
+//! Sending blocks over transports that are not byte streams.
+//!
+//! A stream is sent as a sequence of messages:
+//!
+//! ```ignore
+//! for every property:
+//!     block header and payload, for every block
+//!     block header with 0 bytes and an empty payload, the end of bytes
+//! end of stream
+//! ```
+//!
+//! The type format and property id are repeated in every block header.
+//! `WireSink` and `WireSource` use the wire format of a byte stream.
+//! `SinkWriter` and `SourceReader` adapt a sink or a source to `io::Write` and `io::Read`,
+//! such that `PoolWriter`, `PoolReader` and the functions for reading and writing
+//! properties can be used over any transport.
+
+use std::borrow::Cow;
+use std::io::{self, IoSlice};
+use std::marker::PhantomData;
+
+use parse::{Event, Parser};
+use raw;
+use BlockHeader;
+use Bytes;
+use State;
+
+/// The payload of a block, borrowed from the source when possible.
+pub type Payload<'a> = Cow<'a, [u8]>;
+
+/// Receives the blocks of a stream.
+pub trait BlockSink {
+    /// Sends a block, or the end of bytes of a property when `header.is_end()`.
+    ///
+    /// The payload has `header.bytes` bytes, and is empty for the end of bytes.
+    fn send(&mut self, header: &BlockHeader, payload: &[u8]) -> io::Result<()>;
+    /// Sends the end of stream.
+    fn end(&mut self) -> io::Result<()>;
+    /// Flushes blocks that are sent.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Produces the blocks of a stream.
+pub trait BlockSource {
+    /// Receives a block, or the end of bytes of a property when `header.is_end()`.
+    ///
+    /// Returns `None` at the end of stream.
+    fn recv(&mut self) -> io::Result<Option<(BlockHeader, Payload<'_>)>>;
+}
+
+/// Sends blocks to a writer, using the wire format.
+pub struct WireSink<W: io::Write> {
+    w: W,
+    property: Option<(u16, u16)>,
+}
+
+impl<W: io::Write> WireSink<W> {
+    /// Creates a new sink.
+    pub fn new(w: W) -> WireSink<W> {
+        WireSink {w, property: None}
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.w
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.w
+    }
+
+    /// Unwraps the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<W: io::Write> BlockSink for WireSink<W> {
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` for type format 0,
+    /// a payload that does not have the number of bytes of the header,
+    /// or a block of another property before the end of bytes.
+    fn send(&mut self, header: &BlockHeader, payload: &[u8]) -> io::Result<()> {
+        let key = (header.type_format, header.property_id);
+        if header.type_format == 0 || payload.len() as u64 != header.bytes ||
+           self.property.is_some_and(|p| p != key) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        let mut head = [0; 20];
+        let mut n = 0;
+        if self.property.is_none() {
+            head[..2].copy_from_slice(&header.type_format.to_le_bytes());
+            head[2..4].copy_from_slice(&header.property_id.to_le_bytes());
+            n = 4;
+        }
+        head[n..n + 8].copy_from_slice(&header.bytes.to_le_bytes());
+        n += 8;
+        if header.is_end() {
+            self.property = None;
+        } else {
+            head[n..n + 8].copy_from_slice(&header.offset.to_le_bytes());
+            n += 8;
+            self.property = Some(key);
+        }
+        raw::write_all_vectored(&mut self.w, &mut [IoSlice::new(&head[..n]), IoSlice::new(payload)])
+    }
+
+    /// Returns an error of kind `io::ErrorKind::InvalidInput` before the end of bytes.
+    fn end(&mut self) -> io::Result<()> {
+        if self.property.is_some() {return Err(io::ErrorKind::InvalidInput.into())}
+        State::new().end_type_formats(&mut self.w)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+/// Receives blocks from a reader, using the wire format.
+///
+/// The payload is borrowed from a buffer that is reused for every block.
+pub struct WireSource<R: io::Read> {
+    r: R,
+    property: Option<(u16, u16)>,
+    buf: Vec<u8>,
+}
+
+impl<R: io::Read> WireSource<R> {
+    /// Creates a new source.
+    pub fn new(r: R) -> WireSource<R> {
+        WireSource {r, property: None, buf: vec![]}
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.r
+    }
+
+    /// Unwraps the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.r
+    }
+}
+
+impl<R: io::Read> BlockSource for WireSource<R> {
+    fn recv(&mut self) -> io::Result<Option<(BlockHeader, Payload<'_>)>> {
+        let (type_format, property_id) = match self.property {
+            Some(p) => p,
+            None => match raw::read_property_header(&mut self.r)? {
+                None => return Ok(None),
+                Some((_, ty, prop)) => (ty, prop),
+            },
+        };
+        let state: State<Bytes> = State(PhantomData, Some(property_id));
+        let (header, _) = state.read_block_header(type_format, property_id, &mut self.r)?;
+        if header.is_end() {
+            self.property = None;
+            return Ok(Some((header, Cow::Borrowed(&[]))));
+        }
+        self.property = Some((type_format, property_id));
+        self.buf.clear();
+        io::Read::read_to_end(&mut io::Read::take(&mut self.r, header.bytes), &mut self.buf)?;
+        if (self.buf.len() as u64) < header.bytes {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Some((header, Cow::Borrowed(&self.buf))))
+    }
+}
+
+/// Writes a little endian stream to a sink, one block at a time.
+///
+/// The payload of a block is passed on without copying
+/// when it is written with one call to `write`.
+///
+/// ```ignore
+/// let mut w = SinkWriter::new(queue);
+/// Vector::write_array(POSITION, &pos, &mut w)?;
+/// w.finish()?;
+/// ```
+pub struct SinkWriter<S: BlockSink> {
+    sink: S,
+    parser: Parser,
+    header: BlockHeader,
+    pending: Vec<u8>,
+}
+
+impl<S: BlockSink> SinkWriter<S> {
+    /// Creates a new writer.
+    pub fn new(sink: S) -> SinkWriter<S> {
+        SinkWriter {
+            sink,
+            parser: Parser::new(),
+            header: BlockHeader {type_format: 0, property_id: 0, bytes: 0, offset: 0},
+            pending: vec![],
+        }
+    }
+
+    /// Gets a reference to the sink.
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    /// Gets a mutable reference to the sink.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Unwraps the sink, e.g. after `PoolWriter::finish` has written end of stream.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    /// Writes end of stream and returns the sink.
+    pub fn finish(mut self) -> io::Result<S> {
+        State::new().end_type_formats(&mut self)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+}
+
+impl<S: BlockSink> io::Write for SinkWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut data = buf;
+        while !data.is_empty() {
+            let remaining = self.parser.remaining();
+            if remaining > 0 {
+                let n = (remaining as usize).min(data.len());
+                if self.pending.is_empty() && n as u64 == remaining {
+                    self.sink.send(&self.header, &data[..n])?;
+                } else {
+                    self.pending.extend_from_slice(&data[..n]);
+                    if n as u64 == remaining {
+                        self.sink.send(&self.header, &self.pending)?;
+                        self.pending.clear();
+                    }
+                }
+                self.parser.consume(&data[..n], |_| {});
+                data = &data[n..];
+                continue;
+            }
+
+            // Header fields are passed one byte at a time to stop at the data.
+            let mut event = None;
+            self.parser.consume(&data[..1], |e| event = Some(e));
+            data = &data[1..];
+            match event {
+                Some(Event::Property {type_format, property_id}) => {
+                    self.header.type_format = type_format;
+                    self.header.property_id = property_id;
+                }
+                Some(Event::Block {bytes, offset}) => {
+                    self.header.bytes = bytes;
+                    self.header.offset = offset;
+                }
+                Some(Event::EndBytes) => {
+                    self.header.bytes = 0;
+                    self.header.offset = 0;
+                    self.sink.send(&self.header, &[])?;
+                }
+                Some(Event::End) => self.sink.end()?,
+                None => {}
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+}
+
+/// Reads the blocks of a source as a little endian stream.
+///
+/// ```ignore
+/// let mut r = PoolReader::new(SourceReader::new(queue));
+/// ```
+pub struct SourceReader<S: BlockSource> {
+    source: S,
+    property: Option<(u16, u16)>,
+    out: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<S: BlockSource> SourceReader<S> {
+    /// Creates a new reader.
+    pub fn new(source: S) -> SourceReader<S> {
+        SourceReader {source, property: None, out: vec![], pos: 0, done: false}
+    }
+
+    /// Gets a reference to the source.
+    pub fn get_ref(&self) -> &S {
+        &self.source
+    }
+
+    /// Unwraps the source.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Receives the next block and frames it.
+    ///
+    /// Returns an error of kind `io::ErrorKind::InvalidData` for type format 0,
+    /// a payload that does not have the number of bytes of the header,
+    /// or a block of another property before the end of bytes.
+    fn fill(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.pos = 0;
+        let (header, payload) = match self.source.recv()? {
+            Some(x) => x,
+            None => {
+                if self.property.is_some() {return Err(io::ErrorKind::UnexpectedEof.into())}
+                self.done = true;
+                self.out.extend_from_slice(&[0, 0]);
+                return Ok(());
+            }
+        };
+        let key = (header.type_format, header.property_id);
+        if header.type_format == 0 || payload.len() as u64 != header.bytes ||
+           self.property.is_some_and(|p| p != key) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        if self.property.is_none() {
+            self.out.extend_from_slice(&header.type_format.to_le_bytes());
+            self.out.extend_from_slice(&header.property_id.to_le_bytes());
+        }
+        self.out.extend_from_slice(&header.bytes.to_le_bytes());
+        if header.is_end() {
+            self.property = None;
+        } else {
+            self.out.extend_from_slice(&header.offset.to_le_bytes());
+            self.out.extend_from_slice(&payload);
+            self.property = Some(key);
+        }
+        Ok(())
+    }
+}
+
+impl<S: BlockSource> io::Read for SourceReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.out.len() {
+            if self.done {return Ok(0)}
+            self.fill()?;
+        }
+        let n = (self.out.len() - self.pos).min(buf.len());
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
@@ -0,0 +1,165 @@
+//! Units of properties.
+//!
+//! Units are stored in a block with a reserved custom format.
+//! The data is a list of entries, each with the property id (u16),
+//! the scale to SI units (f64, NaN when unknown),
+//! the length of the name (u16) and the name in UTF-8.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::RawBlock;
+use reserved::{UNITS_FORMAT, UNITS_PROPERTY};
+use Bytes;
+use State;
+
+/// The unit of a property.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Unit {
+    /// The name of the unit, e.g. "GeV".
+    pub name: String,
+    /// The factor that converts a value in this unit to SI units, if known.
+    pub scale: Option<f64>,
+}
+
+impl Unit {
+    /// Creates a new unit.
+    ///
+    /// A scale that is not finite and positive is treated as unknown.
+    pub fn new(name: &str, scale: f64) -> Unit {
+        let scale = if scale.is_finite() && scale > 0.0 {Some(scale)} else {None};
+        Unit {name: name.into(), scale}
+    }
+}
+
+/// The units of properties in a stream.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Units {
+    units: Vec<(u16, Unit)>,
+}
+
+impl Units {
+    /// Creates an empty list of units.
+    pub fn new() -> Units {
+        Units::default()
+    }
+
+    /// Writes units as property id, name and scale to SI units.
+    pub fn write<W: io::Write>(entries: &[(u16, &str, f64)], w: &mut W) -> io::Result<()> {
+        let mut units = Units::new();
+        for &(prop, name, scale) in entries {
+            units.set(prop, Unit::new(name, scale));
+        }
+        units.write_units(w)
+    }
+
+    /// Writes the units.
+    pub fn write_units<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut data = vec![];
+        for &(prop, ref unit) in &self.units {
+            if unit.name.len() > u16::MAX as usize {
+                return Err(Error::new(ErrorKind::InvalidData).with_property(prop).into());
+            }
+            data.extend_from_slice(&prop.to_le_bytes());
+            data.extend_from_slice(&unit.scale.unwrap_or(f64::NAN).to_le_bytes());
+            data.extend_from_slice(&(unit.name.len() as u16).to_le_bytes());
+            data.extend_from_slice(unit.name.as_bytes());
+        }
+        RawBlock::write_property(UNITS_FORMAT, UNITS_PROPERTY, &[RawBlock {
+            type_format: UNITS_FORMAT,
+            property_id: UNITS_PROPERTY,
+            offset: 0,
+            data,
+        }], w)
+    }
+
+    /// Reads units.
+    pub fn read<R: io::Read>(
+        state: State<Bytes>,
+        ty: u16,
+        property_id: u16,
+        r: &mut R
+    ) -> io::Result<Units> {
+        let mut units = Units::new();
+        for block in RawBlock::read_property(state, ty, property_id, r)? {
+            units.merge(&Units::decode(&block)?);
+        }
+        Ok(units)
+    }
+
+    /// Decodes units from a block.
+    pub fn decode(block: &RawBlock) -> io::Result<Units> {
+        let err = || -> io::Error {
+            Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+        };
+        if block.type_format != UNITS_FORMAT {return Err(err())}
+        let mut units = Units::new();
+        let mut data = &block.data[..];
+        while !data.is_empty() {
+            if data.len() < 12 {return Err(err())}
+            let prop = u16::from_le_bytes([data[0], data[1]]);
+            let mut scale = [0; 8];
+            scale.copy_from_slice(&data[2..10]);
+            let len = u16::from_le_bytes([data[10], data[11]]) as usize;
+            data = &data[12..];
+            if data.len() < len {return Err(err())}
+            let name = match ::std::str::from_utf8(&data[..len]) {
+                Ok(name) => name,
+                Err(_) => return Err(err()),
+            };
+            data = &data[len..];
+            units.set(prop, Unit::new(name, f64::from_le_bytes(scale)));
+        }
+        Ok(units)
+    }
+
+    /// Sets the unit of a property.
+    pub fn set(&mut self, property_id: u16, unit: Unit) {
+        match self.units.iter_mut().find(|&&mut (prop, _)| prop == property_id) {
+            Some(&mut (_, ref mut u)) => *u = unit,
+            None => self.units.push((property_id, unit)),
+        }
+    }
+
+    /// Returns the unit of a property.
+    pub fn get(&self, property_id: u16) -> Option<&Unit> {
+        self.units.iter().find(|&&(prop, _)| prop == property_id).map(|(_, unit)| unit)
+    }
+
+    /// Returns `true` if no property has a unit.
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Returns an iterator over property ids and units.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &Unit)> {
+        self.units.iter().map(|&(prop, ref unit)| (prop, unit))
+    }
+
+    /// Adds units from another list, replacing units of the same properties.
+    pub fn merge(&mut self, other: &Units) {
+        for &(prop, ref unit) in &other.units {
+            self.set(prop, unit.clone());
+        }
+    }
+
+    /// Returns the factor that converts values of a property to a target unit.
+    ///
+    /// Returns an error if the property has no unit or a scale is unknown.
+    pub fn factor(&self, property_id: u16, target: &Unit) -> io::Result<f64> {
+        let scale = self.get(property_id).and_then(|unit| unit.scale);
+        match (scale, target.scale) {
+            (Some(from), Some(to)) => Ok(from / to),
+            _ => Err(Error::new(ErrorKind::UnknownUnit).with_property(property_id).into()),
+        }
+    }
+
+    /// Converts values of a property to a target unit.
+    pub fn convert(&self, property_id: u16, target: &Unit, values: &mut [f64]) -> io::Result<()> {
+        let factor = self.factor(property_id, target)?;
+        for x in values {
+            *x *= factor;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Checking streams for problems.
+
+use std::io;
+
+use coverage::CoverageReport;
+use endian::EndianReader;
+use footer;
+use frame::{self, DuplicateProperty};
+use limits::{Limits, Usage};
+use raw;
+use reserved::{self, PRESENCE_FORMAT};
+use time::Time;
+use trace;
+
+/// Options for validating a stream.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValidateOptions {
+    /// Property id that marks the start of a new frame.
+    ///
+    /// This is required to detect duplicate properties.
+    pub frame_marker: Option<u16>,
+    /// Limits on the resources used when reading.
+    pub limits: Limits,
+    /// Property id to report instance coverage of.
+    ///
+    /// When a frame marker is set, coverage is also reported per frame.
+    pub coverage: Option<u16>,
+    /// Time property to check for times that are not monotonic.
+    pub time_property: Option<u16>,
+    /// Report times equal to the previous time too.
+    pub strict_time: bool,
+}
+
+/// A time that is not after the previous time, or is NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeRegression {
+    /// The number of time values before, starting with 0.
+    pub index: u64,
+    /// The previous time.
+    pub previous: f64,
+    /// The time.
+    pub time: f64,
+}
+
+/// The result of validating a stream.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// The number of property headers.
+    pub properties: u64,
+    /// The number of blocks.
+    pub blocks: u64,
+    /// The number of frames.
+    pub frames: u64,
+    /// Properties that appeared more than once in a frame.
+    pub duplicates: Vec<DuplicateProperty>,
+    /// The instance coverage of the property in the options.
+    pub coverage: Option<CoverageReport>,
+    /// Times of the time property in the options that are not monotonic.
+    pub time_regressions: Vec<TimeRegression>,
+    /// The number of bytes after the end of stream, which readers ignore,
+    /// e.g. from concatenating files with `cat` instead of `concat`.
+    ///
+    /// Repeated ends of stream and the trailer of a footer index are not counted.
+    pub trailing_bytes: u64,
+}
+
+impl Report {
+    /// Returns `true` if no problems were found.
+    ///
+    /// Gaps in the coverage of the whole stream are problems.
+    pub fn is_ok(&self) -> bool {
+        self.duplicates.is_empty() && self.time_regressions.is_empty() &&
+        self.trailing_bytes == 0 &&
+        self.coverage.as_ref().map(|c| c.total.is_complete()).unwrap_or(true)
+    }
+}
+
+/// Reads a whole stream and reports problems.
+///
+/// Errors in the structure of the stream and exceeded limits are returned as errors.
+/// Problems that do not prevent reading are collected in the report.
+/// A stream starting with a header is read in the byte order set by the header.
+///
+/// The reader is read to its end, to find data after the end of stream.
+pub fn validate<R: io::Read>(r: &mut R, opts: &ValidateOptions) -> io::Result<Report> {
+    let r = &mut EndianReader::new(r);
+    let mut report = Report::default();
+    let mut seen: Vec<u16> = vec![];
+    let mut in_frame = false;
+    let mut usage = Usage::new(opts.limits);
+    let mut times = 0;
+    let mut last_time = f64::NEG_INFINITY;
+    report.coverage = opts.coverage.map(CoverageReport::new);
+    while let Some((state, ty, prop)) = raw::read_property_header(r)? {
+        usage.property(ty, prop)?;
+        let blocks = raw::read_blocks(state, ty, prop, &mut usage, r)?;
+        report.properties += 1;
+        report.blocks += blocks.len() as u64;
+        if Some(prop) == opts.time_property && raw::item_size(ty).is_some() {
+            if let Some(block) = blocks.first() {
+                let t = Time::decode(block)?;
+                if !frame::is_after(last_time, t, opts.strict_time) {
+                    report.time_regressions.push(TimeRegression {
+                        index: times,
+                        previous: last_time,
+                        time: t,
+                    });
+                }
+                // NaN is reported, but compared as the previous time is not.
+                if !t.is_nan() {last_time = t}
+                times += 1;
+            }
+        }
+        if let Some(ref mut coverage) = report.coverage {
+            if Some(prop) == opts.frame_marker {coverage.start_frame()}
+            if prop == coverage.property_id {
+                for block in &blocks {
+                    coverage.block(ty, block.data.len() as u64, block.offset, &block.data);
+                }
+            }
+        }
+        if Some(prop) == opts.frame_marker {
+            if in_frame {report.frames += 1}
+            in_frame = true;
+            seen.clear();
+        } else if in_frame && ty != PRESENCE_FORMAT &&
+                  !(reserved::is_reserved_property(prop) && reserved::is_reserved_format(ty)) {
+            if seen.contains(&prop) {
+                report.duplicates.push(DuplicateProperty {frame: report.frames, property_id: prop});
+            } else {
+                seen.push(prop);
+            }
+        }
+    }
+    if in_frame {report.frames += 1}
+    report.trailing_bytes = trailing_bytes(r)?;
+    Ok(report)
+}
+
+/// Reads the rest of a stream after the end of stream,
+/// returning the number of bytes unless they are zeros or the trailer of a footer.
+fn trailing_bytes<R: io::Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8192];
+    let mut head = vec![];
+    let mut total = 0;
+    let mut zeros = true;
+    loop {
+        let n = match r.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {
+                trace::retry();
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let keep = (footer::TRAILER as usize).saturating_sub(head.len()).min(n);
+        head.extend_from_slice(&buf[..keep]);
+        zeros &= buf[..n].iter().all(|&b| b == 0);
+        total += n as u64;
+    }
+    Ok(if zeros || total == footer::TRAILER && footer::is_trailer(&head) {0} else {total})
+}
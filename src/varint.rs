@@ -0,0 +1,211 @@
+//! Delta encoded integer arrays.
+//!
+//! Integers that change by small steps, such as event numbers or sorted indices,
+//! are stored as the difference to the previous item,
+//! using zigzag encoding and variable length integers of 7 bits per byte.
+//!
+//! The data of a block starts with the type format of the items (u16),
+//! the number of items (u64) and the first item as the bits of a u64,
+//! followed by the deltas of the other items.
+//! Deltas wrap around, such that sequences of extreme values are encoded without overflow.
+//! The offset instance id of the block is the instance of the first item.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use read_write::{self, Array, Element, Scalar};
+use reserved::VARINT_FORMAT;
+use Bytes;
+use State;
+use Type;
+
+/// Implemented by integer scalar types.
+pub trait Integer: Scalar + Element + Copy {
+    /// Converts to a wider integer.
+    fn to_i128(self) -> i128;
+    /// Converts from a wider integer.
+    ///
+    /// Returns `None` if the value does not fit.
+    fn from_i128(val: i128) -> Option<Self>;
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {$(
+        impl Integer for $t {
+            #[inline]
+            fn to_i128(self) -> i128 {self as i128}
+            #[inline]
+            fn from_i128(val: i128) -> Option<$t> {
+                if val < <$t>::MIN as i128 || val > <$t>::MAX as i128 {None} else {Some(val as $t)}
+            }
+        }
+    )*}
+}
+
+integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Writes an integer array as one delta encoded block.
+pub fn write_varint_delta<T, W>(property_id: u16, items: &[T], w: &mut W) -> io::Result<()>
+    where T: Integer, W: io::Write
+{
+    let (format, _) = T::ty().scalar();
+    let mut data = Vec::with_capacity(18 + items.len());
+    data.extend_from_slice(&format.to_le_bytes());
+    data.extend_from_slice(&(items.len() as u64).to_le_bytes());
+    let mut prev: Option<u64> = None;
+    for item in items {
+        let bits = item.to_i128() as u64;
+        match prev {
+            None => data.extend_from_slice(&bits.to_le_bytes()),
+            Some(prev) => {
+                let delta = bits.wrapping_sub(prev) as i64;
+                write_varint(((delta << 1) ^ (delta >> 63)) as u64, &mut data);
+            }
+        }
+        prev = Some(bits);
+    }
+    RawBlock::write_property(VARINT_FORMAT, property_id, &[RawBlock {
+        type_format: VARINT_FORMAT,
+        property_id,
+        offset: 0,
+        data,
+    }], w)
+}
+
+/// Decodes a delta encoded block into the type format of its items and the items.
+///
+/// Items are returned as the bits of a u64.
+/// `max_items` limits the number of items.
+fn decode(block: &RawBlock, max_items: Option<u64>) -> io::Result<(u16, Vec<u64>)> {
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+    };
+    let data = &block.data[..];
+    if block.type_format != VARINT_FORMAT || data.len() < 10 {return Err(err())}
+    let format = u16::from_le_bytes([data[0], data[1]]);
+    match Type::info(format) {
+        Some((ty, 1, 1)) if ty != Type::F32 && ty != Type::F64 => {}
+        _ => return Err(err()),
+    }
+    let mut count = [0; 8];
+    count.copy_from_slice(&data[2..10]);
+    let count = u64::from_le_bytes(count);
+    if count > max_items.unwrap_or(u64::MAX) {
+        return Err(Error::new(ErrorKind::LimitExceeded).with_property(block.property_id).into());
+    }
+    let mut data = &data[10..];
+    if count == 0 {
+        return if data.is_empty() {Ok((format, vec![]))} else {Err(err())};
+    }
+    // Every delta takes at least one byte.
+    if data.len() < 8 || count - 1 > (data.len() - 8) as u64 {return Err(err())}
+    let mut first = [0; 8];
+    first.copy_from_slice(&data[..8]);
+    data = &data[8..];
+    let mut items = Vec::with_capacity(count as usize);
+    let mut prev = u64::from_le_bytes(first);
+    items.push(prev);
+    for _ in 1..count {
+        let zigzag = read_varint(&mut data).ok_or_else(err)?;
+        let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+        prev = prev.wrapping_add(delta as u64);
+        items.push(prev);
+    }
+    if !data.is_empty() {return Err(err())}
+    Ok((format, items))
+}
+
+/// Expands a delta encoded block into a plain block.
+///
+/// `max_bytes` limits the size of the expanded data.
+pub fn expand_varint_delta(block: &RawBlock, max_bytes: Option<u64>) -> io::Result<RawBlock> {
+    if block.data.len() < 2 {
+        return Err(Error::new(ErrorKind::InvalidData).with_property(block.property_id).into());
+    }
+    let format = u16::from_le_bytes([block.data[0], block.data[1]]);
+    let size = raw::item_size(format).unwrap_or(8);
+    let (format, items) = decode(block, max_bytes.map(|n| n / size))?;
+    let mut data = Vec::with_capacity(items.len() * size as usize);
+    for bits in items {
+        data.extend_from_slice(&bits.to_le_bytes()[..size as usize]);
+    }
+    Ok(RawBlock {type_format: format, property_id: block.property_id, offset: block.offset, data})
+}
+
+/// Reads an array written by `write_varint_delta`, or a plain integer array.
+///
+/// Items are converted to the integer type of the array.
+/// Blocks are applied at their offsets, growing the array when needed.
+///
+/// Returns an error of kind `io::ErrorKind::InvalidData`, with the instance,
+/// if an item does not fit in the integer type,
+/// and of kind `ErrorKind::TypeMismatch` if a plain array has another type format.
+pub fn read_varint_delta<T, A, R>(
+    state: State<Bytes>,
+    ty: u16,
+    property_id: u16,
+    arr: &mut A,
+    r: &mut R
+) -> io::Result<()>
+    where T: Integer, A: Array<Item = T>, R: io::Read
+{
+    for block in RawBlock::read_property(state, ty, property_id, r)? {
+        let items: Vec<i128> = if ty == VARINT_FORMAT {
+            let (format, items) = decode(&block, None)?;
+            let signed = match Type::info(format) {
+                Some((ty, _, _)) => ty.type_id() >= Type::I8.type_id(),
+                None => false,
+            };
+            let size = raw::item_size(format).unwrap_or(8) * 8;
+            items.into_iter().map(|bits| {
+                // Extend from the size of the items.
+                let shift = 64 - size;
+                if signed {((bits << shift) as i64 >> shift) as i128} else {(bits << shift >> shift) as i128}
+            }).collect()
+        } else {
+            block.decode::<T>()?.into_iter().map(|x| x.to_i128()).collect()
+        };
+        let n = items.len();
+        for (i, item) in items.into_iter().enumerate() {
+            let ind = match block.offset.checked_add(i as u64) {
+                Some(x) if x < usize::MAX as u64 => x as usize,
+                _ => return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(property_id).into()),
+            };
+            let item = match T::from_i128(item) {
+                Some(x) => x,
+                None => return Err(Error::new(ErrorKind::InvalidData)
+                    .with_property(property_id)
+                    .with_instance(ind as u64)
+                    .into()),
+            };
+            read_write::make_room(arr, ind, ind.saturating_add(n - i))?;
+            arr.set(ind, item);
+        }
+    }
+    Ok(())
+}
+
+fn write_varint(mut val: u64, data: &mut Vec<u8>) {
+    while val >= 0x80 {
+        data.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    data.push(val as u8);
+}
+
+/// Reads a variable length integer.
+///
+/// Returns `None` at the end of data or if the value does not fit in 64 bits.
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut val = 0_u64;
+    for i in 0..10 {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+        if i == 9 && byte > 1 {return None}
+        val |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte < 0x80 {return Some(val)}
+    }
+    None
+}
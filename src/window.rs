@@ -0,0 +1,90 @@
+//! Extracting the frames of a time window.
+
+use std::io::{self, Read, SeekFrom};
+use std::ops::Range;
+
+use compact::{self, CompactOptions};
+use endian::EndianWriter;
+use index::Index;
+use State;
+
+/// Options for extracting a time window.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindowOptions {
+    /// Write the state before the window in front of the first frame.
+    ///
+    /// This makes the output self-contained for properties that are written as changes.
+    pub keyframe: bool,
+}
+
+/// Copies the frames with time in `t0..=t1`.
+///
+/// Frames start with the time property, which is also the frame marker.
+/// The data before the first frame, such as the file header and the time unit, is kept.
+/// Frames are copied without changes.
+///
+/// A frame without time, where the time property has no built-in format or no data,
+/// belongs to the window when the frame before it does.
+/// When time is not monotonic, each frame is picked by its own time,
+/// so the frames copied might not be contiguous.
+///
+/// With `WindowOptions::keyframe`, the final state of the stream before the first frame copied
+/// is written instead of the data before the first frame, like `compact` does.
+/// The keyframe has no time, so readers see it as data before the first frame.
+/// It is written in the byte order of the stream.
+/// The changes in frames that are skipped after the first frame copied are not included.
+///
+/// The output is terminated with an end of stream.
+pub fn extract_window<R: io::Read + io::Seek, W: io::Write>(
+    r: &mut R,
+    w: &mut W,
+    time_property: u16,
+    t0: f64,
+    t1: f64,
+    opts: &WindowOptions
+) -> io::Result<()> {
+    if t0.is_nan() || t1.is_nan() || t0 > t1 {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    let start = r.stream_position()?;
+    let index = Index::build(r, time_property, Some(time_property))?;
+    let mut picked = vec![];
+    let mut inside = false;
+    for (n, frame) in index.frames().iter().enumerate() {
+        if let Some(t) = frame.time {
+            inside = t0 <= t && t <= t1;
+        }
+        if inside {picked.push(n as u64)}
+    }
+    let first = match picked.first() {
+        Some(&n) => index.frame_range(n)?.start,
+        None => index.preamble_range().end,
+    };
+    if opts.keyframe {
+        r.seek(SeekFrom::Start(start))?;
+        compact::compact_until(r, &mut EndianWriter::new(&mut *w), Some(first), &CompactOptions {
+            frame_marker: Some(time_property),
+            keep_marker: false,
+        })?;
+    } else {
+        copy_range(index.preamble_range(), r, w)?;
+    }
+    for n in picked {
+        copy_range(index.frame_range(n)?, r, w)?;
+    }
+    State::new().end_type_formats(w)
+}
+
+/// Copies bytes of the stream.
+fn copy_range<R: io::Read + io::Seek, W: io::Write>(
+    range: Range<u64>,
+    r: &mut R,
+    w: &mut W
+) -> io::Result<()> {
+    r.seek(SeekFrom::Start(range.start))?;
+    let n = range.end - range.start;
+    if io::copy(&mut r.by_ref().take(n), w)? != n {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
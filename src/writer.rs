@@ -0,0 +1,341 @@
+//! Writing streams with a header.
+
+use std::io;
+use std::mem;
+
+use endian::{Converter, Endian, Header};
+use error::{Error, ErrorKind};
+use finite::FiniteCheck;
+use footer::{self, Recorder};
+use raw::{self, RawBlock};
+use reserved::{self, HEADER_FORMAT, HEADER_PROPERTY};
+use State;
+
+/// Options for writing a stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Return an error when writing NaN or infinite values of `f32` or `f64` properties.
+    pub reject_non_finite: bool,
+    /// Allow properties with reserved ids, which is an error by default.
+    ///
+    /// Properties with reserved ids are allowed with reserved formats,
+    /// since these are written by the crate.
+    pub allow_reserved: bool,
+    /// Align the data of the first block of every property to this many bytes,
+    /// from the start of the stream, such that a mapped file can be read without copying.
+    ///
+    /// Padding properties are inserted where needed and the alignment is recorded in the header.
+    /// Blocks after the first block of a property are not aligned.
+    /// 0 and 1 disable alignment.
+    pub alignment: u16,
+}
+
+impl WriteOptions {
+    /// Sets whether to reject NaN and infinite values.
+    pub fn reject_non_finite(mut self, val: bool) -> WriteOptions {
+        self.reject_non_finite = val;
+        self
+    }
+
+    /// Sets whether to allow properties with reserved ids.
+    pub fn allow_reserved(mut self, val: bool) -> WriteOptions {
+        self.allow_reserved = val;
+        self
+    }
+
+    /// Sets the alignment of data in bytes.
+    pub fn alignment(mut self, val: u16) -> WriteOptions {
+        self.alignment = val;
+        self
+    }
+}
+
+/// Writes a stream starting with a file header.
+///
+/// Data is written through the writer with the usual functions,
+/// e.g. `Scalar::write_array(POSITION, &pos, &mut writer)`.
+/// The header is written before the first byte of data.
+/// Properties with reserved ids are refused, unless allowed by `WriteOptions`.
+/// Nothing of a refused write reaches the underlying writer,
+/// but the writer should not be used afterwards.
+///
+/// With big endian byte order, the writer swaps the header fields
+/// and the scalars of built-in type formats as they pass through:
+///
+/// ```ignore
+/// let mut w = PoolWriter::new(file).with_endianness(Endian::Big);
+/// Vector::write_array(POSITION, &pos, &mut w)?;
+/// w.finish()?;
+/// ```
+pub struct PoolWriter<W: io::Write> {
+    inner: W,
+    header: Header,
+    header_written: bool,
+    converter: Converter,
+    finite: Option<FiniteCheck>,
+    allow_reserved: bool,
+    held: Vec<u8>,
+    position: u64,
+    footer: Option<Recorder>,
+    footer_index: bool,
+}
+
+impl<W: io::Write> PoolWriter<W> {
+    /// Creates a new writer using little endian byte order.
+    pub fn new(inner: W) -> PoolWriter<W> {
+        PoolWriter {
+            inner,
+            header: Header::default(),
+            header_written: false,
+            converter: Converter::default(),
+            finite: None,
+            allow_reserved: false,
+            held: vec![],
+            position: 0,
+            footer: None,
+            footer_index: false,
+        }
+    }
+
+    /// Sets the byte order of the stream.
+    pub fn with_endianness(mut self, endian: Endian) -> PoolWriter<W> {
+        self.header.endian = endian;
+        self
+    }
+
+    /// Sets the options for writing.
+    pub fn with_options(mut self, opts: WriteOptions) -> PoolWriter<W> {
+        self.finite = if opts.reject_non_finite {Some(FiniteCheck::default())} else {None};
+        self.allow_reserved = opts.allow_reserved;
+        self.header.alignment = if opts.alignment > 1 {opts.alignment} else {0};
+        self
+    }
+
+    /// Sets whether `finish` writes an index of all properties at the end of the stream.
+    ///
+    /// The footer is a property with a reserved format before the end of stream,
+    /// followed by a trailer after the end of stream that locates it,
+    /// such that `Index::load_or_build` and `last_frame` do not scan the stream.
+    /// Readers skip the footer.
+    /// The index takes 24 bytes per property, which are kept in memory until finishing.
+    /// An end of stream written through the writer, e.g. by `FrameWriter::finish`,
+    /// is held back until `finish`, such that the footer comes before it.
+    ///
+    /// This should be set before anything is written.
+    pub fn with_footer_index(mut self, val: bool) -> PoolWriter<W> {
+        self.footer_index = val;
+        self.footer = if val {
+            Some(self.footer.take().unwrap_or_else(|| Recorder::new(self.position, vec![])))
+        } else {None};
+        self
+    }
+
+    /// Returns the byte order of the stream.
+    pub fn endianness(&self) -> Endian {
+        self.header.endian
+    }
+
+    /// Returns the file header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes the header, if it is not written yet.
+    pub fn write_header(&mut self) -> io::Result<()> {
+        if self.header_written {return Ok(())}
+        self.header_written = true;
+        let header = self.header;
+        header.write(self)
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing directly to the underlying writer bypasses byte order conversion.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes end of stream and returns the underlying writer.
+    ///
+    /// With a footer index, the footer is written before the end of stream
+    /// and the trailer after it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_header()?;
+        let recorder = if self.footer_index {self.footer.take()} else {None};
+        self.footer_index = false;
+        match recorder {
+            Some(recorder) => {
+                let position = self.position;
+                self.pass(&footer::encode(&recorder.into_entries()))?;
+                State::new().end_type_formats(&mut self)?;
+                self.inner.write_all(&footer::trailer(position))?;
+            }
+            None => State::new().end_type_formats(&mut self)?,
+        }
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: io::Read + io::Write + io::Seek> PoolWriter<W> {
+    /// Continues a finished stream, starting at the current position of `inner`.
+    ///
+    /// New data replaces the end of stream, so the stream must be finished again.
+    /// The byte order and alignment are read from the file header.
+    /// The stream is scanned to find its end, unless it has a footer index.
+    /// A footer index is invalidated right away,
+    /// and written again when finishing, with the properties before and after.
+    /// Use `with_footer_index` to add or drop it.
+    ///
+    /// Returns an error of kind `io::ErrorKind::UnexpectedEof`
+    /// if the stream ends inside a property, see `recover`.
+    pub fn append(mut inner: W) -> io::Result<PoolWriter<W>> {
+        let start = inner.stream_position()?;
+        let header = match raw::read_property_header(&mut inner)? {
+            Some((state, ty, prop)) if ty == HEADER_FORMAT && prop == HEADER_PROPERTY => {
+                let blocks = RawBlock::read_property(state, ty, prop, &mut inner)?;
+                blocks.first().and_then(|block| Header::decode(&block.data))
+            }
+            _ => None,
+        };
+        let (recorder, footer_index) = match footer::read(&mut inner, start)? {
+            Some(footer) => {
+                let trailer = [0; footer::TRAILER as usize];
+                inner.seek(io::SeekFrom::Start(start + footer.end + 2))?;
+                inner.write_all(&trailer)?;
+                inner.flush()?;
+                (Recorder::new(footer.position, footer.entries), true)
+            }
+            None => {
+                inner.seek(io::SeekFrom::Start(start))?;
+                let recorder = footer::scan(&mut inner)?;
+                let end = recorder.end().unwrap_or(recorder.position());
+                (Recorder::new(end, recorder.into_entries()), false)
+            }
+        };
+        let position = recorder.position();
+        inner.seek(io::SeekFrom::Start(start + position))?;
+        let mut w = PoolWriter::new(inner);
+        w.header = header.unwrap_or_default();
+        w.header_written = position > 0;
+        w.converter = if position > 0 {Converter::resume(header)} else {Converter::default()};
+        w.position = position;
+        w.footer = Some(recorder);
+        w.footer_index = footer_index;
+        Ok(w)
+    }
+}
+
+impl<W: io::Write> PoolWriter<W> {
+    /// Passes bytes through the converter to the underlying writer.
+    fn pass(&mut self, data: &[u8]) -> io::Result<()> {
+        let inner = &mut self.inner;
+        let position = &mut self.position;
+        self.converter.convert(data, true, |data| {
+            *position += data.len() as u64;
+            inner.write_all(data)
+        }, |_| Ok(()))?;
+        if let Some(ref mut footer) = self.footer {footer.consume(data)}
+        Ok(())
+    }
+
+    /// Passes bytes through the converter, inserting padding before properties
+    /// such that the data of their first block is aligned.
+    fn pass_aligned(&mut self, mut data: &[u8]) -> io::Result<()> {
+        let alignment = self.header.alignment as u64;
+        while !data.is_empty() {
+            // The header is not padded, nor the end of stream.
+            if self.converter.at_property_boundary() && self.position > 0 &&
+               !data.starts_with(&[0, 0]) {
+                // Data starts after type format, property id, bytes and offset.
+                let pad = padding((alignment - (self.position + 20) % alignment) % alignment,
+                    alignment);
+                if pad > 0 {
+                    let mut buf = Vec::with_capacity(pad as usize);
+                    buf.extend_from_slice(&reserved::PADDING_FORMAT.to_le_bytes());
+                    buf.extend_from_slice(&reserved::PADDING_PROPERTY.to_le_bytes());
+                    if pad > 12 {
+                        buf.extend_from_slice(&(pad - 28).to_le_bytes());
+                        buf.extend_from_slice(&0_u64.to_le_bytes());
+                        buf.resize(pad as usize - 8, 0);
+                    }
+                    buf.extend_from_slice(&0_u64.to_le_bytes());
+                    self.pass(&buf)?;
+                }
+            }
+            // Fields before the data are passed one byte at a time to find where properties end.
+            let n = (self.converter.remaining() as usize).min(data.len()).max(1);
+            self.pass(&data[..n])?;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+}
+
+/// Returns the size of a padding property that adds `needed` bytes modulo `alignment`.
+///
+/// A padding property without blocks takes 12 bytes,
+/// and one with a block takes 28 bytes plus at least one byte of data.
+fn padding(needed: u64, alignment: u64) -> u64 {
+    if needed == 0 || needed == 12 {return needed}
+    let mut pad = needed;
+    while pad < 29 {pad += alignment}
+    pad
+}
+
+impl<W: io::Write> io::Write for PoolWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_header()?;
+        let mut rest = buf;
+        if !self.allow_reserved && self.converter.at_property_boundary() {
+            // The type format and property id are held back until both are known,
+            // such that nothing of a refused property is written.
+            while self.held.len() < 4 && !self.held.starts_with(&[0, 0]) && !rest.is_empty() {
+                self.held.push(rest[0]);
+                rest = &rest[1..];
+            }
+            if self.held.len() < 4 && !self.held.starts_with(&[0, 0]) {return Ok(buf.len())}
+            let held = mem::take(&mut self.held);
+            if held.len() == 4 {
+                let type_format = u16::from_le_bytes([held[0], held[1]]);
+                let property_id = u16::from_le_bytes([held[2], held[3]]);
+                if reserved::is_reserved_property(property_id) &&
+                   !reserved::is_reserved_format(type_format) {
+                    return Err(Error::new(ErrorKind::ReservedProperty)
+                        .with_property(property_id)
+                        .into());
+                }
+            }
+            self.write_parts(&held)?;
+        }
+        if !rest.is_empty() {self.write_parts(rest)?}
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: io::Write> PoolWriter<W> {
+    /// Writes bytes after the check of reserved property ids.
+    fn write_parts(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.footer_index && self.converter.at_property_boundary() && buf.starts_with(&[0, 0]) {
+            // The end of stream is written by `finish`, after the footer.
+            return Ok(());
+        }
+        if let Some(ref mut finite) = self.finite {
+            finite.check(buf)?;
+        }
+        if self.header.alignment > 1 {
+            self.pass_aligned(buf)
+        } else {
+            self.pass(buf)
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! XOR delta encoding of properties between frames.
+//!
+//! Values that vary slowly between frames differ in few bits,
+//! so the XOR of the data with the data of the previous frame is mostly zero bytes.
+//! `FrameWriter::write_xor_delta` writes the first frame of a property as a plain block,
+//! and later frames as a block with a reserved custom format under the property id,
+//! with a keyframe written as a plain block at regular frame numbers.
+//! `FrameReader` replaces these blocks with plain blocks.
+//!
+//! The data of a block starts with the type format of the items (u16),
+//! followed by the XOR of the data with the previous data, as runs.
+//! A control byte below 128 is followed by no bytes and stands for `control + 1` zero bytes.
+//! A control byte of 128 or more is followed by `control - 127` bytes that are not compressed.
+
+use std::io;
+
+use error::{Error, ErrorKind};
+use raw::{self, RawBlock};
+use reserved::XOR_FORMAT;
+
+/// Encodes the XOR of data with the previous data.
+pub(crate) fn encode(type_format: u16, previous: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut res = type_format.to_le_bytes().to_vec();
+    let mut i = 0;
+    while i < data.len() {
+        let zeros = (i..data.len()).take(128).take_while(|&j| data[j] == previous[j]).count();
+        if zeros > 0 {
+            res.push(zeros as u8 - 1);
+            i += zeros;
+            continue;
+        }
+        // Short runs of zero bytes are kept in literals, since they cost a control byte.
+        let start = i;
+        while i < data.len() && i - start < 128 {
+            if data[i] == previous[i] && data.get(i + 1).map(|&b| b == previous[i + 1]).unwrap_or(true) {
+                break;
+            }
+            i += 1;
+        }
+        res.push(127 + (i - start) as u8);
+        res.extend(data[start..i].iter().zip(&previous[start..i]).map(|(a, b)| a ^ b));
+    }
+    res
+}
+
+/// Decodes data from the XOR with the previous data.
+fn decode(block: &RawBlock, previous: &RawBlock) -> io::Result<RawBlock> {
+    let err = || -> io::Error {
+        Error::new(ErrorKind::InvalidData).with_property(block.property_id).into()
+    };
+    let data = &block.data;
+    if data.len() < 2 || u16::from_le_bytes([data[0], data[1]]) != previous.type_format {
+        return Err(err());
+    }
+    let mut res = Vec::with_capacity(previous.data.len());
+    let mut i = 2;
+    while i < data.len() {
+        let control = data[i] as usize;
+        i += 1;
+        if control < 128 {
+            res.resize(res.len() + control + 1, 0);
+        } else {
+            let n = control - 127;
+            if data.len() < i + n {return Err(err())}
+            res.extend_from_slice(&data[i..i + n]);
+            i += n;
+        }
+        if res.len() > previous.data.len() {return Err(err())}
+    }
+    if res.len() != previous.data.len() {return Err(err())}
+    for (a, b) in res.iter_mut().zip(&previous.data) {*a ^= b}
+    Ok(RawBlock {
+        type_format: previous.type_format,
+        property_id: block.property_id,
+        offset: previous.offset,
+        data: res,
+    })
+}
+
+struct Previous {
+    property_id: u16,
+    type_format: u16,
+    data: Vec<u8>,
+    frame: u64,
+}
+
+/// The last data of every property written by a frame writer.
+pub(crate) struct XorWriter {
+    keyframe_interval: u64,
+    previous: Vec<Previous>,
+}
+
+impl XorWriter {
+    pub fn new(keyframe_interval: u64) -> XorWriter {
+        XorWriter {keyframe_interval: keyframe_interval.max(1), previous: vec![]}
+    }
+
+    pub fn set_keyframe_interval(&mut self, val: u64) {
+        self.keyframe_interval = val.max(1);
+    }
+
+    /// Writes the data of a property in a frame, or before the first frame when `frame` is `None`.
+    ///
+    /// A delta is written when the previous data of the property was written
+    /// in an earlier frame since the last keyframe, with the same type format and size.
+    pub fn write<W: io::Write>(
+        &mut self,
+        frame: Option<u64>,
+        property_id: u16,
+        type_format: u16,
+        data: Vec<u8>,
+        w: &mut W
+    ) -> io::Result<()> {
+        let pos = self.previous.iter().position(|p| p.property_id == property_id);
+        let frame = match frame {
+            None => {
+                return RawBlock::write_property(type_format, property_id, &[RawBlock {
+                    type_format, property_id, offset: 0, data
+                }], w);
+            }
+            Some(x) => x,
+        };
+        let keyframe = frame - frame % self.keyframe_interval;
+        let delta = match pos {
+            Some(pos) => {
+                let p = &self.previous[pos];
+                frame != keyframe && p.frame >= keyframe && p.frame < frame &&
+                p.type_format == type_format && p.data.len() == data.len()
+            }
+            None => false,
+        };
+        if let (true, Some(pos)) = (delta, pos) {
+            let xor = encode(type_format, &self.previous[pos].data, &data);
+            RawBlock::write_property(XOR_FORMAT, property_id, &[RawBlock {
+                type_format: XOR_FORMAT, property_id, offset: 0, data: xor
+            }], w)?;
+            self.previous[pos] = Previous {property_id, type_format, data, frame};
+        } else {
+            RawBlock::write_property(type_format, property_id, &[RawBlock {
+                type_format, property_id, offset: 0, data: data.clone()
+            }], w)?;
+            let again = pos.map(|pos| self.previous.swap_remove(pos).frame == frame);
+            // The reader forgets data written more than once in a frame.
+            if again != Some(true) {
+                self.previous.push(Previous {property_id, type_format, data, frame});
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The last plain block of every property read by a frame reader.
+#[derive(Default)]
+pub(crate) struct XorState {
+    previous: Vec<RawBlock>,
+}
+
+impl XorState {
+    /// Replaces XOR delta blocks of a frame with plain blocks
+    /// and remembers the data of properties with one block.
+    ///
+    /// Returns an error if the previous data of a property is not known,
+    /// e.g. after seeking to a frame that is not a keyframe.
+    pub fn apply(&mut self, blocks: &mut [RawBlock]) -> io::Result<()> {
+        for i in 0..blocks.len() {
+            let prop = blocks[i].property_id;
+            if blocks[i].type_format == XOR_FORMAT {
+                let decoded = match self.previous.iter().find(|b| b.property_id == prop) {
+                    Some(previous) => decode(&blocks[i], previous)?,
+                    None => return Err(Error::new(ErrorKind::InvalidData)
+                        .with_property(prop).into()),
+                };
+                blocks[i] = decoded;
+            } else if raw::item_size(blocks[i].type_format).is_none() {
+                continue;
+            }
+            let single = blocks.iter().filter(|b| b.property_id == prop).count() == 1;
+            match self.previous.iter().position(|b| b.property_id == prop) {
+                Some(j) if single => {
+                    // Reuse the buffer of the previous data.
+                    let previous = &mut self.previous[j];
+                    previous.type_format = blocks[i].type_format;
+                    previous.offset = blocks[i].offset;
+                    previous.data.clear();
+                    previous.data.extend_from_slice(&blocks[i].data);
+                }
+                Some(j) => {self.previous.swap_remove(j);}
+                None if single => self.previous.push(blocks[i].clone()),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the previous data, e.g. when seeking.
+    pub fn clear(&mut self) {
+        self.previous.clear();
+    }
+}
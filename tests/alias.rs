@@ -0,0 +1,121 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const OLD_VELOCITY: u16 = 7;
+const VELOCITY: u16 = 12;
+
+/// Writes frames with velocity under the old id, the new id, or both.
+fn stream(ids: &[&[u16]], endian: Endian) -> Vec<u8> {
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]).with_endianness(endian), TIME);
+    for (frame, ids) in ids.iter().enumerate() {
+        w.begin_frame(frame as f64).unwrap();
+        for &id in ids.iter() {
+            Scalar::write_array(id, &vec![id as f32 + frame as f32 / 10.0; 2], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap().get_ref().clone()
+}
+
+fn aliases(policy: AliasConflict) -> Aliases {
+    let mut aliases = Aliases::new().on_conflict(policy);
+    aliases.alias(VELOCITY, &[OLD_VELOCITY]).unwrap();
+    aliases
+}
+
+/// Reads the first velocity of each frame.
+fn velocities(data: &[u8], policy: AliasConflict) -> io::Result<Vec<f32>> {
+    let mut r = FrameReader::with_time(data, TIME).aliases(aliases(policy));
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame()? {
+        assert!(frame.blocks.iter().all(|b| b.property_id != OLD_VELOCITY));
+        let mut v: Vec<f32> = vec![];
+        frame.read_array(VELOCITY, &mut v)?;
+        res.push(v[0]);
+    }
+    Ok(res)
+}
+
+#[test]
+fn mixed_frames() {
+    let data = stream(&[&[OLD_VELOCITY], &[VELOCITY], &[OLD_VELOCITY]], Endian::Little);
+    for &policy in &[AliasConflict::Error, AliasConflict::PreferNew, AliasConflict::PreferOld] {
+        assert_eq!(velocities(&data, policy).unwrap(), vec![7.0, 12.1, 7.2]);
+    }
+}
+
+#[test]
+fn conflicts() {
+    let data = stream(&[&[OLD_VELOCITY], &[VELOCITY, OLD_VELOCITY]], Endian::Little);
+    let err = velocities(&data, AliasConflict::Error).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(VELOCITY)));
+    assert_eq!(velocities(&data, AliasConflict::PreferNew).unwrap(), vec![7.0, 12.1]);
+    assert_eq!(velocities(&data, AliasConflict::PreferOld).unwrap(), vec![7.0, 7.1]);
+}
+
+#[test]
+fn reader_and_dispatcher() {
+    let data = stream(&[&[OLD_VELOCITY], &[VELOCITY]], Endian::Little);
+    let mut r = PoolReader::new(&data[..]).aliases(aliases(AliasConflict::Error));
+    r.register(TIME);
+    r.register(VELOCITY);
+    let mut ids = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        ids.push(prop);
+    }
+    assert_eq!(ids, vec![TIME, VELOCITY, TIME, VELOCITY]);
+
+    let mut seen = vec![];
+    {
+        let mut d = Dispatcher::new();
+        d.aliases(aliases(AliasConflict::Error));
+        d.on_array(VELOCITY, |_, items: &[f32]| {
+            seen.push(items[0]);
+            Ok(())
+        });
+        d.run(&mut PoolReader::new(&data[..]).unknown(Unknown::Collect)).unwrap();
+    }
+    assert_eq!(seen, vec![7.0, 12.1]);
+}
+
+#[test]
+fn remap() {
+    let frames: &[&[u16]] = &[&[OLD_VELOCITY], &[VELOCITY]];
+    let le = stream(frames, Endian::Little);
+    for &endian in &[Endian::Little, Endian::Big] {
+        let mut out = vec![];
+        aliases(AliasConflict::Error).remap(&mut &stream(frames, endian)[..], &mut out).unwrap();
+        // The file header is skipped and the properties are little endian.
+        let header = Header::default().encode().len() + 28;
+        assert_eq!(out.len(), le.len() - header);
+        let mut r = FrameReader::with_time(&out[..], TIME);
+        let mut res = vec![];
+        while let Some(frame) = r.next_frame().unwrap() {
+            let mut v: Vec<f32> = vec![];
+            frame.read_array(VELOCITY, &mut v).unwrap();
+            res.push(v[0]);
+        }
+        assert_eq!(res, vec![7.0, 12.1], "{:?}", endian);
+    }
+}
+
+#[test]
+fn invalid_aliases() {
+    let mut aliases = Aliases::new();
+    aliases.alias(VELOCITY, &[OLD_VELOCITY]).unwrap();
+    assert!(aliases.is_alias(OLD_VELOCITY));
+    assert_eq!(aliases.aliases_of(VELOCITY).collect::<Vec<_>>(), vec![OLD_VELOCITY]);
+    let invalid = [(VELOCITY, VELOCITY), (3, OLD_VELOCITY), (OLD_VELOCITY, 3), (3, VELOCITY)];
+    for &(new, old) in &invalid {
+        let err = aliases.alias(new, &[old]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{} <- {}", new, old);
+    }
+    assert_eq!(aliases.resolve(3), 3);
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::borrow::Cow;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const NAME: u16 = 3;
+const ROTATION: u16 = 4;
+
+const ALIGNMENTS: [u16; 7] = [2, 3, 4, 8, 16, 64, 100];
+
+/// Writes a stream of mixed properties, with odd sizes.
+fn stream(endian: Endian, alignment: u16) -> Vec<u8> {
+    let opts = WriteOptions::default().alignment(alignment);
+    let w = PoolWriter::new(vec![]).with_endianness(endian).with_options(opts);
+    let mut w = FrameWriter::new(w, TIME);
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new("m", 1.0));
+    units.write_units(&mut w).unwrap();
+    let mut rng = Rng::new(160);
+    for i in 0..20 {
+        w.begin_frame(i as f64).unwrap();
+        let n = 1 + rng.below(13) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+        Vector::write_array(POSITION, &pos, &mut w).unwrap();
+        Scalar::write_array(MASS, &vec![rng.unit(); n], &mut w).unwrap();
+        let name: Vec<u8> = (0..1 + rng.below(7)).map(|_| rng.below(256) as u8).collect();
+        Scalar::write_array(NAME, &name, &mut w).unwrap();
+        let rot = vec![[[rng.unit() as f32, 0.0], [0.0, 1.0]]; n];
+        Matrix::write_array(ROTATION, &rot, &mut w).unwrap();
+        // A property with several blocks.
+        let ty = Type::U16.vector(1).unwrap().0;
+        RawBlock::write_property(ty, MASS + 10, &[
+            RawBlock {type_format: ty, property_id: MASS + 10, offset: 0, data: vec![1; 6]},
+            RawBlock {type_format: ty, property_id: MASS + 10, offset: 9, data: vec![2; 2]},
+        ], &mut w).unwrap();
+    }
+    w.finish().unwrap().get_ref().clone()
+}
+
+/// Copies the data to memory aligned to 64 bytes, like a mapped file.
+fn aligned(data: &[u8]) -> (Vec<u8>, usize) {
+    let mut buf = Vec::with_capacity(data.len() + 64);
+    let shift = (64 - buf.as_ptr() as usize % 64) % 64;
+    buf.resize(shift, 0);
+    buf.extend_from_slice(data);
+    (buf, shift)
+}
+
+type Property = (u16, u16, Vec<(u64, Vec<u8>)>);
+
+/// Reads every property, checking that the data of first blocks is aligned.
+fn properties(data: &[u8], alignment: usize) -> Vec<Property> {
+    let mut r = MappedReader::new(data).unwrap();
+    let mut res = vec![];
+    while let Some(prop) = r.next_property().unwrap() {
+        let first = prop.blocks[0].data.as_ptr() as usize - data.as_ptr() as usize;
+        assert!(first.is_multiple_of(alignment), "{} at {}", alignment, first);
+        let blocks = prop.blocks.iter().map(|b| (b.offset, b.data.to_vec())).collect();
+        res.push((prop.type_format, prop.property_id, blocks));
+    }
+    res
+}
+
+#[test]
+fn every_payload_is_aligned() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let plain = stream(endian, 0);
+        let expected = properties(&plain, 1);
+        assert_eq!(expected.len(), 1 + 20 * 6);
+        for &alignment in &ALIGNMENTS {
+            let padded = stream(endian, alignment);
+            assert!(padded.len() > plain.len());
+            let (buf, shift) = aligned(&padded);
+            let data = &buf[shift..];
+            let r = MappedReader::new(data).unwrap();
+            assert_eq!(r.header().map(|h| h.alignment), Some(alignment));
+            // The data is identical, without the padding.
+            assert!(properties(data, alignment as usize) == expected, "{}", alignment);
+            validate(&mut &padded[..], &ValidateOptions::default()).unwrap();
+        }
+    }
+    // Alignment 0 and 1 write no padding.
+    assert_eq!(stream(Endian::Little, 1), stream(Endian::Little, 0));
+    let r = MappedReader::new(&stream(Endian::Little, 1)[..]).unwrap().header().unwrap();
+    assert_eq!(r.alignment, 0);
+}
+
+#[test]
+fn borrowed_scalars() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        for &alignment in &[0, 8] {
+            let (buf, shift) = aligned(&stream(endian, alignment));
+            let mut r = MappedReader::new(&buf[shift..]).unwrap();
+            let mut masses = 0;
+            while let Some(prop) = r.next_property().unwrap() {
+                let block = &prop.blocks[0];
+                match prop.property_id {
+                    POSITION => {
+                        let pos = block.scalars::<f32>().unwrap();
+                        assert_eq!(pos.len(), block.data.len() / 4);
+                        // Without padding, the data is only aligned by chance.
+                        let aligned = alignment == 8 ||
+                            (block.data.as_ptr() as usize).is_multiple_of(4);
+                        let borrowed = cfg!(target_endian = "little") && aligned &&
+                            endian == Endian::Little;
+                        assert_eq!(matches!(pos, Cow::Borrowed(_)), borrowed);
+                        assert_eq!(block.is_borrowable::<f32>(), borrowed);
+                    }
+                    MASS => {
+                        let mass = block.scalars::<f64>().unwrap();
+                        // Every mass of a frame is the same.
+                        assert!(mass.iter().all(|&m| m == mass[0] && (0.0..1.0).contains(&m)));
+                        masses += 1;
+                        let err = Error::from_io(&block.scalars::<f32>().unwrap_err());
+                        assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+                        assert_eq!(err.property(), Some(MASS));
+                    }
+                    _ => {}
+                }
+            }
+            assert_eq!(masses, 20);
+        }
+    }
+}
+
+#[test]
+fn readers_skip_padding() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let read = |data: &[u8]| {
+            let mut r = FrameReader::with_time(PoolReader::new(data), TIME);
+            let mut res = vec![];
+            while let Some(frame) = r.next_frame().unwrap() {
+                res.push((frame.time, frame.blocks));
+            }
+            res
+        };
+        let expected = read(&stream(endian, 0));
+        assert_eq!(expected.len(), 20);
+        assert!(expected.iter().all(|(_, blocks)| blocks.len() >= 6));
+        for &alignment in &ALIGNMENTS {
+            assert!(read(&stream(endian, alignment)) == expected);
+        }
+    }
+    // Without conversion, for little endian streams.
+    let data = stream(Endian::Little, 16);
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut frames = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        assert!(frame.blocks.iter().all(|b| b.property_id != PADDING_PROPERTY));
+        frames += 1;
+    }
+    assert_eq!(frames, 20);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+const PARTICLES: u64 = 40;
+/// The frame where particles die.
+const DIE: u64 = 3;
+/// The particles that die.
+const DEAD: [u64; 3] = [3, 17, 30];
+
+/// Writes frames of particles, where some particles die and new ones reuse their ids.
+///
+/// Returns the stream and the expected alive ids of every frame.
+fn stream() -> (Vec<u8>, Vec<Vec<u64>>) {
+    let mut w = FrameWriter::new(vec![], TIME);
+    let mut alive = AliveMask::new();
+    let mut positions = vec![];
+    let mut expected = vec![];
+    for t in 0..6 {
+        w.begin_frame(t as f64).unwrap();
+        match t {
+            0 => for _ in 0..PARTICLES {
+                let id = alive.spawn();
+                assert_eq!(id, positions.len() as u64);
+                positions.push([id as f32, 0.0, 0.0]);
+            },
+            DIE => for &id in &DEAD {alive.kill(id)},
+            4 | 5 => for _ in 0..2 {
+                let id = alive.spawn();
+                positions.resize(positions.len().max(id as usize + 1), [0.0; 3]);
+                positions[id as usize] = [-(id as f32), t as f32, 0.0];
+            },
+            _ => {}
+        }
+        // Dead particles keep their stale positions.
+        for (_, p) in alive.zip_mut(&mut positions) {p[2] = t as f32}
+        Vector::write_array(POSITION, &positions, &mut w).unwrap();
+        alive.write_changes(&mut w).unwrap();
+        expected.push(alive.ids().collect());
+    }
+    (w.finish().unwrap(), expected)
+}
+
+#[test]
+fn die_and_reuse_ids() {
+    let (data, expected) = stream();
+    // Freed ids are reused in increasing order, and the particle set grows after that.
+    assert_eq!(expected[DIE as usize].len() as u64, PARTICLES - 3);
+    assert_eq!(expected[4].len() as u64, PARTICLES - 1);
+    assert!(expected[4].contains(&3) && expected[4].contains(&17) && !expected[4].contains(&30));
+    assert_eq!(expected[5], (0..PARTICLES + 1).collect::<Vec<u64>>());
+
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut n = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        let alive = r.alive();
+        assert_eq!(alive.ids().collect::<Vec<u64>>(), expected[n]);
+        assert_eq!(alive.count(), expected[n].len() as u64);
+        let mut positions: Vec<[f32; 3]> = vec![];
+        frame.read_array(POSITION, &mut positions).unwrap();
+
+        // Only alive particles were moved in this frame.
+        let moved: Vec<u64> = alive.zip(&positions)
+            .inspect(|&(_, p)| assert_eq!(p[2], n as f32))
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(moved, expected[n]);
+        if n > DIE as usize {
+            for &id in &DEAD {
+                if !alive.is_alive(id) {assert_eq!(positions[id as usize][2], DIE as f32 - 1.0)}
+            }
+        }
+        // Reused ids have the data of the new particle.
+        if n >= 4 {
+            assert_eq!(positions[3], [-3.0, 4.0, n as f32]);
+            assert_eq!(positions[17], [-17.0, 4.0, n as f32]);
+        }
+
+        // The whole mask is written in the first frame, and afterwards only the bytes that changed.
+        let blocks: Vec<(u64, usize)> = frame.blocks.iter()
+            .filter(|b| b.type_format == ALIVE_FORMAT)
+            .map(|b| (b.offset, b.data.len()))
+            .collect();
+        let expected_blocks = match n {
+            0 => vec![(0, 5)],
+            // Adjacent bytes that changed are written in one block.
+            3 => vec![(0, 1), (16, 2)],
+            4 => vec![(0, 1), (16, 1)],
+            5 => vec![(24, 1), (40, 1)],
+            _ => vec![],
+        };
+        assert_eq!(blocks, expected_blocks, "frame {}", n);
+        n += 1;
+    }
+    assert_eq!(n, 6);
+}
+
+#[test]
+fn invalid_blocks() {
+    let mut alive = AliveMask::new();
+    let block = |offset: u64, data: Vec<u8>| RawBlock {
+        type_format: ALIVE_FORMAT,
+        property_id: ALIVE_PROPERTY,
+        offset,
+        data,
+    };
+    alive.apply(&block(8, vec![0b101])).unwrap();
+    assert_eq!(alive.ids().collect::<Vec<u64>>(), vec![8, 10]);
+    for bad in &[block(4, vec![1]), block(9, vec![])] {
+        let err = Error::from_io(&alive.apply(bad).unwrap_err());
+        assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(ALIVE_PROPERTY)));
+    }
+    let mut other = block(0, vec![1]);
+    other.type_format = Type::U8.scalar().0;
+    assert!(alive.apply(&other).is_err());
+
+    // Blocks at unaligned offsets are rejected while reading frames.
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    RawBlock::write_property(ALIVE_FORMAT, ALIVE_PROPERTY, &[block(3, vec![1])], &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let err = Error::from_io(&r.next_frame().unwrap_err());
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::sync::Mutex;
+use std::thread;
+
+#[test]
+fn concurrent_modules() {
+    let alloc = Mutex::new(PropertyAllocator::new());
+    let modules = ["physics", "diagnostics"];
+    let ids: Vec<Vec<(String, u16)>> = thread::scope(|s| {
+        let threads: Vec<_> = modules.iter().map(|module| {
+            let alloc = &alloc;
+            s.spawn(move || {
+                let mut ids = vec![];
+                for i in 0..200 {
+                    // Both modules also ask for shared names.
+                    let name = if i % 4 == 0 {
+                        format!("shared/{}", i)
+                    } else {
+                        format!("{}/{}", module, i)
+                    };
+                    let id = alloc.lock().unwrap().get_or_assign(&name).unwrap();
+                    ids.push((name, id));
+                }
+                ids
+            })
+        }).collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    });
+    let alloc = alloc.into_inner().unwrap();
+    assert_eq!(alloc.len(), 2 * 150 + 50);
+    // Ids are dense and unique.
+    assert!(alloc.iter().map(|(id, _)| id).eq(0..350));
+    for (name, id) in ids.iter().flatten() {
+        assert_eq!(alloc.get(name), Some(*id));
+        assert_eq!(alloc.name(*id), Some(&name[..]));
+    }
+    assert_eq!(ids[0][0].1, ids[1][0].1);
+    assert_eq!(alloc.get("unknown"), None);
+}
+
+#[test]
+fn round_trip_through_file() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let mut alloc = PropertyAllocator::new();
+        alloc.assign("physics/mass", 10).unwrap();
+        let position = alloc.get_or_assign("physics/position").unwrap();
+        let note = alloc.get_or_assign("annotations/note").unwrap();
+        assert_eq!((position, note), (0, 1));
+
+        let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+        alloc.write(&mut w).unwrap();
+        Vector::write_array(position, &vec![[1.0_f32; 3]; 4], &mut w).unwrap();
+        Scalar::write_array(note, &b"start".to_vec(), &mut w).unwrap();
+        let data = w.finish().unwrap();
+
+        // An append reuses the ids, and new names get free ids.
+        let mut read = PropertyAllocator::read(&mut &data[..]).unwrap();
+        assert_eq!(read, alloc, "{:?}", endian);
+        assert_eq!(read.get_or_assign("physics/position").unwrap(), position);
+        assert_eq!(read.get_or_assign("physics/velocity").unwrap(), 2);
+        assert_eq!(read.name(10), Some("physics/mass"));
+
+        // The names are metadata, which readers collect.
+        let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+        while r.next_property().unwrap().is_some() {}
+        let m = r.metadata(note).unwrap();
+        assert_eq!(m.name(), Some("annotations/note"));
+    }
+}
+
+#[test]
+fn renamed_properties() {
+    let mut data = vec![];
+    let mut alloc = PropertyAllocator::new();
+    alloc.assign("old", 3).unwrap();
+    alloc.write(&mut data).unwrap();
+    let mut alloc = PropertyAllocator::new();
+    alloc.assign("new", 3).unwrap();
+    alloc.write(&mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let alloc = PropertyAllocator::read(&mut &data[..]).unwrap();
+    assert_eq!(alloc.iter().collect::<Vec<_>>(), vec![(3, "new")]);
+    assert_eq!(alloc.get("old"), None);
+}
+
+fn conflict(res: std::io::Result<()>, property_id: u16) {
+    let err = res.unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::PropertyConflict);
+    assert_eq!(err.property(), Some(property_id));
+}
+
+#[test]
+fn conflicts() {
+    let mut alloc = PropertyAllocator::new();
+    alloc.assign("a", 0).unwrap();
+    alloc.assign("a", 0).unwrap();
+    // The id is taken by another name.
+    conflict(alloc.assign("b", 0), 0);
+    // The name has another id.
+    conflict(alloc.assign("a", 1), 1);
+    // Reserved ids are refused.
+    conflict(alloc.assign("c", RESERVED_PROPERTY_START), RESERVED_PROPERTY_START);
+    conflict(alloc.assign("c", METADATA_PROPERTY), METADATA_PROPERTY);
+    assert_eq!(alloc.len(), 1);
+    // The lowest free id is assigned.
+    alloc.assign("d", 2).unwrap();
+    assert_eq!(alloc.get_or_assign("e").unwrap(), 1);
+    assert_eq!(alloc.get_or_assign("f").unwrap(), 3);
+
+    // A name used by two properties in a file.
+    let a = Metadata::new().with(NAME, "same");
+    let err = PropertyAllocator::from_metadata(&[(4, a.clone()), (5, a)]).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::PropertyConflict);
+}
+
+#[test]
+fn exhaustion() {
+    let mut alloc = PropertyAllocator::new();
+    for i in 0..RESERVED_PROPERTY_START {
+        assert_eq!(alloc.get_or_assign(&i.to_string()).unwrap(), i);
+    }
+    let err = alloc.get_or_assign("one more").unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::PropertyIdsExhausted);
+    // Existing names still work.
+    assert_eq!(alloc.get_or_assign("7").unwrap(), 7);
+    assert_eq!(alloc.len(), RESERVED_PROPERTY_START as usize);
+}
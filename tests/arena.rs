@@ -0,0 +1,137 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::borrow::Cow;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+const FRAMES: usize = 200;
+const PARTICLES: usize = 1000;
+
+fn position(frame: usize, i: usize) -> [f32; 3] {
+    [frame as f32, i as f32, (frame * i) as f32 * 0.5]
+}
+
+/// Writes frames where the number of particles varies a little.
+fn stream() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for t in 0..FRAMES {
+        w.begin_frame(t as f64).unwrap();
+        let n = PARTICLES - t % 7;
+        let positions: Vec<[f32; 3]> = (0..n).map(|i| position(t, i)).collect();
+        Vector::write_array(POSITION, &positions, &mut w).unwrap();
+        let masses: Vec<f64> = (0..n).map(|i| i as f64 + 0.25).collect();
+        Scalar::write_array(MASS, &masses, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// Checks a view against the written frame.
+fn check(view: &FrameView) {
+    let t = view.number as usize;
+    assert_eq!(view.time, Some(t as f64));
+    let n = PARTICLES - t % 7;
+    let scalars = view.scalars::<f32>(POSITION).unwrap().unwrap();
+    assert_eq!(scalars.len(), 3 * n);
+    let mut positions: Vec<[f32; 3]> = vec![];
+    assert!(view.read_array(POSITION, &mut positions).unwrap());
+    for (i, p) in positions.iter().enumerate() {
+        assert_eq!(*p, position(t, i));
+        assert_eq!(&scalars[3 * i..3 * i + 3], &p[..]);
+    }
+    let masses = view.scalars::<f64>(MASS).unwrap().unwrap();
+    assert_eq!(masses.len(), n);
+    assert_eq!(masses[n - 1], (n - 1) as f64 + 0.25);
+}
+
+#[test]
+fn alternating_arenas() {
+    let data = stream();
+    let mut frames = FrameReader::with_time(&data[..], TIME);
+    let mut arenas = [FrameArena::new(), FrameArena::new()];
+    let mut capacities = vec![];
+    let mut n = 0;
+    loop {
+        let (a, b) = arenas.split_at_mut(1);
+        let (current, previous) = if n % 2 == 0 {(&mut a[0], &b[0])} else {(&mut b[0], &a[0])};
+        let view = match frames.next_frame_in(current).unwrap() {
+            Some(x) => x,
+            None => break,
+        };
+        check(&view);
+        // The data is borrowed from the arena on little endian platforms, and aligned.
+        let masses = view.scalars::<f64>(MASS).unwrap().unwrap();
+        if cfg!(target_endian = "little") {
+            match masses {
+                Cow::Borrowed(x) => assert!((x.as_ptr() as usize).is_multiple_of(8)),
+                Cow::Owned(_) => panic!("copied"),
+            }
+        }
+        // The previous frame is still in the other arena.
+        if n > 0 {assert!(!previous.is_empty())}
+        n += 1;
+        capacities.push((arenas[0].capacity(), arenas[1].capacity()));
+    }
+    assert_eq!(n, FRAMES);
+
+    // The memory of the arenas stays flat once both arenas have held the largest frame,
+    // which comes every 7 frames.
+    let last = capacities[capacities.len() - 1];
+    assert!(capacities[14..].iter().all(|&c| c == last), "{:?}", &capacities[..20]);
+    for arena in &arenas {
+        assert!(arena.capacity() < 3 * (PARTICLES * (12 + 8) + 64));
+        assert!(arena.capacity() >= arena.len());
+    }
+}
+
+#[test]
+fn reset_and_copy() {
+    let data = stream();
+    let mut frames = FrameReader::with_time(&data[..], TIME);
+    let mut arena = FrameArena::with_capacity(1 << 16);
+    assert!(arena.is_empty());
+    assert_eq!(arena.capacity(), 1 << 16);
+    let frame = {
+        let view = frames.next_frame_in(&mut arena).unwrap().unwrap();
+        assert!(view.contains(POSITION) && !view.contains(7));
+        assert_eq!(view.blocks_of(MASS).count(), 1);
+        assert_eq!(view.blocks().count(), 3);
+        view.to_frame()
+    };
+    assert!(!arena.is_empty());
+    let view = frames.next_frame_in(&mut arena).unwrap().unwrap();
+    check(&view);
+    assert_eq!(view.number, 1);
+    arena.reset();
+    assert!(arena.is_empty());
+    assert_eq!(arena.capacity(), 1 << 16);
+
+    // Copies outlive the arena.
+    let mut positions: Vec<[f32; 3]> = vec![];
+    frame.read_array(POSITION, &mut positions).unwrap();
+    assert_eq!(positions[5], position(0, 5));
+
+    // Properties with several blocks are read into arrays, but not borrowed.
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    let ty = Type::F64.scalar().0;
+    let blocks: Vec<RawBlock> = (0..2).map(|i| {
+        let mut data = vec![];
+        (i as f64).write_element(&mut data).unwrap();
+        RawBlock {type_format: ty, property_id: MASS, offset: i, data}
+    }).collect();
+    RawBlock::write_property(ty, MASS, &blocks, &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let mut frames = FrameReader::with_time(&data[..], TIME);
+    let view = frames.next_frame_in(&mut arena).unwrap().unwrap();
+    let mut masses: Vec<f64> = vec![];
+    view.read_array(MASS, &mut masses).unwrap();
+    assert_eq!(masses, vec![0.0, 1.0]);
+    let err = Error::from_io(&view.scalars::<f64>(MASS).unwrap_err());
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(MASS)));
+    assert!(view.scalars::<f64>(POSITION).unwrap().is_none());
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io;
+use std::time::{Duration, Instant};
+
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// An array that only implements the required methods, counting pushes.
+#[derive(Default)]
+struct Pushed<T> {
+    items: Vec<T>,
+    pushes: usize,
+}
+
+impl<T> Array for Pushed<T> {
+    type Item = T;
+
+    fn len(&self) -> usize {self.items.len()}
+    fn get(&self, ind: usize) -> &T {&self.items[ind]}
+    fn set(&mut self, ind: usize, val: T) {self.items[ind] = val}
+    fn push(&mut self, val: T) {
+        self.pushes += 1;
+        self.items.push(val)
+    }
+    fn truncate(&mut self, len: usize) {self.items.truncate(len)}
+}
+
+#[test]
+fn resize_with_default() {
+    let mut arr = Pushed {items: vec![1, 2, 3], pushes: 0};
+    arr.resize_with_default(5);
+    assert_eq!((&arr.items[..], arr.pushes), (&[1, 2, 3, 0, 0][..], 2));
+    arr.resize_with_default(2);
+    assert_eq!(arr.items, vec![1, 2]);
+    arr.reserve(100);
+
+    let mut v = vec![1, 2, 3];
+    Array::resize_with_default(&mut v, 5);
+    assert_eq!(v, vec![1, 2, 3, 0, 0]);
+    Array::resize_with_default(&mut v, 1);
+    assert_eq!(v, vec![1]);
+    Array::reserve(&mut v, 100);
+    assert!(v.capacity() >= 101);
+}
+
+/// Writes properties of a single block each, leaving gaps between the blocks.
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    let masses = |n: usize| -> Vec<u8> {
+        (0..n).flat_map(|i| (i as f64 + 0.5).to_le_bytes().to_vec()).collect()
+    };
+    let positions = |n: usize| -> Vec<u8> {
+        (0..3 * n).flat_map(|i| (i as f32).to_le_bytes().to_vec()).collect()
+    };
+    let f64 = Type::F64.scalar().0;
+    let f32x3 = Type::F32.vector(3).unwrap().0;
+    write_block(f64, MASS, 4, &masses(3), &mut w).unwrap();
+    write_block(f64, MASS, 0, &masses(2), &mut w).unwrap();
+    write_block(f64, MASS, 20, &masses(1), &mut w).unwrap();
+    write_block(f32x3, POSITION, 2, &positions(2), &mut w).unwrap();
+    write_block(f32x3, POSITION, 9, &positions(1), &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+fn read<A, B>(data: &[u8], masses: &mut A, positions: &mut B)
+    where A: Array<Item = f64>, B: Array<Item = [f32; 3]>
+{
+    let mut r = data;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        match prop {
+            MASS => Scalar::read_array(state, ty, masses, &mut r).unwrap(),
+            POSITION => Vector::read_array(state, ty, positions, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+}
+
+#[test]
+fn same_as_pushing() {
+    let data = stream();
+    let (mut masses, mut positions): (Vec<f64>, Vec<[f32; 3]>) = (vec![], vec![]);
+    read(&data, &mut masses, &mut positions);
+    let (mut pushed_masses, mut pushed_positions) = (Pushed::default(), Pushed::default());
+    read(&data, &mut pushed_masses, &mut pushed_positions);
+    assert_eq!(masses, pushed_masses.items);
+    assert_eq!(positions, pushed_positions.items);
+    assert_eq!(pushed_masses.pushes, 21);
+    assert_eq!(masses.len(), 21);
+    assert_eq!(&masses[..7], &[0.5, 1.5, 0.0, 0.0, 0.5, 1.5, 2.5]);
+    assert_eq!(masses[20], 0.5);
+    assert_eq!(positions.len(), 10);
+    assert_eq!(positions[3], [3.0, 4.0, 5.0]);
+    assert_eq!(positions[5], [0.0; 3]);
+
+    // Items after the blocks are kept.
+    let mut masses = vec![9.0; 30];
+    let mut positions = vec![[9.0; 3]; 12];
+    read(&data, &mut masses, &mut positions);
+    assert_eq!(masses.len(), 30);
+    assert_eq!(&masses[..4], &[0.5, 1.5, 9.0, 9.0]);
+    assert_eq!(masses[29], 9.0);
+    assert_eq!((positions[1], positions[11]), ([9.0; 3], [9.0; 3]));
+}
+
+#[test]
+fn claimed_bytes_are_not_allocated() {
+    // A block that claims far more data than there is.
+    let mut data = vec![];
+    data.extend_from_slice(&Type::F64.scalar().0.to_le_bytes());
+    data.extend_from_slice(&MASS.to_le_bytes());
+    data.extend_from_slice(&(8_u64 << 40).to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    data.extend_from_slice(&[0; 80]);
+
+    let mut r = &data[..];
+    let (state, ty, _) = raw::read_property_header(&mut r).unwrap().unwrap();
+    let mut masses: Vec<f64> = vec![];
+    let err = Scalar::read_array(state, ty, &mut masses, &mut r).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(masses.capacity() <= 1 << 22, "{}", masses.capacity());
+}
+
+/// Returns the time taken by reading the first property.
+fn time_read<F>(data: &[u8], f: F) -> Duration
+    where F: FnOnce(State<Bytes>, u16, &mut &[u8])
+{
+    let mut r = data;
+    let (state, ty, _) = raw::read_property_header(&mut r).unwrap().unwrap();
+    let start = Instant::now();
+    f(state, ty, &mut r);
+    start.elapsed()
+}
+
+/// Compares reading into an empty `Vec` with pushing one item at a time.
+///
+/// Run with `cargo test --release --test array -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn bench_cold_read() {
+    const N: usize = 50_000_000;
+    let mut data = vec![];
+    Scalar::write_array(MASS, &vec![1.5_f32; N], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+
+    let resized = time_read(&data, |state, ty, r| {
+        let mut arr: Vec<f32> = vec![];
+        Scalar::read_array(state, ty, &mut arr, r).unwrap();
+        assert_eq!(arr.len(), N);
+    });
+    let pushed = time_read(&data, |state, ty, r| {
+        let mut arr: Pushed<f32> = Pushed::default();
+        Scalar::read_array(state, ty, &mut arr, r).unwrap();
+        assert_eq!(arr.pushes, N);
+    });
+    println!("{} items: resized {:?}, pushed {:?}", N, resized, pushed);
+}
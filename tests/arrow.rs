@@ -0,0 +1,196 @@
+#![cfg(feature = "arrow")]
+
+extern crate arrow_array;
+extern crate arrow_ipc;
+extern crate arrow_schema;
+extern crate binpool;
+
+mod common;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type, Int16Type, UInt32Type, UInt64Type};
+use arrow_array::{Array, RecordBatch};
+use arrow_ipc::reader::FileReader;
+use arrow_schema::DataType;
+use binpool::arrow::*;
+use binpool::*;
+use common::Rng;
+use std::io::{self, Cursor};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const ID: u16 = 3;
+const ROTATION: u16 = 4;
+const CHARGE: u16 = 9;
+const NOTE: u16 = 10;
+
+/// A row of the table: frame, instance, time, position, mass, id, rotation and charge.
+#[derive(Clone, Debug, PartialEq)]
+struct Row {
+    frame: u64,
+    instance: u64,
+    time: Option<f64>,
+    position: Option<Vec<f32>>,
+    mass: Option<f64>,
+    id: Option<u32>,
+    rotation: Option<Vec<f32>>,
+    charge: Option<i16>,
+}
+
+fn merge<T: Copy>(state: &mut Vec<T>, items: &[T]) {
+    if state.len() < items.len() {state.resize(items.len(), items[0])}
+    state[..items.len()].copy_from_slice(items);
+}
+
+/// Writes frames with arrays of different lengths, and returns the rows expected.
+fn recording(frames: usize) -> (Vec<u8>, Vec<Row>) {
+    let mut names = PropertyAllocator::new();
+    names.assign("position", POSITION).unwrap();
+    names.assign("mass", MASS).unwrap();
+    names.assign("id", ID).unwrap();
+    names.assign("rotation", ROTATION).unwrap();
+    let mut w = FrameWriter::new(vec![], TIME);
+    names.write(&mut w).unwrap();
+    // Charges are written before the first frame, and then as changes.
+    let mut charges = vec![-1_i16, 2];
+    Scalar::write_array(CHARGE, &charges, &mut w).unwrap();
+    let mut rng = Rng::new(165);
+    let mut rows = vec![];
+    let mut state = (vec![], vec![], vec![]);
+    for i in 0..frames {
+        let t = i as f64 * 0.5;
+        w.begin_frame(t).unwrap();
+        let n = rng.below(6) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32, 1.0, 2.0]).collect();
+        let masses: Vec<f64> = (0..3).map(|_| rng.unit()).collect();
+        let ids: Vec<u32> = (0..n).map(|j| (i * 10 + j) as u32).collect();
+        let rot: Vec<[[f32; 2]; 2]> = (0..n)
+            .map(|_| [[rng.unit() as f32, 0.0], [0.5, 1.0]])
+            .collect();
+        if n > 0 {
+            Vector::write_array(POSITION, &pos, &mut w).unwrap();
+            Scalar::write_array(ID, &ids, &mut w).unwrap();
+            Matrix::write_array(ROTATION, &rot, &mut w).unwrap();
+        }
+        Scalar::write_array(MASS, &masses, &mut w).unwrap();
+        if i % 3 == 1 {
+            let mut data = vec![];
+            (i as i16).write_element(&mut data).unwrap();
+            write_block(Type::I16.scalar().0, CHARGE, 1, &data, &mut w).unwrap();
+            charges[1] = i as i16;
+        }
+        write_block(Type::offset_custom_format(), NOTE, 0, b"skipped", &mut w).unwrap();
+        // Properties are merged, keeping the items past the end of a shorter array.
+        merge(&mut state.0, &pos);
+        merge(&mut state.1, &ids);
+        merge(&mut state.2, &rot);
+        let (pos, ids, rot) = &state;
+        let len = pos.len().max(3);
+        for j in 0..len {
+            rows.push(Row {
+                frame: i as u64,
+                instance: j as u64,
+                time: Some(t),
+                position: pos.get(j).map(|p| p.to_vec()),
+                mass: masses.get(j).cloned(),
+                id: ids.get(j).cloned(),
+                rotation: rot.get(j).map(|r| vec![r[0][0], r[0][1], r[1][0], r[1][1]]),
+                charge: charges.get(j).cloned(),
+            });
+        }
+    }
+    (w.finish().unwrap(), rows)
+}
+
+fn list(array: &dyn Array, i: usize) -> Option<Vec<f32>> {
+    if array.is_null(i) {return None}
+    Some(array.as_fixed_size_list().value(i).as_primitive::<Float32Type>().values().to_vec())
+}
+
+fn nullable<T: Copy>(array: &dyn Array, i: usize, values: &[T]) -> Option<T> {
+    if array.is_null(i) {None} else {Some(values[i])}
+}
+
+/// Reads the rows of a record batch.
+fn rows(batch: &RecordBatch) -> Vec<Row> {
+    let c = |name: &str| batch.column_by_name(name).unwrap().clone();
+    let (frame, instance, time) = (c("frame"), c("instance"), c("time"));
+    let (position, mass, id) = (c("position"), c("mass"), c("id"));
+    let (rotation, charge) = (c("rotation"), c("property_9"));
+    (0..batch.num_rows()).map(|i| Row {
+        frame: frame.as_primitive::<UInt64Type>().value(i),
+        instance: instance.as_primitive::<UInt64Type>().value(i),
+        time: nullable(&time, i, time.as_primitive::<Float64Type>().values()),
+        position: list(&position, i),
+        mass: nullable(&mass, i, mass.as_primitive::<Float64Type>().values()),
+        id: nullable(&id, i, id.as_primitive::<UInt32Type>().values()),
+        rotation: list(&rotation, i),
+        charge: nullable(&charge, i, charge.as_primitive::<Int16Type>().values()),
+    }).collect()
+}
+
+#[test]
+fn read_back_ipc() {
+    let (data, expected) = recording(20);
+    for &batch_size in &[1, 7, 64, 10000] {
+        let opts = ArrowOptions::default().batch_size(batch_size);
+        let file = write_arrow_ipc(&mut Cursor::new(&data), vec![], TIME, &opts).unwrap();
+        let reader = FileReader::try_new(Cursor::new(file), None).unwrap();
+        let schema = reader.schema();
+        let mut res = vec![];
+        let mut batches = 0;
+        for batch in reader {
+            let batch = batch.unwrap();
+            assert!(batch.num_rows() <= batch_size);
+            res.extend(rows(&batch));
+            batches += 1;
+        }
+        assert_eq!(res, expected);
+        assert_eq!(batches, expected.len().div_ceil(batch_size));
+
+        let names: Vec<&str> = schema.fields().iter().map(|f| &f.name()[..]).collect();
+        assert_eq!(names, vec!["frame", "instance", "time", "position", "mass", "id",
+                               "rotation", "property_9"]);
+        // The custom format is not exported, and the property ids are kept.
+        let ids: Vec<Option<&String>> = schema.fields().iter()
+            .map(|f| f.metadata().get(PROPERTY_ID_KEY))
+            .collect();
+        let ids: Vec<Option<&str>> = ids.iter().map(|id| id.map(|id| &id[..])).collect();
+        assert_eq!(ids, vec![None, None, Some("0"), Some("1"), Some("2"), Some("3"),
+                             Some("4"), Some("9")]);
+        let position = schema.field_with_name("position").unwrap();
+        assert!(matches!(position.data_type(), DataType::FixedSizeList(_, 3)));
+        let rotation = schema.field_with_name("rotation").unwrap();
+        assert!(matches!(rotation.data_type(), DataType::FixedSizeList(_, 4)));
+        assert_eq!(schema.field_with_name("id").unwrap().data_type(), &DataType::UInt32);
+    }
+}
+
+#[test]
+fn batch_by_batch() {
+    let (data, expected) = recording(50);
+    let mut cursor = Cursor::new(&data);
+    let opts = ArrowOptions::default().batch_size(16);
+    let batches = export_arrow(&mut cursor, TIME, &opts).unwrap();
+    let schema = batches.schema();
+    let mut res = vec![];
+    for batch in batches {
+        let batch = batch.unwrap();
+        assert_eq!(batch.schema(), schema);
+        // Every batch is full, except the last one.
+        assert!(batch.num_rows() == 16 || res.len() + batch.num_rows() == expected.len());
+        res.extend(rows(&batch));
+    }
+    assert_eq!(res, expected);
+
+    let opts = ArrowOptions::default().batch_size(0);
+    let err = export_arrow(&mut Cursor::new(&data), TIME, &opts).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    // Errors of the stream are returned, also after the first read.
+    let mut broken = data[..data.len() - 30].to_vec();
+    broken.extend_from_slice(&[0; 2]);
+    let mut cursor = Cursor::new(&broken);
+    assert!(export_arrow(&mut cursor, TIME, &ArrowOptions::default()).is_err());
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+const POSITION: u16 = 1;
+
+/// A sink that fails once it has received `limit` bytes.
+#[derive(Debug)]
+struct Failing {
+    data: Vec<u8>,
+    limit: usize,
+    fail_flush: bool,
+}
+
+impl Write for Failing {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() + buf.len() > self.limit {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.fail_flush {return Err(io::Error::other("flush failed"))}
+        Ok(())
+    }
+}
+
+/// A sink that waits for a signal before every write.
+struct Gated {
+    data: Vec<u8>,
+    gate: Receiver<()>,
+}
+
+impl Write for Gated {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = self.gate.recv();
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {Ok(())}
+}
+
+fn positions(i: u32) -> Vec<[f32; 3]> {
+    vec![[i as f32; 3]; 1 + i as usize % 10]
+}
+
+#[test]
+fn finish_waits_for_queued_data() {
+    // A slow sink, such that data is still queued when finishing.
+    struct Slow(Vec<u8>);
+    impl Write for Slow {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_micros(50));
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {Ok(())}
+    }
+
+    let mut w = AsyncFileWriter::new(Slow(vec![]), 4);
+    let mut expected = vec![];
+    for i in 0..200 {
+        let pos = positions(i);
+        Vector::write_array(POSITION, &pos, &mut expected).unwrap();
+        if i % 2 == 0 {
+            w.write_with(move |w| Vector::write_array(POSITION, &pos, w)).unwrap();
+        } else {
+            let mut data = vec![];
+            for p in &pos {p.write_element(&mut data).unwrap()}
+            let ty = Type::F32.vector(3).unwrap().0;
+            w.write_property(ty, POSITION, vec![
+                RawBlock {type_format: ty, property_id: POSITION, offset: 0, data}
+            ]).unwrap();
+        }
+    }
+    State::new().end_type_formats(&mut expected).unwrap();
+    let Slow(data) = w.finish().unwrap();
+    assert!(data == expected);
+}
+
+#[test]
+fn flush_waits_for_queued_data() {
+    let (open, gate) = mpsc::channel();
+    let mut w = AsyncFileWriter::new(Gated {data: vec![], gate}, 100);
+    for i in 0..10 {
+        let pos = positions(i);
+        w.write_with(move |w| Vector::write_array(POSITION, &pos, w)).unwrap();
+    }
+    // Every write of the sink is allowed in advance.
+    for _ in 0..10_000 {open.send(()).unwrap()}
+    w.flush().unwrap();
+    drop(open);
+    let data = w.finish().unwrap().data;
+    let mut expected = vec![];
+    for i in 0..10 {
+        Vector::write_array(POSITION, &positions(i), &mut expected).unwrap();
+    }
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert!(data == expected);
+}
+
+#[test]
+fn backpressure() {
+    let (open, gate) = mpsc::channel();
+    let mut w = AsyncFileWriter::new(Gated {data: vec![], gate}, 2);
+    // The thread blocks in the first write, and the queue holds two more.
+    let mut busy = None;
+    for i in 0..100 {
+        let pos = positions(i);
+        if let Err(err) = w.try_write_with(move |w| Vector::write_array(POSITION, &pos, w)) {
+            assert_eq!(Error::from_io(&err).kind(), ErrorKind::Busy);
+            busy = Some(i);
+            break;
+        }
+    }
+    let busy = busy.expect("queue is never full");
+    assert!((2..=3).contains(&busy), "{}", busy);
+    drop(open);
+    let data = w.finish().unwrap().data;
+    let mut r = &data[..];
+    let mut n = 0;
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        n += 1;
+    }
+    assert_eq!(n, busy);
+}
+
+#[test]
+fn failing_sink() {
+    let sink = Failing {data: vec![], limit: 1000, fail_flush: false};
+    let mut w = AsyncFileWriter::new(sink, 4);
+    let mut err = None;
+    for i in 0..1000 {
+        let pos = positions(i);
+        if let Err(e) = w.write_with(move |w| Vector::write_array(POSITION, &pos, w)) {
+            err = Some(e);
+            break;
+        }
+    }
+    // The error of the thread is returned to the producer once, then writes are refused.
+    let err = err.expect("error is not reported");
+    assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    assert_eq!(err.to_string(), "disk full");
+    let err = w.write_with(|_| Ok(())).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(w.flush().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    assert_eq!(w.finish().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn deferred_errors() {
+    // An error is returned by `flush` when nothing is written after it.
+    let sink = Failing {data: vec![], limit: 10, fail_flush: false};
+    let mut w = AsyncFileWriter::new(sink, 4);
+    w.write_with(|w| Vector::write_array(POSITION, &positions(5), w)).unwrap();
+    assert_eq!(w.flush().unwrap_err().kind(), io::ErrorKind::WriteZero);
+
+    // An error is returned by `finish` after the thread is joined.
+    let sink = Failing {data: vec![], limit: 10, fail_flush: false};
+    let mut w = AsyncFileWriter::new(sink, 4);
+    w.write_with(|w| Vector::write_array(POSITION, &positions(5), w)).unwrap();
+    assert_eq!(w.finish().unwrap_err().kind(), io::ErrorKind::WriteZero);
+
+    let sink = Failing {data: vec![], limit: 10_000, fail_flush: true};
+    let w = AsyncFileWriter::new(sink, 4);
+    assert_eq!(w.finish().unwrap_err().to_string(), "flush failed");
+
+    // Errors of the functions sent to the thread are returned too.
+    let sink = Failing {data: vec![], limit: 10_000, fail_flush: false};
+    let mut w = AsyncFileWriter::new(sink, 4);
+    w.write_with(|_| Err(io::ErrorKind::InvalidData.into())).unwrap();
+    assert_eq!(w.flush().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::{self, Read};
+
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const EMPTY: u16 = 3;
+
+/// Writes properties of one, none and several blocks.
+fn blocks() -> Vec<Vec<RawBlock>> {
+    let f32x3 = Type::F32.vector(3).unwrap().0;
+    let f64 = Type::F64.scalar().0;
+    let block = |type_format, property_id, offset, len: usize| RawBlock {
+        type_format, property_id, offset, data: (0..len).map(|i| i as u8).collect(),
+    };
+    vec![
+        vec![block(f32x3, POSITION, 0, 24)],
+        vec![],
+        vec![block(f64, MASS, 0, 16), block(f64, MASS, 5, 8), block(f64, MASS, 1 << 40, 800)],
+    ]
+}
+
+fn stream<W: io::Write>(w: &mut W) {
+    for blocks in &blocks() {
+        if blocks.is_empty() {
+            RawBlock::write_property(Type::U8.scalar().0, EMPTY, &[], w).unwrap();
+        } else {
+            RawBlock::write_property(blocks[0].type_format, blocks[0].property_id, blocks, w)
+                .unwrap();
+        }
+    }
+    State::new().end_type_formats(w).unwrap();
+}
+
+/// Reads every block with headers, returning the blocks of each property.
+fn read<R: Read>(r: &mut R) -> io::Result<Vec<Vec<RawBlock>>> {
+    let mut properties = vec![];
+    while let Some((mut header, mut state)) = State::read_full_header(r)? {
+        let mut blocks = vec![];
+        while !header.is_end() {
+            // The reader is at the data of the block.
+            let mut data = vec![0; header.bytes as usize];
+            r.read_exact(&mut data)?;
+            blocks.push(RawBlock {
+                type_format: header.type_format,
+                property_id: header.property_id,
+                offset: header.offset,
+                data,
+            });
+            let next = state.end_data().read_block_header(header.type_format,
+                header.property_id, r)?;
+            header = next.0;
+            state = next.1;
+        }
+        // The end of bytes has no offset.
+        assert_eq!(header.offset, 0);
+        properties.push(blocks);
+    }
+    Ok(properties)
+}
+
+#[test]
+fn headers() {
+    let mut data = vec![];
+    stream(&mut data);
+    let mut r = &data[..];
+    assert_eq!(read(&mut r).unwrap(), blocks());
+    assert!(r.is_empty());
+
+    let mut r = &data[..];
+    let (header, _) = State::read_full_header(&mut r).unwrap().unwrap();
+    assert_eq!(header, BlockHeader {
+        type_format: Type::F32.vector(3).unwrap().0,
+        property_id: POSITION,
+        bytes: 24,
+        offset: 0,
+    });
+    assert!(!header.is_end());
+    assert_eq!(r.len(), data.len() - 4 - 16);
+
+    // A property without blocks.
+    let mut r = &data[4 + 16 + 24 + 8..];
+    let (header, _) = State::read_full_header(&mut r).unwrap().unwrap();
+    assert_eq!((header.property_id, header.bytes, header.is_end()), (EMPTY, 0, true));
+    let (header, _) = State::read_full_header(&mut r).unwrap().unwrap();
+    assert_eq!((header.property_id, header.bytes), (MASS, 16));
+
+    // End of stream, and no data.
+    assert!(State::read_full_header(&mut &[0, 0][..]).unwrap().is_none());
+    assert!(State::read_full_header(&mut &[][..]).unwrap().is_none());
+}
+
+#[test]
+fn big_endian() {
+    let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big);
+    stream(&mut w);
+    let data = w.finish().unwrap();
+    // Headers are read in the byte order of the stream, after its file header.
+    let mut properties = read(&mut PoolReader::new(&data[..])).unwrap();
+    assert_eq!(properties.remove(0)[0].type_format, HEADER_FORMAT);
+    assert_eq!(properties, blocks());
+}
+
+#[test]
+fn torn_headers() {
+    let mut data = vec![];
+    stream(&mut data);
+    // Cuts inside the type format, property id, bytes and offset.
+    for &cut in &[1, 3, 4, 11, 12, 19] {
+        let err = State::read_full_header(&mut &data[..cut]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "{}", cut);
+    }
+    let err = read(&mut &data[..data.len() - 10]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,152 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::sync::Arc;
+use std::thread;
+
+const POSITION: u16 = 1;
+const VELOCITY: u16 = 2;
+const MASS: u16 = 3;
+const NOTE: u16 = 4;
+
+const FRAMES: u64 = 100;
+
+/// Encodes items as a block starting at an instance.
+fn block(prop: u16, offset: u64, items: &[f64]) -> RawBlock {
+    let mut data = vec![];
+    for x in items {x.write_element(&mut data).unwrap()}
+    RawBlock {type_format: Type::F64.scalar().0, property_id: prop, offset, data}
+}
+
+/// Writes one block of positions and velocities and two blocks of masses and notes per frame.
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    let ty = Type::F64.scalar().0;
+    for i in 0..FRAMES {
+        let x = i as f64;
+        RawBlock::write_property(ty, POSITION, &[block(POSITION, 0, &[x; 3])], &mut w).unwrap();
+        RawBlock::write_property(ty, VELOCITY, &[block(VELOCITY, 0, &[-x; 3])], &mut w).unwrap();
+        for &prop in &[MASS, NOTE] {
+            let blocks = [block(prop, 0, &[x]), block(prop, i, &[x, x])];
+            RawBlock::write_property(ty, prop, &blocks, &mut w).unwrap();
+        }
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// Counts the blocks received by property.
+fn counts(blocks: &[BroadcastBlock]) -> [u64; 4] {
+    let mut res = [0; 4];
+    for b in blocks {res[b.property_id as usize - 1] += 1}
+    res
+}
+
+#[test]
+fn disjoint_and_overlapping() {
+    let data = stream();
+    let mut broadcast = Broadcast::new();
+    let subscribers = vec![
+        broadcast.subscribe(&[POSITION], 4, Backpressure::Block),
+        broadcast.subscribe(&[VELOCITY, MASS], 4, Backpressure::Block),
+        broadcast.subscribe(&[POSITION, MASS], 1, Backpressure::Block),
+    ];
+    assert!(broadcast.is_subscribed(MASS));
+    assert!(!broadcast.is_subscribed(NOTE));
+    let threads: Vec<_> = subscribers.into_iter()
+        .map(|mut s| thread::spawn(move || {
+            let blocks: Vec<BroadcastBlock> = s.by_ref().collect();
+            (blocks, s.dropped())
+        }))
+        .collect();
+    broadcast.run(&mut &data[..]).unwrap();
+    let lagged = broadcast.lagged();
+    drop(broadcast);
+    let received: Vec<(Vec<BroadcastBlock>, u64)> =
+        threads.into_iter().map(|t| t.join().unwrap()).collect();
+
+    // No block is dropped when the reader waits, and notes are not delivered.
+    assert_eq!(counts(&received[0].0), [FRAMES, 0, 0, 0]);
+    assert_eq!(counts(&received[1].0), [0, FRAMES, 2 * FRAMES, 0]);
+    assert_eq!(counts(&received[2].0), [FRAMES, 0, 2 * FRAMES, 0]);
+    assert!(received.iter().all(|r| r.1 == 0));
+    assert_eq!(lagged.len(), 3);
+
+    // Blocks are received in order, and subscribers of the same property share the data.
+    let positions: Vec<&BroadcastBlock> = received[2].0.iter()
+        .filter(|b| b.property_id == POSITION)
+        .collect();
+    for (i, (a, b)) in received[0].0.iter().zip(positions).enumerate() {
+        assert!(Arc::ptr_eq(&a.data, &b.data));
+        let items = block(POSITION, 0, &[i as f64; 3]);
+        assert_eq!(&a.data[..], &items.data[..]);
+        assert_eq!((a.type_format, a.offset), (items.type_format, 0));
+    }
+    let masses: Vec<u64> = received[1].0.iter()
+        .filter(|b| b.property_id == MASS)
+        .map(|b| b.offset)
+        .collect();
+    assert_eq!(&masses[..6], &[0, 0, 0, 1, 0, 2]);
+}
+
+#[test]
+fn drop_policies() {
+    let data = stream();
+    let mut broadcast = Broadcast::new();
+    let oldest = broadcast.subscribe(&[POSITION], 3, Backpressure::DropOldest);
+    let newest = broadcast.subscribe(&[POSITION], 3, Backpressure::DropNewest);
+    let all = broadcast.subscribe(&[], 0, Backpressure::DropNewest);
+    // Nobody receives the blocks until the stream has been read.
+    broadcast.run(&mut &data[..]).unwrap();
+    assert_eq!(broadcast.lagged(), vec![FRAMES - 3, FRAMES - 3, 6 * FRAMES - 1]);
+    drop(broadcast);
+
+    assert_eq!((oldest.lagged(), oldest.dropped()), (FRAMES - 3, FRAMES - 3));
+    let offsets = |s: Subscriber| -> Vec<f64> {
+        s.map(|b| b.data[..8].iter().rev().fold(0_u64, |acc, &x| acc << 8 | x as u64))
+            .map(f64::from_bits)
+            .collect()
+    };
+    assert_eq!(offsets(oldest), vec![97.0, 98.0, 99.0]);
+    assert_eq!(newest.dropped(), FRAMES - 3);
+    assert_eq!(offsets(newest), vec![0.0, 1.0, 2.0]);
+    // A capacity of 0 holds one block.
+    assert_eq!(all.try_recv().unwrap().property_id, POSITION);
+    assert!(all.try_recv().is_none());
+    assert!(all.recv().is_none());
+}
+
+#[test]
+fn dropped_subscriber_does_not_block() {
+    let data = stream();
+    let mut broadcast = Broadcast::new();
+    let slow = broadcast.subscribe(&[MASS], 1, Backpressure::Block);
+    let fast = broadcast.subscribe(&[VELOCITY], 1, Backpressure::Block);
+    let reader = thread::spawn(move || {
+        broadcast.run(&mut &data[..]).unwrap();
+        broadcast.is_subscribed(MASS)
+    });
+    // The slow subscriber goes away after one block, and the reader continues.
+    assert_eq!(slow.recv().unwrap().property_id, MASS);
+    drop(slow);
+    assert_eq!(fast.count() as u64, FRAMES);
+    assert!(!reader.join().unwrap());
+}
+
+#[test]
+fn truncated_stream() {
+    let data = stream();
+    let mut broadcast = Broadcast::new();
+    let s = broadcast.subscribe(&[], 1000, Backpressure::Block);
+    let err = broadcast.run(&mut &data[..60]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    drop(broadcast);
+    assert_eq!(s.count(), 1);
+
+    // Properties without subscribers are skipped, but still read to the end.
+    let broadcast = Broadcast::new();
+    assert!(broadcast.run(&mut &data[..]).is_ok());
+    assert!(broadcast.run(&mut &data[..data.len() - 3]).is_err());
+}
@@ -0,0 +1,170 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::collections::HashSet;
+
+const TIME: u16 = 0;
+const FIELD: u16 = 1;
+const POSITION: u16 = 2;
+
+const FRAMES: usize = 60;
+const CELLS: usize = 20_000;
+
+fn stream() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64).unwrap();
+        let field: Vec<f64> = (0..CELLS).map(|j| (i * j) as f64).collect();
+        Scalar::write_array(FIELD, &field, &mut w).unwrap();
+        let positions: Vec<[f32; 3]> = (0..CELLS / 4).map(|j| [i as f32, j as f32, 0.0]).collect();
+        Vector::write_array(POSITION, &positions, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+type Arrays = (Vec<f64>, Vec<[f32; 3]>);
+
+fn arrays(frame: &Frame) -> Arrays {
+    let (mut field, mut positions) = (vec![], vec![]);
+    frame.read_array(FIELD, &mut field).unwrap();
+    frame.read_array(POSITION, &mut positions).unwrap();
+    (field, positions)
+}
+
+/// Reads every frame without a pool.
+fn unpooled(data: &[u8]) -> Vec<Arrays> {
+    let mut r = FrameReader::with_time(data, TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {res.push(arrays(&frame))}
+    res
+}
+
+#[test]
+fn recycled_across_frames() {
+    let data = stream();
+    let expected = unpooled(&data);
+    let pool = BufferPool::new(8 << 20);
+    let mut r = FrameReader::with_time(&data[..], TIME).buffer_pool(pool.clone());
+    let mut seen = HashSet::new();
+    let mut n = 0;
+    while let Some(frame) = r.next_pooled_frame().unwrap() {
+        assert_eq!(arrays(&frame), expected[n]);
+        for block in frame.blocks.iter().filter(|b| b.data.len() > 1000) {
+            let ptr = block.data.as_ptr() as usize;
+            // After the first frame, the data is read into buffers of earlier frames.
+            if n > 0 {assert!(seen.contains(&ptr), "frame {}", n)}
+            seen.insert(ptr);
+        }
+        n += 1;
+    }
+    assert_eq!(n, FRAMES);
+    // Only the buffers of the first frames are allocated.
+    assert!(seen.len() <= 4, "{}", seen.len());
+
+    let stats = pool.stats();
+    assert!(stats.hit_rate() > 0.9, "{:?}", stats);
+    assert_eq!(stats.discarded, 0);
+    assert!(stats.peak_bytes <= pool.max_bytes() as u64);
+    assert!(stats.peak_bytes >= (CELLS * 8) as u64);
+    assert_eq!(stats.hits + stats.misses, stats.returned);
+
+    // Frames that are kept do not return their buffers.
+    let mut r = FrameReader::with_time(&data[..], TIME).buffer_pool(pool.clone());
+    pool.clear();
+    assert_eq!(pool.stats(), BufferStats::default());
+    let kept: Vec<Frame> = (0..3).map(|_| r.next_pooled_frame().unwrap().unwrap().into_inner())
+        .collect();
+    assert!(pool.stats().pooled_bytes < (CELLS * 8) as u64);
+    for (i, frame) in kept.iter().enumerate() {assert_eq!(arrays(frame), expected[i])}
+}
+
+#[test]
+fn bounded_capacity() {
+    let data = stream();
+    let expected = unpooled(&data);
+    // The pool is too small for the field.
+    let pool = BufferPool::new(CELLS * 8 - 1);
+    let mut r = FrameReader::with_time(&data[..], TIME).buffer_pool(pool.clone());
+    let mut n = 0;
+    while let Some(frame) = r.next_pooled_frame().unwrap() {
+        assert_eq!(arrays(&frame), expected[n]);
+        n += 1;
+    }
+    let stats = pool.stats();
+    assert!(stats.discarded >= FRAMES as u64);
+    assert!(stats.peak_bytes < (CELLS * 8) as u64);
+    assert!(stats.pooled_bytes <= stats.peak_bytes);
+
+    // Frames without a pool are the same as frames from `next_frame`.
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let frame = r.next_pooled_frame().unwrap().unwrap();
+    assert_eq!(arrays(&frame), expected[0]);
+}
+
+#[test]
+fn decoded_storage() {
+    let pool = BufferPool::new(1 << 20);
+    let items: Vec<f64> = (0..1000).map(|i| i as f64).collect();
+    let mut data = vec![];
+    for x in &items {x.write_element(&mut data).unwrap()}
+    let block = RawBlock {type_format: Type::F64.scalar().0, property_id: FIELD, offset: 0, data};
+    let ptr = {
+        let decoded = pool.decode::<f64>(&block).unwrap();
+        assert_eq!(&decoded[..], &items[..]);
+        decoded.as_ptr()
+    };
+    // The storage is reused after being returned.
+    let decoded = pool.decode::<f64>(&block).unwrap();
+    assert_eq!(decoded.as_ptr(), ptr);
+    assert_eq!(&decoded[..], &items[..]);
+    assert!(pool.decode::<f32>(&block).is_err());
+    let kept = decoded.into_inner();
+    assert_eq!(pool.checkout_vec::<f64>(10).capacity(), 0);
+    drop(kept);
+
+    // Storage of another type is pooled separately.
+    let mut bytes = pool.checkout(100);
+    bytes.extend_from_slice(&[1; 100]);
+    drop(bytes);
+    let bytes = pool.checkout(50);
+    assert!(bytes.is_empty() && bytes.capacity() >= 100);
+}
+
+#[test]
+fn shared_between_threads() {
+    let pool = BufferPool::new(1 << 20);
+    let threads: Vec<_> = (0..4).map(|i| {
+        let pool = pool.clone();
+        std::thread::spawn(move || {
+            for j in 0..100 {
+                let mut buffer = pool.take(64);
+                buffer.resize(64 + i * j, 0);
+                pool.give(buffer);
+            }
+        })
+    }).collect();
+    for t in threads {t.join().unwrap()}
+    let stats = pool.stats();
+    assert_eq!(stats.hits + stats.misses, 400);
+    assert_eq!(stats.returned + stats.discarded, 400);
+    assert!(stats.misses <= 4);
+}
+
+#[test]
+fn skipped_properties() {
+    let data = stream();
+    let pool = BufferPool::new(8 << 20);
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip).buffer_pool(pool.clone());
+    r.register(TIME);
+    let mut times = 0;
+    while let Some((state, ty, _)) = r.next_property().unwrap() {
+        let mut t = 0.0_f64;
+        t.read_property(state, ty, &mut r).unwrap();
+        assert_eq!(t, times as f64);
+        times += 1;
+    }
+    assert_eq!(times, FRAMES);
+    assert!(pool.stats().hit_rate() > 0.9, "{:?}", pool.stats());
+}
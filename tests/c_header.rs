@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::{generate_c_header, Type};
+
+/// Returns the value of a `#define` or enum constant in the header.
+fn constant(header: &str, name: &str) -> u32 {
+    header.lines()
+        .filter_map(|line| {
+            let line = line.trim().trim_end_matches(',');
+            let rest = line.strip_prefix("#define ").unwrap_or(line);
+            let mut parts = rest.split([' ', '=']).filter(|s| !s.is_empty());
+            if parts.next() != Some(name) {return None}
+            let val = parts.next()?;
+            match val.strip_prefix("0x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => val.parse().ok(),
+            }
+        })
+        .next()
+        .unwrap_or_else(|| panic!("{} is not in the header", name))
+}
+
+/// Checks the arithmetic of the header helpers, using the emitted constants,
+/// against `Type::info` and `Type::format_for`.
+#[test]
+fn helpers_match_type_info() {
+    let header = &generate_c_header();
+    let size = constant(header, "BINPOOL_SIZE");
+    let types = constant(header, "BINPOOL_TYPES");
+    let offset = constant(header, "BINPOOL_OFFSET_CUSTOM_FORMAT");
+    let names = ["U8", "U16", "U32", "U64", "I8", "I16", "I32", "I64", "F32", "F64"];
+    let ids: Vec<u32> = names.iter()
+        .map(|name| constant(header, &format!("BINPOOL_TYPE_{}", name)))
+        .collect();
+    let ty_of = |id: u32| ids.iter().position(|&x| x == id).map(|i| {
+        [Type::U8, Type::U16, Type::U32, Type::U64, Type::I8,
+         Type::I16, Type::I32, Type::I64, Type::F32, Type::F64][i]
+    });
+
+    for format in 0..=u16::MAX as u32 {
+        let info = if format == 0 || format >= offset {
+            None
+        } else {
+            let f = format - 1;
+            ty_of(f / (size * size)).map(|ty| {
+                (ty, ((f % (size * size)) / size + 1) as u8, (f % size + 1) as u8)
+            })
+        };
+        assert_eq!(info, Type::info(format as u16), "binpool_info({})", format);
+    }
+    for (i, &id) in ids.iter().enumerate() {
+        let ty = ty_of(id).unwrap();
+        assert_eq!(id as u16, ty.type_id(), "BINPOOL_TYPE_{}", names[i]);
+        assert!(id < types);
+        for rows in 0..=255_u32 {
+            for cols in 0..=255_u32 {
+                let format = if rows == 0 || cols == 0 || rows > size || cols > size {
+                    None
+                } else {
+                    Some((1 + id * size * size + (rows - 1) * size + (cols - 1)) as u16)
+                };
+                assert_eq!(format, Type::format_for(ty, rows as u8, cols as u8),
+                    "binpool_format_for({}, {}, {})", names[i], rows, cols);
+            }
+        }
+    }
+}
+
+#[test]
+fn checked_in_header() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/binpool_format.h");
+    let current = std::fs::read_to_string(path).unwrap();
+    assert!(current == generate_c_header(), "run `cargo run --example c_header`");
+}
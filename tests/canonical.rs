@@ -0,0 +1,156 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::Write;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+const MASS: u16 = 3;
+const NAME: u16 = 4;
+
+fn block(type_format: u16, property_id: u16, offset: u64, data: Vec<u8>) -> RawBlock {
+    RawBlock {type_format, property_id, offset, data}
+}
+
+fn f32s(items: &[f32]) -> Vec<u8> {
+    items.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
+
+fn units() -> Units {
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new("m", 1.0));
+    units
+}
+
+/// Writes two frames of the same logical data.
+///
+/// The first writing has properties in order and one block per property.
+/// The second writing has properties in reverse order,
+/// positions split into blocks out of order and overlapping,
+/// and a zero-length block.
+fn write<W: Write>(reordered: bool, w: &mut W) {
+    let f32 = Type::F32.scalar().0;
+    let custom = Type::offset_custom_format();
+    for frame in 0..2 {
+        Time::write(TIME, frame as f64, w).unwrap();
+        if frame == 0 {units().write_units(w).unwrap()}
+        let pos: Vec<f32> = (0..6).map(|i| (i + 10 * frame) as f32).collect();
+        if reordered {
+            RawBlock::write_property(custom, NAME, &[block(custom, NAME, 0, b"abc".to_vec())], w)
+                .unwrap();
+            Scalar::write_array(MASS, &vec![1.5_f32; 3], w).unwrap();
+            RawBlock::write_property(f32, POSITION, &[
+                block(f32, POSITION, 4, f32s(&pos[4..])),
+                block(f32, POSITION, 0, f32s(&[-1.0, -1.0, -1.0])),
+                block(f32, POSITION, 3, vec![]),
+            ], w).unwrap();
+            RawBlock::write_property(f32, POSITION, &[block(f32, POSITION, 0, f32s(&pos[..4]))], w)
+                .unwrap();
+        } else {
+            Scalar::write_array(POSITION, &pos, w).unwrap();
+            Scalar::write_array(MASS, &vec![1.5_f32; 3], w).unwrap();
+            RawBlock::write_property(custom, NAME, &[block(custom, NAME, 0, b"abc".to_vec())], w)
+                .unwrap();
+        }
+    }
+}
+
+fn canonical(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    canonicalize(&mut &data[..], &mut out, Some(TIME)).unwrap();
+    out
+}
+
+fn properties(mut r: &[u8]) -> Vec<(u16, u16)> {
+    let mut res = vec![];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        res.push((ty, prop));
+    }
+    res
+}
+
+#[test]
+fn orderings_are_identical() {
+    let mut a = vec![];
+    write(false, &mut a);
+    State::new().end_type_formats(&mut a).unwrap();
+    let mut b = vec![];
+    write(true, &mut b);
+    State::new().end_type_formats(&mut b).unwrap();
+    assert_ne!(a, b);
+
+    let (a, b) = (canonical(&a), canonical(&b));
+    assert_eq!(a, b);
+    assert_eq!(canonical(&a), a);
+
+    let mut r = FrameReader::with_time(&a[..], TIME);
+    let frame = r.next_frame().unwrap().unwrap();
+    let mut pos: Vec<f32> = vec![];
+    frame.read_array(POSITION, &mut pos).unwrap();
+    assert_eq!(pos, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    // One block per property.
+    assert_eq!(frame.blocks.len(), 5);
+}
+
+#[test]
+fn reserved_properties_keep_their_position() {
+    let mut data = vec![];
+    {
+        let mut w = PoolWriter::new(&mut data);
+        write(true, &mut w);
+        w.finish().unwrap();
+    }
+    let out = canonical(&data);
+    let f32 = Type::F32.scalar().0;
+    let f64 = Type::F64.scalar().0;
+    let custom = Type::offset_custom_format();
+    // The header stays first and the units stay between the time and the sorted properties.
+    assert_eq!(properties(&out), vec![
+        (HEADER_FORMAT, HEADER_PROPERTY),
+        (f64, TIME),
+        (UNITS_FORMAT, UNITS_PROPERTY),
+        (f32, POSITION),
+        (f32, MASS),
+        (custom, NAME),
+        (f64, TIME),
+        (f32, POSITION),
+        (f32, MASS),
+        (custom, NAME),
+    ]);
+    let mut r = PoolReader::new(&out[..]).unknown(Unknown::Skip);
+    while r.next_property().unwrap().is_some() {}
+    assert_eq!(r.units(), &units());
+}
+
+#[test]
+fn big_endian() {
+    let mut streams = vec![];
+    for &reordered in &[false, true] {
+        let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big);
+        write(reordered, &mut w);
+        streams.push(w.finish().unwrap());
+    }
+    let (a, b) = (canonical(&streams[0]), canonical(&streams[1]));
+    assert_eq!(a, b);
+
+    // The output is big endian and has the data of the little endian output.
+    let mut le = PoolWriter::new(vec![]);
+    write(true, &mut le);
+    let le = canonical(&le.finish().unwrap());
+    let mut r = PoolReader::new(&a[..]);
+    r.register(TIME);
+    assert_eq!(r.next_property().unwrap().map(|p| p.2), Some(TIME));
+    assert_eq!(r.header().map(|h| h.endian), Some(Endian::Big));
+    let mut converted = vec![];
+    {
+        let mut r = PoolReader::new(&a[..]);
+        std::io::copy(&mut r, &mut converted).unwrap();
+    }
+    let header = Header::default().encode().len() + 28;
+    assert_eq!(&converted[header..], &le[header..]);
+    assert_ne!(&a[header..], &le[header..]);
+}
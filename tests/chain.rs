@@ -0,0 +1,157 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::{Cursor, Read};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes frames of a known stream.
+fn stream(endian: Endian, frames: std::ops::Range<u64>) -> Vec<u8> {
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]).with_endianness(endian), TIME);
+    let mut rng = Rng::new(156);
+    for i in 0..frames.end {
+        let n = 1 + rng.below(10) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+        if !frames.contains(&i) {continue}
+        w.begin_frame(i as f64 * 0.5).unwrap();
+        Vector::write_array(POSITION, &pos, &mut w).unwrap();
+        if i % 4 == 0 {
+            Scalar::write_array(MASS, &vec![i as f64; n], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap().get_ref().clone()
+}
+
+/// Splits the stream at frames 8 and 20.
+fn files(endian: Endian) -> Vec<Vec<u8>> {
+    vec![stream(endian, 0..8), stream(endian, 8..20), stream(endian, 20..30)]
+}
+
+type Frames = Vec<(u64, Option<f64>, Vec<RawBlock>)>;
+
+fn frames<R: Read>(r: &mut FrameReader<R>) -> Frames {
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        res.push((frame.number, frame.time, frame.blocks));
+    }
+    res
+}
+
+fn sources(files: &[Vec<u8>]) -> ChainedReader<Cursor<Vec<u8>>> {
+    ChainedReader::new(files.iter().cloned().map(Cursor::new).collect())
+}
+
+#[test]
+fn same_as_original() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let original = stream(endian, 0..30);
+        let expected = frames(&mut FrameReader::with_time(PoolReader::new(&original[..]), TIME));
+        assert_eq!(expected.len(), 30);
+        let chain = sources(&files(endian));
+        let mut r = FrameReader::with_time(PoolReader::new(chain), TIME);
+        assert!(frames(&mut r) == expected, "{:?}", endian);
+    }
+
+    // Little endian streams read the same without conversion.
+    let original = stream(Endian::Little, 0..30);
+    let expected = frames(&mut FrameReader::with_time(&original[..], TIME));
+    let mut r = FrameReader::with_time(sources(&files(Endian::Little)), TIME);
+    assert!(frames(&mut r) == expected);
+
+    // The bytes are the same, except the headers and ends of the files in the middle.
+    let mut data = vec![];
+    sources(&files(Endian::Big)).read_to_end(&mut data).unwrap();
+    assert_eq!(data, stream(Endian::Big, 0..30));
+}
+
+#[test]
+fn open_files() {
+    let dir = std::env::temp_dir().join(format!("binpool-chain-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<_> = (1..4).map(|i| dir.join(format!("run.{:04}.pool", i))).collect();
+    for (path, data) in paths.iter().zip(files(Endian::Little)) {
+        std::fs::write(path, data).unwrap();
+    }
+    let chain = ChainedReader::open(&paths).unwrap();
+    assert_eq!(chain.file_count(), 3);
+    let mut r = FrameReader::with_time(chain, TIME);
+    let original = stream(Endian::Little, 0..30);
+    assert!(frames(&mut r) == frames(&mut FrameReader::with_time(&original[..], TIME)));
+
+    // A file that is missing is reported with its index.
+    let missing = [paths[0].clone(), dir.join("missing.pool")];
+    let err = ChainedReader::open(&missing).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(Error::from_io(&err).file(), Some(1));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn errors_in_files() {
+    // The second file is truncated in the middle of a property.
+    let mut files = files(Endian::Little);
+    let len = files[1].len();
+    files[1].truncate(len - 30);
+    let mut chain = sources(&files);
+    let err = std::io::copy(&mut chain, &mut std::io::sink()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    let err = Error::from_io(&err);
+    assert_eq!((err.file(), err.at_byte()), (Some(1), Some(len as u64 - 30)));
+    assert_eq!(chain.current_file(), 1);
+
+    // Errors from decoding are annotated by the chain.
+    let mut r = FrameReader::with_time(sources(&self::files(Endian::Little)), TIME);
+    let frame = r.next_frame().unwrap().unwrap();
+    let mut arr: Vec<f64> = vec![];
+    let err = frame.read_array(POSITION, &mut arr).unwrap_err();
+    let chain = r.get_ref();
+    let err = Error::from_io(&chain.annotate(err));
+    assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+    assert_eq!((err.property(), err.file()), (Some(POSITION), Some(0)));
+    assert_eq!(err.at_byte(), Some(chain.position()));
+    // A file that is already known is kept.
+    let err = Error::new(ErrorKind::InvalidData).with_file(2).into_io(std::io::ErrorKind::Other);
+    assert_eq!(Error::from_io(&chain.annotate(err)).file(), Some(2));
+
+    // The byte order of every file must be the same.
+    let files = vec![stream(Endian::Little, 0..8), stream(Endian::Big, 8..20)];
+    let err = std::io::copy(&mut sources(&files), &mut std::io::sink()).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!((err.property(), err.file()), (Some(HEADER_PROPERTY), Some(1)));
+}
+
+#[test]
+fn chained_index() {
+    let files = files(Endian::Little);
+    let original = stream(Endian::Little, 0..30);
+    let expected = frames(&mut FrameReader::with_time(&original[..], TIME));
+    let mut chain = sources(&files);
+    let index = ChainedIndex::build(&mut chain, TIME, Some(TIME)).unwrap();
+    assert_eq!(index.frame_count(), 30);
+    assert_eq!(index.indices().len(), 3);
+    assert_eq!(index.locate(0).unwrap().0, 0);
+    assert_eq!(index.locate(7).unwrap().0, 0);
+    assert_eq!(index.locate(8).unwrap().0, 1);
+    let (file, entry) = index.locate(25).unwrap();
+    assert_eq!((file, entry.time), (2, Some(12.5)));
+    let err = index.locate(30).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.range()), (ErrorKind::FrameOutOfRange, Some((0, 30))));
+
+    // Reading from any frame continues across files with global frame numbers.
+    let mut r = FrameReader::with_time(chain, TIME);
+    for &n in &[0, 5, 8, 19, 20, 29] {
+        let cursor = index.seek_frame(n, r.get_mut()).unwrap();
+        assert_eq!(cursor.frame, n);
+        r.resume(&cursor).unwrap();
+        assert!(frames(&mut r) == expected[n as usize..], "{}", n);
+    }
+}
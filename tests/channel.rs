@@ -0,0 +1,205 @@
+#![cfg(feature = "test-util")]
+
+extern crate binpool;
+#[macro_use]
+extern crate proptest;
+
+use binpool::test_util::{stream, Model, StreamConfig};
+use binpool::*;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+/// Creates a channel transport, over crossbeam when enabled and asked for.
+fn transport(capacity: usize, crossbeam: bool) -> (ChannelSink, ChannelSource) {
+    #[cfg(feature = "crossbeam")]
+    {
+        if crossbeam {return crossbeam_transport(capacity)}
+    }
+    let _ = crossbeam;
+    channel(capacity)
+}
+
+/// Decodes the items of every property from raw blocks.
+fn decode<R: Read>(mut r: R) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    let mut res = vec![];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        let (scalar, rows, cols) = Type::info(ty).unwrap();
+        let size = (scalar.type_size() * rows as u64 * cols as u64) as usize;
+        let mut items: Vec<Vec<u8>> = vec![];
+        for block in RawBlock::read_property(state, ty, prop, &mut r).unwrap() {
+            for (i, item) in block.data.chunks(size).enumerate() {
+                let ind = block.offset as usize + i;
+                if ind >= items.len() {items.resize(ind + 1, vec![0; size])}
+                items[ind] = item.to_vec();
+            }
+        }
+        res.push((ty, prop, items));
+    }
+    let mut rest = vec![];
+    r.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    res
+}
+
+fn expected(model: &Model) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    model.properties.iter()
+        .map(|p| (p.type_format, p.property_id, p.items.clone()))
+        .collect()
+}
+
+/// Writes a stream in chunks on another thread.
+fn send(data: Vec<u8>, chunk: usize, sink: ChannelSink) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut w = SinkWriter::new(sink);
+        for part in data.chunks(chunk) {w.write_all(part)?}
+        w.into_inner().finish()
+    })
+}
+
+fn header(bytes: u64, offset: u64) -> BlockHeader {
+    BlockHeader {type_format: Type::U8.scalar().0, property_id: POSITION, bytes, offset}
+}
+
+proptest! {
+    #[test]
+    fn round_trip(
+        model in stream(StreamConfig::default()),
+        chunk in 1..64usize,
+        capacity in 0..4usize,
+        crossbeam in proptest::bool::ANY,
+    ) {
+        let data = model.encode();
+        let (sink, source) = transport(capacity, crossbeam);
+        let writer = send(data.clone(), chunk, sink);
+        prop_assert_eq!(decode(SourceReader::new(source)), expected(&model));
+        writer.join().unwrap().unwrap();
+
+        // The bytes are the same as the encoded stream.
+        let (sink, source) = transport(capacity, crossbeam);
+        let writer = send(data.clone(), data.len(), sink);
+        let mut received = vec![];
+        SourceReader::new(source).read_to_end(&mut received).unwrap();
+        writer.join().unwrap().unwrap();
+        prop_assert_eq!(received, data);
+    }
+}
+
+#[test]
+fn frames_between_threads() {
+    let positions: Vec<Vec<[f32; 3]>> = (0..50).map(|i| vec![[i as f32; 3]; 1 + i]).collect();
+    for &crossbeam in &[false, true] {
+        let (sink, source) = transport(2, crossbeam);
+        let written = positions.clone();
+        let writer = thread::spawn(move || -> io::Result<()> {
+            let mut w = FrameWriter::new(PoolWriter::new(SinkWriter::new(sink)), TIME);
+            let mut units = Units::new();
+            units.set(POSITION, Unit::new("m", 1.0));
+            units.write_units(&mut w)?;
+            for (i, pos) in written.iter().enumerate() {
+                w.begin_frame(i as f64)?;
+                Vector::write_array(POSITION, pos, &mut w)?;
+            }
+            w.finish()?;
+            Ok(())
+        });
+
+        let mut r = FrameReader::with_time(PoolReader::new(SourceReader::new(source)), TIME);
+        let mut res = vec![];
+        while let Some(frame) = r.next_frame().unwrap() {
+            let mut pos = vec![];
+            frame.read_array(POSITION, &mut pos).unwrap();
+            res.push((frame.time, pos));
+        }
+        writer.join().unwrap().unwrap();
+        let expected: Vec<_> = positions.iter().cloned().enumerate()
+            .map(|(i, pos)| (Some(i as f64), pos))
+            .collect();
+        assert_eq!(res, expected);
+        // The header and units are read before the first frame.
+        let preamble: Vec<u16> = r.preamble().iter().map(|b| b.property_id).collect();
+        assert_eq!(preamble, vec![HEADER_PROPERTY, UNITS_PROPERTY]);
+    }
+}
+
+#[test]
+fn end_of_stream() {
+    // Dropping the sink between properties ends the stream.
+    let (mut sink, source) = channel(4);
+    sink.send(&header(2, 0), &[1, 2]).unwrap();
+    sink.send(&header(0, 0), &[]).unwrap();
+    drop(sink);
+    let mut data = vec![];
+    SourceReader::new(source).read_to_end(&mut data).unwrap();
+    let mut expected = vec![];
+    write_block(Type::U8.scalar().0, POSITION, 0, &[1, 2], &mut expected).unwrap();
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert_eq!(data, expected);
+
+    // Dropping it in the middle of a property does not.
+    let (mut sink, source) = channel(4);
+    sink.send(&header(2, 0), &[1, 2]).unwrap();
+    drop(sink);
+    let mut data = vec![];
+    let err = SourceReader::new(source).read_to_end(&mut data).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    // The source returns nothing after the end of stream.
+    let (mut sink, mut source) = channel(4);
+    sink.end().unwrap();
+    assert!(source.recv().unwrap().is_none());
+    assert!(source.recv().unwrap().is_none());
+    assert_eq!(sink.send(&header(1, 0), &[1]).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    sink.finish().unwrap();
+}
+
+#[test]
+fn invalid_blocks() {
+    let (mut sink, source) = channel(4);
+    let invalid = |err: io::Error| err.kind() == io::ErrorKind::InvalidInput;
+    assert!(invalid(sink.send(&header(2, 0), &[1]).unwrap_err()));
+    assert!(invalid(sink.send(&BlockHeader {type_format: 0, ..header(1, 0)}, &[1]).unwrap_err()));
+    sink.send(&header(1, 0), &[1]).unwrap();
+    let other = BlockHeader {property_id: POSITION + 1, ..header(1, 0)};
+    assert!(invalid(sink.send(&other, &[1]).unwrap_err()));
+    assert!(invalid(sink.end().unwrap_err()));
+
+    // The source has gone away.
+    drop(source);
+    let err = sink.send(&header(0, 0), &[]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn bounded_capacity() {
+    for &crossbeam in &[false, true] {
+        let (mut sink, mut source) = transport(1, crossbeam);
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = sent.clone();
+        let writer = thread::spawn(move || {
+            for i in 0..5 {
+                sink.send(&header(1, i), &[i as u8]).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            sink.send(&header(0, 0), &[]).unwrap();
+            sink.finish().unwrap();
+        });
+        // The sender waits while the channel is full.
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+        let mut offsets = vec![];
+        while let Some((header, payload)) = source.recv().unwrap() {
+            if header.is_end() {continue}
+            assert_eq!(&payload[..], &[header.offset as u8]);
+            offsets.push(header.offset);
+        }
+        writer.join().unwrap();
+        assert_eq!(offsets, vec![0, 1, 2, 3, 4]);
+        assert_eq!(sent.load(Ordering::SeqCst), 5);
+    }
+}
@@ -0,0 +1,153 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Cursor;
+
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+fn stream(endian: Endian) -> Vec<u8> {
+    let mut rng = Rng::new(155);
+    let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+    Units::write(&[(POSITION, "m", 1.0)], &mut w).unwrap();
+    write_metadata(&[(POSITION, &Metadata::new().with(DESCRIPTION, "position"))], &mut w).unwrap();
+    for i in 0..30 {
+        let n = 1 + rng.below(20) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+        Vector::write_array(POSITION, &pos, &mut w).unwrap();
+        if i % 3 == 0 {
+            Scalar::write_array(MASS, &vec![i as f64; n], &mut w).unwrap();
+        }
+        if i == 20 {
+            write_metadata(&[(MASS, &Metadata::new().with(DESCRIPTION, "mass"))], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap()
+}
+
+fn reader<R: std::io::Read>(r: &mut PoolReader<R>) {
+    r.register(POSITION);
+    r.register(MASS);
+}
+
+/// Reads `n` properties, or all when `n` is `None`.
+fn read<R: std::io::Read>(r: &mut PoolReader<R>, n: Option<usize>) -> Vec<RawBlock> {
+    let mut res = vec![];
+    while n != Some(res.len()) {
+        match r.next_property().unwrap() {
+            Some((state, ty, prop)) => {
+                let mut blocks = RawBlock::read_property(state, ty, prop, r).unwrap();
+                assert_eq!(blocks.len(), 1);
+                res.push(blocks.remove(0));
+            }
+            None => break,
+        }
+    }
+    res
+}
+
+#[test]
+fn stop_and_resume() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = stream(endian);
+        let mut r = PoolReader::new(&data[..]);
+        reader(&mut r);
+        let all = read(&mut r, None);
+        assert_eq!(all.len(), 40);
+
+        for &stop in &[0, 1, 7, 20, 39, 40] {
+            let mut r = PoolReader::new(Cursor::new(&data[..]));
+            reader(&mut r);
+            let mut blocks = read(&mut r, Some(stop));
+            // The checkpoint is stored between sessions.
+            let mut stored = vec![];
+            r.checkpoint().unwrap().write(&mut stored).unwrap();
+            let cp = Checkpoint::read(&mut &stored[..]).unwrap();
+            assert_eq!(cp, r.checkpoint().unwrap());
+            // The header is read with the first property.
+            let header = if stop > 0 {Some(endian)} else {None};
+            assert_eq!(cp.header.map(|h| h.endian), header);
+
+            let mut r = PoolReader::resume(Cursor::new(&data[..]), &cp).unwrap();
+            reader(&mut r);
+            blocks.extend(read(&mut r, None));
+            // Units and metadata read before the checkpoint are kept.
+            assert_eq!(r.units().get(POSITION).map(|u| &u.name[..]), Some("m"));
+            assert!(blocks == all, "{:?} {}", endian, stop);
+            assert_eq!(r.metadata(MASS).and_then(|m| m.description()), Some("mass"));
+            assert_eq!(r.metadata(POSITION).and_then(|m| m.description()), Some("position"));
+        }
+    }
+}
+
+#[test]
+fn resumed_twice() {
+    let data = stream(Endian::Little);
+    let mut r = PoolReader::new(Cursor::new(&data[..]));
+    reader(&mut r);
+    let mut blocks = read(&mut r, Some(5));
+    let cp = r.checkpoint().unwrap();
+    let mut r = PoolReader::resume(Cursor::new(&data[..]), &cp).unwrap();
+    reader(&mut r);
+    blocks.extend(read(&mut r, Some(5)));
+    let cp = r.checkpoint().unwrap();
+    let mut r = PoolReader::resume(Cursor::new(&data[..]), &cp).unwrap();
+    reader(&mut r);
+    blocks.extend(read(&mut r, None));
+
+    let mut r = PoolReader::new(&data[..]);
+    reader(&mut r);
+    assert!(blocks == read(&mut r, None));
+}
+
+fn mismatch(data: &[u8], cp: &Checkpoint) {
+    let err = PoolReader::resume(Cursor::new(data), cp).err().unwrap();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::CheckpointMismatch);
+}
+
+#[test]
+fn changed_stream() {
+    let data = stream(Endian::Little);
+    let mut r = PoolReader::new(Cursor::new(&data[..]));
+    reader(&mut r);
+    read(&mut r, Some(10));
+    let cp = r.checkpoint().unwrap();
+    let position = cp.position as usize;
+
+    // A byte before the position changed.
+    let mut changed = data.clone();
+    changed[position - 1] ^= 1;
+    mismatch(&changed, &cp);
+    // Truncated before the position.
+    mismatch(&data[..position - 1], &cp);
+    // Bytes inserted before the position.
+    let mut changed = data.clone();
+    changed.insert(position - 30, 0);
+    mismatch(&changed, &cp);
+    // Another position.
+    mismatch(&data, &Checkpoint {position: cp.position + 1, ..cp.clone()});
+    mismatch(&data, &Checkpoint {hash: cp.hash ^ 1, ..cp.clone()});
+
+    // Changes after the position are not checked.
+    let mut changed = data.clone();
+    changed[position + 30] ^= 1;
+    PoolReader::resume(Cursor::new(&changed[..]), &cp).unwrap();
+}
+
+#[test]
+fn inside_property() {
+    let data = stream(Endian::Little);
+    let mut r = PoolReader::new(&data[..]);
+    reader(&mut r);
+    let (state, ty, _) = r.next_property().unwrap().unwrap();
+    let err = r.checkpoint().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let mut arr: Vec<[f32; 3]> = vec![];
+    Vector::read_array(state, ty, &mut arr, &mut r).unwrap();
+    r.checkpoint().unwrap();
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+const FRAMES: usize = 8;
+
+/// Returns an empty temporary directory.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("binpool-commit-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn positions(frame: usize) -> Vec<[f32; 3]> {
+    (0..1 + frame * 3).map(|i| [frame as f32, i as f32, 0.5]).collect()
+}
+
+/// Writes frames, calling `begin` to start each frame.
+fn write_frames<W: Write>(w: &mut W, frames: usize, mut begin: impl FnMut(&mut W, f64)) {
+    for i in 0..frames {
+        begin(w, i as f64);
+        Vector::write_array(POSITION, &positions(i), w).unwrap();
+    }
+}
+
+/// Reads the positions of every frame.
+fn read(path: &Path) -> Vec<(Option<f64>, Vec<[f32; 3]>)> {
+    let data = fs::read(path).unwrap();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut pos = vec![];
+        frame.read_array(POSITION, &mut pos).unwrap();
+        res.push((frame.time, pos));
+    }
+    res
+}
+
+fn expected(frames: usize) -> Vec<(Option<f64>, Vec<[f32; 3]>)> {
+    (0..frames).map(|i| (Some(i as f64), positions(i))).collect()
+}
+
+/// Writes a whole run, returning the stream and the committed length when each frame begins.
+fn run(path: &Path, opts: CommitOptions) -> (Vec<u8>, Vec<u64>) {
+    let mut w = CommitWriter::create(path, TIME, opts).unwrap();
+    let mut boundaries = vec![];
+    write_frames(&mut w, FRAMES, |w, t| {
+        w.begin_frame(t).unwrap();
+        boundaries.push(w.committed_len());
+    });
+    w.finish().unwrap();
+    assert!(!journal_path(path).exists());
+    (fs::read(path).unwrap(), boundaries)
+}
+
+#[test]
+fn same_stream_for_every_policy() {
+    let dir = temp_dir("policies");
+    let path = dir.join("run.pool");
+    let mut reference = FrameWriter::new(vec![], TIME);
+    write_frames(&mut reference, FRAMES, |w, t| w.begin_frame(t).unwrap());
+    let reference = reference.finish().unwrap();
+    let policies = [SyncPolicy::Never, SyncPolicy::EveryFrame, SyncPolicy::EveryFrames(3)];
+    for &sync in &policies {
+        for &journal in &[false, true] {
+            let opts = CommitOptions::default().sync(sync).journal(journal);
+            let (data, boundaries) = run(&path, opts);
+            assert_eq!(data, reference);
+            // Frames are committed when the next frame begins.
+            assert_eq!(boundaries[0], 0);
+            assert!(boundaries.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(read(&path), expected(FRAMES));
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn crash_mid_frame() {
+    let dir = temp_dir("crash");
+    let path = dir.join("run.pool");
+    let (full, boundaries) = run(&path, CommitOptions::default());
+    for crashed in 1..FRAMES {
+        // The writer is dropped while writing a frame, which loses the frame.
+        let opts = CommitOptions::default().sync(SyncPolicy::EveryFrames(2));
+        let mut w = CommitWriter::create(&path, TIME, opts).unwrap();
+        write_frames(&mut w, crashed + 1, |w, t| w.begin_frame(t).unwrap());
+        assert_eq!((w.frames(), w.committed_frames()), (crashed as u64 + 1, crashed as u64));
+        assert_eq!(w.committed_len(), boundaries[crashed]);
+        drop(w);
+        assert_eq!(fs::metadata(&path).unwrap().len(), boundaries[crashed]);
+
+        // Parts of the frame may have reached the file anyway, when a write was torn.
+        let end = if crashed + 1 < FRAMES {boundaries[crashed + 1] as usize} else {full.len() - 2};
+        for cut in boundaries[crashed] as usize..end {
+            fs::write(&path, &full[..cut]).unwrap();
+            let report = recover_file(&path, &RecoverOptions::default().time_property(TIME))
+                .unwrap();
+            assert!(report.len >= boundaries[crashed] + 2);
+            // Every committed frame is replayed, and at most the start of the torn frame.
+            let frames = read(&path);
+            assert_eq!(&frames[..crashed], &expected(crashed)[..]);
+            assert!(frames.len() <= crashed + 1);
+            if frames.len() > crashed {
+                assert_eq!(frames[crashed], (Some(crashed as f64), vec![]));
+            }
+            assert_eq!(report.last_time, frames.last().unwrap().0);
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn journal_replay() {
+    let dir = temp_dir("journal");
+    let path = dir.join("run.pool");
+    let (full, boundaries) = run(&path, CommitOptions::default());
+    assert_eq!(replay_journal(&path).unwrap(), 0);
+
+    // Frames 3 and 4 are in the journal, since the file was synced when frame 3 began.
+    let crashed = 5;
+    let opts = CommitOptions::default().sync(SyncPolicy::EveryFrames(3)).journal(true);
+    let mut w = CommitWriter::create(&path, TIME, opts).unwrap();
+    write_frames(&mut w, crashed + 1, |w, t| w.begin_frame(t).unwrap());
+    drop(w);
+    let journal = fs::read(journal_path(&path)).unwrap();
+    let record = |frame: usize| 24 + (boundaries[frame + 1] - boundaries[frame]) as usize;
+    assert_eq!(journal.len(), record(3) + record(4));
+
+    // The appends to the file were lost, and the file ends inside frame 3.
+    let torn = boundaries[3] as usize + 10;
+    for &(journal_len, replayed, frames) in &[
+        (journal.len(), 2, 5),
+        // A torn record was never appended, and the file was not written after it.
+        (journal.len() - 1, 1, 4),
+        (record(3) + 20, 1, 4),
+    ] {
+        fs::write(&path, &full[..torn]).unwrap();
+        fs::write(journal_path(&path), &journal[..journal_len]).unwrap();
+        if frames == 4 {
+            let f = OpenOptions::new().write(true).open(&path).unwrap();
+            f.set_len(boundaries[3]).unwrap();
+        }
+        assert_eq!(replay_journal(&path).unwrap(), replayed);
+        assert!(!journal_path(&path).exists());
+        assert_eq!(fs::metadata(&path).unwrap().len(), boundaries[frames]);
+        assert_eq!(replay_journal(&path).unwrap(), 0);
+        recover_file(&path, &RecoverOptions::default()).unwrap();
+        assert_eq!(read(&path), expected(frames));
+    }
+
+    // A journal with a broken hash is not replayed.
+    let mut broken = journal.clone();
+    broken[20] ^= 1;
+    fs::write(&path, &full[..boundaries[3] as usize]).unwrap();
+    fs::write(journal_path(&path), &broken).unwrap();
+    assert_eq!(replay_journal(&path).unwrap(), 0);
+    assert_eq!(fs::metadata(&path).unwrap().len(), boundaries[3]);
+    fs::remove_dir_all(&dir).unwrap();
+}
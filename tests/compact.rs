@@ -0,0 +1,160 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const KIND: u16 = 3;
+
+/// Writes frames that grow, shrink and partly overwrite their properties.
+fn stream(endian: Endian) -> Vec<u8> {
+    let mut rng = Rng::new(144);
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]).with_endianness(endian), TIME);
+    w.write_time_unit(&TimeUnit::Custom("ticks".into())).unwrap();
+    for frame in 0..20 {
+        w.begin_frame(frame as f64).unwrap();
+        let n = 1 + rng.below(30) as usize;
+        let offset = rng.below(20);
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+        let mut data = vec![];
+        for p in &pos {
+            p.write_element(&mut data).unwrap();
+        }
+        let ty = Type::F32.vector(3).unwrap().0;
+        RawBlock::write_property(ty, POSITION, &[
+            RawBlock {type_format: ty, property_id: POSITION, offset, data: data.clone()},
+            // Overlaps the first block.
+            RawBlock {type_format: ty, property_id: POSITION, offset: offset / 2, data},
+        ], &mut w).unwrap();
+
+        // Run-length encoded in some frames, plain in others.
+        let mass = vec![frame as u16; 1 + rng.below(40) as usize];
+        if frame % 3 == 0 {
+            Scalar::write_array(MASS, &mass, &mut w).unwrap();
+        } else {
+            write_rle(MASS, &mass, &mut w).unwrap();
+        }
+
+        // The type format changes, leaving out the older blocks.
+        if frame < 10 {
+            Scalar::write_array(KIND, &vec![frame as u8; 50], &mut w).unwrap();
+        } else if frame % 4 == 0 {
+            Scalar::write_array(KIND, &vec![frame as u32; 5], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap().get_ref().clone()
+}
+
+/// The arrays of the final state.
+#[derive(Debug, Default, PartialEq)]
+struct Arrays {
+    position: Vec<[f32; 3]>,
+    mass: Vec<u16>,
+    kind: Vec<u32>,
+}
+
+/// Reads the final state by applying every block of the stream in order.
+fn replay(data: &[u8]) -> (Arrays, u64, Option<f64>, Option<TimeUnit>) {
+    // The pool reader converts big endian streams.
+    let mut r = FrameReader::with_time(PoolReader::new(data), TIME);
+    let mut blocks = vec![];
+    let mut last_time = None;
+    while let Some(frame) = r.next_frame().unwrap() {
+        last_time = frame.time;
+        blocks.extend(frame.blocks);
+    }
+    let frames = r.frames();
+    blocks.splice(0..0, r.preamble().iter().cloned());
+    let blocks = blocks.into_iter().map(|b| {
+        if b.type_format == RLE_FORMAT {expand_rle(&b, None).unwrap()} else {b}
+    });
+    let u32_format = Type::U32.scalar().0;
+    let frame = Frame {
+        number: 0,
+        time: None,
+        blocks: blocks.filter(|b| b.property_id != KIND || b.type_format == u32_format)
+            .collect(),
+    };
+    let mut arrays = Arrays::default();
+    frame.read_array(POSITION, &mut arrays.position).unwrap();
+    frame.read_array(MASS, &mut arrays.mass).unwrap();
+    frame.read_array(KIND, &mut arrays.kind).unwrap();
+    (arrays, frames, last_time, r.time_unit().cloned())
+}
+
+#[test]
+fn replay_matches() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = stream(endian);
+        let (expected, frames, time, unit) = replay(&data);
+        assert_eq!(frames, 20);
+        assert_eq!(time, Some(19.0));
+        assert!(expected.position.len() > 20 && expected.mass.len() > 20);
+        assert_eq!(expected.kind.len(), 5);
+
+        let mut out = vec![];
+        let opts = CompactOptions {frame_marker: Some(TIME), keep_marker: false};
+        compact(&mut std::io::Cursor::new(&data), &mut out, &opts).unwrap();
+        assert!(out.len() < data.len() / 4, "{:?}", endian);
+        // Without the marker, all data is before the first frame.
+        let (state, frames, time, compacted_unit) = replay(&out);
+        assert_eq!((frames, time), (0, None));
+        assert_eq!(compacted_unit, unit);
+        assert_eq!(state.position.len(), expected.position.len());
+        for (i, (a, b)) in state.position.iter().zip(&expected.position).enumerate() {
+            assert_eq!(a, b, "instance {}", i);
+        }
+        assert_eq!(state, expected);
+        // The byte order is kept.
+        let mut r = PoolReader::new(&out[..]).unknown(Unknown::Skip);
+        while r.next_property().unwrap().is_some() {}
+        assert_eq!(r.header().map(|h| h.endian), Some(endian));
+
+        // With the marker, the output is the last frame.
+        let mut out = vec![];
+        let opts = CompactOptions {frame_marker: Some(TIME), keep_marker: true};
+        compact(&mut std::io::Cursor::new(&data), &mut out, &opts).unwrap();
+        let (state, frames, time, compacted_unit) = replay(&out);
+        assert_eq!((frames, time), (1, Some(19.0)));
+        assert_eq!(compacted_unit, unit);
+        assert_eq!(state, expected);
+        validate(&mut &out[..], &ValidateOptions::default()).unwrap();
+    }
+}
+
+#[test]
+fn compact_is_idempotent() {
+    let data = stream(Endian::Little);
+    let opts = CompactOptions {frame_marker: Some(TIME), keep_marker: true};
+    let mut once = vec![];
+    compact(&mut std::io::Cursor::new(&data), &mut once, &opts).unwrap();
+    let mut twice = vec![];
+    compact(&mut std::io::Cursor::new(&once), &mut twice, &opts).unwrap();
+    assert_eq!(once, twice);
+}
+
+#[test]
+fn only_written_instances() {
+    // Gaps between blocks are kept as gaps.
+    let ty = Type::U8.scalar().0;
+    let mut data = vec![];
+    RawBlock::write_property(ty, MASS, &[
+        RawBlock {type_format: ty, property_id: MASS, offset: 10, data: vec![1; 5]},
+        RawBlock {type_format: ty, property_id: MASS, offset: 0, data: vec![2; 3]},
+        RawBlock {type_format: ty, property_id: MASS, offset: 12, data: vec![3; 5]},
+    ], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let mut out = vec![];
+    compact(&mut std::io::Cursor::new(&data), &mut out, &CompactOptions::default()).unwrap();
+    let mut r = &out[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let blocks = RawBlock::read_property(state.unwrap(), ty, prop, &mut r).unwrap();
+    let blocks: Vec<_> = blocks.iter().map(|b| (b.offset, b.data.clone())).collect();
+    assert_eq!(blocks, vec![(0, vec![2; 3]), (10, vec![1, 1, 3, 3, 3, 3, 3])]);
+}
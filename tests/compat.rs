@@ -0,0 +1,107 @@
+#![cfg(feature = "test-util")]
+
+extern crate binpool;
+
+use binpool::compat::{self, MANIFEST};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The fixtures checked in with the crate.
+fn assets() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("compat")
+}
+
+/// Returns an empty temporary directory.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("binpool-compat-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Copies the checked-in fixtures to a temporary directory.
+fn copy(name: &str) -> PathBuf {
+    let dir = temp_dir(name);
+    for entry in fs::read_dir(assets()).unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), dir.join(entry.file_name())).unwrap();
+    }
+    dir
+}
+
+fn message(res: io::Result<usize>) -> String {
+    let err = res.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    err.to_string()
+}
+
+#[test]
+fn checked_in_fixtures() {
+    let n = compat::verify_all(assets()).unwrap();
+    assert_eq!(n, compat::fixtures().unwrap().len());
+    // Every fixture is described by its manifest section.
+    let manifest = fs::read_to_string(assets().join(MANIFEST)).unwrap();
+    for (name, bytes) in compat::fixtures().unwrap() {
+        let lines = compat::describe(&bytes).unwrap();
+        let section = format!("fixture {}\n{}\n", name, lines.join("\n"));
+        assert!(manifest.contains(&section), "{}", name);
+    }
+}
+
+#[test]
+fn regenerate() {
+    let dir = temp_dir("regenerate");
+    let added = compat::regenerate(&dir).unwrap();
+    let names: Vec<&str> = compat::fixtures().unwrap().iter().map(|f| f.0).collect();
+    assert_eq!(added, names);
+    assert_eq!(compat::verify_all(&dir).unwrap(), names.len());
+    let manifest = fs::read(dir.join(MANIFEST)).unwrap();
+    assert_eq!(manifest, fs::read(assets().join(MANIFEST)).unwrap());
+
+    // Existing fixtures are kept, and missing ones are added.
+    fs::write(dir.join(names[0]), b"older").unwrap();
+    fs::remove_file(dir.join(names[1])).unwrap();
+    assert_eq!(compat::regenerate(&dir).unwrap(), Vec::<&str>::new());
+    assert_eq!(fs::read(dir.join(names[0])).unwrap(), b"older");
+    assert!(!dir.join(names[1]).exists());
+    assert_eq!(fs::read(dir.join(MANIFEST)).unwrap(), manifest);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn detects_changes() {
+    let names: Vec<&str> = compat::fixtures().unwrap().iter().map(|f| f.0).collect();
+
+    // A fixture that decodes to other contents.
+    let dir = copy("changed");
+    let path = dir.join("scalars.pool");
+    let mut bytes = fs::read(&path).unwrap();
+    let last = bytes.len() - 3;
+    bytes[last] ^= 1;
+    fs::write(&path, bytes).unwrap();
+    assert!(message(compat::verify_all(&dir)).contains("`scalars.pool`"));
+
+    // A fixture that is missing.
+    fs::copy(assets().join("scalars.pool"), &path).unwrap();
+    compat::verify_all(&dir).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(message(compat::verify_all(&dir)).contains("`scalars.pool`"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // A fixture that is not in the manifest.
+    let dir = copy("unlisted");
+    fs::write(dir.join("new.pool"), b"").unwrap();
+    assert!(message(compat::verify_all(&dir)).contains("`new.pool`: not in the manifest"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    // A fixture that has not been generated.
+    let dir = copy("generated");
+    let manifest = fs::read_to_string(dir.join(MANIFEST)).unwrap();
+    let last = names[names.len() - 1];
+    let start = manifest.find(&format!("\nfixture {}", last)).unwrap();
+    fs::write(dir.join(MANIFEST), &manifest[..start]).unwrap();
+    fs::remove_file(dir.join(last)).unwrap();
+    assert!(message(compat::verify_all(&dir)).contains("not generated"));
+    fs::remove_dir_all(&dir).unwrap();
+}
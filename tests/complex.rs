@@ -0,0 +1,73 @@
+#![cfg(feature = "num-complex")]
+
+extern crate binpool;
+extern crate num_complex;
+
+use binpool::complex::{from_interleaved, to_interleaved};
+use binpool::*;
+use num_complex::Complex;
+
+const PSI: u16 = 1;
+
+fn read<T: Vector>(data: &[u8]) -> Vec<T> {
+    let mut r = data;
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr = vec![];
+    Vector::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap();
+    arr
+}
+
+fn psi() -> Vec<Complex<f64>> {
+    (0..100).map(|i| Complex::new((i as f64).cos(), -(i as f64).sin())).collect()
+}
+
+#[test]
+fn round_trip() {
+    let psi = psi();
+    let mut data = vec![];
+    Vector::write_array(PSI, &psi, &mut data).unwrap();
+    assert_eq!(read::<Complex<f64>>(&data), psi);
+
+    let psi: Vec<Complex<f32>> = vec![Complex::new(1.0, 2.0), Complex::new(-0.0, f32::MAX)];
+    let mut data = vec![];
+    Vector::write_array(PSI, &psi, &mut data).unwrap();
+    assert_eq!(read::<Complex<f32>>(&data), psi);
+    // The type format is a vector of dimension 2.
+    assert_eq!(&data[..2], &Type::F32.vector(2).unwrap().0.to_le_bytes());
+}
+
+#[test]
+fn cross_layout() {
+    let psi = psi();
+    let pairs = to_interleaved(&psi);
+    assert_eq!(pairs[1], [psi[1].re, psi[1].im]);
+    assert_eq!(from_interleaved(&pairs), psi);
+
+    // Written as complex, read as pairs.
+    let mut a = vec![];
+    Vector::write_array(PSI, &psi, &mut a).unwrap();
+    assert_eq!(read::<[f64; 2]>(&a), pairs);
+    // Written as pairs, read as complex.
+    let mut b = vec![];
+    Vector::write_array(PSI, &pairs, &mut b).unwrap();
+    assert_eq!(a, b);
+    assert_eq!(read::<Complex<f64>>(&b), psi);
+}
+
+#[test]
+fn frames() {
+    let psi = psi();
+    let mut w = FrameWriter::new(vec![], 0);
+    w.begin_frame(0.0).unwrap();
+    Vector::write_array(PSI, &psi, &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let frame = FrameReader::with_time(&data[..], 0).next_frame().unwrap().unwrap();
+    let mut arr: Vec<Complex<f64>> = vec![];
+    frame.read_array(PSI, &mut arr).unwrap();
+    assert_eq!(arr, psi);
+    // Another scalar type does not match.
+    let mut arr: Vec<Complex<f32>> = vec![];
+    let err = frame.read_array(PSI, &mut arr).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(Error::from_io(&err).property(), Some(PSI));
+}
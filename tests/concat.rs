@@ -0,0 +1,220 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::fs;
+use std::io::{self, Cursor};
+use std::ops::Range;
+use std::path::Path;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+/// The fixtures checked in with the crate.
+fn fixtures() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("compat");
+    let mut res: Vec<(String, Vec<u8>)> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|x| x == "pool"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(&path).unwrap())
+        })
+        .collect();
+    res.sort();
+    res
+}
+
+type Property = (u16, u16, Range<usize>, usize);
+
+/// Returns the type format, property id, range and number of blocks
+/// of every property of a little endian stream.
+fn properties(data: &[u8]) -> Vec<Property> {
+    let mut res = vec![];
+    let mut r = data;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let start = data.len() - r.len() - 4;
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        res.push((ty, prop, start..data.len() - r.len(), blocks.len()));
+    }
+    res
+}
+
+/// Returns the number of blocks of a little endian stream, besides the header.
+fn blocks(data: &[u8]) -> usize {
+    properties(data).iter().filter(|p| p.0 != HEADER_FORMAT).map(|p| p.3).sum()
+}
+
+/// The expected concatenation of little endian streams.
+fn expected(inputs: &[&[u8]]) -> Vec<u8> {
+    let mut res = vec![];
+    for (file, data) in inputs.iter().enumerate() {
+        for (ty, prop, range, _) in properties(data) {
+            if ty == HEADER_FORMAT && prop == HEADER_PROPERTY && file > 0 {continue}
+            if ty == FOOTER_FORMAT && prop == FOOTER_PROPERTY {continue}
+            res.extend_from_slice(&data[range]);
+        }
+    }
+    res.extend_from_slice(&[0, 0]);
+    res
+}
+
+fn concat_all(inputs: &[&[u8]], opts: &ConcatOptions) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    let written = concat(inputs.iter().cloned(), &mut out, opts)?;
+    assert_eq!(written, out.len() as u64);
+    Ok(out)
+}
+
+/// Reads every property of a stream, returning the number of blocks.
+fn read_all(data: &[u8]) -> usize {
+    let mut r = PoolReader::new(data).unknown(Unknown::Collect).reserved_as_data(true);
+    while r.next_property().unwrap().is_some() {}
+    r.unknown_blocks().len()
+}
+
+#[test]
+fn fixtures_both_ways() {
+    let fixtures: Vec<(String, Vec<u8>)> = fixtures().into_iter()
+        .filter(|f| f.0 != "big_endian.pool")
+        .collect();
+    assert_eq!(fixtures.len(), 9);
+    let mut inputs: Vec<&[u8]> = fixtures.iter().map(|f| &f.1[..]).collect();
+    let total: usize = inputs.iter().map(|data| properties(data).len()).sum();
+    for _ in 0..2 {
+        // With `cat`, readers stop at the end of stream of the first file.
+        let cat = inputs.concat();
+        let first = properties(inputs[0]).len() as u64;
+        assert_eq!(read_all(&cat), blocks(inputs[0]));
+        let report = validate(&mut &cat[..], &ValidateOptions::default()).unwrap();
+        assert_eq!(report.properties, first);
+        assert_eq!(report.trailing_bytes, (cat.len() - inputs[0].len()) as u64);
+        assert!(!report.is_ok());
+
+        // With `concat`, every property is read.
+        let out = concat_all(&inputs, &ConcatOptions::default()).unwrap();
+        assert_eq!(out, expected(&inputs));
+        assert_eq!(read_all(&out), inputs.iter().map(|data| blocks(data)).sum::<usize>());
+        let report = validate(&mut &out[..], &ValidateOptions::default()).unwrap();
+        assert_eq!(report.properties, total as u64);
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(recover(&mut Cursor::new(out.clone()), &RecoverOptions::default()).unwrap()
+            .properties, total as u64);
+
+        // Concatenating the result again gives the same stream.
+        assert_eq!(concat_all(&[&out], &ConcatOptions::default()).unwrap(), out);
+        inputs.reverse();
+    }
+}
+
+#[test]
+fn byte_order() {
+    let fixtures = fixtures();
+    let big = &fixtures.iter().find(|f| f.0 == "big_endian.pool").unwrap().1;
+    let little = &fixtures.iter().find(|f| f.0 == "scalars.pool").unwrap().1;
+
+    // Big endian inputs keep the header of the first input.
+    let out = concat_all(&[big, big], &ConcatOptions::default()).unwrap();
+    let report = validate(&mut &out[..], &ValidateOptions::default()).unwrap();
+    let single = validate(&mut &big[..], &ValidateOptions::default()).unwrap();
+    assert_eq!(report.properties, 2 * single.properties - 1);
+    assert!(report.is_ok());
+
+    for inputs in &[[&little[..], &big[..]], [&big[..], &little[..]]] {
+        let err = concat_all(inputs, &ConcatOptions::default()).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!((err.kind(), err.file()), (ErrorKind::InvalidData, Some(1)));
+    }
+}
+
+/// Writes units followed by a frame.
+fn with_units(unit: &str) -> Vec<u8> {
+    let mut data = vec![];
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new(unit, 1.0));
+    units.write_units(&mut data).unwrap();
+    Time::write(TIME, 0.5, &mut data).unwrap();
+    Vector::write_array(POSITION, &vec![[1.0_f32; 3]; 2], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    data
+}
+
+/// Counts the units of a stream.
+fn units(data: &[u8]) -> usize {
+    properties(data).iter().filter(|p| p.0 == UNITS_FORMAT && p.1 == UNITS_PROPERTY).count()
+}
+
+#[test]
+fn sticky_properties() {
+    let (m, km) = (with_units("m"), with_units("km"));
+    let keep = ConcatOptions::default();
+    let verify = ConcatOptions::default().sticky(StickyPolicy::Verify);
+    let dedupe = ConcatOptions::default().sticky(StickyPolicy::Dedupe);
+
+    // Everything is kept by default.
+    let out = concat_all(&[&m, &km, &m], &keep).unwrap();
+    assert_eq!(out, expected(&[&m, &km, &m]));
+    assert_eq!(units(&out), 3);
+
+    // Equal units pass, and units that differ are reported with the input they are in.
+    assert_eq!(concat_all(&[&m, &m], &verify).unwrap(), expected(&[&m, &m]));
+    let err = concat_all(&[&m, &m, &km], &verify).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::PropertyConflict);
+    assert_eq!((err.file(), err.property()), (Some(2), Some(UNITS_PROPERTY)));
+
+    // Units equal to the last ones before are left out.
+    let out = concat_all(&[&m, &m, &km, &km, &m], &dedupe).unwrap();
+    assert_eq!(units(&out), 3);
+    assert_eq!(read_all(&out), 3 + 5 * 2);
+    assert_eq!(blocks(&out), 3 + 5 * 2);
+    let out = concat_all(&[&m, &m], &dedupe).unwrap();
+    assert_eq!(out, [&m[..m.len() - 2], &m[properties(&m)[1].2.start..]].concat());
+}
+
+#[test]
+fn footers() {
+    let write = |t: f64| {
+        let mut w = PoolWriter::new(vec![]).with_footer_index(true);
+        Time::write(TIME, t, &mut w).unwrap();
+        Vector::write_array(POSITION, &vec![[t as f32; 3]; 3], &mut w).unwrap();
+        w.finish().unwrap()
+    };
+    let (a, b) = (write(0.0), write(1.0));
+
+    // The footers and their trailers are left out.
+    let out = concat_all(&[&a, &b], &ConcatOptions::default()).unwrap();
+    assert_eq!(out, expected(&[&a, &b]));
+    assert!(!properties(&out).iter().any(|p| p.0 == FOOTER_FORMAT));
+    assert_eq!(properties(&out).len(), 5);
+    let index = Index::load_or_build(&mut Cursor::new(&out), TIME, Some(TIME)).unwrap();
+    let times: Vec<Option<f64>> = index.frames().iter().map(|f| f.time).collect();
+    assert_eq!(times, vec![Some(0.0), Some(1.0)]);
+    let report = validate(&mut &out[..], &ValidateOptions::default()).unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+
+    // A trailer alone is not reported, but data after it is.
+    let report = validate(&mut &a[..], &ValidateOptions::default()).unwrap();
+    assert_eq!(report.trailing_bytes, 0);
+    let report = validate(&mut &[&a[..], &b[..]].concat()[..], &ValidateOptions::default())
+        .unwrap();
+    assert_eq!(report.trailing_bytes, 16 + b.len() as u64);
+    // Zeros after the end of stream are not reported.
+    let data = [&with_units("m")[..], &[0; 40][..]].concat();
+    let report = validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+    assert!(report.is_ok());
+}
+
+#[test]
+fn torn_input() {
+    let (a, b) = (with_units("m"), with_units("km"));
+    // An input without an end of stream must end between two properties.
+    let out = concat_all(&[&a[..a.len() - 2], &b], &ConcatOptions::default()).unwrap();
+    assert_eq!(out, expected(&[&a, &b]));
+    for cut in properties(&b).iter().flat_map(|p| p.2.start + 1..p.2.end) {
+        let err = concat_all(&[&a, &b[..cut]], &ConcatOptions::default()).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!((err.kind(), err.file()), (ErrorKind::UnexpectedEof, Some(1)), "{}", cut);
+    }
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+const HIT: u16 = 0xfe00;
+const HITS: u16 = 1;
+const MASS: u16 = 2;
+
+/// Copies every property of a little endian stream, followed by end of stream.
+fn copy_all(data: &[u8]) -> Vec<u8> {
+    let mut r = data;
+    let mut out = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let before = out.len();
+        let n = raw::copy_property(state, ty, prop, &mut r, &mut out).unwrap();
+        assert_eq!(n, (out.len() - before) as u64);
+    }
+    State::new().end_type_formats(&mut out).unwrap();
+    out
+}
+
+#[test]
+fn fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("compat");
+    let mut n = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if !name.ends_with(".pool") || name == "big_endian.pool" {continue}
+        let data = fs::read(&path).unwrap();
+        assert_eq!(copy_all(&data), data, "{}", name);
+        n += 1;
+    }
+    assert_eq!(n, 9);
+}
+
+/// Writes a stream with built-in and custom formats, and properties of several blocks.
+fn stream(rng: &mut Rng) -> Vec<u8> {
+    let mut w = vec![];
+    Scalar::write_array(MASS, &vec![1.5_f64; 3], &mut w).unwrap();
+    write_block(HIT, HITS, 0, &[1, 0, 0, 0, 7, 0], &mut w).unwrap();
+    for &(ty, prop) in &[(Type::F32.vector(3).unwrap().0, 3), (HIT, HITS)] {
+        // Blocks smaller and larger than the chunks that are copied.
+        let blocks: Vec<RawBlock> = [0, 12, 3 * 40_000, 12 * 100_000].iter().enumerate()
+            .map(|(i, &len)| RawBlock {
+                type_format: ty,
+                property_id: prop,
+                offset: i as u64 * 7,
+                data: (0..len).map(|_| rng.below(256) as u8).collect(),
+            })
+            .collect();
+        RawBlock::write_property(ty, prop, &blocks, &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+#[test]
+fn formats_and_blocks() {
+    let data = stream(&mut Rng::new(201));
+    assert_eq!(copy_all(&data), data);
+
+    // Properties are copied with the property id they are given.
+    let mut r = &data[..];
+    let mut out = vec![];
+    let mut expected = vec![];
+    let mut other = &data[..];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        raw::copy_property(state, ty, prop + 10, &mut r, &mut out).unwrap();
+        let (state, ty, prop) = raw::read_property_header(&mut other).unwrap().unwrap();
+        let blocks = RawBlock::read_property(state, ty, prop, &mut other).unwrap();
+        RawBlock::write_property(ty, prop + 10, &blocks, &mut expected).unwrap();
+        assert_eq!(r.len(), other.len());
+    }
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn payload() {
+    let data: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+    for &n in &[0, 1, 65_536, 65_537, 200_000] {
+        let mut r = &data[..];
+        let mut out = vec![];
+        assert_eq!(raw::copy_payload(n as u64, &mut r, &mut out).unwrap(), n as u64);
+        assert_eq!(out, &data[..n]);
+        assert_eq!(r.len(), data.len() - n);
+    }
+    let err = raw::copy_payload(200_001, &mut &data[..], &mut io::sink()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+/// Fails to read after a number of bytes.
+struct Failing<'a> {
+    data: &'a [u8],
+    left: usize,
+}
+
+impl<'a> Read for Failing<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.left == 0 {return Err(io::Error::other("broken"))}
+        let n = buf.len().min(self.left);
+        self.left -= n;
+        self.data.read(&mut buf[..n])
+    }
+}
+
+#[test]
+fn read_errors() {
+    let mut data = vec![];
+    let blocks: Vec<RawBlock> = [1000, 200_000].iter().map(|&len| RawBlock {
+        type_format: HIT,
+        property_id: HITS,
+        offset: 0,
+        data: vec![7; len],
+    }).collect();
+    RawBlock::write_property(HIT, HITS, &blocks, &mut data).unwrap();
+    let second = 4 + 16 + 1000;
+
+    for &fail in &[
+        4, 10, 20, 21, 500, second - 1, second, second + 10,
+        // In the second chunk of the second block, and after it.
+        second + 16 + 70_000, data.len() - 9, data.len() - 1,
+    ] {
+        let mut r = Failing {data: &data, left: fail};
+        let (state, ty, prop) = raw::read_property_header(&mut r).unwrap().unwrap();
+        let mut out = vec![];
+        let err = raw::copy_property(state, ty, prop, &mut r, &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "broken");
+
+        // What is written is the start of the property,
+        // without a block header that is not followed by data.
+        assert_eq!(&out[..], &data[..out.len()]);
+        // A block is written with its first chunk, and the rest a chunk at a time.
+        let expected = match fail {
+            f if f < second => 0,
+            f if f < second + 16 + 65_536 => second,
+            // In the end of bytes.
+            f if f >= data.len() - 8 => data.len() - 8,
+            f => second + 16 + (f - second - 16) / 65_536 * 65_536,
+        };
+        assert_eq!(out.len(), expected, "{}", fail);
+
+        // A property that ends inside a block is cut by `recover`.
+        let mut f = Cursor::new(out);
+        let report = recover(&mut f, &RecoverOptions::default()).unwrap();
+        assert_eq!((report.properties, report.len), (0, 2));
+    }
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+const OTHER: u16 = 3;
+
+fn block(offset: u64, n: usize) -> RawBlock {
+    RawBlock {
+        type_format: Type::F32.vector(3).unwrap().0,
+        property_id: POSITION,
+        offset,
+        data: vec![0; n * 12],
+    }
+}
+
+/// Writes frames with position blocks of `(offset, n)`.
+fn stream(frames: &[&[(u64, usize)]], endian: Endian) -> Vec<u8> {
+    let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+    for (i, blocks) in frames.iter().enumerate() {
+        Time::write(TIME, i as f64, &mut w).unwrap();
+        let blocks: Vec<RawBlock> = blocks.iter().map(|&(offset, n)| block(offset, n)).collect();
+        RawBlock::write_property(blocks[0].type_format, POSITION, &blocks, &mut w).unwrap();
+        // Blocks of other properties are not counted.
+        Scalar::write_array(OTHER, &vec![1_u8; 1000], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+#[test]
+fn gaps_and_overlaps() {
+    let frames: &[&[(u64, usize)]] = &[
+        &[(0, 10), (20, 5)],
+        // Overlaps 8..10, leaves 12..20 and 25..30 uncovered.
+        &[(8, 4), (30, 2)],
+        // Adjacent ranges are coalesced.
+        &[(32, 3), (35, 1), (0, 1)],
+    ];
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = stream(frames, endian);
+        let report = coverage(&mut &data[..], POSITION).unwrap();
+        assert_eq!(report.property_id, POSITION);
+        assert!(report.frames.is_empty());
+        let total = &report.total;
+        assert_eq!(total.ranges(), vec![0..12, 20..25, 30..36], "{:?}", endian);
+        assert_eq!(total.gaps(), vec![12..20, 25..30]);
+        assert_eq!(total.end(), 36);
+        assert!(!total.is_complete());
+        assert_eq!(total.instances(), 12 + 5 + 6);
+        assert_eq!(total.written(), 10 + 5 + 4 + 2 + 3 + 1 + 1);
+        assert_eq!(total.overlap(), 3);
+        assert_eq!(total.blocks(), 7);
+        assert_eq!(total.overlapping_blocks(), 2);
+
+        let report = coverage_per_frame(&mut &data[..], POSITION, Some(TIME)).unwrap();
+        let frames: Vec<_> = report.frames.iter().map(|c| c.ranges()).collect();
+        assert_eq!(frames, vec![vec![0..10, 20..25], vec![8..12, 30..32], vec![0..1, 32..36]]);
+        assert_eq!(report.frames[1].gaps(), vec![0..8, 12..30]);
+        assert_eq!(report.total, coverage(&mut &data[..], POSITION).unwrap().total);
+    }
+}
+
+#[test]
+fn many_small_ranges() {
+    let mut c = Coverage::new();
+    // Every other instance, then the instances between, in reverse order.
+    for i in 0..100_000 {c.insert(2 * i, 2 * i + 1)}
+    assert_eq!(c.ranges().len(), 100_000);
+    for i in (0..100_000).rev() {c.insert(2 * i + 1, 2 * i + 2)}
+    assert_eq!(c.ranges(), vec![0..200_000]);
+    assert!(c.is_complete());
+    assert_eq!((c.instances(), c.overlap()), (200_000, 0));
+    // A range over everything.
+    c.insert(0, 300_000);
+    assert_eq!(c.ranges(), vec![0..300_000]);
+    assert_eq!((c.overlap(), c.overlapping_blocks()), (200_000, 1));
+    // Empty ranges count as blocks only.
+    c.insert(400_000, 400_000);
+    assert_eq!((c.blocks(), c.ranges().len()), (200_002, 1));
+}
+
+#[test]
+fn validate_reports_gaps() {
+    let opts = ValidateOptions {coverage: Some(POSITION), ..ValidateOptions::default()};
+    let complete = stream(&[&[(0, 5)], &[(5, 5)]], Endian::Little);
+    let report = validate(&mut &complete[..], &opts).unwrap();
+    let total = &report.coverage.as_ref().unwrap().total;
+    assert_eq!((total.end(), total.gaps().len()), (10, 0));
+    assert!(report.is_ok());
+
+    let gap = stream(&[&[(0, 5)], &[(6, 5)]], Endian::Big);
+    let report = validate(&mut &gap[..], &opts).unwrap();
+    let total = &report.coverage.as_ref().unwrap().total;
+    assert_eq!(total.ranges(), vec![0..5, 6..11]);
+    assert!(!report.is_ok());
+}
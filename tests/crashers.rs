@@ -0,0 +1,179 @@
+//! Inputs that made read paths panic or abort, minimized from fuzzing with `fuzz/`.
+
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+/// Returns a stream with one block header and the given data, without end of bytes.
+fn block(type_format: u16, bytes: u64, offset: u64, data: &[u8]) -> Vec<u8> {
+    let mut res = vec![];
+    res.extend_from_slice(&type_format.to_le_bytes());
+    res.extend_from_slice(&0_u16.to_le_bytes());
+    res.extend_from_slice(&bytes.to_le_bytes());
+    res.extend_from_slice(&offset.to_le_bytes());
+    res.extend_from_slice(data);
+    res
+}
+
+type Read = dyn Fn(State<Bytes>, u16, &mut &[u8]) -> std::io::Result<()>;
+
+/// Reads the first property of a stream as arrays of several element types.
+fn read_arrays(data: &[u8]) -> Vec<std::io::Result<()>> {
+    let read = |f: &Read| {
+        let mut r = data;
+        let (state, ty, _) = State::read(&mut r)?;
+        f(state.unwrap(), ty, &mut r)
+    };
+    vec![
+        read(&|state, ty, r| Scalar::read_array(state, ty, &mut Vec::<u32>::new(), r)),
+        read(&|state, ty, r| Vector::read_array(state, ty, &mut Vec::<[f32; 3]>::new(), r)),
+        read(&|state, ty, r| Matrix::read_array(state, ty, &mut Vec::<[[u8; 2]; 2]>::new(), r)),
+    ]
+}
+
+/// Reads a stream through the frame reader and validation.
+fn read_frames(data: &[u8]) {
+    let mut frames = FrameReader::with_time(data, 0);
+    while let Ok(Some(frame)) = frames.next_frame() {
+        let _ = frame.read_array(0, &mut Vec::<u32>::new());
+    }
+    let _ = validate(&mut &data[..], &ValidateOptions::default());
+}
+
+#[test]
+fn offset_overflow() {
+    // `offset + n` overflowed for an offset near `u64::MAX`.
+    let cases = [
+        block(Type::U32.scalar().0, 4, u64::MAX, &[1; 4]),
+        block(Type::F32.vector(3).unwrap().0, 12, u64::MAX, &[1; 12]),
+        block(Type::U8.matrix(2, 2).unwrap().0, 4, u64::MAX, &[1; 4]),
+        // Growing the array to the offset overflowed its capacity.
+        block(Type::F32.vector(3).unwrap().0, 12, u64::MAX / 2, &[1; 12]),
+        block(Type::U32.scalar().0, 4, 1 << 60, &[1; 4]),
+    ];
+    for (i, data) in cases.iter().enumerate() {
+        for res in read_arrays(data) {
+            assert!(res.is_err(), "case {}", i);
+        }
+        read_frames(data);
+    }
+}
+
+#[test]
+fn huge_offset_without_data() {
+    // The array grew to the offset before the data was read,
+    // which aborted when the allocation failed.
+    let cases = [
+        block(Type::U32.scalar().0, 4, 1 << 40, &[]),
+        block(Type::F32.vector(3).unwrap().0, 12, 1 << 40, &[]),
+        block(Type::U8.matrix(2, 2).unwrap().0, 4, 1 << 40, &[]),
+    ];
+    for (i, data) in cases.iter().enumerate() {
+        for res in read_arrays(data) {
+            assert!(res.is_err(), "case {}", i);
+        }
+        read_frames(data);
+    }
+}
+
+#[test]
+fn bytes_overflow() {
+    // A block of `u64::MAX` bytes, which does not fit any element size.
+    let data = block(Type::U32.scalar().0, u64::MAX, 0, &[]);
+    for res in read_arrays(&data) {
+        assert!(res.is_err());
+    }
+    read_frames(&data);
+}
+
+#[test]
+fn truncated_everywhere() {
+    let mut data = vec![];
+    Scalar::write_array(0, &vec![1_u32, 2, 3], &mut data).unwrap();
+    Vector::write_array(1, &vec![[1.0_f32, 2.0, 3.0]], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    for len in 0..data.len() {
+        read_arrays(&data[..len]);
+        read_frames(&data[..len]);
+    }
+}
+
+#[test]
+fn codec_offset_overflow() {
+    use binpool::codec;
+
+    let cases = [
+        block(Type::U32.scalar().0, 4, u64::MAX, &[1; 4]),
+        block(Type::U32.vector(2).unwrap().0, 8, u64::MAX / 2, &[1; 8]),
+        block(Type::U32.scalar().0, 4, 1 << 60, &[1; 4]),
+    ];
+    for (i, data) in cases.iter().enumerate() {
+        let mut r = &data[..];
+        let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+        let dim = if i == 1 {2} else {1};
+        let mut res: Vec<u32> = vec![];
+        assert!(codec::read_array(ty, dim, &mut res, &mut r).is_err(), "case {}", i);
+    }
+}
+
+#[test]
+fn codec_block_header_without_data() {
+    use binpool::codec;
+
+    // A block of almost 4 GB with no data, which was allocated before decoding.
+    let data = [
+        0x02, 0x19, 0x00, 0x41, 0x00, 0x00, 0x00, 0xe1, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x25, 0xfb,
+        0x00, 0x00, 0x00, 0x6e, 0x61, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+    let mut r = &data[..];
+    let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    let mut res: Vec<u16> = vec![];
+    assert!(codec::read_array(ty, 2, &mut res, &mut r).is_err());
+    assert!(res.len() < data.len());
+}
+
+#[test]
+fn downsample_rle_bomb() {
+    // A run length encoded block expanding to about 5 * 10^17 bytes,
+    // which aborted on allocation.
+    let data = [
+        0xf7, 0xff, 0x01, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x01, 0x00, 0x40, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let opts = DownsampleOptions {
+        frame_marker: Some(1),
+        frame_step: 2,
+        instance_step: 3,
+        limits: Limits {max_block_bytes: Some(1 << 20), ..Limits::default()},
+    };
+    let err = downsample(&mut &data[..], &mut vec![], &opts).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::LimitExceeded);
+}
+
+#[test]
+fn codec_huge_offset() {
+    use binpool::codec;
+
+    // One item at an offset of 2^31 vectors, which grew the vector to 4 GiB of zeros.
+    let data = block(Type::U16.vector(2).unwrap().0, 4, 1 << 31, &[1; 4]);
+    let mut r = &data[..];
+    let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    let mut res: Vec<u16> = vec![];
+    assert!(codec::read_array_max(ty, 2, 1 << 16, &mut res, &mut r).is_err());
+    assert!(res.is_empty());
+    // Within the bound, the gap is filled with zeros.
+    let mut data = block(Type::U16.vector(2).unwrap().0, 4, 2, &[1, 0, 2, 0]);
+    data.extend_from_slice(&[0; 8]);
+    let mut r = &data[..];
+    let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    codec::read_array_max(ty, 2, 6, &mut res, &mut r).ok().unwrap();
+    assert_eq!(res, vec![0, 0, 0, 0, 1, 2]);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+const HIT: u16 = 0xfe00;
+const TRACK: u16 = HIT + 1;
+const HITS: u16 = 1;
+const MASS: u16 = 2;
+
+fn registry(hit: u32, track: Option<u32>) -> CustomFormats {
+    let mut formats = CustomFormats::new();
+    formats.register(HIT, hit, Some("channel: u32, adc: u16")).unwrap();
+    if let Some(track) = track {formats.register(TRACK, track, None).unwrap()}
+    formats
+}
+
+/// Writes a stream with custom hits and masses, with an optional registry.
+fn stream(formats: Option<&CustomFormats>) -> Vec<u8> {
+    let mut w = vec![];
+    if let Some(formats) = formats {formats.write(&mut w).unwrap()}
+    write_block(HIT, HITS, 0, &[1, 0, 0, 0, 7, 0], &mut w).unwrap();
+    Scalar::write_array(MASS, &vec![1.5_f64, 2.5], &mut w).unwrap();
+    w
+}
+
+/// Reads a stream, returning the masses and the registry of the stream.
+fn read(mut r: PoolReader<&[u8]>) -> io::Result<(Vec<f64>, CustomFormats)> {
+    r.register(MASS);
+    r.register(HITS);
+    let mut masses = vec![];
+    while let Some((state, ty, prop)) = r.next_property()? {
+        match prop {
+            MASS => Scalar::read_array(state, ty, &mut masses, &mut r)?,
+            _ => {RawBlock::read_property(state, ty, prop, &mut r)?;}
+        }
+    }
+    Ok((masses, r.file_formats().clone()))
+}
+
+#[test]
+fn registry_roundtrip() {
+    assert!(HIT >= Type::offset_custom_format());
+    let formats = registry(3, Some(1));
+    let mut data = vec![];
+    formats.write(&mut data).unwrap();
+    let (_, read) = read(PoolReader::new(&data[..])).unwrap();
+    assert_eq!(read, formats);
+    assert_eq!(read.get(HIT).unwrap(), &CustomFormat {
+        version: 3,
+        layout: Some("channel: u32, adc: u16".into()),
+    });
+    assert_eq!(read.get(TRACK).unwrap().layout, None);
+
+    // Built-in and reserved formats can not be registered.
+    let mut formats = CustomFormats::new();
+    for &format in &[0, Type::F64.scalar().0, u16::MAX] {
+        let err = formats.register(format, 1, None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    assert!(formats.is_empty());
+}
+
+#[test]
+fn matching() {
+    let formats = registry(2, Some(1));
+    let data = stream(Some(&formats));
+    // The same versions, or formats that are only registered on one side.
+    for local in &[registry(2, Some(1)), registry(2, None), CustomFormats::new()] {
+        let r = PoolReader::new(&data[..]).custom_formats(local.clone());
+        let (masses, file) = read(r).unwrap();
+        assert_eq!(masses, vec![1.5, 2.5]);
+        assert_eq!(file, formats);
+    }
+    let mut local = registry(2, None);
+    local.register(TRACK + 1, 9, None).unwrap();
+    assert!(read(PoolReader::new(&data[..]).custom_formats(local)).is_ok());
+}
+
+#[test]
+fn mismatching() {
+    let data = stream(Some(&registry(2, Some(1))));
+    let r = PoolReader::new(&data[..]).custom_formats(registry(2, Some(4)));
+    let err = read(r).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::CustomFormatVersionMismatch);
+    assert_eq!(err.versions(), Some((TRACK, 1, 4)));
+    assert!(format!("{}", err).contains("version 1 in stream, 4 registered"));
+
+    // The callback handles a migration instead.
+    let calls = Rc::new(RefCell::new(vec![]));
+    let c = calls.clone();
+    let r = PoolReader::new(&data[..])
+        .custom_formats(registry(3, Some(4)))
+        .on_format_mismatch(move |format, file, local| {
+            c.borrow_mut().push((format, file.version, local.version));
+            Ok(())
+        });
+    let (masses, _) = read(r).unwrap();
+    assert_eq!(masses, vec![1.5, 2.5]);
+    assert_eq!(*calls.borrow(), vec![(HIT, 2, 3), (TRACK, 1, 4)]);
+
+    // The callback can stop reading.
+    let r = PoolReader::new(&data[..])
+        .custom_formats(registry(3, None))
+        .on_format_mismatch(|_, _, _| Err(io::ErrorKind::Unsupported.into()));
+    assert_eq!(read(r).unwrap_err().kind(), io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn missing_registry() {
+    let data = stream(None);
+    let r = PoolReader::new(&data[..])
+        .custom_formats(registry(5, Some(5)))
+        .on_format_mismatch(|_, _, _| panic!("no registry"));
+    let (masses, file) = read(r).unwrap();
+    assert_eq!(masses, vec![1.5, 2.5]);
+    assert!(file.is_empty());
+
+    // The registry block is the only difference between the streams.
+    let with = stream(Some(&registry(5, None)));
+    let mut registry_block = vec![];
+    registry(5, None).write(&mut registry_block).unwrap();
+    assert_eq!(&with[registry_block.len()..], &data[..]);
+}
@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::{Cursor, Write};
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+const MASS: u16 = 3;
+const CHARGE: u16 = 4;
+
+/// Writes 100 frames where masses never change and charges change every 10 frames.
+fn record<W: Write>(w: &mut W) {
+    for step in 0..100 {
+        Time::write(TIME, step as f64, w).unwrap();
+        let pos: Vec<[f32; 3]> = (0..50).map(|i| [step as f32, i as f32, 0.0]).collect();
+        Vector::write_array(POSITION, &pos, w).unwrap();
+        Scalar::write_array(MASS, &vec![1.5_f64; 50], w).unwrap();
+        Scalar::write_array(CHARGE, &vec![(step / 10) as i8; 50], w).unwrap();
+    }
+}
+
+fn plain() -> Vec<u8> {
+    let mut w = vec![];
+    record(&mut w);
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+fn dedup(interval: u64, compare_bytes: bool) -> (Vec<u8>, u64) {
+    let mut w = DedupWriter::new(vec![]).compare_bytes(compare_bytes);
+    if interval > 0 {w = w.keyframes(TIME, interval)}
+    record(&mut w);
+    let skipped = w.skipped();
+    (w.finish().unwrap(), skipped)
+}
+
+type Values = (Vec<[f32; 3]>, Vec<f64>, Vec<i8>);
+
+/// Replays frames, keeping values until they are overwritten.
+fn replay(data: &[u8]) -> Vec<Values> {
+    let mut r = FrameReader::with_time(data, TIME);
+    let mut state: Values = (vec![], vec![], vec![]);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        frame.read_array(POSITION, &mut state.0).unwrap();
+        frame.read_array(MASS, &mut state.1).unwrap();
+        frame.read_array(CHARGE, &mut state.2).unwrap();
+        res.push(state.clone());
+    }
+    res
+}
+
+#[test]
+fn shrinks_with_identical_replay() {
+    let plain = plain();
+    let expected = replay(&plain);
+    assert_eq!(expected.len(), 100);
+    for &compare_bytes in &[false, true] {
+        let (data, skipped) = dedup(0, compare_bytes);
+        // Masses are written once and charges once per 10 frames.
+        assert_eq!(skipped, 99 + 90);
+        let saved = plain.len() - data.len();
+        assert_eq!(saved, 99 * (28 + 400) + 90 * (28 + 50));
+        assert_eq!(replay(&data), expected);
+    }
+}
+
+#[test]
+fn keyframes() {
+    let (data, skipped) = dedup(25, false);
+    // Frames 0, 25, 50 and 75 write every property.
+    assert_eq!(skipped, 96 + 88);
+    assert_eq!(replay(&data), replay(&plain()));
+
+    let mut cursor = Cursor::new(&data[..]);
+    let index = Index::build(&mut cursor, TIME, Some(TIME)).unwrap();
+    assert_eq!(index.frame_count(), 100);
+    for &(n, written) in &[(50, true), (51, false), (60, true)] {
+        let cursor_at = index.seek_frame(n, &mut cursor).unwrap();
+        let mut r = FrameReader::with_time(&mut cursor, TIME);
+        r.resume(&cursor_at).unwrap();
+        let frame = r.next_frame().unwrap().unwrap();
+        let mut mass: Vec<f64> = vec![];
+        assert_eq!(frame.read_array(MASS, &mut mass).unwrap(), n % 25 == 0, "frame {}", n);
+        let mut charge: Vec<i8> = vec![];
+        assert_eq!(frame.read_array(CHARGE, &mut charge).unwrap(), written, "frame {}", n);
+        if written {assert_eq!(charge, vec![(n / 10) as i8; 50])}
+    }
+}
+
+#[test]
+fn offsets_are_compared() {
+    let mut w = DedupWriter::new(vec![]);
+    Scalar::write_array(MASS, &vec![1.0_f32; 2], &mut w).unwrap();
+    let block = RawBlock {
+        type_format: Type::F32.scalar().0,
+        property_id: MASS,
+        offset: 1,
+        data: vec![0, 0, 128, 63, 0, 0, 128, 63],
+    };
+    RawBlock::write_property(block.type_format, MASS, &[block], &mut w).unwrap();
+    Scalar::write_array(MASS, &vec![1.0_f32; 2], &mut w).unwrap();
+    assert_eq!(w.skipped(), 0);
+    assert!(w.finish().is_ok());
+}
@@ -0,0 +1,216 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const ENERGY: u16 = 2;
+
+/// Encodes items as a block starting at an instance.
+fn block<T: Element>(ty: u16, prop: u16, offset: u64, items: &[T]) -> RawBlock {
+    let mut data = vec![];
+    for x in items {x.write_element(&mut data).unwrap()}
+    RawBlock {type_format: ty, property_id: prop, offset, data}
+}
+
+fn position_format() -> u16 {
+    Type::F32.vector(3).unwrap().0
+}
+
+fn energy_format() -> u16 {
+    Type::F64.scalar().0
+}
+
+/// Writes frames of particles with ids from a global counter,
+/// in blocks of runs of consecutive ids.
+fn stream(frames: usize) -> Vec<u8> {
+    let mut rng = Rng::new(183);
+    let mut w = FrameWriter::new(vec![], TIME);
+    let mut counter = 1 << 40;
+    for i in 0..frames {
+        w.begin_frame(i as f64).unwrap();
+        let mut positions = vec![];
+        let mut energies = vec![];
+        for _ in 0..1 + rng.below(4) {
+            let n = 1 + rng.below(6);
+            let items: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+            positions.push(block(position_format(), POSITION, counter, &items));
+            // Some particles have no energy.
+            let skip = rng.below(n);
+            let items: Vec<f64> = (skip..n).map(|_| rng.unit()).collect();
+            energies.push(block(energy_format(), ENERGY, counter + skip, &items));
+            counter += n + rng.below(1 << 20);
+        }
+        RawBlock::write_property(position_format(), POSITION, &positions, &mut w).unwrap();
+        RawBlock::write_property(energy_format(), ENERGY, &energies, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// Returns the items of a property by instance id, for every frame.
+///
+/// The ids of the instances are translated with a function.
+fn items<T: Element>(
+    data: &[u8],
+    prop: u16,
+    id: &dyn Fn(u64) -> u64
+) -> Vec<BTreeMap<u64, T>> {
+    let mut fr = FrameReader::with_time(data, TIME);
+    let mut res = vec![];
+    while let Some(frame) = fr.next_frame().unwrap() {
+        let mut items = BTreeMap::new();
+        for block in frame.blocks.iter().filter(|b| b.property_id == prop) {
+            for (i, x) in block.decode::<T>().unwrap().into_iter().enumerate() {
+                assert!(items.insert(id(block.offset + i as u64), x).is_none());
+            }
+        }
+        res.push(items);
+    }
+    res
+}
+
+/// Returns the map written to a compacted stream.
+fn read_map(data: &[u8]) -> IdMap {
+    let mut r = data;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        if ty == ID_MAP_FORMAT {return IdMap::decode(&blocks[0]).unwrap()}
+    }
+    panic!("no map");
+}
+
+#[test]
+fn replay_through_map() {
+    let data = stream(30);
+    let map = IdMap::scan(&mut &data[..], &[POSITION, ENERGY]).unwrap();
+    // The ids are sorted.
+    assert!(map.ids().windows(2).all(|w| w[0] < w[1]));
+    assert!(map.len() > 100);
+    let mut out = vec![];
+    compact_copy(&mut &data[..], &mut out, &map).unwrap();
+    assert!(out.len() < data.len() + 8 * map.len() as usize + 100);
+
+    // The original ids are recovered from the map in the output.
+    let recovered = read_map(&out);
+    assert_eq!(recovered.ids(), map.ids());
+    let sparse = |i: u64| recovered.sparse(i).unwrap();
+    assert_eq!(items::<[f32; 3]>(&out, POSITION, &sparse), items(&data, POSITION, &|id| id));
+    assert_eq!(items::<f64>(&out, ENERGY, &sparse), items(&data, ENERGY, &|id| id));
+
+    // Dense indices fit in dense arrays.
+    let mut fr = FrameReader::with_time(&out[..], TIME);
+    while let Some(frame) = fr.next_frame().unwrap() {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        frame.read_array(POSITION, &mut positions).unwrap();
+        assert!(positions.len() as u64 <= map.len());
+        let time: Vec<f64> = frame.blocks.iter()
+            .filter(|b| b.property_id == TIME)
+            .flat_map(|b| b.decode::<f64>().unwrap())
+            .collect();
+        assert_eq!(time, vec![frame.number as f64]);
+    }
+}
+
+/// Writes a little endian stream in big endian, with the given alignment.
+fn big_endian(data: &[u8], alignment: u16) -> Vec<u8> {
+    let opts = WriteOptions::default().allow_reserved(true).alignment(alignment);
+    let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big).with_options(opts);
+    w.write_all(&data[..data.len() - 2]).unwrap();
+    w.finish().unwrap()
+}
+
+#[test]
+fn big_endian_stream() {
+    let data = stream(30);
+    let map = IdMap::scan(&mut &data[..], &[POSITION, ENERGY]).unwrap();
+    let mut out = vec![];
+    compact_copy(&mut &data[..], &mut out, &map).unwrap();
+
+    let data = big_endian(&data, 64);
+    assert_eq!(IdMap::scan(&mut &data[..], &[POSITION, ENERGY]).unwrap(), map);
+    let mut be_out = vec![];
+    compact_copy(&mut &data[..], &mut be_out, &map).unwrap();
+    // The output keeps the byte order, without the padding of the stream.
+    assert!(be_out == big_endian(&out, 0));
+    let mut r = PoolReader::new(&be_out[..]).unknown(Unknown::Skip);
+    while r.next_property().unwrap().is_some() {}
+    assert_eq!(r.header(), Some(Header {endian: Endian::Big, ..Header::default()}));
+}
+
+#[test]
+fn split_many_times() {
+    let n = 100_u64;
+    let base = u64::MAX - 1000;
+    let energies: Vec<f64> = (0..n).map(|i| i as f64).collect();
+    let mut data = vec![];
+    RawBlock::write_property(energy_format(), ENERGY,
+                             &[block(energy_format(), ENERGY, base, &energies)], &mut data)
+        .unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+
+    // Shuffled ids map consecutive instances to indices that are rarely consecutive.
+    let mut ids: Vec<u64> = (0..n).map(|i| base + i).collect();
+    let mut rng = Rng::new(1830);
+    rng.shuffle(&mut ids);
+    let map = IdMap::from_ids(&ids).unwrap();
+    let mut out = vec![];
+    compact_copy(&mut &data[..], &mut out, &map).unwrap();
+
+    let mut r = &out[..];
+    let mut blocks = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let b = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        if prop == ENERGY {blocks = b}
+    }
+    let runs = ids.windows(2).filter(|w| w[1] != w[0] + 1).count() + 1;
+    assert_eq!(blocks.len(), runs);
+    assert!(runs > 80);
+    let mut total = 0;
+    for b in &blocks {
+        for (i, x) in b.decode::<f64>().unwrap().into_iter().enumerate() {
+            let id = map.sparse(b.offset + i as u64).unwrap();
+            assert_eq!(x, (id - base) as f64);
+            total += 1;
+        }
+    }
+    assert_eq!(total, n);
+
+    // Reversed ids split the block into single items.
+    let ids: Vec<u64> = (0..n).rev().map(|i| base + i).collect();
+    let mut out = vec![];
+    compact_copy(&mut &data[..], &mut out, &IdMap::from_ids(&ids).unwrap()).unwrap();
+    let mut r = &out[..];
+    let mut count = 0;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let b = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        if prop == ENERGY {count = b.len()}
+    }
+    assert_eq!(count, n as usize);
+}
+
+#[test]
+fn unmapped_ids() {
+    let data = stream(3);
+    let map = IdMap::scan(&mut &data[..], &[POSITION]).unwrap();
+    let mut out = vec![];
+    // Energies are not remapped.
+    compact_copy(&mut &data[..], &mut out, &map).unwrap();
+    assert_eq!(items::<f64>(&out, ENERGY, &|id| id), items(&data, ENERGY, &|id| id));
+
+    // An id that is not in the map.
+    let map = IdMap::from_ids(&map.ids()[1..]).unwrap().properties(&[POSITION]);
+    let err = compact_copy(&mut &data[..], &mut vec![], &map).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(POSITION)));
+    assert_eq!(err.instance(), Some(1 << 40));
+
+    let err = IdMap::from_ids(&[3, 5, 3]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
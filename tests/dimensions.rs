@@ -0,0 +1,81 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+/// A vector with more components than the built-in formats support.
+#[derive(Clone, Copy)]
+struct Big([f32; 100]);
+
+impl Default for Big {
+    fn default() -> Big {Big([0.0; 100])}
+}
+
+impl Vector for Big {
+    type Scalar = f32;
+
+    fn dim() -> usize {100}
+    fn get(&self, ind: usize) -> &f32 {&self.0[ind]}
+    fn set(&mut self, ind: usize, val: f32) {self.0[ind] = val}
+}
+
+/// A matrix with more rows than the built-in formats support.
+#[derive(Clone, Copy)]
+struct Tall([[u8; 2]; 81]);
+
+impl Default for Tall {
+    fn default() -> Tall {Tall([[0; 2]; 81])}
+}
+
+impl Matrix for Tall {
+    type Scalar = u8;
+
+    fn dim() -> [usize; 2] {[81, 2]}
+    fn get(&self, row: usize, col: usize) -> &u8 {&self.0[row][col]}
+    fn set(&mut self, row: usize, col: usize, val: u8) {self.0[row][col] = val}
+}
+
+/// A vector without components.
+#[derive(Clone, Copy, Default)]
+struct Empty;
+
+impl Vector for Empty {
+    type Scalar = u16;
+
+    fn dim() -> usize {0}
+    fn get(&self, _: usize) -> &u16 {unreachable!()}
+    fn set(&mut self, _: usize, _: u16) {unreachable!()}
+}
+
+fn check(res: std::io::Result<()>, rows: u64, cols: u64) {
+    let err = res.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::DimensionOutOfRange);
+    assert_eq!(err.dimensions(), Some((rows, cols)));
+    assert_eq!(err.range(), Some((1, Type::max_matrix_dim() as u64 + 1)));
+    assert!(err.to_string().contains(&format!("{}x{}", rows, cols)), "{}", err);
+}
+
+#[test]
+fn write_errors() {
+    let mut w = vec![];
+    check(Big::default().write_property(1, &mut w), 1, 100);
+    check(Vector::write_array(1, &vec![Big::default(); 3], &mut w), 1, 100);
+    check(Tall::default().write_property(2, &mut w), 81, 2);
+    check(Matrix::write_array(2, &vec![Tall::default()], &mut w), 81, 2);
+    check(Vector::write_array(3, &vec![Empty], &mut w), 1, 0);
+    // Nothing was written.
+    assert!(w.is_empty());
+}
+
+#[test]
+fn read_errors() {
+    let mut data = vec![];
+    Vector::write_array(1, &vec![[1.0_f32; 4]], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let res = Vector::read_array(state.unwrap(), ty, &mut Vec::<Big>::new(), &mut r);
+    check(res, 1, 100);
+}
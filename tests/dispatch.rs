@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+const ENERGY: u16 = 3;
+const OTHER: u16 = 4;
+
+mod physics {
+    use binpool::Dispatcher;
+
+    #[derive(Default)]
+    pub struct Physics {
+        pub time: f32,
+        pub positions: Vec<(u64, [f32; 3])>,
+    }
+
+    pub fn register<'a>(d: &mut Dispatcher<'a>, physics: &'a mut Physics) {
+        // Both handlers borrow the state, so split the borrow.
+        let Physics {ref mut time, ref mut positions} = *physics;
+        d.on_scalar(super::TIME, move |t: f32| {
+            *time = t;
+            Ok(())
+        });
+        d.on_array(super::POSITION, move |offset, items: &[[f32; 3]]| {
+            positions.extend(items.iter().enumerate().map(|(i, &p)| (offset + i as u64, p)));
+            Ok(())
+        });
+    }
+}
+
+mod diagnostics {
+    use binpool::Dispatcher;
+
+    #[derive(Default)]
+    pub struct Diagnostics {
+        pub energy: Vec<f64>,
+        pub unknown: Vec<(u16, usize)>,
+    }
+
+    pub fn register<'a>(d: &mut Dispatcher<'a>, diagnostics: &'a mut Diagnostics) {
+        let Diagnostics {ref mut energy, ref mut unknown} = *diagnostics;
+        d.on_array(super::ENERGY, move |_, items: &[f64]| {
+            energy.extend_from_slice(items);
+            Ok(())
+        });
+        d.on_default(move |block| {
+            unknown.push((block.property_id, block.data.len()));
+            Ok(())
+        });
+    }
+}
+
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    0.5_f32.write_property(TIME, &mut w).unwrap();
+    Vector::write_array(POSITION, &vec![[1.0_f32, 2.0, 3.0], [4.0, 5.0, 6.0]], &mut w).unwrap();
+    Scalar::write_array(ENERGY, &vec![10.0_f64, 20.0], &mut w).unwrap();
+    Scalar::write_array(OTHER, &vec![1_u8; 3], &mut w).unwrap();
+    let bytes: Vec<u8> = [7.0_f32, 8.0, 9.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+    write_block(Type::F32.vector(3).unwrap().0, POSITION, 5, &bytes, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+#[test]
+fn handlers_from_two_modules() {
+    let data = stream();
+    let mut p = physics::Physics::default();
+    let mut diag = diagnostics::Diagnostics::default();
+    {
+        let mut d = Dispatcher::new();
+        physics::register(&mut d, &mut p);
+        diagnostics::register(&mut d, &mut diag);
+        d.run(&mut &data[..]).unwrap();
+    }
+    assert_eq!(p.time, 0.5);
+    assert_eq!(p.positions, vec![
+        (0, [1.0, 2.0, 3.0]),
+        (1, [4.0, 5.0, 6.0]),
+        (5, [7.0, 8.0, 9.0]),
+    ]);
+    assert_eq!(diag.energy, vec![10.0, 20.0]);
+    assert_eq!(diag.unknown, vec![(OTHER, 3)]);
+}
+
+#[test]
+fn handler_error_has_property() {
+    let data = stream();
+    let mut d = Dispatcher::new();
+    d.on_raw(ENERGY, |_| Err(std::io::ErrorKind::Other.into()));
+    let err = d.run(&mut &data[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    assert_eq!(Error::from_io(&err).property(), Some(ENERGY));
+}
+
+#[test]
+fn scalar_with_several_items() {
+    let data = stream();
+    let mut d = Dispatcher::new();
+    d.on_scalar(ENERGY, |_: f64| Ok(()));
+    let err = d.run(&mut &data[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(Error::from_io(&err).property(), Some(ENERGY));
+}
@@ -0,0 +1,244 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const MARKER: u16 = 1;
+const ARRAY: u16 = 3;
+const SINGLE: u16 = 4;
+
+fn downsampled(input: &[u8], opts: &DownsampleOptions) -> Vec<u8> {
+    let mut out = vec![];
+    downsample(&mut &input[..], &mut out, opts).unwrap();
+    out
+}
+
+type Property = (u16, u16, Vec<RawBlock>);
+
+fn properties(mut r: &[u8]) -> Vec<Property> {
+    let mut res = vec![];
+    while !r.is_empty() {
+        let (state, ty, prop) = State::read(&mut r).unwrap();
+        let state = match state {
+            Some(state) => state,
+            None => break,
+        };
+        res.push((ty, prop, RawBlock::read_property(state, ty, prop, &mut r).unwrap()));
+    }
+    res
+}
+
+fn instances(k: u64) -> DownsampleOptions {
+    DownsampleOptions {instance_step: k, ..DownsampleOptions::default()}
+}
+
+#[test]
+fn example_scalar() {
+    let input = std::fs::read("assets/test-scalar.pool").unwrap();
+    let out = downsampled(&input, &instances(2));
+    let mut r = &out[..];
+    let mut data: Vec<f32> = vec![];
+    let mut val = 0_u8;
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        match prop {
+            ARRAY => Scalar::read_array(state, ty, &mut data, &mut r).unwrap(),
+            SINGLE => val.read_property(state, ty, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    assert_eq!(data, vec![1.0, 3.0]);
+    assert_eq!(val, 10);
+}
+
+#[test]
+fn example_vector() {
+    let input = std::fs::read("assets/test-vector.pool").unwrap();
+    let out = downsampled(&input, &instances(2));
+    let mut r = &out[..];
+    let mut data: Vec<[f32; 2]> = vec![];
+    let mut val = [0_u8; 2];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        match prop {
+            ARRAY => Vector::read_array(state, ty, &mut data, &mut r).unwrap(),
+            SINGLE => val.read_property(state, ty, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    assert_eq!(data, vec![[1.0, 2.0]]);
+    assert_eq!(val, [10; 2]);
+}
+
+#[test]
+fn example_matrix() {
+    let input = std::fs::read("assets/test-matrix.pool").unwrap();
+    let out = downsampled(&input, &instances(3));
+    let mut r = &out[..];
+    let mut data: Vec<[[f32; 2]; 2]> = vec![];
+    let mut val = [[0_u8; 2]; 2];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        match prop {
+            ARRAY => Matrix::read_array(state, ty, &mut data, &mut r).unwrap(),
+            SINGLE => val.read_property(state, ty, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    assert_eq!(data, vec![[[1.0, 2.0], [3.0, 4.0]]]);
+    assert_eq!(val, [[10; 2]; 2]);
+}
+
+#[test]
+fn offsets_map_to_every_kth_instance() {
+    let items: Vec<u32> = (0..20).collect();
+    let mut input = vec![];
+    let data: Vec<u8> = items.iter().flat_map(|x| x.to_le_bytes()).collect();
+    write_block(Type::U32.scalar().0, ARRAY, 7, &data, &mut input).unwrap();
+    State::new().end_type_formats(&mut input).unwrap();
+
+    let out = downsampled(&input, &instances(3));
+    let props = properties(&out);
+    assert_eq!(props.len(), 1);
+    let block = &props[0].2[0];
+    // Instances 7..27 in the input, of which 9, 12, .., 24 are kept as 3, 4, .., 8.
+    assert_eq!(block.offset, 3);
+    assert_eq!(block.decode::<u32>().unwrap(), vec![2, 5, 8, 11, 14, 17]);
+}
+
+#[test]
+fn every_kth_frame() {
+    let mut input = vec![];
+    for frame in 0..5_u32 {
+        (frame as f64).write_property(MARKER, &mut input).unwrap();
+        Scalar::write_array(ARRAY, &vec![frame; 4], &mut input).unwrap();
+    }
+    State::new().end_type_formats(&mut input).unwrap();
+
+    let opts = DownsampleOptions {
+        frame_marker: Some(MARKER),
+        frame_step: 2,
+        ..DownsampleOptions::default()
+    };
+    let props = properties(&downsampled(&input, &opts));
+    let frames: Vec<u32> = props.iter()
+        .filter(|p| p.1 == ARRAY)
+        .map(|p| p.2[0].decode::<u32>().unwrap()[0])
+        .collect();
+    assert_eq!(frames, vec![0, 2, 4]);
+    assert_eq!(props.len(), 6);
+}
+
+#[test]
+fn invalid_options() {
+    let input = [0_u8, 0];
+    for opts in &[
+        instances(0),
+        DownsampleOptions {frame_step: 0, ..DownsampleOptions::default()},
+        DownsampleOptions {frame_step: 2, ..DownsampleOptions::default()},
+    ] {
+        let err = downsample(&mut &input[..], &mut vec![], opts).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn run_length_encoded() {
+    let items = vec![5_u16; 100];
+    let mut input = vec![];
+    write_rle(ARRAY, &items, &mut input).unwrap();
+    State::new().end_type_formats(&mut input).unwrap();
+    assert_eq!(properties(&input)[0].0, RLE_FORMAT);
+
+    let props = properties(&downsampled(&input, &instances(10)));
+    assert_eq!(props[0].0, Type::U16.scalar().0);
+    assert_eq!(props[0].2[0].decode::<u16>().unwrap(), vec![5; 10]);
+}
+
+#[test]
+fn delta_encoded() {
+    let items: Vec<i64> = (0..50).collect();
+    let mut input = vec![];
+    write_varint_delta(ARRAY, &items, &mut input).unwrap();
+    State::new().end_type_formats(&mut input).unwrap();
+
+    let props = properties(&downsampled(&input, &instances(5)));
+    assert_eq!(props[0].0, Type::I64.scalar().0);
+    assert_eq!(props[0].2[0].decode::<i64>().unwrap(), vec![0, 5, 10, 15, 20, 25, 30, 35, 40, 45]);
+}
+
+#[test]
+fn presence_bitmap() {
+    let items: Vec<Option<u8>> = (0..20).map(|i| if i % 3 == 0 {None} else {Some(i)}).collect();
+    let mut input = vec![];
+    write_optional_array(ARRAY, &items, &mut input).unwrap();
+    State::new().end_type_formats(&mut input).unwrap();
+
+    let out = downsampled(&input, &instances(2));
+    let mut r = &out[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let mut arr = vec![];
+    read_optional_array(state.unwrap(), ty, prop, &mut arr, &mut r).unwrap();
+    let expected: Vec<Option<u8>> = items.iter().cloned().step_by(2).collect();
+    assert_eq!(arr, expected);
+}
+
+#[test]
+fn xor_deltas_are_rejected() {
+    let mut input = vec![];
+    write_block(XOR_FORMAT, ARRAY, 0, &[0, 0, 0], &mut input).unwrap();
+    State::new().end_type_formats(&mut input).unwrap();
+
+    let err = downsample(&mut &input[..], &mut vec![], &instances(2)).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+
+    // Kept as is when nothing is dropped.
+    assert_eq!(downsampled(&input, &DownsampleOptions::default()), input);
+}
+
+/// Reads the properties of a stream in any byte order, without the file header.
+fn converted(data: &[u8]) -> (Option<Header>, Vec<Property>) {
+    let mut r = PoolReader::new(data);
+    let mut res = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        if ty != HEADER_FORMAT {res.push((ty, prop, blocks))}
+    }
+    (r.header(), res)
+}
+
+#[test]
+fn big_endian() {
+    let write = |endian| {
+        let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+        for frame in 0..5_u32 {
+            (frame as f64).write_property(MARKER, &mut w).unwrap();
+            let items: Vec<u32> = (0..10).map(|i| frame * 100 + i).collect();
+            Scalar::write_array(ARRAY, &items, &mut w).unwrap();
+            Vector::write_array(SINGLE, &vec![[frame as u16, 7]; 3], &mut w).unwrap();
+        }
+        w.finish().unwrap()
+    };
+    let (big, little) = (write(Endian::Big), write(Endian::Little));
+    assert_eq!(downsampled(&big, &DownsampleOptions::default()), big);
+
+    let opts = DownsampleOptions {
+        frame_marker: Some(MARKER),
+        frame_step: 2,
+        instance_step: 2,
+        ..DownsampleOptions::default()
+    };
+    let out = downsampled(&big, &opts);
+    let (header, props) = converted(&out);
+    // The output keeps the byte order of the input.
+    assert_eq!(header.map(|h| h.endian), Some(Endian::Big));
+    assert_eq!(props, converted(&downsampled(&little, &opts)).1);
+    let frames: Vec<Vec<u32>> = props.iter()
+        .filter(|p| p.1 == ARRAY)
+        .map(|p| p.2[0].decode::<u32>().unwrap())
+        .collect();
+    assert_eq!(frames, vec![
+        vec![0, 2, 4, 6, 8],
+        vec![200, 202, 204, 206, 208],
+        vec![400, 402, 404, 406, 408],
+    ]);
+    assert!(validate(&mut &out[..], &ValidateOptions::default()).unwrap().is_ok());
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const VELOCITY: u16 = 2;
+
+fn block(offset: u64, items: &[f32]) -> RawBlock {
+    RawBlock {
+        type_format: Type::F32.scalar().0,
+        property_id: VELOCITY,
+        offset,
+        data: items.iter().flat_map(|x| x.to_le_bytes()).collect(),
+    }
+}
+
+/// Writes two frames, where the second one has two property headers for velocity
+/// when `duplicate` is set, or two blocks under one property header otherwise.
+fn stream(duplicate: bool) -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    Scalar::write_array(VELOCITY, &vec![1.0_f32, 2.0, 3.0], &mut w).unwrap();
+    w.begin_frame(1.0).unwrap();
+    let (a, b) = (block(0, &[4.0]), block(2, &[6.0]));
+    if duplicate {
+        RawBlock::write_property(a.type_format, VELOCITY, &[a], &mut w).unwrap();
+        RawBlock::write_property(b.type_format, VELOCITY, &[b], &mut w).unwrap();
+    } else {
+        RawBlock::write_property(a.type_format, VELOCITY, &[a, b], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// Reads the velocity of the last frame, starting from the first frame.
+fn read(
+    data: &[u8],
+    policy: DuplicatePolicy
+) -> std::io::Result<(Vec<f32>, Vec<DuplicateProperty>)> {
+    let warnings = Rc::new(RefCell::new(vec![]));
+    let w = warnings.clone();
+    let mut r = FrameReader::with_time(data, TIME)
+        .on_duplicate(policy)
+        .duplicate_warning(move |d| w.borrow_mut().push(d));
+    let mut velocity: Vec<f32> = vec![];
+    while let Some(frame) = r.next_frame()? {
+        frame.read_array(VELOCITY, &mut velocity)?;
+    }
+    let warnings = warnings.borrow().clone();
+    Ok((velocity, warnings))
+}
+
+fn report(data: &[u8]) -> Report {
+    let opts = ValidateOptions {frame_marker: Some(TIME), ..ValidateOptions::default()};
+    validate(&mut &data[..], &opts).unwrap()
+}
+
+#[test]
+fn range_updates_are_not_duplicates() {
+    let data = stream(false);
+    for &policy in &[
+        DuplicatePolicy::Error,
+        DuplicatePolicy::Warn,
+        DuplicatePolicy::KeepFirst,
+        DuplicatePolicy::KeepLast,
+    ] {
+        assert_eq!(read(&data, policy).unwrap(), (vec![4.0, 2.0, 6.0], vec![]));
+    }
+    let report = report(&data);
+    assert!(report.duplicates.is_empty());
+    assert!(report.is_ok());
+}
+
+#[test]
+fn duplicate_headers() {
+    let data = stream(true);
+    let duplicate = DuplicateProperty {frame: 1, property_id: VELOCITY};
+
+    let err = read(&data, DuplicatePolicy::Error).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(Error::from_io(&err).property(), Some(VELOCITY));
+
+    assert_eq!(read(&data, DuplicatePolicy::Warn).unwrap(), (vec![4.0, 2.0, 6.0], vec![duplicate]));
+    assert_eq!(read(&data, DuplicatePolicy::KeepFirst).unwrap(), (vec![4.0, 2.0, 3.0], vec![]));
+    assert_eq!(read(&data, DuplicatePolicy::KeepLast).unwrap(), (vec![1.0, 2.0, 6.0], vec![]));
+
+    let report = report(&data);
+    assert_eq!(report.duplicates, vec![duplicate]);
+    assert!(!report.is_ok());
+}
@@ -0,0 +1,198 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::Cursor;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+
+/// The header property, which is little endian in both byte orders.
+const HEADER_LE: [u8; 40] = [
+    0xfe, 0xff, 0xff, 0xff,
+    0x0c, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    b'b', b'i', b'n', b'p', b'o', b'o', b'l', 0, 0x01, 0x00, 0x00, 0x00,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// The header property with the big endian flag set.
+const HEADER_BE: [u8; 40] = [
+    0xfe, 0xff, 0xff, 0xff,
+    0x0c, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    b'b', b'i', b'n', b'p', b'o', b'o', b'l', 0, 0x01, 0x00, 0x01, 0x00,
+    0, 0, 0, 0, 0, 0, 0, 0,
+];
+
+/// Time 0.5 as `f64`, then position `[1, 2]` as a vector of two `u32`, then end of stream.
+const BODY_LE: [u8; 74] = [
+    0x01, 0xe1, 0x01, 0x00,
+    0x08, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0xe0, 0x3f,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0x02, 0x32, 0x02, 0x00,
+    0x08, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0x01, 0, 0, 0, 0x02, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+
+const BODY_BE: [u8; 74] = [
+    0xe1, 0x01, 0x00, 0x01,
+    0, 0, 0, 0, 0, 0, 0, 0x08,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0x3f, 0xe0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0x32, 0x02, 0x00, 0x02,
+    0, 0, 0, 0, 0, 0, 0, 0x08,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0x01, 0, 0, 0, 0x02,
+    0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+
+fn write(endian: Endian, footer: bool) -> Vec<u8> {
+    let mut w = PoolWriter::new(vec![]).with_endianness(endian).with_footer_index(footer);
+    Time::write(TIME, 0.5, &mut w).unwrap();
+    Vector::write_array(POSITION, &vec![[1_u32, 2]], &mut w).unwrap();
+    w.finish().unwrap()
+}
+
+fn golden(endian: Endian) -> Vec<u8> {
+    match endian {
+        Endian::Little => [&HEADER_LE[..], &BODY_LE[..]].concat(),
+        Endian::Big => [&HEADER_BE[..], &BODY_BE[..]].concat(),
+    }
+}
+
+#[test]
+fn golden_bytes() {
+    assert_eq!(write(Endian::Little, false), golden(Endian::Little));
+    assert_eq!(write(Endian::Big, false), golden(Endian::Big));
+}
+
+#[test]
+fn read_golden_bytes() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = golden(endian);
+        let mut r = PoolReader::new(&data[..]);
+        r.register(TIME);
+        r.register(POSITION);
+        let (state, ty, prop) = r.next_property().unwrap().unwrap();
+        assert_eq!(prop, TIME);
+        let mut t = 0.0_f64;
+        t.read_property(state, ty, &mut r).unwrap();
+        assert_eq!(t, 0.5);
+        let (state, ty, prop) = r.next_property().unwrap().unwrap();
+        assert_eq!(prop, POSITION);
+        let mut pos: Vec<[u32; 2]> = vec![];
+        Vector::read_array(state, ty, &mut pos, &mut r).unwrap();
+        assert_eq!(pos, vec![[1, 2]]);
+        assert!(r.next_property().unwrap().is_none());
+        assert_eq!(r.header().map(|h| h.endian), Some(endian));
+    }
+}
+
+#[test]
+fn all_scalar_types() {
+    fn write_all<W: std::io::Write>(w: &mut W) {
+        Scalar::write_array(1, &vec![0x12_u8], w).unwrap();
+        Scalar::write_array(2, &vec![0x1234_u16], w).unwrap();
+        Scalar::write_array(3, &vec![0x1234_5678_u32], w).unwrap();
+        Scalar::write_array(4, &vec![0x1234_5678_9abc_def0_u64], w).unwrap();
+        Scalar::write_array(5, &vec![-2_i8], w).unwrap();
+        Scalar::write_array(6, &vec![-2_i16], w).unwrap();
+        Scalar::write_array(7, &vec![-2_i32], w).unwrap();
+        Scalar::write_array(8, &vec![-2_i64], w).unwrap();
+        Scalar::write_array(9, &vec![1.5_f32], w).unwrap();
+        Scalar::write_array(10, &vec![-1.5_f64], w).unwrap();
+    }
+    let mut le = PoolWriter::new(vec![]);
+    write_all(&mut le);
+    let le = le.finish().unwrap();
+    let mut be = PoolWriter::new(vec![]).with_endianness(Endian::Big);
+    write_all(&mut be);
+    let be = be.finish().unwrap();
+
+    // Every field after the header is reversed: type format, property id,
+    // bytes, offset, each scalar of the data and the end of bytes.
+    let mut expected = HEADER_BE.to_vec();
+    let mut rest = &le[HEADER_LE.len()..];
+    let mut take = |n: usize| {
+        let mut field = rest[..n].to_vec();
+        field.reverse();
+        rest = &rest[n..];
+        field
+    };
+    for size in &[1, 2, 4, 8, 1, 2, 4, 8, 4, 8] {
+        for n in &[2, 2, 8, 8, *size, 8] {
+            expected.extend(take(*n));
+        }
+    }
+    expected.extend(take(2));
+    assert_eq!(be, expected);
+}
+
+#[test]
+fn validate_both() {
+    let opts = ValidateOptions {
+        frame_marker: Some(TIME),
+        time_property: Some(TIME),
+        ..ValidateOptions::default()
+    };
+    for &footer in &[false, true] {
+        let reports: Vec<Report> = [Endian::Little, Endian::Big].iter()
+            .map(|&endian| validate(&mut &write(endian, footer)[..], &opts).unwrap())
+            .collect();
+        assert!(reports[0].is_ok(), "{:?}", reports[0]);
+        assert_eq!(reports[0].frames, 1);
+        assert_eq!(reports[0], reports[1]);
+    }
+}
+
+#[test]
+fn index_both() {
+    let expected = Index::build(&mut Cursor::new(write(Endian::Little, false)), TIME, Some(TIME))
+        .unwrap();
+    assert_eq!(expected.frames(), &[IndexEntry {position: 40, time: Some(0.5)}]);
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = write(endian, false);
+        let index = Index::build(&mut Cursor::new(&data), TIME, Some(TIME)).unwrap();
+        assert_eq!(index, expected);
+        let mut r = Cursor::new(&data);
+        assert_eq!(Index::load_or_build(&mut r, TIME, Some(TIME)).unwrap(), expected);
+        assert_eq!(r.position(), data.len() as u64);
+
+        // With a footer, the index is loaded and is the same as when built.
+        let data = write(endian, true);
+        let built = Index::build(&mut Cursor::new(&data), TIME, Some(TIME)).unwrap();
+        let loaded = Index::load_or_build(&mut Cursor::new(&data), TIME, Some(TIME)).unwrap();
+        assert_eq!(built, loaded);
+        assert_eq!(built.frames(), expected.frames());
+    }
+}
+
+#[test]
+fn last_frame_both() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        for &footer in &[false, true] {
+            let mut w = PoolWriter::new(vec![]).with_endianness(endian).with_footer_index(footer);
+            // Frames of 80 KB, such that the tail is searched before the whole stream.
+            for i in 0..3 {
+                Time::write(TIME, i as f64, &mut w).unwrap();
+                Vector::write_array(POSITION, &vec![[i as u32, 7]; 10_000], &mut w).unwrap();
+            }
+            let data = w.finish().unwrap();
+            let frame = last_frame(&mut Cursor::new(&data), TIME).unwrap();
+            assert_eq!(frame.time, Some(2.0), "{:?} {}", endian, footer);
+            let mut pos: Vec<[u32; 2]> = vec![];
+            frame.read_array(POSITION, &mut pos).unwrap();
+            assert_eq!(pos, vec![[2, 7]; 10_000]);
+        }
+    }
+}
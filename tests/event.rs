@@ -0,0 +1,104 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const EVENT: u16 = 0;
+const P4: u16 = 1;
+const CHARGE: u16 = 2;
+const RUN: u16 = 3;
+
+/// Writes properties to a stream.
+type Write = dyn Fn(&mut Vec<u8>);
+
+/// Event numbers that do not increase, with differing particle counts.
+const EVENTS: [(u64, usize); 3] = [(42, 2), (7, 5), (1000, 1)];
+
+fn momenta(number: u64, n: usize) -> Vec<[f64; 4]> {
+    (0..n).map(|i| [number as f64, i as f64, 0.5, 1.0]).collect()
+}
+
+fn charges(n: usize) -> Vec<i8> {
+    (0..n).map(|i| if i % 2 == 0 {1} else {-1}).collect()
+}
+
+/// Writes a run number before the first event and a trailer after the last event.
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    77_u32.write_property(RUN, &mut w).unwrap();
+    for &(number, n) in &EVENTS {
+        number.write_property(EVENT, &mut w).unwrap();
+        Vector::write_array(P4, &momenta(number, n), &mut w).unwrap();
+        Scalar::write_array(CHARGE, &charges(n), &mut w).unwrap();
+    }
+    78_u32.write_property(RUN, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+#[test]
+fn three_events() {
+    let data = stream();
+    let mut r = EventReader::new(&data[..], EVENT);
+    assert_eq!(r.marker(), EVENT);
+    let mut i = 0;
+    while let Some(event) = r.next_event().unwrap() {
+        let (number, n) = EVENTS[i];
+        assert_eq!((event.number, event.index()), (number, i as u64));
+        assert_eq!(event.blocks()[0].property_id, EVENT);
+        assert_eq!(event.array::<[f64; 4]>(P4).unwrap(), momenta(number, n));
+        assert_eq!(event.array::<i8>(CHARGE).unwrap(), charges(n));
+        assert_eq!(event.blocks_of(P4).count(), 1);
+        // The trailer belongs to the last event.
+        let last = i == EVENTS.len() - 1;
+        assert_eq!(event.contains(RUN), last);
+        assert_eq!(event.property::<u32>(RUN).unwrap(), if last {Some(78)} else {None});
+        i += 1;
+
+        // The preamble is kept.
+        let run: Vec<&RawBlock> = r.preamble().iter().filter(|b| b.property_id == RUN).collect();
+        assert_eq!(run.len(), 1);
+        assert_eq!(run[0].decode::<u32>().unwrap(), vec![77]);
+    }
+    assert_eq!(i, EVENTS.len());
+    assert_eq!(r.events(), 3);
+    assert!(r.next_event().unwrap().is_none());
+}
+
+#[test]
+fn no_events() {
+    let mut w = vec![];
+    77_u32.write_property(RUN, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    let mut r = EventReader::new(&w[..], EVENT);
+    assert!(r.next_event().unwrap().is_none());
+    assert_eq!(r.preamble().len(), 1);
+    assert_eq!(r.events(), 0);
+}
+
+#[test]
+fn event_numbers() {
+    // Every non-negative integer type is accepted.
+    let read = |write: &Write| {
+        let mut w = vec![];
+        write(&mut w);
+        State::new().end_type_formats(&mut w).unwrap();
+        EventReader::new(&w[..], EVENT).next_event().map(|e| e.unwrap().number)
+    };
+    assert_eq!(read(&|w| 5_u8.write_property(EVENT, w).unwrap()).unwrap(), 5);
+    assert_eq!(read(&|w| 300_i16.write_property(EVENT, w).unwrap()).unwrap(), 300);
+    assert_eq!(read(&|w| u64::MAX.write_property(EVENT, w).unwrap()).unwrap(), u64::MAX);
+    assert_eq!(read(&|w| i64::MAX.write_property(EVENT, w).unwrap()).unwrap(), i64::MAX as u64);
+
+    // Negative, fractional and several event numbers.
+    let bad: [&Write; 3] = [
+        &|w| (-1_i32).write_property(EVENT, w).unwrap(),
+        &|w| 1.0_f64.write_property(EVENT, w).unwrap(),
+        &|w| Scalar::write_array(EVENT, &vec![1_u64, 2], w).unwrap(),
+    ];
+    for write in &bad {
+        let err = Error::from_io(&read(write).unwrap_err());
+        assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(EVENT)));
+    }
+}
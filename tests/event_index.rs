@@ -0,0 +1,152 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Cursor;
+
+const EVENT: u16 = 0;
+const P4: u16 = 1;
+const RUN: u16 = 2;
+
+const EVENTS: usize = 200;
+
+/// Writes events with unique random event numbers and differing particle counts,
+/// after a preamble.
+///
+/// Returns the stream and the event numbers in the order of the stream.
+fn stream() -> (Vec<u8>, Vec<u64>) {
+    let mut rng = Rng::new(186);
+    let mut w = vec![];
+    7_u32.write_property(RUN, &mut w).unwrap();
+    let mut numbers: Vec<u64> = (0..EVENTS as u64)
+        .map(|i| i * 10_000 + rng.below(10_000))
+        .collect();
+    rng.shuffle(&mut numbers);
+    for &number in &numbers {
+        number.write_property(EVENT, &mut w).unwrap();
+        let p4: Vec<[f64; 4]> = (0..1 + rng.below(20)).map(|_| [rng.unit(); 4]).collect();
+        Vector::write_array(P4, &p4, &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    (w, numbers)
+}
+
+/// Reads the events in order.
+fn sequential(data: &[u8]) -> Vec<Event> {
+    let mut r = EventReader::new(data, EVENT);
+    let mut res = vec![];
+    while let Some(event) = r.next_event().unwrap() {res.push(event)}
+    res
+}
+
+fn assert_same(a: &Event, b: &Event) {
+    assert_eq!((a.number, a.index()), (b.number, b.index()));
+    assert_eq!(a.blocks(), b.blocks());
+    assert_eq!(a.array::<[f64; 4]>(P4).unwrap(), b.array::<[f64; 4]>(P4).unwrap());
+}
+
+#[test]
+fn random_access() {
+    let (data, numbers) = stream();
+    let events = sequential(&data);
+    assert_eq!(events.len(), EVENTS);
+    let mut file = Cursor::new(&data);
+    let index = EventIndex::build(&mut file, EVENT, DuplicateEvents::Error).unwrap();
+    assert_eq!((index.marker(), index.event_count()), (EVENT, EVENTS as u64));
+    let recorded: Vec<u64> = index.events().iter().map(|e| e.number).collect();
+    assert_eq!(recorded, numbers);
+
+    // Events out of order.
+    let mut rng = Rng::new(1860);
+    let mut order: Vec<usize> = (0..EVENTS).collect();
+    rng.shuffle(&mut order);
+    for &i in &order {
+        let number = numbers[i];
+        assert!(index.contains(number));
+        assert_eq!(index.find(number), &[i]);
+        let event = index.read_event(number, &mut file).unwrap().unwrap();
+        assert_same(&event, &events[i]);
+        assert_same(&index.read_event_at(i, &mut file).unwrap(), &events[i]);
+    }
+
+    // The byte ranges of events cover the stream after the preamble.
+    let preamble = index.preamble_range();
+    assert_eq!(preamble.start, 0);
+    let mut end = preamble.end;
+    for i in 0..EVENTS {
+        let range = index.event_range(i).unwrap();
+        assert_eq!(range.start, end);
+        end = range.end;
+    }
+    // The end of stream is not part of the last event.
+    assert_eq!(end as usize, data.len() - 2);
+
+    // Event numbers that do not exist.
+    assert!(!index.contains(3));
+    assert!(index.find(3).is_empty());
+    assert!(index.read_event(3, &mut file).unwrap().is_none());
+    assert!(index.read_events(3, &mut file).unwrap().is_empty());
+    let err = Error::from_io(&index.read_event_at(EVENTS, &mut file).unwrap_err());
+    assert_eq!((err.kind(), err.range()), (ErrorKind::FrameOutOfRange, Some((0, EVENTS as u64))));
+}
+
+#[test]
+fn sidecar() {
+    let (data, numbers) = stream();
+    let mut file = Cursor::new(&data);
+    let index = EventIndex::build(&mut file, EVENT, DuplicateEvents::Error).unwrap();
+    let mut sidecar = vec![];
+    index.write(&mut sidecar).unwrap();
+    let read = EventIndex::read(&mut &sidecar[..], DuplicateEvents::Error).unwrap();
+    assert_eq!(read, index);
+    let events = sequential(&data);
+    let event = read.read_event(numbers[150], &mut file).unwrap().unwrap();
+    assert_same(&event, &events[150]);
+
+    // A stream where the event number changed does not match the sidecar.
+    let mut other = data.clone();
+    // The event number follows the property header and the block header.
+    let at = read.events()[150].position as usize + 4 + 16;
+    other[at..at + 8].copy_from_slice(&3_u64.to_le_bytes());
+    let err = read.read_event(numbers[150], &mut Cursor::new(&other)).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(EVENT)));
+}
+
+#[test]
+fn duplicate_event_numbers() {
+    let mut w = vec![];
+    let mut positions = vec![];
+    for (i, &number) in [5_u64, 9, 5, 5].iter().enumerate() {
+        positions.push(w.len() as u64);
+        number.write_property(EVENT, &mut w).unwrap();
+        Vector::write_array(P4, &vec![[i as f64; 4]], &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+
+    let err = EventIndex::build(&mut Cursor::new(&w), EVENT, DuplicateEvents::Error).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(EVENT)));
+    assert_eq!(err.at_byte(), Some(positions[2]));
+
+    let mut file = Cursor::new(&w);
+    let index = EventIndex::build(&mut file, EVENT, DuplicateEvents::KeepAll).unwrap();
+    assert_eq!(index.find(5), &[0, 2, 3]);
+    // The first event with the number is read.
+    let event = index.read_event(5, &mut file).unwrap().unwrap();
+    assert_eq!(event.array::<[f64; 4]>(P4).unwrap(), vec![[0.0; 4]]);
+    let all: Vec<(u64, Vec<[f64; 4]>)> = index.read_events(5, &mut file).unwrap().iter()
+        .map(|e| (e.index(), e.array(P4).unwrap()))
+        .collect();
+    assert_eq!(all, vec![(0, vec![[0.0; 4]]), (2, vec![[2.0; 4]]), (3, vec![[3.0; 4]])]);
+
+    // A sidecar with duplicates follows the option too.
+    let mut sidecar = vec![];
+    index.write(&mut sidecar).unwrap();
+    assert!(EventIndex::read(&mut &sidecar[..], DuplicateEvents::Error).is_err());
+    assert!(EventIndex::read(&mut &sidecar[..], DuplicateEvents::KeepAll).is_ok());
+}
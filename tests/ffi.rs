@@ -0,0 +1,216 @@
+#![cfg(feature = "ffi")]
+
+extern crate binpool;
+
+use binpool::ffi::*;
+use binpool::*;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::{c_int, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+const POSITION: u16 = 1;
+const HITS: u16 = 2;
+const HIT: u16 = 0xfe00;
+
+/// Returns a path in an empty temporary directory.
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("binpool-ffi-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("data.pool")
+}
+
+fn c_path(path: &Path) -> CString {
+    CString::new(path.to_str().unwrap()).unwrap()
+}
+
+fn last_error() -> String {
+    unsafe {CStr::from_ptr(binpool_last_error_message())}.to_string_lossy().into_owned()
+}
+
+/// Blocks in the order they were passed to the callback, and the number of blocks to accept.
+struct Collected {
+    blocks: Vec<RawBlock>,
+    accept: usize,
+}
+
+extern "C" fn collect(
+    user_data: *mut c_void,
+    type_format: u16,
+    property_id: u16,
+    offset: u64,
+    data: *const c_void,
+    len_bytes: u64
+) -> c_int {
+    let collected = unsafe {&mut *(user_data as *mut Collected)};
+    if collected.blocks.len() == collected.accept {return 1}
+    let data = if len_bytes == 0 {vec![]} else {
+        unsafe {std::slice::from_raw_parts(data as *const u8, len_bytes as usize)}.to_vec()
+    };
+    collected.blocks.push(RawBlock {type_format, property_id, offset, data});
+    BINPOOL_OK
+}
+
+fn read_path(path: &Path, accept: usize) -> (c_int, Vec<RawBlock>) {
+    let mut collected = Collected {blocks: vec![], accept};
+    let code = unsafe {
+        binpool_read_path(c_path(path).as_ptr(), Some(collect),
+            &mut collected as *mut Collected as *mut c_void)
+    };
+    (code, collected.blocks)
+}
+
+/// The blocks written by `write`.
+fn blocks() -> Vec<RawBlock> {
+    let f32x3 = Type::F32.vector(3).unwrap().0;
+    let mut positions = vec![];
+    for x in &[1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0] {positions.extend_from_slice(&x.to_le_bytes())}
+    vec![
+        RawBlock {type_format: f32x3, property_id: POSITION, offset: 0, data: positions.clone()},
+        RawBlock {type_format: f32x3, property_id: POSITION, offset: 2, data: positions},
+        RawBlock {type_format: HIT, property_id: HITS, offset: 0, data: vec![1, 0, 0, 0, 7]},
+        RawBlock {type_format: Type::U8.scalar().0, property_id: HITS, offset: 9, data: vec![3]},
+    ]
+}
+
+/// Writes every block as a property through the C interface.
+unsafe fn write(w: *mut BinpoolWriter) {
+    assert!(!w.is_null(), "{}", last_error());
+    for b in &blocks() {
+        let code = binpool_write_array(w, b.type_format, b.property_id, b.offset,
+            b.data.as_ptr() as *const c_void, b.data.len() as u64);
+        assert_eq!(code, BINPOOL_OK);
+    }
+    assert_eq!(binpool_finish(w), BINPOOL_OK);
+}
+
+/// The same stream written in Rust.
+fn expected() -> Vec<u8> {
+    let mut data = vec![];
+    for b in &blocks() {
+        write_block(b.type_format, b.property_id, b.offset, &b.data, &mut data).unwrap();
+    }
+    State::new().end_type_formats(&mut data).unwrap();
+    data
+}
+
+#[test]
+fn round_trip_path() {
+    let path = temp_path("path");
+    unsafe {write(binpool_writer_open_path(c_path(&path).as_ptr()))};
+    assert_eq!(fs::read(&path).unwrap(), expected());
+
+    let (code, read) = read_path(&path, usize::MAX);
+    assert_eq!(code, BINPOOL_OK);
+    assert_eq!(read, blocks());
+
+    // The callback can stop reading.
+    let (code, read) = read_path(&path, 1);
+    assert_eq!(code, BINPOOL_ERROR_CALLBACK);
+    assert_eq!(read.len(), 1);
+    assert!(!last_error().is_empty());
+
+    // A writer that is closed does not write end of stream.
+    let w = unsafe {binpool_writer_open_path(c_path(&path).as_ptr())};
+    let b = &blocks()[2];
+    unsafe {
+        binpool_write_array(w, b.type_format, b.property_id, b.offset,
+            b.data.as_ptr() as *const c_void, b.data.len() as u64);
+        binpool_writer_close(w);
+        binpool_writer_close(ptr::null_mut());
+    }
+    let data = fs::read(&path).unwrap();
+    let mut single = vec![];
+    write_block(b.type_format, b.property_id, b.offset, &b.data, &mut single).unwrap();
+    assert_eq!(data, single);
+    // Readers treat the end of the file as the end of stream.
+    assert_eq!(read_path(&path, usize::MAX), (BINPOOL_OK, vec![b.clone()]));
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn round_trip_fd() {
+    use std::os::unix::io::IntoRawFd;
+
+    let path = temp_path("fd");
+    let fd = fs::File::create(&path).unwrap().into_raw_fd();
+    unsafe {write(binpool_writer_open_fd(fd))};
+    assert_eq!(fs::read(&path).unwrap(), expected());
+
+    let fd = fs::File::open(&path).unwrap().into_raw_fd();
+    let mut collected = Collected {blocks: vec![], accept: usize::MAX};
+    let code = unsafe {
+        binpool_read_fd(fd, Some(collect), &mut collected as *mut Collected as *mut c_void)
+    };
+    assert_eq!(code, BINPOOL_OK);
+    assert_eq!(collected.blocks, blocks());
+
+    unsafe {
+        assert!(binpool_writer_open_fd(-1).is_null());
+        assert_eq!(binpool_read_fd(-1, Some(collect), ptr::null_mut()), BINPOOL_ERROR_IO);
+    }
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn errors() {
+    let path = temp_path("errors");
+    let missing = path.parent().unwrap().join("missing").join("data.pool");
+    unsafe {
+        assert!(binpool_writer_open_path(ptr::null()).is_null());
+        assert!(last_error().contains("null"));
+        assert!(binpool_writer_open_path(c_path(&missing).as_ptr()).is_null());
+        assert!(!last_error().is_empty());
+        assert_eq!(read_path(&missing, 0).0, BINPOOL_ERROR_IO);
+        assert_eq!(binpool_read_path(ptr::null(), Some(collect), ptr::null_mut()),
+            BINPOOL_ERROR_NULL);
+
+        let w = binpool_writer_open_path(c_path(&path).as_ptr());
+        let data = [0_u8; 12];
+        let ptr = data.as_ptr() as *const c_void;
+        let f32x3 = Type::F32.vector(3).unwrap().0;
+        assert_eq!(binpool_write_array(ptr::null_mut(), f32x3, 1, 0, ptr, 12), BINPOOL_ERROR_NULL);
+        assert_eq!(binpool_write_array(w, f32x3, 1, 0, ptr::null(), 12), BINPOOL_ERROR_NULL);
+        assert_eq!(binpool_write_array(w, 0, 1, 0, ptr, 12), BINPOOL_ERROR_INVALID_DATA);
+        assert_eq!(binpool_write_array(w, f32x3, 1, 0, ptr, 10), BINPOOL_ERROR_INVALID_DATA);
+        assert!(last_error().contains("multiple"));
+        assert_eq!(binpool_finish(ptr::null_mut()), BINPOOL_ERROR_NULL);
+        // Nothing was written by the failed calls.
+        assert_eq!(binpool_finish(w), BINPOOL_OK);
+        assert_eq!(binpool_read_path(c_path(&path).as_ptr(), None, ptr::null_mut()),
+            BINPOOL_ERROR_NULL);
+    }
+    assert_eq!(fs::read(&path).unwrap(), vec![0, 0]);
+
+    // A torn file is invalid data.
+    let data = expected();
+    fs::write(&path, &data[..data.len() - 5]).unwrap();
+    assert_eq!(read_path(&path, usize::MAX).0, BINPOOL_ERROR_INVALID_DATA);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+/// The error codes in the checked in header are the ones of the library.
+#[test]
+fn header_codes() {
+    let header = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("include").join("binpool.h")).unwrap();
+    for &(name, code) in &[
+        ("BINPOOL_OK", BINPOOL_OK),
+        ("BINPOOL_ERROR_NULL", BINPOOL_ERROR_NULL),
+        ("BINPOOL_ERROR_IO", BINPOOL_ERROR_IO),
+        ("BINPOOL_ERROR_INVALID_DATA", BINPOOL_ERROR_INVALID_DATA),
+        ("BINPOOL_ERROR_CALLBACK", BINPOOL_ERROR_CALLBACK),
+    ] {
+        assert!(header.contains(&format!("#define {} {}\n", name, code)), "{}", name);
+    }
+    for name in &[
+        "binpool_last_error_message", "binpool_writer_open_path", "binpool_writer_open_fd",
+        "binpool_write_array", "binpool_finish", "binpool_writer_close",
+        "binpool_read_path", "binpool_read_fd",
+    ] {
+        assert!(header.contains(&format!("{}(", name)), "{}", name);
+    }
+}
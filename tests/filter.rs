@@ -0,0 +1,181 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const ENERGY: u16 = 2;
+
+/// Encodes items as a block starting at an instance.
+fn block<T: Element>(ty: u16, prop: u16, offset: u64, items: &[T]) -> RawBlock {
+    let mut data = vec![];
+    for x in items {x.write_element(&mut data).unwrap()}
+    RawBlock {type_format: ty, property_id: prop, offset, data}
+}
+
+fn position_format() -> u16 {
+    Type::F32.vector(3).unwrap().0
+}
+
+fn energy_format() -> u16 {
+    Type::F64.scalar().0
+}
+
+/// Writes frames where positions and energies are written in several blocks,
+/// with gaps between the blocks and energies that overwrite earlier energies.
+fn stream(frames: usize) -> Vec<u8> {
+    let mut rng = Rng::new(176);
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..frames {
+        w.begin_frame(i as f64).unwrap();
+        let mut blocks = vec![];
+        let mut offset = 0;
+        for _ in 0..1 + rng.below(3) {
+            let items: Vec<[f32; 3]> = (0..1 + rng.below(8))
+                .map(|_| [rng.unit() as f32, 0.0, rng.unit() as f32 * 40.0 - 20.0])
+                .collect();
+            blocks.push(block(position_format(), POSITION, offset, &items));
+            offset += items.len() as u64 + rng.below(3);
+        }
+        RawBlock::write_property(position_format(), POSITION, &blocks, &mut w).unwrap();
+        let blocks: Vec<RawBlock> = (0..1 + rng.below(3)).map(|_| {
+            let items: Vec<f64> = (0..1 + rng.below(10)).map(|_| rng.unit() * 10.0).collect();
+            block(energy_format(), ENERGY, rng.below(6), &items)
+        }).collect();
+        RawBlock::write_property(energy_format(), ENERGY, &blocks, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// Returns the written items of a property in a frame, by instance, after a full decode.
+fn written<T: Element>(frame: &Frame, prop: u16) -> Vec<Option<T>> {
+    let mut items: Vec<T> = vec![];
+    frame.read_array(prop, &mut items).unwrap();
+    let mut res: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    // Instances in gaps between blocks are not written.
+    let mut seen = vec![false; res.len()];
+    for block in frame.blocks.iter().filter(|b| b.property_id == prop) {
+        let n = block.decode::<T>().unwrap().len();
+        for s in &mut seen[block.offset as usize..block.offset as usize + n] {*s = true}
+    }
+    for (x, &s) in res.iter_mut().zip(&seen) {if !s {*x = None}}
+    res
+}
+
+fn near(_: u64, pos: &[f32; 3]) -> bool {
+    pos[2].abs() < 10.0
+}
+
+#[test]
+fn filtered_against_full_decode() {
+    let data = stream(40);
+    let mut r = PoolReader::new(&data[..]);
+    for &prop in &[TIME, POSITION, ENERGY] {r.register(prop)}
+    let mut res = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        if prop == POSITION {
+            let mut near_positions = vec![];
+            read_array_filtered(state, ty, &mut r, near, &mut near_positions).unwrap();
+            res.push(near_positions);
+        } else {
+            RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        }
+    }
+
+    let mut fr = FrameReader::with_time(&data[..], TIME);
+    let mut expected = vec![];
+    while let Some(frame) = fr.next_frame().unwrap() {
+        let near: Vec<(u64, [f32; 3])> = written(&frame, POSITION).into_iter()
+            .enumerate()
+            .filter_map(|(i, pos)| pos.map(|pos| (i as u64, pos)))
+            .filter(|&(i, ref pos)| near(i, pos))
+            .collect();
+        expected.push(near);
+    }
+    assert_eq!(res, expected);
+    assert!(expected.iter().map(|x| x.len()).sum::<usize>() > 20);
+
+    // Another element type.
+    let mut r = PoolReader::new(&data[..]);
+    for &prop in &[TIME, POSITION, ENERGY] {r.register(prop)}
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        if prop == POSITION {
+            let mut out: Vec<(u64, [f64; 3])> = vec![];
+            let err = read_array_filtered(state, ty, &mut r, |_, _| true, &mut out).unwrap_err();
+            assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+            break;
+        }
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+    }
+}
+
+#[test]
+fn filtered_by_against_full_decode() {
+    let data = stream(40);
+    let mut fr = FrameReader::with_time(&data[..], TIME);
+    let mut total = 0;
+    while let Some(frame) = fr.next_frame().unwrap() {
+        let mut res = vec![];
+        let above = |_: u64, e: &f64| *e > 5.0;
+        assert!(frame.read_array_filtered_by(POSITION, ENERGY, above, &mut res).unwrap());
+        let positions = written::<[f32; 3]>(&frame, POSITION);
+        let energies = written::<f64>(&frame, ENERGY);
+        let expected: Vec<(u64, [f32; 3])> = positions.into_iter().zip(energies)
+            .enumerate()
+            .filter_map(|(i, x)| match x {
+                (Some(pos), Some(e)) if above(i as u64, &e) => Some((i as u64, pos)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(res, expected);
+        total += res.len();
+
+        // A property that is not in the frame.
+        let mut out: Vec<(u64, f64)> = vec![];
+        assert!(!frame.read_array_filtered_by(9, ENERGY, |_, _: &f64| true, &mut out).unwrap());
+    }
+    assert!(total > 20);
+}
+
+#[test]
+fn large_offsets() {
+    // Instances far apart do not allocate memory for the instances between them.
+    let far = u64::MAX - 1;
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    let blocks = [
+        block(position_format(), POSITION, 1, &[[1.0_f32, 0.0, 0.0]]),
+        block(position_format(), POSITION, far, &[[2.0_f32, 0.0, 0.0]]),
+    ];
+    RawBlock::write_property(position_format(), POSITION, &blocks, &mut w).unwrap();
+    let blocks = [
+        block(energy_format(), ENERGY, far, &[7.0_f64]),
+        block(energy_format(), ENERGY, 1, &[3.0_f64]),
+    ];
+    RawBlock::write_property(energy_format(), ENERGY, &blocks, &mut w).unwrap();
+    let data = w.finish().unwrap();
+
+    let mut fr = FrameReader::with_time(&data[..], TIME);
+    let frame = fr.next_frame().unwrap().unwrap();
+    let mut res: Vec<(u64, [f32; 3])> = vec![];
+    frame.read_array_filtered_by(POSITION, ENERGY, |_, _: &f64| true, &mut res).unwrap();
+    assert_eq!(res, vec![(1, [1.0, 0.0, 0.0]), (far, [2.0, 0.0, 0.0])]);
+
+    // An instance id past the end of the id space.
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    let blocks = [block(energy_format(), ENERGY, u64::MAX, &[1.0_f64, 2.0])];
+    RawBlock::write_property(energy_format(), ENERGY, &blocks, &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let mut fr = FrameReader::with_time(&data[..], TIME);
+    let frame = fr.next_frame().unwrap().unwrap();
+    let mut res: Vec<(u64, f64)> = vec![];
+    let err = frame.read_array_filtered_by(ENERGY, ENERGY, |_, _: &f64| true, &mut res);
+    let err = Error::from_io(&err.unwrap_err());
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(ENERGY)));
+}
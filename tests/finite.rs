@@ -0,0 +1,99 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const FORCE: u16 = 4;
+const MASS: u16 = 5;
+
+fn checked_writer() -> PoolWriter<Vec<u8>> {
+    PoolWriter::new(vec![]).with_options(WriteOptions::default().reject_non_finite(true))
+}
+
+fn check_error(err: std::io::Error, property: u16, instance: u64) {
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::NonFinite);
+    assert_eq!(err.property(), Some(property));
+    assert_eq!(err.instance(), Some(instance));
+}
+
+#[test]
+fn write_rejects() {
+    for &x in &[f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let mut w = checked_writer();
+        let err = Scalar::write_array(MASS, &vec![1.0, 2.0, x], &mut w).unwrap_err();
+        check_error(err, MASS, 2);
+    }
+    for &x in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let mut w = checked_writer();
+        // The instance is the index of the vector, not of the scalar.
+        let err = Vector::write_array(FORCE, &vec![[0.0; 3], [0.0, x, 0.0]], &mut w).unwrap_err();
+        check_error(err, FORCE, 1);
+    }
+}
+
+#[test]
+fn write_accepts_negative_zero() {
+    let mut w = checked_writer();
+    Scalar::write_array(MASS, &vec![-0.0_f32, 0.0, f32::MIN_POSITIVE, f32::MAX], &mut w).unwrap();
+    Vector::write_array(FORCE, &vec![[-0.0_f64; 3]], &mut w).unwrap();
+    w.finish().unwrap();
+}
+
+#[test]
+fn write_unchecked_by_default() {
+    let mut w = PoolWriter::new(vec![]);
+    Scalar::write_array(MASS, &vec![f32::NAN, f32::INFINITY], &mut w).unwrap();
+    w.finish().unwrap();
+}
+
+/// Writes masses and forces with a non-finite value of each, unchecked.
+fn stream(endian: Endian) -> Vec<u8> {
+    let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+    Scalar::write_array(MASS, &vec![-0.0_f32, f32::NAN, 1.0], &mut w).unwrap();
+    Vector::write_array(FORCE, &vec![[1.0, 2.0, 3.0], [-0.0, 0.0, f64::NEG_INFINITY]], &mut w)
+        .unwrap();
+    Scalar::write_array(MASS, &vec![f32::INFINITY, -0.0], &mut w).unwrap();
+    w.finish().unwrap()
+}
+
+fn read_all(r: &mut PoolReader<&[u8]>) -> std::io::Result<()> {
+    r.register(FORCE);
+    r.register(MASS);
+    while let Some((state, ty, prop)) = r.next_property()? {
+        if prop == FORCE {
+            Vector::read_array(state, ty, &mut Vec::<[f64; 3]>::new(), r)?;
+        } else {
+            Scalar::read_array(state, ty, &mut Vec::<f32>::new(), r)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn read_policies() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = stream(endian);
+
+        let mut r = PoolReader::new(&data[..]);
+        read_all(&mut r).unwrap();
+        assert_eq!(r.non_finite_count(), 0);
+
+        let mut r = PoolReader::new(&data[..]).non_finite(NonFinitePolicy::Error);
+        check_error(read_all(&mut r).unwrap_err(), MASS, 1);
+
+        let mut r = PoolReader::new(&data[..]).non_finite(NonFinitePolicy::Count);
+        read_all(&mut r).unwrap();
+        assert_eq!(r.non_finite_count(), 3);
+    }
+}
+
+#[test]
+fn read_accepts_negative_zero() {
+    let mut w = PoolWriter::new(vec![]);
+    Scalar::write_array(MASS, &vec![-0.0_f32; 4], &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let mut r = PoolReader::new(&data[..]).non_finite(NonFinitePolicy::Error);
+    read_all(&mut r).unwrap();
+}
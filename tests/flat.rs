@@ -0,0 +1,88 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 1;
+
+fn flat() -> Vec<f32> {
+    (0..12).map(|i| i as f32).collect()
+}
+
+fn vectors() -> Vec<[f32; 3]> {
+    (0..4).map(|i| [3.0 * i as f32, 3.0 * i as f32 + 1.0, 3.0 * i as f32 + 2.0]).collect()
+}
+
+/// Writes a single block of items at an offset.
+fn block<T: Element>(offset: u64, items: &[T]) -> Vec<u8> {
+    let (ty, _) = T::format().unwrap();
+    let mut data = vec![];
+    for item in items {item.write_element(&mut data).unwrap()}
+    let mut w = vec![];
+    write_block(ty, POSITION, offset, &data, &mut w).unwrap();
+    w
+}
+
+fn read_flat(data: &[u8], arr: &mut Vec<[f32; 3]>) -> std::io::Result<()> {
+    let mut r = data;
+    let (state, ty, _) = State::read(&mut r)?;
+    <[f32; 3]>::read_array_flat(state.unwrap(), ty, arr, &mut r)
+}
+
+fn read_scalars(data: &[u8], arr: &mut Vec<f32>) -> std::io::Result<()> {
+    let mut r = data;
+    let (state, ty, _) = State::read(&mut r)?;
+    <[f32; 3]>::read_scalars(state.unwrap(), ty, arr, &mut r)
+}
+
+#[test]
+fn scalars_to_vectors() {
+    let mut arr = vec![];
+    read_flat(&block(0, &flat()), &mut arr).unwrap();
+    assert_eq!(arr, vectors());
+    // The offset counts scalars.
+    let mut arr = vec![];
+    read_flat(&block(6, &flat()[..3]), &mut arr).unwrap();
+    assert_eq!(arr, vec![[0.0; 3], [0.0; 3], [0.0, 1.0, 2.0]]);
+}
+
+#[test]
+fn vectors_to_scalars() {
+    let mut arr = vec![];
+    read_scalars(&block(0, &vectors()), &mut arr).unwrap();
+    assert_eq!(arr, flat());
+    // The offset counts vectors.
+    let mut arr = vec![-1.0];
+    read_scalars(&block(1, &vectors()[..1]), &mut arr).unwrap();
+    assert_eq!(arr, vec![-1.0, 0.0, 0.0, 0.0, 1.0, 2.0]);
+}
+
+#[test]
+fn misaligned() {
+    let mut arr = vec![];
+    let err = read_flat(&block(4, &flat()[..3]), &mut arr).unwrap_err();
+    assert_eq!(Error::from_io(&err).field(), Some(Field::OffsetInstanceId));
+    // Scalars that do not fill the last vector.
+    let err = read_flat(&block(0, &flat()[..5]), &mut arr).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::BytesNotMultiple);
+    assert!(arr.is_empty());
+}
+
+#[test]
+fn explicit_only() {
+    // The plain reads do not reinterpret.
+    let data = block(0, &flat());
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<[f32; 3]> = vec![];
+    let err = Vector::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+
+    // Another scalar type.
+    let mut arr = vec![];
+    let err = read_flat(&block(0, &[1_u32, 2, 3]), &mut arr).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+    let err = read_scalars(&block(0, &[[1.0_f32; 2]]), &mut vec![]).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+}
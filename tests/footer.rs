@@ -0,0 +1,213 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const SPEED: u16 = 3;
+
+const FRAMES: usize = 40;
+
+/// Writes frames `from..to`, with masses before the first frame.
+fn write_frames<W: io::Write>(w: &mut PoolWriter<W>, from: usize, to: usize) {
+    if from == 0 {Scalar::write_array(MASS, &vec![1.0_f64; 4], w).unwrap()}
+    for i in from..to {
+        Time::write(TIME, i as f64 * 0.5, w).unwrap();
+        Vector::write_array(POSITION, &vec![[i as f32; 3]; 1 + i % 7], w).unwrap();
+        // A time stored as `f32` for some frames.
+        if i % 3 == 0 {(i as f32).write_property(SPEED, w).unwrap()}
+        Scalar::write_array(MASS, &vec![i as f64; 1 + i % 2], w).unwrap();
+    }
+}
+
+fn writer(endian: Endian, alignment: u16, footer: bool) -> PoolWriter<Cursor<Vec<u8>>> {
+    PoolWriter::new(Cursor::new(vec![]))
+        .with_endianness(endian)
+        .with_options(WriteOptions::default().alignment(alignment))
+        .with_footer_index(footer)
+}
+
+fn stream(endian: Endian, alignment: u16, footer: bool) -> Vec<u8> {
+    let mut w = writer(endian, alignment, footer);
+    write_frames(&mut w, 0, FRAMES);
+    w.finish().unwrap().into_inner()
+}
+
+/// Counts the bytes read through it.
+struct Counted<R> {
+    inner: R,
+    read: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Counted<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Loads an index, returning it with the number of bytes read.
+fn load(data: &[u8], start: u64, marker: u16, time: Option<u16>) -> (Index, u64) {
+    let mut r = Counted {inner: Cursor::new(data), read: 0};
+    r.seek(SeekFrom::Start(start)).unwrap();
+    let index = Index::load_or_build(&mut r, marker, time).unwrap();
+    // The reader is left at the end of stream, before the trailer if any.
+    let end = r.inner.position() as usize;
+    assert_eq!(&data[end - 2..end], &[0, 0]);
+    (index, r.read)
+}
+
+/// Returns the number of bytes from the footer to the end of the data,
+/// which is what loading an index from the footer reads.
+fn footer_len(data: &[u8], start: u64) -> u64 {
+    let mut position = [0; 8];
+    position.copy_from_slice(&data[data.len() - 8..]);
+    data.len() as u64 - start - u64::from_le_bytes(position)
+}
+
+fn build(data: &[u8], start: u64, marker: u16, time: Option<u16>) -> Index {
+    let mut r = Cursor::new(data);
+    r.set_position(start);
+    Index::build(&mut r, marker, time).unwrap()
+}
+
+/// Frame markers and time properties.
+const INDEXES: &[(u16, Option<u16>)] = &[
+    (TIME, Some(TIME)),
+    (TIME, None),
+    (POSITION, Some(TIME)),
+    (POSITION, None),
+    (SPEED, Some(SPEED)),
+    (TIME, Some(SPEED)),
+    (MASS, None),
+];
+
+#[test]
+fn loaded_equals_built() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        for &alignment in &[0, 64] {
+            let data = stream(endian, alignment, true);
+            let plain = stream(endian, alignment, false);
+            for &(marker, time) in INDEXES {
+                let built = build(&data, 0, marker, time);
+                assert_eq!(built.frames(), build(&plain, 0, marker, time).frames());
+                assert!(built.frame_count() > 0);
+                // Only the footer is read.
+                let (loaded, read) = load(&data, 0, marker, time);
+                assert_eq!(loaded, built, "{:?} {} {} {:?}", endian, alignment, marker, time);
+                assert_eq!(read, footer_len(&data, 0));
+
+                // Without a footer, the stream is scanned.
+                let (loaded, _) = load(&plain, 0, marker, time);
+                assert_eq!(loaded, build(&plain, 0, marker, time));
+            }
+
+            // Masses of several items have no time in the footer, so the stream is scanned,
+            // which fails like building the index does.
+            let mut r = Counted {inner: Cursor::new(&data), read: 0};
+            assert!(Index::load_or_build(&mut r, MASS, Some(MASS)).is_err());
+            assert!(r.read > footer_len(&data, 0));
+            assert!(Index::build(&mut Cursor::new(&data), MASS, Some(MASS)).is_err());
+        }
+    }
+
+    // A stream that starts after other data.
+    let mut data = vec![7; 33];
+    data.extend_from_slice(&stream(Endian::Little, 64, true));
+    for &(marker, time) in INDEXES {
+        let (loaded, read) = load(&data, 33, marker, time);
+        assert_eq!(loaded, build(&data, 33, marker, time));
+        assert_eq!(read, footer_len(&data, 33));
+        assert!(loaded.frames()[0].position > 33);
+    }
+}
+
+#[test]
+fn readers_skip_footer() {
+    let data = stream(Endian::Little, 0, true);
+    let plain = stream(Endian::Little, 0, false);
+    let read = |data: &[u8]| {
+        let mut r = FrameReader::with_time(PoolReader::new(data), TIME);
+        let mut frames = vec![];
+        while let Some(frame) = r.next_frame().unwrap() {
+            let ids: Vec<u16> = frame.blocks.iter().map(|b| b.property_id).collect();
+            frames.push((frame.time, ids));
+        }
+        frames
+    };
+    assert_eq!(read(&data), read(&plain));
+    assert_eq!(read(&data).len(), FRAMES);
+    let last = last_frame(&mut Cursor::new(&data), TIME).unwrap();
+    assert_eq!(last.time, Some((FRAMES - 1) as f64 * 0.5));
+}
+
+#[test]
+fn append() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        for &alignment in &[0, 64] {
+            for &footer in &[false, true] {
+                let mut w = writer(endian, alignment, footer);
+                write_frames(&mut w, 0, FRAMES / 2);
+                let mut f = w.finish().unwrap();
+
+                // The footer is invalidated right away, so an unfinished append is scanned.
+                f.set_position(0);
+                let w = PoolWriter::append(f).unwrap();
+                let unfinished = w.get_ref().get_ref().clone();
+                let (loaded, _) = load(&unfinished, 0, TIME, Some(TIME));
+                assert_eq!(loaded, build(&unfinished, 0, TIME, Some(TIME)));
+                let mut f = w.finish().unwrap();
+
+                // Appending the other frames gives the same stream as writing them at once.
+                f.set_position(0);
+                let mut w = PoolWriter::append(f).unwrap();
+                write_frames(&mut w, FRAMES / 2, FRAMES);
+                let data = w.finish().unwrap().into_inner();
+                assert_eq!(data, stream(endian, alignment, footer));
+                for &(marker, time) in INDEXES {
+                    assert_eq!(load(&data, 0, marker, time).0, build(&data, 0, marker, time));
+                }
+            }
+        }
+    }
+
+    // The footer is dropped when appending without it.
+    let mut f = Cursor::new(stream(Endian::Little, 0, true));
+    let mut w = PoolWriter::append(&mut f).unwrap().with_footer_index(false);
+    Time::write(TIME, 100.0, &mut w).unwrap();
+    w.finish().unwrap();
+    let data = f.into_inner();
+    let (loaded, read) = load(&data, 0, TIME, Some(TIME));
+    assert_eq!(loaded, build(&data, 0, TIME, Some(TIME)));
+    assert_eq!(loaded.frame_count(), FRAMES as u64 + 1);
+    assert!(read > footer_len(&stream(Endian::Little, 0, true), 0));
+}
+
+#[test]
+fn broken_footer() {
+    let data = stream(Endian::Little, 0, true);
+    let expected = build(&data, 0, TIME, Some(TIME));
+    let len = data.len();
+    // The magic, and the position of the footer.
+    for &i in &[len - 16, len - 8, len - 1] {
+        let mut broken = data.clone();
+        broken[i] ^= 1;
+        let (loaded, read) = load(&broken, 0, TIME, Some(TIME));
+        assert_eq!(loaded, expected, "{}", i);
+        assert!(read > footer_len(&data, 0));
+    }
+    // A trailer without the end of stream before it.
+    let (loaded, _) = load(&data[..len - 16], 0, TIME, Some(TIME));
+    assert_eq!(loaded, expected);
+}
@@ -0,0 +1,77 @@
+extern crate binpool;
+
+use binpool::Type;
+
+const TYPES: [Type; 10] = [
+    Type::U8, Type::U16, Type::U32, Type::U64,
+    Type::I8, Type::I16, Type::I32, Type::I64,
+    Type::F32, Type::F64,
+];
+
+#[test]
+fn info_then_format_for() {
+    let mut builtin = 0;
+    for format in 0..=u16::MAX {
+        match Type::info(format) {
+            Some((ty, rows, cols)) => {
+                builtin += 1;
+                assert_eq!(Type::format_for(ty, rows, cols), Some(format));
+            }
+            None => assert!(format == 0 || format >= Type::offset_custom_format()),
+        }
+    }
+    assert_eq!(builtin, Type::builtin_format_count());
+}
+
+#[test]
+fn format_for_then_info() {
+    let max = Type::max_matrix_dim();
+    for &ty in &TYPES {
+        for rows in 0..=u8::MAX {
+            for cols in 0..=u8::MAX {
+                let supported = rows >= 1 && cols >= 1 && rows <= max && cols <= max;
+                match Type::format_for(ty, rows, cols) {
+                    Some(format) => {
+                        assert!(supported, "{:?} {}x{}", ty, rows, cols);
+                        assert_eq!(Type::info(format), Some((ty, rows, cols)));
+                    }
+                    None => assert!(!supported, "{:?} {}x{}", ty, rows, cols),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn all_formats() {
+    let all: Vec<(u16, Type, u8, u8)> = Type::all_formats().collect();
+    assert_eq!(all.len(), Type::builtin_format_count() as usize);
+    assert_eq!(all.first().map(|f| f.0), Some(1));
+    assert_eq!(all.last().map(|f| f.0), Some(Type::offset_custom_format() - 1));
+    for pair in all.windows(2) {
+        assert!(pair[0].0 < pair[1].0);
+    }
+    for &(format, ty, rows, cols) in &all {
+        assert_eq!(Type::info(format), Some((ty, rows, cols)));
+    }
+    for &ty in &TYPES {
+        assert_eq!(all.iter().filter(|f| f.1 == ty).count(), 80 * 80);
+    }
+}
+
+#[test]
+fn dimensions() {
+    assert_eq!(Type::max_vector_dim(), 80);
+    assert_eq!(Type::max_matrix_dim(), 80);
+    for &ty in &TYPES {
+        for dim in 1..=Type::max_vector_dim() {
+            assert_eq!(Type::format_for(ty, 1, dim), ty.vector(dim).map(|v| v.0));
+        }
+        assert_eq!(ty.vector(Type::max_vector_dim() + 1), None);
+        assert_eq!(Type::format_for(ty, 1, 1), Some(ty.scalar().0));
+    }
+    assert_eq!(
+        Type::builtin_format_count() as u32 + Type::custom_formats() as u32 + 1,
+        1 << 16
+    );
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const FIELD: u16 = 1;
+
+fn field(rows: u32, cols: u32) -> Vec<f32> {
+    (0..rows * cols).map(|i| i as f32 * 0.5).collect()
+}
+
+fn read<T: Scalar + Clone>(data: &[u8]) -> Grid<T> {
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let grid = read_grid(state.unwrap(), ty, prop, &mut r).unwrap();
+    assert!(r.is_empty());
+    grid
+}
+
+#[test]
+fn uneven_shapes() {
+    for &(rows, cols) in &[(1, 1), (80, 80), (81, 81), (161, 7), (7, 161), (512, 512), (79, 240)] {
+        let data = field(rows, cols);
+        let mut buf = vec![];
+        write_grid(FIELD, rows, cols, &data, &mut buf).unwrap();
+        let grid = read::<f32>(&buf);
+        assert_eq!((grid.rows, grid.cols), (rows, cols));
+        assert_eq!(grid.data, data, "{}x{}", rows, cols);
+        // Tiles cover every scalar once.
+        let covered: u64 = (0..grid.tile_count())
+            .map(|t| grid.tile_range(t).unwrap())
+            .map(|(r, c)| (r[1] - r[0]) as u64 * (c[1] - c[0]) as u64)
+            .sum();
+        assert_eq!(covered, rows as u64 * cols as u64);
+        assert!(grid.tile_range(grid.tile_count()).is_none());
+    }
+}
+
+#[test]
+fn degenerate() {
+    for &(rows, cols) in &[(1, 1000), (1000, 1)] {
+        let data: Vec<u16> = (0..1000).collect();
+        let mut buf = vec![];
+        write_grid(FIELD, rows, cols, &data, &mut buf).unwrap();
+        let grid = read::<u16>(&buf);
+        assert_eq!(grid, Grid {rows, cols, data});
+        assert_eq!(grid.tile_count(), 13);
+        assert_eq!(grid.tile_range(12).map(|x| x.0[1] - x.0[0] + x.1[1] - x.1[0]), Some(41));
+    }
+}
+
+#[test]
+fn tile_updates() {
+    let mut grid = Grid {rows: 100, cols: 200, data: field(100, 200)};
+    assert_eq!(grid.tile_count(), 6);
+    let mut buf = vec![];
+    grid.write(FIELD, &mut buf).unwrap();
+    // Only the tile at the second tile row, third tile column changes.
+    let (rows, cols) = grid.tile_range(5).unwrap();
+    assert_eq!((rows, cols), ([80, 100], [160, 200]));
+    for i in rows[0]..rows[1] {
+        for j in cols[0]..cols[1] {
+            grid.data[(i * 200 + j) as usize] = -1.0;
+        }
+    }
+    let mut update = vec![];
+    grid.write_tiles(FIELD, vec![5], &mut update).unwrap();
+    assert!(update.len() < buf.len() / 5);
+
+    let mut res = read::<f32>(&buf);
+    let mut r = &update[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    res.read(state.unwrap(), ty, prop, &mut r).unwrap();
+    assert_eq!(res, grid);
+
+    assert!(grid.write_tiles(FIELD, vec![6], &mut vec![]).is_err());
+}
+
+#[test]
+fn invalid() {
+    // The data does not fit the shape.
+    let err = write_grid(FIELD, 2, 3, &[0_u8; 5], &mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    let mut buf = vec![];
+    write_grid(FIELD, 2, 3, &[0_u8; 6], &mut buf).unwrap();
+    let mut r = &buf[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let err = read_grid::<u16, _>(state.unwrap(), ty, prop, &mut r).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.property(), Some(FIELD));
+}
@@ -0,0 +1,161 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::{self, Write};
+
+const POSITION: u16 = 1;
+const NOTE: u16 = 2;
+
+fn format() -> u16 {Type::F32.vector(3).unwrap().0}
+
+fn item(x: f32) -> Vec<u8> {
+    let mut data = vec![];
+    [x; 3].write_element(&mut data).unwrap();
+    data
+}
+
+/// Property id with blocks of offset and data.
+type Property = (u16, Vec<(u64, Vec<u8>)>);
+
+/// Reads every property.
+fn read(data: &[u8]) -> Vec<Property> {
+    validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+    let mut r = PoolReader::new(data);
+    r.register(POSITION);
+    r.register(NOTE);
+    let mut res = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        res.push((prop, blocks.into_iter().map(|b| (b.offset, b.data)).collect()));
+    }
+    res
+}
+
+#[test]
+fn same_as_raw_blocks() {
+    let mut a = vec![];
+    let mut block = begin_block(format(), POSITION, &mut a).unwrap();
+    assert_eq!((block.type_format(), block.property_id()), (format(), POSITION));
+    block.write(0, &item(1.0)).unwrap();
+    // Empty data is skipped.
+    block.write(5, &[]).unwrap();
+    block.write(3, &[item(2.0), item(3.0)].concat()).unwrap();
+    block.finish().unwrap();
+
+    let mut b = vec![];
+    RawBlock::write_property(format(), POSITION, &[
+        RawBlock {type_format: format(), property_id: POSITION, offset: 0, data: item(1.0)},
+        RawBlock {
+            type_format: format(),
+            property_id: POSITION,
+            offset: 3,
+            data: [item(2.0), item(3.0)].concat(),
+        },
+    ], &mut b).unwrap();
+    assert_eq!(a, b);
+
+    // Without blocks, the property is empty.
+    let mut a = vec![];
+    begin_block(format(), POSITION, &mut a).unwrap().finish().unwrap();
+    let mut b = vec![];
+    RawBlock::write_property(format(), POSITION, &[], &mut b).unwrap();
+    assert_eq!(a, b);
+}
+
+/// Writes positions, failing before the given index.
+fn write_positions<W: Write>(fail_at: usize, w: &mut W) -> io::Result<()> {
+    let mut block = begin_block(format(), POSITION, w)?;
+    for i in 0..5 {
+        if i == fail_at {
+            return Err(io::Error::other("no more positions"));
+        }
+        block.write(i as u64, &item(i as f32))?;
+    }
+    block.finish()
+}
+
+#[test]
+fn dropped_by_early_return() {
+    for fail_at in 0..6 {
+        let mut data = vec![];
+        let res = write_positions(fail_at, &mut data);
+        assert_eq!(res.is_err(), fail_at < 5);
+        // The stream stays valid, and a property can follow.
+        Scalar::write_array(NOTE, &b"after".to_vec(), &mut data).unwrap();
+        State::new().end_type_formats(&mut data).unwrap();
+        let props = read(&data);
+        assert_eq!(props.len(), 2);
+        let blocks: Vec<_> = (0..fail_at.min(5)).map(|i| (i as u64, item(i as f32))).collect();
+        assert_eq!(props[0], (POSITION, blocks));
+        assert_eq!(props[1], (NOTE, vec![(0, b"after".to_vec())]));
+    }
+}
+
+#[test]
+fn big_endian() {
+    let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big);
+    let _ = write_positions(2, &mut w);
+    write_positions(5, &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let props = read(&data);
+    assert_eq!(props.len(), 2);
+    // The headers and data of the blocks are converted.
+    assert_eq!(props[0].1, vec![(0, item(0.0)), (1, item(1.0))]);
+    assert_eq!(props[1].1.len(), 5);
+    assert_eq!(props[1].1[4], (4, item(4.0)));
+}
+
+/// A sink that fails after `limit` bytes.
+struct Failing {
+    data: Vec<u8>,
+    limit: usize,
+}
+
+impl Write for Failing {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.limit - self.data.len());
+        if n == 0 && !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::WriteZero, "disk full"));
+        }
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {Ok(())}
+}
+
+#[test]
+fn broken_writes() {
+    // Fails in the middle of the second block.
+    let mut w = Failing {data: vec![], limit: 4 + 16 + 12 + 20};
+    {
+        let mut block = begin_block(format(), POSITION, &mut w).unwrap();
+        block.write(0, &item(1.0)).unwrap();
+        assert_eq!(block.write(1, &item(2.0)).unwrap_err().kind(), io::ErrorKind::WriteZero);
+        // Nothing more is written, since the stream can not be made valid.
+        assert_eq!(block.write(2, &item(3.0)).unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(block.finish().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+    assert_eq!(w.data.len(), w.limit);
+    let mut w = Failing {data: vec![], limit: 4 + 16 + 12 + 20};
+    {
+        let mut block = begin_block(format(), POSITION, &mut w).unwrap();
+        block.write(0, &item(1.0)).unwrap();
+        let _ = block.write(1, &item(2.0));
+    }
+    assert_eq!(w.data.len(), w.limit);
+
+    // Fails when writing the end of bytes.
+    let mut w = Failing {data: vec![], limit: 4 + 16 + 12 + 4};
+    let mut block = begin_block(format(), POSITION, &mut w).unwrap();
+    block.write(0, &item(1.0)).unwrap();
+    assert_eq!(block.finish().unwrap_err().kind(), io::ErrorKind::WriteZero);
+
+    // Type format 0 is the end of stream.
+    let mut data = vec![];
+    let err = begin_block(0, POSITION, &mut data).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    assert!(data.is_empty());
+}
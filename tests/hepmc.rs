@@ -0,0 +1,182 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const SAMPLE: &str = include_str!("data/events.hepmc3");
+
+/// The data of an event.
+#[derive(Debug, PartialEq)]
+struct Event {
+    number: Option<f64>,
+    weight: Vec<f64>,
+    momentum: Vec<[f64; 4]>,
+    pdg_id: Vec<i32>,
+    status: Vec<i32>,
+    mass: Vec<f64>,
+}
+
+fn import(text: &str, mapping: &HepMcMapping) -> std::io::Result<Vec<u8>> {
+    let mut w = vec![];
+    import_hepmc3(text.as_bytes(), &mut w, mapping)?;
+    Ok(w)
+}
+
+fn events(data: &[u8]) -> Vec<Event> {
+    let m = HepMcMapping::default();
+    let mut r = FrameReader::with_time(data, m.event);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        // Every array is read on its own, since empty arrays are not written.
+        let mut ev = Event {
+            number: frame.time,
+            weight: vec![],
+            momentum: vec![],
+            pdg_id: vec![],
+            status: vec![],
+            mass: vec![],
+        };
+        frame.read_array(m.weight.unwrap(), &mut ev.weight).unwrap();
+        frame.read_array(m.momentum.unwrap(), &mut ev.momentum).unwrap();
+        frame.read_array(m.pdg_id.unwrap(), &mut ev.pdg_id).unwrap();
+        frame.read_array(m.status.unwrap(), &mut ev.status).unwrap();
+        frame.read_array(m.mass.unwrap(), &mut ev.mass).unwrap();
+        res.push(ev);
+    }
+    res
+}
+
+#[test]
+fn sample_file() {
+    let mut data = vec![];
+    let n = import_hepmc3(SAMPLE.as_bytes(), &mut data, &HepMcMapping::default()).unwrap();
+    assert_eq!(n, 3);
+    validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+    let events = events(&data);
+    assert_eq!(events, vec![
+        Event {
+            number: Some(0.0),
+            weight: vec![1.0],
+            momentum: vec![
+                [0.0, 0.0, 6500.0, 6500.0],
+                [0.0, 0.0, -6500.0, 6500.0],
+                [1.5, -2.0, 30.0, 96.0],
+                [0.5, 0.25, 10.0, 10.02],
+                [1.0, -2.25, 20.0, 20.1],
+            ],
+            pdg_id: vec![2212, 2212, 23, 11, -11],
+            status: vec![4, 4, 62, 1, 1],
+            mass: vec![0.938272, 0.938272, 91.1876, 0.000511, 0.000511],
+        },
+        Event {
+            number: Some(1.0),
+            weight: vec![2.5],
+            momentum: vec![
+                [0.0, 0.0, 6500.0, 6500.0],
+                [0.0, 0.0, -6500.0, 6500.0],
+                [-4.0, 3.0, 0.0, 5.0],
+            ],
+            pdg_id: vec![2212, 2212, 22],
+            status: vec![4, 4, 1],
+            mass: vec![0.938272, 0.938272, 0.0],
+        },
+        // An event without particles has the weight only.
+        Event {
+            number: Some(7.0),
+            weight: vec![0.125],
+            momentum: vec![],
+            pdg_id: vec![],
+            status: vec![],
+            mass: vec![],
+        },
+    ]);
+
+    // The names and the momentum unit of the first event come before the first frame.
+    let m = HepMcMapping::default();
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+    while r.next_property().unwrap().is_some() {}
+    let names: Vec<_> = [m.event, 1, 2, 3, 4, 5].iter()
+        .map(|&id| r.metadata(id).and_then(|m| m.name()).unwrap().to_string())
+        .collect();
+    assert_eq!(names, vec!["event", "weight", "momentum", "pdg_id", "status", "mass"]);
+    for id in [m.momentum, m.mass].iter().map(|id| id.unwrap()) {
+        let unit = r.units().get(id).unwrap();
+        assert_eq!(unit.name, "GEV");
+        assert_eq!(unit.scale, Some(1.602176634e-10));
+    }
+    assert!(r.units().get(m.pdg_id.unwrap()).is_none());
+}
+
+#[test]
+fn mapping() {
+    let mapping = HepMcMapping::default()
+        .event(10)
+        .weight(None)
+        .momentum(Some(11))
+        .pdg_id(None)
+        .status(Some(12))
+        .mass(None);
+    let data = import(SAMPLE, &mapping).unwrap();
+    let mut r = FrameReader::with_time(&data[..], 10);
+    let mut ids = vec![];
+    let mut momenta = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        for block in &frame.blocks {
+            if !ids.contains(&block.property_id) {ids.push(block.property_id)}
+        }
+        let mut momentum: Vec<[f64; 4]> = vec![];
+        frame.read_array(11, &mut momentum).unwrap();
+        momenta += momentum.len();
+    }
+    ids.sort_unstable();
+    assert_eq!(ids, vec![10, 11, 12]);
+    assert_eq!(momenta, 8);
+
+    let mapping = HepMcMapping::default().status(Some(1));
+    let err = import(SAMPLE, &mapping).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::PropertyConflict, Some(1)));
+}
+
+#[test]
+fn malformed_lines() {
+    let lines: Vec<&str> = SAMPLE.lines().collect();
+    // Replaces a line of the sample and returns the line number of the error.
+    let replace = |n: usize, line: &str| {
+        let mut text = lines.clone();
+        text[n - 1] = line;
+        let err = import(&text.join("\n"), &HepMcMapping::default()).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", line);
+        err.line()
+    };
+    // A number that does not parse.
+    assert_eq!(replace(9, "P 1 0 2212 0.0 x 6500.0 6500.0 0.938272 4"), Some(9));
+    // Missing and extra fields.
+    assert_eq!(replace(9, "P 1 0 2212 0.0 0.0 6500.0 6500.0 0.938272"), Some(9));
+    assert_eq!(replace(9, "P 1 0 2212 0.0 0.0 6500.0 6500.0 0.938272 4 7"), Some(9));
+    // Particle ids out of order.
+    assert_eq!(replace(10, "P 3 0 2212 0.0 0.0 -6500.0 6500.0 0.938272 4"), Some(10));
+    // Another number of particles than declared is reported at the event line.
+    assert_eq!(replace(5, "E 0 1 6"), Some(5));
+    assert_eq!(replace(15, "E 1 1 4"), Some(15));
+    assert_eq!(replace(5, "E zero 1 5"), Some(5));
+    assert_eq!(replace(7, "W heavy"), Some(7));
+    assert_eq!(replace(6, "U GEV"), Some(6));
+    // Another unit than the first event.
+    assert_eq!(replace(16, "U MEV MM"), Some(15));
+    assert_eq!(replace(8, "X 1 2 3"), Some(8));
+    // Records outside of the listing.
+    assert_eq!(replace(2, "HepMC::Asciiv3-END_EVENT_LISTING"), Some(2));
+    let mut text = SAMPLE.to_string();
+    text.push_str("E 8 0 0\n");
+    let err = import(&text, &HepMcMapping::default()).unwrap_err();
+    assert_eq!(Error::from_io(&err).line(), Some(lines.len() as u64 + 1));
+    // Particles before the first event.
+    assert_eq!(replace(3, "P 1 0 22 0.0 0.0 1.0 1.0 0.0 1"), Some(3));
+
+    // The error message has the line number.
+    let err = import("HepMC::Asciiv3-START_EVENT_LISTING\nE 0 0 x\n", &HepMcMapping::default());
+    assert!(format!("{}", Error::from_io(&err.unwrap_err())).contains('2'));
+}
@@ -0,0 +1,256 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io;
+
+const TIME: u16 = 0;
+const ENERGY: u16 = 1;
+const POSITION: u16 = 2;
+const WEIGHT: u16 = 3;
+const CHARGE: u16 = 4;
+
+/// The arrays written in a frame, with the weight of the frame.
+struct Frame {
+    weight: Option<f64>,
+    energy: Vec<f64>,
+    position: Vec<[f32; 3]>,
+    charge: Vec<i16>,
+}
+
+/// Writes frames with energies partly outside `0..10`, positions and charges,
+/// and a weight in some frames.
+fn stream(seed: u64, frames: usize) -> (Vec<u8>, Vec<Frame>) {
+    let mut rng = Rng::new(seed);
+    let mut w = FrameWriter::new(vec![], TIME);
+    // Data before the first frame counts as a frame.
+    let preamble = Frame {weight: None, energy: vec![5.5], position: vec![], charge: vec![]};
+    Scalar::write_array(ENERGY, &preamble.energy, &mut w).unwrap();
+    let mut res = vec![preamble];
+    for i in 0..frames {
+        w.begin_frame(i as f64).unwrap();
+        let n = 1 + rng.below(6) as usize;
+        let frame = Frame {
+            weight: if rng.below(3) == 0 {None} else {Some(0.5 + rng.unit())},
+            energy: (0..n).map(|_| rng.unit() * 14.0 - 2.0).collect(),
+            position: (0..n).map(|_| {
+                [rng.unit() as f32 * 2.4 - 1.2, rng.unit() as f32 * 2.4 - 1.2, 0.0]
+            }).collect(),
+            charge: (0..1 + rng.below(4)).map(|_| rng.below(5) as i16 - 2).collect(),
+        };
+        if let Some(weight) = frame.weight {
+            Scalar::write_array(WEIGHT, &vec![weight], &mut w).unwrap();
+        }
+        Scalar::write_array(ENERGY, &frame.energy, &mut w).unwrap();
+        Vector::write_array(POSITION, &frame.position, &mut w).unwrap();
+        Scalar::write_array(CHARGE, &frame.charge, &mut w).unwrap();
+        res.push(frame);
+    }
+    // The preamble is filled last.
+    let preamble = res.remove(0);
+    res.push(preamble);
+    (w.finish().unwrap(), res)
+}
+
+/// Returns the bin of a value by brute force, with 0 for underflow.
+fn bin(b: &Binning, x: f64) -> usize {
+    if x < b.min {return 0}
+    for i in 0..b.bins {
+        let upper = b.min + (b.max - b.min) * (i + 1) as f64 / b.bins as f64;
+        if x < upper {return i + 1}
+    }
+    b.bins + 1
+}
+
+/// Counts with underflow and overflow, and the weighted values in range.
+type Filled = (Vec<f64>, Vec<(f64, f64)>);
+
+fn brute_force(b: &Binning, values: &[(f64, f64)]) -> Filled {
+    let mut counts = vec![0.0; b.bins + 2];
+    let mut in_range = vec![];
+    for &(x, w) in values {
+        let i = bin(b, x);
+        counts[i] += w;
+        if i > 0 && i <= b.bins {in_range.push((x, w))}
+    }
+    (counts, in_range)
+}
+
+fn mean_variance(values: &[(f64, f64)]) -> (f64, f64) {
+    let sum_w: f64 = values.iter().map(|v| v.1).sum();
+    let mean = values.iter().map(|v| v.0 * v.1).sum::<f64>() / sum_w;
+    let var = values.iter().map(|v| (v.0 - mean).powi(2) * v.1).sum::<f64>() / sum_w;
+    (mean, var)
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * (1.0 + a.abs().max(b.abs()))
+}
+
+fn assert_close(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len());
+    for (x, y) in a.iter().zip(b) {assert!(close(*x, *y), "{:?} != {:?}", a, b)}
+}
+
+fn one_d(h: &Histogram) -> &Histogram1D {
+    match *h {
+        Histogram::OneD(ref h) => h,
+        _ => panic!("not a histogram of one variable"),
+    }
+}
+
+fn two_d(h: &Histogram) -> &Histogram2D {
+    match *h {
+        Histogram::TwoD(ref h) => h,
+        _ => panic!("not a histogram of two variables"),
+    }
+}
+
+fn check_1d(h: &Histogram1D, b: &Binning, values: &[(f64, f64)]) {
+    let (counts, in_range) = brute_force(b, values);
+    assert_close(h.counts(), &counts[1..=b.bins]);
+    assert!(close(h.underflow(), counts[0]));
+    assert!(close(h.overflow(), counts[b.bins + 1]));
+    assert_eq!(h.entries(), values.len() as u64);
+    let (mean, var) = mean_variance(&in_range);
+    assert!(close(h.mean(), mean) && close(h.variance(), var));
+}
+
+fn specs() -> Vec<HistogramSpec> {
+    vec![
+        HistogramSpec::new(ENERGY, 0, Binning::new(10, 0.0, 10.0)).weight(WEIGHT),
+        HistogramSpec::new(CHARGE, 0, Binning::new(3, -1.0, 2.0)),
+        HistogramSpec::new(POSITION, 0, Binning::new(4, -1.0, 1.0))
+            .y(POSITION, 1, Binning::new(5, -1.0, 1.0)),
+        HistogramSpec::new(ENERGY, 0, Binning::new(6, 0.0, 12.0))
+            .y(CHARGE, 0, Binning::new(2, -1.0, 1.0)),
+    ]
+}
+
+#[test]
+fn against_brute_force() {
+    let (data, frames) = stream(177, 30);
+    let hists = fill_histograms(&mut &data[..], TIME, &specs()).unwrap();
+    let specs = specs();
+
+    let energies: Vec<(f64, f64)> = frames.iter()
+        .flat_map(|f| f.energy.iter().map(move |&e| (e, f.weight.unwrap_or(1.0))))
+        .collect();
+    check_1d(one_d(&hists[0]), &specs[0].x_binning, &energies);
+    assert!(one_d(&hists[0]).underflow() > 0.0 && one_d(&hists[0]).overflow() > 0.0);
+    let charges: Vec<(f64, f64)> = frames.iter()
+        .flat_map(|f| f.charge.iter().map(|&c| (c as f64, 1.0)))
+        .collect();
+    check_1d(one_d(&hists[1]), &specs[1].x_binning, &charges);
+
+    // Pairs of instances written in both properties.
+    let pairs = |h: &Histogram2D, pairs: Vec<(f64, f64)>| {
+        let (bx, by) = h.binning();
+        let mut counts = vec![vec![0.0; by.bins + 2]; bx.bins + 2];
+        for &(x, y) in &pairs {counts[bin(bx, x)][bin(by, y)] += 1.0}
+        for (i, col) in counts.iter().enumerate() {
+            for (j, &c) in col.iter().enumerate() {assert_eq!(h.get(i, j), Some(c))}
+        }
+        assert_eq!(h.get(bx.bins + 2, 0), None);
+        assert_eq!(h.entries(), pairs.len() as u64);
+        let in_range: Vec<&(f64, f64)> = pairs.iter()
+            .filter(|p| (1..=bx.bins).contains(&bin(bx, p.0)))
+            .filter(|p| (1..=by.bins).contains(&bin(by, p.1)))
+            .collect();
+        let xs: Vec<(f64, f64)> = in_range.iter().map(|p| (p.0, 1.0)).collect();
+        let ys: Vec<(f64, f64)> = in_range.iter().map(|p| (p.1, 1.0)).collect();
+        let (mx, vx) = mean_variance(&xs);
+        let (my, vy) = mean_variance(&ys);
+        assert!(close(h.mean().0, mx) && close(h.mean().1, my));
+        assert!(close(h.variance().0, vx) && close(h.variance().1, vy));
+    };
+    pairs(two_d(&hists[2]), frames.iter()
+        .flat_map(|f| f.position.iter().map(|p| (p[0] as f64, p[1] as f64)))
+        .collect());
+    pairs(two_d(&hists[3]), frames.iter()
+        .flat_map(|f| f.energy.iter().zip(&f.charge).map(|(&e, &c)| (e, c as f64)))
+        .collect());
+    assert_eq!(two_d(&hists[2]).edges().0, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+}
+
+#[test]
+fn under_and_overflow() {
+    let b = Binning::new(4, 0.0, 2.0);
+    let mut h = Histogram1D::new(b).unwrap();
+    assert_eq!(h.edges(), vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+    let values = [-0.1, f64::NEG_INFINITY, 0.0, 0.5, 1.999, 2.0, 7.0, f64::INFINITY];
+    for &x in &values {h.fill(x, 2.0)}
+    h.fill(f64::NAN, 1.0);
+    assert_eq!(h.underflow(), 4.0);
+    assert_eq!(h.counts(), &[2.0, 2.0, 0.0, 2.0]);
+    assert_eq!(h.overflow(), 6.0);
+    assert_eq!(h.entries(), 8);
+    // Only values in range count for the mean.
+    assert!(close(h.mean(), (0.0 + 0.5 + 1.999) / 3.0));
+    assert!(Histogram1D::new(b).unwrap().mean().is_nan());
+
+    let mut h = Histogram2D::new(b, Binning::new(1, 0.0, 1.0)).unwrap();
+    h.fill(-1.0, 2.0, 1.0);
+    h.fill(1.0, -1.0, 1.0);
+    h.fill(3.0, 0.5, 1.0);
+    h.fill(1.0, f64::NAN, 1.0);
+    assert_eq!((h.get(0, 2), h.get(3, 0), h.get(5, 1)), (Some(1.0), Some(1.0), Some(1.0)));
+    assert_eq!(h.entries(), 3);
+    assert!(h.mean().0.is_nan());
+
+    for b in &[Binning::new(0, 0.0, 1.0), Binning::new(2, 1.0, 1.0),
+               Binning::new(2, 0.0, f64::INFINITY), Binning::new(2, f64::NAN, 1.0)] {
+        assert_eq!(Histogram1D::new(*b).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        let err = fill_histograms(&mut &[][..], TIME, &[HistogramSpec::new(ENERGY, 0, *b)]);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn merge_across_files() {
+    let (a, _) = stream(1, 20);
+    let (b, _) = stream(2, 15);
+    let mut merged = fill_histograms(&mut &a[..], TIME, &specs()).unwrap();
+    let other = fill_histograms(&mut &b[..], TIME, &specs()).unwrap();
+    for (h, o) in merged.iter_mut().zip(&other) {h.merge(o).unwrap()}
+
+    // The same as filling both files in one histogram.
+    let mut whole = fill_histograms(&mut &a[..], TIME, &specs()).unwrap();
+    for (h, spec) in whole.iter_mut().zip(specs()) {
+        let mut hb = fill_histograms(&mut &b[..], TIME, &[spec]).unwrap();
+        h.merge(&hb.remove(0)).unwrap();
+    }
+    assert_eq!(merged, whole);
+    let (h, b1) = (one_d(&merged[1]), one_d(&other[1]));
+    assert_eq!(h.entries(), one_d(&whole[1]).entries());
+    assert!(h.entries() > b1.entries());
+
+    // Histograms with other binnings or dimensions do not merge.
+    let mut h = merged[0].clone();
+    assert_eq!(h.merge(&merged[1]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(h.merge(&merged[2]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let mut h2 = two_d(&merged[2]).clone();
+    assert_eq!(h2.merge(two_d(&merged[3])).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn components() {
+    let (data, frames) = stream(3, 10);
+    // The second component of positions.
+    let spec = HistogramSpec::new(POSITION, 1, Binning::new(8, -1.0, 1.0));
+    let hists = fill_histograms(&mut &data[..], TIME, &[spec]).unwrap();
+    let ys: Vec<(f64, f64)> = frames.iter()
+        .flat_map(|f| f.position.iter().map(|p| (p[1] as f64, 1.0)))
+        .collect();
+    check_1d(one_d(&hists[0]), &Binning::new(8, -1.0, 1.0), &ys);
+
+    let spec = HistogramSpec::new(POSITION, 3, Binning::new(8, -1.0, 1.0));
+    let err = fill_histograms(&mut &data[..], TIME, &[spec]).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::DimensionOutOfRange, Some(POSITION)));
+    assert_eq!(err.range(), Some((0, 3)));
+}
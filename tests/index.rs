@@ -0,0 +1,164 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use std::io::Cursor;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 1;
+const DATA: u16 = 2;
+const FRAMES: u64 = 1000;
+
+/// Writes frames at times `0.0, 0.5, 1.0, ..` with the frame number as data,
+/// with a preamble before the first frame.
+fn frames(n: u64) -> Vec<u8> {
+    let mut w = vec![];
+    Scalar::write_array(DATA, &vec![u32::MAX], &mut w).unwrap();
+    let mut w = FrameWriter::new(w, TIME);
+    for i in 0..n {
+        w.begin_frame(i as f64 * 0.5).unwrap();
+        // Frames of different sizes.
+        Scalar::write_array(DATA, &vec![i as u32; 1 + i as usize % 7], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn read_cursor(data: &[u8], index: &Index, cursor: &FrameCursor) -> Frame {
+    let mut r = Cursor::new(data);
+    r.set_position(cursor.position);
+    let mut fr = FrameReader::with_time(r, index.marker());
+    fr.resume(cursor).unwrap();
+    fr.next_frame().unwrap().unwrap()
+}
+
+fn build(data: &[u8]) -> Index {
+    Index::build(&mut Cursor::new(data), TIME, Some(TIME)).unwrap()
+}
+
+#[test]
+fn seek_random_times() {
+    let data = frames(FRAMES);
+    let index = build(&data);
+    assert_eq!(index.frame_count(), FRAMES);
+    assert!(index.is_monotonic());
+    let mut rng = Rng::new(108);
+    for _ in 0..200 {
+        let t = rng.unit() * FRAMES as f64 * 0.5;
+        let mut r = Cursor::new(&data[..]);
+        let cursor = index.seek_time(t, &mut r).unwrap();
+        assert_eq!(r.position(), cursor.position);
+        let n = (t / 0.5).floor() as u64;
+        assert_eq!(cursor.frame, n, "t = {}", t);
+        let frame = read_cursor(&data, &index, &cursor);
+        assert_eq!(frame.number, n);
+        assert_eq!(frame.time, Some(n as f64 * 0.5));
+        assert!(frame.time.unwrap() <= t);
+        let mut items: Vec<u32> = vec![];
+        frame.read_array(DATA, &mut items).unwrap();
+        assert_eq!(items, vec![n as u32; 1 + n as usize % 7]);
+    }
+}
+
+#[test]
+fn seek_edges() {
+    let data = frames(FRAMES);
+    let index = build(&data);
+    let mut r = Cursor::new(&data[..]);
+    assert_eq!(index.seek_time(-1.0, &mut r).unwrap().frame, 0);
+    assert_eq!(index.seek_time(1e9, &mut r).unwrap().frame, FRAMES - 1);
+    assert_eq!(index.seek_time(10.0, &mut r).unwrap().frame, 20);
+    assert_eq!(index.seek_time(10.49, &mut r).unwrap().frame, 20);
+
+    let nearest = index.clone().seek_mode(SeekMode::Nearest);
+    assert_eq!(nearest.seek_time(10.49, &mut r).unwrap().frame, 21);
+    assert_eq!(nearest.seek_time(10.2, &mut r).unwrap().frame, 20);
+    assert_eq!(nearest.seek_time(-1.0, &mut r).unwrap().frame, 0);
+}
+
+#[test]
+fn non_monotonic_times() {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for &t in &[0.0, 2.0, 1.0, 3.0] {
+        w.begin_frame(t).unwrap();
+    }
+    let data = w.finish().unwrap();
+    let index = build(&data);
+    assert!(!index.is_monotonic());
+    // The nearest frame is picked, regardless of the seek mode.
+    let mut r = Cursor::new(&data[..]);
+    assert_eq!(index.seek_time(1.1, &mut r).unwrap().frame, 2);
+    assert_eq!(index.seek_time(1.9, &mut r).unwrap().frame, 1);
+}
+
+#[test]
+fn no_times() {
+    let mut data = vec![];
+    for i in 0..3_u32 {
+        Scalar::write_array(DATA, &vec![i], &mut data).unwrap();
+    }
+    State::new().end_type_formats(&mut data).unwrap();
+    let index = Index::build(&mut Cursor::new(&data[..]), DATA, None).unwrap();
+    assert_eq!(index.frame_count(), 3);
+    assert!(index.seek_time(0.0, &mut Cursor::new(&data[..])).is_err());
+}
+
+#[test]
+fn seek_frames_in_random_order() {
+    let n = 200;
+    let data = frames(n);
+    let index = build(&data);
+
+    let mut sequential = vec![];
+    let mut fr = FrameReader::with_time(&data[..], TIME);
+    while let Some(frame) = fr.next_frame().unwrap() {
+        sequential.push(frame);
+    }
+    assert_eq!(sequential.len() as u64, index.frame_count());
+
+    let mut order: Vec<u64> = (0..n).collect();
+    Rng::new(109).shuffle(&mut order);
+    for &i in &order {
+        let mut r = Cursor::new(&data[..]);
+        let cursor = index.seek_frame(i, &mut r).unwrap();
+        assert_eq!(cursor.frame, i);
+        let frame = read_cursor(&data, &index, &cursor);
+        let expected = &sequential[i as usize];
+        assert_eq!((frame.number, frame.time), (expected.number, expected.time));
+        assert_eq!(frame.blocks, expected.blocks);
+    }
+}
+
+#[test]
+fn frame_ranges() {
+    let data = frames(10);
+    let index = build(&data);
+    let preamble = index.preamble_range();
+    assert_eq!(preamble.start, 0);
+    let mut end = preamble.end;
+    for i in 0..10 {
+        let range = index.frame_range(i).unwrap();
+        assert_eq!(range.start, end);
+        assert!(range.end > range.start);
+        end = range.end;
+    }
+    // Everything but the end of stream is covered.
+    assert_eq!(end, data.len() as u64 - 2);
+}
+
+#[test]
+fn frame_out_of_range() {
+    let data = frames(10);
+    let index = build(&data);
+    for err in [
+        index.seek_frame(10, &mut Cursor::new(&data[..])).unwrap_err(),
+        index.frame_range(u64::MAX).unwrap_err(),
+    ] {
+        let err = Error::from_io(&err);
+        assert_eq!(err.kind(), ErrorKind::FrameOutOfRange);
+        assert_eq!(err.range(), Some((0, 10)));
+    }
+}
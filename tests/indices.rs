@@ -0,0 +1,65 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const INDICES: u16 = 3;
+
+fn read(data: &[u8]) -> std::io::Result<Vec<usize>> {
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r)?;
+    assert_eq!(prop, INDICES);
+    let mut arr = vec![];
+    read_index_array(state.unwrap(), ty, &mut arr, &mut r)?;
+    Ok(arr)
+}
+
+#[test]
+fn written_as_u64() {
+    let mut data = vec![];
+    write_index_array(INDICES, &[1, 2, 300], &mut data).unwrap();
+    let mut expected = vec![];
+    Scalar::write_array(INDICES, &vec![1_u64, 2, 300], &mut expected).unwrap();
+    assert_eq!(data, expected);
+    assert_eq!(read(&data).unwrap(), vec![1, 2, 300]);
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn above_u32_max() {
+    let big = [u32::MAX as usize + 1, 1 << 40, usize::MAX, 0];
+    let mut data = vec![];
+    write_index_array(INDICES, &big, &mut data).unwrap();
+    assert_eq!(read(&data).unwrap(), big.to_vec());
+}
+
+#[test]
+fn read_u32() {
+    let mut data = vec![];
+    Scalar::write_array(INDICES, &vec![7_u32, u32::MAX], &mut data).unwrap();
+    assert_eq!(read(&data).unwrap(), vec![7, u32::MAX as usize]);
+
+    // Blocks at an offset fill the indices before with zeros.
+    let mut data = vec![];
+    RawBlock::write_property(Type::U32.scalar().0, INDICES, &[RawBlock {
+        type_format: Type::U32.scalar().0,
+        property_id: INDICES,
+        offset: 2,
+        data: 5_u32.to_le_bytes().to_vec(),
+    }], &mut data).unwrap();
+    assert_eq!(read(&data).unwrap(), vec![0, 0, 5]);
+}
+
+#[test]
+fn other_types() {
+    let mut data = vec![];
+    Scalar::write_array(INDICES, &vec![1_i64], &mut data).unwrap();
+    let err = read(&data).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+
+    let mut data = vec![];
+    Scalar::write_array(INDICES, &vec![1_u16], &mut data).unwrap();
+    let err = read(&data).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+}
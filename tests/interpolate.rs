@@ -0,0 +1,206 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const VELOCITY: u16 = 2;
+const ROTATION: u16 = 3;
+const ID: u16 = 4;
+const NOTE: u16 = 5;
+
+/// The arrays of a frame.
+#[derive(Clone, Debug, PartialEq)]
+struct Arrays {
+    positions: Vec<[f32; 3]>,
+    velocities: Vec<f64>,
+    rotations: Vec<[[f32; 2]; 2]>,
+    ids: Vec<u32>,
+}
+
+impl Arrays {
+    fn random(rng: &mut Rng, n: usize) -> Arrays {
+        Arrays {
+            positions: (0..n).map(|_| [rng.unit() as f32, rng.unit() as f32, -1.0]).collect(),
+            velocities: (0..n).map(|_| rng.unit() * 10.0).collect(),
+            rotations: (0..n).map(|_| [[rng.unit() as f32, 0.0], [0.0, 1.0]]).collect(),
+            ids: (0..n).map(|_| rng.below(1000) as u32).collect(),
+        }
+    }
+
+    fn read(frame: &Frame) -> Arrays {
+        let mut res = Arrays {
+            positions: vec![],
+            velocities: vec![],
+            rotations: vec![],
+            ids: vec![],
+        };
+        frame.read_array(POSITION, &mut res.positions).unwrap();
+        frame.read_array(VELOCITY, &mut res.velocities).unwrap();
+        frame.read_array(ROTATION, &mut res.rotations).unwrap();
+        frame.read_array(ID, &mut res.ids).unwrap();
+        res
+    }
+}
+
+/// Writes the arrays as frames at times `0, 1, ...` and reads the frames.
+fn frames(arrays: &[Arrays]) -> Vec<Frame> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for (i, a) in arrays.iter().enumerate() {
+        w.begin_frame(i as f64 * 0.1).unwrap();
+        Vector::write_array(POSITION, &a.positions, &mut w).unwrap();
+        Scalar::write_array(VELOCITY, &a.velocities, &mut w).unwrap();
+        Matrix::write_array(ROTATION, &a.rotations, &mut w).unwrap();
+        Scalar::write_array(ID, &a.ids, &mut w).unwrap();
+        let note = vec![i as u8; 3];
+        write_block(Type::offset_custom_format(), NOTE, 0, &note, &mut w).unwrap();
+    }
+    let data = w.finish().unwrap();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {res.push(frame)}
+    res
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-12 * a.abs().max(b.abs()).max(1.0)
+}
+
+fn interpolator() -> Interpolator {
+    Interpolator::new(&[POSITION, VELOCITY, ROTATION, ID, NOTE])
+}
+
+#[test]
+fn endpoints() {
+    let mut rng = Rng::new(163);
+    for _ in 0..20 {
+        let a = Arrays::random(&mut rng, 10);
+        let b = Arrays::random(&mut rng, 10);
+        let f = frames(&[a.clone(), b.clone()]);
+        let start = interpolator().blend(&f[0], &f[1], 0.0).unwrap();
+        assert_eq!(Arrays::read(&start), a);
+        assert_eq!((start.number, start.time), (0, Some(0.0)));
+        let end = interpolator().blend(&f[0], &f[1], 1.0).unwrap();
+        // Integers keep the values of the earlier frame.
+        assert_eq!(Arrays::read(&end), Arrays {ids: a.ids.clone(), ..b.clone()});
+        assert_eq!((end.number, end.time), (0, Some(0.1)));
+        // Custom formats too.
+        let notes: Vec<_> = end.blocks_of(NOTE).map(|b| b.data.clone()).collect();
+        assert_eq!(notes, vec![vec![0; 3]]);
+        // Unless the nearer frame is used.
+        let end = interpolator().nearest(true).blend(&f[0], &f[1], 1.0).unwrap();
+        assert_eq!(Arrays::read(&end), b);
+        let notes: Vec<_> = end.blocks_of(NOTE).map(|b| b.data.clone()).collect();
+        assert_eq!(notes, vec![vec![1; 3]]);
+    }
+}
+
+#[test]
+fn linear() {
+    let mut rng = Rng::new(1163);
+    for _ in 0..20 {
+        let a = Arrays::random(&mut rng, 10);
+        let b = Arrays::random(&mut rng, 10);
+        let f = frames(&[a.clone(), b.clone()]);
+        let mid = Arrays::read(&interpolator().blend(&f[0], &f[1], 0.5).unwrap());
+        let half = |x: f32, y: f32| ((x as f64 + y as f64) / 2.0) as f32;
+        for i in 0..10 {
+            for j in 0..3 {
+                assert_eq!(mid.positions[i][j], half(a.positions[i][j], b.positions[i][j]));
+            }
+            assert!(close(mid.velocities[i], (a.velocities[i] + b.velocities[i]) / 2.0));
+            assert_eq!(mid.rotations[i][0][0], half(a.rotations[i][0][0], b.rotations[i][0][0]));
+            assert_eq!(mid.rotations[i][1], [0.0, 1.0]);
+        }
+        assert_eq!(mid.ids, a.ids);
+
+        // Equal steps of alpha give equal steps of values.
+        let at = |alpha| Arrays::read(&interpolator().blend(&f[0], &f[1], alpha).unwrap());
+        let (x, y, z) = (at(0.25), at(0.5), at(0.75));
+        for i in 0..10 {
+            let step = y.velocities[i] - x.velocities[i];
+            assert!(close(z.velocities[i] - y.velocities[i], step));
+            assert!(close(step, (b.velocities[i] - a.velocities[i]) / 4.0));
+        }
+    }
+}
+
+#[test]
+fn different_lengths() {
+    let mut rng = Rng::new(2163);
+    let short = Arrays::random(&mut rng, 5);
+    let long = Arrays::random(&mut rng, 8);
+    let f = frames(&[short.clone(), long.clone()]);
+    // The result has the length of the nearer frame.
+    let early = Arrays::read(&interpolator().blend(&f[0], &f[1], 0.25).unwrap());
+    assert_eq!(early.velocities.len(), 5);
+    let late = Arrays::read(&interpolator().blend(&f[0], &f[1], 0.75).unwrap());
+    assert_eq!(late.velocities.len(), 8);
+    for i in 0..5 {
+        let v = short.velocities[i] + (long.velocities[i] - short.velocities[i]) * 0.75;
+        assert!(close(late.velocities[i], v));
+    }
+    // Items only in one frame are taken from it.
+    assert_eq!(&late.velocities[5..], &long.velocities[5..]);
+    assert_eq!(&late.positions[5..], &long.positions[5..]);
+    assert_eq!(&late.rotations[5..], &long.rotations[5..]);
+
+    // Particles destroyed in the later frame.
+    let f = frames(&[long.clone(), short.clone()]);
+    let early = Arrays::read(&interpolator().blend(&f[0], &f[1], 0.25).unwrap());
+    assert_eq!(early.velocities.len(), 8);
+    assert_eq!(&early.velocities[5..], &long.velocities[5..]);
+    let late = Arrays::read(&interpolator().blend(&f[0], &f[1], 0.75).unwrap());
+    assert_eq!(late.velocities.len(), 5);
+}
+
+#[test]
+fn changes_and_missing_properties() {
+    let mut rng = Rng::new(3163);
+    let a = Arrays::random(&mut rng, 4);
+    let f = frames(std::slice::from_ref(&a));
+    // The later frame writes a change of one velocity only, in two blocks.
+    let ty = Type::F64.scalar().0;
+    let mut blocks = vec![];
+    for (offset, x) in &[(0_u64, 1.0_f64), (2, 3.0)] {
+        let mut data = vec![];
+        x.write_element(&mut data).unwrap();
+        blocks.push(RawBlock {type_format: ty, property_id: VELOCITY, offset: *offset, data});
+    }
+    let b = Frame {number: 1, time: None, blocks};
+    let mid = interpolator().blend(&f[0], &b, 0.25).unwrap();
+    // Items missing between the blocks are zero.
+    let v = &a.velocities;
+    let expected = [v[0] + (1.0 - v[0]) * 0.25, v[1] * 0.75, v[2] + (3.0 - v[2]) * 0.25];
+    let mut velocities: Vec<f64> = vec![];
+    mid.read_array(VELOCITY, &mut velocities).unwrap();
+    assert_eq!(velocities.len(), 4);
+    for i in 0..3 {assert!(close(velocities[i], expected[i]))}
+    assert_eq!(velocities[3], v[3]);
+    // Properties missing in the later frame keep their values, and the time of the first.
+    assert_eq!(mid.time, Some(0.0));
+    let mut positions: Vec<[f32; 3]> = vec![];
+    mid.read_array(POSITION, &mut positions).unwrap();
+    assert_eq!(positions, a.positions);
+
+    // Another type format is not interpolated.
+    let mut data = vec![];
+    9.0_f32.write_element(&mut data).unwrap();
+    let ty = Type::F32.scalar().0;
+    let block = RawBlock {type_format: ty, property_id: VELOCITY, offset: 0, data};
+    let b = Frame {number: 1, time: None, blocks: vec![block]};
+    let mid = interpolator().blend(&f[0], &b, 0.5).unwrap();
+    let mut velocities: Vec<f64> = vec![];
+    mid.read_array(VELOCITY, &mut velocities).unwrap();
+    assert_eq!(velocities, a.velocities);
+
+    for &alpha in &[-0.1, 1.5, f64::NAN] {
+        let err = interpolator().blend(&f[0], &f[0], alpha).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
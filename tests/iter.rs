@@ -0,0 +1,153 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 3;
+
+fn positions(n: usize) -> Vec<[f32; 3]> {
+    (0..n).map(|i| [i as f32, 2.0 * i as f32, -(i as f32)]).collect()
+}
+
+#[test]
+fn same_as_write_array() {
+    for n in 1..20 {
+        let pos = positions(n);
+        let mut a = vec![];
+        write_from_iter(POSITION, pos.iter().cloned(), &mut a).unwrap();
+        let mut b = vec![];
+        Vector::write_array(POSITION, &pos, &mut b).unwrap();
+        assert_eq!(a, b);
+
+        let mass: Vec<f64> = (0..n).map(|i| i as f64 * 0.5).collect();
+        let mut a = vec![];
+        write_from_iter(POSITION, mass.iter().cloned(), &mut a).unwrap();
+        let mut b = vec![];
+        Scalar::write_array(POSITION, &mass, &mut b).unwrap();
+        assert_eq!(a, b);
+
+        let rot: Vec<[[i16; 2]; 2]> = (0..n as i16).map(|i| [[i, -i], [i * 2, 1]]).collect();
+        let mut a = vec![];
+        write_from_iter(POSITION, rot.iter().cloned(), &mut a).unwrap();
+        let mut b = vec![];
+        Matrix::write_array(POSITION, &rot, &mut b).unwrap();
+        assert_eq!(a, b);
+    }
+}
+
+#[test]
+fn computed_on_the_fly() {
+    // Positions from a layout of separate arrays.
+    let xs = [1.0_f32, 2.0, 3.0];
+    let ys = [4.0_f32, 5.0, 6.0];
+    let zs = [7.0_f32, 8.0, 9.0];
+    let iter = (0..3).map(|i| [xs[i], ys[i], zs[i]]);
+    let mut data = vec![];
+    write_from_iter(POSITION, iter, &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<[f32; 3]> = vec![];
+    Vector::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap();
+    assert_eq!(arr, vec![[1.0, 4.0, 7.0], [2.0, 5.0, 8.0], [3.0, 6.0, 9.0]]);
+}
+
+#[test]
+fn empty() {
+    let mut a = vec![];
+    write_from_iter(POSITION, Vec::<u32>::new(), &mut a).unwrap();
+    let mut b = vec![];
+    RawBlock::write_property(Type::U32.vector(1).unwrap().0, POSITION, &[], &mut b).unwrap();
+    assert_eq!(a, b);
+    let mut a = vec![];
+    write_from_iter_chunked(POSITION, Vec::<u32>::new(), 4, &mut a).unwrap();
+    assert_eq!(a, b);
+}
+
+/// An iterator that reports a length that differs from the number of items.
+struct Lying {
+    len: usize,
+    items: usize,
+}
+
+impl Iterator for Lying {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        if self.items == 0 {return None}
+        self.items -= 1;
+        Some(self.items as u16)
+    }
+}
+
+impl ExactSizeIterator for Lying {
+    fn len(&self) -> usize {self.len}
+}
+
+fn mismatch(len: usize, items: usize) -> Error {
+    let mut data = vec![];
+    let err = write_from_iter(POSITION, Lying {len, items}, &mut data).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    Error::from_io(&err)
+}
+
+#[test]
+fn length_mismatch() {
+    for &(len, items, instance) in &[(5, 3, 3), (3, 5, 3), (0, 1, 0), (1, 0, 0)] {
+        let err = mismatch(len, items);
+        assert_eq!(err.kind(), ErrorKind::LengthMismatch);
+        assert_eq!(err.property(), Some(POSITION));
+        assert_eq!(err.instance(), Some(instance), "{} {}", len, items);
+    }
+}
+
+/// Reads the blocks of a property as offsets and items.
+fn blocks(data: &[u8]) -> Vec<(u64, Vec<[f32; 3]>)> {
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    assert_eq!(prop, POSITION);
+    let blocks = RawBlock::read_property(state.unwrap(), ty, prop, &mut r).unwrap();
+    assert!(r.is_empty());
+    blocks.iter().map(|b| {
+        let items = b.data.chunks(12).map(|mut item| {
+            let mut x = [0.0; 3];
+            x.read_element(&mut item).unwrap();
+            x
+        }).collect();
+        (b.offset, items)
+    }).collect()
+}
+
+#[test]
+fn unknown_length() {
+    for n in 1..30 {
+        for &chunk_len in &[1, 4, 7, 29, 100] {
+            let pos = positions(n);
+            // Filtering hides the length.
+            let iter = pos.iter().cloned().filter(|_| true);
+            let mut data = vec![];
+            write_from_iter_chunked(POSITION, iter, chunk_len, &mut data).unwrap();
+            let blocks = blocks(&data);
+            assert_eq!(blocks.len(), n.div_ceil(chunk_len));
+            let mut offset = 0;
+            for (start, items) in &blocks {
+                assert_eq!(*start, offset);
+                assert!(!items.is_empty() && items.len() <= chunk_len);
+                assert_eq!(&items[..], &pos[offset as usize..offset as usize + items.len()]);
+                offset += items.len() as u64;
+            }
+            assert_eq!(offset, n as u64);
+
+            // One chunk is the same as an array.
+            if n <= chunk_len {
+                let mut b = vec![];
+                Vector::write_array(POSITION, &pos, &mut b).unwrap();
+                assert_eq!(data, b);
+            }
+        }
+    }
+    let mut data = vec![];
+    let err = write_from_iter_chunked(POSITION, positions(3), 0, &mut data).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(data.is_empty());
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::Cursor;
+
+const TIME: u16 = 0;
+const ENERGY: u16 = 1;
+const COUNT: u16 = 2;
+const POSITION: u16 = 3;
+
+const FRAMES: usize = 20;
+
+/// Returns `true` if the count is written in a frame.
+fn has_count(frame: usize) -> bool {
+    !frame.is_multiple_of(3) && frame != 10
+}
+
+fn energy(frame: usize) -> f64 {
+    frame as f64 * 1.5
+}
+
+/// Writes the total energy in every frame and the particle count in some,
+/// with two instances each.
+fn stream() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    Scalar::write_array(COUNT, &vec![99_u32, 99], &mut w).unwrap();
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64).unwrap();
+        Scalar::write_array(ENERGY, &vec![energy(i), -energy(i)], &mut w).unwrap();
+        if has_count(i) {Scalar::write_array(COUNT, &vec![i as u32, 0], &mut w).unwrap()}
+        if i.is_multiple_of(2) {
+            Vector::write_array(POSITION, &vec![[i as f32; 2]], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap()
+}
+
+type Row = (f64, Option<f64>, Option<u32>);
+
+fn join(data: &[u8], opts: &JoinOptions) -> Vec<Row> {
+    let mut r = Cursor::new(data);
+    let rows = join_frames_as::<f64, u32, _>(&mut r, TIME, (ENERGY, COUNT), opts).unwrap();
+    rows.map(|x| x.unwrap()).collect()
+}
+
+#[test]
+fn skip() {
+    let rows = join(&stream(), &JoinOptions::default());
+    let expected: Vec<Row> = (0..FRAMES)
+        .filter(|&i| has_count(i))
+        .map(|i| (i as f64, Some(energy(i)), Some(i as u32)))
+        .collect();
+    assert_eq!(rows, expected);
+    assert_eq!(rows.len(), 12);
+}
+
+#[test]
+fn yield_none() {
+    let rows = join(&stream(), &JoinOptions::default().missing(Missing::Yield));
+    let expected: Vec<Row> = (0..FRAMES)
+        .map(|i| (i as f64, Some(energy(i)), if has_count(i) {Some(i as u32)} else {None}))
+        .collect();
+    assert_eq!(rows, expected);
+
+    // The second instance.
+    let opts = JoinOptions::default().missing(Missing::Yield).instance(1);
+    let rows = join(&stream(), &opts);
+    assert_eq!(rows[0], (0.0, Some(-0.0), None));
+    assert_eq!(rows[1], (1.0, Some(-1.5), Some(0)));
+
+    // Instances past the end of the arrays are missing.
+    let opts = JoinOptions::default().missing(Missing::Yield).instance(2);
+    let rows = join(&stream(), &opts);
+    assert_eq!(rows.len(), FRAMES);
+    assert!(rows.iter().all(|r| r.1.is_none() && r.2.is_none()));
+}
+
+#[test]
+fn carry_forward() {
+    let rows = join(&stream(), &JoinOptions::default().missing(Missing::CarryForward));
+    // The count before the first frame is not carried, so the first frame is skipped.
+    let mut expected = vec![];
+    let mut last = None;
+    for i in 0..FRAMES {
+        if has_count(i) {last = Some(i as u32)}
+        if last.is_some() {expected.push((i as f64, Some(energy(i)), last))}
+    }
+    assert_eq!(rows, expected);
+    assert_eq!(rows.len(), FRAMES - 1);
+    assert_eq!(rows[9], (10.0, Some(15.0), Some(8)));
+}
+
+#[test]
+fn dynamic_and_n_properties() {
+    let data = stream();
+    let mut r = Cursor::new(&data);
+    let opts = JoinOptions::default().missing(Missing::Yield);
+    let rows: Vec<_> = join_frames(&mut r, TIME, (ENERGY, POSITION), &opts).unwrap()
+        .map(|x| x.unwrap())
+        .collect();
+    assert_eq!(rows.len(), FRAMES);
+    for (i, (t, energy, pos)) in rows.into_iter().enumerate() {
+        assert_eq!(t, i as f64);
+        assert_eq!(energy.unwrap().to_f64(), vec![self::energy(i)]);
+        match pos {
+            Some(pos) => {
+                assert_eq!(pos.dim(), (1, 2));
+                assert_eq!(pos.decode::<[f32; 2]>().unwrap(), [i as f32; 2]);
+            }
+            None => assert!(!i.is_multiple_of(2)),
+        }
+    }
+
+    let mut r = Cursor::new(&data);
+    let properties = [COUNT, POSITION, ENERGY];
+    let rows: Vec<_> = join_frames_n(&mut r, TIME, &properties, &Default::default()).unwrap()
+        .map(|x| x.unwrap())
+        .collect();
+    let times: Vec<f64> = rows.iter().map(|r| r.0).collect();
+    assert_eq!(times, vec![2.0, 4.0, 8.0, 14.0, 16.0]);
+    for (t, values) in &rows {
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0].as_ref().unwrap().get(0), Some(*t));
+        assert_eq!(values[2].as_ref().unwrap().get(0), Some(energy(*t as usize)));
+    }
+
+    // Another element type is an error.
+    let mut r = Cursor::new(&data);
+    let mut rows = join_frames_as::<f32, u32, _>(&mut r, TIME, (ENERGY, COUNT), &opts).unwrap();
+    let err = rows.next().unwrap().unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+}
@@ -0,0 +1,159 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes frames of the given number of particles, returning the stream,
+/// the position where each frame starts and the end of the last frame.
+fn stream(particles: &[usize], footer: bool) -> (Vec<u8>, Vec<usize>) {
+    let mut w = PoolWriter::new(vec![]).with_footer_index(footer);
+    w.write_header().unwrap();
+    let mut starts = vec![];
+    for (i, &n) in particles.iter().enumerate() {
+        starts.push(w.get_ref().len());
+        Time::write(TIME, i as f64 * 0.5, &mut w).unwrap();
+        Vector::write_array(POSITION, &vec![[i as f32; 3]; n], &mut w).unwrap();
+        Scalar::write_array(MASS, &vec![i as f64; n], &mut w).unwrap();
+    }
+    starts.push(w.get_ref().len());
+    (w.finish().unwrap(), starts)
+}
+
+/// Checks that a frame is the frame with index `i`.
+fn check(frame: &Frame, i: usize, n: usize) {
+    assert_eq!((frame.number, frame.time), (0, Some(i as f64 * 0.5)));
+    let mut pos: Vec<[f32; 3]> = vec![];
+    frame.read_array(POSITION, &mut pos).unwrap();
+    assert_eq!(pos, vec![[i as f32; 3]; n]);
+    let mut mass: Vec<f64> = vec![];
+    frame.read_array(MASS, &mut mass).unwrap();
+    assert_eq!(mass, vec![i as f64; n]);
+}
+
+/// Counts the bytes read through it.
+struct Counted<R> {
+    inner: R,
+    read: u64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Counted<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn clean_files() {
+    let mut rng = Rng::new(198);
+    // Frames of a few hundred bytes up to more than the part of the tail read at first.
+    let particles: Vec<usize> = (0..200).map(|_| 1 + rng.below(3000) as usize).collect();
+    for &footer in &[false, true] {
+        let (data, _) = stream(&particles, footer);
+        let mut r = Counted {inner: Cursor::new(&data), read: 0};
+        let frame = last_frame(&mut r, TIME).unwrap();
+        check(&frame, particles.len() - 1, particles[particles.len() - 1]);
+        // Only the tail is read.
+        assert!(r.read < data.len() as u64 / 4, "{} of {}", r.read, data.len());
+    }
+
+    // A last frame that is larger than the tail read at first.
+    let (data, _) = stream(&[10, 20, 10_000], false);
+    check(&last_frame(&mut Cursor::new(&data), TIME).unwrap(), 2, 10_000);
+
+    // A file with a single frame, with or without a header.
+    for &footer in &[false, true] {
+        let (data, _) = stream(&[5], footer);
+        check(&last_frame(&mut Cursor::new(&data), TIME).unwrap(), 0, 5);
+    }
+    let mut data = vec![];
+    Time::write(TIME, 0.0, &mut data).unwrap();
+    Scalar::write_array(MASS, &vec![0.0_f64; 3], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let frame = last_frame(&mut Cursor::new(&data), TIME).unwrap();
+    assert_eq!((frame.time, frame.blocks.len()), (Some(0.0), 2));
+}
+
+#[test]
+fn torn_tails() {
+    let particles = [3, 1, 4, 1, 5, 9, 2, 6];
+    let (data, starts) = stream(&particles, false);
+    let mut props = vec![];
+    let mut r = &data[..];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        let start = data.len() - r.len() - 4;
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        props.push(start..data.len() - r.len());
+    }
+    // The frame with the last property that starts before a position.
+    let frame = |pos: usize, inclusive: bool| {
+        starts.iter().rposition(|&s| s < pos || inclusive && s == pos)
+    };
+    for cut in 0..data.len() - 2 {
+        let i = match props.iter().find(|p| p.start < cut && cut < p.end) {
+            // The stream ends after a property, which may be the end of a frame.
+            None => frame(cut, false),
+            // A property with a torn header may belong to the frame before,
+            // so the frame before that one is returned.
+            Some(p) if cut - p.start < 4 => frame(p.start, false).and_then(|i| i.checked_sub(1)),
+            // Otherwise, the frame before the torn one is returned.
+            Some(p) => frame(p.start, true).and_then(|i| i.checked_sub(1)),
+        };
+        let last = last_frame(&mut Cursor::new(&data[..cut]), TIME);
+        let i = match i {
+            Some(i) => i,
+            None => {
+                // A torn header or first frame has no complete frame before it.
+                let err = last.unwrap_err();
+                assert_eq!(Error::from_io(&err).kind(), ErrorKind::FrameOutOfRange, "{}", cut);
+                continue;
+            }
+        };
+        let last = last.unwrap();
+        if cut < starts[i + 1] {
+            // Only the properties of the frame before the cut are in it.
+            let kept = props.iter().filter(|p| p.start >= starts[i] && p.end <= cut).count();
+            assert_eq!((last.time, last.blocks.len()), (Some(i as f64 * 0.5), kept), "{}", cut);
+        } else {
+            check(&last, i, particles[i]);
+        }
+    }
+
+    // Without frames.
+    let mut data = vec![];
+    Scalar::write_array(MASS, &vec![0.0_f64; 3], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let err = last_frame(&mut Cursor::new(&data), TIME).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::FrameOutOfRange);
+}
+
+#[test]
+fn torn_tail_of_a_large_file() {
+    let particles: Vec<usize> = (0..40).map(|i| 500 + i * 100).collect();
+    let (data, starts) = stream(&particles, false);
+    let last = particles.len() - 1;
+    let mut rng = Rng::new(2);
+    for _ in 0..50 {
+        // Cut inside the last frame, or just after it.
+        let cut = starts[last] + 1 + rng.below((starts[last + 1] - starts[last]) as u64) as usize;
+        let frame = last_frame(&mut Cursor::new(&data[..cut]), TIME).unwrap();
+        let i = if cut == starts[last + 1] {last} else {last - 1};
+        check(&frame, i, particles[i]);
+    }
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+fn positions(frame: u32) -> Vec<[f32; 3]> {
+    (0..100).map(|i| [frame as f32, i as f32, 0.5]).collect()
+}
+
+/// Writes 1000 frames of 100 positions and masses.
+fn recording() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for frame in 0..1000 {
+        w.begin_frame(frame as f64).unwrap();
+        Vector::write_array(POSITION, &positions(frame), &mut w).unwrap();
+        Scalar::write_array(MASS, &vec![frame as f64; 100], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// Records the byte ranges that are read.
+struct Counting<'a> {
+    inner: Cursor<&'a [u8]>,
+    reads: Vec<Range<u64>>,
+}
+
+impl<'a> Read for Counting<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.inner.position();
+        let n = self.inner.read(buf)?;
+        if n > 0 {self.reads.push(start..start + n as u64)}
+        Ok(n)
+    }
+}
+
+impl<'a> Seek for Counting<'a> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn load_frame_500_only() {
+    let data = recording();
+    let index = Index::build(&mut Cursor::new(&data[..]), TIME, Some(TIME)).unwrap();
+    assert_eq!(index.frame_count(), 1000);
+    let range = index.frame_range(500).unwrap();
+
+    let mut r = Counting {inner: Cursor::new(&data[..]), reads: vec![]};
+    let frame = index.lazy_frame(500, &mut r).unwrap();
+    assert_eq!(frame.number(), 500);
+    assert_eq!(frame.time(), Some(500.0));
+    assert_eq!(frame.property_ids(), vec![TIME, POSITION, MASS]);
+    let headers: u64 = r.reads.iter().map(|x| x.end - x.start).sum();
+    // Only headers are read, not the 2000 bytes of positions and masses.
+    assert!(headers < 200, "{} bytes of headers", headers);
+
+    let pos = frame.property::<[f32; 3]>(POSITION).unwrap();
+    let mass = frame.property::<f64>(MASS).unwrap();
+    assert_eq!(pos.load(&mut r).unwrap(), positions(500));
+    assert_eq!(mass.load(&mut r).unwrap(), vec![500.0; 100]);
+
+    for read in &r.reads {
+        let inside = read.start >= range.start && read.end <= range.end;
+        assert!(inside, "{:?} not in {:?}", read, range);
+    }
+    let total: u64 = r.reads.iter().map(|x| x.end - x.start).sum();
+    assert_eq!(total - headers, 100 * 12 + 100 * 8);
+}
+
+#[test]
+fn handles_are_independent() {
+    let data = recording();
+    let index = Index::build(&mut Cursor::new(&data[..]), TIME, Some(TIME)).unwrap();
+    let mut r = Cursor::new(&data[..]);
+    let a = index.lazy_frame(3, &mut r).unwrap().property::<[f32; 3]>(POSITION).unwrap();
+    let b = index.lazy_frame(700, &mut r).unwrap().property::<[f32; 3]>(POSITION).unwrap();
+    let c = a.clone();
+    // Loading in any order gives the data of each frame.
+    assert_eq!(b.load(&mut r).unwrap(), positions(700));
+    assert_eq!(c.load(&mut r).unwrap(), positions(3));
+    assert_eq!(a.load(&mut r).unwrap(), positions(3));
+    assert_eq!(a.blocks(), c.blocks());
+}
+
+#[test]
+fn type_checked_at_load() {
+    let data = recording();
+    let index = Index::build(&mut Cursor::new(&data[..]), TIME, Some(TIME)).unwrap();
+    let mut r = Cursor::new(&data[..]);
+    let frame = index.lazy_frame(10, &mut r).unwrap();
+    assert!(frame.property::<f64>(42).is_none());
+    let wrong = frame.property::<[f64; 3]>(POSITION).unwrap();
+    let err = wrong.load(&mut r).unwrap_err();
+    assert_eq!(Error::from_io(&err).property(), Some(POSITION));
+    let wrong = frame.property::<f32>(MASS).unwrap();
+    assert!(wrong.load(&mut r).is_err());
+}
@@ -0,0 +1,224 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const SAMPLE: &str = include_str!("data/events.lhe");
+const LOOSE: &str = include_str!("data/loose.lhe");
+
+/// The data of an event.
+#[derive(Debug, Default, PartialEq)]
+struct Event {
+    number: Option<f64>,
+    process_id: Option<i32>,
+    weight: Option<f64>,
+    scale: Option<f64>,
+    aqcd: Option<f64>,
+    pdg_id: Vec<i32>,
+    status: Vec<i32>,
+    mothers: Vec<[i32; 2]>,
+    colors: Vec<[i32; 2]>,
+    momentum: Vec<[f64; 4]>,
+    mass: Vec<f64>,
+    lifetime: Vec<f64>,
+    spin: Vec<f64>,
+}
+
+fn import(text: &str, opts: &LheOptions) -> std::io::Result<(u64, Vec<u8>)> {
+    let mut w = vec![];
+    let n = import_lhe(text.as_bytes(), &mut w, opts)?;
+    Ok((n, w))
+}
+
+fn events(data: &[u8]) -> Vec<Event> {
+    let o = LheOptions::default();
+    let mut r = FrameReader::with_time(data, o.event);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut ev = Event {number: frame.time, ..Event::default()};
+        ev.process_id = frame.read_property(o.process_id.unwrap()).unwrap();
+        ev.weight = frame.read_property(o.weight.unwrap()).unwrap();
+        ev.scale = frame.read_property(o.scale.unwrap()).unwrap();
+        ev.aqcd = frame.read_property(o.aqcd.unwrap()).unwrap();
+        frame.read_array(o.pdg_id.unwrap(), &mut ev.pdg_id).unwrap();
+        frame.read_array(o.status.unwrap(), &mut ev.status).unwrap();
+        frame.read_array(o.mothers.unwrap(), &mut ev.mothers).unwrap();
+        frame.read_array(o.colors.unwrap(), &mut ev.colors).unwrap();
+        frame.read_array(o.momentum.unwrap(), &mut ev.momentum).unwrap();
+        frame.read_array(o.mass.unwrap(), &mut ev.mass).unwrap();
+        frame.read_array(o.lifetime.unwrap(), &mut ev.lifetime).unwrap();
+        frame.read_array(o.spin.unwrap(), &mut ev.spin).unwrap();
+        res.push(ev);
+    }
+    res
+}
+
+/// Counts the `<event>` tags and the particles declared in the first line of every event.
+fn count(text: &str) -> (usize, usize) {
+    let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+    let (mut events, mut particles) = (0, 0);
+    while let Some(line) = lines.next() {
+        if !line.starts_with("<event") {continue}
+        events += 1;
+        particles += lines.next().unwrap().split_whitespace().next().unwrap()
+            .parse::<usize>().unwrap();
+    }
+    (events, particles)
+}
+
+#[test]
+fn round_trip_counts() {
+    for &(text, expected) in &[(SAMPLE, (3, 10)), (LOOSE, (2, 4))] {
+        assert_eq!(count(text), expected);
+        let (n, data) = import(text, &LheOptions::default()).unwrap();
+        assert_eq!(n, expected.0 as u64);
+        assert!(validate(&mut &data[..], &ValidateOptions::default()).unwrap().is_ok());
+
+        let events = events(&data);
+        assert_eq!(events.len(), expected.0);
+        let numbers: Vec<Option<f64>> = events.iter().map(|ev| ev.number).collect();
+        assert_eq!(numbers, (0..expected.0).map(|i| Some(i as f64)).collect::<Vec<_>>());
+        for ev in &events {
+            let n = ev.pdg_id.len();
+            for len in &[ev.status.len(), ev.mothers.len(), ev.colors.len(), ev.momentum.len(),
+                ev.mass.len(), ev.lifetime.len(), ev.spin.len()]
+            {
+                assert_eq!(*len, n);
+            }
+        }
+        assert_eq!(events.iter().map(|ev| ev.pdg_id.len()).sum::<usize>(), expected.1);
+    }
+}
+
+#[test]
+fn sample_file() {
+    let (_, data) = import(SAMPLE, &LheOptions::default()).unwrap();
+    let events = events(&data);
+    assert_eq!(events[0].process_id, Some(1));
+    assert_eq!(events[0].weight, Some(512.34));
+    assert_eq!(events[0].scale, Some(91.188));
+    assert_eq!(events[0].aqcd, Some(0.13));
+    assert_eq!(events[0].pdg_id, vec![2, -2, 23, 11, -11]);
+    assert_eq!(events[0].status, vec![-1, -1, 2, 1, 1]);
+    assert_eq!(events[0].mothers, vec![[0, 0], [0, 0], [1, 2], [3, 3], [3, 3]]);
+    assert_eq!(events[0].colors[..2], [[501, 0], [0, 501]]);
+    assert_eq!(events[0].momentum[3], [12.0, -5.0, 30.0, 33.0]);
+    assert_eq!(events[0].mass[2], 91.188);
+    assert_eq!(events[0].spin, vec![-1.0, 1.0, 0.0, -1.0, 1.0]);
+
+    // The weights after the particles are skipped.
+    assert_eq!((events[1].process_id, events[1].weight), (Some(2), Some(250.0)));
+    assert_eq!(events[1].pdg_id, vec![21, 21, 25]);
+    assert_eq!(events[1].lifetime, vec![0.0, 0.0, 1e-12]);
+    assert_eq!(events[2].weight, Some(-512.34));
+
+    // The names and the init block come before the first frame.
+    let o = LheOptions::default();
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+    while r.next_property().unwrap().is_some() {}
+    let names: Vec<&str> = (0..14).map(|id| r.metadata(id).and_then(|m| m.name()).unwrap())
+        .collect();
+    assert_eq!(names, vec![
+        "event", "process_id", "weight", "scale", "aqed", "aqcd", "pdg_id", "status",
+        "mothers", "colors", "momentum", "mass", "lifetime", "spin",
+    ]);
+    let init = r.metadata(o.event).and_then(|m| m.get(LHE_INIT)).unwrap();
+    assert_eq!(init.lines().count(), 3);
+    assert!(init.starts_with("2212 2212 6.500000e+03"));
+    assert!(init.ends_with("</generator>"));
+}
+
+#[test]
+fn loose_formatting() {
+    let (_, data) = import(LOOSE, &LheOptions::default()).unwrap();
+    let events = events(&data);
+    // Fortran exponents.
+    assert_eq!((events[0].weight, events[0].scale), (Some(1.0), Some(91.188)));
+    assert_eq!(events[0].momentum[2], [30.0, 20.0, 10.0, 400.0]);
+    assert_eq!(events[0].mass, vec![0.0, 0.0, 173.0, 173.0]);
+    assert_eq!(events[0].colors, vec![[501, 502], [502, 503], [501, 0], [0, 503]]);
+    // An event without particles has the event scalars only.
+    assert_eq!(events[1], Event {
+        number: Some(1.0),
+        process_id: Some(81),
+        weight: Some(0.5),
+        scale: Some(91.188),
+        aqcd: Some(0.118),
+        ..Event::default()
+    });
+}
+
+#[test]
+fn options() {
+    let opts = LheOptions::default()
+        .event(20)
+        .process_id(None)
+        .momentum(Some(21))
+        .mass(None)
+        .lifetime(None)
+        .spin(None)
+        .colors(None);
+    let (n, data) = import(SAMPLE, &opts).unwrap();
+    assert_eq!(n, 3);
+    let mut r = FrameReader::with_time(&data[..], 20);
+    let mut ids = vec![];
+    let mut momenta = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        for block in &frame.blocks {
+            if !ids.contains(&block.property_id) {ids.push(block.property_id)}
+        }
+        let mut momentum: Vec<[f64; 4]> = vec![];
+        frame.read_array(21, &mut momentum).unwrap();
+        momenta += momentum.len();
+    }
+    ids.sort_unstable();
+    assert_eq!(ids, vec![2, 3, 4, 5, 6, 7, 8, 20, 21]);
+    assert_eq!(momenta, 10);
+
+    let err = import(SAMPLE, &LheOptions::default().spin(Some(2))).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::PropertyConflict, Some(2)));
+}
+
+#[test]
+fn malformed_lines() {
+    let lines: Vec<&str> = SAMPLE.lines().collect();
+    let line_of = |prefix: &str| lines.iter().position(|l| l.starts_with(prefix)).unwrap() + 1;
+    // Replaces a line of the sample and returns the line number of the error.
+    let replace = |n: usize, line: &str| {
+        let mut text = lines.clone();
+        text[n - 1] = line;
+        let err = import(&text.join("\n"), &LheOptions::default()).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!(err.kind(), ErrorKind::InvalidData, "{}", line);
+        err.line()
+    };
+    let first = line_of("<event>") + 1;
+    // A number that does not parse, missing and extra fields.
+    assert_eq!(replace(first, " 5 1 x 91.188 0.0075 0.13"), Some(first as u64));
+    assert_eq!(replace(first, " 5 1 512.34 91.188 0.0075"), Some(first as u64));
+    assert_eq!(replace(first + 1, "2 -1 0 0 501 0 0 0 45.6 45.6 0 0"), Some(first as u64 + 1));
+    assert_eq!(replace(first + 1, "2 -1 0 0 501 0 0 0 45.6 45.6 0 0 -1 7"),
+        Some(first as u64 + 1));
+    // Lines after the particles are read as particles when more are declared,
+    // and an event that ends first is reported at its tag.
+    assert_eq!(replace(first, " 6 1 512.34 91.188 0.0075 0.13"), Some(line_of("<mgrwt>") as u64));
+    let last = lines.iter().rposition(|l| l.starts_with("<event>")).unwrap() + 1;
+    assert_eq!(replace(last + 1, " 3 1 -512.34 91.188 0.0075 0.13"), Some(last as u64));
+    // Text outside of blocks, and blocks that are not closed.
+    assert_eq!(replace(line_of("</init>") + 1, "junk"), Some(line_of("</init>") as u64 + 1));
+    assert_eq!(replace(lines.len() - 1, "<!-- -->"), Some(last as u64));
+    let err = import("<header>\n<event>\n", &LheOptions::default()).unwrap_err();
+    assert_eq!(Error::from_io(&err).line(), Some(2));
+    // A second init block.
+    let err = import(&SAMPLE.replace("</header>", "</header>\n<init>\n</init>"),
+        &LheOptions::default()).unwrap_err();
+    assert_eq!(Error::from_io(&err).line(), Some(line_of("<init>") as u64 + 2));
+
+    // No events.
+    let (n, data) = import("<LesHouchesEvents>\n</LesHouchesEvents>\n", &LheOptions::default())
+        .unwrap();
+    assert_eq!(n, 0);
+    assert!(events(&data).is_empty());
+}
@@ -0,0 +1,105 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::Cursor;
+
+use binpool::*;
+
+const PROP: u16 = 1;
+
+fn u8_block(offset: u64, len: usize) -> RawBlock {
+    RawBlock {type_format: Type::U8.scalar().0, property_id: PROP, offset, data: vec![0; len]}
+}
+
+/// Writes a property with one `u8` block per entry of `(offset, len)`.
+fn stream(properties: &[&[(u64, usize)]]) -> Vec<u8> {
+    let mut w = vec![];
+    for blocks in properties {
+        let blocks: Vec<RawBlock> = blocks.iter()
+            .map(|&(offset, len)| u8_block(offset, len))
+            .collect();
+        RawBlock::write_property(Type::U8.scalar().0, PROP, &blocks, &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// Reads a stream with limits in every reader that accepts limits,
+/// returning the limit that was exceeded by each.
+fn exceeded(data: &[u8], limits: Limits) -> Vec<Option<Limit>> {
+    let limit = |res: std::io::Result<()>| match res {
+        Ok(()) => None,
+        Err(err) => {
+            let err = Error::from_io(&err);
+            assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+            assert_eq!(err.property(), Some(PROP));
+            err.limit()
+        }
+    };
+    let mut reader = PoolReader::new(data).limits(limits).unknown(Unknown::Skip);
+    let opts = ValidateOptions {limits, ..ValidateOptions::default()};
+    let mut frames = FrameReader::new(data, PROP).limits(limits);
+    vec![
+        limit(reader.next_property().map(|_| ())),
+        limit(validate(&mut &data[..], &opts).map(|_| ())),
+        limit(Index::build_with_limits(&mut Cursor::new(data), PROP, None, limits).map(|_| ())),
+        limit((|| {while frames.next_frame()?.is_some() {} Ok(())})()),
+    ]
+}
+
+fn check(data: &[u8], limits: Limits, limit: Limit) {
+    assert_eq!(exceeded(data, limits), vec![Some(limit); 4], "{:?}", limit);
+    assert_eq!(exceeded(data, Limits::unlimited()), vec![None; 4], "{:?}", limit);
+}
+
+#[test]
+fn max_block_bytes() {
+    let limits = Limits {max_block_bytes: Some(3), ..Limits::default()};
+    check(&stream(&[&[(0, 4)]]), limits, Limit::BlockBytes);
+    assert_eq!(exceeded(&stream(&[&[(0, 3)]]), limits), vec![None; 4]);
+}
+
+#[test]
+fn max_total_payload() {
+    let limits = Limits {max_total_payload: Some(5), ..Limits::default()};
+    check(&stream(&[&[(0, 3)], &[(0, 3)]]), limits, Limit::TotalPayload);
+    assert_eq!(exceeded(&stream(&[&[(0, 3)], &[(0, 2)]]), limits), vec![None; 4]);
+}
+
+#[test]
+fn max_blocks() {
+    let limits = Limits {max_blocks: Some(2), ..Limits::default()};
+    check(&stream(&[&[(0, 1), (1, 1), (2, 1)]]), limits, Limit::Blocks);
+    // Blocks are counted per property header.
+    let data = stream(&[&[(0, 1), (1, 1)], &[(0, 1), (1, 1)]]);
+    assert_eq!(exceeded(&data, limits), vec![None; 4]);
+}
+
+#[test]
+fn max_instances_per_property() {
+    let limits = Limits {max_instances_per_property: Some(10), ..Limits::default()};
+    // A block of one byte at a huge offset, before anything is allocated.
+    check(&stream(&[&[(u64::MAX - 1, 1)]]), limits, Limit::InstancesPerProperty);
+    check(&stream(&[&[(10, 1)]]), limits, Limit::InstancesPerProperty);
+    assert_eq!(exceeded(&stream(&[&[(9, 1)]]), limits), vec![None; 4]);
+}
+
+#[test]
+fn max_properties() {
+    let limits = Limits {max_properties: Some(1), ..Limits::default()};
+    check(&stream(&[&[], &[]]), limits, Limit::Properties);
+    assert_eq!(exceeded(&stream(&[&[]]), limits), vec![None; 4]);
+}
+
+#[test]
+fn block_header_without_data() {
+    // A 28 byte stream announcing a block of 2^64 - 1 bytes.
+    let mut data = vec![];
+    data.extend_from_slice(&Type::U8.scalar().0.to_le_bytes());
+    data.extend_from_slice(&PROP.to_le_bytes());
+    data.extend_from_slice(&u64::MAX.to_le_bytes());
+    data.extend_from_slice(&0_u64.to_le_bytes());
+    let limits = Limits {max_block_bytes: Some(1 << 20), ..Limits::default()};
+    assert_eq!(exceeded(&data, limits), vec![Some(Limit::BlockBytes); 4]);
+}
@@ -0,0 +1,121 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const ENERGY: u16 = 1;
+
+/// The events of a cascade, where one particle decays into three,
+/// and one of those decays again into a particle that reuses the id of the first one.
+fn events() -> Vec<(Vec<Creation>, Vec<u64>)> {
+    vec![
+        (vec![Creation::new(1), Creation::new(2)], vec![]),
+        (vec![], vec![]),
+        (vec![Creation::with_parent(3, 1), Creation::with_parent(4, 1),
+              Creation::with_parent(5, 1)], vec![1]),
+        (vec![Creation::with_parent(1, 4)], vec![4]),
+        (vec![], vec![2]),
+    ]
+}
+
+/// Writes frames with events, energies and an alive mask.
+fn stream() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    let mut alive = AliveMask::new();
+    for (t, (created, destroyed)) in events().into_iter().enumerate() {
+        w.begin_frame(t as f64).unwrap();
+        for &id in &destroyed {alive.kill(id)}
+        for c in &created {alive.set_alive(c.id, true)}
+        Scalar::write_array(ENERGY, &vec![t as f64; 6], &mut w).unwrap();
+        write_events(&created, &destroyed, &mut w).unwrap();
+        alive.write_changes(&mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+#[test]
+fn cascade() {
+    let data = stream();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut lineage = Lineage::new();
+    let expected = events();
+    while let Some(frame) = r.next_frame().unwrap() {
+        let (ref created, ref destroyed) = expected[frame.number as usize];
+        assert_eq!(r.created(), &created[..]);
+        assert_eq!(r.destroyed(), &destroyed[..]);
+        let mut energies: Vec<f64> = vec![];
+        frame.read_array(ENERGY, &mut energies).unwrap();
+        assert_eq!(energies, vec![frame.number as f64; 6]);
+        lineage.record(frame.number, r.created(), r.destroyed());
+    }
+
+    // The first particle with id 1 decayed into 3, 4 and 5 in frame 2.
+    let first = lineage.parent(3).unwrap();
+    assert_eq!(*first, Particle {id: 1, parent: None, created: 0, destroyed: Some(2)});
+    for &id in &[3, 4, 5] {
+        assert_eq!(lineage.parent(id), Some(first));
+        assert_eq!(lineage.particle(id).unwrap().created, 2);
+    }
+
+    // The id 1 was reused by the decay product of 4.
+    let reused = lineage.particle(1).unwrap();
+    assert_eq!(*reused, Particle {id: 1, parent: Some(4), created: 3, destroyed: None});
+    assert!(lineage.children(1).is_empty());
+    let ancestors: Vec<(u64, u64)> = lineage.ancestors(1).iter()
+        .map(|p| (p.id, p.created))
+        .collect();
+    assert_eq!(ancestors, vec![(4, 2), (1, 0)]);
+    let descendants: Vec<u64> = lineage.descendants(4).iter().map(|p| p.id).collect();
+    assert_eq!(descendants, vec![1]);
+    assert_eq!(lineage.particle(4).unwrap().destroyed, Some(3));
+
+    // A particle without a parent or children.
+    assert_eq!(lineage.parent(2), None);
+    assert!(lineage.children(2).is_empty());
+    assert_eq!(lineage.particle(2).unwrap().destroyed, Some(4));
+    assert_eq!(lineage.particle(9), None);
+    assert!(lineage.ancestors(9).is_empty());
+
+    // The alive mask agrees with the events.
+    assert_eq!(r.alive().ids().collect::<Vec<u64>>(), vec![1, 3, 5]);
+}
+
+#[test]
+fn empty_events_write_nothing() {
+    let mut w = vec![];
+    write_events(&[], &[], &mut w).unwrap();
+    assert!(w.is_empty());
+
+    let mut created = vec![];
+    write_events(&[Creation::new(7)], &[], &mut created).unwrap();
+    let mut both = vec![];
+    write_events(&[Creation::new(7)], &[3], &mut both).unwrap();
+    // A destruction takes a property header, a block header, its id and the end of the property.
+    assert_eq!(both.len() - created.len(), 4 + 16 + 8 + 8);
+
+    let err = write_events(&[Creation::with_parent(1, NO_PARENT)], &[], &mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn truncated_events() {
+    let block = |data: Vec<u8>| RawBlock {
+        type_format: CREATED_FORMAT,
+        property_id: CREATED_PROPERTY,
+        offset: 0,
+        data,
+    };
+    // An id without a parent.
+    for data in &[vec![0; 8], vec![0; 12]] {
+        let mut w = FrameWriter::new(vec![], TIME);
+        w.begin_frame(0.0).unwrap();
+        RawBlock::write_property(CREATED_FORMAT, CREATED_PROPERTY, &[block(data.clone())], &mut w)
+            .unwrap();
+        let data = w.finish().unwrap();
+        let mut r = FrameReader::with_time(&data[..], TIME);
+        let err = Error::from_io(&r.next_frame().unwrap_err());
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const POSITION: u16 = 2;
+const MASS: u16 = 3;
+
+/// Writes two frames, with metadata before each.
+fn stream() -> Vec<u8> {
+    let pos = Metadata::new()
+        .with(DESCRIPTION, "particle position in the lab frame, after boundary wrapping")
+        .with("frame", "lab");
+    let mass = Metadata::new().with(DESCRIPTION, "rest mass");
+    let mut w = FrameWriter::new(vec![], TIME);
+    write_metadata(&[(POSITION, &pos), (MASS, &mass)], &mut w).unwrap();
+    for i in 0..2 {
+        w.begin_frame(i as f64).unwrap();
+        Vector::write_array(POSITION, &vec![[i as f32; 3]; 4], &mut w).unwrap();
+        Scalar::write_array(MASS, &vec![1.5_f64; 4], &mut w).unwrap();
+        if i == 0 {
+            // Replaces one key and adds another.
+            let update = Metadata::new().with("frame", "comoving").with("unit", "m");
+            write_metadata(&[(POSITION, &update)], &mut w).unwrap();
+        }
+    }
+    w.finish().unwrap()
+}
+
+#[test]
+fn merged_last_wins() {
+    let data = stream();
+    let mut r = PoolReader::new(&data[..]);
+    for &prop in &[TIME, POSITION, MASS] {r.register(prop)}
+    assert!(r.metadata(POSITION).is_none());
+    let mut metadata = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        metadata.push(r.metadata(POSITION).and_then(|m| m.get("frame")).map(String::from));
+    }
+    // The update is seen after the first frame.
+    assert_eq!(metadata[0].as_deref(), Some("lab"));
+    assert_eq!(metadata.last().unwrap().as_deref(), Some("comoving"));
+
+    let pos = r.metadata(POSITION).unwrap();
+    assert_eq!(pos.iter().collect::<Vec<_>>(), vec![
+        (DESCRIPTION, "particle position in the lab frame, after boundary wrapping"),
+        ("frame", "comoving"),
+        ("unit", "m"),
+    ]);
+    assert_eq!(r.metadata(MASS).and_then(|m| m.description()), Some("rest mass"));
+    assert!(r.metadata(TIME).is_none());
+}
+
+#[test]
+fn unaware_readers_skip() {
+    let data = stream();
+    // Reading frames.
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut n = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut pos: Vec<[f32; 3]> = vec![];
+        frame.read_array(POSITION, &mut pos).unwrap();
+        assert_eq!(pos, vec![[n as f32; 3]; 4]);
+        n += 1;
+    }
+    assert_eq!(n, 2);
+
+    // Reading properties, skipping those that are not known.
+    let mut r = &data[..];
+    let mut masses = 0;
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        if prop == MASS {
+            let mut mass: Vec<f64> = vec![];
+            Scalar::read_array(state, ty, &mut mass, &mut r).unwrap();
+            assert_eq!(mass, vec![1.5; 4]);
+            masses += 1;
+        } else {
+            RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        }
+    }
+    assert_eq!(masses, 2);
+    assert!(validate(&mut &data[..], &ValidateOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn invalid() {
+    let mut data = vec![];
+    write_metadata(&[(POSITION, &Metadata::new().with("key", "value"))], &mut data).unwrap();
+    // Cuts the value short, keeping the block consistent.
+    let mut r = &data[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let mut block = RawBlock::read_property(state.unwrap(), ty, prop, &mut r).unwrap().remove(0);
+    block.data.pop();
+    let err = decode_metadata(&block).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(METADATA_PROPERTY)));
+    assert_eq!(decode_metadata(&RawBlock {data: vec![], ..block}).unwrap(), vec![]);
+}
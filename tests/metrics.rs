@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+const FRAMES: u64 = 50;
+
+/// The number of particles in a frame.
+fn particles(frame: u64) -> u64 {
+    1 + frame % 5
+}
+
+/// Writes a time, one block of positions and two blocks of masses per frame.
+fn write<W: Write>(w: W) -> W {
+    let mut w = FrameWriter::new(w, TIME);
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64 * 0.5).unwrap();
+        let positions = vec![[i as f32; 3]; particles(i) as usize];
+        Vector::write_array(POSITION, &positions, &mut w).unwrap();
+        let ty = Type::F64.scalar().0;
+        let blocks: Vec<RawBlock> = (0..2).map(|j| {
+            let mut data = vec![];
+            for _ in 0..particles(i) {(j as f64).write_element(&mut data).unwrap()}
+            RawBlock {type_format: ty, property_id: MASS, offset: j * particles(i), data}
+        }).collect();
+        RawBlock::write_property(ty, MASS, &blocks, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+/// The counts of the stream written by `write`.
+fn expected(bytes: u64) -> MetricsSnapshot {
+    let items: u64 = (0..FRAMES).map(particles).sum();
+    MetricsSnapshot {
+        bytes,
+        blocks: 4 * FRAMES,
+        properties: 3 * FRAMES,
+        frames: FRAMES,
+        frame_time: Some((FRAMES - 1) as f64 * 0.5),
+        per_property: vec![
+            PropertyMetrics {property_id: TIME, bytes: 8 * FRAMES, blocks: FRAMES},
+            PropertyMetrics {property_id: POSITION, bytes: 12 * items, blocks: FRAMES},
+            PropertyMetrics {property_id: MASS, bytes: 2 * 8 * items, blocks: 2 * FRAMES},
+        ],
+    }
+}
+
+/// Reads at most a few bytes at a time.
+struct Uneven<'a> {
+    data: &'a [u8],
+    n: usize,
+}
+
+impl<'a> Read for Uneven<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.n = self.n % 13 + 1;
+        let n = self.n.min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn known_stream() {
+    let pushed = RefCell::new(vec![]);
+    let w = Metered::new(vec![]).time_property(TIME)
+        .on_blocks(10, |s: &MetricsSnapshot| pushed.borrow_mut().push(s.blocks));
+    let metrics = w.metrics();
+    let data = write(w).into_inner();
+    let written = metrics.snapshot();
+    assert_eq!(written, expected(data.len() as u64));
+    assert_eq!((metrics.bytes(), metrics.blocks()), (data.len() as u64, 4 * FRAMES));
+    assert_eq!(written.property(MASS).unwrap().blocks, 2 * FRAMES);
+    assert!(written.property(3).is_none());
+
+    // A snapshot is pushed every 10 blocks.
+    let pushed = pushed.into_inner();
+    assert_eq!(pushed.len() as u64, 4 * FRAMES / 10);
+    for (i, &blocks) in pushed.iter().enumerate() {
+        assert!(blocks >= 10 * (i as u64 + 1) && blocks < 10 * (i as u64 + 2), "{:?}", pushed);
+    }
+
+    // The same counts are read in uneven chunks.
+    let mut r = Metered::new(Uneven {data: &data, n: 0}).time_property(TIME);
+    let mut frames = FrameReader::with_time(&mut r, TIME);
+    let mut n = 0;
+    while frames.next_frame().unwrap().is_some() {n += 1}
+    assert_eq!(n, FRAMES);
+    assert_eq!(r.metrics().snapshot(), written);
+
+    // Without a time property, frames are not counted.
+    let mut r = Metered::new(&data[..]);
+    io::copy(&mut r, &mut io::sink()).unwrap();
+    let snapshot = r.metrics().snapshot();
+    assert_eq!((snapshot.frames, snapshot.frame_time), (0, None));
+    assert_eq!(snapshot.per_property, written.per_property);
+}
+
+#[test]
+fn shared_counters() {
+    let data = write(vec![]);
+    let metrics = Metrics::new();
+    assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+
+    // Two streams add to the same counters.
+    for _ in 0..2 {
+        let mut r = Metered::with_metrics(&data[..], metrics.clone()).time_property(TIME);
+        io::copy(&mut r, &mut io::sink()).unwrap();
+    }
+    let snapshot = metrics.snapshot();
+    assert_eq!((snapshot.bytes, snapshot.blocks), (2 * data.len() as u64, 8 * FRAMES));
+    assert_eq!(snapshot.property(POSITION).unwrap().blocks, 2 * FRAMES);
+    metrics.reset();
+    assert_eq!(metrics.snapshot(), MetricsSnapshot::default());
+
+    // The counts only grow while another thread reads them.
+    let mut w = Metered::new(vec![]).time_property(TIME);
+    let watched = w.metrics();
+    let watcher = std::thread::spawn(move || {
+        let mut last = MetricsSnapshot::default();
+        while last.properties < 3 * FRAMES {
+            let s = watched.snapshot();
+            assert!(s.bytes >= last.bytes && s.blocks >= last.blocks && s.frames >= last.frames);
+            last = s;
+        }
+        last
+    });
+    for chunk in data.chunks(100) {w.write_all(chunk).unwrap()}
+    let last = watcher.join().unwrap();
+    assert_eq!(w.metrics().snapshot(), expected(data.len() as u64));
+    assert!(last.bytes <= data.len() as u64);
+}
+
+#[test]
+fn frame_time() {
+    // A time stored as `f32`.
+    let mut data = vec![];
+    for &t in &[0.5_f32, 1.5] {
+        t.write_property(TIME, &mut data).unwrap();
+        Scalar::write_array(MASS, &vec![t; 3], &mut data).unwrap();
+    }
+    let mut r = Metered::new(&data[..]).time_property(TIME);
+    io::copy(&mut r, &mut io::sink()).unwrap();
+    let snapshot = r.metrics().snapshot();
+    assert_eq!((snapshot.frames, snapshot.frame_time), (2, Some(1.5)));
+
+    // A time property that is not a scalar still counts frames, but has no time.
+    let mut data = vec![];
+    Scalar::write_array(TIME, &vec![1.0_f64; 2], &mut data).unwrap();
+    let mut r = Metered::new(&data[..]).time_property(TIME);
+    io::copy(&mut r, &mut io::sink()).unwrap();
+    let snapshot = r.metrics().snapshot();
+    assert_eq!((snapshot.frames, snapshot.frame_time), (1, None));
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io;
+
+const COUNT: u16 = 1;
+const ENERGY: u16 = 2;
+const POSITION: u16 = 3;
+
+/// Encodes a property with one block, as written by every version.
+fn property(ty: u16, prop: u16, data: &[u8]) -> Vec<u8> {
+    let mut res = vec![];
+    res.extend_from_slice(&ty.to_le_bytes());
+    res.extend_from_slice(&prop.to_le_bytes());
+    res.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    res.extend_from_slice(&0_u64.to_le_bytes());
+    res.extend_from_slice(data);
+    res.extend_from_slice(&0_u64.to_le_bytes());
+    res
+}
+
+fn format(ty: Type, rows: u8, cols: u8) -> u16 {
+    Type::format_for(ty, rows, cols).unwrap()
+}
+
+/// A headerless stream, where counts are `i32` and energies `f64`,
+/// written with the type ids of `u32` and `i64`.
+fn old_stream() -> Vec<u8> {
+    let counts: Vec<u8> = [3_i32, -1, 7].iter().flat_map(|x| x.to_le_bytes()).collect();
+    let energies: Vec<u8> = [0.5_f64, -2.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+    let positions: Vec<u8> = [1.0_f32, 2.0, 3.0].iter().flat_map(|x| x.to_le_bytes()).collect();
+    let mut res = property(format(Type::U32, 1, 1), COUNT, &counts);
+    res.extend(property(format(Type::I64, 1, 1), ENERGY, &energies));
+    res.extend(property(format(Type::F32, 1, 3), POSITION, &positions));
+    res.extend_from_slice(&0_u16.to_le_bytes());
+    res
+}
+
+fn options() -> MigrateOptions {
+    MigrateOptions::default()
+        .real_type(COUNT, Type::I32)
+        .real_type(ENERGY, Type::F64)
+        .real_type(POSITION, Type::F32)
+}
+
+/// Migrates a stream, returning the new stream and the report.
+fn run(old: &[u8], opts: &MigrateOptions) -> io::Result<(Vec<u8>, MigrationReport)> {
+    let mut new = vec![];
+    let report = migrate(&mut &old[..], &mut new, opts)?;
+    Ok((new, report))
+}
+
+type Decoded = (Vec<i32>, Vec<f64>, Vec<[f32; 3]>);
+
+/// Reads a stream with a reader that fails on other types and unknown properties.
+fn strict(data: &[u8]) -> io::Result<Decoded> {
+    let mut r = PoolReader::new(data);
+    for &prop in &[COUNT, ENERGY, POSITION] {r.register(prop)}
+    let (mut counts, mut energies, mut positions) = (vec![], vec![], vec![]);
+    while let Some((state, ty, prop)) = r.next_property()? {
+        match prop {
+            COUNT => Scalar::read_array(state, ty, &mut counts, &mut r)?,
+            ENERGY => Scalar::read_array(state, ty, &mut energies, &mut r)?,
+            POSITION => Vector::read_array(state, ty, &mut positions, &mut r)?,
+            _ => unreachable!(),
+        }
+    }
+    let header = r.header().unwrap();
+    assert_eq!(header, Header::default());
+    Ok((counts, energies, positions))
+}
+
+#[test]
+fn headerless_with_wrong_types() {
+    let old = old_stream();
+    // The old stream does not decode with the real types.
+    let err = strict(&old).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+
+    let (new, report) = run(&old, &options()).unwrap();
+    assert_eq!(report.version, 0);
+    assert_eq!(report.migrations, vec![
+        Migration::AddedHeader,
+        Migration::Retyped {property_id: COUNT, from: format(Type::U32, 1, 1),
+                            to: format(Type::I32, 1, 1)},
+        Migration::Retyped {property_id: ENERGY, from: format(Type::I64, 1, 1),
+                            to: format(Type::F64, 1, 1)},
+    ]);
+    let (counts, energies, positions) = strict(&new).unwrap();
+    assert_eq!(counts, vec![3, -1, 7]);
+    assert_eq!(energies, vec![0.5, -2.0]);
+    assert_eq!(positions, vec![[1.0, 2.0, 3.0]]);
+    validate(&mut &new[..], &ValidateOptions::default()).unwrap();
+
+    // Migrating again changes nothing.
+    let (again, report) = run(&new, &options()).unwrap();
+    assert_eq!(again, new);
+    assert_eq!(report, MigrationReport {version: Header::VERSION, migrations: vec![]});
+}
+
+#[test]
+fn older_header_big_endian_and_aligned() {
+    let mut w = PoolWriter::new(vec![])
+        .with_endianness(Endian::Big)
+        .with_options(WriteOptions::default().alignment(16));
+    Scalar::write_array(COUNT, &vec![3_i32, -1, 7], &mut w).unwrap();
+    Scalar::write_array(ENERGY, &vec![0.5_f64, -2.0], &mut w).unwrap();
+    Vector::write_array(POSITION, &vec![[1.0_f32, 2.0, 3.0]], &mut w).unwrap();
+    let mut old = w.finish().unwrap();
+    // Version 0 of the header, which follows the magic bytes.
+    let magic = &Header::default().encode()[..8];
+    let at = 8 + old.windows(8).position(|w| w == magic).unwrap();
+    old[at..at + 2].copy_from_slice(&0_u16.to_le_bytes());
+
+    let (new, report) = run(&old, &MigrateOptions::default()).unwrap();
+    assert_eq!(report.version, 0);
+    assert_eq!(report.migrations, vec![
+        Migration::UpgradedVersion {from: 0},
+        Migration::ConvertedToLittleEndian,
+        Migration::RemovedAlignment {alignment: 16},
+    ]);
+    let (counts, energies, positions) = strict(&new).unwrap();
+    assert_eq!(counts, vec![3, -1, 7]);
+    assert_eq!(energies, vec![0.5, -2.0]);
+    assert_eq!(positions, vec![[1.0, 2.0, 3.0]]);
+    assert!(new.len() < old.len());
+}
+
+#[test]
+fn errors() {
+    // Newer versions are not supported.
+    let mut newer = vec![];
+    Header {version: Header::VERSION + 1, ..Header::default()}.write(&mut newer).unwrap();
+    newer.extend(old_stream());
+    let err = run(&newer, &options()).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::UnsupportedVersion);
+
+    // Three `i16` are not a multiple of `i32`.
+    let mut old = property(format(Type::I16, 1, 1), COUNT, &[1, 0, 2, 0, 3, 0]);
+    old.extend_from_slice(&0_u16.to_le_bytes());
+    let err = run(&old, &options()).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::BytesNotMultiple, Some(COUNT)));
+
+    // An empty stream gets a header.
+    let (new, report) = run(&[], &options()).unwrap();
+    assert_eq!(report.migrations, vec![Migration::AddedHeader]);
+    assert_eq!(strict(&new).unwrap(), (vec![], vec![], vec![]));
+}
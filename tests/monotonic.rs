@@ -0,0 +1,117 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+
+fn writer(strict: bool) -> FrameWriter<Vec<u8>> {
+    FrameWriter::new(vec![], TIME).require_monotonic_time(true).strictly_increasing_time(strict)
+}
+
+/// Returns the times of the error of starting a frame.
+fn refused(w: &mut FrameWriter<Vec<u8>>, t: f64) -> (f64, f64) {
+    let len = w.get_ref().len();
+    let frames = w.frames();
+    let err = w.begin_frame(t).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::NonMonotonicTime);
+    assert_eq!(err.property(), Some(TIME));
+    // Nothing is written.
+    assert_eq!((w.get_ref().len(), w.frames()), (len, frames));
+    err.times().unwrap()
+}
+
+#[test]
+fn equal_times() {
+    let mut w = writer(false);
+    w.begin_frame(1.0).unwrap();
+    w.begin_frame(1.0).unwrap();
+    assert_eq!(refused(&mut w, 0.5), (1.0, 0.5));
+    w.begin_frame(2.0).unwrap();
+    assert_eq!(w.frames(), 3);
+
+    let mut w = writer(true);
+    w.begin_frame(1.0).unwrap();
+    assert_eq!(refused(&mut w, 1.0), (1.0, 1.0));
+    assert_eq!(refused(&mut w, -1.0), (1.0, -1.0));
+    w.begin_frame(1.5).unwrap();
+    assert_eq!(refused(&mut w, 1.5), (1.5, 1.5));
+}
+
+#[test]
+fn nan_times() {
+    for &strict in &[false, true] {
+        let mut w = writer(strict);
+        let (previous, t) = refused(&mut w, f64::NAN);
+        assert_eq!(previous, f64::NEG_INFINITY);
+        assert!(t.is_nan());
+        w.begin_frame(0.0).unwrap();
+        let (previous, t) = refused(&mut w, f64::NAN);
+        assert_eq!(previous, 0.0);
+        assert!(t.is_nan());
+        // NaN is not remembered as the previous time.
+        w.begin_frame(1.0).unwrap();
+    }
+    // Infinite times are ordered.
+    let mut w = writer(true);
+    assert_eq!(refused(&mut w, f64::NEG_INFINITY), (f64::NEG_INFINITY, f64::NEG_INFINITY));
+    w.begin_frame(-1e300).unwrap();
+    w.begin_frame(f64::INFINITY).unwrap();
+    refused(&mut w, f64::INFINITY);
+}
+
+#[test]
+fn unchecked_by_default() {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for &t in &[1.0, 1.0, 0.0, f64::NAN, -5.0] {
+        w.begin_frame(t).unwrap();
+    }
+    assert_eq!(w.frames(), 5);
+}
+
+fn validate_times(times: &[f64], strict: bool) -> Vec<(u64, f64, f64)> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for &t in times {
+        w.begin_frame(t).unwrap();
+        Scalar::write_array(1, &vec![t as f32], &mut w).unwrap();
+    }
+    let data = w.finish().unwrap();
+    let opts = ValidateOptions {
+        time_property: Some(TIME),
+        strict_time: strict,
+        ..Default::default()
+    };
+    let report = validate(&mut &data[..], &opts).unwrap();
+    assert_eq!(report.frames, 0);
+    assert_eq!(report.is_ok(), report.time_regressions.is_empty());
+    report.time_regressions.iter().map(|r| (r.index, r.previous, r.time)).collect()
+}
+
+#[test]
+fn validate_existing_files() {
+    let times = [0.0, 1.0, 1.0, 0.5, 2.0, 2.0];
+    assert_eq!(validate_times(&times, false), vec![(3, 1.0, 0.5)]);
+    assert_eq!(validate_times(&times, true), vec![(2, 1.0, 1.0), (3, 1.0, 0.5), (5, 2.0, 2.0)]);
+    assert!(validate_times(&[0.0, 1.0, 2.0], true).is_empty());
+
+    // NaN is reported in both settings, and the time after it is compared with the time before.
+    for &strict in &[false, true] {
+        let res = validate_times(&[1.0, f64::NAN, 0.5, 3.0], strict);
+        assert_eq!(res.len(), 2);
+        assert_eq!((res[0].0, res[0].1), (1, 1.0));
+        assert!(res[0].2.is_nan());
+        assert_eq!(res[1], (2, 1.0, 0.5));
+        let res = validate_times(&[f64::NAN], strict);
+        assert_eq!((res[0].0, res[0].1), (0, f64::NEG_INFINITY));
+    }
+
+    // Without the time property, times are not checked.
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(1.0).unwrap();
+    w.begin_frame(0.0).unwrap();
+    let data = w.finish().unwrap();
+    let report = validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+    assert!(report.time_regressions.is_empty());
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const CHARGE: u16 = 2;
+
+/// Writes a simulation of a sweep, with values derived from `run`.
+fn simulation(run: u32, endian: Endian) -> Vec<u8> {
+    let mut w = PoolWriter::new(vec![]).with_endianness(endian);
+    Time::write(TIME, run as f64, &mut w).unwrap();
+    let pos: Vec<[f32; 2]> = (0..run + 1).map(|i| [run as f32, i as f32]).collect();
+    Vector::write_array(POSITION, &pos, &mut w).unwrap();
+    Scalar::write_array(CHARGE, &vec![run as i8 - 1; 2], &mut w).unwrap();
+    w.finish().unwrap()
+}
+
+/// Merges three simulations, the last one big endian, into bands of 10 ids from 100.
+fn merged() -> Vec<u8> {
+    let mut w = vec![];
+    let mut namespaces = Namespaces::new();
+    for run in 0..3 {
+        let endian = if run == 2 {Endian::Big} else {Endian::Little};
+        let data = simulation(run, endian);
+        let ns = namespaces.copy(&mut &data[..], &mut w, 100 + 10 * run as u16, 10).unwrap();
+        assert_eq!(ns, Namespace {base: 100 + 10 * run as u16, stride: 10});
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+#[test]
+fn read_each_namespace() {
+    let data = merged();
+    for run in 0..3_u32 {
+        let mut namespaces = Namespaces::new();
+        let mut time = None;
+        let mut pos: Vec<[f32; 2]> = vec![];
+        let mut charge: Vec<i8> = vec![];
+        let mut r = &data[..];
+        while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+            if ty == NAMESPACE_FORMAT && prop == NAMESPACE_PROPERTY {
+                namespaces.read_manifest(state, ty, prop, &mut r).unwrap();
+                continue;
+            }
+            let (ns, original) = namespaces.original(prop).unwrap();
+            if ns.base != 100 + 10 * run as u16 {
+                RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+                continue;
+            }
+            match original {
+                TIME => {
+                    let mut t = 0.0;
+                    t.read_property(state, ty, &mut r).unwrap();
+                    time = Some(t);
+                }
+                POSITION => Vector::read_array(state, ty, &mut pos, &mut r).unwrap(),
+                CHARGE => Scalar::read_array(state, ty, &mut charge, &mut r).unwrap(),
+                _ => panic!("unexpected property {}", original),
+            }
+        }
+        assert_eq!(namespaces.namespaces().len(), 3);
+        assert_eq!(time, Some(run as f64));
+        let expected: Vec<[f32; 2]> = (0..run + 1).map(|i| [run as f32, i as f32]).collect();
+        assert_eq!(pos, expected);
+        assert_eq!(charge, vec![run as i8 - 1; 2]);
+    }
+}
+
+#[test]
+fn merged_ids() {
+    let data = merged();
+    let mut ids = vec![];
+    let mut r = &data[..];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        ids.push(prop);
+    }
+    let m = NAMESPACE_PROPERTY;
+    assert_eq!(ids, vec![m, 100, 101, 102, m, 110, 111, 112, m, 120, 121, 122]);
+    let report = validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+    assert!(report.is_ok());
+}
+
+#[test]
+fn collisions() {
+    let data = simulation(1, Endian::Little);
+    let mut namespaces = Namespaces::new();
+    namespaces.copy(&mut &data[..], &mut vec![], 100, 10).unwrap();
+    // The band overlaps the one emitted before.
+    let err = namespaces.copy(&mut &data[..], &mut vec![], 105, 10).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // The band runs into reserved property ids.
+    let err = namespaces.copy(&mut &data[..], &mut vec![], 0xfffa, 10).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(namespaces.namespaces().len(), 1);
+
+    // A property id of the source does not fit in the band.
+    let err = namespace_copy(&mut &data[..], &mut vec![], 200, 2).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.property(), Some(CHARGE));
+    assert_eq!(err.range(), Some((0, 2)));
+}
@@ -0,0 +1,90 @@
+//! Round trips through the `codec` module against in-memory buffers.
+//!
+//! Without the `std` feature, this test crate is `no_std` too,
+//! such that `cargo test --no-default-features --test no_std` checks that
+//! the library builds and works with only `core` and `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+extern crate binpool;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use binpool::codec::{self, ByteRead, ByteWrite, Value};
+use binpool::Type;
+
+fn round_trip<T: Value + PartialEq + core::fmt::Debug>(components: &[T], dim: u8)
+    where Vec<u8>: ByteWrite, for<'a> &'a [u8]: ByteRead
+{
+    let mut buf: Vec<u8> = vec![];
+    codec::write_array(7, dim, 3, components, &mut buf).ok().unwrap();
+    codec::write_end(&mut buf).ok().unwrap();
+
+    let mut r = &buf[..];
+    let (ty, prop) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    assert_eq!(prop, 7);
+    assert_eq!(ty, T::ty().vector(dim).unwrap().0);
+    let mut res: Vec<T> = vec![];
+    codec::read_array(ty, dim, &mut res, &mut r).ok().unwrap();
+    assert_eq!(&res[..3 * dim as usize], &vec![T::default(); 3 * dim as usize][..]);
+    assert_eq!(&res[3 * dim as usize..], components);
+    assert!(codec::read_property_header(&mut r).ok().unwrap().is_none());
+    assert!(r.is_empty());
+}
+
+#[test]
+fn scalars() {
+    round_trip(&[1_u8, 2, 3], 1);
+    round_trip(&[-1_i16, 0, 1], 1);
+    round_trip(&[u32::MAX, 0], 1);
+    round_trip(&[i64::MIN, i64::MAX], 1);
+    round_trip(&[0.5_f32, -0.25], 1);
+    round_trip(&[1e300_f64, -0.0], 1);
+}
+
+#[test]
+fn vectors() {
+    round_trip(&[1.0_f32, 2.0, 3.0, 4.0, 5.0, 6.0], 3);
+    round_trip(&[1_u16, 2, 3, 4], 2);
+}
+
+#[test]
+fn empty_array() {
+    let mut buf: Vec<u8> = vec![];
+    codec::write_array::<_, f64>(1, 1, 0, &[], &mut buf).ok().unwrap();
+    // Type format, property id and end of bytes.
+    assert_eq!(buf.len(), 12);
+    let mut r = &buf[..];
+    let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    assert_eq!(ty, Type::F64.scalar().0);
+    let mut res: Vec<f64> = vec![];
+    codec::read_array(ty, 1, &mut res, &mut r).ok().unwrap();
+    assert!(res.is_empty());
+}
+
+#[test]
+fn truncated() {
+    let mut buf: Vec<u8> = vec![];
+    codec::write_array(1, 1, 0, &[1_u32, 2, 3], &mut buf).ok().unwrap();
+    for len in 0..buf.len() {
+        let mut r = &buf[..len];
+        let res = codec::read_property_header(&mut r).and_then(|header| {
+            let (ty, _) = header.unwrap();
+            let mut res: Vec<u32> = vec![];
+            codec::read_array(ty, 1, &mut res, &mut r)
+        });
+        assert!(res.is_err(), "{}", len);
+    }
+}
+
+#[test]
+fn type_mismatch() {
+    let mut buf: Vec<u8> = vec![];
+    codec::write_array(1, 1, 0, &[1_u32], &mut buf).ok().unwrap();
+    let mut r = &buf[..];
+    let (ty, _) = codec::read_property_header(&mut r).ok().unwrap().unwrap();
+    let mut res: Vec<f32> = vec![];
+    assert!(codec::read_array(ty, 1, &mut res, &mut r).is_err());
+}
@@ -0,0 +1,104 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const DECAY: u16 = 1;
+
+fn write(offset: u64, items: &[Option<f32>]) -> Vec<u8> {
+    let mut data = vec![];
+    write_optional_array_at(DECAY, offset, items, &mut data).unwrap();
+    data
+}
+
+fn read(data: &[u8]) -> Vec<Option<f32>> {
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let mut arr = vec![];
+    read_optional_array(state.unwrap(), ty, prop, &mut arr, &mut r).unwrap();
+    assert!(r.is_empty());
+    arr
+}
+
+/// Patterns with lengths that do not fill the last byte of the bitmap.
+fn patterns() -> Vec<Vec<Option<f32>>> {
+    let mixed: Vec<Option<f32>> = (0..19)
+        .map(|i| if i % 3 == 0 {None} else {Some(i as f32)})
+        .collect();
+    vec![
+        vec![],
+        vec![None; 13],
+        (0..13).map(|i| Some(i as f32)).collect(),
+        mixed,
+        vec![Some(-1.0), None, Some(0.0)],
+    ]
+}
+
+#[test]
+fn round_trip() {
+    for items in patterns() {
+        assert_eq!(read(&write(0, &items)), items);
+    }
+}
+
+#[test]
+fn nonzero_offsets() {
+    for items in patterns() {
+        for &offset in &[1, 7, 8, 100] {
+            let res = read(&write(offset, &items));
+            // Instances before the offset are not covered and are `None`.
+            let mut expected = vec![None; offset as usize];
+            expected.extend_from_slice(&items);
+            if items.is_empty() {expected.clear()}
+            assert_eq!(res, expected, "offset {}", offset);
+        }
+    }
+    // Keeps the values before the offset.
+    let mut data = write(0, &[Some(1.0), Some(2.0)]);
+    data.extend(write(1, &[None, Some(3.0)]));
+    let mut r = &data[..];
+    let mut arr: Vec<Option<f32>> = vec![];
+    for _ in 0..2 {
+        let (state, ty, prop) = State::read(&mut r).unwrap();
+        read_optional_array(state.unwrap(), ty, prop, &mut arr, &mut r).unwrap();
+    }
+    assert_eq!(arr, vec![Some(1.0), None, Some(3.0)]);
+}
+
+#[test]
+fn plain_readers_see_dense_array() {
+    let items = vec![Some(1.0_f32), None, Some(3.0), None];
+    let dense = vec![1.0, 0.0, 3.0, 0.0];
+
+    let data = write(0, &items);
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<f32> = vec![];
+    Scalar::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap();
+    assert_eq!(arr, dense);
+
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    write_optional_array(DECAY, &items, &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let frame = FrameReader::with_time(&data[..], TIME).next_frame().unwrap().unwrap();
+    let mut arr: Vec<f32> = vec![];
+    assert!(frame.read_array(DECAY, &mut arr).unwrap());
+    assert_eq!(arr, dense);
+    assert!(validate(&mut &data[..], &ValidateOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn missing_bitmap() {
+    let mut data = vec![];
+    Scalar::write_array(DECAY, &vec![1.0_f32], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let mut arr: Vec<Option<f32>> = vec![];
+    let err = read_optional_array(state.unwrap(), ty, prop, &mut arr, &mut r).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.property(), Some(DECAY));
+}
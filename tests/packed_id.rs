@@ -0,0 +1,153 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::collections::BTreeMap;
+use std::io;
+
+const MOMENTUM: u16 = 1;
+
+/// Momenta of particles by event.
+type Events = BTreeMap<u64, Vec<(u32, [f64; 3])>>;
+
+#[test]
+fn boundaries() {
+    let split = PackedId::new(40, 24).unwrap();
+    assert_eq!((split.event_bits(), split.particle_bits()), (40, 24));
+    assert_eq!(split.max_event(), (1 << 40) - 1);
+    assert_eq!(split.max_particle(), (1 << 24) - 1);
+    assert_eq!(split.pack(0, 0), Some(0));
+    assert_eq!(split.pack(0, split.max_particle()), Some((1 << 24) - 1));
+    assert_eq!(split.pack(1, 0), Some(1 << 24));
+    assert_eq!(split.pack(split.max_event(), split.max_particle()), Some(u64::MAX));
+    assert_eq!(split.pack(split.max_event() + 1, 0), None);
+    assert_eq!(split.pack(0, split.max_particle() + 1), None);
+    assert_eq!(split.unpack(u64::MAX), (split.max_event(), split.max_particle()));
+    assert_eq!(split.unpack(1 << 24), (1, 0));
+
+    let split = PackedId::new(32, 32).unwrap();
+    assert_eq!(split.max_particle(), u32::MAX);
+    assert_eq!(split.pack(u32::MAX as u64, u32::MAX), Some(u64::MAX));
+    assert_eq!(split.pack(1 << 32, 0), None);
+    let split = PackedId::new(63, 1).unwrap();
+    assert_eq!(split.pack(0, 1), Some(1));
+    assert_eq!(split.pack(0, 2), None);
+
+    for &(event_bits, particle_bits) in &[(40, 25), (30, 34), (64, 0), (0, 64), (255, 255)] {
+        let err = PackedId::new(event_bits, particle_bits).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    let mut rng = Rng::new(184);
+    for particle_bits in 1..33 {
+        let split = PackedId::new(64 - particle_bits, particle_bits).unwrap();
+        for _ in 0..20 {
+            let event = rng.next_u64() >> rng.below(64) & split.max_event();
+            let particle = (rng.next_u64() >> rng.below(64)) as u32 & split.max_particle();
+            let id = split.pack(event, particle).unwrap();
+            assert_eq!(split.unpack(id), (event, particle));
+        }
+    }
+}
+
+/// Writes the momenta of particles of events, in one or two blocks per event.
+fn stream(split: PackedId, events: &Events) -> Vec<u8> {
+    let mut w = vec![];
+    write_metadata(&[(MOMENTUM, &split.metadata())], &mut w).unwrap();
+    for (&event, particles) in events {
+        let half = particles.len() / 2;
+        for part in &[&particles[..half], &particles[half..]] {
+            if part.is_empty() {continue}
+            let items: Vec<[f64; 3]> = part.iter().map(|x| x.1).collect();
+            split.write_event(MOMENTUM, event, part[0].0, &items, &mut w).unwrap();
+        }
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// Reads the momenta grouped by event, with a configured split.
+fn read(split: PackedId, data: &[u8]) -> io::Result<Events> {
+    let mut r = PoolReader::new(data);
+    r.register(MOMENTUM);
+    let mut res = Events::new();
+    while let Some((state, ty, prop)) = r.next_property()? {
+        split.check(prop, r.metadata(prop).unwrap())?;
+        for (event, items) in split.read_by_event(state, ty, prop, &mut r)? {
+            res.entry(event).or_default().extend(items);
+        }
+    }
+    Ok(res)
+}
+
+#[test]
+fn write_and_group_by_event() {
+    let split = PackedId::new(40, 24).unwrap();
+    let mut rng = Rng::new(1840);
+    let mut events = BTreeMap::new();
+    // Events at the boundaries of the id space, with particles at the boundaries of an event.
+    for &event in &[0, 1, 2, 1000, split.max_event() - 1, split.max_event()] {
+        let first = if event % 2 == 0 {0} else {split.max_particle() - 4};
+        let n = 1 + rng.below(5) as u32;
+        let particles: Vec<(u32, [f64; 3])> = (first..first + n)
+            .map(|i| (i, [event as f64, i as f64, rng.unit()]))
+            .collect();
+        events.insert(event, particles);
+    }
+    let data = stream(split, &events);
+    assert_eq!(read(split, &data).unwrap(), events);
+
+    // The split is recorded in metadata.
+    let mut r = PoolReader::new(&data[..]);
+    r.register(MOMENTUM);
+    r.next_property().unwrap();
+    let metadata = r.metadata(MOMENTUM).unwrap();
+    assert_eq!(metadata.get(PACKED_ID), Some("40/24"));
+    assert_eq!(PackedId::from_metadata(metadata).unwrap(), Some(split));
+
+    // Particles past the last index of an event.
+    let mut w = vec![];
+    let last = split.max_particle() - 1;
+    let err = split.write_event(MOMENTUM, 3, last, &[[0.0; 3]; 3], &mut w);
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert!(split.write_event(MOMENTUM, 3, last, &[[0.0; 3]; 2], &mut w).is_ok());
+    let err = split.write_event(MOMENTUM, split.max_event() + 1, 0, &[[0.0; 3]], &mut w);
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let err = split.write_event(MOMENTUM, 0, split.max_particle() + 1, &[[0.0; 3]], &mut w);
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn mismatched_split() {
+    let split = PackedId::new(40, 24).unwrap();
+    let mut events = BTreeMap::new();
+    events.insert(7, vec![(0, [1.0; 3]), (1, [2.0; 3])]);
+    let data = stream(split, &events);
+
+    let err = Error::from_io(&read(PackedId::new(32, 32).unwrap(), &data).unwrap_err());
+    assert_eq!((err.kind(), err.property()), (ErrorKind::PackedIdMismatch, Some(MOMENTUM)));
+    assert_eq!(err.splits(), Some((24, 32)));
+    assert!(err.to_string().contains("split 40/24 in stream, 32/32 configured"));
+
+    // Metadata without a split is accepted, and a split that is not valid is an error.
+    assert!(split.check(MOMENTUM, &Metadata::new()).is_ok());
+    for val in &["40/25", "40", "40/24/0", "forty/24"] {
+        let err = split.check(MOMENTUM, &Metadata::new().with(PACKED_ID, val)).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(MOMENTUM)));
+    }
+
+    // Items of another element type.
+    let blocks = [RawBlock {
+        type_format: Type::F32.scalar().0,
+        property_id: MOMENTUM,
+        offset: 0,
+        data: vec![0; 4],
+    }];
+    let err = split.group_by_event::<[f64; 3], _>(&blocks).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "rayon")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Cursor;
+
+const EVENT: u16 = 0;
+const ENERGY: u16 = 1;
+
+const EVENTS: usize = 1000;
+
+/// Writes events with differing particle counts.
+fn stream() -> Vec<u8> {
+    let mut rng = Rng::new(187);
+    let mut w = vec![];
+    for i in 0..EVENTS as u64 {
+        (i * 3 + rng.below(3)).write_property(EVENT, &mut w).unwrap();
+        let energies: Vec<f64> = (0..1 + rng.below(30)).map(|_| rng.unit() * 100.0).collect();
+        Scalar::write_array(ENERGY, &energies, &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// A reduction of an event: the event number, the number of particles and the total energy.
+fn reduce(event: Event) -> (u64, usize, f64) {
+    let energies = event.array::<f64>(ENERGY).unwrap();
+    (event.number, energies.len(), energies.iter().sum())
+}
+
+#[test]
+fn parallel_against_sequential() {
+    let data = stream();
+    let mut r = EventReader::new(&data[..], EVENT);
+    let mut sequential = vec![];
+    while let Some(event) = r.next_event().unwrap() {sequential.push(reduce(event))}
+    assert_eq!(sequential.len(), EVENTS);
+
+    let index = EventIndex::build(&mut Cursor::new(&data), EVENT, DuplicateEvents::Error).unwrap();
+    let res = par_events_with(&index, || Ok(Cursor::new(&data[..])), reduce).unwrap();
+    assert_eq!(res, sequential);
+
+    // Independent file handles.
+    let dir = std::env::temp_dir().join(format!("binpool-par-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("events.pool");
+    std::fs::write(&path, &data).unwrap();
+    assert_eq!(par_events(&index, &path, reduce).unwrap(), sequential);
+    // The index of the events is kept.
+    let indices = par_events(&index, &path, |event| event.index()).unwrap();
+    assert_eq!(indices, (0..EVENTS as u64).collect::<Vec<u64>>());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // No events.
+    let mut empty = vec![];
+    State::new().end_type_formats(&mut empty).unwrap();
+    let index = EventIndex::build(&mut Cursor::new(&empty), EVENT, DuplicateEvents::Error).unwrap();
+    assert!(par_events_with(&index, || Ok(Cursor::new(&empty[..])), reduce).unwrap().is_empty());
+}
+
+#[test]
+fn first_failing_event() {
+    let data = stream();
+    let index = EventIndex::build(&mut Cursor::new(&data), EVENT, DuplicateEvents::Error).unwrap();
+    // Change the event numbers of two events far apart, such that they do not match the index.
+    let mut broken = data.clone();
+    for &i in &[900, 150] {
+        // The event number follows the property header and the block header.
+        let at = index.events()[i].position as usize + 4 + 16;
+        broken[at..at + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    }
+    for _ in 0..5 {
+        let err = par_events_with(&index, || Ok(Cursor::new(&broken[..])), reduce).unwrap_err();
+        let err = Error::from_io(&err);
+        assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(EVENT)));
+        assert_eq!(err.event(), Some(index.events()[150].number));
+    }
+
+    // Readers that fail to open.
+    let err = par_events_with(&index, || -> std::io::Result<Cursor<&[u8]>> {
+        Err(std::io::ErrorKind::NotFound.into())
+    }, reduce).unwrap_err();
+    assert_eq!(Error::from_io(&err).event(), Some(index.events()[0].number));
+    let err = par_events(&index, "does-not-exist.pool", reduce).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+}
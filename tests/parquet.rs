@@ -0,0 +1,170 @@
+#![cfg(feature = "parquet")]
+
+extern crate arrow_array;
+extern crate arrow_schema;
+extern crate binpool;
+extern crate parquet;
+
+mod common;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type, UInt64Type};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::DataType;
+use binpool::arrow::{export_arrow, ArrowOptions};
+use binpool::parquet::*;
+use binpool::*;
+use common::Rng;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::file::statistics::Statistics;
+use std::io::{self, Cursor};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes frames with 1 to 5 particles, at times `0, 0.25, ...`.
+fn recording(frames: usize) -> Vec<u8> {
+    let mut names = PropertyAllocator::new();
+    names.assign("position", POSITION).unwrap();
+    names.assign("mass", MASS).unwrap();
+    let mut w = FrameWriter::new(vec![], TIME);
+    names.write(&mut w).unwrap();
+    let mut rng = Rng::new(166);
+    for i in 0..frames {
+        w.begin_frame(i as f64 * 0.25).unwrap();
+        let n = 1 + rng.below(5) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32, i as f32, 0.0]).collect();
+        Vector::write_array(POSITION, &pos, &mut w).unwrap();
+        Scalar::write_array(MASS, &vec![i as f64; n], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+type Row = (u64, u64, Option<f64>, Option<Vec<f32>>, Option<f64>);
+
+fn rows(batch: &RecordBatch) -> Vec<Row> {
+    let c = |name: &str| batch.column_by_name(name).unwrap().clone();
+    let (frame, instance, time, position, mass) =
+        (c("frame"), c("instance"), c("time"), c("position"), c("mass"));
+    (0..batch.num_rows()).map(|i| {
+        let pos = if position.is_null(i) {None} else {
+            let list = position.as_fixed_size_list().value(i);
+            Some(list.as_primitive::<Float32Type>().values().to_vec())
+        };
+        let f64_at = |a: &dyn Array| {
+            if a.is_null(i) {None} else {Some(a.as_primitive::<Float64Type>().value(i))}
+        };
+        (frame.as_primitive::<UInt64Type>().value(i),
+         instance.as_primitive::<UInt64Type>().value(i),
+         f64_at(&time), pos, f64_at(&mass))
+    }).collect()
+}
+
+/// Returns the rows of the Arrow export of a stream.
+fn expected(data: &[u8]) -> Vec<Row> {
+    let mut r = Cursor::new(data);
+    let batches = export_arrow(&mut r, TIME, &ArrowOptions::default()).unwrap();
+    batches.flat_map(|batch| rows(&batch.unwrap())).collect()
+}
+
+/// Writes a Parquet file to a temporary file, since readers need a file.
+fn temp_file(name: &str, data: &[u8]) -> std::fs::File {
+    let name = format!("binpool-{}-{}.parquet", name, std::process::id());
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, data).unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    file
+}
+
+fn min_max(stats: Option<&Statistics>) -> (i64, i64) {
+    match stats {
+        Some(Statistics::Int64(s)) => (*s.min_opt().unwrap(), *s.max_opt().unwrap()),
+        x => panic!("{:?}", x),
+    }
+}
+
+#[test]
+fn read_back() {
+    let data = recording(25);
+    let expected = expected(&data);
+    for &batch_size in &[3, 8192] {
+        let opts = ParquetOptions::default().frames_per_row_group(10).batch_size(batch_size);
+        let file = write_parquet(&mut Cursor::new(&data), vec![], TIME, &opts).unwrap();
+        let file = temp_file("read-back", &file);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file.try_clone().unwrap());
+        let reader = reader.unwrap();
+        let schema = reader.schema().clone();
+        let names: Vec<&str> = schema.fields().iter().map(|f| &f.name()[..]).collect();
+        assert_eq!(names, vec!["frame", "instance", "time", "position", "mass"]);
+        let position = schema.field_with_name("position").unwrap();
+        assert!(matches!(position.data_type(), DataType::FixedSizeList(_, 3)));
+        assert_eq!(schema.field_with_name("mass").unwrap().data_type(), &DataType::Float64);
+        let res: Vec<Row> = reader.build().unwrap().flat_map(|b| rows(&b.unwrap())).collect();
+        assert_eq!(res, expected);
+        assert_eq!(res.iter().find(|r| r.0 == 24).map(|r| r.4), Some(Some(24.0)));
+
+        // One row group per 10 frames, with statistics for skipping frames.
+        let reader = SerializedFileReader::new(file).unwrap();
+        let meta = reader.metadata();
+        assert_eq!(meta.num_row_groups(), 3);
+        let mut rows = 0;
+        for (g, group) in meta.row_groups().iter().enumerate() {
+            let frames = min_max(group.column(0).statistics());
+            assert_eq!(frames, (g as i64 * 10, (g as i64 * 10 + 9).min(24)));
+            let count = expected.iter().filter(|r| r.0 / 10 == g as u64).count();
+            assert_eq!(group.num_rows(), count as i64);
+            rows += count;
+            let instances = min_max(group.column(1).statistics());
+            assert_eq!(instances.0, 0);
+            assert!(instances.1 < 5);
+            match group.column(2).statistics() {
+                Some(Statistics::Double(s)) => {
+                    assert_eq!(*s.min_opt().unwrap(), g as f64 * 2.5);
+                }
+                x => panic!("{:?}", x),
+            }
+        }
+        assert_eq!(rows, expected.len());
+    }
+}
+
+#[test]
+fn compression() {
+    let data = recording(5);
+    let codecs = [
+        Compression::UNCOMPRESSED,
+        Compression::SNAPPY,
+        Compression::ZSTD(Default::default()),
+        Compression::GZIP(Default::default()),
+    ];
+    for &codec in &codecs {
+        let opts = ParquetOptions::default().compression(codec);
+        let file = write_parquet(&mut Cursor::new(&data), vec![], TIME, &opts).unwrap();
+        let reader = SerializedFileReader::new(temp_file("compression", &file)).unwrap();
+        let group = reader.metadata().row_group(0);
+        assert!(group.columns().iter().all(|c| c.compression() == codec), "{:?}", codec);
+    }
+}
+
+#[test]
+fn export_to_file() {
+    let data = recording(12);
+    let path = std::env::temp_dir().join(format!("binpool-{}.parquet", std::process::id()));
+    let opts = ParquetOptions::default().frames_per_row_group(4);
+    export_parquet(&mut Cursor::new(&data), &path, TIME, &opts).unwrap();
+    let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 3);
+    let file = std::fs::File::open(&path).unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap().build().unwrap();
+    let res: Vec<Row> = reader.flat_map(|b| rows(&b.unwrap())).collect();
+    assert_eq!(res, expected(&data));
+    std::fs::remove_file(&path).unwrap();
+
+    let opts = ParquetOptions::default().frames_per_row_group(0);
+    let err = write_parquet(&mut Cursor::new(&data), vec![], TIME, &opts).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::{self, Read};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const DATA: u16 = 2;
+const DELAY: Duration = Duration::from_millis(20);
+
+/// Sleeps before every read.
+struct Slow<R>(R);
+
+impl<R: Read> Read for Slow<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        thread::sleep(DELAY);
+        self.0.read(buf)
+    }
+}
+
+fn frames(n: u32) -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..n {
+        w.begin_frame(i as f64).unwrap();
+        Scalar::write_array(DATA, &vec![i; 4], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn time(frame: &Frame) -> f64 {
+    let mut time: Vec<f64> = vec![];
+    frame.read_array(TIME, &mut time).unwrap();
+    time[0]
+}
+
+#[test]
+fn advance_after_prefetch_is_prompt() {
+    let data = frames(3);
+    let mut playback = PlaybackReader::new(3, move || {
+        FrameReader::with_time(Slow(io::Cursor::new(data)), TIME)
+    });
+    assert!(playback.current_frame().is_none());
+    // Each frame takes several slow reads to decode.
+    thread::sleep(DELAY * 100);
+    for i in 0..3 {
+        let start = Instant::now();
+        assert!(playback.advance().unwrap());
+        assert!(start.elapsed() < DELAY, "frame {} took {:?}", i, start.elapsed());
+        let frame = playback.current_frame().unwrap();
+        assert_eq!(time(frame), i as f64);
+        let mut arr: Vec<u32> = vec![];
+        frame.read_array(DATA, &mut arr).unwrap();
+        assert_eq!(arr, vec![i; 4]);
+    }
+    assert!(!playback.advance().unwrap());
+    assert!(playback.current_frame().is_none());
+    assert!(!playback.advance().unwrap());
+}
+
+#[test]
+fn errors_surface_on_advance() {
+    let mut data = frames(2);
+    // Cuts the second frame in the middle of its array.
+    let len = data.len() - 20;
+    data.truncate(len);
+    let mut playback = PlaybackReader::new(1, move || {
+        FrameReader::with_time(io::Cursor::new(data), TIME)
+    });
+    assert!(playback.advance().unwrap());
+    assert_eq!(time(playback.current_frame().unwrap()), 0.0);
+    let err = playback.advance().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(playback.current_frame().is_none());
+    assert!(!playback.advance().unwrap());
+}
+
+#[test]
+fn drop_stops_prefetching() {
+    // The background thread blocks when the prefetch depth is reached.
+    let data = frames(1000);
+    let mut playback = PlaybackReader::new(2, move || {
+        FrameReader::with_time(Slow(io::Cursor::new(data)), TIME)
+    });
+    assert!(playback.advance().unwrap());
+    let start = Instant::now();
+    drop(playback);
+    // Dropping waits for at most the frame that is being decoded.
+    assert!(start.elapsed() < DELAY * 50, "{:?}", start.elapsed());
+}
@@ -0,0 +1,281 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const DATA: u16 = 2;
+
+/// A clock that only moves when sleeping or when moved by the test.
+#[derive(Clone, Default)]
+struct MockClock {
+    now: Rc<RefCell<Duration>>,
+    sleeps: Rc<RefCell<Vec<Duration>>>,
+}
+
+impl MockClock {
+    fn advance(&self, dur: Duration) {
+        *self.now.borrow_mut() += dur;
+    }
+
+    fn sleeps(&self) -> Vec<f64> {
+        self.sleeps.borrow().iter().map(|d| d.as_secs_f64()).collect()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        *self.now.borrow()
+    }
+
+    fn sleep(&mut self, dur: Duration) {
+        self.sleeps.borrow_mut().push(dur);
+        self.advance(dur);
+    }
+}
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+/// Writes frames at the given times, or without time property.
+fn frames(times: &[Option<f64>]) -> Vec<u8> {
+    let mut w = vec![];
+    for (i, t) in times.iter().enumerate() {
+        Scalar::write_array(DATA, &vec![i as u32], &mut w).unwrap();
+        if let Some(t) = *t {Time::write(TIME, t, &mut w).unwrap()}
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+fn paced(data: Vec<u8>, clock: &MockClock) -> Player<io::Cursor<Vec<u8>>, MockClock> {
+    let reader = FrameReader::new(io::Cursor::new(data), DATA).time_property(TIME);
+    Player::with_reader(reader).clock(clock.clone())
+}
+
+fn assert_close(a: &[f64], b: &[f64]) {
+    assert_eq!(a.len(), b.len(), "{:?} != {:?}", a, b);
+    for (x, y) in a.iter().zip(b) {
+        assert!((x - y).abs() < 1e-6, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn non_uniform_steps() {
+    let times = [Some(10.0), Some(10.5), Some(10.6), Some(12.0)];
+    for &speed in &[1.0, 2.0, 0.5] {
+        let clock = MockClock::default();
+        let mut player = paced(frames(&times), &clock).speed(speed);
+        let mut n = 0;
+        while let Some(frame) = player.next_frame().unwrap() {
+            assert_eq!(frame.time, times[n]);
+            // Every frame is delivered at its time since the first frame.
+            let due = (times[n].unwrap() - 10.0) / speed;
+            assert!((clock.now().as_secs_f64() - due).abs() < 1e-6, "speed {}", speed);
+            n += 1;
+        }
+        assert_eq!(n, 4);
+        let steps: Vec<f64> = [0.5, 0.1, 1.4].iter().map(|x| x / speed).collect();
+        assert_close(&clock.sleeps(), &steps);
+    }
+}
+
+#[test]
+fn missing_time_property() {
+    let times = [None, None, None];
+    // Without a frame rate, frames are delivered immediately.
+    let clock = MockClock::default();
+    let mut player = paced(frames(&times), &clock);
+    while player.next_frame().unwrap().is_some() {}
+    assert!(clock.sleeps().is_empty());
+
+    let clock = MockClock::default();
+    let mut player = paced(frames(&times), &clock).fps(4.0).speed(2.0);
+    while player.next_frame().unwrap().is_some() {}
+    assert_close(&clock.sleeps(), &[0.125, 0.125]);
+}
+
+#[test]
+fn falls_behind() {
+    let clock = MockClock::default();
+    let mut player = paced(frames(&[Some(0.0), Some(0.1), Some(0.2), Some(0.3)]), &clock);
+    player.next_frame().unwrap().unwrap();
+    assert_eq!(player.lag(), ms(0));
+    // Decoding and drawing took longer than a frame.
+    clock.advance(ms(250));
+    player.next_frame().unwrap().unwrap();
+    assert_eq!(player.lag(), ms(150));
+    player.next_frame().unwrap().unwrap();
+    assert_eq!(player.lag(), ms(50));
+    // Caught up again.
+    player.next_frame().unwrap().unwrap();
+    assert_eq!(player.lag(), ms(0));
+    assert_close(&clock.sleeps(), &[0.05]);
+}
+
+#[test]
+fn pause_and_resume() {
+    let clock = MockClock::default();
+    let mut player = paced(frames(&[Some(0.0), Some(1.0), Some(2.0)]), &clock);
+    player.next_frame().unwrap().unwrap();
+    clock.advance(ms(400));
+    player.pause();
+    assert!(player.is_paused());
+    let err = player.next_frame().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+    // Time spent paused is not counted.
+    clock.advance(ms(5000));
+    assert!((player.position() - 0.4).abs() < 1e-6);
+    player.resume();
+    assert!(!player.is_paused());
+    player.next_frame().unwrap().unwrap();
+    player.next_frame().unwrap().unwrap();
+    assert_close(&clock.sleeps(), &[0.6, 1.0]);
+    assert_eq!(clock.now(), ms(7000));
+    assert!(player.next_frame().unwrap().is_none());
+}
+
+const MASS: u16 = 3;
+
+/// Writes frames at times `0, 1, ...`, with masses written as changes
+/// between keyframes every 4 frames, and returns the masses of every frame.
+fn recording(n: usize) -> (Vec<u8>, Vec<Vec<f64>>) {
+    let mut w = vec![];
+    let mut masses = vec![];
+    let mut states = vec![];
+    for i in 0..n {
+        Time::write(TIME, i as f64, &mut w).unwrap();
+        Scalar::write_array(DATA, &vec![i as u32], &mut w).unwrap();
+        if i % 4 == 0 {
+            masses = vec![i as f64; 6];
+            Scalar::write_array(MASS, &masses, &mut w).unwrap();
+        } else {
+            let mut data = vec![];
+            (i as f64 + 0.5).write_element(&mut data).unwrap();
+            write_block(Type::F64.scalar().0, MASS, (i % 6) as u64, &data, &mut w).unwrap();
+            masses[i % 6] = i as f64 + 0.5;
+        }
+        states.push(masses.clone());
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    (w, states)
+}
+
+fn indexed(data: Vec<u8>, clock: &MockClock) -> Player<io::Cursor<Vec<u8>>, MockClock> {
+    let mut cursor = io::Cursor::new(data);
+    let index = Index::build(&mut cursor, TIME, Some(TIME)).unwrap();
+    Player::with_index(cursor, index).clock(clock.clone())
+}
+
+/// Returns the number and masses of the frame to display.
+fn tick<C: Clock>(player: &mut Player<io::Cursor<Vec<u8>>, C>) -> (u64, Vec<f64>) {
+    let frame = player.tick().unwrap().unwrap();
+    let mut masses = vec![];
+    frame.read_array(MASS, &mut masses).unwrap();
+    (frame.number, masses)
+}
+
+#[test]
+fn seek_during_play() {
+    let (data, states) = recording(10);
+    for &interval in &[0, 4] {
+        let clock = MockClock::default();
+        let mut player = indexed(data.clone(), &clock).keyframes(interval);
+        // Playback starts paused at the first frame.
+        assert_eq!(tick(&mut player), (0, states[0].clone()));
+        clock.advance(ms(5000));
+        assert_eq!(tick(&mut player).0, 0);
+        player.play();
+        clock.advance(ms(2500));
+        assert_eq!(tick(&mut player), (2, states[2].clone()));
+        player.seek_time(7.2).unwrap();
+        assert_eq!(tick(&mut player), (7, states[7].clone()));
+        clock.advance(ms(1000));
+        assert_eq!(tick(&mut player), (8, states[8].clone()));
+        // Seeking backwards replays the changes from a keyframe.
+        player.seek_frame(6).unwrap();
+        assert_eq!(tick(&mut player), (6, states[6].clone()));
+        assert!((player.position() - 6.0).abs() < 1e-6);
+        player.seek_frame(1).unwrap();
+        assert_eq!(tick(&mut player), (1, states[1].clone()));
+        // Before the first frame, the first frame is displayed.
+        player.seek_time(-3.0).unwrap();
+        assert_eq!(tick(&mut player).0, 0);
+        // Playback continues at the new speed.
+        player.seek_time(3.0).unwrap();
+        player.set_speed(2.0);
+        clock.advance(ms(1600));
+        assert_eq!(tick(&mut player), (6, states[6].clone()));
+    }
+
+    let clock = MockClock::default();
+    let mut player = indexed(data, &clock);
+    let err = player.seek_frame(10).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::FrameOutOfRange);
+    let err = player.seek_time(f64::NAN).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn loop_wrap() {
+    let (data, states) = recording(10);
+    let clock = MockClock::default();
+    let mut player = indexed(data.clone(), &clock).keyframes(4);
+    player.set_loop(true);
+    assert!(player.is_looping());
+    player.play();
+    clock.advance(ms(9500));
+    assert_eq!(tick(&mut player), (9, states[9].clone()));
+    // The last frame is displayed for a frame, then playback starts over.
+    clock.advance(ms(1000));
+    assert_eq!(tick(&mut player), (0, states[0].clone()));
+    assert!((player.position() - 0.5).abs() < 1e-6);
+    clock.advance(ms(3000));
+    assert_eq!(tick(&mut player), (3, states[3].clone()));
+    // Several periods at once.
+    clock.advance(ms(21000));
+    assert_eq!(tick(&mut player), (4, states[4].clone()));
+
+    // Without looping, the last frame stays.
+    let clock = MockClock::default();
+    let mut player = indexed(data, &clock);
+    player.play();
+    clock.advance(ms(25000));
+    assert_eq!(tick(&mut player), (9, states[9].clone()));
+}
+
+#[test]
+fn pause_and_resume_ticks() {
+    let (data, states) = recording(10);
+    let clock = MockClock::default();
+    let mut player = indexed(data, &clock).keyframes(4);
+    player.play();
+    clock.advance(ms(3200));
+    assert_eq!(tick(&mut player), (3, states[3].clone()));
+    player.pause();
+    clock.advance(ms(60000));
+    assert_eq!(tick(&mut player), (3, states[3].clone()));
+    // Seeking while paused stays paused.
+    player.seek_frame(5).unwrap();
+    clock.advance(ms(2000));
+    assert_eq!(tick(&mut player), (5, states[5].clone()));
+    player.play();
+    clock.advance(ms(1000));
+    assert_eq!(tick(&mut player), (6, states[6].clone()));
+    // The cached frame is not decoded again.
+    let read = player.reader().frames();
+    assert_eq!(tick(&mut player).0, 6);
+    assert_eq!(player.reader().frames(), read);
+    assert_eq!(player.current_frame().map(|f| f.number), Some(6));
+
+    // Without an index, there is nothing to tick.
+    let mut player = paced(frames(&[Some(0.0)]), &clock);
+    assert_eq!(player.tick().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::cell::RefCell;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const DATA: u16 = 2;
+const INTERVAL: u64 = 4096;
+
+/// Writes 50 frames with arrays of 1 to 50 KiB.
+fn medium() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..50_u32 {
+        w.begin_frame(i as f64).unwrap();
+        Scalar::write_array(DATA, &vec![i; (i as usize + 1) * 256], &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn check(reports: &[Progress], total: u64) {
+    assert!(!reports.is_empty());
+    let mut last = 0;
+    let mut frame = None;
+    for p in reports {
+        assert!(p.bytes >= last, "{:?}", p);
+        assert!(p.bytes - last <= INTERVAL, "{:?} after {}", p, last);
+        assert!(p.frame >= frame, "{:?}", p);
+        assert_eq!(p.total, Some(total));
+        last = p.bytes;
+        frame = p.frame;
+    }
+    assert_eq!(last, total);
+    assert_eq!(frame, Some(49));
+}
+
+#[test]
+fn frame_reader() {
+    let data = medium();
+    let total = data.len() as u64;
+    let reports = RefCell::new(vec![]);
+    {
+        let r = ProgressReader::new(&data[..])
+            .total(total)
+            .interval(INTERVAL)
+            .frame_marker(TIME)
+            .on_progress(|p| reports.borrow_mut().push(p));
+        let mut fr = FrameReader::with_time(r, TIME);
+        let mut frames = 0;
+        while let Some(frame) = fr.next_frame().unwrap() {
+            assert_eq!(frame.number, frames);
+            frames += 1;
+        }
+        assert_eq!(frames, 50);
+        assert_eq!(fr.get_ref().progress().bytes, total);
+    }
+    let reports = reports.into_inner();
+    check(&reports, total);
+    // Properties are reported at their start.
+    let frame_starts = reports.iter().filter(|p| p.property == Some(TIME)).count();
+    assert!(frame_starts >= 50);
+}
+
+#[test]
+fn downsample_and_read_to_end() {
+    let data = medium();
+    let total = data.len() as u64;
+    let mut reports = vec![];
+    let mut out = vec![];
+    {
+        let mut r = ProgressReader::new(&data[..])
+            .total(total)
+            .interval(INTERVAL)
+            .frame_marker(TIME)
+            .on_progress(|p| reports.push(p));
+        downsample(&mut r, &mut out, &DownsampleOptions::default()).unwrap();
+    }
+    check(&reports, total);
+    assert_eq!(out, data);
+
+    let mut reports = vec![];
+    {
+        let mut r = ProgressReader::new(&data[..])
+            .total(total)
+            .interval(INTERVAL)
+            .frame_marker(TIME)
+            .on_progress(|p| reports.push(p));
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).unwrap();
+    }
+    check(&reports, total);
+}
+
+/// Sleeps before every read, like a slow disk.
+struct Slow<'a>(&'a [u8]);
+
+impl<'a> Read for Slow<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        thread::sleep(Duration::from_millis(1));
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn cancel_mid_payload() {
+    // 4 MiB in one block, which takes at least a second to read in steps of 4 KiB.
+    let mut data = vec![];
+    Scalar::write_array(DATA, &vec![7_u32; 1 << 20], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let total = data.len() as u64;
+
+    let token = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let token = token.clone();
+        thread::spawn(move || {
+            let mut r = ProgressReader::new(Slow(&data)).interval(INTERVAL).cancel_token(token);
+            let (state, ty, _) = State::read(&mut r).unwrap();
+            let mut arr: Vec<u32> = vec![];
+            let res = Scalar::read_array(state.unwrap(), ty, &mut arr, &mut r);
+            (res, r.progress().bytes, Instant::now())
+        })
+    };
+    thread::sleep(Duration::from_millis(50));
+    token.store(true, Ordering::Relaxed);
+    let cancelled = Instant::now();
+    let (res, bytes, stopped) = reader.join().unwrap();
+
+    let err = res.unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    assert_eq!(err.at_byte(), Some(bytes));
+    assert!(bytes > 20 && bytes < total, "stopped at {} of {}", bytes, total);
+    // The token is checked before every read, which reads at most one interval.
+    assert!(stopped.duration_since(cancelled) < Duration::from_millis(500));
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::ErrorKind as IoErrorKind;
+
+use binpool::*;
+
+const KNOWN: u16 = 1;
+const DIAGNOSTIC: u16 = 2;
+const CUSTOM: u16 = 3;
+
+/// Writes one known property and two unknown ones, of which one has a custom format.
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    Scalar::write_array(DIAGNOSTIC, &vec![7_u16; 5], &mut w).unwrap();
+    Vector::write_array(KNOWN, &vec![[1.0_f32, 2.0], [3.0, 4.0]], &mut w).unwrap();
+    write_block(Type::offset_custom_format() + 4, CUSTOM, 2, &[9; 13], &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+fn read_known(r: &mut PoolReader<&[u8]>) -> std::io::Result<Vec<[f32; 2]>> {
+    let mut arr = vec![];
+    while let Some((state, ty, prop)) = r.next_property()? {
+        match prop {
+            KNOWN => Vector::read_array(state, ty, &mut arr, r)?,
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    Ok(arr)
+}
+
+#[test]
+fn strict_by_default() {
+    let data = stream();
+    let mut r = PoolReader::new(&data[..]);
+    r.register(KNOWN);
+    let err = read_known(&mut r).unwrap_err();
+    assert_eq!(err.kind(), IoErrorKind::InvalidData);
+    assert_eq!(Error::from_io(&err).property(), Some(DIAGNOSTIC));
+}
+
+#[test]
+fn collect_unknown() {
+    let data = stream();
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Collect);
+    r.register(KNOWN);
+    assert_eq!(read_known(&mut r).unwrap(), vec![[1.0, 2.0], [3.0, 4.0]]);
+    let unknown: Vec<(u16, u16, u64, usize)> = r.unknown_blocks().iter()
+        .map(|b| (b.type_format, b.property_id, b.offset, b.data.len()))
+        .collect();
+    assert_eq!(unknown, vec![
+        (Type::U16.scalar().0, DIAGNOSTIC, 0, 10),
+        (Type::offset_custom_format() + 4, CUSTOM, 2, 13),
+    ]);
+    assert_eq!(r.unknown_blocks()[1].data, vec![9; 13]);
+    assert_eq!(r.take_unknown_blocks().len(), 2);
+    assert!(r.unknown_blocks().is_empty());
+}
+
+#[test]
+fn skip_unknown() {
+    let data = stream();
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+    r.register(KNOWN);
+    assert_eq!(read_known(&mut r).unwrap(), vec![[1.0, 2.0], [3.0, 4.0]]);
+    assert!(r.unknown_blocks().is_empty());
+}
@@ -0,0 +1,141 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const VELOCITY: u16 = 2;
+const ENERGY: u16 = 3;
+
+const STEPS: u64 = 20;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Particles {
+    positions: Vec<[f32; 3]>,
+    velocities: Vec<[f32; 3]>,
+    energy: f64,
+}
+
+impl Recordable for Particles {
+    fn write_frame<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        Vector::write_array(POSITION, &self.positions, w)?;
+        Vector::write_array(VELOCITY, &self.velocities, w)?;
+        self.energy.write_property(ENERGY, w)
+    }
+
+    fn read_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.positions.clear();
+        frame.read_array(POSITION, &mut self.positions)?;
+        self.velocities.clear();
+        frame.read_array(VELOCITY, &mut self.velocities)?;
+        self.energy = frame.read_property(ENERGY)?.unwrap();
+        Ok(())
+    }
+}
+
+fn start() -> Particles {
+    Particles {
+        positions: (0..5).map(|i| [i as f32, 0.0, 0.0]).collect(),
+        velocities: (0..5).map(|i| [0.0, 1.0, i as f32 * 0.5]).collect(),
+        energy: 0.0,
+    }
+}
+
+/// Moves particles and slows them down.
+fn sim_step(s: &mut Particles) {
+    for (p, v) in s.positions.iter_mut().zip(&mut s.velocities) {
+        for i in 0..3 {
+            p[i] += v[i] * 0.1;
+            v[i] *= 0.9;
+        }
+    }
+    s.energy = s.velocities.iter().flat_map(|v| v.iter()).map(|&x| (x * x) as f64).sum();
+}
+
+/// Records the simulation, returning the stream and the state after each step.
+fn record() -> (Vec<u8>, Vec<Particles>) {
+    let mut recorder = Recorder::new(vec![], TIME);
+    let mut state = start();
+    let mut states = vec![];
+    for i in 0..STEPS {
+        assert_eq!(recorder.steps(), i);
+        recorder.step(&mut state, sim_step).unwrap();
+        states.push(state.clone());
+    }
+    assert_eq!(recorder.steps(), STEPS);
+    (recorder.finish().unwrap(), states)
+}
+
+#[test]
+fn record_and_replay() {
+    let (data, states) = record();
+    let mut replayer = Replayer::new(&data[..], TIME);
+    let mut state = Particles::default();
+    for (i, expected) in states.iter().enumerate() {
+        assert!(replayer.step(&mut state).unwrap());
+        assert_eq!(&state, expected);
+        assert_eq!(replayer.reader().frames(), i as u64 + 1);
+    }
+    assert!(!replayer.step(&mut state).unwrap());
+    assert!(!replayer.step(&mut state).unwrap());
+    assert_eq!(&state, states.last().unwrap());
+
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    // The time of a frame is the number of steps before it.
+    let mut times = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {times.push(frame.time.unwrap())}
+    assert_eq!(times, (0..STEPS).map(|i| i as f64).collect::<Vec<f64>>());
+}
+
+#[test]
+fn verify() {
+    let (data, _) = record();
+
+    // A deterministic step matches the recording.
+    let mut replayer = Replayer::new(&data[..], TIME);
+    let mut state = start();
+    for _ in 0..STEPS {
+        assert_eq!(replayer.verify(&mut state, sim_step).unwrap(), Some(vec![]));
+    }
+    assert_eq!(replayer.verify(&mut state, sim_step).unwrap(), None);
+
+    // A wrong energy does not change the following steps.
+    let mut replayer = Replayer::new(&data[..], TIME);
+    let mut state = start();
+    for i in 0..STEPS {
+        let diverged = replayer.verify(&mut state, |s| {
+            sim_step(s);
+            if i == 12 {s.energy = -1.0}
+        }).unwrap().unwrap();
+        assert_eq!(diverged, if i == 12 {vec![ENERGY]} else {vec![]}, "{}", i);
+    }
+
+    // The state keeps the result of the step, so a divergence carries over.
+    let mut replayer = Replayer::new(&data[..], TIME);
+    let mut state = start();
+    for i in 0..STEPS {
+        let diverged = replayer.verify(&mut state, |s| {
+            sim_step(s);
+            if i == 5 {s.velocities[2][1] += 1e-3}
+        }).unwrap().unwrap();
+        let expected = match i {
+            0..=4 => vec![],
+            5 => vec![VELOCITY],
+            _ => vec![POSITION, VELOCITY, ENERGY],
+        };
+        assert_eq!(diverged, expected, "{}", i);
+    }
+
+    // Fewer items than recorded.
+    let mut replayer = Replayer::new(&data[..], TIME);
+    let mut state = start();
+    let diverged = replayer.verify(&mut state, |s| {
+        sim_step(s);
+        s.positions.pop();
+    }).unwrap().unwrap();
+    assert_eq!(diverged, vec![POSITION]);
+    assert_eq!(state.positions.len(), 4);
+}
@@ -0,0 +1,177 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+const FRAMES: usize = 6;
+
+/// The fixtures checked in with the crate.
+fn fixtures() -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("compat");
+    let mut res: Vec<(String, Vec<u8>)> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|x| x == "pool"))
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read(&path).unwrap())
+        })
+        .collect();
+    res.sort();
+    res
+}
+
+/// Frames with a time, positions and masses in three blocks.
+fn frames() -> Vec<u8> {
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]), TIME);
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64 + 0.5).unwrap();
+        Vector::write_array(POSITION, &vec![[i as f32; 3]; 1 + i], &mut w).unwrap();
+        let ty = Type::F64.scalar().0;
+        let blocks: Vec<RawBlock> = (0..3).map(|j| {
+            let mut data = vec![];
+            (j as f64).write_element(&mut data).unwrap();
+            RawBlock {type_format: ty, property_id: MASS, offset: j, data}
+        }).collect();
+        RawBlock::write_property(ty, MASS, &blocks, &mut w).unwrap();
+    }
+    let mut w = w.finish().unwrap();
+    std::mem::take(w.get_mut())
+}
+
+/// Reads every property of a stream in either byte order.
+fn read_all(data: &[u8]) {
+    let mut r = PoolReader::new(data).unknown(Unknown::Collect).reserved_as_data(true);
+    while r.next_property().unwrap().is_some() {}
+}
+
+/// Counts the properties of a little endian stream.
+fn count(data: &[u8]) -> u64 {
+    let mut r = data;
+    let mut n = 0;
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        n += 1;
+    }
+    n
+}
+
+/// Recovers a stream cut at every position, checking that the result reads to the end.
+fn chop(name: &str, data: &[u8], opts: &RecoverOptions) -> Vec<RecoveryReport> {
+    let mut reports = vec![];
+    for cut in 0..data.len() {
+        let mut f = Cursor::new(data[..cut].to_vec());
+        let dry = recover(&mut f, &opts.clone().dry_run(true)).unwrap();
+        assert_eq!(f.get_ref(), &data[..cut], "{} {}", name, cut);
+        let report = recover(&mut f, opts).unwrap();
+        assert_eq!(dry, report);
+
+        // The complete properties are kept, followed by the end of stream.
+        let kept = report.len as usize - 2;
+        assert!(!report.complete && kept <= cut, "{} {}", name, cut);
+        assert_eq!(report.original_len, cut as u64);
+        assert_eq!(report.dropped_bytes, (cut - kept) as u64);
+        let recovered = f.into_inner();
+        assert_eq!(&recovered[..kept], &data[..kept]);
+        assert_eq!(&recovered[kept..kept + 2], &[0, 0]);
+        read_all(&recovered[..kept + 2]);
+        if name != "big_endian.pool" {assert_eq!(count(&recovered), report.properties)}
+
+        // Recovering again finds the end of stream.
+        let again = recover(&mut Cursor::new(recovered), opts).unwrap();
+        assert!(again.complete);
+        assert_eq!((again.len, again.properties), (report.len, report.properties));
+        reports.push(report);
+    }
+    // The uncut stream is complete.
+    let report = recover(&mut Cursor::new(data.to_vec()), opts).unwrap();
+    assert!(report.complete && report.dropped_bytes == 0);
+    assert_eq!(report.len, data.len() as u64);
+    reports
+}
+
+#[test]
+fn fixtures_at_every_position() {
+    let fixtures = fixtures();
+    assert_eq!(fixtures.len(), 10);
+    for (name, data) in &fixtures {
+        let reports = chop(name, data, &RecoverOptions::default());
+        // Properties are only ever added as the cut moves on.
+        for w in reports.windows(2) {assert!(w[0].properties <= w[1].properties)}
+        if name == "big_endian.pool" {continue}
+        // A stream cut right after the end of a property keeps all of it.
+        let full = recover(&mut Cursor::new(data.clone()), &RecoverOptions::default()).unwrap();
+        let last = &reports[data.len() - 2];
+        assert_eq!((last.properties, last.dropped_bytes), (full.properties, 0), "{}", name);
+        assert_eq!(count(data), full.properties, "{}", name);
+    }
+}
+
+#[test]
+fn frames_at_every_position() {
+    let data = frames();
+    let opts = RecoverOptions::default().time_property(TIME);
+    let reports = chop("frames", &data, &opts);
+    let mut complete_frames = 0;
+    for (cut, report) in reports.iter().enumerate() {
+        let mut f = Cursor::new(data[..cut].to_vec());
+        recover(&mut f, &opts).unwrap();
+        let recovered = f.into_inner();
+        let mut r = FrameReader::with_time(&recovered[..report.len as usize], TIME);
+        let mut last = None;
+        let mut n = 0;
+        while let Some(frame) = r.next_frame().unwrap() {
+            last = frame.time;
+            n += 1;
+        }
+        // The time of the last frame kept is reported.
+        assert_eq!(report.last_time, last, "{}", cut);
+        assert!(n >= complete_frames);
+        complete_frames = n;
+    }
+    assert_eq!(complete_frames, FRAMES);
+}
+
+#[test]
+fn dropped_blocks() {
+    let data = frames();
+    // The masses of the last frame end with the end of bytes, and the end of stream.
+    let masses = data.len() - 2 - 8 - 3 * (16 + 8) - 4;
+    let mut f = Cursor::new(data[..data.len() - 2 - 8 - 3].to_vec());
+    let report = recover(&mut f, &RecoverOptions::default().time_property(TIME)).unwrap();
+    assert_eq!(report.dropped_blocks, 3);
+    assert_eq!(report.len, masses as u64 + 2);
+    assert_eq!(report.last_time, Some(FRAMES as f64 - 0.5));
+    let mut f = Cursor::new(data[..masses + 4 + 16 + 4].to_vec());
+    assert_eq!(recover(&mut f, &RecoverOptions::default()).unwrap().dropped_blocks, 1);
+    let mut f = Cursor::new(data[..masses + 2].to_vec());
+    assert_eq!(recover(&mut f, &RecoverOptions::default()).unwrap().dropped_blocks, 0);
+}
+
+#[test]
+fn truncated_file() {
+    let data = frames();
+    let dir = std::env::temp_dir().join(format!("binpool-recover-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cut.pool");
+    fs::write(&path, &data[..data.len() - 10]).unwrap();
+
+    let opts = RecoverOptions::default().time_property(TIME);
+    let dry = recover_file(&path, &opts.clone().dry_run(true)).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), &data[..data.len() - 10]);
+    let report = recover_file(&path, &opts).unwrap();
+    assert_eq!(dry, report);
+    let recovered = fs::read(&path).unwrap();
+    assert_eq!(recovered.len() as u64, report.len);
+    assert_eq!(count(&recovered), report.properties);
+    assert!(recover_file(&path, &opts).unwrap().complete);
+    assert_eq!(fs::read(&path).unwrap(), recovered);
+    fs::remove_dir_all(&dir).unwrap();
+}
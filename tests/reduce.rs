@@ -0,0 +1,121 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 0;
+const CHARGE: u16 = 1;
+const MOMENTUM: u16 = 2;
+
+const FRAMES: u64 = 20;
+/// The frame where a particle gains charge.
+const BROKEN_CHARGE: u64 = 7;
+/// The frame where momentum is not conserved.
+const BROKEN_MOMENTUM: u64 = 12;
+
+/// Writes frames of particles with zero total charge and momentum,
+/// except in the broken frames.
+/// Charges are written in two blocks.
+fn stream() -> Vec<u8> {
+    let mut rng = Rng::new(178);
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64).unwrap();
+        let n = 2 + rng.below(4) as usize;
+        let mut charges: Vec<i32> = (0..n).map(|_| rng.below(5) as i32 - 2).collect();
+        charges.push(-charges.iter().sum::<i32>());
+        if i == BROKEN_CHARGE {charges[0] += 1}
+        let mut momenta: Vec<[f64; 3]> = (0..n).map(|_| [rng.below(9) as f64 - 4.0; 3]).collect();
+        let mut last = [0.0; 3];
+        for p in &momenta {for k in 0..3 {last[k] -= p[k]}}
+        momenta.push(last);
+        if i == BROKEN_MOMENTUM {momenta[1][2] += 0.5}
+
+        let half = charges.len() / 2;
+        let ty = Type::I32.scalar().0;
+        let blocks: Vec<RawBlock> = [(0, &charges[..half]), (half, &charges[half..])].iter()
+            .map(|&(offset, items)| {
+                let mut data = vec![];
+                for x in items {x.write_element(&mut data).unwrap()}
+                RawBlock {type_format: ty, property_id: CHARGE, offset: offset as u64, data}
+            })
+            .collect();
+        RawBlock::write_property(ty, CHARGE, &blocks, &mut w).unwrap();
+        Vector::write_array(MOMENTUM, &momenta, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn reader(data: &[u8]) -> FrameReader<&[u8]> {
+    FrameReader::with_time(data, TIME)
+        .reduce(CHARGE, vec![0.0, 0.0], |acc, q: &i32| {
+            acc[0] += *q as f64;
+            acc[1] += 1.0;
+        })
+        .reduce(MOMENTUM, vec![0.0; 3], |acc, p: &[f64; 3]| {
+            for k in 0..3 {acc[k] += p[k]}
+        })
+        .invariant("charge", |res| {
+            if res[0][0] == 0.0 {Ok(())} else {Err(format!("total charge {}", res[0][0]))}
+        })
+        .invariant("momentum", |res| {
+            if res[1].iter().all(|&p| p == 0.0) {Ok(())} else {Err("momentum".into())}
+        })
+}
+
+#[test]
+fn broken_frames_are_flagged() {
+    let data = stream();
+    let mut r = reader(&data);
+    let mut frames = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        assert_eq!(frame.number, frames);
+        // The reductions of the frame are available after reading it.
+        let mut charges: Vec<i32> = vec![];
+        frame.read_array(CHARGE, &mut charges).unwrap();
+        assert_eq!(r.reductions()[0], vec![charges.iter().sum::<i32>() as f64,
+                                           charges.len() as f64]);
+        frames += 1;
+    }
+    assert_eq!(frames, FRAMES);
+    let violations = r.violations();
+    let flagged: Vec<(u64, &str)> = violations.iter().map(|v| (v.frame, &v.name[..])).collect();
+    assert_eq!(flagged, vec![(BROKEN_CHARGE, "charge"), (BROKEN_MOMENTUM, "momentum")]);
+    assert_eq!(violations[0].message, "total charge 1");
+    assert_eq!(violations[0].values[0][0], 1.0);
+    assert_eq!(violations[1].values[1], vec![0.0, 0.0, 0.5]);
+}
+
+#[test]
+fn abort_on_first_violation() {
+    let data = stream();
+    let mut r = reader(&data).abort_on_violation(true);
+    for i in 0..BROKEN_CHARGE {
+        assert_eq!(r.next_frame().unwrap().unwrap().number, i);
+    }
+    let err = r.next_frame().unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.frame()), (ErrorKind::InvariantViolation, Some(BROKEN_CHARGE)));
+    assert_eq!(r.violations().len(), 1);
+}
+
+#[test]
+fn no_reductions() {
+    // Invariants without reductions see no results.
+    let data = stream();
+    let mut r = FrameReader::with_time(&data[..], TIME)
+        .invariant("empty", |res| if res.is_empty() {Ok(())} else {Err("results".into())});
+    while r.next_frame().unwrap().is_some() {}
+    assert!(r.violations().is_empty());
+    assert!(r.reductions().is_empty());
+
+    // Another element type than in the stream.
+    let mut r = FrameReader::with_time(&data[..], TIME)
+        .reduce(MOMENTUM, vec![0.0], |acc, p: &[f32; 3]| acc[0] += p[0] as f64);
+    let err = Error::from_io(&r.next_frame().unwrap_err());
+    assert_eq!((err.kind(), err.property()), (ErrorKind::TypeMismatch, Some(MOMENTUM)));
+}
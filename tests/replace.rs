@@ -0,0 +1,129 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+/// Writes frames with 5, 2 and 3 positions.
+fn shrinking() -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    for (frame, &n) in [5, 2, 3].iter().enumerate() {
+        w.begin_frame(frame as f64).unwrap();
+        let pos: Vec<[f32; 2]> = (0..n).map(|i| [frame as f32, i as f32]).collect();
+        Vector::write_array(POSITION, &pos, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn read_frames(opts: &ReadOptions) -> Vec<Vec<[f32; 2]>> {
+    let data = shrinking();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut pos = vec![];
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        frame.read_array_with(POSITION, &mut pos, opts).unwrap();
+        res.push(pos.clone());
+    }
+    res
+}
+
+#[test]
+fn shrinking_frame() {
+    let frames = read_frames(&ReadOptions::default().mode(ReadMode::Replace));
+    assert_eq!(frames.iter().map(|f| f.len()).collect::<Vec<_>>(), vec![5, 2, 3]);
+    assert_eq!(frames[1], vec![[1.0, 0.0], [1.0, 1.0]]);
+    assert_eq!(frames[2], vec![[2.0, 0.0], [2.0, 1.0], [2.0, 2.0]]);
+
+    // Merging keeps the stale positions of the first frame.
+    let frames = read_frames(&ReadOptions::default());
+    assert_eq!(frames[1].len(), 5);
+    assert_eq!(frames[1][4], [0.0, 4.0]);
+}
+
+#[test]
+fn shrinking_property() {
+    let opts = ReadOptions::default().mode(ReadMode::Replace);
+    let mut pos: Vec<[f32; 2]> = vec![];
+    let mut mass: Vec<u16> = vec![];
+    let mut m: Vec<[[u8; 2]; 2]> = vec![];
+    for &n in &[4, 1] {
+        let mut data = vec![];
+        Vector::write_array(POSITION, &vec![[n as f32; 2]; n], &mut data).unwrap();
+        Scalar::write_array(2, &vec![n as u16; n], &mut data).unwrap();
+        Matrix::write_array(3, &vec![[[n as u8; 2]; 2]; n], &mut data).unwrap();
+        let mut r = &data[..];
+        let (state, ty, _) = State::read(&mut r).unwrap();
+        Vector::read_array_with(state.unwrap(), ty, &mut pos, &opts, &mut r).unwrap();
+        let (state, ty, _) = State::read(&mut r).unwrap();
+        Scalar::read_array_with(state.unwrap(), ty, &mut mass, &opts, &mut r).unwrap();
+        let (state, ty, _) = State::read(&mut r).unwrap();
+        Matrix::read_array_with(state.unwrap(), ty, &mut m, &opts, &mut r).unwrap();
+    }
+    assert_eq!(pos, vec![[1.0; 2]]);
+    assert_eq!(mass, vec![1]);
+    assert_eq!(m, vec![[[1; 2]; 2]]);
+}
+
+#[test]
+fn kept_when_property_fails() {
+    let opts = ReadOptions::default().mode(ReadMode::Replace);
+    let mut data = vec![];
+    Scalar::write_array(2, &vec![7_u16; 3], &mut data).unwrap();
+    let mut mass = vec![1_u16, 2];
+    // The data ends after the block header.
+    let mut r = &data[..20];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    assert!(Scalar::read_array_with(state.unwrap(), ty, &mut mass, &opts, &mut r).is_err());
+    assert_eq!(mass, vec![1, 2]);
+
+    // A frame with blocks of another type.
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.begin_frame(0.0).unwrap();
+    Scalar::write_array(POSITION, &vec![1_u8], &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let frame = FrameReader::with_time(&data[..], TIME).next_frame().unwrap().unwrap();
+    let mut pos = vec![[3.0_f32; 2]];
+    assert!(frame.read_array_with(POSITION, &mut pos, &opts).is_err());
+    assert_eq!(pos, vec![[3.0; 2]]);
+    // A frame without the property keeps the array.
+    assert!(!frame.read_array_with(42, &mut pos, &opts).unwrap());
+    assert_eq!(pos, vec![[3.0; 2]]);
+}
+
+/// An array from another crate, written before arrays could be shortened.
+#[derive(Default)]
+struct Fixed(Vec<u16>);
+
+impl Array for Fixed {
+    type Item = u16;
+
+    fn len(&self) -> usize {self.0.len()}
+    fn get(&self, ind: usize) -> &u16 {&self.0[ind]}
+    fn set(&mut self, ind: usize, val: u16) {self.0[ind] = val}
+    fn push(&mut self, val: u16) {self.0.push(val)}
+}
+
+#[test]
+fn array_without_truncate() {
+    // Arrays that can not be shortened are merged into.
+    for &mode in &[ReadMode::Replace, ReadMode::Merge] {
+        let opts = ReadOptions::default().mode(mode);
+        let mut mass = Fixed(vec![9; 4]);
+        for &n in &[3, 1] {
+            let mut data = vec![];
+            Scalar::write_array(2, &vec![n as u16; n], &mut data).unwrap();
+            let mut r = &data[..];
+            let (state, ty, _) = State::read(&mut r).unwrap();
+            Scalar::read_array_with(state.unwrap(), ty, &mut mass, &opts, &mut r).unwrap();
+        }
+        assert_eq!(mass.0, vec![1, 3, 3, 9]);
+    }
+
+    let mut fixed = Fixed(vec![1, 2, 3]);
+    fixed.resize_with_default(5);
+    fixed.resize_with_default(1);
+    assert_eq!(fixed.0, vec![1, 2, 3, 0, 0]);
+}
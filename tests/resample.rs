@@ -0,0 +1,154 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::f64::consts::PI;
+use std::io::{self, Cursor};
+
+const TIME: u16 = 0;
+const SIGNAL: u16 = 1;
+const STEP: u16 = 2;
+const NOTE: u16 = 3;
+
+/// Records `sin t` and `cos t` over `0..=2π` with adaptive time steps in `min..max`,
+/// and the number of the frame as an integer.
+fn sine(seed: u64, min: f64, max: f64) -> Vec<u8> {
+    let mut rng = Rng::new(seed);
+    let mut w = FrameWriter::new(vec![], TIME);
+    TimeUnit::Seconds.write(TIME, &mut w).unwrap();
+    Scalar::write_array(NOTE, &b"sine".to_vec(), &mut w).unwrap();
+    let mut t = 0.0;
+    let mut i = 0_u32;
+    loop {
+        w.begin_frame(t).unwrap();
+        Scalar::write_array(SIGNAL, &vec![t.sin(), t.cos()], &mut w).unwrap();
+        Scalar::write_array(STEP, &vec![i], &mut w).unwrap();
+        if t == 2.0 * PI {break}
+        t = (t + min + rng.unit() * (max - min)).min(2.0 * PI);
+        i += 1;
+    }
+    w.finish().unwrap()
+}
+
+type Sample = (f64, Vec<f64>, Vec<u32>);
+
+/// Resamples a stream and reads the frames of the output.
+fn resampled(data: &[u8], dt: f64, opts: &ResampleOptions) -> io::Result<Vec<Sample>> {
+    let mut out = vec![];
+    resample(&mut Cursor::new(data), &mut out, TIME, dt, opts)?;
+    validate(&mut &out[..], &ValidateOptions::default()).unwrap();
+    let mut r = FrameReader::with_time(&out[..], TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame()? {
+        let (mut signal, mut step) = (vec![], vec![]);
+        frame.read_array(SIGNAL, &mut signal)?;
+        frame.read_array(STEP, &mut step)?;
+        res.push((frame.time.unwrap(), signal, step));
+    }
+    Ok(res)
+}
+
+fn max_error(samples: &[Sample]) -> f64 {
+    samples.iter()
+        .map(|(t, signal, _)| (signal[0] - t.sin()).abs().max((signal[1] - t.cos()).abs()))
+        .fold(0.0, f64::max)
+}
+
+#[test]
+fn sine_wave() {
+    let dt = 0.01;
+    for seed in 0..5 {
+        let samples = resampled(&sine(seed, 0.005, 0.05), dt, &ResampleOptions::default()).unwrap();
+        // Every time on the grid in the recorded range.
+        assert_eq!(samples.len(), (2.0 * PI / dt) as usize + 1);
+        for (k, sample) in samples.iter().enumerate() {
+            assert_eq!(sample.0, k as f64 * dt);
+        }
+        // Linear interpolation is off by at most `h^2 / 8` times the second derivative.
+        let err = max_error(&samples);
+        assert!(err > 0.0 && err <= 0.05 * 0.05 / 8.0, "{}", err);
+        // Smaller steps give smaller errors.
+        let fine = resampled(&sine(seed, 0.001, 0.005), dt, &ResampleOptions::default()).unwrap();
+        assert!(max_error(&fine) <= 0.005 * 0.005 / 8.0);
+    }
+}
+
+#[test]
+fn normal_stream() {
+    let data = sine(1, 0.005, 0.05);
+    let mut out = vec![];
+    resample(&mut Cursor::new(&data), &mut out, TIME, 0.1, &ResampleOptions::default()).unwrap();
+    let index = Index::build(&mut Cursor::new(&out), TIME, Some(TIME)).unwrap();
+    assert_eq!(index.frame_count(), 63);
+    assert!(index.is_monotonic());
+    // The data before the first frame and the time unit are kept.
+    let mut r = FrameReader::with_time(&out[..], TIME);
+    r.next_frame().unwrap().unwrap();
+    assert_eq!(r.time_unit(), Some(&TimeUnit::Seconds));
+    assert!(r.preamble().iter().any(|b| b.property_id == NOTE && b.data == b"sine"));
+}
+
+#[test]
+fn integers() {
+    let data = sine(2, 0.05, 0.3);
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut times = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {times.push(frame.time.unwrap())}
+    for &nearest in &[false, true] {
+        let opts = ResampleOptions::default().nearest(nearest);
+        for (t, _, step) in resampled(&data, 0.01, &opts).unwrap() {
+            let i = times.iter().rposition(|&x| x <= t).unwrap();
+            let expected = if nearest && i + 1 < times.len() &&
+                              t - times[i] >= times[i + 1] - t {i + 1} else {i};
+            assert_eq!(step, vec![expected as u32], "{} {}", t, nearest);
+        }
+    }
+}
+
+#[test]
+fn extrapolate() {
+    let data = sine(3, 0.01, 0.05);
+    let opts = ResampleOptions::default().start(-0.5).end(7.0);
+    let clamped = resampled(&data, 0.1, &opts).unwrap();
+    assert_eq!(clamped.len(), 76);
+    assert_eq!(clamped[0].0, -0.5);
+    // Times before and after the recording have the values of the first and last frame.
+    for (t, signal, _) in &clamped {
+        if *t < 0.0 {assert_eq!(signal, &vec![0.0, 1.0])}
+        if *t > 2.0 * PI {assert_eq!(signal, &vec![(2.0 * PI).sin(), 1.0])}
+    }
+
+    let skipped = resampled(&data, 0.1, &opts.extrapolate(Extrapolate::Skip)).unwrap();
+    assert_eq!(skipped.len(), 63);
+    assert_eq!(skipped, clamped[5..68]);
+
+    let err = resampled(&data, 0.1, &opts.extrapolate(Extrapolate::Error)).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::FrameOutOfRange);
+    // Within the range, nothing is extrapolated.
+    let opts = ResampleOptions::default().start(1.0).end(2.0).extrapolate(Extrapolate::Error);
+    assert_eq!(resampled(&data, 0.1, &opts).unwrap().len(), 11);
+}
+
+#[test]
+fn invalid_input() {
+    let data = sine(4, 0.05, 0.3);
+    for &dt in &[0.0, -1.0, f64::NAN, f64::INFINITY] {
+        let err = resampled(&data, dt, &ResampleOptions::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    let opts = ResampleOptions::default().end(f64::INFINITY);
+    assert_eq!(resampled(&data, 0.1, &opts).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+    // Time going backwards.
+    let mut w = FrameWriter::new(vec![], TIME);
+    for &t in &[0.0, 2.0, 1.0] {
+        w.begin_frame(t).unwrap();
+        Scalar::write_array(SIGNAL, &vec![t], &mut w).unwrap();
+    }
+    let err = resampled(&w.finish().unwrap(), 0.1, &ResampleOptions::default()).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::InvalidData);
+}
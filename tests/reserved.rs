@@ -0,0 +1,104 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use std::io::Write;
+
+use binpool::*;
+
+const POSITION: u16 = 1;
+/// A property id that legacy streams used for their own data.
+const LEGACY: u16 = 0xff10;
+
+#[test]
+fn constants() {
+    assert!(is_reserved_property(RESERVED_PROPERTY_START));
+    assert!(!is_reserved_property(RESERVED_PROPERTY_START - 1));
+    assert!(is_reserved_format(RESERVED_FORMAT_START));
+    assert!(!is_reserved_format(Type::offset_custom_format()));
+    for &format in &[HEADER_FORMAT, UNITS_FORMAT, METADATA_FORMAT, FOOTER_FORMAT] {
+        assert!(is_reserved_format(format));
+    }
+    for &prop in &[HEADER_PROPERTY, UNITS_PROPERTY, METADATA_PROPERTY] {
+        assert!(is_reserved_property(prop));
+    }
+}
+
+#[test]
+fn refused() {
+    let mut w = PoolWriter::new(vec![]);
+    Scalar::write_array(POSITION, &vec![1.0_f32], &mut w).unwrap();
+    let len = w.get_ref().len();
+    let err = Scalar::write_array(LEGACY, &vec![2.0_f32], &mut w).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let err = Error::from_io(&err);
+    assert_eq!((err.kind(), err.property()), (ErrorKind::ReservedProperty, Some(LEGACY)));
+    // Nothing of the refused property was written.
+    assert_eq!(w.get_ref().len(), len);
+
+    // Also when the property header is written one byte at a time.
+    let mut w = PoolWriter::new(vec![]);
+    w.write_header().unwrap();
+    let len = w.get_ref().len();
+    let mut header = Type::U8.scalar().0.to_le_bytes().to_vec();
+    header.extend_from_slice(&LEGACY.to_le_bytes());
+    for &byte in &header[..3] {
+        w.write_all(&[byte]).unwrap();
+    }
+    assert_eq!(w.get_ref().len(), len);
+    let err = w.write_all(&header[3..]).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::ReservedProperty);
+    assert_eq!(w.get_ref().len(), len);
+
+    // Metadata is written with reserved formats.
+    let mut w = PoolWriter::new(vec![]);
+    write_metadata(&[(POSITION, &Metadata::new().with(DESCRIPTION, "x"))], &mut w).unwrap();
+}
+
+/// Writes user data next to metadata and a property with a reserved id.
+fn legacy() -> Vec<u8> {
+    let opts = WriteOptions::default().allow_reserved(true);
+    let mut w = PoolWriter::new(vec![]).with_options(opts);
+    write_metadata(&[(LEGACY, &Metadata::new().with(DESCRIPTION, "legacy"))], &mut w).unwrap();
+    Scalar::write_array(POSITION, &vec![1.0_f32, 2.0], &mut w).unwrap();
+    Scalar::write_array(LEGACY, &vec![7_u32; 3], &mut w).unwrap();
+    w.finish().unwrap()
+}
+
+#[test]
+fn metadata_next_to_data() {
+    let data = legacy();
+    let mut r = PoolReader::new(&data[..]);
+    r.register(POSITION);
+    let mut props = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        props.push(prop);
+    }
+    // The reserved property is not data.
+    assert_eq!(props, vec![POSITION]);
+    assert_eq!(r.metadata(LEGACY).and_then(|m| m.description()), Some("legacy"));
+    let reserved: Vec<(u16, u16, usize)> = r.reserved_blocks().iter()
+        .map(|b| (b.type_format, b.property_id, b.data.len()))
+        .collect();
+    assert_eq!(reserved, vec![(Type::U32.scalar().0, LEGACY, 12)]);
+}
+
+#[test]
+fn reserved_as_data() {
+    let data = legacy();
+    let mut r = PoolReader::new(&data[..]).reserved_as_data(true);
+    for &prop in &[METADATA_PROPERTY, POSITION, LEGACY] {r.register(prop)}
+    let mut legacy: Vec<u32> = vec![];
+    let mut props = vec![];
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        match prop {
+            LEGACY => Scalar::read_array(state, ty, &mut legacy, &mut r).unwrap(),
+            _ => {RawBlock::read_property(state, ty, prop, &mut r).unwrap();}
+        }
+        props.push(prop);
+    }
+    assert_eq!(props, vec![METADATA_PROPERTY, POSITION, LEGACY]);
+    assert_eq!(legacy, vec![7; 3]);
+    assert!(r.reserved_blocks().is_empty());
+}
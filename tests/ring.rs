@@ -0,0 +1,199 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Write;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+const NOTE: u16 = 3;
+
+/// The state of the simulation after every frame.
+#[derive(Clone, Debug, PartialEq, Default)]
+struct Arrays {
+    positions: Vec<[f32; 3]>,
+    masses: Vec<f64>,
+}
+
+/// Writes frames of fully written positions and masses written as changes only.
+fn record<W: Write>(frames: usize, w: &mut W, begin: &mut dyn FnMut(&mut W, f64)) -> Vec<Arrays> {
+    let mut rng = Rng::new(158);
+    let mut state = Arrays::default();
+    let mut res = vec![];
+    for i in 0..frames {
+        begin(w, i as f64);
+        let n = 1 + rng.below(20) as usize;
+        state.positions = (0..n).map(|_| [rng.unit() as f32; 3]).collect();
+        Vector::write_array(POSITION, &state.positions, w).unwrap();
+        // A few masses change every frame.
+        let offset = rng.below(30) as usize;
+        let changes: Vec<f64> = (0..1 + rng.below(5)).map(|_| rng.unit()).collect();
+        let mut data = vec![];
+        for x in &changes {x.write_element(&mut data).unwrap()}
+        write_block(Type::F64.vector(1).unwrap().0, MASS, offset as u64, &data, w).unwrap();
+        let end = offset + changes.len();
+        if state.masses.len() < end {state.masses.resize(end, 0.0)}
+        state.masses[offset..end].copy_from_slice(&changes);
+        res.push(state.clone());
+    }
+    res
+}
+
+/// Replays a stream, merging the blocks of every frame.
+fn replay(data: &[u8]) -> Vec<(f64, Arrays)> {
+    let mut r = FrameReader::with_time(data, TIME);
+    let mut state = Arrays::default();
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        frame.read_array(POSITION, &mut state.positions).unwrap();
+        frame.read_array(MASS, &mut state.masses).unwrap();
+        res.push((frame.time.unwrap(), state.clone()));
+    }
+    res
+}
+
+/// Writes the frames of a recorder without dropping any.
+fn original(frames: usize) -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME);
+    Scalar::write_array(NOTE, &b"preamble".to_vec(), &mut w).unwrap();
+    record(frames, &mut w, &mut |w, t| w.begin_frame(t).unwrap());
+    w.finish().unwrap()
+}
+
+fn ring(opts: RingOptions, frames: usize) -> (RingRecorder, Vec<Arrays>) {
+    let mut ring = RingRecorder::new(TIME, opts);
+    Scalar::write_array(NOTE, &b"preamble".to_vec(), &mut ring).unwrap();
+    let states = record(frames, &mut ring, &mut |ring, t| ring.begin_frame(t).unwrap());
+    (ring, states)
+}
+
+#[test]
+fn keeps_the_tail() {
+    for &frames in &[1, 5, 10, 11, 50] {
+        let (ring, states) = ring(RingOptions::default().max_frames(10), frames);
+        let kept = frames.min(10);
+        assert_eq!(ring.frame_count(), kept);
+        assert_eq!(ring.evicted(), (frames - kept) as u64);
+
+        let mut data = vec![];
+        ring.snapshot_to(&mut data).unwrap();
+        validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+        // The snapshot replays the tail, with masses of evicted frames in the keyframe.
+        let snapshot = replay(&data);
+        assert_eq!(snapshot, replay(&original(frames))[frames - kept..], "{}", frames);
+        assert_eq!(snapshot.last().unwrap().1.masses, states[frames - 1].masses);
+
+        // Blocks before the first frame are kept.
+        let mut r = FrameReader::with_time(&data[..], TIME);
+        r.next_frame().unwrap();
+        assert_eq!(r.preamble().len(), 1);
+        assert_eq!(r.preamble()[0].data, b"preamble");
+    }
+
+    // Without frames, the snapshot has the preamble only.
+    let (ring, _) = ring(RingOptions::default().max_frames(10), 0);
+    let mut data = vec![];
+    ring.snapshot_to(&mut data).unwrap();
+    let mut expected = vec![];
+    Scalar::write_array(NOTE, &b"preamble".to_vec(), &mut expected).unwrap();
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert_eq!(data, expected);
+}
+
+#[test]
+fn max_bytes() {
+    let max = 4000;
+    let mut ring = RingRecorder::new(TIME, RingOptions::default().max_bytes(max));
+    let states = record(200, &mut ring, &mut |ring, t| {
+        ring.begin_frame(t).unwrap();
+        assert!(ring.frame_count() == 1 || ring.memory_usage() <= max);
+    });
+    assert!(ring.evicted() > 0);
+    let kept = ring.frame_count();
+    assert!(kept > 1);
+    assert!(ring.memory_usage() <= max);
+    let mut data = vec![];
+    ring.snapshot_to(&mut data).unwrap();
+    assert_eq!(replay(&data), replay(&original(200))[200 - kept..]);
+    assert_eq!(replay(&data).last().unwrap().1.masses, states[199].masses);
+
+    // A frame above the limit is kept alone.
+    let mut ring = RingRecorder::new(TIME, RingOptions::default().max_bytes(100));
+    ring.begin_frame(0.0).unwrap();
+    Scalar::write_array(MASS, &vec![1.0; 100], &mut ring).unwrap();
+    ring.begin_frame(1.0).unwrap();
+    Scalar::write_array(MASS, &vec![2.0; 100], &mut ring).unwrap();
+    assert_eq!((ring.frame_count(), ring.evicted()), (1, 1));
+}
+
+#[test]
+fn memory_usage() {
+    let mut ring = RingRecorder::new(TIME, RingOptions::default());
+    let empty = ring.memory_usage();
+    ring.begin_frame(0.0).unwrap();
+    let mut total = 0;
+    for i in 0..100 {
+        let data = vec![i as f64; 10];
+        Scalar::write_array(MASS, &data, &mut ring).unwrap();
+        total += 20 + 80 + 8;
+    }
+    ring.begin_frame(1.0).unwrap();
+    // Finished frames are shrunk to fit, so the payload and the overhead are counted.
+    let usage = ring.memory_usage() - empty;
+    assert!(usage >= total, "{} {}", usage, total);
+    assert!(usage < total + 200, "{} {}", usage, total);
+
+    // Evicted frames are merged into the keyframe, which counts too.
+    let mut ring = RingRecorder::new(TIME, RingOptions::default().max_frames(1));
+    ring.begin_frame(0.0).unwrap();
+    Scalar::write_array(MASS, &vec![1.0; 1000], &mut ring).unwrap();
+    let before = ring.memory_usage();
+    ring.begin_frame(1.0).unwrap();
+    assert_eq!(ring.evicted(), 1);
+    assert!(ring.memory_usage() >= empty + 8000);
+    assert!(ring.memory_usage() < before);
+}
+
+#[test]
+fn changed_and_custom_formats() {
+    let custom = Type::offset_custom_format();
+    let mut ring = RingRecorder::new(TIME, RingOptions::default().max_frames(1));
+    ring.begin_frame(0.0).unwrap();
+    Scalar::write_array(MASS, &vec![1.0_f32; 3], &mut ring).unwrap();
+    write_block(custom, NOTE, 0, b"first", &mut ring).unwrap();
+    ring.begin_frame(1.0).unwrap();
+    // Masses written with another type replace the items of the old type.
+    Scalar::write_array(MASS, &vec![2.0_f64; 2], &mut ring).unwrap();
+    write_block(custom, NOTE, 5, b"second", &mut ring).unwrap();
+    ring.begin_frame(2.0).unwrap();
+
+    let mut data = vec![];
+    ring.snapshot_to(&mut data).unwrap();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let frame = r.next_frame().unwrap().unwrap();
+    assert_eq!(frame.time, Some(2.0));
+    let mut masses: Vec<f64> = vec![];
+    frame.read_array(MASS, &mut masses).unwrap();
+    assert_eq!(masses, vec![2.0, 2.0]);
+    assert_eq!(frame.blocks_of(MASS).count(), 1);
+    // The last blocks of a custom format are kept.
+    let notes: Vec<_> = frame.blocks_of(NOTE).collect();
+    assert_eq!(notes.len(), 1);
+    assert_eq!((notes[0].offset, &notes[0].data[..]), (5, &b"second"[..]));
+}
+
+#[test]
+fn incomplete_frames() {
+    let mut ring = RingRecorder::new(TIME, RingOptions::default().max_frames(1));
+    ring.begin_frame(0.0).unwrap();
+    let mut data = vec![];
+    Scalar::write_array(MASS, &vec![1.0_f32; 3], &mut data).unwrap();
+    ring.write_all(&data[..10]).unwrap();
+    let err = ring.begin_frame(1.0).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::InvalidData);
+}
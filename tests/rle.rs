@@ -0,0 +1,118 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const FLAG: u16 = 1;
+
+fn write(items: &[u32], max_ratio: f64) -> Vec<u8> {
+    let mut data = vec![];
+    write_rle_with(FLAG, items, max_ratio, &mut data).unwrap();
+    data
+}
+
+fn type_format(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data[1]])
+}
+
+fn read(data: &[u8]) -> Vec<u32> {
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let mut arr = vec![];
+    read_rle(state.unwrap(), ty, prop, &mut arr, &mut r).unwrap();
+    assert!(r.is_empty());
+    arr
+}
+
+/// Reads through `PoolReader`, which expands run-length encoding.
+fn read_plain(data: &[u8]) -> Vec<u32> {
+    let mut r = PoolReader::new(data);
+    r.register(FLAG);
+    let (state, ty, _) = r.next_property().unwrap().unwrap();
+    assert_eq!(ty, Type::U32.scalar().0);
+    let mut arr = vec![];
+    for block in RawBlock::read_property(state, ty, FLAG, &mut r).unwrap() {
+        for (i, item) in block.decode::<u32>().unwrap().into_iter().enumerate() {
+            let ind = block.offset as usize + i;
+            if ind >= arr.len() {arr.resize(ind + 1, 0)}
+            arr[ind] = item;
+        }
+    }
+    arr
+}
+
+#[test]
+fn all_equal() {
+    let items = vec![7_u32; 10_000];
+    let data = write(&items, 1.0);
+    assert_eq!(type_format(&data), RLE_FORMAT);
+    // One run of count and item, after the item type format.
+    assert_eq!(data.len(), 28 + 2 + 8 + 4);
+    assert_eq!(read(&data), items);
+    assert_eq!(read_plain(&data), items);
+}
+
+#[test]
+fn alternating_falls_back() {
+    let items: Vec<u32> = (0..1000).map(|i| i % 2).collect();
+    let data = write(&items, 1.0);
+    assert_eq!(type_format(&data), Type::U32.scalar().0);
+    assert_eq!(data.len(), 28 + 4000);
+    assert_eq!(read(&data), items);
+    assert_eq!(read_plain(&data), items);
+}
+
+#[test]
+fn threshold() {
+    // 4 runs of 25 items take 2 + 4 * 12 bytes, about an eighth of 400 bytes.
+    let items: Vec<u32> = (0..100).map(|i| i / 25).collect();
+    assert_eq!(type_format(&write(&items, 0.2)), RLE_FORMAT);
+    assert_eq!(type_format(&write(&items, 0.1)), Type::U32.scalar().0);
+}
+
+#[test]
+fn random() {
+    let mut rng = Rng::new(142);
+    for _ in 0..100 {
+        let mut items = vec![];
+        for _ in 0..1 + rng.below(20) {
+            let val = rng.below(4) as u32;
+            let max = if rng.below(2) == 0 {3} else {100};
+            let run = 1 + rng.below(max);
+            items.extend(std::iter::repeat_n(val, run as usize));
+        }
+        let data = write(&items, 1.0);
+        assert_eq!(read(&data), items);
+        assert_eq!(read_plain(&data), items);
+    }
+}
+
+#[test]
+fn offsets() {
+    // Moves a block of runs to an offset.
+    let at = |offset: u64, items: &[u32]| -> RawBlock {
+        let data = write(items, 1.0);
+        let mut r = &data[..];
+        let (state, ty, prop) = State::read(&mut r).unwrap();
+        let block = RawBlock::read_property(state.unwrap(), ty, prop, &mut r).unwrap().remove(0);
+        assert_eq!(block.type_format, RLE_FORMAT);
+        RawBlock {offset, ..block}
+    };
+    let blocks = [at(5, &[1, 1, 1, 1, 2, 2, 2, 2]), at(2, &[3; 4])];
+    let mut data = vec![];
+    RawBlock::write_property(RLE_FORMAT, FLAG, &blocks, &mut data).unwrap();
+    // The second block overwrites the first item of the first block.
+    let expected = vec![0, 0, 3, 3, 3, 3, 1, 1, 1, 2, 2, 2, 2];
+    assert_eq!(read(&data), expected);
+    assert_eq!(read_plain(&data), expected);
+
+    let expanded = expand_rle(&blocks[0], None).unwrap();
+    assert_eq!((expanded.type_format, expanded.offset), (Type::U32.scalar().0, 5));
+    assert_eq!(expanded.decode::<u32>().unwrap(), vec![1, 1, 1, 1, 2, 2, 2, 2]);
+    assert_eq!(Error::from_io(&expand_rle(&blocks[0], Some(16)).unwrap_err()).kind(),
+        ErrorKind::LimitExceeded);
+}
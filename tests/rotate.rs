@@ -0,0 +1,171 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::cell::RefCell;
+use std::io::{self, Cursor, Write};
+use std::rc::Rc;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+type Files = Rc<RefCell<Vec<Vec<u8>>>>;
+
+/// A file in memory.
+struct File(Files, usize);
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut()[self.1].extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {Ok(())}
+}
+
+fn positions(rng: &mut Rng) -> Vec<[f32; 3]> {
+    (0..1 + rng.below(20)).map(|_| [rng.unit() as f32; 3]).collect()
+}
+
+fn units() -> Units {
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new("m", 1.0));
+    units
+}
+
+/// Writes 30 frames, returning the files.
+fn write(endian: Endian, opts: RotateOptions, finish: bool) -> Vec<Vec<u8>> {
+    let files: Files = Rc::new(RefCell::new(vec![]));
+    let created = files.clone();
+    let mut w = RotatingWriter::new(TIME, opts, move |i| {
+        let mut files = created.borrow_mut();
+        assert_eq!(i as usize, files.len());
+        files.push(vec![]);
+        Ok(File(created.clone(), i as usize))
+    }).unwrap().with_endianness(endian);
+    w.write_sticky(|buf| units().write_units(buf)).unwrap();
+    let m = Metadata::new().with(DESCRIPTION, "position");
+    w.write_sticky(|buf| write_metadata(&[(POSITION, &m)], buf)).unwrap();
+    let mut rng = Rng::new(157);
+    for i in 0..30 {
+        w.begin_frame(i as f64).unwrap();
+        Vector::write_array(POSITION, &positions(&mut rng), &mut w).unwrap();
+    }
+    assert_eq!(w.frames(), 30);
+    if finish {w.finish().unwrap();}
+    let files = files.borrow().clone();
+    files
+}
+
+/// Reads the times and positions of every frame.
+fn frames<R: io::Read>(r: R) -> Vec<(Option<f64>, Vec<[f32; 3]>)> {
+    let mut r = FrameReader::with_time(PoolReader::new(r), TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut pos = vec![];
+        frame.read_array(POSITION, &mut pos).unwrap();
+        res.push((frame.time, pos));
+    }
+    res
+}
+
+fn expected() -> Vec<(Option<f64>, Vec<[f32; 3]>)> {
+    let mut rng = Rng::new(157);
+    (0..30).map(|i| (Some(i as f64), positions(&mut rng))).collect()
+}
+
+#[test]
+fn three_rotations() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let files = write(endian, RotateOptions::default().max_frames(8), true);
+        assert_eq!(files.len(), 4);
+
+        let chain = ChainedReader::new(files.iter().cloned().map(Cursor::new).collect());
+        assert_eq!(frames(chain), expected());
+
+        // Every file is terminated and can be read on its own.
+        for (i, file) in files.iter().enumerate() {
+            validate(&mut &file[..], &ValidateOptions::default()).unwrap();
+            assert_eq!(&file[file.len() - 2..], &[0, 0]);
+            let frames = frames(&file[..]);
+            let start = i * 8;
+            assert_eq!(frames, expected()[start..(start + 8).min(30)]);
+
+            // With the header and sticky blocks.
+            let mut r = PoolReader::new(&file[..]).unknown(Unknown::Skip);
+            while r.next_property().unwrap().is_some() {}
+            assert_eq!(r.header().map(|h| h.endian), Some(endian));
+            assert_eq!(r.units(), &units());
+            assert_eq!(r.metadata(POSITION).and_then(|m| m.description()), Some("position"));
+        }
+    }
+}
+
+#[test]
+fn max_bytes() {
+    let files = write(Endian::Little, RotateOptions::default().max_bytes(500), true);
+    assert!(files.len() > 3);
+    let mut n = 0;
+    for file in &files {
+        // Files are only rotated at frames, so every file has whole frames.
+        let frames = frames(&file[..]);
+        assert!(!frames.is_empty());
+        assert_eq!(frames, expected()[n..n + frames.len()]);
+        n += frames.len();
+    }
+    assert_eq!(n, 30);
+    let chain = ChainedReader::new(files.into_iter().map(Cursor::new).collect());
+    assert_eq!(frames(chain), expected());
+
+    // Without limits, there is one file.
+    assert_eq!(write(Endian::Little, RotateOptions::default(), true).len(), 1);
+}
+
+#[test]
+fn crash_between_files() {
+    let files = write(Endian::Little, RotateOptions::default().max_frames(8), false);
+    assert_eq!(files.len(), 4);
+    for file in &files[..3] {
+        validate(&mut &file[..], &ValidateOptions::default()).unwrap();
+    }
+    // Only the last file is not terminated.
+    let last = &files[3];
+    let mut r = &last[..];
+    let err = loop {
+        match State::read(&mut r) {
+            Ok((Some(state), ty, prop)) => {
+                RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+            }
+            Ok((None, ..)) => panic!("end of stream"),
+            Err(err) => break err,
+        }
+    };
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(r.is_empty());
+    let mut data = last.clone();
+    State::new().end_type_formats(&mut data).unwrap();
+    assert_eq!(frames(&data[..]), expected()[24..]);
+}
+
+#[test]
+fn sticky_blocks_are_complete() {
+    let mut w = RotatingWriter::new(TIME, RotateOptions::default(), |_| Ok(vec![])).unwrap();
+    let mut data = vec![];
+    units().write_units(&mut data).unwrap();
+    for len in &[1, data.len() - 1] {
+        let err = w.write_sticky(|buf| buf.write_all(&data[..*len])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+    let err = w.write_sticky(|buf| State::new().end_type_formats(buf)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    // Nothing was written.
+    w.rotate().unwrap();
+    let file = w.finish().unwrap();
+    let mut expected = PoolWriter::new(vec![]);
+    expected.write_header().unwrap();
+    assert_eq!(file, expected.finish().unwrap());
+}
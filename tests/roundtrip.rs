@@ -0,0 +1,102 @@
+#![cfg(feature = "test-util")]
+
+extern crate binpool;
+#[macro_use]
+extern crate proptest;
+
+use binpool::test_util::{property, stream, Model, ModelProperty, StreamConfig};
+use binpool::*;
+
+const TIME: u16 = 0;
+
+/// Decodes the items of every property from raw blocks.
+fn decode(data: &[u8]) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    let mut r = data;
+    let mut res = vec![];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        let (scalar, rows, cols) = Type::info(ty).unwrap();
+        let size = (scalar.type_size() * rows as u64 * cols as u64) as usize;
+        let mut items: Vec<Vec<u8>> = vec![];
+        for block in RawBlock::read_property(state, ty, prop, &mut r).unwrap() {
+            for (i, item) in block.data.chunks(size).enumerate() {
+                let ind = block.offset as usize + i;
+                if ind >= items.len() {items.resize(ind + 1, vec![0; size])}
+                items[ind] = item.to_vec();
+            }
+        }
+        res.push((ty, prop, items));
+    }
+    assert!(r.is_empty());
+    res
+}
+
+fn expected(model: &Model) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    model.properties.iter()
+        .map(|p| (p.type_format, p.property_id, p.items.clone()))
+        .collect()
+}
+
+/// Edge cases: many blocks per property and offsets far from the data.
+fn edge_cases() -> StreamConfig {
+    StreamConfig {max_properties: 3, max_blocks: 64, max_items: 4, max_offset: 4096}
+}
+
+fn u32s(items: &[Vec<u8>]) -> Vec<u32> {
+    items.iter().map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]])).collect()
+}
+
+/// Reads a property with all its blocks as the only property of a frame.
+fn frame(p: &ModelProperty) -> Frame {
+    let mut data = vec![];
+    Time::write(TIME, 0.0, &mut data).unwrap();
+    Model {properties: vec![p.clone()]}.write(&mut data).unwrap();
+    FrameReader::with_time(&data[..], TIME).next_frame().unwrap().unwrap()
+}
+
+proptest! {
+    #[test]
+    fn raw(model in stream(StreamConfig::default())) {
+        prop_assert_eq!(decode(&model.encode()), expected(&model));
+    }
+
+    #[test]
+    fn raw_edge_cases(model in stream(edge_cases())) {
+        prop_assert_eq!(decode(&model.encode()), expected(&model));
+    }
+
+    #[test]
+    fn validate_accepts(model in stream(StreamConfig::default())) {
+        let data = model.encode();
+        let report = validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+        prop_assert!(report.is_ok(), "{:?}", report);
+    }
+
+    #[test]
+    fn canonicalize_keeps_items(model in stream(StreamConfig::default())) {
+        let mut out = vec![];
+        canonicalize(&mut &model.encode()[..], &mut out, None).unwrap();
+        // Properties are sorted by id.
+        let mut expected = expected(&model);
+        expected.sort_by_key(|p| p.1);
+        prop_assert_eq!(decode(&out), expected);
+    }
+
+    #[test]
+    fn scalar(p in property(Type::U32.scalar().0, 1, edge_cases())) {
+        let mut arr: Vec<u32> = vec![];
+        frame(&p).read_array(1, &mut arr).unwrap();
+        prop_assert_eq!(arr, u32s(&p.items));
+    }
+
+    #[test]
+    fn vector(p in property(Type::U32.vector(3).unwrap().0, 2, StreamConfig::default())) {
+        let mut arr: Vec<[u32; 3]> = vec![];
+        frame(&p).read_array(2, &mut arr).unwrap();
+        let items: Vec<u32> = arr.iter().flat_map(|v| v.iter().cloned()).collect();
+        let expected: Vec<Vec<u8>> = p.items.iter()
+            .flat_map(|x| x.chunks(4))
+            .map(|c| c.to_vec())
+            .collect();
+        prop_assert_eq!(items, u32s(&expected));
+    }
+}
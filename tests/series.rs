@@ -0,0 +1,164 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+const TIME: u16 = 0;
+const ENERGY: u16 = 1;
+const POSITION: u16 = 2;
+const NOTE: u16 = 3;
+
+/// The values written to a stream, for every frame and instance.
+struct Generated {
+    data: Vec<u8>,
+    energy: Vec<Vec<Option<f64>>>,
+    position: Vec<Vec<Option<[f32; 3]>>>,
+}
+
+fn set<T: Copy>(values: &mut Vec<Option<T>>, offset: usize, items: &[T]) {
+    if values.len() < offset + items.len() {values.resize(offset + items.len(), None)}
+    for (i, &x) in items.iter().enumerate() {values[offset + i] = Some(x)}
+}
+
+/// Writes frames where energies are written in full, as changes, or not at all.
+fn generate(frames: usize) -> Generated {
+    let mut rng = Rng::new(170);
+    let mut w = FrameWriter::new(vec![], TIME);
+    // Data before the first frame is not part of the series.
+    Scalar::write_array(ENERGY, &vec![-1.0; 10], &mut w).unwrap();
+    let mut res = Generated {data: vec![], energy: vec![], position: vec![]};
+    for i in 0..frames {
+        w.begin_frame(i as f64 * 0.1).unwrap();
+        let mut energy = vec![];
+        match rng.below(3) {
+            0 => {
+                let items: Vec<f64> = (0..1 + rng.below(12)).map(|_| rng.unit()).collect();
+                Scalar::write_array(ENERGY, &items, &mut w).unwrap();
+                set(&mut energy, 0, &items);
+            }
+            1 => {
+                // Several blocks, where later blocks win.
+                let ty = Type::F64.scalar().0;
+                let mut blocks = vec![];
+                for _ in 0..1 + rng.below(3) {
+                    let offset = rng.below(10) as usize;
+                    let items: Vec<f64> = (0..1 + rng.below(4)).map(|_| rng.unit()).collect();
+                    let mut data = vec![];
+                    for x in &items {x.write_element(&mut data).unwrap()}
+                    blocks.push(RawBlock {type_format: ty, property_id: ENERGY,
+                                          offset: offset as u64, data});
+                    set(&mut energy, offset, &items);
+                }
+                RawBlock::write_property(ty, ENERGY, &blocks, &mut w).unwrap();
+            }
+            _ => {}
+        }
+        let n = rng.below(8) as usize;
+        let pos: Vec<[f32; 3]> = (0..n).map(|_| [rng.unit() as f32, i as f32, 1.0]).collect();
+        if n > 0 {Vector::write_array(POSITION, &pos, &mut w).unwrap()}
+        let mut position = vec![];
+        set(&mut position, 0, &pos);
+        write_block(Type::offset_custom_format(), NOTE, 0, b"custom", &mut w).unwrap();
+        res.energy.push(energy);
+        res.position.push(position);
+    }
+    res.data = w.finish().unwrap();
+    res
+}
+
+/// Returns the series of an instance as generated.
+fn expected<T: Copy>(values: &[Vec<Option<T>>], instance: usize, carry: bool) -> Vec<(f64, T)> {
+    let mut res = vec![];
+    let mut last = None;
+    for (i, frame) in values.iter().enumerate() {
+        let val = if instance < frame.len() {frame[instance]} else {None};
+        if val.is_some() {last = val}
+        if let Some(x) = if carry {last} else {val} {res.push((i as f64 * 0.1, x))}
+    }
+    res
+}
+
+#[test]
+fn matches_generator() {
+    let g = generate(60);
+    for instance in 0..14 {
+        for &carry in &[false, true] {
+            let opts = SeriesOptions::default().carry_forward(carry);
+            let mut r = Cursor::new(&g.data);
+            let series = extract_series_as::<f64, _>(&mut r, TIME, ENERGY, instance, &opts);
+            let expected = expected(&g.energy, instance as usize, carry);
+            assert_eq!(series.unwrap(), expected, "{} {}", instance, carry);
+            if instance < 10 && carry {assert!(!expected.is_empty())}
+
+            // The series is read from the current position.
+            let mut r = Cursor::new(&g.data);
+            let series = extract_series_as::<[f32; 3], _>(&mut r, TIME, POSITION, instance, &opts);
+            assert_eq!(series.unwrap(), self::expected(&g.position, instance as usize, carry));
+        }
+    }
+}
+
+#[test]
+fn dynamic_values() {
+    let g = generate(30);
+    let opts = SeriesOptions::default();
+    let mut r = Cursor::new(&g.data);
+    let series = extract_series(&mut r, TIME, POSITION, 2, &opts).unwrap();
+    let expected = expected(&g.position, 2, false);
+    assert_eq!(series.len(), expected.len());
+    for ((t, val), (te, pos)) in series.iter().zip(&expected) {
+        assert_eq!(t, te);
+        assert_eq!((val.ty(), val.dim()), (Type::F32, (1, 3)));
+        assert_eq!(val.type_format(), Type::F32.vector(3).unwrap().0);
+        let pos: Vec<f64> = pos.iter().map(|&x| x as f64).collect();
+        assert_eq!(val.to_f64(), pos);
+        assert_eq!(val.decode::<[f32; 3]>().unwrap(), [pos[0] as f32, pos[1] as f32, 1.0]);
+    }
+    // Custom formats are skipped.
+    let mut r = Cursor::new(&g.data);
+    assert!(extract_series(&mut r, TIME, NOTE, 0, &opts).unwrap().is_empty());
+
+    let mut r = Cursor::new(&g.data);
+    let err = extract_series_as::<f32, _>(&mut r, TIME, ENERGY, 0, &opts).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+}
+
+/// Counts the bytes read.
+struct Counting<R>(R, u64);
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.0.read(buf)?;
+        self.1 += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn reads_one_item() {
+    // Large arrays, of which one item is read per frame.
+    let mut w = FrameWriter::new(vec![], TIME);
+    for i in 0..20 {
+        w.begin_frame(i as f64).unwrap();
+        let items: Vec<f64> = (0..10000).map(|j| (i * j) as f64).collect();
+        Scalar::write_array(ENERGY, &items, &mut w).unwrap();
+    }
+    let data = w.finish().unwrap();
+    let mut r = Counting(Cursor::new(&data), 0);
+    let opts = SeriesOptions::default();
+    let series = extract_series_as::<f64, _>(&mut r, TIME, ENERGY, 7, &opts).unwrap();
+    let expected: Vec<(f64, f64)> = (0..20).map(|i| (i as f64, (i * 7) as f64)).collect();
+    assert_eq!(series, expected);
+    assert!(r.1 < data.len() as u64 / 100, "{} of {}", r.1, data.len());
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::Write;
+use std::thread;
+
+const THREADS: u16 = 8;
+const ROUNDS: u32 = 5;
+const PROPERTIES: u32 = 100;
+
+/// Writes a property of one thread, with the round and sequence number in every item.
+fn write<W: Write>(thread: u16, round: u32, seq: u32, w: &mut W) {
+    let ty = Type::U32.vector(3).unwrap().0;
+    // Properties have up to 3 blocks of different sizes.
+    let blocks: Vec<RawBlock> = (0..1 + seq % 3).map(|b| {
+        let n = 1 + (seq * 7 + b * 13) % 200;
+        let mut data = vec![];
+        for _ in 0..n {
+            [thread as u32, round, seq].write_element(&mut data).unwrap();
+        }
+        RawBlock {type_format: ty, property_id: thread, offset: b as u64 * 1000, data}
+    }).collect();
+    RawBlock::write_property(ty, thread, &blocks, w).unwrap();
+}
+
+#[test]
+fn eight_threads() {
+    let w = SharedWriter::new(vec![]);
+    for round in 0..ROUNDS {
+        thread::scope(|s| {
+            for thread in 0..THREADS {
+                let w = &w;
+                s.spawn(move || {
+                    let mut local = w.local();
+                    for seq in 0..PROPERTIES {
+                        write(thread, round, seq, &mut local);
+                        assert!(!local.is_pending());
+                    }
+                });
+            }
+        });
+        w.barrier().unwrap();
+    }
+    let data = w.finish().unwrap();
+    validate(&mut &data[..], &ValidateOptions::default()).unwrap();
+
+    let mut seen = vec![vec![vec![false; PROPERTIES as usize]; ROUNDS as usize]; THREADS as usize];
+    let mut last_round = 0;
+    let mut r = &data[..];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        let mut key = None;
+        for (b, block) in blocks.iter().enumerate() {
+            assert_eq!(block.offset, b as u64 * 1000);
+            for item in block.data.chunks(12) {
+                let mut x = [0_u32; 3];
+                x.read_element(&mut &item[..]).unwrap();
+                assert_eq!(x[0], prop as u32);
+                // A property is never mixed with another.
+                assert!(key.is_none() || key == Some(x));
+                key = Some(x);
+            }
+        }
+        let [thread, round, seq] = key.unwrap();
+        assert_eq!(blocks.len() as u32, 1 + seq % 3);
+        // Barriers sequence the rounds.
+        assert!(round >= last_round);
+        last_round = round;
+        let seen = &mut seen[thread as usize][round as usize][seq as usize];
+        assert!(!*seen, "{:?} twice", key);
+        *seen = true;
+    }
+    assert!(seen.iter().flatten().flatten().all(|&x| x));
+}
+
+#[test]
+fn incomplete_and_end_of_stream() {
+    let w = SharedWriter::new(vec![]);
+    {
+        let mut local = w.local();
+        write(1, 0, 0, &mut local);
+        // Half of a property is buffered and left out when dropped.
+        let mut data = vec![];
+        write(2, 0, 0, &mut data);
+        local.write_all(&data[..data.len() / 2]).unwrap();
+        assert!(local.is_pending());
+        let err = State::new().end_type_formats(&mut w.local()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    let data = w.finish().unwrap();
+    let mut expected = vec![];
+    write(1, 0, 0, &mut expected);
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert_eq!(data, expected);
+}
@@ -0,0 +1,144 @@
+#![cfg(feature = "bytes")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 1;
+const STRESS: u16 = 2;
+const NOTE: u16 = 3;
+
+const NOTE_FORMAT: u16 = 0xfe00;
+
+/// Encodes items as a block starting at an instance.
+fn block<T: Element>(ty: u16, prop: u16, offset: u64, items: &[T]) -> RawBlock {
+    let mut data = vec![];
+    for x in items {x.write_element(&mut data).unwrap()}
+    RawBlock {type_format: ty, property_id: prop, offset, data}
+}
+
+fn position(i: u64) -> [f32; 3] {
+    [i as f32, 0.5, -(i as f32)]
+}
+
+/// Writes positions in two blocks, a 2x3 matrix and a custom format.
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    let ty = Type::F32.vector(3).unwrap().0;
+    let first: Vec<[f32; 3]> = (0..4).map(position).collect();
+    let second: Vec<[f32; 3]> = (10..13).map(position).collect();
+    let blocks = [block(ty, POSITION, 0, &first), block(ty, POSITION, 10, &second)];
+    RawBlock::write_property(ty, POSITION, &blocks, &mut w).unwrap();
+
+    let ty = Type::F64.matrix(2, 3).unwrap().0;
+    let stress: Vec<f64> = (0..3 * 6).map(|i| i as f64).collect();
+    RawBlock::write_property(ty, STRESS, &[block(ty, STRESS, 5, &stress)], &mut w).unwrap();
+
+    let note = RawBlock {type_format: NOTE_FORMAT, property_id: NOTE, offset: 0, data: vec![7; 5]};
+    RawBlock::write_property(NOTE_FORMAT, NOTE, &[note], &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+fn read(data: &[u8]) -> Vec<SharedBlock> {
+    let mut r = data;
+    let mut res = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        res.extend(SharedBlock::read_property(state, ty, prop, &mut r).unwrap());
+    }
+    res
+}
+
+fn ptr(data: &[u8]) -> usize {
+    data.as_ptr() as usize
+}
+
+#[test]
+fn vector_items() {
+    let blocks = {
+        let data = stream();
+        read(&data)
+    };
+    // The data outlives the stream and the reader.
+    assert_eq!(blocks.len(), 4);
+    let (first, second) = (&blocks[0], &blocks[1]);
+    assert_eq!((first.item_size(), first.items()), (Some(12), Some(4)));
+    assert_eq!((second.offset, second.items()), (10, Some(3)));
+    // The blocks of a property share one buffer.
+    assert_eq!(ptr(&second.data), ptr(&first.data) + 4 * 12);
+
+    for i in 0..4 {
+        let item = first.item(i).unwrap();
+        assert_eq!(ptr(&item), ptr(&first.data) + i as usize * 12);
+        assert_eq!(item.len(), 12);
+        let shared = SharedBlock {data: item, ..first.clone()};
+        assert_eq!(shared.decode::<[f32; 3]>().unwrap(), vec![position(i)]);
+    }
+    assert!(first.item(4).is_none());
+    assert!(first.item(u64::MAX).is_none());
+
+    // Instances are found with the offset of the block.
+    assert_eq!(ptr(&second.instance(11).unwrap()), ptr(&second.data) + 12);
+    assert!(second.instance(9).is_none());
+    assert!(second.instance(13).is_none());
+
+    let slice = second.slice(1..3).unwrap();
+    assert_eq!((slice.offset, slice.items()), (11, Some(2)));
+    assert_eq!(ptr(&slice.data), ptr(&second.data) + 12);
+    assert_eq!(slice.decode::<[f32; 3]>().unwrap(), vec![position(11), position(12)]);
+    assert_eq!(second.slice(3..3).unwrap().items(), Some(0));
+    assert!(second.slice(2..4).is_none());
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 2..1;
+    assert!(second.item_range(reversed).is_none());
+    assert_eq!(second.item_range(0..3).unwrap(), second.data);
+}
+
+#[test]
+fn matrix_items() {
+    let blocks = read(&stream());
+    let stress = &blocks[2];
+    assert_eq!((stress.item_size(), stress.items(), stress.offset), (Some(48), Some(3), 5));
+    let item = stress.instance(6).unwrap();
+    assert_eq!(ptr(&item), ptr(&stress.data) + 48);
+    let decoded = SharedBlock {data: item, ..stress.clone()}.decode::<f64>();
+    // A matrix is not decoded as scalars.
+    assert!(decoded.is_err());
+    let values: Vec<f64> = stress.item(1).unwrap().chunks(8)
+        .map(|c| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(c);
+            f64::from_le_bytes(bytes)
+        })
+        .collect();
+    assert_eq!(values, vec![6.0, 7.0, 8.0, 9.0, 10.0, 11.0]);
+    let slice = stress.slice(1..3).unwrap();
+    assert_eq!((slice.offset, slice.data.len()), (6, 96));
+    assert_eq!(ptr(&slice.data), ptr(&stress.data) + 48);
+
+    // Custom formats have no items.
+    let note = &blocks[3];
+    assert_eq!((note.item_size(), note.items()), (None, None));
+    assert!(note.item(0).is_none() && note.slice(0..1).is_none());
+    assert_eq!(&note.data[..], &[7; 5]);
+}
+
+#[test]
+fn conversion_without_copies() {
+    let original = block(Type::U16.scalar().0, POSITION, 3, &[1_u16, 2, 3]);
+    let owned = original.clone();
+    let at = ptr(&owned.data);
+    let shared = SharedBlock::from(owned);
+    assert_eq!(ptr(&shared.data), at);
+    let clone = shared.clone();
+    assert_eq!(ptr(&clone.data), at);
+    assert_eq!(shared.decode::<u16>().unwrap(), vec![1, 2, 3]);
+    assert_eq!(shared.to_raw(), original);
+
+    // A property that ends early.
+    let data = stream();
+    let mut r = &data[..40];
+    let (state, ty, prop) = raw::read_property_header(&mut r).unwrap().unwrap();
+    let err = SharedBlock::read_property(state, ty, prop, &mut r).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes a recording of `n` particles, with positions and masses derived from `file`.
+fn recording(file: u32, n: u32) -> Vec<u8> {
+    let mut w = vec![];
+    let pos: Vec<[f32; 3]> = (0..n).map(|i| [file as f32, i as f32, 0.0]).collect();
+    Vector::write_array(POSITION, &pos, &mut w).unwrap();
+    let mass: Vec<u32> = (0..n).map(|i| file * 100 + i).collect();
+    Scalar::write_array(MASS, &mass, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// Reads a recording into the arrays, shifted by `base`.
+fn load(data: &[u8], base: u64, pos: &mut Vec<[f32; 3]>, mass: &mut Vec<u32>) {
+    let opts = ReadOptions::default().instance_offset(base);
+    let mut r = data;
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        match prop {
+            POSITION => Vector::read_array_with(state, ty, pos, &opts, &mut r).unwrap(),
+            MASS => Scalar::read_array_with(state, ty, mass, &opts, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+}
+
+#[test]
+fn two_files_into_one_vec() {
+    let (a, b) = (recording(1, 5), recording(2, 3));
+    let mut pos = vec![];
+    let mut mass = vec![];
+    load(&a, 0, &mut pos, &mut mass);
+    load(&b, 5, &mut pos, &mut mass);
+
+    assert_eq!(pos.len(), 8);
+    assert_eq!(mass, vec![100, 101, 102, 103, 104, 200, 201, 202]);
+    for (i, p) in pos.iter().enumerate() {
+        let expected = if i < 5 {[1.0, i as f32, 0.0]} else {[2.0, (i - 5) as f32, 0.0]};
+        assert_eq!(*p, expected);
+    }
+}
+
+#[test]
+fn gap_between_files() {
+    let (a, b) = (recording(1, 2), recording(2, 2));
+    let mut pos = vec![];
+    let mut mass = vec![];
+    load(&a, 0, &mut pos, &mut mass);
+    load(&b, 4, &mut pos, &mut mass);
+    // Instances between the files get the default value.
+    assert_eq!(mass, vec![100, 101, 0, 0, 200, 201]);
+    assert_eq!(pos[2], [0.0; 3]);
+}
+
+#[test]
+fn overflow() {
+    let mut data = vec![];
+    RawBlock::write_property(Type::U32.scalar().0, MASS, &[RawBlock {
+        type_format: Type::U32.scalar().0,
+        property_id: MASS,
+        offset: 10,
+        data: 7_u32.to_le_bytes().to_vec(),
+    }], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let opts = ReadOptions::default().instance_offset(u64::MAX - 5);
+    let mut mass: Vec<u32> = vec![];
+    let err = Scalar::read_array_with(state.unwrap(), ty, &mut mass, &opts, &mut r).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.field(), Some(Field::OffsetInstanceId));
+    assert!(mass.is_empty());
+}
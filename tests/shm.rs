@@ -0,0 +1,279 @@
+#![cfg(feature = "shm")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const VALUE: u16 = 2;
+
+/// Tells a spawned test binary to run as the producer, with the mode and the segment.
+const CHILD: &str = "BINPOOL_SHM_CHILD";
+
+const FRAMES: usize = 200;
+const PROPERTIES: u32 = 20_000;
+
+/// A segment path unique to the test, removed at the end of the test.
+struct Segment(PathBuf);
+
+impl AsRef<Path> for Segment {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn segment(name: &str) -> Segment {
+    let path = std::env::temp_dir().join(format!("binpool-shm-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    Segment(path)
+}
+
+/// Frames with a varying number of positions, such that blocks wrap around the ring anywhere.
+fn frames() -> Vec<u8> {
+    let mut rng = Rng::new(193);
+    let mut w = FrameWriter::new(vec![], TIME);
+    for t in 0..FRAMES {
+        w.begin_frame(t as f64).unwrap();
+        let positions: Vec<[f32; 3]> = (0..1 + rng.below(100))
+            .map(|i| [t as f32, i as f32, rng.unit() as f32])
+            .collect();
+        Vector::write_array(POSITION, &positions, &mut w).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn positions(frame: Frame) -> (Option<f64>, Vec<[f32; 3]>) {
+    let mut res = vec![];
+    frame.read_array(POSITION, &mut res).unwrap();
+    (frame.time, res)
+}
+
+/// Writes property `i` of a long stream, where every item is `i`.
+fn write_value<W: Write>(i: u32, w: &mut W) -> io::Result<()> {
+    Scalar::write_array(VALUE, &vec![i; 1 + i as usize % 50], w)
+}
+
+/// Checks that a property was written by `write_value`, and returns its number.
+fn check_value(blocks: &[RawBlock]) -> u32 {
+    assert_eq!(blocks.len(), 1);
+    let items: Vec<u32> = blocks[0].data.chunks(4)
+        .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+        .collect();
+    let i = items[0];
+    assert_eq!(items, vec![i; 1 + i as usize % 50]);
+    i
+}
+
+/// Runs the producer of a test in a child process, see `child`.
+fn spawn(mode: &str, path: &Segment) -> Child {
+    Command::new(std::env::current_exe().unwrap())
+        .args(["child", "--exact", "--test-threads=1"])
+        .env(CHILD, format!("{}:{}", mode, path.0.display()))
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+/// Opens a segment as soon as the child has created it.
+fn open(path: &Segment) -> ShmReader {
+    for _ in 0..1000 {
+        if let Ok(r) = ShmReader::open(path) {return r.timeout(Some(Duration::from_secs(20)))}
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("no segment at {}", path.0.display());
+}
+
+/// The producer, when the test binary is spawned by another test.
+#[test]
+fn child() {
+    let var = match std::env::var(CHILD) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let (mode, path) = var.split_at(var.find(':').unwrap());
+    let path = &path[1..];
+    match mode {
+        "frames" => {
+            let mut w = ShmWriter::create(path, 1000).unwrap();
+            w.write_all(&frames()).unwrap();
+        }
+        "live" => {
+            let w = ShmWriter::create(path, 4096).unwrap().overflow(Overflow::Overwrite);
+            let mut w = io::BufWriter::with_capacity(1000, w);
+            for i in 0..PROPERTIES {write_value(i, &mut w).unwrap()}
+            State::new().end_type_formats(&mut w).unwrap();
+            w.flush().unwrap();
+        }
+        _ => panic!("unknown mode {}", mode),
+    }
+}
+
+#[test]
+fn frames_from_child() {
+    let data = frames();
+    let mut expected = vec![];
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    while let Some(frame) = r.next_frame().unwrap() {expected.push(positions(frame))}
+
+    // The bytes are the same as written, although the ring is much smaller than the stream.
+    let path = segment("bytes");
+    let mut producer = spawn("frames", &path);
+    let mut received = vec![];
+    open(&path).read_to_end(&mut received).unwrap();
+    assert!(producer.wait().unwrap().success());
+    assert_eq!(received, data);
+
+    // The frames are read from the segment like from a file.
+    let path = segment("frames");
+    let mut producer = spawn("frames", &path);
+    let mut r = FrameReader::with_time(PoolReader::new(open(&path)), TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {res.push(positions(frame))}
+    assert!(producer.wait().unwrap().success());
+    assert_eq!(res, expected);
+    assert_eq!(r.get_ref().get_ref().lag(), 0);
+    assert_eq!(r.get_ref().get_ref().position(), data.len() as u64);
+}
+
+#[test]
+fn live_overwriting_child() {
+    let path = segment("live");
+    let mut producer = spawn("live", &path);
+    let mut r = open(&path);
+    let (mut read, mut overruns, mut last) = (0, 0, None);
+    let mut rng = Rng::new(1);
+    loop {
+        let res = raw::read_property_header(&mut r).and_then(|header| match header {
+            Some((state, ty, prop)) => RawBlock::read_property(state, ty, prop, &mut r).map(Some),
+            None => Ok(None),
+        });
+        match res {
+            Ok(Some(blocks)) => {
+                // Every property read is whole, and comes after the previous one.
+                let i = check_value(&blocks);
+                assert!(last < Some(i));
+                last = Some(i);
+                read += 1;
+                if rng.below(200) == 0 {thread::sleep(Duration::from_millis(2))}
+            }
+            Ok(None) => break,
+            Err(err) => {
+                assert_eq!(Error::from_io(&err).kind(), ErrorKind::Overrun, "{}", err);
+                overruns += 1;
+                while !r.resync() {thread::yield_now()}
+            }
+        }
+    }
+    assert!(producer.wait().unwrap().success());
+    assert_eq!(last, Some(PROPERTIES - 1));
+    assert!(read + overruns > 0);
+    if overruns == 0 {assert_eq!(read, PROPERTIES)}
+}
+
+#[test]
+fn overrun_and_resync() {
+    let path = segment("overrun");
+    let mut w = ShmWriter::create(&path, 256).unwrap().overflow(Overflow::Overwrite);
+    let mut r = ShmReader::open(&path).unwrap();
+    assert_eq!(r.sequence(), w.sequence());
+    for i in 0..10 {write_value(i, &mut w).unwrap()}
+    assert!(w.lag() > 256);
+    let err = r.read(&mut [0; 8]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::Overrun);
+
+    // The reader continues at the latest property.
+    assert!(r.resync());
+    let (state, ty, prop) = raw::read_property_header(&mut r).unwrap().unwrap();
+    assert_eq!(check_value(&RawBlock::read_property(state, ty, prop, &mut r).unwrap()), 9);
+    assert_eq!(r.lag(), 0);
+
+    // A property larger than the ring can not be resynced to.
+    Scalar::write_array(VALUE, &vec![7_u32; 100], &mut w).unwrap();
+    assert!(r.read(&mut [0; 8]).is_err());
+    assert!(!r.resync());
+    write_value(10, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    assert!(r.resync());
+    drop(w);
+    let mut rest = vec![];
+    r.read_to_end(&mut rest).unwrap();
+    let mut expected = vec![];
+    write_value(10, &mut expected).unwrap();
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert_eq!(rest, expected);
+}
+
+#[test]
+fn blocking_writer() {
+    let path = segment("block");
+    let w = ShmWriter::create(&path, 64).unwrap();
+    let mut w = w.timeout(Some(Duration::from_millis(20)));
+    let mut r = ShmReader::open(&path).unwrap().timeout(Some(Duration::from_millis(20)));
+    assert_eq!(r.read(&mut [0; 8]).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    let data: Vec<u8> = (0..100).collect();
+    assert_eq!(w.write_all(&data).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    assert_eq!((w.position(), w.lag()), (64, 64));
+
+    // Reading makes room for the rest.
+    let mut received = vec![0; 64];
+    r.read_exact(&mut received).unwrap();
+    w.write_all(&data[64..]).unwrap();
+    drop(w);
+    r.read_to_end(&mut received).unwrap();
+    assert_eq!(received, data);
+    assert!(ShmWriter::create(&path, 0).is_err());
+}
+
+#[test]
+fn restarted_producer() {
+    let path = segment("restart");
+    let mut first = ShmWriter::create(&path, 128).unwrap();
+    let mut r = ShmReader::open(&path).unwrap();
+    first.write_all(&[1; 10]).unwrap();
+    let mut buf = [0; 10];
+    r.read_exact(&mut buf).unwrap();
+
+    // A new writer empties the ring, and the previous writer and reader find out.
+    let mut second = ShmWriter::create(&path, 128).unwrap();
+    assert_eq!(second.sequence(), first.sequence() + 1);
+    second.write_all(&[2; 5]).unwrap();
+    let restarted = |err: io::Error| {
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        Error::from_io(&err).kind() == ErrorKind::ProducerRestarted
+    };
+    assert!(restarted(first.write(&[1]).unwrap_err()));
+    assert!(restarted(r.read(&mut buf).unwrap_err()));
+    drop(first);
+    assert!(r.resync());
+    assert_eq!((r.sequence(), r.position()), (second.sequence(), 0));
+    r.read_exact(&mut buf[..5]).unwrap();
+    assert_eq!(&buf[..5], &[2; 5]);
+
+    // A new capacity replaces the file, and readers open it again.
+    let third = ShmWriter::create(&path, 4096).unwrap();
+    assert!(third.sequence() > second.sequence());
+    assert!(restarted(r.read(&mut buf).unwrap_err()));
+    assert_eq!(ShmReader::open(&path).unwrap().sequence(), third.sequence());
+
+    // Files that are not segments.
+    let other = segment("other");
+    std::fs::write(&other, [0; 100]).unwrap();
+    let err = ShmReader::open(&other).err().unwrap();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const COUPLING: u16 = 1;
+
+fn round_trip<T: SparseValue>(
+    entries: &[(u32, u32, T)],
+    shape: [u32; 2],
+    duplicates: SparseDuplicates
+) -> Sparse<T> {
+    let mut data = vec![];
+    write_sparse(COUPLING, entries, shape, &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let res = read_sparse(state.unwrap(), ty, prop, duplicates, &mut r).unwrap();
+    assert!(r.is_empty());
+    res
+}
+
+#[test]
+fn empty() {
+    let res = round_trip::<f64>(&[], [1000, 2000], SparseDuplicates::Sum);
+    assert_eq!(res, Sparse {rows: 1000, cols: 2000, entries: vec![]});
+    let grid = round_trip::<u8>(&[], [0, 0], SparseDuplicates::Sum).to_grid();
+    assert!(grid.data.is_empty());
+}
+
+#[test]
+fn single_entry() {
+    let res = round_trip(&[(7, 3, -2.5_f32)], [10, 4], SparseDuplicates::LastWins);
+    assert_eq!(res.entries, vec![(7, 3, -2.5)]);
+    let grid = res.to_grid();
+    assert_eq!((grid.rows, grid.cols), (10, 4));
+    assert_eq!(grid.data[7 * 4 + 3], -2.5);
+    assert_eq!(grid.data.iter().filter(|&&x| x != 0.0).count(), 1);
+}
+
+#[test]
+fn random_against_dense() {
+    let mut rng = Rng::new(131);
+    for _ in 0..20 {
+        let shape = [1 + rng.below(200) as u32, 1 + rng.below(200) as u32];
+        let mut dense = vec![0_i32; shape[0] as usize * shape[1] as usize];
+        let mut entries = vec![];
+        for _ in 0..rng.below(500) {
+            let row = rng.below(shape[0] as u64) as u32;
+            let col = rng.below(shape[1] as u64) as u32;
+            let val = rng.below(1000) as i32 - 500;
+            dense[(row * shape[1] + col) as usize] += val;
+            entries.push((row, col, val));
+        }
+        let res = round_trip(&entries, shape, SparseDuplicates::Sum);
+        // Every coordinate appears once.
+        let mut coords: Vec<(u32, u32)> = res.entries.iter().map(|e| (e.0, e.1)).collect();
+        coords.sort();
+        coords.dedup();
+        assert_eq!(coords.len(), res.entries.len());
+        assert_eq!(res.to_grid().data, dense);
+    }
+}
+
+#[test]
+fn duplicates() {
+    let entries = [(0, 1, 200_u8), (1, 0, 1), (0, 1, 100), (0, 1, 7)];
+    let sum = round_trip(&entries, [2, 2], SparseDuplicates::Sum);
+    // Integer sums wrap around.
+    assert_eq!(sum.entries, vec![(0, 1, 51), (1, 0, 1)]);
+    let last = round_trip(&entries, [2, 2], SparseDuplicates::LastWins);
+    assert_eq!(last.entries, vec![(0, 1, 7), (1, 0, 1)]);
+}
+
+#[test]
+fn invalid() {
+    let err = write_sparse(COUPLING, &[(2, 0, 1.0_f32)], [2, 2], &mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // Another value type.
+    let mut data = vec![];
+    write_sparse(COUPLING, &[(0, 0, 1.0_f32)], [2, 2], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let err = read_sparse::<f64, _>(state.unwrap(), ty, prop, SparseDuplicates::Sum, &mut r);
+    let err = Error::from_io(&err.unwrap_err());
+    assert_eq!((err.kind(), err.property()), (ErrorKind::InvalidData, Some(COUPLING)));
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const TIME: u16 = 1;
+const MARKER: u16 = 2;
+const DATA: u16 = 3;
+
+/// Writes frames with time values alternating between `f32` and `f64`.
+fn mixed() -> Vec<u8> {
+    let mut w = vec![];
+    TimeUnit::Steps.write(TIME, &mut w).unwrap();
+    for (i, &t) in [0.5, 1.25, 2.0, 3.75].iter().enumerate() {
+        if i % 2 == 0 {
+            (t as f32).write_property(TIME, &mut w).unwrap();
+        } else {
+            Time::write(TIME, t, &mut w).unwrap();
+        }
+        Scalar::write_array(DATA, &vec![i as u32; 2], &mut w).unwrap();
+    }
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+#[test]
+fn read_mixed() {
+    let data = mixed();
+    let mut r = &data[..];
+    let mut times = vec![];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        match (ty, prop) {
+            (_, TIME) if ty == TIME_UNIT_FORMAT => {
+                assert_eq!(TimeUnit::read(state, ty, prop, &mut r).unwrap(), TimeUnit::Steps);
+            }
+            (_, TIME) => times.push(Time::read(state, ty, &mut r).unwrap()),
+            _ => {RawBlock::read_property(state, ty, prop, &mut r).unwrap();}
+        }
+    }
+    assert_eq!(times, vec![0.5, 1.25, 2.0, 3.75]);
+}
+
+#[test]
+fn frame_reader_mixed() {
+    let data = mixed();
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut times = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        times.push(frame.time);
+    }
+    assert_eq!(times, vec![Some(0.5), Some(1.25), Some(2.0), Some(3.75)]);
+    assert_eq!(r.time_unit(), Some(&TimeUnit::Steps));
+}
+
+#[test]
+fn frame_writer_stamps_times() {
+    let mut w = FrameWriter::new(vec![], TIME);
+    w.write_time_unit(&TimeUnit::Custom("ticks".into())).unwrap();
+    for i in 0..3 {
+        w.begin_frame(i as f64 * 0.1).unwrap();
+        Scalar::write_array(DATA, &vec![i as u8], &mut w).unwrap();
+    }
+    assert_eq!(w.frames(), 3);
+    let data = w.finish().unwrap();
+
+    let mut r = FrameReader::with_time(&data[..], TIME);
+    let mut frames = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        frames.push((frame.number, frame.time, frame.read_property::<u8>(DATA).unwrap()));
+    }
+    assert_eq!(frames, vec![
+        (0, Some(0.0), Some(0)),
+        (1, Some(0.1), Some(1)),
+        (2, Some(0.2), Some(2)),
+    ]);
+    assert_eq!(r.time_unit(), Some(&TimeUnit::Custom("ticks".into())));
+}
+
+#[test]
+fn no_time_property() {
+    let mut data = vec![];
+    for i in 0..2 {
+        0_u8.write_property(MARKER, &mut data).unwrap();
+        Scalar::write_array(DATA, &vec![i as u32], &mut data).unwrap();
+    }
+    State::new().end_type_formats(&mut data).unwrap();
+
+    let mut r = FrameReader::new(&data[..], MARKER).time_property(TIME);
+    let mut frames = 0;
+    while let Some(frame) = r.next_frame().unwrap() {
+        assert_eq!(frame.time, None);
+        frames += 1;
+    }
+    assert_eq!(frames, 2);
+    assert_eq!(r.time_unit(), None);
+}
+
+#[test]
+fn decode_rejects_arrays() {
+    let mut data = vec![];
+    Scalar::write_array(TIME, &vec![1.0_f64, 2.0], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    let blocks = RawBlock::read_property(state.unwrap(), ty, prop, &mut r).unwrap();
+    let err = Time::decode(&blocks[0]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(Error::from_io(&err).property(), Some(TIME));
+}
@@ -0,0 +1,227 @@
+#![cfg(feature = "tracing")]
+
+extern crate binpool;
+extern crate tracing;
+extern crate tracing_subscriber;
+
+use binpool::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const NOTE: u16 = 2;
+
+const FRAMES: usize = 3;
+
+/// A span or event, with the index of its parent span.
+#[derive(Clone, Debug)]
+struct Node {
+    name: &'static str,
+    parent: Option<usize>,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Node {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields.iter().find(|f| f.0 == name).map(|f| &f.1[..])
+    }
+}
+
+impl Visit for Node {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() != "message" {self.fields.push((field.name(), format!("{:?}", value)))}
+    }
+}
+
+#[derive(Default)]
+struct Recorded {
+    spans: Vec<Node>,
+    events: Vec<Node>,
+    /// The index of each open span, since ids are reused after spans close.
+    ids: HashMap<u64, usize>,
+}
+
+/// A layer that records the spans and events with the target of the library.
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Recorded>>);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        assert_eq!(attrs.metadata().target(), "binpool");
+        let mut rec = self.0.lock().unwrap();
+        let parent = ctx.span(id).and_then(|s| s.parent()).map(|p| rec.ids[&p.id().into_u64()]);
+        let mut node = Node {name: attrs.metadata().name(), parent, fields: vec![]};
+        attrs.record(&mut node);
+        let index = rec.spans.len();
+        rec.spans.push(node);
+        rec.ids.insert(id.into_u64(), index);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut rec = self.0.lock().unwrap();
+        let index = rec.ids[&id.into_u64()];
+        values.record(&mut rec.spans[index]);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        assert_eq!(event.metadata().target(), "binpool");
+        let mut rec = self.0.lock().unwrap();
+        let parent = ctx.event_span(event).map(|s| rec.ids[&s.id().into_u64()]);
+        let mut node = Node {name: event.metadata().name(), parent, fields: vec![]};
+        event.record(&mut node);
+        rec.events.push(node);
+    }
+}
+
+/// Runs `f` while recording, and returns the spans and events.
+fn record<F: FnOnce()>(f: F) -> (Vec<Node>, Vec<Node>) {
+    let recorder = Recorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    let rec = recorder.0.lock().unwrap();
+    (rec.spans.clone(), rec.events.clone())
+}
+
+/// The type format of positions, as recorded.
+fn positions() -> String {
+    Type::F32.vector(3).unwrap().0.to_string()
+}
+
+/// A small file with a header, units and frames with positions in two blocks and a note.
+fn file() -> Vec<u8> {
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]), TIME);
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new("m", 1.0));
+    units.write_units(&mut w).unwrap();
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64 * 0.25).unwrap();
+        let ty = Type::F32.vector(3).unwrap().0;
+        let blocks: Vec<RawBlock> = (0..2).map(|j| {
+            let mut data = vec![];
+            for _ in 0..=i {[j as f32; 3].write_element(&mut data).unwrap()}
+            RawBlock {type_format: ty, property_id: POSITION, offset: j * 10, data}
+        }).collect();
+        RawBlock::write_property(ty, POSITION, &blocks, &mut w).unwrap();
+        (i as u16).write_property(NOTE, &mut w).unwrap();
+    }
+    let mut w = w.finish().unwrap();
+    std::mem::take(w.get_mut())
+}
+
+#[test]
+fn frame_hierarchy() {
+    let data = file();
+    let (spans, events) = record(|| {
+        let mut r = FrameReader::with_time(PoolReader::new(&data[..]), TIME);
+        let mut n = 0;
+        while r.next_frame().unwrap().is_some() {n += 1}
+        assert_eq!(n, FRAMES);
+    });
+    assert!(events.is_empty(), "{:?}", events);
+
+    // Frames have no parent, and hold the blocks of their time, positions and note.
+    let frames: Vec<usize> = (0..spans.len()).filter(|&i| spans[i].name == "frame").collect();
+    assert_eq!(frames.len(), FRAMES);
+    for (i, &frame) in frames.iter().enumerate() {
+        let span = &spans[frame];
+        assert_eq!(span.parent, None);
+        assert_eq!(span.field("frame"), Some(&i.to_string()[..]));
+        assert_eq!(span.field("time"), Some(&format!("{:?}", i as f64 * 0.25)[..]));
+        let blocks: Vec<&Node> = spans.iter().filter(|s| s.parent == Some(frame)).collect();
+        assert!(blocks.iter().all(|b| b.name == "block"));
+        let ids: Vec<&str> = blocks.iter().map(|b| b.field("property_id").unwrap()).collect();
+        assert_eq!(ids, vec!["0", "1", "1", "2"]);
+        let position = blocks[2];
+        assert_eq!(position.field("type_format"), Some(&positions()[..]));
+        assert_eq!(position.field("bytes"), Some(&(12 * (i + 1)).to_string()[..]));
+        assert_eq!(position.field("offset"), Some("10"));
+    }
+
+    // Blocks before the first frame are not in a frame, and blocks are not nested.
+    let first = frames[0];
+    assert!(spans[..first].iter().all(|s| s.name == "block" && s.parent.is_none()));
+    assert!(!spans[..first].is_empty());
+    assert_eq!(spans.len(), first + FRAMES * 5);
+}
+
+/// Fails the first read with `io::ErrorKind::Interrupted`.
+struct Interrupted<'a> {
+    data: &'a [u8],
+    interrupted: bool,
+}
+
+impl<'a> Read for Interrupted<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.interrupted {
+            self.interrupted = true;
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        self.data.read(buf)
+    }
+}
+
+#[test]
+fn events() {
+    let data = file();
+    let (_, events) = record(|| {
+        // The header, notes and positions are skipped.
+        let r = Interrupted {data: &data, interrupted: false};
+        let mut r = PoolReader::new(r).unknown(Unknown::Skip);
+        r.register(TIME);
+        let mut times = vec![];
+        while let Some((state, ty, prop)) = r.next_property().unwrap() {
+            assert_eq!(prop, TIME);
+            let mut t = 0.0_f64;
+            t.read_property(state, ty, &mut r).unwrap();
+            times.push(t);
+        }
+        assert_eq!(times, vec![0.0, 0.25, 0.5]);
+
+        let mut r = &data[..];
+        let mut frames = FrameReader::with_time(&mut r, TIME);
+        frames.next_frame().unwrap();
+        let (state, ty, _) = raw::read_property_header(&mut r).unwrap().unwrap();
+        let mut x = 0_u8;
+        assert!(x.read_property(state, ty, &mut r).is_err());
+
+        let limits = Limits {max_block_bytes: Some(20), ..Limits::unlimited()};
+        let mut r = FrameReader::with_time(PoolReader::new(&data[..]), TIME).limits(limits);
+        while let Ok(Some(_)) = r.next_frame() {}
+    });
+    let names: Vec<&str> = events.iter().map(|e| e.name).collect();
+    assert_eq!(names[0], "retry");
+    assert_eq!(names.iter().filter(|&&x| x == "retry").count(), 1);
+    let skips: Vec<&Node> = events.iter().filter(|e| e.name == "skip").collect();
+    assert_eq!(skips.len(), 1 + 2 * FRAMES);
+    assert_eq!(skips[0].field("property_id"), Some(&HEADER_PROPERTY.to_string()[..]));
+    assert_eq!(skips[3].field("property_id"), Some("1"));
+    assert_eq!(skips[3].field("type_format"), Some(&positions()[..]));
+    assert_eq!(skips[3].field("bytes"), Some("48"));
+    assert_eq!(skips[4].field("property_id"), Some("2"));
+    assert!(skips.iter().all(|e| e.parent.is_none()));
+
+    // The positions of the second frame, which follow its time, are read as a byte.
+    let mismatch = events.iter().find(|e| e.name == "type_mismatch").unwrap();
+    assert_eq!(mismatch.field("property_id"), Some("1"));
+    assert_eq!(mismatch.field("type_format"), Some(&positions()[..]));
+    assert_eq!(mismatch.field("expected"), Some(&Type::U8.scalar().0.to_string()[..]));
+
+    // The limit is exceeded by the positions of the second frame, inside its span and block.
+    let limit = events.last().unwrap();
+    assert_eq!(limit.name, "limit_exceeded");
+    assert_eq!(limit.fields, vec![
+        ("property_id", "1".to_string()),
+        ("limit", "\"max_block_bytes\"".to_string()),
+        ("value", "24".to_string()),
+        ("max", "20".to_string()),
+    ]);
+    assert!(limit.parent.is_some());
+}
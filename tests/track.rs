@@ -0,0 +1,157 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Write;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const NOTE: u16 = 1;
+const EVENT: u16 = 2;
+
+/// Writes the frames of the physics track.
+fn physics<W: Write>(frame: u32, rng: &mut Rng, w: &mut W) {
+    Time::write(TIME, frame as f64, w).unwrap();
+    let pos: Vec<[f32; 3]> = (0..1 + rng.below(50)).map(|_| [rng.unit() as f32; 3]).collect();
+    Vector::write_array(POSITION, &pos, w).unwrap();
+}
+
+/// Writes the annotations of a frame, which use the same property ids as the physics.
+fn annotations<W: Write>(frame: u32, w: &mut W) {
+    Time::write(TIME, frame as f64, w).unwrap();
+    Scalar::write_array(NOTE, &format!("frame {}", frame).into_bytes(), w).unwrap();
+    if frame.is_multiple_of(3) {
+        Scalar::write_array(EVENT, &vec![frame; 2], w).unwrap();
+    }
+}
+
+/// Writes the tracks interleaved, and each track separately.
+fn streams() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let mut w = TrackWriter::new(vec![]);
+    let (mut a, mut b) = (vec![], vec![]);
+    let (mut rng_a, mut rng_b) = (Rng::new(146), Rng::new(146));
+    for frame in 0..30 {
+        physics(frame, &mut rng_a, &mut w.track(0).unwrap());
+        physics(frame, &mut rng_b, &mut a);
+        // Annotations are not written in every frame.
+        if frame.is_multiple_of(2) {
+            annotations(frame, &mut w.track(1).unwrap());
+            annotations(frame, &mut b);
+        }
+    }
+    State::new().end_type_formats(&mut a).unwrap();
+    State::new().end_type_formats(&mut b).unwrap();
+    (w.finish().unwrap(), a, b)
+}
+
+#[test]
+fn read_tracks_independently() {
+    let (data, a, b) = streams();
+    for &(track, expected) in &[(0, &a), (1, &b)] {
+        let mut out = vec![];
+        TrackReader::new(&data[..]).read_track(track, &mut out).unwrap();
+        assert!(out == *expected, "track {}", track);
+    }
+    // A track that was never written is empty.
+    let mut out = vec![];
+    TrackReader::new(&data[..]).read_track(7, &mut out).unwrap();
+    assert_eq!(out, vec![0, 0]);
+}
+
+#[test]
+fn demux() {
+    let (data, a, b) = streams();
+    let mut sinks = vec![(1, vec![]), (0, vec![])];
+    TrackReader::new(&data[..]).demux(&mut sinks).unwrap();
+    assert!(sinks[0].1 == b);
+    assert!(sinks[1].1 == a);
+
+    // The demultiplexed tracks read like the separate streams.
+    let mut r = &sinks[0].1[..];
+    let mut notes = 0;
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        let blocks = RawBlock::read_property(state, ty, prop, &mut r).unwrap();
+        if prop == NOTE && ty == Type::U8.scalar().0 {
+            assert_eq!(blocks[0].data, format!("frame {}", 2 * notes).into_bytes());
+            notes += 1;
+        }
+    }
+    assert_eq!(notes, 15);
+}
+
+/// Writes a little endian stream in big endian, with the given alignment.
+fn big_endian(data: &[u8], alignment: u16) -> Vec<u8> {
+    let opts = WriteOptions::default().allow_reserved(true).alignment(alignment);
+    let mut w = PoolWriter::new(vec![]).with_endianness(Endian::Big).with_options(opts);
+    w.write_all(&data[..data.len() - 2]).unwrap();
+    w.finish().unwrap()
+}
+
+#[test]
+fn demux_big_endian() {
+    let (data, a, b) = streams();
+    let data = big_endian(&data, 64);
+    let mut sinks = vec![(1, vec![]), (0, vec![])];
+    TrackReader::new(&data[..]).demux(&mut sinks).unwrap();
+    // The tracks keep the byte order, without the padding of the stream.
+    assert!(sinks[0].1 == big_endian(&b, 0));
+    assert!(sinks[1].1 == big_endian(&a, 0));
+    let mut r = PoolReader::new(&sinks[0].1[..]).unknown(Unknown::Skip);
+    while r.next_property().unwrap().is_some() {}
+    assert_eq!(r.header(), Some(Header {endian: Endian::Big, ..Header::default()}));
+
+    let mut out = vec![];
+    TrackReader::new(&data[..]).read_track(0, &mut out).unwrap();
+    assert!(out == sinks[1].1);
+}
+
+#[test]
+fn untagged_stream_is_track_0() {
+    let (_, a, _) = streams();
+    let mut out = vec![];
+    TrackReader::new(&a[..]).read_track(0, &mut out).unwrap();
+    assert!(out == a);
+    let mut out = vec![];
+    TrackReader::new(&a[..]).read_track(1, &mut out).unwrap();
+    assert_eq!(out, vec![0, 0]);
+}
+
+#[test]
+fn switches() {
+    let mut w = TrackWriter::new(vec![]);
+    Time::write(TIME, 0.0, &mut w.track(0).unwrap()).unwrap();
+    let len = w.get_ref().len();
+    // No switch is written for the current track.
+    Time::write(TIME, 1.0, &mut w.track(0).unwrap()).unwrap();
+    assert_eq!(w.get_ref().len(), 2 * len);
+    assert_eq!(w.track(3).unwrap().id(), 3);
+    assert_eq!(w.current_track(), 3);
+    assert!(w.get_ref().len() > 2 * len);
+
+    // Switching in the middle of a property or writing end of stream is refused.
+    w.track(3).unwrap().write_all(&Type::F64.scalar().0.to_le_bytes()).unwrap();
+    assert_eq!(w.track(1).err().unwrap().kind(), std::io::ErrorKind::InvalidInput);
+    let mut w = TrackWriter::new(vec![]);
+    let err = State::new().end_type_formats(&mut w.track(2).unwrap()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn invalid_switch() {
+    let mut data = vec![];
+    RawBlock::write_property(TRACK_FORMAT, TRACK_PROPERTY, &[RawBlock {
+        type_format: TRACK_FORMAT,
+        property_id: TRACK_PROPERTY,
+        offset: 0,
+        data: vec![1, 0, 0],
+    }], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let err = TrackReader::new(&data[..]).read_track(0, &mut vec![]).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!(err.property(), Some(TRACK_PROPERTY));
+}
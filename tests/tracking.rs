@@ -0,0 +1,90 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+/// Writes two properties:
+///
+/// ```ignore
+/// 0: type format, 2: property id 5, 4: bytes, 12: offset, 20: data, 32: end of bytes
+/// 40: type format, 42: property id 6, 44: bytes, 52: offset, 60: data, 68: end of bytes
+/// 76: end of stream
+/// ```
+fn stream() -> Vec<u8> {
+    let mut w = vec![];
+    Scalar::write_array(5, &vec![1_u32, 2, 3], &mut w).unwrap();
+    0.5_f64.write_property(6, &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    assert_eq!(w.len(), 78);
+    w
+}
+
+fn read(data: &[u8]) -> std::io::Result<(Vec<u32>, f64)> {
+    let mut r = TrackingReader::new(data);
+    let mut arr: Vec<u32> = vec![];
+    let mut val = 0.0_f64;
+    while let (Some(state), ty, prop) = r.track(State::read)? {
+        match prop {
+            5 => r.track(|r| Scalar::read_array(state, ty, &mut arr, r))?,
+            6 => r.track(|r| val.read_property(state, ty, r))?,
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    Ok((arr, val))
+}
+
+#[test]
+fn complete() {
+    assert_eq!(read(&stream()).unwrap(), (vec![1, 2, 3], 0.5));
+}
+
+#[test]
+fn truncated() {
+    let data = stream();
+    let cases = [
+        (0, 0, Field::TypeFormat, None),
+        (1, 0, Field::TypeFormat, None),
+        (3, 2, Field::PropertyId, None),
+        (4, 4, Field::Bytes, Some(5)),
+        (10, 4, Field::Bytes, Some(5)),
+        (15, 12, Field::OffsetInstanceId, Some(5)),
+        (20, 20, Field::Data, Some(5)),
+        (25, 20, Field::Data, Some(5)),
+        (35, 32, Field::Bytes, Some(5)),
+        (41, 40, Field::TypeFormat, None),
+        (50, 44, Field::Bytes, Some(6)),
+        (64, 60, Field::Data, Some(6)),
+        (70, 68, Field::Bytes, Some(6)),
+        (77, 76, Field::TypeFormat, None),
+    ];
+    for &(len, at_byte, field, property) in &cases {
+        let err = read(&data[..len]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "truncated at {}", len);
+        let err = Error::from_io(&err);
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof, "truncated at {}", len);
+        assert_eq!(err.at_byte(), Some(at_byte), "truncated at {}", len);
+        assert_eq!(err.field(), Some(field), "truncated at {}", len);
+        assert_eq!(err.property(), property, "truncated at {}", len);
+    }
+}
+
+#[test]
+fn position_and_property() {
+    let data = stream();
+    let mut r = TrackingReader::new(&data[..]);
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    assert_eq!((r.position(), r.property(), r.field()), (4, Some(5), Field::Bytes));
+    let mut arr: Vec<u32> = vec![];
+    Scalar::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap();
+    assert_eq!(prop, 5);
+    assert_eq!(r.position(), 40);
+    assert_eq!(r.field(), Field::TypeFormat);
+}
+
+#[test]
+fn existing_position_is_kept() {
+    let r = TrackingReader::new(&b""[..]);
+    let err: std::io::Error = Error::new(ErrorKind::InvalidData).with_position(123).into();
+    assert_eq!(Error::from_io(&r.annotate(err)).at_byte(), Some(123));
+}
@@ -0,0 +1,221 @@
+#![cfg(feature = "test-util")]
+
+extern crate binpool;
+#[macro_use]
+extern crate proptest;
+
+use binpool::test_util::{stream, Model, StreamConfig};
+use binpool::*;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+
+enum Message {
+    Block(BlockHeader, Vec<u8>),
+    End,
+}
+
+/// A queue of messages between threads, with the payload borrowed when received.
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    received: Option<Message>,
+    /// The address of every payload sent.
+    sent: Vec<usize>,
+}
+
+impl BlockSink for Queue {
+    fn send(&mut self, header: &BlockHeader, payload: &[u8]) -> io::Result<()> {
+        if !payload.is_empty() {self.sent.push(payload.as_ptr() as usize)}
+        self.messages.push_back(Message::Block(*header, payload.to_vec()));
+        Ok(())
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        self.messages.push_back(Message::End);
+        Ok(())
+    }
+}
+
+impl BlockSource for Queue {
+    fn recv(&mut self) -> io::Result<Option<(BlockHeader, Payload<'_>)>> {
+        self.received = self.messages.pop_front();
+        match self.received {
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+            Some(Message::End) => Ok(None),
+            Some(Message::Block(header, ref data)) => Ok(Some((header, Cow::Borrowed(data)))),
+        }
+    }
+}
+
+/// Decodes the items of every property from raw blocks.
+fn decode<R: Read>(mut r: R) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    let mut res = vec![];
+    while let (Some(state), ty, prop) = State::read(&mut r).unwrap() {
+        let (scalar, rows, cols) = Type::info(ty).unwrap();
+        let size = (scalar.type_size() * rows as u64 * cols as u64) as usize;
+        let mut items: Vec<Vec<u8>> = vec![];
+        for block in RawBlock::read_property(state, ty, prop, &mut r).unwrap() {
+            for (i, item) in block.data.chunks(size).enumerate() {
+                let ind = block.offset as usize + i;
+                if ind >= items.len() {items.resize(ind + 1, vec![0; size])}
+                items[ind] = item.to_vec();
+            }
+        }
+        res.push((ty, prop, items));
+    }
+    let mut rest = vec![];
+    r.read_to_end(&mut rest).unwrap();
+    assert!(rest.is_empty());
+    res
+}
+
+fn expected(model: &Model) -> Vec<(u16, u16, Vec<Vec<u8>>)> {
+    model.properties.iter()
+        .map(|p| (p.type_format, p.property_id, p.items.clone()))
+        .collect()
+}
+
+/// Sends a stream through a queue, written in chunks.
+fn send(data: &[u8], chunk: usize) -> Queue {
+    let mut w = SinkWriter::new(Queue::default());
+    for part in data.chunks(chunk) {w.write_all(part).unwrap()}
+    w.into_inner()
+}
+
+fn receive(queue: Queue) -> Vec<u8> {
+    let mut data = vec![];
+    SourceReader::new(queue).read_to_end(&mut data).unwrap();
+    data
+}
+
+fn block(type_format: u16, property_id: u16, offset: u64, data: &[u8]) -> Message {
+    let bytes = data.len() as u64;
+    Message::Block(BlockHeader {type_format, property_id, bytes, offset}, data.to_vec())
+}
+
+fn end_bytes(type_format: u16, property_id: u16) -> Message {
+    block(type_format, property_id, 0, &[])
+}
+
+proptest! {
+    #[test]
+    fn sink_and_source(model in stream(StreamConfig::default()), chunk in 1..64usize) {
+        let data = model.encode();
+        let queue = send(&data, chunk);
+        // One message per block and end of bytes, and the end of stream.
+        let blocks: usize = model.properties.iter()
+            .map(|p| 1 + p.blocks.iter().filter(|b| !b.data.is_empty()).count())
+            .sum();
+        prop_assert_eq!(queue.messages.len(), blocks + 1);
+        prop_assert!(matches!(queue.messages.back(), Some(Message::End)));
+        prop_assert_eq!(receive(queue), data.clone());
+        prop_assert_eq!(decode(SourceReader::new(send(&data, chunk))), expected(&model));
+    }
+
+    #[test]
+    fn wire_sink_and_source(model in stream(StreamConfig::default())) {
+        let data = model.encode();
+        let mut source = WireSource::new(&data[..]);
+        let mut queue = Queue::default();
+        while let Some((header, payload)) = source.recv().unwrap() {
+            queue.send(&header, &payload).unwrap();
+        }
+        queue.end().unwrap();
+        let mut sink = WireSink::new(vec![]);
+        while let Some((header, payload)) = queue.recv().unwrap() {
+            sink.send(&header, &payload).unwrap();
+        }
+        sink.end().unwrap();
+        prop_assert_eq!(sink.into_inner(), data.clone());
+        // The same messages as the writer sends.
+        prop_assert_eq!(receive(send(&data, data.len())), data);
+    }
+}
+
+#[test]
+fn borrowed_payloads() {
+    let mut data = vec![];
+    Scalar::write_array(POSITION, &vec![1.0_f32; 100], &mut data).unwrap();
+    Scalar::write_array(POSITION + 1, &vec![2_u8; 7], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    // Written at once, the payloads are passed on without copying.
+    let queue = send(&data, data.len());
+    let range = data.as_ptr() as usize..data.as_ptr() as usize + data.len();
+    assert_eq!(queue.sent.len(), 2);
+    assert!(queue.sent.iter().all(|p| range.contains(p)));
+    // Written in small chunks, they are collected first.
+    let queue = send(&data, 3);
+    assert!(queue.sent.iter().all(|p| !range.contains(p)));
+    assert_eq!(receive(queue), data);
+}
+
+#[test]
+fn frames() {
+    let w = PoolWriter::new(SinkWriter::new(Queue::default()));
+    let mut w = FrameWriter::new(w, TIME);
+    let mut units = Units::new();
+    units.set(POSITION, Unit::new("m", 1.0));
+    units.write_units(&mut w).unwrap();
+    let positions: Vec<Vec<[f32; 3]>> = (0..10).map(|i| vec![[i as f32; 3]; 1 + i]).collect();
+    for (i, pos) in positions.iter().enumerate() {
+        w.begin_frame(i as f64).unwrap();
+        Vector::write_array(POSITION, pos, &mut w).unwrap();
+    }
+    let mut w = w.finish().unwrap();
+    let queue = std::mem::take(w.get_mut().get_mut());
+
+    let mut r = FrameReader::with_time(PoolReader::new(SourceReader::new(queue)), TIME);
+    let mut res = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut pos = vec![];
+        frame.read_array(POSITION, &mut pos).unwrap();
+        res.push((frame.time, pos));
+    }
+    let expected: Vec<_> = positions.into_iter().enumerate()
+        .map(|(i, pos)| (Some(i as f64), pos))
+        .collect();
+    assert_eq!(res, expected);
+    // The header and units are read before the first frame.
+    let preamble: Vec<u16> = r.preamble().iter().map(|b| b.property_id).collect();
+    assert_eq!(preamble, vec![HEADER_PROPERTY, UNITS_PROPERTY]);
+}
+
+#[test]
+fn invalid_messages() {
+    let ty = Type::U8.scalar().0;
+    let read = |messages: Vec<Message>| {
+        let queue = Queue {messages: messages.into(), ..Queue::default()};
+        let mut data = vec![];
+        SourceReader::new(queue).read_to_end(&mut data).unwrap_err().kind()
+    };
+    let mismatch = Message::Block(BlockHeader {
+        type_format: ty, property_id: POSITION, bytes: 3, offset: 0,
+    }, vec![1; 2]);
+    assert_eq!(read(vec![mismatch, end_bytes(ty, POSITION), Message::End]),
+               io::ErrorKind::InvalidData);
+    // Another property before the end of bytes.
+    assert_eq!(read(vec![block(ty, POSITION, 0, &[1]), block(ty, 2, 0, &[1])]),
+               io::ErrorKind::InvalidData);
+    assert_eq!(read(vec![block(0, POSITION, 0, &[1])]), io::ErrorKind::InvalidData);
+    // End of stream before the end of bytes.
+    assert_eq!(read(vec![block(ty, POSITION, 0, &[1]), Message::End]),
+               io::ErrorKind::UnexpectedEof);
+
+    let mut sink = WireSink::new(vec![]);
+    let header = BlockHeader {type_format: ty, property_id: POSITION, bytes: 1, offset: 0};
+    assert_eq!(sink.send(&header, &[1, 2]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    sink.send(&header, &[1]).unwrap();
+    let other = BlockHeader {property_id: 2, ..header};
+    assert_eq!(sink.send(&other, &[1]).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    assert_eq!(sink.end().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    sink.send(&BlockHeader {bytes: 0, ..header}, &[]).unwrap();
+    sink.end().unwrap();
+    let mut expected = vec![];
+    write_block(ty, POSITION, 0, &[1], &mut expected).unwrap();
+    State::new().end_type_formats(&mut expected).unwrap();
+    assert_eq!(sink.into_inner(), expected);
+}
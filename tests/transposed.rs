@@ -0,0 +1,93 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 1;
+
+/// A column vector, written as a 3x1 matrix like some C++ math libraries do.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+struct Column([f32; 3]);
+
+impl Matrix for Column {
+    type Scalar = f32;
+
+    fn dim() -> [usize; 2] {[3, 1]}
+    fn get(&self, row: usize, _: usize) -> &f32 {&self.0[row]}
+    fn set(&mut self, row: usize, _: usize, val: f32) {self.0[row] = val}
+}
+
+fn columns() -> Vec<Column> {
+    (0..5).map(|i| Column([i as f32, 1.0, -(i as f32)])).collect()
+}
+
+fn rows() -> Vec<[f32; 3]> {
+    columns().iter().map(|c| c.0).collect()
+}
+
+fn transposed() -> ReadOptions {
+    ReadOptions::default().accept_transposed_vectors(true)
+}
+
+#[test]
+fn vectors_from_columns() {
+    let mut data = vec![];
+    Matrix::write_array(POSITION, &columns(), &mut data).unwrap();
+    assert_eq!(&data[..2], &Type::F32.matrix(3, 1).unwrap().0.to_le_bytes());
+
+    // Strict by default.
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<[f32; 3]> = vec![];
+    let err = Vector::read_array(state.unwrap(), ty, &mut arr, &mut r).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::TypeMismatch);
+
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    Vector::read_array_with(state.unwrap(), ty, &mut arr, &transposed(), &mut r).unwrap();
+    assert_eq!(arr, rows());
+}
+
+#[test]
+fn columns_from_vectors() {
+    let mut data = vec![];
+    Vector::write_array(POSITION, &rows(), &mut data).unwrap();
+
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<Column> = vec![];
+    assert!(Matrix::read_array(state.unwrap(), ty, &mut arr, &mut r).is_err());
+
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    Matrix::read_array_with(state.unwrap(), ty, &mut arr, &transposed(), &mut r).unwrap();
+    assert_eq!(arr, columns());
+}
+
+#[test]
+fn frames() {
+    let mut w = FrameWriter::new(vec![], 0);
+    w.begin_frame(0.0).unwrap();
+    Matrix::write_array(POSITION, &columns(), &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let frame = FrameReader::with_time(&data[..], 0).next_frame().unwrap().unwrap();
+    let mut arr: Vec<[f32; 3]> = vec![];
+    assert!(frame.read_array(POSITION, &mut arr).is_err());
+    assert!(frame.read_array_with(POSITION, &mut arr, &transposed()).unwrap());
+    assert_eq!(arr, rows());
+    // Other dimensions are still refused.
+    let mut arr: Vec<[f32; 2]> = vec![];
+    assert!(frame.read_array_with(POSITION, &mut arr, &transposed()).is_err());
+}
+
+#[test]
+fn square_matrices_are_not_transposed() {
+    let mut data = vec![];
+    Matrix::write_array(POSITION, &vec![[[1_u8, 2], [3, 4]]], &mut data).unwrap();
+    let mut r = &data[..];
+    let (state, ty, _) = State::read(&mut r).unwrap();
+    let mut arr: Vec<[u8; 4]> = vec![];
+    let res = Vector::read_array_with(state.unwrap(), ty, &mut arr, &transposed(), &mut r);
+    assert!(res.is_err());
+}
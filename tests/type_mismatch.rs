@@ -0,0 +1,194 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+
+const POSITION: u16 = 7;
+
+/// Reads the first property of a stream with a function of the read traits.
+fn read<F>(data: &[u8], f: F) -> Error
+    where F: FnOnce(State<Bytes>, u16, &mut &[u8]) -> std::io::Result<()>
+{
+    let mut r = data;
+    let (state, ty, prop) = State::read(&mut r).unwrap();
+    assert_eq!(prop, POSITION);
+    let err = f(state.unwrap(), ty, &mut r).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    Error::from_io(&err)
+}
+
+fn mismatch(err: &Error, expected: (Type, u8, u8), found: Format) {
+    assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+    assert_eq!(err.property(), Some(POSITION));
+    assert_eq!(err.expected_type(), Some(expected));
+    assert_eq!(err.found_format(), Some(found));
+    assert_eq!(err.item_size(), None);
+}
+
+#[test]
+fn scalar_types() {
+    let mut data = vec![];
+    Scalar::write_array(POSITION, &vec![1.0_f32; 3], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| Scalar::read_array(s, ty, &mut Vec::<f64>::new(), r));
+    mismatch(&err, (Type::F64, 1, 1), Format::Builtin(Type::F32, 1, 1));
+    let err = read(&data, |s, ty, r| Scalar::read_array(s, ty, &mut Vec::<u32>::new(), r));
+    mismatch(&err, (Type::U32, 1, 1), Format::Builtin(Type::F32, 1, 1));
+    // A vector is not a scalar.
+    let err = read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[f32; 3]>::new(), r));
+    mismatch(&err, (Type::F32, 1, 3), Format::Builtin(Type::F32, 1, 1));
+
+    let mut data = vec![];
+    1_i16.write_property(POSITION, &mut data).unwrap();
+    let err = read(&data, |s, ty, r| 0_u16.read_property(s, ty, r));
+    mismatch(&err, (Type::U16, 1, 1), Format::Builtin(Type::I16, 1, 1));
+}
+
+#[test]
+fn vector_types() {
+    let mut data = vec![];
+    Vector::write_array(POSITION, &vec![[1.0_f32; 3]; 2], &mut data).unwrap();
+    // Another scalar type.
+    let err = read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[f64; 3]>::new(), r));
+    mismatch(&err, (Type::F64, 1, 3), Format::Builtin(Type::F32, 1, 3));
+    // Another dimension.
+    let err = read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[f32; 4]>::new(), r));
+    mismatch(&err, (Type::F32, 1, 4), Format::Builtin(Type::F32, 1, 3));
+    let err = read(&data, |s, ty, r| [0.0_f32; 2].read_property(s, ty, r));
+    mismatch(&err, (Type::F32, 1, 2), Format::Builtin(Type::F32, 1, 3));
+    // Flat reads expect scalars, and reads into scalars expect vectors.
+    let err = read(&data, |s, ty, r| {
+        <[f32; 3]>::read_array_flat(s, ty, &mut Vec::new(), r)
+    });
+    mismatch(&err, (Type::F32, 1, 1), Format::Builtin(Type::F32, 1, 3));
+    let err = read(&data, |s, ty, r| <[f32; 2]>::read_scalars(s, ty, &mut Vec::new(), r));
+    mismatch(&err, (Type::F32, 1, 2), Format::Builtin(Type::F32, 1, 3));
+}
+
+#[test]
+fn matrix_types() {
+    let mut data = vec![];
+    Matrix::write_array(POSITION, &vec![[[1_u8; 2]; 2]; 2], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| Matrix::read_array(s, ty, &mut Vec::<[[u8; 3]; 3]>::new(), r));
+    mismatch(&err, (Type::U8, 3, 3), Format::Builtin(Type::U8, 2, 2));
+    let err = read(&data, |s, ty, r| [[0_i8; 2]; 2].read_property(s, ty, r));
+    mismatch(&err, (Type::I8, 2, 2), Format::Builtin(Type::U8, 2, 2));
+    let err = read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[u8; 4]>::new(), r));
+    mismatch(&err, (Type::U8, 1, 4), Format::Builtin(Type::U8, 2, 2));
+}
+
+#[test]
+fn custom_format() {
+    let mut data = vec![];
+    let format = Type::offset_custom_format() + 2;
+    RawBlock::write_property(format, POSITION, &[RawBlock {
+        type_format: format,
+        property_id: POSITION,
+        offset: 0,
+        data: vec![0; 8],
+    }], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| Scalar::read_array(s, ty, &mut Vec::<f64>::new(), r));
+    mismatch(&err, (Type::F64, 1, 1), Format::Custom(format));
+    assert_eq!(err.to_string(),
+        format!("type mismatch of property 7: expected f64, found custom format {}", format));
+}
+
+#[test]
+fn bytes_not_multiple() {
+    let mut data = vec![];
+    write_block(Type::F32.vector(3).unwrap().0, POSITION, 0, &[0; 20], &mut data).unwrap();
+    let check = |err: Error| {
+        assert_eq!(err.kind(), ErrorKind::BytesNotMultiple);
+        assert_eq!(err.property(), Some(POSITION));
+        assert_eq!(err.item_size(), Some((20, 12)));
+        assert_eq!(err.expected_type(), None);
+        assert_eq!(err.to_string(),
+            "bytes are not a multiple of the item size of property 7 (20 bytes, item size 12)");
+    };
+    check(read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[f32; 3]>::new(), r)));
+    check(read(&data, |s, ty, r| [0.0_f32; 3].read_property(s, ty, r)));
+
+    // A multiple of the item size, but not a single item.
+    let mut data = vec![];
+    write_block(Type::F32.vector(3).unwrap().0, POSITION, 0, &[0; 24], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| [0.0_f32; 3].read_property(s, ty, r));
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert_eq!((err.property(), err.field()), (Some(POSITION), Some(Field::Bytes)));
+}
+
+#[test]
+fn display() {
+    let mut data = vec![];
+    Vector::write_array(POSITION, &vec![[1.0_f32; 3]; 2], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| Vector::read_array(s, ty, &mut Vec::<[f64; 3]>::new(), r));
+    assert_eq!(err.to_string(),
+        "type mismatch of property 7: expected f64 vector of 3, found f32 vector of 3");
+    let mut data = vec![];
+    Matrix::write_array(POSITION, &vec![[[1_u8; 2]; 3]; 2], &mut data).unwrap();
+    let err = read(&data, |s, ty, r| Scalar::read_array(s, ty, &mut Vec::<i64>::new(), r));
+    assert_eq!(err.to_string(),
+        "type mismatch of property 7: expected i64, found u8 matrix 3x2");
+}
+
+#[test]
+fn details_side_by_side() {
+    let format = Type::F32.vector(3).unwrap().0;
+    let err = Error::new(ErrorKind::TypeMismatch)
+        .with_property(POSITION)
+        .with_types((Type::F64, 1, 3), format)
+        .with_frame(4)
+        .with_event(5)
+        .with_line(6);
+    assert_eq!(err.expected_type(), Some((Type::F64, 1, 3)));
+    assert_eq!(err.found_format(), Some(Format::Builtin(Type::F32, 1, 3)));
+    assert_eq!((err.frame(), err.event(), err.line()), (Some(4), Some(5), Some(6)));
+    assert_eq!(err.to_string(), "type mismatch of property 7: \
+        expected f64 vector of 3, found f32 vector of 3 at line 6 in frame 4 in event 5");
+
+    let err = Error::new(ErrorKind::BytesNotMultiple).with_frame(1).with_item_size(10, 4);
+    assert_eq!((err.frame(), err.item_size()), (Some(1), Some((10, 4))));
+    let err = Error::new(ErrorKind::NonMonotonicTime).with_times(1.0, 0.5).with_event(2);
+    assert_eq!((err.times(), err.event()), (Some((1.0, 0.5)), Some(2)));
+}
+
+#[test]
+fn through_readers() {
+    // The property id is known when reading through a pool reader.
+    let mut data = vec![];
+    Vector::write_array(POSITION, &vec![[1_u16; 2]; 2], &mut data).unwrap();
+    State::new().end_type_formats(&mut data).unwrap();
+    let mut r = PoolReader::new(&data[..]);
+    r.register(POSITION);
+    let (state, ty, _) = r.next_property().unwrap().unwrap();
+    let err = Vector::read_array(state, ty, &mut Vec::<[u16; 3]>::new(), &mut r).unwrap_err();
+    let err = Error::from_io(&err);
+    assert_eq!(err.kind(), ErrorKind::TypeMismatch);
+    assert_eq!(err.property(), Some(POSITION));
+    assert_eq!(err.expected_type(), Some((Type::U16, 1, 3)));
+}
+
+#[test]
+fn decoded_blocks() {
+    let ty = Type::F32.vector(3).unwrap().0;
+    let mut data = vec![];
+    [1.0_f32; 3].write_element(&mut data).unwrap();
+    let block = RawBlock {type_format: ty, property_id: POSITION, offset: 0, data};
+    let err = Error::from_io(&block.decode::<[f64; 3]>().unwrap_err());
+    mismatch(&err, (Type::F64, 1, 3), Format::Builtin(Type::F32, 1, 3));
+    let err = Error::from_io(&block.decode::<f32>().unwrap_err());
+    mismatch(&err, (Type::F32, 1, 1), Format::Builtin(Type::F32, 1, 3));
+
+    let block = RawBlock {data: vec![0; 14], ..block};
+    let err = Error::from_io(&block.decode::<[f32; 3]>().unwrap_err());
+    assert_eq!(err.kind(), ErrorKind::BytesNotMultiple);
+    assert_eq!((err.property(), err.item_size()), (Some(POSITION), Some((14, 12))));
+
+    // Frames decode blocks too.
+    let mut w = FrameWriter::new(vec![], 0);
+    w.begin_frame(0.0).unwrap();
+    Vector::write_array(POSITION, &vec![[1.0_f32; 3]; 2], &mut w).unwrap();
+    let data = w.finish().unwrap();
+    let frame = FrameReader::with_time(&data[..], 0).next_frame().unwrap().unwrap();
+    let err = frame.read_array(POSITION, &mut Vec::<[f32; 4]>::new()).unwrap_err();
+    mismatch(&Error::from_io(&err), (Type::F32, 1, 4), Format::Builtin(Type::F32, 1, 3));
+}
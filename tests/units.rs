@@ -0,0 +1,147 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io;
+
+const POSITION: u16 = 1;
+const ENERGY: u16 = 2;
+const COUNT: u16 = 3;
+
+const GEV: f64 = 1.602e-10;
+const MEV: f64 = 1.602e-13;
+
+/// Writes energies and positions, with units if any are given.
+fn stream(units: &[(u16, &str, f64)]) -> Vec<u8> {
+    let mut w = vec![];
+    if !units.is_empty() {Units::write(units, &mut w).unwrap()}
+    Scalar::write_array(ENERGY, &vec![2.0_f64, 0.5], &mut w).unwrap();
+    Vector::write_array(POSITION, &vec![[1.0_f32, 2.0, 3.0]], &mut w).unwrap();
+    Scalar::write_array(COUNT, &vec![7_u32], &mut w).unwrap();
+    State::new().end_type_formats(&mut w).unwrap();
+    w
+}
+
+/// Reads energies and positions, and the units of the stream.
+fn read(data: &[u8]) -> (Vec<f64>, Vec<[f32; 3]>, Units) {
+    let mut r = PoolReader::new(data).unknown(Unknown::Skip);
+    r.register(ENERGY);
+    r.register(POSITION);
+    let (mut energies, mut positions) = (vec![], vec![]);
+    while let Some((state, ty, prop)) = r.next_property().unwrap() {
+        match prop {
+            ENERGY => Scalar::read_array(state, ty, &mut energies, &mut r).unwrap(),
+            POSITION => Vector::read_array(state, ty, &mut positions, &mut r).unwrap(),
+            _ => panic!("unexpected property {}", prop),
+        }
+    }
+    (energies, positions, r.units().clone())
+}
+
+#[test]
+fn round_trip() {
+    let units = [(ENERGY, "GeV", GEV), (POSITION, "cm", 0.01), (COUNT, "events", f64::NAN)];
+    let (energies, positions, read_units) = read(&stream(&units));
+    assert_eq!(energies, vec![2.0, 0.5]);
+    assert_eq!(positions, vec![[1.0, 2.0, 3.0]]);
+    assert_eq!(read_units.get(ENERGY), Some(&Unit::new("GeV", GEV)));
+    assert_eq!(read_units.get(POSITION), Some(&Unit {name: "cm".into(), scale: Some(0.01)}));
+    assert_eq!(read_units.get(COUNT), Some(&Unit {name: "events".into(), scale: None}));
+    let ids: Vec<u16> = read_units.iter().map(|(prop, _)| prop).collect();
+    assert_eq!(ids, vec![ENERGY, POSITION, COUNT]);
+
+    // A stream without units reads the same, with no units.
+    let (plain_energies, plain_positions, plain_units) = read(&stream(&[]));
+    assert_eq!((plain_energies, plain_positions), (energies, positions));
+    assert!(plain_units.is_empty());
+    assert!(validate(&mut &stream(&units)[..], &ValidateOptions::default()).unwrap().is_ok());
+}
+
+#[test]
+fn later_units_replace_earlier() {
+    let mut data = vec![];
+    Units::write(&[(ENERGY, "GeV", GEV), (POSITION, "m", 1.0)], &mut data).unwrap();
+    Units::write(&[(ENERGY, "MeV", MEV)], &mut data).unwrap();
+    data.extend_from_slice(&stream(&[]));
+    let units = read(&data).2;
+    assert_eq!(units.get(ENERGY).unwrap().name, "MeV");
+    assert_eq!(units.get(POSITION).unwrap().name, "m");
+}
+
+#[test]
+fn registered_units_property() {
+    let data = stream(&[(ENERGY, "GeV", GEV)]);
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+    r.register(UNITS_PROPERTY);
+    let (state, ty, prop) = r.next_property().unwrap().unwrap();
+    assert_eq!((ty, prop), (UNITS_FORMAT, UNITS_PROPERTY));
+    let units = Units::read(state, ty, prop, &mut r).unwrap();
+    assert_eq!(units.get(ENERGY), Some(&Unit::new("GeV", GEV)));
+    // Units that are read as a property are not collected.
+    assert!(r.units().is_empty());
+}
+
+#[test]
+fn unknown_scales() {
+    for &scale in &[f64::NAN, f64::INFINITY, 0.0, -1.0] {
+        assert_eq!(Unit::new("?", scale).scale, None);
+    }
+    assert_eq!(Unit::new("keV", 1.602e-16).scale, Some(1.602e-16));
+}
+
+#[test]
+fn conversion() {
+    let units = read(&stream(&[(ENERGY, "GeV", GEV), (COUNT, "events", f64::NAN)])).2;
+    let factor = units.factor(ENERGY, &Unit::new("MeV", MEV)).unwrap();
+    assert!((factor - 1000.0).abs() < 1e-9);
+    let mut energies = vec![2.0, 0.5];
+    units.convert(ENERGY, &Unit::new("MeV", MEV), &mut energies).unwrap();
+    assert!((energies[0] - 2000.0).abs() < 1e-6 && (energies[1] - 500.0).abs() < 1e-6);
+    units.convert(ENERGY, &Unit::new("GeV", GEV), &mut energies).unwrap();
+    assert!((energies[0] - 2000.0).abs() < 1e-6);
+
+    // A property without a unit, a unit without a scale, or a target without a scale.
+    for &(prop, ref target) in &[
+        (POSITION, Unit::new("m", 1.0)),
+        (COUNT, Unit::new("events", 1.0)),
+        (ENERGY, Unit::new("?", f64::NAN)),
+    ] {
+        let mut values = vec![1.0];
+        let err = units.convert(prop, target, &mut values).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = Error::from_io(&err);
+        assert_eq!((err.kind(), err.property()), (ErrorKind::UnknownUnit, Some(prop)));
+        assert_eq!(values, vec![1.0]);
+    }
+}
+
+#[test]
+fn invalid_blocks() {
+    let mut data = vec![];
+    Units::write(&[(ENERGY, "GeV", GEV)], &mut data).unwrap();
+    let (state, ty, prop) = raw::read_property_header(&mut &data[..]).unwrap().unwrap();
+    let mut r = &data[4..];
+    let block = RawBlock::read_property(state, ty, prop, &mut r).unwrap().remove(0);
+    assert_eq!(Units::decode(&block).unwrap().get(ENERGY).unwrap().name, "GeV");
+
+    let mut broken = vec![];
+    // Cut inside the entry header and inside the name.
+    for len in &[1, 11, 12, block.data.len() - 1] {
+        broken.push(RawBlock {data: block.data[..*len].to_vec(), ..block.clone()});
+    }
+    let mut utf8 = block.clone();
+    *utf8.data.last_mut().unwrap() = 0xff;
+    broken.push(utf8);
+    broken.push(RawBlock {type_format: UNITS_FORMAT + 1, ..block.clone()});
+    for b in &broken {
+        let err = Units::decode(b).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(Error::from_io(&err).property(), Some(UNITS_PROPERTY));
+    }
+
+    // Names that do not fit in the length are not written.
+    let name = "m".repeat(1 << 16);
+    let err = Units::write(&[(POSITION, &name, 1.0)], &mut vec![]).unwrap_err();
+    assert_eq!(Error::from_io(&err).property(), Some(POSITION));
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io;
+
+const EVENT: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes a delta encoded array followed by masses.
+fn stream<T: Integer>(items: &[T]) -> Vec<u8> {
+    let mut w = vec![];
+    write_varint_delta(EVENT, items, &mut w).unwrap();
+    Scalar::write_array(MASS, &vec![1.5_f64, 2.5], &mut w).unwrap();
+    w
+}
+
+/// Reads the delta encoded array and checks the masses after it.
+fn read<T: Integer>(data: &[u8]) -> io::Result<Vec<T>> {
+    let mut r = PoolReader::new(data);
+    r.register(EVENT);
+    r.register(MASS);
+    let mut items = vec![];
+    while let Some((state, ty, prop)) = r.next_property()? {
+        if prop == EVENT {
+            read_varint_delta(state, ty, prop, &mut items, &mut r)?;
+        } else {
+            let mut masses: Vec<f64> = vec![];
+            Scalar::read_array(state, ty, &mut masses, &mut r)?;
+            assert_eq!(masses, vec![1.5, 2.5]);
+        }
+    }
+    Ok(items)
+}
+
+fn round_trip<T: Integer + PartialEq + std::fmt::Debug>(items: &[T]) -> usize {
+    let data = stream(items);
+    assert_eq!(read::<T>(&data).unwrap(), items);
+    data.len()
+}
+
+/// Returns the delta encoded block of a stream.
+fn block(data: &[u8]) -> RawBlock {
+    let mut r = PoolReader::new(data);
+    r.register(EVENT);
+    let (state, ty, prop) = r.next_property().unwrap().unwrap();
+    assert_eq!(ty, VARINT_FORMAT);
+    RawBlock::read_property(state, ty, prop, &mut r).unwrap().remove(0)
+}
+
+#[test]
+fn random() {
+    let mut rng = Rng::new(179);
+    for _ in 0..50 {
+        let n = rng.below(40) as usize;
+        let u: Vec<u64> = (0..n).map(|_| rng.next_u64() >> rng.below(64)).collect();
+        round_trip(&u);
+        let i: Vec<i64> = u.iter().map(|&x| x as i64).collect();
+        round_trip(&i);
+        let i: Vec<i8> = u.iter().map(|&x| x as i8).collect();
+        round_trip(&i);
+        let i: Vec<u16> = u.iter().map(|&x| x as u16).collect();
+        round_trip(&i);
+        let i: Vec<i32> = u.iter().map(|&x| x as i32).collect();
+        round_trip(&i);
+    }
+    assert_eq!(read::<u32>(&stream::<u32>(&[])).unwrap(), vec![]);
+}
+
+#[test]
+fn sorted() {
+    let mut rng = Rng::new(1790);
+    let mut event = 1_000_000_u64;
+    let events: Vec<u64> = (0..1000).map(|_| {event += rng.below(100); event}).collect();
+    let len = round_trip(&events);
+    // The deltas take one byte each, instead of eight.
+    let mut plain = vec![];
+    Scalar::write_array(EVENT, &events, &mut plain).unwrap();
+    assert!(len * 5 < plain.len(), "{} {}", len, plain.len());
+
+    // Decreasing sequences have negative deltas.
+    let down: Vec<i32> = (0..500).rev().map(|x| x * 3 - 700).collect();
+    round_trip(&down);
+}
+
+#[test]
+fn alternating_extremes() {
+    let items: Vec<i64> = (0..64).map(|i| if i % 3 == 0 {i64::MIN} else {i64::MAX}).collect();
+    round_trip(&items);
+    let items: Vec<u64> = (0..64).map(|i| if i % 2 == 0 {0} else {u64::MAX}).collect();
+    round_trip(&items);
+    let items: Vec<i8> = (0..64).map(|i| if i % 2 == 0 {i8::MIN} else {i8::MAX}).collect();
+    round_trip(&items);
+    // Deltas wrap around instead of overflowing, so the largest steps take 10 bytes.
+    let items = [0_u64, u64::MAX / 2 + 1, 0];
+    let block = block(&stream(&items));
+    assert!(block.data.len() <= 2 + 8 + 8 + 2 * 10);
+}
+
+#[test]
+fn conversion() {
+    // Items are converted to the integer type of the array.
+    let data = stream(&[-3_i64, 100, 7]);
+    assert_eq!(read::<i16>(&data).unwrap(), vec![-3, 100, 7]);
+    assert_eq!(read::<i8>(&data).unwrap(), vec![-3, 100, 7]);
+    let data = stream(&[1_u64, 2, 300]);
+    let err = Error::from_io(&read::<u8>(&data).unwrap_err());
+    assert_eq!((err.kind(), err.property(), err.instance()),
+               (ErrorKind::InvalidData, Some(EVENT), Some(2)));
+    let err = Error::from_io(&read::<u64>(&stream(&[5_i8, -1])).unwrap_err());
+    assert_eq!(err.instance(), Some(1));
+
+    // Plain integer arrays are accepted.
+    let mut data = vec![];
+    Scalar::write_array(EVENT, &vec![4_u16, 5], &mut data).unwrap();
+    assert_eq!(read::<u16>(&data).unwrap(), vec![4, 5]);
+    let mut data = vec![];
+    Scalar::write_array(EVENT, &vec![4.0_f32], &mut data).unwrap();
+    assert_eq!(Error::from_io(&read::<u32>(&data).unwrap_err()).kind(), ErrorKind::TypeMismatch);
+}
+
+#[test]
+fn expand_and_count() {
+    let items = [10_i32, 11, 9, 9, 20];
+    let block = block(&stream(&items));
+    // The number of items comes after the type format.
+    assert_eq!(&block.data[..2], &Type::I32.scalar().0.to_le_bytes());
+    assert_eq!(&block.data[2..10], &5_u64.to_le_bytes());
+    let plain = expand_varint_delta(&block, None).unwrap();
+    assert_eq!(plain.type_format, Type::I32.scalar().0);
+    assert_eq!(plain.decode::<i32>().unwrap(), items);
+    let err = expand_varint_delta(&block, Some(19)).unwrap_err();
+    assert_eq!(Error::from_io(&err).kind(), ErrorKind::LimitExceeded);
+    assert!(expand_varint_delta(&block, Some(20)).is_ok());
+
+    // A count that the data can not hold.
+    for count in &[6_u64, 4, u64::MAX] {
+        let mut bad = block.clone();
+        bad.data[2..10].copy_from_slice(&count.to_le_bytes());
+        let err = expand_varint_delta(&bad, None).unwrap_err();
+        assert_eq!(Error::from_io(&err).kind(), ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn skip_and_copy() {
+    let items: Vec<u64> = (0..100).map(|i| i * i).collect();
+    let data = stream(&items);
+
+    // Readers that do not know the property skip it.
+    let mut r = PoolReader::new(&data[..]).unknown(Unknown::Skip);
+    r.register(MASS);
+    let (state, ty, prop) = r.next_property().unwrap().unwrap();
+    assert_eq!(prop, MASS);
+    let mut masses: Vec<f64> = vec![];
+    Scalar::read_array(state, ty, &mut masses, &mut r).unwrap();
+    assert_eq!(masses, vec![1.5, 2.5]);
+
+    // Copies keep the block.
+    let mut r = &data[..];
+    let mut copy = vec![];
+    while let Some((state, ty, prop)) = raw::read_property_header(&mut r).unwrap() {
+        copy_property(state, ty, prop, &mut r, &mut copy).unwrap();
+    }
+    assert_eq!(read::<u64>(&copy).unwrap(), items);
+    let mut canonical = vec![];
+    canonicalize(&mut &data[..], &mut canonical, None).unwrap();
+    assert_eq!(read::<u64>(&canonical).unwrap(), items);
+}
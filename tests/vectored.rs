@@ -0,0 +1,162 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+use binpool::*;
+use std::io::{self, IoSlice, Write};
+
+const POSITION: u16 = 4;
+
+/// Counts the calls of a socket-like sink, which accepts at most `max` bytes per call.
+struct Counting {
+    data: Vec<u8>,
+    writes: usize,
+    vectored: usize,
+    max: usize,
+}
+
+impl Counting {
+    fn new(max: usize) -> Counting {
+        Counting {data: vec![], writes: 0, vectored: 0, max}
+    }
+}
+
+impl Write for Counting {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        let n = buf.len().min(self.max);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+        self.vectored += 1;
+        let mut n = 0;
+        for buf in bufs {
+            let m = buf.len().min(self.max - n);
+            self.data.extend_from_slice(&buf[..m]);
+            n += m;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {Ok(())}
+}
+
+fn format() -> u16 {Type::F32.vector(3).unwrap().0}
+
+fn items(n: usize) -> Vec<u8> {
+    let mut data = vec![];
+    for i in 0..n {[i as f32; 3].write_element(&mut data).unwrap()}
+    data
+}
+
+/// Writes blocks with the typed state, one field at a time.
+fn with_state(blocks: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    let mut w = vec![];
+    let mut state = State::new()
+        .write_type_format(format(), &mut w).unwrap()
+        .write_property_id(POSITION, &mut w).unwrap();
+    for (offset, data) in blocks {
+        if data.is_empty() {continue}
+        state = state
+            .write_bytes(data.len() as u64, &mut w).unwrap()
+            .write_offset_instance_id(*offset, &mut w).unwrap()
+            .write_data(data, &mut w).unwrap()
+            .end_data();
+    }
+    state.end_bytes(&mut w).unwrap();
+    w
+}
+
+fn raw(blocks: &[(u64, Vec<u8>)]) -> Vec<RawBlock> {
+    blocks.iter().map(|(offset, data)| RawBlock {
+        type_format: format(),
+        property_id: POSITION,
+        offset: *offset,
+        data: data.clone(),
+    }).collect()
+}
+
+#[test]
+fn one_call_per_block() {
+    let mut w = Counting::new(usize::MAX);
+    write_block(format(), POSITION, 7, &items(100), &mut w).unwrap();
+    assert_eq!((w.vectored, w.writes), (1, 0));
+    assert_eq!(w.data, with_state(&[(7, items(100))]));
+
+    let blocks = vec![(0, items(3)), (10, items(1)), (20, vec![]), (30, items(50))];
+    let mut w = Counting::new(usize::MAX);
+    RawBlock::write_property(format(), POSITION, &raw(&blocks), &mut w).unwrap();
+    // Empty blocks are skipped.
+    assert_eq!((w.vectored, w.writes), (3, 0));
+    assert_eq!(w.data, with_state(&blocks));
+
+    // Without blocks, the property header and end of bytes are one call.
+    let mut w = Counting::new(usize::MAX);
+    RawBlock::write_property(format(), POSITION, &[], &mut w).unwrap();
+    assert_eq!((w.vectored, w.writes), (1, 0));
+    assert_eq!(w.data, with_state(&[]));
+    let mut w = Counting::new(usize::MAX);
+    write_block(format(), POSITION, 0, &[], &mut w).unwrap();
+    assert_eq!((w.vectored, w.data), (1, with_state(&[])));
+
+    // A guard writes the property header, one call per block and the end of bytes.
+    let mut w = Counting::new(usize::MAX);
+    let mut block = begin_block(format(), POSITION, &mut w).unwrap();
+    block.write(0, &items(3)).unwrap();
+    block.write(10, &items(1)).unwrap();
+    block.finish().unwrap();
+    assert_eq!(w.vectored, 2);
+    assert_eq!(w.data, with_state(&[(0, items(3)), (10, items(1))]));
+}
+
+#[test]
+fn short_writes() {
+    let blocks = vec![(0, items(3)), (10, items(1)), (30, items(50))];
+    for &max in &[1, 3, 7, 20, 21, 100] {
+        let mut w = Counting::new(max);
+        RawBlock::write_property(format(), POSITION, &raw(&blocks), &mut w).unwrap();
+        // Partial writes are continued until everything is written.
+        assert_eq!(w.data, with_state(&blocks), "{}", max);
+        assert!(w.vectored >= 3);
+        let mut w = Counting::new(max);
+        write_block(format(), POSITION, 3, &items(5), &mut w).unwrap();
+        assert_eq!(w.data, with_state(&[(3, items(5))]));
+    }
+}
+
+#[test]
+fn writers_without_vectored_writes() {
+    /// Uses the default `write_vectored`, which writes the first buffer.
+    struct Plain(Vec<u8>, usize);
+    impl Write for Plain {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1 += 1;
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {Ok(())}
+    }
+
+    let mut w = Plain(vec![], 0);
+    write_block(format(), POSITION, 7, &items(10), &mut w).unwrap();
+    assert_eq!(w.0, with_state(&[(7, items(10))]));
+    // One call per part: header, data and end of bytes.
+    assert_eq!(w.1, 3);
+
+    // Interrupted writes are retried.
+    struct Interrupted(Vec<u8>, bool);
+    impl Write for Interrupted {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1 = !self.1;
+            if self.1 {return Err(io::ErrorKind::Interrupted.into())}
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {Ok(())}
+    }
+    let mut w = Interrupted(vec![], false);
+    write_block(format(), POSITION, 7, &items(10), &mut w).unwrap();
+    assert_eq!(w.0, with_state(&[(7, items(10))]));
+}
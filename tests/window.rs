@@ -0,0 +1,164 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+
+const TIME: u16 = 0;
+const POSITION: u16 = 1;
+const MASS: u16 = 2;
+
+/// Writes 50 frames, where positions are written as changes after the first frame.
+///
+/// The mass is only written in the first frame.
+fn stream(endian: Endian) -> Vec<u8> {
+    let mut rng = Rng::new(145);
+    let mut w = FrameWriter::new(PoolWriter::new(vec![]).with_endianness(endian), TIME);
+    w.write_time_unit(&TimeUnit::Steps).unwrap();
+    w.begin_frame(0.0).unwrap();
+    Vector::write_array(POSITION, &vec![[0.0_f32; 2]; 100], &mut w).unwrap();
+    Scalar::write_array(MASS, &vec![2.5_f32; 100], &mut w).unwrap();
+    for frame in 1..50 {
+        w.begin_frame(frame as f64).unwrap();
+        let offset = rng.below(100);
+        let n = 1 + rng.below(100 - offset) as usize;
+        let pos: Vec<[f32; 2]> = (0..n).map(|_| [frame as f32, rng.unit() as f32]).collect();
+        let mut data = vec![];
+        for p in &pos {
+            p.write_element(&mut data).unwrap();
+        }
+        let ty = Type::F32.vector(2).unwrap().0;
+        RawBlock::write_property(ty, POSITION, &[
+            RawBlock {type_format: ty, property_id: POSITION, offset, data}
+        ], &mut w).unwrap();
+    }
+    w.finish().unwrap().get_ref().clone()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Arrays {
+    position: Vec<[f32; 2]>,
+    mass: Vec<f32>,
+}
+
+impl Arrays {
+    fn apply(&mut self, frame: &Frame) {
+        frame.read_array(POSITION, &mut self.position).unwrap();
+        frame.read_array(MASS, &mut self.mass).unwrap();
+    }
+}
+
+/// Replays a stream, returning the time and state after each frame.
+fn replay(data: &[u8]) -> (Vec<(Option<f64>, Arrays)>, Option<TimeUnit>) {
+    let mut r = FrameReader::with_time(PoolReader::new(data), TIME);
+    let mut arrays = Arrays::default();
+    let mut frames = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        if frame.number == 0 {
+            arrays.apply(&Frame {number: 0, time: None, blocks: r.preamble().to_vec()});
+        }
+        arrays.apply(&frame);
+        frames.push((frame.time, arrays.clone()));
+    }
+    (frames, r.time_unit().cloned())
+}
+
+fn extract(data: &[u8], t0: f64, t1: f64, keyframe: bool) -> Vec<u8> {
+    let mut out = vec![];
+    let opts = WindowOptions {keyframe};
+    extract_window(&mut std::io::Cursor::new(data), &mut out, TIME, t0, t1, &opts).unwrap();
+    out
+}
+
+#[test]
+fn middle_window() {
+    for &endian in &[Endian::Little, Endian::Big] {
+        let data = stream(endian);
+        let (original, unit) = replay(&data);
+        assert_eq!(original.len(), 50);
+
+        let out = extract(&data, 20.0, 30.0, true);
+        let (window, window_unit) = replay(&out);
+        assert_eq!(window_unit, unit, "{:?}", endian);
+        assert_eq!(window.len(), 11);
+        for (frame, expected) in window.iter().zip(&original[20..31]) {
+            assert_eq!(frame, expected);
+        }
+
+        // Without a keyframe, the frames are copied, but the state before them is missing.
+        let out = extract(&data, 20.0, 30.0, false);
+        let (window, _) = replay(&out);
+        let times: Vec<_> = window.iter().map(|&(t, _)| t.unwrap()).collect();
+        assert_eq!(times, (20..31).map(|t| t as f64).collect::<Vec<_>>());
+        assert!(window[0].1.mass.is_empty());
+        assert_ne!(window[0].1, original[20].1);
+    }
+}
+
+#[test]
+fn copied_verbatim() {
+    let data = stream(Endian::Big);
+    let out = extract(&data, 10.0, 12.5, false);
+    let mut a = FrameReader::with_time(PoolReader::new(&data[..]), TIME);
+    let mut b = FrameReader::with_time(PoolReader::new(&out[..]), TIME);
+    for _ in 0..10 {a.next_frame().unwrap();}
+    for _ in 0..3 {
+        assert_eq!(a.next_frame().unwrap().unwrap().blocks,
+                   b.next_frame().unwrap().unwrap().blocks);
+    }
+    assert!(b.next_frame().unwrap().is_none());
+}
+
+#[test]
+fn empty_and_invalid_windows() {
+    let data = stream(Endian::Little);
+    let (window, _) = replay(&extract(&data, 60.0, 70.0, false));
+    assert!(window.is_empty());
+    // A keyframe of the whole stream is written, with no frames after it.
+    let out = extract(&data, 60.0, 70.0, true);
+    let r = &mut FrameReader::with_time(&out[..], TIME);
+    assert!(r.next_frame().unwrap().is_none());
+    assert!(!r.preamble().is_empty());
+
+    let mut out = vec![];
+    let opts = WindowOptions::default();
+    for &(t0, t1) in &[(2.0, 1.0), (f64::NAN, 1.0), (0.0, f64::NAN)] {
+        let err = extract_window(&mut std::io::Cursor::new(&data), &mut out, TIME, t0, t1, &opts)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
+
+#[test]
+fn frames_without_time_and_non_monotonic_time() {
+    let mut w = FrameWriter::new(vec![], TIME).require_monotonic_time(false);
+    let frame = |w: &mut FrameWriter<Vec<u8>>, t: Option<f64>, id: u8| {
+        match t {
+            Some(t) => w.begin_frame(t).unwrap(),
+            // A frame marker without data.
+            None => RawBlock::write_property(Type::F64.scalar().0, TIME, &[], w).unwrap(),
+        }
+        Scalar::write_array(MASS, &vec![id], w).unwrap();
+    };
+    frame(&mut w, Some(0.0), 0);
+    frame(&mut w, Some(1.0), 1);
+    frame(&mut w, None, 2);
+    frame(&mut w, Some(5.0), 3);
+    frame(&mut w, None, 4);
+    frame(&mut w, Some(1.5), 5);
+    frame(&mut w, Some(0.5), 6);
+    frame(&mut w, None, 7);
+    let data = w.finish().unwrap();
+
+    let out = extract(&data, 1.0, 2.0, false);
+    let mut r = FrameReader::new(&out[..], TIME);
+    let mut ids = vec![];
+    while let Some(frame) = r.next_frame().unwrap() {
+        ids.push(frame.read_property::<u8>(MASS).unwrap().unwrap());
+    }
+    // The frames without time follow the frame before them.
+    assert_eq!(ids, vec![1, 2, 5]);
+}
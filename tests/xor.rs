@@ -0,0 +1,133 @@
+#![cfg(feature = "std")]
+
+extern crate binpool;
+
+mod common;
+
+use binpool::*;
+use common::Rng;
+use std::io::Cursor;
+
+const TIME: u16 = 0;
+const FIELD: u16 = 1;
+const PROBE: u16 = 2;
+
+const FRAMES: usize = 300;
+const CELLS: usize = 64;
+const INTERVAL: u64 = 32;
+
+/// A field on a grid that varies slowly, and a probe position.
+struct Series {
+    field: Vec<Vec<f64>>,
+    probe: Vec<Vec<[f32; 3]>>,
+}
+
+fn series() -> Series {
+    let mut rng = Rng::new(180);
+    let mut field: Vec<f64> = (0..CELLS).map(|i| 280.0 + i as f64 * 0.1).collect();
+    let mut probe = vec![[1.0_f32, 2.0, 3.0]; 2];
+    let mut res = Series {field: vec![], probe: vec![]};
+    for i in 0..FRAMES {
+        for x in &mut field {
+            if rng.below(4) == 0 {*x += (rng.unit() - 0.5) * 1e-9}
+        }
+        if i % 10 == 3 {field[5] = f64::from_bits(0x7ff8_0000_dead_beef)}
+        if i % 10 == 4 {field[5] = -0.0}
+        for p in &mut probe {p[0] += 0.001}
+        // The number of probes changes.
+        if i == 150 {probe.push([0.0; 3])}
+        res.field.push(field.clone());
+        res.probe.push(probe.clone());
+    }
+    res
+}
+
+fn write(s: &Series, interval: u64) -> Vec<u8> {
+    let mut w = FrameWriter::new(vec![], TIME).keyframe_interval(interval);
+    for i in 0..FRAMES {
+        w.begin_frame(i as f64).unwrap();
+        w.write_xor_delta(FIELD, &s.field[i]).unwrap();
+        w.write_xor_delta(PROBE, &s.probe[i]).unwrap();
+    }
+    w.finish().unwrap()
+}
+
+fn bits(field: &[f64]) -> Vec<u64> {
+    field.iter().map(|x| x.to_bits()).collect()
+}
+
+/// Reads frames and checks them against the series, starting at a frame.
+fn check<R: std::io::Read>(r: &mut FrameReader<R>, s: &Series, start: usize) {
+    let mut n = start;
+    while let Some(frame) = r.next_frame().unwrap() {
+        let mut field: Vec<f64> = vec![];
+        frame.read_array(FIELD, &mut field).unwrap();
+        assert_eq!(bits(&field), bits(&s.field[n]), "frame {}", n);
+        let mut probe: Vec<[f32; 3]> = vec![];
+        frame.read_array(PROBE, &mut probe).unwrap();
+        assert_eq!(probe, s.probe[n]);
+        n += 1;
+    }
+    assert_eq!(n, FRAMES);
+}
+
+/// Returns the number of delta blocks in a stream.
+fn deltas(data: &[u8]) -> usize {
+    let mut r = PoolReader::new(data).unknown(Unknown::Collect).reserved_as_data(true);
+    while r.next_property().unwrap().is_some() {}
+    r.unknown_blocks().iter().filter(|b| b.type_format == XOR_FORMAT).count()
+}
+
+#[test]
+fn bit_exact() {
+    let s = series();
+    let data = write(&s, INTERVAL);
+    check(&mut FrameReader::with_time(&data[..], TIME), &s, 0);
+
+    // Every frame is a delta, except keyframes and the frame where the number of probes changes.
+    let keyframes = FRAMES.div_ceil(INTERVAL as usize);
+    assert_eq!(deltas(&data), 2 * (FRAMES - keyframes) - 1);
+
+    let mut plain = FrameWriter::new(vec![], TIME);
+    for i in 0..FRAMES {
+        plain.begin_frame(i as f64).unwrap();
+        Scalar::write_array(FIELD, &s.field[i], &mut plain).unwrap();
+        Vector::write_array(PROBE, &s.probe[i], &mut plain).unwrap();
+    }
+    let plain = plain.finish().unwrap();
+    assert!(data.len() * 3 < plain.len(), "{} {}", data.len(), plain.len());
+
+    // Without keyframes after the first frame.
+    let data = write(&s, u64::MAX);
+    check(&mut FrameReader::with_time(&data[..], TIME), &s, 0);
+    assert_eq!(deltas(&data), 2 * (FRAMES - 1) - 1);
+}
+
+#[test]
+fn seek_to_keyframes() {
+    let s = series();
+    let data = write(&s, INTERVAL);
+    let index = Index::build(&mut Cursor::new(&data), TIME, Some(TIME)).unwrap();
+    for &n in &[0, INTERVAL, 5 * INTERVAL, 9 * INTERVAL] {
+        let mut r = Cursor::new(&data);
+        let cursor = index.seek_frame(n, &mut r).unwrap();
+        let mut fr = FrameReader::with_time(r, TIME);
+        fr.resume(&cursor).unwrap();
+        check(&mut fr, &s, n as usize);
+    }
+
+    // Deltas can not be read without the previous frames.
+    let mut r = Cursor::new(&data);
+    let cursor = index.seek_frame(INTERVAL + 1, &mut r).unwrap();
+    let mut fr = FrameReader::with_time(r, TIME);
+    fr.resume(&cursor).unwrap();
+    assert!(fr.next_frame().is_err());
+
+    // A reader that read earlier frames forgets them when seeking.
+    let mut r = Cursor::new(&data);
+    let mut fr = FrameReader::with_time(&mut r, TIME);
+    for _ in 0..INTERVAL + 1 {fr.next_frame().unwrap().unwrap();}
+    let cursor = index.seek_frame(INTERVAL + 2, fr.get_mut()).unwrap();
+    fr.resume(&cursor).unwrap();
+    assert!(fr.next_frame().is_err());
+}